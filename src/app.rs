@@ -1,23 +1,105 @@
+use std::cell::Cell;
+use std::collections::HashMap;
 use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use cgmath::*;
+use sdl2::event::{Event, WindowEvent};
 use sdl2::pixels::Color;
+use sdl2::ttf::Font;
 use sdl2::render::{self, TextureCreator};
-use sdl2::video::{DisplayMode, WindowContext};
+use sdl2::video::{DisplayMode, FullscreenType, WindowContext, WindowPos};
 use sdl2::{video::Window, Sdl, render::Canvas};
 use wgpu::util::DeviceExt;
-use wgpu::{BindGroupLayoutDescriptor, DepthBiasState, Device, DeviceDescriptor, Features, InstanceDescriptor, Limits, Queue, RenderPassDepthStencilAttachment, StencilState, Surface, SurfaceConfiguration, TextureUsages};
+use wgpu::{DepthBiasState, Device, DeviceDescriptor, Features, InstanceDescriptor, Limits, Queue, RenderPassDepthStencilAttachment, StencilState, Surface, SurfaceConfiguration, TextureUsages};
+use crate::debug::frame_graph::{self, FrameGraph, PassKind};
+use crate::debug::gpu_timer::GpuTimer;
+use crate::debug::overlay::{DebugOverlay, OverlayStats};
+use crate::debug::profiler::{profile_scope, Profiler};
 use crate::game_object::GameObject;
+use crate::gameplay::collision::CollisionWorld;
+use crate::gameplay::picking::{self, PickHit, PickVolume, Picked};
 use crate::gameplay::play;
 use crate::input::button_module::{Button, TextAlign};
-use crate::rendering::camera::{Camera, CameraRenderizable, CameraUniform};
-use crate::rendering::model::{self, DrawModel, Model, Vertex};
-use crate::rendering::textures::Texture;
+use crate::rendering::instance_animation::InstanceAnimator;
+use crate::rendering::post_process::PostProcess;
+use crate::rendering::debug_view::{DebugView, DebugViewRenderer, DebugViewSources};
+use crate::rendering::lights::{Light, LightingPath, Lights};
+use crate::input::input_state::InputState;
+use crate::ui::accessibility::{SpeechHook, SystemSpeech, UiAccessibility};
+use crate::ui::captions::Captions;
+use crate::ui::world_labels::WorldLabels;
+use crate::ui::chat::ChatBox;
+use crate::audio::mixer::{Audio, AudioBus, SoundHandle};
+#[cfg(feature = "voice")]
+use crate::audio::voice::{Listener, VoiceChat};
+use crate::ui::scale::{UiSettings, UI_SETTINGS_PATH};
+use crate::ui::script_screen::ScriptScreens;
+use crate::gameplay::abilities::AbilityLibrary;
+use crate::gameplay::dialogue::DialogueLibrary;
+use crate::gameplay::inventory::ItemLibrary;
+use crate::util::localization::{Localization, FALLBACK_LANGUAGE};
+use crate::util::settings::{EngineSettings, SETTINGS_PATH};
+use crate::ui::text::TextRenderer;
+use crate::ui::ui_renderer::UiRenderer;
+use crate::util::file_watcher::FileWatcher;
+use crate::util::frame_limiter::FrameLimiter;
+use crate::util::noise::Noise;
+use crate::util::rng::{Rng, RngStreams};
+use crate::util::timestep::FixedTimestep;
+use crate::util::cvars::{CvarFlags, CvarRegistry, CvarValue, CVARS_PATH};
+use crate::util::{content_hash, vfs};
+use crate::util::monitors::{self, FullscreenMode, MonitorInfo};
+use crate::rendering::display_output::{pick_present_mode, pick_surface_format, DisplaySettings, OutputMode, DISPLAY_SETTINGS_PATH};
+use crate::rendering::post_pass::PostSlot;
+use crate::rendering::render_passes::{FramePasses, PassSlot, PassTargets, RenderPass, RenderPasses};
+use crate::rendering::parallax::ParallaxBackground;
+use crate::rendering::sprite::SpriteRenderer;
+use crate::rendering::sky::Sky;
+use crate::rendering::skybox::Skybox;
+use crate::rendering::shadow_map::ShadowMap;
+use crate::rendering::volumetric_fog::{FogQuality, VolumetricFog};
+use crate::rendering::planar_reflection::PlanarReflections;
+use crate::rendering::portal::{PortalView, Portals};
+use crate::rendering::trails::TrailRenderer;
+use crate::rendering::thumbnail::ThumbnailRenderer;
+use crate::rendering::blob_shadows::{BlobShadows, ShadowQuality};
+use crate::rendering::particles::ParticleSystem;
+use crate::rendering::clip_capture::{ClipCapture, ClipFormat, ClipSettings};
+use crate::editor::instance_brush::{BrushEdit, InstanceBrush, PaintSurface};
+use crate::rendering::camera::{Camera, CameraRenderizable, IsometricAngle, Projection, Ray, DEFAULT_FOVY, DEFAULT_ZFAR, DEFAULT_ZNEAR, MAX_FOVY, MIN_FOVY};
+use crate::rendering::model::{self, InstanceRaw, InstancedDraw, VariationRange, Vertex};
+use crate::rendering::batching::{self, BatchStats};
+use crate::rendering::scene_renderer::SceneRenderer;
+use crate::rendering::instance_manager::InstanceManager;
+use crate::rendering::textures::{Texture, TextureHandle, TextureManager};
+use crate::rendering::frames_in_flight::{FramePacer, DEFAULT_FRAMES_IN_FLIGHT, MAX_FRAMES_IN_FLIGHT};
+use crate::rendering::upload_queue::{UploadQueue, DEFAULT_BUDGET};
 use crate::resources;
+use crate::assets::{AssetManager, LoadedAsset};
+use crate::scene::graph::{MaterialId, ModelId, SceneGraph, Transform};
+use crate::scene::level2d::Level2DData;
+use crate::scene::manager::{SceneContext, SceneManager, Transition};
+use crate::scene::persistent::PersistentObjects;
+use crate::util::color::Color as LinearColor;
+use crate::rendering::external_texture::{ExternalTexture, ProceduralSource};
+use crate::rendering::material_reflection::{ParamValue, ShaderMaterial, ShaderReflection};
+use crate::rendering::shader_manager::{ReloadableBuilder, ShaderManager};
+use crate::rendering::shader_preprocessor::ShaderLibrary;
+use crate::rendering::shader_variants::{draw_features, ShaderFeatures, ShaderVariants};
 
 // instances: these values are just for generating the elements
 const NUM_INSTANCES_PER_ROW: u32 = 10;
+const DEFAULT_SIMULATION_RATE: f32 = 60.0; // fixed steps per second
+const UI_FONT_SIZE: u16 = 20; // in design pixels, see ui::scale
+const THUMBNAIL_SIZE: u32 = 128;
+const ISOMETRIC_VIEW_HEIGHT: f32 = 20.0; // world units that fit vertically in the isometric projections
+const SCREEN_STATIC_SEED: u32 = 0x5747;
+const MUSIC_FADE_MS: i32 = 1000; // the music cvar fades the tracks in and out
+const POST_FOLDER: &str = "post"; // post/before_effects and post/after_effects of the assets, a pass for each .wgsl
+const SHADERS_FOLDER: &str = "shaders"; // in the assets, the files have the names of the library (common/camera.wgsl)
 const INSTANCE_DISPLACEMENT: cgmath::Vector3<f32> = cgmath::Vector3::new(NUM_INSTANCES_PER_ROW as f32 * 0.5, 0.0, NUM_INSTANCES_PER_ROW as f32 * 0.5);
 // instances 
 
@@ -73,68 +155,69 @@ impl Vertex {
 }
 */
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum GameState {
     Playing,
+    Paused, // the world is frozen (physics, animations, timers) but we keep rendering and the ui keeps working
+    Calibrating, // the brightness test pattern covers the screen, the keys move the values of the display
+    Browsing, // the list of the games on the local network over the game, the world keeps going
 }
 
 pub struct AppState {
     pub is_running: bool,
     pub state: GameState,
+    previous_states: Vec<GameState>, // the states under the current one, so a pause menu can go back to where we were
 }
 
-// Instancing
-struct Instance {
-    position: cgmath::Vector3<f32>,
-    rotation: cgmath::Quaternion<f32>,
-}
+impl AppState {
+    pub fn new(state: GameState) -> Self {
+        Self { is_running: true, state, previous_states: Vec::new() }
+    }
 
+    // puts a state on top of the current one (the pause menu over the game)
+    pub fn push_state(&mut self, state: GameState) {
+        self.previous_states.push(self.state);
+        self.state = state;
+    }
 
-// quaternions are not very usable in wgpu so instead of doing math in the shader we are gonna save the raw instance here directly
-#[repr(C)]
-#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-struct InstanceRaw {
-    model: [[f32; 4]; 4],
-}
+    // goes back to the state under the current one, false if there was nothing under it
+    pub fn pop_state(&mut self) -> bool {
+        match self.previous_states.pop() {
+            Some(state) => {
+                self.state = state;
+                true
+            }
+            None => false,
+        }
+    }
 
-impl InstanceRaw {
-    // we create a vertexBuffer
-    fn desc() -> wgpu::VertexBufferLayout<'static> {
-        use std::mem;
-        wgpu::VertexBufferLayout {
-            array_stride: mem::size_of::<InstanceRaw>() as wgpu::BufferAddress, // the vertexbuffer is of type instance raw it means that our shader will only change to use the next instance
-            step_mode: wgpu::VertexStepMode::Instance,
-            attributes: &[
-                // A mat 4 is basically a vec4 of vec4's so we have to define every vec4 of it
-                wgpu::VertexAttribute {
-                    offset: 0,
-                    shader_location: 5,
-                    format: wgpu::VertexFormat::Float32x4,
-                },
-                wgpu::VertexAttribute {
-                    offset: mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
-                    shader_location: 6,
-                    format: wgpu::VertexFormat::Float32x4,
-                },
-                wgpu::VertexAttribute {
-                    offset: mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
-                    shader_location: 7,
-                    format: wgpu::VertexFormat::Float32x4,
-                },
-                wgpu::VertexAttribute {
-                    offset: mem::size_of::<[f32; 12]>() as wgpu::BufferAddress,
-                    shader_location: 8,
-                    format: wgpu::VertexFormat::Float32x4,
-                },
-            ],
+    // true if any state of the stack is a pause, a settings menu opened from the pause menu still pauses the world
+    // the calibration screen covers the game so it pauses it too
+    pub fn is_paused(&self) -> bool {
+        matches!(self.state, GameState::Paused | GameState::Calibrating) || self.previous_states.contains(&GameState::Paused)
+    }
+
+    pub fn toggle_pause(&mut self) {
+        if self.state == GameState::Paused {
+            self.pop_state();
+        } else {
+            self.push_state(GameState::Paused);
         }
     }
 }
 
+// Instancing
+struct Instance {
+    position: cgmath::Vector3<f32>,
+    rotation: cgmath::Quaternion<f32>,
+    scale: f32, // uniform, so the gpu rotation of the animated instances still works on it
+}
+
 impl Instance {
+    // quaternions are not very usable in wgpu so instead of doing math in the shader we save the matrix directly
     fn to_raw(&self) -> InstanceRaw {
-        InstanceRaw {
-            model: (cgmath::Matrix4::from_translation(self.position) * cgmath::Matrix4::from(self.rotation)).into(),
-        }
+        let model = cgmath::Matrix4::from_translation(self.position) * cgmath::Matrix4::from(self.rotation) * cgmath::Matrix4::from_scale(self.scale);
+        InstanceRaw::new(model.into(), 0)
     }
     
 }
@@ -146,43 +229,121 @@ pub struct App {
     pub width: u32,
     pub height: u32,
     pub canvas: Canvas<Window>,
-    pub current_display: DisplayMode,
+    pub current_display: DisplayMode, // the mode of the monitor the window is on
+    pub monitor: i32,
+    pub frame_limiter: FrameLimiter,
+    limit_to_refresh_rate: bool, // the limiter follows the monitor until the game sets its own limit
+    windowed_size: Option<(u32, u32)>, // the size before spanning the monitors, some while it spans them
+    fullscreen: FullscreenMode,
     pub texture_creator: TextureCreator<WindowContext>,
     pub surface: Surface,
-    pub queue: Queue,
-    pub device: Device,
+    pub queue: Arc<Queue>, // shared with the tasks of the asset manager, they make their resources on other threads
+    pub device: Arc<Device>,
     pub config: SurfaceConfiguration,
-    pub render_pipeline: wgpu::RenderPipeline,
+    pub scene_variants: ShaderVariants, // the pipelines themselves are in shaders, they change when depth_map.wgsl does
+    pub clustered_variants: ShaderVariants, // the same shader with the clustered lights (r_lighting)
+    pub shaders: ShaderManager,
     pub index_buffer: wgpu::Buffer,
-    pub diffuse_bind_group: wgpu::BindGroup,
-    pub diffuse_texture: Texture,
+    pub textures: TextureManager,
+    pub diffuse_texture: TextureHandle,
+    texture_bind_group_layout: Arc<wgpu::BindGroupLayout>,
+    // the textures and models are loaded again when their files change, so the artists see them in the running game
+    asset_watcher: FileWatcher,
+    watched_models: HashMap<PathBuf, (PathBuf, ModelId)>, // a file of a model (the mtl too) to the model path and id
+    pub assets: AssetManager, // the textures, models and shaders loading on background tasks
     pub camera: CameraRenderizable,
-    instances: Vec<Instance>,
-    instance_buffer: wgpu::Buffer,
+    pub world: SceneGraph, // the entities of the 3D scene, the renderer draws every one that has a model
+    scene_renderer: SceneRenderer,
+    default_model: ModelId, // the model the static batch and the brush use
+    pub static_instances: InstanceManager, // copies of the default model outside of the graph, only what changes is uploaded again
+    pub collision: CollisionWorld, // the colliders follow their entities and instances once a frame
     depth_texture: Texture,
-    obj_model: Model
+    gpu_timer: Option<GpuTimer>, // only exists when the adapter supports timestamp queries
+    pub instance_animator: InstanceAnimator, // spins and bobs the instances of the graph on the gpu, enabled toggles it
+    draw_stats: Cell<BatchStats>, // the mesh draws and material binds of the last main pass, for the overlay
+    // the delta time for everything that simulates the world (physics, particles, animation, timers), 0 while paused
+    // rendering, ui and screen effects keep using the real delta
+    pub simulation_delta: f32,
+    // the gameplay that has to run at the same rate on every machine (GameLogic::fixed_update) runs in these steps
+    // and the scene graph is drawn between the last two of them, change the rate with set_simulation_rate
+    pub timestep: FixedTimestep,
+    pub clear_color: LinearColor,
+    pub sky: Sky, // drawn behind the 3D scene and lighting it, set sky.visible to false to see the clear color instead
+    pub skybox: Skybox, // a cubemap behind the scene instead of the analytic sky, set one with load_skybox
+    pub lights: Lights, // the point and directional lights of the game, on top of the sun of the sky
+    pub shadow_map: ShadowMap, // the sun seen from above, the fog uses it for the light shafts
+    pub fog: VolumetricFog,
+    pub mirrors: PlanarReflections, // each mirror renders the scene again, keep them few
+    pub trails: TrailRenderer, // the ribbons behind projectiles and blades
+    pub blob_shadows: BlobShadows, // the spots under the casters when there is no shadow map
+    pub particles: ParticleSystem, // sparks, smoke and dust, simulated on the gpu
+    pub passes: RenderPasses, // the passes of the game, at the slots between the engine ones
+    pub portals: Portals, // each portal renders the scene once per level, even more than the mirrors
+    pub post_process: PostProcess,
+    pub clips: ClipCapture, // the last seconds of the game in memory, trigger writes them as a clip
+    debug_view: DebugViewRenderer, // takes the place of the post process while a debug view is picked
+    pub display: DisplaySettings, // change it with set_display_settings so the final pass gets it
+    pub settings: EngineSettings, // the window, vsync and controls of the player, change them with apply_settings
+    pub output_mode: OutputMode, // what the surface ended up being, hdr needs the monitor and the driver to offer it
+    present_modes: Vec<wgpu::PresentMode>, // the ones the surface supports, for set_present_mode
+    frames: FramePacer, // how far the cpu can get ahead of the gpu, the per frame rings follow its slot
+    pub sprites: SpriteRenderer, // the 2D mode, it draws on top of the 3D scene when there are sprites queued
+    pub parallax: ParallaxBackground, // background layers of the 2D mode, queued before the game sprites every frame
+    pub scenes: SceneManager,
+    pub persistent: PersistentObjects, // the objects that survive scene switches, gameplay can reach them from here too
+    pub frame_graph: FrameGraph,
+    pub overlay: DebugOverlay, // F3, the frame times, the camera and the gpu
+    pub input: InputState, // the keys and mouse of this frame and the actions bound to them
+    pub ui: UiRenderer, // the flat shapes of the ui (button backgrounds, panels), drawn before the text
+    pub text: TextRenderer, // the ui text, drawn on the surface after the post process
+    pub accessibility: UiAccessibility, // the focus changes and announcements for screen readers
+    pub captions: Captions, // what the sounds say, shown when the captions are on in the ui settings
+    pub labels: WorldLabels, // the nameplates and damage numbers over the scene
+    pub chat: ChatBox,
+    pub ui_screens: ScriptScreens, // the menus and huds of the ui scripts, the mods can bring their own
+    pub abilities: AbilityLibrary, // what every caster can cast, from the ron files of the assets
+    pub items: ItemLibrary,
+    pub dialogues: DialogueLibrary,
+    pub strings: Localization, // the text of the current language, by key
+    pub audio: Audio, // the effects and the music, paused with the game
+    #[cfg(feature = "voice")]
+    pub voice: Option<VoiceChat>, // while the voice cvar is on and the devices opened
+    pub rng: RngStreams, // the seed cvar starts them again
+    pub uploads: UploadQueue, // big uploads that can take a few frames, so they don't make the game hitch
+    screen_feed: ExternalTexture, // the picture of the demo screen, a new frame every update
+    screen_material: Option<MaterialId>, // none when screen.wgsl didn't make a material
+    pub cvars: CvarRegistry, // the runtime settings by name, what changes there is applied at the start of the update
 }
 
 impl App {
-    pub async fn new(title: &str, ext_width: Option<u32>, ext_height: Option<u32>) -> App{
+    // monitor is the index of the display the window opens on (see monitors()), none is the first one
+    // the size given here wins over the one of the settings, for the tools that open their own window
+    pub async fn new(title: &str, ext_width: Option<u32>, ext_height: Option<u32>, monitor: Option<i32>) -> App{
         // base sdl2
         let context = sdl2::init().expect("SDL2 wasn't initialized");
         let video_susbsystem = context.video().expect("The Video subsystem wasn't initialized");
 
-        let current_display = video_susbsystem.current_display_mode(0).unwrap();
-        
-        let width = match ext_width {
-            Some(w) => w,
-            None => current_display.w as u32,
-        };
-        let height =  match ext_height {
-            Some(h) => h,
-            None => current_display.h as u32,
-        };
+        let monitor = monitors::valid_monitor(&video_susbsystem, monitor.unwrap_or(0));
+        let monitor_info = monitors::monitor_info(&video_susbsystem, monitor).unwrap();
+        // the ui scale of the player or, the first time, the one for the dpi of the monitor
+        UiSettings::set_current(UiSettings::load(UI_SETTINGS_PATH, UiSettings::for_display(&video_susbsystem, monitor)));
+        let current_display = monitor_info.mode;
+        let settings = EngineSettings::load(SETTINGS_PATH);
+
+        // the settings of the player, and the size of the monitor when they don't have one
+        let (settings_width, settings_height) = settings.resolution.unwrap_or((current_display.w as u32, current_display.h as u32));
+        let width = ext_width.unwrap_or(settings_width);
+        let height = ext_height.unwrap_or(settings_height);
 
         env::set_var("SDL_VIDEO_MINIMIZE_ON_FOCUS_LOSS", "0"); // this is highly needed so the sdl2 can alt tab without generating bugs
 
-        let window: Window = video_susbsystem.window(title, width, height as u32).vulkan().build().expect("The window wasn't created");
+        let (window_x, window_y) = monitors::centered_position(&monitor_info, width, height);
+        let mut window_builder = video_susbsystem.window(title, width, height);
+        window_builder.position(window_x, window_y).resizable().vulkan();
+        if settings.fullscreen {
+            window_builder.fullscreen_desktop();
+        }
+        let window: Window = window_builder.build().expect("The window wasn't created");
         
         // WGPU INSTANCES AND SURFACE
         let instance = wgpu::Instance::new(InstanceDescriptor::default());
@@ -196,23 +357,33 @@ impl App {
 
         println!("{}", adapter.get_info().name);
 
+        // timestamp queries are optional, we only ask for them if the adapter has them so the profiler can show gpu times
+        // and the same for the lines of the wireframe debug view
+        let optional_features = adapter.features() & (Features::TIMESTAMP_QUERY | Features::POLYGON_MODE_LINE);
+
         let (device, queue) = adapter.request_device(
             &DeviceDescriptor { 
                 label: None, 
-                features: Features::empty(), 
-                limits: Limits::default() }
+                features: optional_features, 
+                // the biggest surface the adapter can do, a window spanning a few monitors goes over the default 8192
+                limits: Limits { max_texture_dimension_2d: adapter.limits().max_texture_dimension_2d, ..Limits::default() } }
             , None).await.unwrap();
+        let (device, queue) = (Arc::new(device), Arc::new(queue));
 
         // Surface settings
         let surface_caps = surface.get_capabilities(&adapter);
-        let surface_format = surface_caps.formats;
+        // hdr when the monitor path has a float format and the player didn't turn it off, if not the usual sdr one
+        let display = DisplaySettings::load(DISPLAY_SETTINGS_PATH);
+        let (surface_format, output_mode) = pick_surface_format(&surface_caps.formats, display.hdr);
 
+        // the real pixels of the window, more than its size on high dpi screens
+        let (width, height) = window.vulkan_drawable_size();
         let config = wgpu::SurfaceConfiguration {
             usage: TextureUsages::RENDER_ATTACHMENT,
-            format: surface_format[0],
+            format: surface_format,
             width,
             height,
-            present_mode: wgpu::PresentMode::AutoNoVsync,
+            present_mode: pick_present_mode(&surface_caps.present_modes, settings.present_mode(display.present_mode)),
             alpha_mode: surface_caps.alpha_modes[0],
             view_formats: vec![],
         };
@@ -224,88 +395,90 @@ impl App {
         let depth_texture = Texture::create_depth_texture_non_comparison_sampler(&device, &config, "depth_texture");
         // depth
 
+        // the scene is drawn offscreen and then copied to the surface with the screen effects
+        let mut post_process = PostProcess::new(&device, &config);
+        let clips = ClipCapture::new(&device, &config, ClipSettings::default());
+        post_process.set_output(&display, output_mode);
+        let sprites = SpriteRenderer::new(&device, &config, post_process.format());
+        let mut ui = UiRenderer::new(&device, &config);
+        ui.white_level = display.output_params(output_mode)[1];
+        let mut text = TextRenderer::new(&device, &config);
+        text.white_level = display.output_params(output_mode)[1];
+
         // Textures
-        let diffuse_bytes = include_bytes!("../assets/textures/sad_hamster.png"); // search the image
-        let diffuse_texture = Texture::from_bytes(diffuse_bytes, &device, &queue, "sad-hamster.png").unwrap();
+        // the images are read from the assets folder when the game starts, the manager keeps one copy of each
+        // a shipped build only loads the assets that were packed with it, development builds load anything
+        if cfg!(not(debug_assertions)) {
+            for root in resources::asset_roots() {
+                if let Err(e) = content_hash::verify_loads_under(&root) {
+                    eprintln!("the assets under {} are not verified: {:#}", root.display(), e);
+                }
+            }
+        }
+        // the mods go over the assets before anything is loaded from them
+        {
+            let mut vfs = vfs::global().write().unwrap();
+            vfs.discover_mods(vfs::MODS_FOLDER);
+            for mount in vfs.active_mods() {
+                println!("mod {} (priority {}) {}", mount.name, mount.priority, mount.description);
+            }
+        }
+        let mut textures = TextureManager::new(vfs::DEFAULT_BASE);
 
         // The bindgroup describes resources and how the shader will access to them
-        let texture_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
-            label: Some("texture_bind_group_layout"),
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture {
-                        multisampled: false,
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                    },
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    // This should match the filterable field of the
-                    // corresponding Texture entry above.
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                    count: None,
-                },
-            ],
-        });
+        // the layout of the materials is read from the group 0 of the scene shader so the two can't disagree, it is the
+        // embedded one because the layouts stay the same after a reload (only the modules change)
+        let scene_source = ShaderLibrary::builtin().preprocess("depth_map.wgsl", &[]).expect("the embedded scene shader doesn't preprocess");
+        let scene_materials = ShaderReflection::from_wgsl(&scene_source, 0).expect("the embedded scene shader doesn't parse");
+        let texture_bind_group_layout = Arc::new(scene_materials.create_layout(&device, "texture_bind_group_layout"));
 
         // we have to create a bind group for each texture since the fact that the layout and the group are separated is because we can swap the bind group on runtime
-        let diffuse_bind_group = device.create_bind_group(
-            &wgpu::BindGroupDescriptor {
-                label: Some("diffuse_bind_group"),
-                layout: &texture_bind_group_layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: wgpu::BindingResource::Sampler(&diffuse_texture.sampler),
-                    }
-                ],
-            }
-        );
+        // the manager makes them the first time they are asked for (textures.bind_group) so we don't create one here
         // Textures
 
         // Camera
         // we set up the camera
         let camera = CameraRenderizable::new(&device, &config);
+        let mut lights = Lights::new(&device);
+        // a warm lamp over the middle of the instance grid so the demo shows the point lights
+        lights.add(Light::point(cgmath::Point3::new(0.0, 4.0, 0.0), 15.0, LinearColor::rgb(1.0, 0.7, 0.4), 3.0));
+        // and a faint cool fill from above so the side away from the lamp isn't pitch black
+        lights.add(Light::directional(cgmath::Vector3::new(0.3, -1.0, 0.2), LinearColor::rgb(0.5, 0.6, 0.8), 0.3));
+        let sky = Sky::new(&device, post_process.format(), &lights);
+        let skybox = Skybox::new(&device, post_process.format(), &camera.camera);
 
         // SHADERING PROCESS 
-        // we get access to our shader file
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/depth_map.wgsl").into()),
-        });
+        // the scene shader is read from src/shaders when it is there and made again when the file changes
+        let mut shaders = ShaderManager::new(Some(ShaderManager::source_dir()));
 
-        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        let render_pipeline_layout = Arc::new(device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("Render Pipeline Layout"),
             bind_group_layouts: &[
                 &texture_bind_group_layout,
-                &camera.bind_group_layout
+                &camera.bind_group_layout,
+                &sky.bind_group_layout,
             ],
             push_constant_ranges: &[],
-        });
+        }));
 
         // here we define elements that will be sent to the gpu
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        // the builder is kept by the manager, it makes the pipeline again with the new module after every reload
+        let format = config.format;
+        let depth_compare = camera.camera.depth_compare();
+        // the forward and the clustered lights are two sets of variants of the same shader, the builder makes any of them
+        let scene_pipeline = move |layout: Arc<wgpu::PipelineLayout>| -> ReloadableBuilder { Box::new(move |device: &Device, shader: &wgpu::ShaderModule| device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
+            layout: Some(&*layout),
             vertex: wgpu::VertexState {
-                module: &shader,
+                module: shader,
                 entry_point: "vs_main",
                 buffers: &[model::ModelVertex::desc(), InstanceRaw::desc()], // we set the values of the instance for the render pipeline
             },
             fragment: Some(wgpu::FragmentState {
-                module: &shader, 
+                module: shader, 
                 entry_point: "fs_main",
                 targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
+                    format,
                     blend: Some(wgpu::BlendState::REPLACE),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
@@ -322,7 +495,7 @@ impl App {
             depth_stencil: Some(wgpu::DepthStencilState { 
                 format: Texture::DEPTH_FORMAT,
                 depth_write_enabled: true, 
-                depth_compare: wgpu::CompareFunction::Less, // this sets what pixels to draw in wich order, the less says that pixels will be drawn front to back.
+                depth_compare, // this sets what pixels to draw in wich order, the less says that pixels will be drawn front to back.
                 stencil: StencilState::default(), 
                 bias: DepthBiasState::default() 
             }),
@@ -332,7 +505,12 @@ impl App {
                 alpha_to_coverage_enabled: false,
             },
             multiview: None,
-        });
+        })) };
+        let scene_features = ShaderFeatures::NORMAL_MAP | ShaderFeatures::FOG;
+        let layout = render_pipeline_layout.clone();
+        let scene_variants = ShaderVariants::new(&mut shaders, &device, "Render Pipeline", "depth_map.wgsl", &[], scene_features, Box::new(move || scene_pipeline(layout.clone())));
+        let layout = render_pipeline_layout;
+        let clustered_variants = ShaderVariants::new(&mut shaders, &device, "Clustered Render Pipeline", "depth_map.wgsl", &["CLUSTERED_LIGHTS"], scene_features, Box::new(move || scene_pipeline(layout.clone())));
 
         /* 
         let vertex_buffer = device.create_buffer_init(
@@ -357,11 +535,29 @@ impl App {
         canvas.set_blend_mode(sdl2::render::BlendMode::Blend);
         let texture_creator = canvas.texture_creator();
 
-        // instances
-        // this will define a list of instances and setting their position/rotation automatically bassed on the constants especified
+        // the window shows up while the model loads, the grid is drawn once it's there
+        let mut assets = AssetManager::new(device.clone(), queue.clone(), texture_bind_group_layout.clone());
+        let diffuse_texture = assets.load_texture(&mut textures, "textures/sad_hamster.png").expect("the placeholder texture wasn't made");
+        let mut world = SceneGraph::new();
+        let default_model = assets.load_model(&mut world, Path::new(env!("OUT_DIR")).join("res").join("Revolver.obj"));
+
+        // the copy in OUT_DIR is not what the artists edit, the hot reload watches the one in res
+        let asset_watcher = FileWatcher::new(Duration::from_millis(500));
+        let mut watched_models = HashMap::new();
+        let source_model = Path::new(env!("CARGO_MANIFEST_DIR")).join("res").join("Revolver.obj");
+        for file in model::Model::source_files(&source_model) {
+            asset_watcher.watch(&file);
+            watched_models.insert(file, (source_model.clone(), default_model));
+        }
+
+        // the demo grid, children of one entity so moving it moves them all
         const SPACE_BETWEEN: f32 = 3.0;
-        let instances = (0..NUM_INSTANCES_PER_ROW).flat_map(|z| {
-            (0..NUM_INSTANCES_PER_ROW).map(move |x| {
+        // each one with its own phase, shade and size so the grid doesn't bob as one block
+        const GRID_VARIATION: VariationRange = VariationRange { phase: 3.0, tint: 0.2, scale: 0.1 };
+        let mut variation_rng = Rng::new(NUM_INSTANCES_PER_ROW as u64);
+        let grid = world.spawn("instance grid", Transform::default());
+        for z in 0..NUM_INSTANCES_PER_ROW {
+            for x in 0..NUM_INSTANCES_PER_ROW {
                 let x = SPACE_BETWEEN * (x as f32 - NUM_INSTANCES_PER_ROW as f32 / 2.0);
                 let z = SPACE_BETWEEN * (z as f32 - NUM_INSTANCES_PER_ROW as f32 / 2.0);
 
@@ -373,28 +569,115 @@ impl App {
                     cgmath::Quaternion::from_axis_angle(position.normalize(), cgmath::Deg(45.0))
                 };
 
-                Instance {
-                    position, rotation,
+                let entity = world.spawn_child(grid, "instance", Transform::from_position(position).with_rotation(rotation)).expect("the grid was just spawned");
+                if let Some(entity) = world.get_mut(entity) {
+                    entity.model = Some(default_model);
+                    entity.variation = GRID_VARIATION.sample(&mut variation_rng);
                 }
-            })
-        }).collect::<Vec<_>>();
+            }
+        }
 
-        // now that we have our data we will create our isntances buffer to send at the gpu
-        let instance_data = instances.iter().map(Instance::to_raw).collect::<Vec<_>>();
-        let instance_buffer = device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Instance Buffer"),
-                contents: bytemuck::cast_slice(&instance_data),
-                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST
+        // a screen behind the grid, a material of a custom shader: its bindings are read from screen.wgsl and its pipeline
+        // is the one of the scene with that layout at the group of the material
+        let screen = shaders.library().preprocess("screen.wgsl", &[]).and_then(|source| {
+            let mut custom = ShaderMaterial::new(&device, &queue, &source, 0, "screen")?;
+            let layout = Arc::new(device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Screen Pipeline Layout"),
+                bind_group_layouts: &[&custom.layout, &camera.bind_group_layout, &sky.bind_group_layout],
+                push_constant_ranges: &[],
+            }));
+            custom.pipeline = Some(shaders.create_pipeline(&device, "Screen Pipeline", "screen.wgsl", &[], scene_pipeline(layout)));
+            custom.params.set("tint", ParamValue::Vec4([0.7, 1.0, 0.8, 1.0]))?;
+            custom.params.set("brightness", ParamValue::Float(1.5))?;
+            custom.params.set("scanlines", ParamValue::Float(120.0))?;
+            custom.update(&device, &queue);
+            model::Material::from_shader(&device, &queue, &texture_bind_group_layout, "screen".to_string(), custom)
+        });
+        let screen_material = match screen {
+            Ok(material) => {
+                let material = world.add_material(material);
+                let screen = world.spawn("screen", Transform::from_position(cgmath::Vector3::new(0.0, 2.0, -SPACE_BETWEEN * NUM_INSTANCES_PER_ROW as f32 / 2.0 - 4.0)));
+                if let Some(entity) = world.get_mut(screen) {
+                    entity.model = Some(default_model);
+                    entity.material = Some(material);
+                }
+                Some(material)
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                None
             }
+        };
+        // what the screen shows, made on the cpu every frame like a video would be
+        let screen_feed = ExternalTexture::new(&device, ProceduralSource::new(128, 96, screen_pattern), "screen_feed");
+        world.update_world_transforms();
+        // the grid is solid, the camera stops at its boxes
+        let mut collision = CollisionWorld::new();
+        for entity in world.get(grid).map(|grid| grid.children().to_vec()).unwrap_or_default() {
+            collision.attach_entity(&world, entity, PickVolume::Box);
+        }
+
+        let mut scene_renderer = SceneRenderer::new(&device, (NUM_INSTANCES_PER_ROW * NUM_INSTANCES_PER_ROW) as usize);
+        scene_renderer.prepare(&device, &queue, &world);
+        let mut instance_animator = InstanceAnimator::new(&device, scene_renderer.source_buffer(), scene_renderer.buffer());
+        instance_animator.bob_height = 0.25;
+
+        // static instances start empty, the instance brush paints them
+        let mut static_instances = InstanceManager::new(&device, 64, "Static Instance Buffer");
+        // the painted scenery gets shades and sizes of its own, it doesn't move so the phase is left alone
+        static_instances.variation = VariationRange { phase: 0.0, tint: 0.15, scale: 0.1 };
+
+        let gpu_timer = GpuTimer::new(&device, &queue, 8);
+
+        let shadow_map = ShadowMap::new(&device, 2048, InstanceRaw::desc());
+        let mirrors = PlanarReflections::new(
+            &device,
+            post_process.format(),
+            &config,
+            &camera.camera,
+            &texture_bind_group_layout,
+            &sky.bind_group_layout,
+            &[model::ModelVertex::desc(), InstanceRaw::desc()],
+        );
+        let portals = Portals::new(
+            &device,
+            post_process.format(),
+            &config,
+            &camera.camera,
+            &texture_bind_group_layout,
+            &sky.bind_group_layout,
+            &[model::ModelVertex::desc(), InstanceRaw::desc()],
+        );
+        let trails = TrailRenderer::new(&device, &queue, post_process.format(), &camera.camera);
+        let blob_shadows = BlobShadows::new(&device, post_process.format(), &depth_texture);
+        let particles = ParticleSystem::new(&device, post_process.format(), &camera.camera);
+        let fog = VolumetricFog::new(&device, post_process.format(), FogQuality::Medium, &shadow_map, &depth_texture);
+        let mut debug_view = DebugViewRenderer::new(
+            &device,
+            &config,
+            &[&texture_bind_group_layout, &camera.bind_group_layout, &sky.bind_group_layout],
+            post_process.format(),
+            &[model::ModelVertex::desc(), InstanceRaw::desc()],
+            &camera.camera,
+            &DebugViewSources { scene_target: &post_process.scene_target, depth: &depth_texture, shadow_map: &shadow_map },
         );
-        // instances
+        debug_view.set_output(&display, output_mode);
 
-        let obj_model = resources::load_model("Revolver.obj", &device, &queue, &texture_bind_group_layout).await.unwrap();
+        let mut input = InputState::with_default_actions();
+        settings.apply_input(&mut input);
+        match context.game_controller() {
+            Ok(controllers) => input.enable_controllers(controllers),
+            Err(e) => eprintln!("the gamepads won't work: {}", e),
+        }
 
-        App {
+        let mut app = App {
             last_frame: Instant::now(),
             current_display,
+            monitor,
+            frame_limiter: FrameLimiter::new(Some(monitor_info.refresh_rate())),
+            limit_to_refresh_rate: true,
+            windowed_size: None,
+            fullscreen: if settings.fullscreen { FullscreenMode::Borderless } else { FullscreenMode::Windowed },
             context,
             width,
             height,
@@ -404,27 +687,322 @@ impl App {
             queue,
             device,
             config,
-            render_pipeline,
+            scene_variants,
+            clustered_variants,
+            shaders,
             index_buffer,
-            diffuse_bind_group,
+            textures,
             diffuse_texture,
+            texture_bind_group_layout,
+            asset_watcher,
+            watched_models,
+            assets,
             camera,
-            instances,
-            instance_buffer,
+            world,
+            scene_renderer,
+            default_model,
+            static_instances,
+            collision,
             depth_texture,
-            obj_model
+            gpu_timer,
+            instance_animator,
+            draw_stats: Cell::new(BatchStats::default()),
+            simulation_delta: 0.0,
+            timestep: FixedTimestep::new(DEFAULT_SIMULATION_RATE),
+            clear_color: LinearColor::rgb(0.1, 0.2, 0.3),
+            sky,
+            skybox,
+            lights,
+            shadow_map,
+            fog,
+            mirrors,
+            trails,
+            blob_shadows,
+            particles,
+            portals,
+            post_process,
+            clips,
+            debug_view,
+            display,
+            settings,
+            output_mode,
+            present_modes: surface_caps.present_modes.clone(),
+            frames: FramePacer::new(DEFAULT_FRAMES_IN_FLIGHT),
+            sprites,
+            parallax: ParallaxBackground::new(),
+            scenes: SceneManager::new(),
+            persistent: PersistentObjects::new(),
+            frame_graph: FrameGraph::new(),
+            passes: RenderPasses::new(),
+            overlay: DebugOverlay::new(&adapter.get_info()),
+            input,
+            ui,
+            text,
+            accessibility: UiAccessibility::new(),
+            captions: Captions::new(),
+            labels: WorldLabels::new(),
+            chat: ChatBox::new(),
+            ui_screens: ScriptScreens::load(),
+            abilities: AbilityLibrary::load(),
+            items: ItemLibrary::load(),
+            dialogues: DialogueLibrary::load(),
+            strings: Localization::load(FALLBACK_LANGUAGE),
+            audio: Audio::new(vfs::DEFAULT_BASE),
+            #[cfg(feature = "voice")]
+            voice: None,
+            rng: RngStreams::from_time(),
+            uploads: UploadQueue::new(DEFAULT_BUDGET),
+            screen_feed,
+            screen_material,
+            cvars: Self::engine_cvars(),
+        };
+        // the saved ones go through apply_cvars like a change from the console
+        app.cvars.load(CVARS_PATH);
+        app.apply_cvars();
+        app.load_post_passes();
+        // the shaders of the assets folder (or of a mod) go over the embedded ones with the same name once they are read
+        let names: Vec<String> = app.shaders.library().names().map(str::to_string).collect();
+        for name in names {
+            let path = Path::new(vfs::DEFAULT_BASE).join(SHADERS_FOLDER).join(&name);
+            if vfs::global().read().unwrap().exists(&path) {
+                app.load_shader_async(&name, path);
+            }
+        }
+        app
+    }
+
+    // the settings of the engine that the console and the config can change, the game registers its own on app.cvars
+    fn engine_cvars() -> CvarRegistry {
+        let mut cvars = CvarRegistry::new();
+        cvars.register("version", CvarValue::Text(env!("CARGO_PKG_VERSION").to_string()), CvarFlags::READ_ONLY, "the version of the engine");
+        cvars.register_ranged("monitor", CvarValue::Int(0), (0.0, 16.0), CvarFlags::ARCHIVE, "the monitor the window is on, it follows the window when it is dragged to another one");
+        cvars.register("fullscreen_exclusive", CvarValue::Bool(false), CvarFlags::ARCHIVE, "the fullscreen changes the mode of the monitor to the resolution of the settings instead of covering the desktop");
+        cvars.register_ranged("fps_max", CvarValue::Int(-1), (-1.0, 1000.0), CvarFlags::ARCHIVE, "the frame limit, -1 follows the monitor and 0 has no limit");
+        cvars.register_ranged("sim_rate", CvarValue::Float(DEFAULT_SIMULATION_RATE), (10.0, 1000.0), CvarFlags::ARCHIVE, "fixed steps of the simulation per second");
+        cvars.register_ranged("r_frames_in_flight", CvarValue::Int(DEFAULT_FRAMES_IN_FLIGHT as i64), (1.0, MAX_FRAMES_IN_FLIGHT as f64), CvarFlags::ARCHIVE, "how many frames the cpu can be ahead of the gpu");
+        cvars.register("r_fog", CvarValue::Bool(true), CvarFlags::ARCHIVE, "the volumetric fog");
+        cvars.register("r_fog_quality", CvarValue::Text("medium".to_string()), CvarFlags::ARCHIVE, "low, medium or high, how many froxels the volumetric fog has");
+        cvars.register_ranged("r_distance_fog", CvarValue::Float(0.0), (0.0, 1.0), CvarFlags::ARCHIVE, "the density of the cheap distance fog of the scene shader, 0 turns it off");
+        cvars.register("r_skybox", CvarValue::Text(String::new()), CvarFlags::ARCHIVE, "a folder of the assets with the six png faces of a cubemap behind the scene, empty for the sky");
+        cvars.register("r_lighting", CvarValue::Text(LightingPath::Forward.name().to_string()), CvarFlags::ARCHIVE, "the lights: forward (up to 16) or clustered (hundreds)");
+        cvars.register("r_shadows", CvarValue::Text(ShadowQuality::Blob.name().to_string()), CvarFlags::ARCHIVE, "the shadows: blob, map or off");
+        cvars.register("clip_capture", CvarValue::Bool(false), CvarFlags::ARCHIVE, "keep the last seconds of the game in memory for the highlight clips");
+        cvars.register("clip_format", CvarValue::Text(ClipFormat::Images.name().to_string()), CvarFlags::ARCHIVE, "the highlight clips as images or video (it needs ffmpeg)");
+        cvars.register("ui_screen_reader", CvarValue::Bool(false), CvarFlags::ARCHIVE, "say the focused button of the menus with the text to speech of the system");
+        cvars.register("thumbnail", CvarValue::Text(String::new()), CvarFlags::NONE, "renders a model of the assets (models/crate.obj) into a .thumb.png next to it, for the asset browsers");
+        cvars.register("seed", CvarValue::Int(0), CvarFlags::NONE, "the seed of the random streams, the same one gives the same game (replays and tests), 0 takes it from the clock");
+        cvars.register("map", CvarValue::Text(String::new()), CvarFlags::NONE, "the 2D level to play, a .tmx of the maps folder of the assets (empty for none)");
+        cvars.register_ranged("scene_fade", CvarValue::Float(0.5), (0.0, 5.0), CvarFlags::ARCHIVE, "seconds of each half of the fade between 2D levels, 0 cuts straight to the new one");
+        cvars.register("debug_log", CvarValue::Bool(false), CvarFlags::NONE, "print what the gameplay does (clicks, casts, dialogue choices) and what the hot reload picked up to the console");
+        cvars.register("name", CvarValue::Text("player".to_string()), CvarFlags::ARCHIVE, "the name of the player in the chat");
+        cvars.register("language", CvarValue::Text(FALLBACK_LANGUAGE.to_string()), CvarFlags::ARCHIVE, "the language of the texts, a file in the lang folder of the assets");
+        cvars.register_ranged("volume", CvarValue::Float(1.0), (0.0, 1.0), CvarFlags::ARCHIVE, "the master volume");
+        cvars.register_ranged("volume_effects", CvarValue::Float(1.0), (0.0, 1.0), CvarFlags::ARCHIVE, "the volume of the sound effects");
+        cvars.register_ranged("volume_music", CvarValue::Float(0.7), (0.0, 1.0), CvarFlags::ARCHIVE, "the volume of the music");
+        cvars.register("music", CvarValue::Text(String::new()), CvarFlags::ARCHIVE, "the music track of the assets that loops in the background, empty for silence");
+        cvars.register_ranged("volume_ui", CvarValue::Float(1.0), (0.0, 1.0), CvarFlags::ARCHIVE, "the volume of the menus");
+        #[cfg(feature = "voice")]
+        {
+            cvars.register("voice", CvarValue::Bool(false), CvarFlags::ARCHIVE, "the voice chat, push to talk");
+            cvars.register_ranged("voice_volume", CvarValue::Float(1.0), (0.0, 2.0), CvarFlags::ARCHIVE, "the volume of the other players");
+            cvars.register("voice_loopback", CvarValue::Bool(false), CvarFlags::NONE, "hear your own voice, to test the mic");
+        }
+        cvars.register_ranged("r_fov", CvarValue::Float(DEFAULT_FOVY), (MIN_FOVY as f64, MAX_FOVY as f64), CvarFlags::ARCHIVE, "the vertical field of view in degrees");
+        cvars.register("r_znear", CvarValue::Float(DEFAULT_ZNEAR), CvarFlags::ARCHIVE, "the near clip plane of the camera");
+        cvars.register("r_zfar", CvarValue::Float(DEFAULT_ZFAR), CvarFlags::ARCHIVE, "the far clip plane of the camera (the infinite projection ignores it)");
+        cvars.register("r_projection", CvarValue::Text("perspective".to_string()), CvarFlags::ARCHIVE, "the projection of the camera: perspective, infinite (no far plane, for open worlds), isometric or pixel_art (the 2:1 one)");
+        cvars.register("r_debug_view", CvarValue::Text(DebugView::Final.name().to_string()), CvarFlags::CHEAT, "final, albedo, normals, wireframe, depth, overdraw, quad occupancy or shadow cascades");
+        cvars
+    }
+
+    // what changed in the cvars since the last frame goes to the systems, the archived ones are saved again
+    pub fn apply_cvars(&mut self) {
+        let changed = self.cvars.take_changed();
+        if changed.is_empty() {
+            return;
+        }
+        for name in &changed {
+            match name.as_str() {
+                "fps_max" => match self.cvars.int(name).unwrap_or(-1) {
+                    -1 => self.limit_to_refresh_rate(),
+                    0 => self.set_frame_limit(None),
+                    fps => self.set_frame_limit(Some(fps as u32)),
+                },
+                // dragging the window sets it too, then it is already there
+                "monitor" => {
+                    let index = self.cvars.int(name).unwrap_or(0) as i32;
+                    if index != self.monitor {
+                        if let Err(e) = self.move_to_monitor(index) {
+                            eprintln!("{:#}", e);
+                        }
+                    }
+                }
+                "fullscreen_exclusive" if self.fullscreen != FullscreenMode::Windowed => {
+                    if let Err(e) = self.set_fullscreen(self.fullscreen_mode(&self.settings)) {
+                        eprintln!("the fullscreen couldn't change: {:#}", e);
+                    }
+                }
+                "sim_rate" => self.set_simulation_rate(self.cvars.float(name).unwrap_or(DEFAULT_SIMULATION_RATE)),
+                "r_frames_in_flight" => self.set_frames_in_flight(self.cvars.int(name).unwrap_or(DEFAULT_FRAMES_IN_FLIGHT as i64) as usize),
+                "r_fov" => {
+                    if let Err(e) = self.camera.set_fovy(&self.queue, self.cvars.float(name).unwrap_or(DEFAULT_FOVY)) {
+                        eprintln!("{}", e);
+                    }
+                }
+                // both planes together, a near plane past the old far one is fine if the far one moved too
+                "r_znear" | "r_zfar" => {
+                    let znear = self.cvars.float("r_znear").unwrap_or(DEFAULT_ZNEAR);
+                    let zfar = self.cvars.float("r_zfar").unwrap_or(DEFAULT_ZFAR);
+                    if let Err(e) = self.camera.set_clip_planes(&self.queue, znear, zfar) {
+                        eprintln!("{}", e);
+                    }
+                }
+                "r_projection" => match self.cvars.text(name).unwrap_or("") {
+                    "perspective" => self.camera.set_projection(&self.queue, Projection::Perspective),
+                    "infinite" => self.camera.set_projection(&self.queue, Projection::InfinitePerspective),
+                    "isometric" => self.camera.set_isometric(&self.queue, IsometricAngle::True, cgmath::Deg(45.0).into(), ISOMETRIC_VIEW_HEIGHT),
+                    "pixel_art" => self.camera.set_isometric(&self.queue, IsometricAngle::PixelArt, cgmath::Deg(45.0).into(), ISOMETRIC_VIEW_HEIGHT),
+                    other => eprintln!("r_projection: unknown projection {:?}", other),
+                },
+                "r_fog" => self.fog.enabled = self.cvars.bool(name).unwrap_or(true),
+                "r_fog_quality" => match self.cvars.text(name).and_then(FogQuality::from_name) {
+                    Some(quality) => self.fog.set_quality(&self.device, quality, &self.shadow_map, &self.depth_texture),
+                    None => eprintln!("r_fog_quality: unknown quality {:?}", self.cvars.text(name)),
+                },
+                "r_distance_fog" => self.sky.fog_density = self.cvars.float(name).unwrap_or(0.0),
+                "r_skybox" => {
+                    let folder = self.cvars.text(name).unwrap_or("").to_string();
+                    if folder.is_empty() {
+                        self.skybox.clear_cubemap();
+                    } else if let Err(e) = self.load_skybox(&folder, "png") {
+                        eprintln!("{:#}", e);
+                        self.skybox.clear_cubemap();
+                    }
+                }
+                "r_lighting" => match self.cvars.text(name).and_then(LightingPath::from_name) {
+                    Some(path) => self.lights.path = path,
+                    None => eprintln!("r_lighting: unknown path {:?}", self.cvars.text(name)),
+                },
+                "r_shadows" => match self.cvars.text(name).and_then(ShadowQuality::from_name) {
+                    Some(quality) => self.blob_shadows.enabled = quality == ShadowQuality::Blob,
+                    None => eprintln!("r_shadows: unknown quality {:?}", self.cvars.text(name)),
+                },
+                "clip_capture" => self.clips.set_enabled(self.cvars.bool(name).unwrap_or(false)),
+                "clip_format" => match self.cvars.text(name).and_then(ClipFormat::from_name) {
+                    Some(format) => {
+                        let settings = ClipSettings { format, ..*self.clips.settings() };
+                        self.clips.set_settings(&self.device, &self.config, settings);
+                    }
+                    None => eprintln!("clip_format: unknown format {:?}", self.cvars.text(name)),
+                },
+                "r_debug_view" => {
+                    let view = self.cvars.text(name).and_then(DebugView::from_name);
+                    match view {
+                        Some(view) => {
+                            self.set_debug_view(view);
+                        }
+                        None => eprintln!("r_debug_view: unknown view {:?}", self.cvars.text(name)),
+                    }
+                }
+                "language" => {
+                    let language = self.cvars.text(name).unwrap_or(FALLBACK_LANGUAGE).to_string();
+                    self.strings.set_language(&language);
+                }
+                "seed" => {
+                    let seed = self.cvars.int(name).unwrap_or(0) as u64;
+                    if seed != 0 {
+                        self.rng.reseed(seed);
+                    }
+                }
+                "thumbnail" => {
+                    let file = self.cvars.text(name).unwrap_or("").to_string();
+                    if !file.is_empty() {
+                        if let Err(e) = self.write_thumbnail(&file) {
+                            eprintln!("{}", e);
+                        }
+                    }
+                }
+                "map" => {
+                    let map = self.cvars.text(name).unwrap_or("").to_string();
+                    if map.is_empty() {
+                        let mut scene_context = SceneContext { device: &self.device, queue: &self.queue, sprites: &mut self.sprites, parallax: &mut self.parallax, persistent: &mut self.persistent, uploads: &mut self.uploads, input: &self.input, alpha: 0.0 };
+                        self.scenes.unload_current(&mut scene_context);
+                    } else {
+                        let fade = self.cvars.float("scene_fade").unwrap_or(0.5);
+                        let transition = if fade > 0.0 { Transition::fade(fade) } else { Transition::Cut };
+                        self.scenes.load(transition, move || Level2DData::load(&map));
+                    }
+                }
+                "ui_screen_reader" => {
+                    let speech: Option<Box<dyn SpeechHook>> = if self.cvars.bool(name).unwrap_or(false) { Some(Box::new(SystemSpeech::new())) } else { None };
+                    self.accessibility.set_speech_hook(speech);
+                }
+                "volume" => self.audio.set_master_volume(self.cvars.float(name).unwrap_or(1.0)),
+                "volume_effects" => self.audio.set_bus_volume(AudioBus::Effects, self.cvars.float(name).unwrap_or(1.0)),
+                "volume_music" => self.audio.set_bus_volume(AudioBus::Music, self.cvars.float(name).unwrap_or(0.7)),
+                "volume_ui" => self.audio.set_bus_volume(AudioBus::Ui, self.cvars.float(name).unwrap_or(1.0)),
+                "music" => match self.cvars.text(name).unwrap_or("") {
+                    "" => self.audio.stop_music(MUSIC_FADE_MS),
+                    track => {
+                        if let Err(e) = self.audio.play_music(track, MUSIC_FADE_MS) {
+                            eprintln!("{:#}", e);
+                        }
+                    }
+                },
+                #[cfg(feature = "voice")]
+                "voice" | "voice_volume" | "voice_loopback" => self.apply_voice_cvars(),
+                _ => {}
+            }
+        }
+        // the setters above write the cvars back, with the same values nothing is marked again
+        self.cvars.take_changed();
+        if changed.iter().any(|name| self.cvars.get(name).is_some_and(|cvar| cvar.flags.archive)) {
+            if let Err(e) = self.cvars.save(CVARS_PATH) {
+                eprintln!("the cvars were not saved: {}", e);
+            }
         }
     }
 
+    // the surface follows the size of the window, in real pixels (on high dpi screens they are more than the window size)
     pub fn resize(&mut self) {
-        self.config.width = self.current_display.w as u32;
-        self.config.height = self.current_display.h as u32;
+        let (width, height) = self.canvas.window().vulkan_drawable_size();
+        // a minimized window is 0x0, the surface keeps its size until it comes back
+        if width == 0 || height == 0 {
+            return;
+        }
+        self.resize_to(width, height);
+    }
+
+    fn resize_to(&mut self, width: u32, height: u32) {
+        self.width = width.max(1);
+        self.height = height.max(1);
+        self.config.width = self.width;
+        self.config.height = self.height;
         self.surface.configure(&self.device, &self.config);
+        // the projection has to follow or a wide window stretches everything
+        self.camera.camera.aspect = self.config.width as f32 / self.config.height as f32;
 
-        self.depth_texture = Texture::create_depth_texture(&self.device, &self.config, "depth_texture");
+        self.depth_texture = Texture::create_depth_texture_non_comparison_sampler(&self.device, &self.config, "depth_texture");
+        self.post_process.resize(&self.device, &self.config, &self.depth_texture);
+        self.clips.resize(&self.device, &self.config);
+        self.fog.resize(&self.device, &self.depth_texture);
+        self.blob_shadows.resize(&self.device, &self.depth_texture);
+        self.passes.resize(&self.device, &self.depth_texture);
+        self.debug_view.resize(&self.device, &self.config, &DebugViewSources { scene_target: &self.post_process.scene_target, depth: &self.depth_texture, shadow_map: &self.shadow_map });
+        self.mirrors.resize(&self.device, &self.config);
+        self.portals.resize(&self.device, &self.config);
+        self.sprites.resize(self.config.width, self.config.height);
+        self.ui.resize(self.config.width, self.config.height);
+        self.text.resize(self.config.width, self.config.height);
     }
 
-    pub fn render(&self) -> Result<(), wgpu::SurfaceError> {
+    pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        profile_scope!("render");
+        if let Some(gpu_timer) = &self.gpu_timer {
+            gpu_timer.begin_frame(&self.device);
+            gpu_timer.report_to_profiler();
+        }
+
         // WGPU
         let output = self.surface.get_current_texture()?;
         let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default()); // this let us to control how render code interacts with textures
@@ -434,65 +1012,459 @@ impl App {
             label: Some("Render Encoder"),
         });
 
-        {
-            // we make a render pass, this will have all the methods for drawing in the screen
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor { 
-                label: Some("Render Pass"), 
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment { // here we will define the base colors of the screen
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.1,
-                            g: 0.2,
-                            b: 0.3,
-                            a: 1.0,
-                        }),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
-                    view: &self.depth_texture.view,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
-                        store: wgpu::StoreOp::Store,
-                    }),
-                    stencil_ops: None,
-                }),
-                occlusion_query_set: None,
-                timestamp_writes: None,
-            });
+        // the static batch and the scene graph, the passes that draw the scene from other points of view use the same list
+        let mut draws = vec![InstancedDraw {
+            model: self.world.model(self.default_model),
+            material: None,
+            buffer: self.static_instances.buffer(),
+            instances: self.static_instances.range(),
+        }];
+        draws.extend(self.scene_renderer.draws(&self.world));
 
-            render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.draw_model_instanced(&self.obj_model, 0..self.instances.len() as u32, &self.camera.bind_group);
+        // every pass of the frame is in the list, the graph dump records the same passes that were encoded
+        let frame = self.frame_passes(&draws);
+        let targets = PassTargets { scene: &self.post_process.scene_target.view, depth: &self.depth_texture, surface: &view, camera: self.camera.bind_group() };
+        frame.run(&mut encoder, &targets);
+        let nodes = frame.into_nodes();
+        self.frame_graph.add_nodes(nodes);
 
+        if let Some(gpu_timer) = &self.gpu_timer {
+            gpu_timer.resolve(&mut encoder);
         }
 
         // we have the render pass inside the {} so we can do the submit to the queue, we can also drop the render pass if you prefeer
-        self.queue.submit(std::iter::once(encoder.finish()));
+        self.frames.submit(&self.queue, std::iter::once(encoder.finish()));
+        self.clips.after_submit(&self.device, &self.queue);
+        if let Some(gpu_timer) = &self.gpu_timer {
+            gpu_timer.after_submit();
+        }
         output.present();
 
         Ok(())
     }
 
+    // the passes of the frame in the order they run, the ones of the game go in the slots between the engine ones
+    fn frame_passes<'a>(&'a self, draws: &'a [InstancedDraw<'a>]) -> FramePasses<'a> {
+        let mut frame = FramePasses::new(self.frame_graph.is_recording());
+
+        // the compute pass goes first in the same encoder, so the render pass already sees the rotated instances
+        frame.add("instance animation", PassKind::Compute, &[frame_graph::buffer("instance_sources")], &[frame_graph::buffer("instances")], move |encoder, _| {
+            let timestamps = self.gpu_timer.as_ref().and_then(|timer| timer.compute_pass_writes("instance animation"));
+            self.instance_animator.animate(encoder, &self.queue, self.scene_renderer.instance_count(), timestamps);
+        });
+        if self.particles.is_active() {
+            frame.add("particles", PassKind::Compute, &[frame_graph::buffer("particle_emitters")], &[frame_graph::buffer("particles")], move |encoder, _| {
+                let timestamps = self.gpu_timer.as_ref().and_then(|timer| timer.compute_pass_writes("particles"));
+                self.particles.simulate(encoder, timestamps);
+            });
+        }
+
+        // only the fog (and its debug view) reads the shadow map for now, without them there is no need to draw the scene twice
+        let debug_view = self.debug_view.view();
+        if self.fog.enabled || debug_view.needs_shadow_map() {
+            frame.add("shadow map", PassKind::Render, &[frame_graph::buffer("static_instances"), frame_graph::buffer("instances")], &[frame_graph::texture("shadow_map")], move |encoder, _| {
+                self.shadow_map.render(encoder, draws);
+            });
+        }
+        let overdraw = debug_view.counts_overdraw().then(|| self.debug_view.overdraw_counter());
+        if let Some(overdraw) = overdraw {
+            frame.add("clear overdraw", PassKind::Copy, &[], &[frame_graph::buffer("overdraw_counts")], move |encoder, _| overdraw.clear(encoder));
+        }
+        if !self.mirrors.mirrors.is_empty() {
+            frame.add("reflections", PassKind::Render, &[frame_graph::buffer("static_instances"), frame_graph::buffer("instances")], &[frame_graph::texture("mirror_reflections")], move |encoder, _| {
+                self.mirrors.render(encoder, draws, &self.sky.bind_group);
+            });
+        }
+        if !self.portals.portals.is_empty() {
+            frame.add("portals", PassKind::Render, &[frame_graph::buffer("static_instances"), frame_graph::buffer("instances")], &[frame_graph::texture("portal_views")], move |encoder, _| {
+                self.portals.render(encoder, draws, &self.sky.bind_group);
+            });
+        }
+        frame.add_slot(&self.passes, PassSlot::BeforeScene);
+
+        let scene_reads = [frame_graph::buffer("camera"), frame_graph::buffer("sky"), frame_graph::buffer("lights"), frame_graph::buffer("static_instances"), frame_graph::buffer("instances"), frame_graph::texture("diffuse")];
+        if let Some(pipeline) = self.debug_view.scene_pipeline() {
+            // the debug views that replace the scene show only the meshes, the sky and the mirrors have their own pipelines
+            let writes = [frame_graph::texture("scene_target"), frame_graph::texture("depth"), frame_graph::buffer("overdraw_counts")];
+            let writes = if overdraw.is_some() { &writes[..] } else { &writes[..2] };
+            frame.add("debug scene", PassKind::Render, &scene_reads, writes, move |encoder, targets| {
+                let clear = if debug_view.replaces_scene() { wgpu::Color::BLACK } else { self.clear_color.into() };
+                let mut render_pass = self.scene_pass(encoder, targets, "Debug Scene Pass", Some(clear), None);
+                render_pass.set_pipeline(pipeline);
+                render_pass.set_bind_group(2, &self.sky.bind_group, &[]);
+                if let Some(overdraw) = overdraw {
+                    render_pass.set_bind_group(3, overdraw.bind_group(), &[]);
+                }
+                self.draw_stats.set(batching::draw_by_material(&mut render_pass, draws, self.camera.bind_group(), None));
+            });
+        } else {
+            let [camera, sky, lights, static_instances, instances, diffuse] = scene_reads;
+            let reads = [camera, sky, lights, static_instances, instances, diffuse, frame_graph::texture("mirror_reflections"), frame_graph::texture("portal_views")];
+            frame.add("opaque", PassKind::Render, &reads, &[frame_graph::texture("scene_target"), frame_graph::texture("depth")], move |encoder, targets| {
+                let timestamps = self.gpu_timer.as_ref().and_then(|timer| timer.render_pass_writes("opaque"));
+                let mut render_pass = self.scene_pass(encoder, targets, "Opaque Pass", Some(self.clear_color.into()), timestamps);
+                // with a cubemap the analytic sky only lights the scene
+                if !self.skybox.is_active() {
+                    self.sky.draw(&mut render_pass);
+                }
+
+                let variants = match self.lights.path {
+                    LightingPath::Forward => &self.scene_variants,
+                    LightingPath::Clustered => &self.clustered_variants,
+                };
+                // the fog is for the whole scene, the normal map is up to each material
+                let scene = if self.sky.fog_density > 0.0 { ShaderFeatures::FOG } else { ShaderFeatures::NONE };
+                // the custom shader materials bring their own pipeline
+                let variant = |material: &model::Material| match material.custom.as_ref().and_then(|custom| custom.pipeline) {
+                    Some(pipeline) => self.shaders.pipeline(pipeline),
+                    None => self.shaders.pipeline(variants.get(draw_features(material, scene))),
+                };
+                render_pass.set_bind_group(2, &self.sky.bind_group, &[]);
+
+                // the static batch is drawn from its own buffer so we never have to re-upload it, the meshes of
+                // every draw go by variant and material so each one is bound once
+                self.draw_stats.set(batching::draw_by_material(&mut render_pass, draws, self.camera.bind_group(), Some(&variant)));
+
+                self.mirrors.draw(&mut render_pass, self.camera.bind_group(), &self.sky.bind_group);
+                self.portals.draw(&mut render_pass, self.camera.bind_group(), &self.sky.bind_group);
+            });
+            // after everything opaque, so it is only shaded where nothing covers it
+            if self.skybox.is_active() {
+                frame.add("skybox", PassKind::Render, &[frame_graph::buffer("skybox"), frame_graph::texture("skybox_cubemap"), frame_graph::texture("depth")], &[frame_graph::texture("scene_target")], move |encoder, targets| {
+                    let mut render_pass = self.scene_pass(encoder, targets, "Skybox Pass", None, None);
+                    self.skybox.draw(&mut render_pass);
+                });
+            }
+            // blended over all of it, they don't write the depth
+            if self.trails.has_work() || self.particles.is_active() {
+                frame.add(
+                    "transparent",
+                    PassKind::Render,
+                    &[frame_graph::buffer("camera"), frame_graph::buffer("trail_vertices"), frame_graph::buffer("particles"), frame_graph::texture("depth")],
+                    &[frame_graph::texture("scene_target")],
+                    move |encoder, targets| {
+                        let mut render_pass = self.scene_pass(encoder, targets, "Transparent Pass", None, None);
+                        self.trails.draw(&mut render_pass, self.camera.bind_group());
+                        self.particles.draw(&mut render_pass, self.camera.bind_group());
+                    },
+                );
+            }
+        }
+
+        if self.debug_view.is_active() {
+            // the debug views show the 3D scene alone, without the fog, the sprites or the screen effects
+            // the overdraw ones still count the sprites and the ui, the particles and the panels are what fills the most
+            if let Some(overdraw) = overdraw {
+                frame.add("sprite overdraw", PassKind::Render, &[frame_graph::buffer("sprite_instances")], &[frame_graph::buffer("overdraw_counts")], move |encoder, targets| {
+                    self.sprites.render_counting_overdraw(encoder, targets.scene, overdraw.bind_group());
+                });
+                frame.add("ui overdraw", PassKind::Render, &[frame_graph::buffer("ui_vertices")], &[frame_graph::buffer("overdraw_counts")], move |encoder, targets| {
+                    self.ui.render_counting_overdraw(encoder, targets.surface, overdraw.bind_group());
+                });
+            }
+            self.debug_view.add_pass(&mut frame);
+        } else {
+            // the spots darken the floor before the fog covers it
+            if self.blob_shadows.is_active() {
+                frame.add("blob shadows", PassKind::Render, &[frame_graph::buffer("blob_casters"), frame_graph::texture("depth")], &[frame_graph::texture("scene_target")], move |encoder, targets| {
+                    self.blob_shadows.render(encoder, targets.scene);
+                });
+            }
+            frame.add_slot(&self.passes, PassSlot::AfterScene);
+            // the fog covers the 3D scene only, the sprites go over it
+            if self.fog.enabled {
+                frame.add("fog inject", PassKind::Compute, &[frame_graph::buffer("fog"), frame_graph::texture("shadow_map")], &[frame_graph::texture("fog_scattering")], move |encoder, _| self.fog.inject(encoder));
+                frame.add("fog integrate", PassKind::Compute, &[frame_graph::texture("fog_scattering")], &[frame_graph::texture("fog_integrated")], move |encoder, _| self.fog.integrate(encoder));
+                frame.add("fog apply", PassKind::Render, &[frame_graph::texture("fog_integrated"), frame_graph::texture("depth")], &[frame_graph::texture("scene_target")], move |encoder, targets| {
+                    self.fog.apply(encoder, targets.scene);
+                });
+            }
+
+            // 2D sprites go over the 3D scene (or alone, for 2D games)
+            if self.sprites.has_work() {
+                frame.add("sprites", PassKind::Render, &[frame_graph::buffer("sprite_instances"), frame_graph::texture("sprite_atlases")], &[frame_graph::texture("scene_target")], move |encoder, targets| {
+                    self.sprites.render(encoder, targets.scene);
+                });
+            }
+            frame.add_slot(&self.passes, PassSlot::AfterSprites);
+
+            // the last passes, they take the scene and write the surface
+            self.post_process.add_passes(&mut frame);
+            if self.clips.is_capturing_this_frame() {
+                frame.add("clip capture", PassKind::Render, &[frame_graph::texture("scene_target")], &[frame_graph::texture("clip_target")], move |encoder, _| {
+                    self.clips.render(encoder, &self.post_process);
+                });
+            }
+        }
+        frame.add_slot(&self.passes, PassSlot::Overlay);
+
+        // the ui goes over everything, the effects don't touch it, the text over its backgrounds
+        self.ui.add_pass(&mut frame);
+        self.text.add_pass(&mut frame);
+        frame
+    }
+
+    // a pass on the scene target and its depth, the one with a clear color starts the scene and the rest draw over it
+    fn scene_pass<'r>(&'r self, encoder: &'r mut wgpu::CommandEncoder, targets: &'r PassTargets<'r>, label: &str, clear: Option<wgpu::Color>, timestamp_writes: Option<wgpu::RenderPassTimestampWrites<'r>>) -> wgpu::RenderPass<'r> {
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(label),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment { // here we will define the base colors of the screen
+                view: targets.scene,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: clear.map_or(wgpu::LoadOp::Load, wgpu::LoadOp::Clear),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: &targets.depth.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: if clear.is_some() { wgpu::LoadOp::Clear(self.camera.camera.depth_clear_value()) } else { wgpu::LoadOp::Load },
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes,
+        })
+    }
+
+    // a mirror facing normal, half_size is half its width and height, the index is for app.mirrors.mirrors
+    pub fn add_mirror(&mut self, center: cgmath::Point3<f32>, normal: cgmath::Vector3<f32>, half_size: cgmath::Vector2<f32>) -> usize {
+        self.mirrors.add(&self.device, center, normal, half_size)
+    }
+
+    // two portals that lead to each other, each is a center and the normal of the side that shows the view
+    // the indices are for app.portals.portals, max_depth there sets how many times they can be seen inside each other
+    pub fn add_portal_pair(&mut self, a: (cgmath::Point3<f32>, cgmath::Vector3<f32>), b: (cgmath::Point3<f32>, cgmath::Vector3<f32>), half_size: cgmath::Vector2<f32>) -> (usize, usize) {
+        self.portals.add_pair(&self.device, a, b, half_size)
+    }
+
+    // a screen that shows what the camera sees, the camera can be moved later through app.portals.portals[index].view
+    pub fn add_camera_screen(&mut self, center: cgmath::Point3<f32>, normal: cgmath::Vector3<f32>, half_size: cgmath::Vector2<f32>, camera: Camera) -> usize {
+        self.portals.add(&self.device, center, normal, half_size, PortalView::Fixed(camera))
+    }
+
+    // the calibration screen (or a settings menu) changed the values, the hdr switch applies on the next start
+    pub fn set_display_settings(&mut self, settings: DisplaySettings) {
+        self.display = settings.clamped();
+        self.post_process.set_output(&self.display, self.output_mode);
+        self.debug_view.set_output(&self.display, self.output_mode);
+        self.ui.white_level = self.display.output_params(self.output_mode)[1];
+        self.text.white_level = self.display.output_params(self.output_mode)[1];
+        if let Err(e) = self.display.save(DISPLAY_SETTINGS_PATH) {
+            eprintln!("the display settings were not saved: {}", e);
+        }
+    }
+
+    pub fn frames_in_flight(&self) -> usize {
+        self.frames.frames_in_flight()
+    }
+
+    // an effect of the assets and its caption (registered under the name of the file without the extension)
+    // the gameplay calls this instead of the two, a file that doesn't exist yet still shows the caption
+    pub fn play_sound(&mut self, path: &str) -> Option<SoundHandle> {
+        if let Some(name) = Path::new(path).file_stem().and_then(|name| name.to_str()) {
+            self.captions.on_sound(name);
+        }
+        self.audio.play_file(path, AudioBus::Effects)
+    }
+
+    // the devices open when the voice cvar goes on and close when it goes off, if they can't open it goes back off
+    #[cfg(feature = "voice")]
+    fn apply_voice_cvars(&mut self) {
+        let enabled = self.cvars.bool("voice").unwrap_or(false);
+        if enabled && self.voice.is_none() {
+            let voice = self.context.audio().map_err(|e| anyhow::anyhow!(e)).and_then(|audio| VoiceChat::open(&audio));
+            match voice {
+                Ok(voice) => self.voice = Some(voice),
+                Err(e) => {
+                    eprintln!("the voice chat couldn't start: {}", e);
+                    let _ = self.cvars.set_internal("voice", CvarValue::Bool(false));
+                    return;
+                }
+            }
+        } else if !enabled {
+            self.voice = None;
+        }
+        if let Some(voice) = &mut self.voice {
+            voice.playback.set_volume(self.cvars.float("voice_volume").unwrap_or(1.0));
+            voice.loopback = self.cvars.bool("voice_loopback").unwrap_or(false);
+        }
+    }
+
+    // push to talk, nothing happens while the voice is off
+    #[cfg(feature = "voice")]
+    pub fn set_voice_transmitting(&mut self, transmitting: bool) {
+        if let Some(voice) = &mut self.voice {
+            voice.capture.set_transmitting(transmitting);
+        }
+    }
+
+    // the voices are heard from the camera, what the mic said goes to the connection
+    #[cfg(feature = "voice")]
+    fn update_voice(&mut self) {
+        let Some(voice) = &mut self.voice else { return };
+        let camera = &self.camera.camera;
+        let right = (camera.target - camera.eye).normalize().cross(camera.up);
+        // there is no connection yet to send the packets, with loopback on they were already played back
+        let _ = voice.update(Listener { position: camera.eye, right });
+    }
+
+    // 2 is double buffering and 3 triple, the rings made with the old count have to be made again
+    pub fn set_frames_in_flight(&mut self, frames_in_flight: usize) {
+        self.frames.wait_idle(&self.device);
+        self.frames = FramePacer::new(frames_in_flight);
+        let _ = self.cvars.set_internal("r_frames_in_flight", CvarValue::Int(self.frames.frames_in_flight() as i64));
+    }
+
+    // vsync, mailbox or immediate while the game runs, the mode that is not supported falls back to the closest one
+    // it is saved with the display settings, the one that was really used is returned
+    pub fn set_present_mode(&mut self, mode: wgpu::PresentMode) -> wgpu::PresentMode {
+        self.config.present_mode = pick_present_mode(&self.present_modes, mode);
+        self.surface.configure(&self.device, &self.config);
+        self.set_display_settings(DisplaySettings { present_mode: mode, ..self.display });
+        self.config.present_mode
+    }
+
+    pub fn present_mode(&self) -> wgpu::PresentMode {
+        self.config.present_mode
+    }
+
+    // a folder of the assets with the six faces of a cubemap (see textures::CUBEMAP_FACES), it replaces the sky behind
+    // the scene but the lighting still follows the day/night cycle (the r_skybox cvar)
+    pub fn load_skybox(&mut self, folder: impl AsRef<Path>, extension: &str) -> anyhow::Result<()> {
+        let cubemap = Texture::load_cubemap(&self.device, &self.queue, Path::new(vfs::DEFAULT_BASE).join(folder), extension)?;
+        self.skybox.set_cubemap(&self.device, cubemap);
+        Ok(())
+    }
+
+    fn draw_overlay(&mut self, font: &Font) {
+        if !self.overlay.is_visible() {
+            return;
+        }
+        let camera = &self.camera.camera;
+        let stats = OverlayStats {
+            camera_eye: camera.eye.into(),
+            camera_target: camera.target.into(),
+            fovy: camera.fovy,
+            entities: self.world.len(),
+            static_instances: self.static_instances.len(),
+            textures: self.textures.len(),
+            assets_loading: self.assets.loading(),
+            draws: self.draw_stats.get(),
+            debug_view: self.debug_view.view().name(),
+            present_mode: self.present_mode(),
+            frames_in_flight: self.frames_in_flight(),
+            gpu_times: self.gpu_timer.as_ref().map(|timer| timer.results()).unwrap_or_default(),
+        };
+        self.overlay.draw(&mut self.ui, &mut self.text, font, &stats, self.config.width);
+    }
+
+    // false when the device can't show the view (the wireframe without line drawing), the view stays as it was
+    pub fn set_debug_view(&mut self, view: DebugView) -> bool {
+        if !self.debug_view.supports(view) {
+            eprintln!("the {} debug view is not supported by this gpu", view.name());
+            let _ = self.cvars.set_internal("r_debug_view", CvarValue::Text(self.debug_view.view().name().to_string()));
+            return false;
+        }
+        if view.counts_overdraw() {
+            let layout = self.debug_view.overdraw_counter().layout();
+            self.sprites.enable_overdraw_count(&self.device, layout);
+            self.ui.enable_overdraw_count(&self.device, layout);
+        }
+        self.debug_view.set_view(view);
+        let _ = self.cvars.set_internal("r_debug_view", CvarValue::Text(view.name().to_string()));
+        true
+    }
+
+    pub fn debug_view(&self) -> DebugView {
+        self.debug_view.view()
+    }
+
+    // the view after the current one, skipping the ones the gpu can't show
+    pub fn next_debug_view(&self) -> DebugView {
+        self.debug_view.next_supported(self.debug_view.view())
+    }
+
+    // the ui scale and the minimum font size, from a settings menu, saved for the next start
+    pub fn set_ui_settings(&mut self, settings: UiSettings) {
+        UiSettings::set_current(settings);
+        if let Err(e) = UiSettings::current().save(UI_SETTINGS_PATH) {
+            eprintln!("the ui settings were not saved: {}", e);
+        }
+    }
+
+    // a post process pass of the game, the source only writes fs_main (see src/shaders/common/post_pass.wgsl)
+    pub fn add_post_pass(&mut self, name: &str, slot: PostSlot, source: &str) -> anyhow::Result<()> {
+        self.post_process.add_pass(&self.device, &self.depth_texture, name, slot, source)
+    }
+
+    // the post passes of the assets and the mods, the folder is the slot and the file name is the name of the pass
+    fn load_post_passes(&mut self) {
+        for (folder, slot) in [("before_effects", PostSlot::BeforeEffects), ("after_effects", PostSlot::AfterEffects)] {
+            let files = vfs::global().read().unwrap().list(&format!("{}/{}", POST_FOLDER, folder));
+            for file in files.into_iter().filter(|file| file.ends_with(".wgsl")) {
+                let name = Path::new(&file).file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+                let source = vfs::global().read().unwrap().read(&Path::new(vfs::DEFAULT_BASE).join(&file)).and_then(|bytes| Ok(String::from_utf8(bytes)?));
+                if let Err(e) = source.and_then(|source| self.add_post_pass(&name, slot, &source)) {
+                    eprintln!("the post pass {} was not loaded: {:#}", file, e);
+                }
+            }
+        }
+    }
+
+    // a pass of the game with its own pipeline, it runs at its slot every frame until it's removed (see render_passes.rs)
+    pub fn add_render_pass(&mut self, pass: Box<dyn RenderPass>) -> anyhow::Result<()> {
+        self.passes.add(&self.device, &self.depth_texture, pass)
+    }
+
     pub fn update(mut self) {
         // SDL2
-        let mut app_state = AppState { is_running: true, state: GameState::Playing};
+        let mut app_state = AppState::new(GameState::Playing);
         let mut event_pump = self.context.event_pump().unwrap();
 
         // we define a font for our text
         let ttf_context = sdl2::ttf::init().unwrap(); // we create a "context"
         let use_font = "./assets/fonts/Inter-Thin.ttf";
-        let mut _font = ttf_context.load_font(use_font, 20).unwrap();
+        // the font is made for the ui scale, when the scale changes it is loaded again at the new size
+        let mut font_size = UiSettings::current().font_size(UI_FONT_SIZE);
+        let mut _font = ttf_context.load_font(use_font, font_size).unwrap();
 
         // here we define the initial state of our game states
         let mut play = play::GameLogic::new(&mut self, 5.0);
 
         // main game loop
         while app_state.is_running { 
+            Profiler::begin_frame();
+            let wanted_font_size = UiSettings::current().font_size(UI_FONT_SIZE);
+            if wanted_font_size != font_size {
+                match ttf_context.load_font(use_font, wanted_font_size) {
+                    Ok(font) => _font = font,
+                    Err(e) => eprintln!("the font couldn't be loaded at {}: {}", wanted_font_size, e),
+                }
+                font_size = wanted_font_size;
+            }
             let delta_time = self.delta_time().as_secs_f32();
+            self.overlay.record_frame(delta_time);
+            self.simulation_delta = if app_state.is_paused() { 0.0 } else { delta_time };
+            // the effects and the music stop with the world, the channels that finished are let go
+            self.audio.set_paused(app_state.is_paused());
+            self.audio.update();
+            let simulation_delta = self.simulation_delta;
+            // the events of the frame, the game reads them from self.input
+            let events = self.input.update(&mut event_pump);
+            let resized = events.iter().any(|event| matches!(event, Event::Window { win_event: WindowEvent::SizeChanged(..), .. }));
+            if resized {
+                self.resize();
+            }
+
+            // this lets the pending readbacks (and any other map_async) finish without blocking the frame
+            self.device.poll(wgpu::Maintain::Poll);
 
+            self.frame_graph.begin_frame();
+            self.uploads.record_frame_graph(&mut self.frame_graph);
+            self.screen_feed.record_frame_graph(&mut self.frame_graph);
             match self.render() {
                 Ok(_) => {},
                 Err(wgpu::SurfaceError::Outdated) => { 
@@ -500,23 +1472,417 @@ impl App {
                 }
                 Err(e) => eprintln!("Error: {}", e),
             }
+            self.frame_graph.end_frame();
+            // what the console or the config changed last frame, before the pacer since frames in flight is one of them
+            self.apply_cvars();
+
+            // the update below fills the rings for the next render, so the slot moves between them
+            // it waits here (and not in the middle of the frame) when the gpu is frames_in_flight frames behind
+            self.frames.begin_frame(&self.device);
+            self.camera.begin_frame(&self.queue, self.frames.slot());
             
             match app_state.state {
-                GameState::Playing => {
-                    for instance in &mut self.instances {
-                        let amount = cgmath::Quaternion::from_angle_y(cgmath::Rad(10.0) * delta_time);
-                        let current = instance.rotation;
-                        // instance.rotation = amount * current;
-                        // instance.position.y += 1.0 * delta_time;
+                GameState::Playing | GameState::Paused | GameState::Calibrating | GameState::Browsing => {
+                    profile_scope!("update");
+                    self.hot_reload();
+                    self.finish_assets();
+                    // the uploads queued last frame go first, what doesn't fit the budget waits for the next one
+                    self.uploads.flush(&self.queue);
+                    // the fixed steps of the simulation, none while paused, a slow frame runs a few of them
+                    let steps = self.timestep.advance(simulation_delta);
+                    let step = self.timestep.step;
+                    for _ in 0..steps {
+                        self.world.begin_fixed_step();
+                        play.fixed_update(&mut self, step);
+                        // the physics of the 2D levels goes with the same steps
+                        let mut scene_context = SceneContext { device: &self.device, queue: &self.queue, sprites: &mut self.sprites, parallax: &mut self.parallax, persistent: &mut self.persistent, uploads: &mut self.uploads, input: &self.input, alpha: 0.0 };
+                        self.scenes.fixed_update(&mut scene_context, step);
+                    }
+                    // only what changed in the graph is uploaded, the static batch uploads what changed after the game ran
+                    // the animation of the instances goes on the gpu, the colliders and the picking follow it on the cpu
+                    self.world.update_world_transforms_interpolated(self.timestep.alpha());
+                    // the screen takes the new frame before the materials write what changed
+                    if let Some(material) = self.screen_material.filter(|_| self.screen_feed.update(&self.queue, simulation_delta)) {
+                        if let Some(custom) = self.world.material_mut(material).custom.as_mut() {
+                            if let Err(e) = custom.bind_external("t_screen", &self.screen_feed) {
+                                eprintln!("{}", e);
+                            }
+                        }
+                    }
+                    self.world.update_materials(&self.device, &self.queue);
+                    self.instance_animator.update(simulation_delta);
+                    let animator = &self.instance_animator;
+                    self.collision.sync(&self.world, &self.static_instances, |entity| animator.entity_matrix(entity));
+                    if self.scene_renderer.prepare(&self.device, &self.queue, &self.world) {
+                        self.instance_animator.set_instance_buffers(&self.device, self.scene_renderer.source_buffer(), self.scene_renderer.buffer());
                     }
-                    let instance_data = self.instances.iter().map(Instance::to_raw).collect::<Vec<_>>();
-                    // Update the instance buffer
-                    self.queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&instance_data));
-                    self.camera.uniform.update_view_proj(&self.camera.camera);
-                    self.queue.write_buffer(&self.camera.buffer, 0, bytemuck::cast_slice(&[self.camera.uniform]));
-                    play.update(&_font, &mut app_state, &mut event_pump, &mut self);
+                    self.camera.update(&self.queue, simulation_delta);
+                    #[cfg(feature = "voice")]
+                    self.update_voice();
+                    self.sky.update(&self.queue, &self.camera.camera, simulation_delta);
+                    self.skybox.update(&self.queue, &self.camera.camera);
+                    // the clusters go with the camera that is drawn, shake included
+                    self.lights.update(&self.queue, &self.camera.shake.apply(&self.camera.camera), self.config.width, self.config.height);
+                    self.mirrors.update(&self.queue, &self.camera.camera);
+                    self.portals.update(&self.queue, &self.camera.camera);
+                    self.trails.update(simulation_delta, &self.world);
+                    self.trails.prepare(&self.device, &self.queue, &self.camera.camera);
+                    self.particles.update(&self.queue, simulation_delta, &self.camera.camera, &self.world);
+                    self.clips.update(simulation_delta);
+                    self.blob_shadows.update(&self.device, &self.queue, &self.camera.camera, &self.world);
+                    self.passes.prepare(&self.device, &self.queue, &self.camera.camera);
+                    self.shadow_map.update(&self.queue, self.sky.cycle.sun_direction(), self.camera.camera.target);
+                    self.fog.update(&self.queue, &self.camera.camera, &self.sky, &self.shadow_map, simulation_delta);
+                    self.debug_view.update(&self.queue, &self.camera.camera, &self.shadow_map);
+                    // the scene runs (and switches) before the post process so the fade of this frame is uploaded
+                    let mut scene_context = SceneContext { device: &self.device, queue: &self.queue, sprites: &mut self.sprites, parallax: &mut self.parallax, persistent: &mut self.persistent, uploads: &mut self.uploads, input: &self.input, alpha: self.timestep.alpha() };
+                    self.scenes.update(&mut scene_context, &mut self.post_process, delta_time, simulation_delta);
+                    self.post_process.update(&self.queue, delta_time);
+                    self.parallax.update(simulation_delta);
+                    self.parallax.draw(&mut self.sprites);
+                    self.sprites.prepare(&self.device, &self.queue);
+                    // they stop with the game like the sounds they come from
+                    self.captions.update(simulation_delta);
+                    self.labels.update(simulation_delta, &self.camera.camera, &self.world, &self.collision);
+                    // the chat keeps fading while paused, the other players didn't stop
+                    self.chat.update(delta_time);
+                    // the events of the ui were already spoken, the log shows them for the ones without a reader
+                    let log = self.cvars.bool("debug_log").unwrap_or(false);
+                    for event in self.accessibility.drain_events() {
+                        if log {
+                            println!("ui: {}", event.describe());
+                        }
+                    }
+                    play.update(&_font, &mut app_state, &mut self);
+                    // over everything the game drew
+                    self.draw_overlay(&_font);
+                    self.static_instances.prepare(&self.device, &self.queue);
+                    self.ui.prepare(&self.device, &self.queue);
+                    self.text.prepare(&self.device, &self.queue);
                 }
             }
+            self.follow_window_monitor();
+            Profiler::end_frame();
+            self.frame_limiter.wait();
+        }
+    }
+
+    // the entity or static instance under the mouse, the closest one, where the animation draws it this frame
+    pub fn pick(&self, mouse_x: i32, mouse_y: i32, volume: PickVolume) -> Option<PickHit<Picked>> {
+        let ray = self.camera.camera.screen_to_ray(mouse_x as f32, mouse_y as f32, self.config.width as f32, self.config.height as f32)?;
+        let entity = picking::pick_entity(&self.world, &ray, volume, |entity| self.instance_animator.entity_matrix(entity)).map(|hit| PickHit { target: Picked::Entity(hit.target), distance: hit.distance, point: hit.point });
+
+        let static_instance = self.world.model(self.default_model).bounds().and_then(|bounds| {
+            let matrices: Vec<cgmath::Matrix4<f32>> = self.static_instances.matrices().iter().map(|matrix| (*matrix).into()).collect();
+            picking::pick_instance(&ray, &bounds, &matrices, volume)
+        });
+        let static_instance = static_instance.map(|hit| PickHit { target: Picked::StaticInstance(hit.target), distance: hit.distance, point: hit.point });
+
+        match (entity, static_instance) {
+            (Some(entity), Some(instance)) => Some(if instance.distance < entity.distance { instance } else { entity }),
+            (entity, instance) => entity.or(instance),
+        }
+    }
+
+    // the preview of a model for the asset browsers, blocks until the gpu finished so it's only done on request
+    fn write_thumbnail(&self, file: &str) -> anyhow::Result<()> {
+        let path = vfs::resolve(Path::new(vfs::DEFAULT_BASE).join(file));
+        let model = model::Model::load(&path, &self.device, &self.queue, &self.texture_bind_group_layout)?;
+        let Some(bounds) = model.bounds() else { anyhow::bail!("{} has no vertices", file) };
+        let renderer = ThumbnailRenderer::new(&self.device, &self.texture_bind_group_layout);
+        let thumbnail = renderer.render(&self.device, &self.queue, &model, bounds.center(), bounds.extents().magnitude(), THUMBNAIL_SIZE);
+        let Some(image) = image::RgbaImage::from_raw(thumbnail.width, thumbnail.height, thumbnail.pixels) else { anyhow::bail!("the thumbnail of {} wasn't read back", file) };
+        image.save(path.with_extension("thumb.png"))?;
+        Ok(())
+    }
+
+    // a shader of the library read from a file, the pipelines that use the name are made again when it's there
+    pub fn load_shader_async(&mut self, name: &str, path: impl AsRef<Path>) {
+        self.assets.load_shader(name, vfs::resolve(path));
+    }
+
+    // puts the assets that finished loading in place of their placeholders
+    fn finish_assets(&mut self) {
+        for (path, asset) in self.assets.finished() {
+            match asset {
+                LoadedAsset::Texture { handle, texture } => {
+                    let old = self.textures.replace(handle, texture);
+                    let new = self.textures.shared(handle);
+                    self.world.replace_texture(&self.device, &self.texture_bind_group_layout, &old, &new);
+                }
+                LoadedAsset::Model { id, model } => {
+                    self.world.replace_model(id, model);
+                    // the default model is already watched from res
+                    if id != self.default_model {
+                        for file in model::Model::source_files(&path) {
+                            self.asset_watcher.watch(&file);
+                            self.watched_models.insert(file, (path.clone(), id));
+                        }
+                    }
+                }
+                LoadedAsset::Shader { name, source } => {
+                    let rebuilt = self.shaders.add_source(&self.device, &name, &source);
+                    println!("loaded the shader {} ({} pipelines)", name, rebuilt);
+                }
+            }
+        }
+    }
+
+    // swaps the gpu resources of the assets that changed on disk, the handles and ids keep pointing to them
+    fn hot_reload(&mut self) {
+        profile_scope!("hot reload");
+        self.shaders.reload(&self.device);
+        // the textures loaded since the last frame start being watched
+        for (_, path) in self.textures.paths() {
+            self.asset_watcher.watch(path);
+        }
+        for path in self.ui_screens.files().into_iter().chain(self.abilities.files()).chain(self.items.files()).chain(self.dialogues.files()).chain(self.strings.files()) {
+            self.asset_watcher.watch(&path);
+        }
+
+        // what was picked up only goes to the console with debug_log, the failures always do
+        let log = self.cvars.bool("debug_log").unwrap_or(false);
+        for path in self.asset_watcher.changes() {
+            if self.ui_screens.files().contains(&path) {
+                self.ui_screens.reload();
+                if log {
+                    println!("reloaded the ui scripts ({})", path.display());
+                }
+            }
+            if self.abilities.files().contains(&path) {
+                self.abilities.reload();
+                if log {
+                    println!("reloaded the abilities ({})", path.display());
+                }
+            }
+            if self.items.files().contains(&path) {
+                self.items.reload();
+                if log {
+                    println!("reloaded the items ({})", path.display());
+                }
+            }
+            if self.dialogues.files().contains(&path) {
+                self.dialogues.reload();
+                if log {
+                    println!("reloaded the dialogues ({})", path.display());
+                }
+            }
+            if self.strings.files().contains(&path) {
+                self.strings.reload();
+                if log {
+                    println!("reloaded the language {} ({})", self.strings.language(), path.display());
+                }
+            }
+            if let Some(handle) = self.textures.find_loaded(&path) {
+                match self.textures.reload(&self.device, &self.queue, handle) {
+                    Ok(old) => {
+                        let new = self.textures.shared(handle);
+                        let materials = self.world.replace_texture(&self.device, &self.texture_bind_group_layout, &old, &new);
+                        if log {
+                            println!("reloaded {} ({} materials)", path.display(), materials);
+                        }
+                    }
+                    Err(e) => eprintln!("{} was not reloaded: {:#}", path.display(), e),
+                }
+            }
+            if let Some((model_path, id)) = self.watched_models.get(&path).cloned() {
+                match model::Model::load(&model_path, &self.device, &self.queue, &self.texture_bind_group_layout) {
+                    Ok(model) => {
+                        self.world.replace_model(id, model);
+                        if log {
+                            println!("reloaded {}", model_path.display());
+                        }
+                    }
+                    // a half saved file fails to load, the next save tries again
+                    Err(e) => eprintln!("{} was not reloaded: {:#}", model_path.display(), e),
+                }
+            }
+        }
+    }
+
+    // the monitors connected now, for a settings menu
+    pub fn monitors(&self) -> anyhow::Result<Vec<MonitorInfo>> {
+        let video = self.context.video().map_err(anyhow::Error::msg)?;
+        monitors::list_monitors(&video)
+    }
+
+    pub fn refresh_rate(&self) -> u32 {
+        monitors::refresh_rate_of(&self.current_display)
+    }
+
+    // centers the window on another monitor, the frame limiter follows its refresh rate if it was following the old one
+    pub fn move_to_monitor(&mut self, index: i32) -> anyhow::Result<()> {
+        let video = self.context.video().map_err(anyhow::Error::msg)?;
+        let monitor = monitors::monitor_info(&video, index)?;
+        monitors::center_window_on(self.canvas.window_mut(), &monitor);
+        println!("the window went to the monitor {} ({})", monitor.index, monitor.name);
+        self.set_current_monitor(monitor);
+        Ok(())
+    }
+
+    // a borderless window over the given monitors (all of them with an empty list), for simulators and exhibitions
+    // the monitors should touch each other, a gap between them is part of the surface that nobody sees
+    pub fn span_monitors(&mut self, indices: &[i32]) -> anyhow::Result<()> {
+        let mut monitors = self.monitors()?;
+        if !indices.is_empty() {
+            monitors.retain(|monitor| indices.contains(&monitor.index));
+        }
+        let Some(bounds) = monitors::spanning_bounds(&monitors) else {
+            anyhow::bail!("none of the monitors {:?} is connected", indices);
+        };
+        let max_size = self.device.limits().max_texture_dimension_2d;
+        if bounds.width() > max_size || bounds.height() > max_size {
+            anyhow::bail!("the monitors cover {}x{} and the gpu can't make a surface bigger than {}", bounds.width(), bounds.height(), max_size);
+        }
+
+        if self.windowed_size.is_none() {
+            self.windowed_size = Some(self.canvas.window().size());
+        }
+        let window = self.canvas.window_mut();
+        window.set_bordered(false);
+        window.set_size(bounds.width(), bounds.height())?;
+        window.set_position(WindowPos::Positioned(bounds.x()), WindowPos::Positioned(bounds.y()));
+        self.resize_to(bounds.width(), bounds.height());
+        Ok(())
+    }
+
+    // back to the normal window centered on the monitor it was on
+    pub fn stop_spanning(&mut self) -> anyhow::Result<()> {
+        let Some((width, height)) = self.windowed_size.take() else { return Ok(()) };
+        let video = self.context.video().map_err(anyhow::Error::msg)?;
+        let monitor = monitors::monitor_info(&video, self.monitor)?;
+        let window = self.canvas.window_mut();
+        window.set_bordered(true);
+        window.set_size(width, height)?;
+        monitors::center_window_on(window, &monitor);
+        self.resize_to(width, height);
+        Ok(())
+    }
+
+    pub fn is_spanning(&self) -> bool {
+        self.windowed_size.is_some()
+    }
+
+    pub fn fullscreen(&self) -> FullscreenMode {
+        self.fullscreen
+    }
+
+    // windowed, borderless on the desktop or exclusive with a mode of the monitor (see monitors::display_modes)
+    // the surface and everything at its size are made again for the new size, the limiter follows the new refresh rate
+    pub fn set_fullscreen(&mut self, mode: FullscreenMode) -> anyhow::Result<()> {
+        if self.is_spanning() {
+            self.stop_spanning()?;
+        }
+        let window = self.canvas.window_mut();
+        match mode {
+            FullscreenMode::Windowed => window.set_fullscreen(FullscreenType::Off).map_err(anyhow::Error::msg)?,
+            FullscreenMode::Borderless => window.set_fullscreen(FullscreenType::Desktop).map_err(anyhow::Error::msg)?,
+            FullscreenMode::Exclusive(display_mode) => {
+                // the mode only counts for the true fullscreen, it has to be set before going to it
+                if window.fullscreen_state() == FullscreenType::True {
+                    window.set_fullscreen(FullscreenType::Off).map_err(anyhow::Error::msg)?;
+                }
+                window.set_display_mode(Some(display_mode)).map_err(anyhow::Error::msg)?;
+                window.set_fullscreen(FullscreenType::True).map_err(anyhow::Error::msg)?;
+            }
+        }
+        self.fullscreen = mode;
+
+        let video = self.context.video().map_err(anyhow::Error::msg)?;
+        let monitor = monitors::monitor_info(&video, self.canvas.window().display_index().unwrap_or(self.monitor))?;
+        if mode == FullscreenMode::Windowed {
+            monitors::center_window_on(self.canvas.window_mut(), &monitor);
+        }
+        self.set_current_monitor(monitor);
+        self.resize();
+        Ok(())
+    }
+
+    // the exclusive one takes the mode of the monitor with the resolution of the settings (or the biggest), it goes
+    // borderless when the monitor has no modes to give
+    fn fullscreen_mode(&self, settings: &EngineSettings) -> FullscreenMode {
+        if !settings.fullscreen {
+            return FullscreenMode::Windowed;
+        }
+        if !self.cvars.bool("fullscreen_exclusive").unwrap_or(false) {
+            return FullscreenMode::Borderless;
+        }
+        let modes = self.context.video().map_err(anyhow::Error::msg).and_then(|video| monitors::display_modes(&video, self.monitor));
+        match modes {
+            Ok(modes) => {
+                let wanted = modes.iter().find(|mode| settings.resolution == Some((mode.w as u32, mode.h as u32)));
+                wanted.or(modes.first()).map_or(FullscreenMode::Borderless, |mode| FullscreenMode::Exclusive(*mode))
+            }
+            Err(e) => {
+                eprintln!("{:#}", e);
+                FullscreenMode::Borderless
+            }
+        }
+    }
+
+    // the settings of the options menu: the window changes size (or goes fullscreen), the vsync and the controls
+    // follow and the file is saved
+    pub fn apply_settings(&mut self, settings: EngineSettings) {
+        let settings = settings.clamped();
+        if !settings.fullscreen {
+            let (width, height) = settings.resolution.unwrap_or((self.current_display.w as u32, self.current_display.h as u32));
+            if let Err(e) = self.canvas.window_mut().set_size(width, height) {
+                eprintln!("the window couldn't be {}x{}: {}", width, height, e);
+            }
+        }
+        if let Err(e) = self.set_fullscreen(self.fullscreen_mode(&settings)) {
+            eprintln!("the fullscreen couldn't change: {:#}", e);
+        }
+        if settings.vsync != self.settings.vsync {
+            self.set_present_mode(settings.present_mode(self.display.present_mode));
+        }
+        settings.apply_input(&mut self.input);
+        if let Err(e) = settings.save(SETTINGS_PATH) {
+            eprintln!("{} was not saved: {:#}", SETTINGS_PATH, e);
+        }
+        self.settings = settings;
+    }
+
+    // how many fixed steps the simulation runs per second, independent of the frame rate (and of the frame limit)
+    pub fn set_simulation_rate(&mut self, steps_per_second: f32) {
+        let max_steps = self.timestep.max_steps;
+        self.timestep = FixedTimestep::new(steps_per_second);
+        self.timestep.max_steps = max_steps;
+        let _ = self.cvars.set_internal("sim_rate", CvarValue::Float(steps_per_second));
+    }
+
+    // none runs as fast as it can, after this the limiter stops following the monitor
+    pub fn set_frame_limit(&mut self, fps: Option<u32>) {
+        self.limit_to_refresh_rate = false;
+        self.frame_limiter.set_fps(fps);
+        let _ = self.cvars.set_internal("fps_max", CvarValue::Int(fps.map_or(0, |fps| fps as i64)));
+    }
+
+    // back to the default, the refresh rate of the monitor the window is on
+    pub fn limit_to_refresh_rate(&mut self) {
+        self.limit_to_refresh_rate = true;
+        self.frame_limiter.set_fps(Some(self.refresh_rate()));
+        let _ = self.cvars.set_internal("fps_max", CvarValue::Int(-1));
+    }
+
+    fn set_current_monitor(&mut self, monitor: MonitorInfo) {
+        self.monitor = monitor.index;
+        let _ = self.cvars.set_internal("monitor", CvarValue::Int(monitor.index as i64));
+        self.current_display = monitor.mode;
+        if self.limit_to_refresh_rate {
+            self.frame_limiter.set_fps(Some(monitor.refresh_rate()));
+        }
+    }
+
+    // the player can drag the window to another monitor, once a frame we look if it happened
+    fn follow_window_monitor(&mut self) {
+        let Ok(index) = self.canvas.window().display_index() else { return };
+        if index == self.monitor {
+            return;
+        }
+        let monitor = self.context.video().map_err(anyhow::Error::msg).and_then(|video| monitors::monitor_info(&video, index));
+        if let Ok(monitor) = monitor {
+            self.set_current_monitor(monitor);
         }
     }
 
@@ -526,4 +1892,47 @@ impl App {
         self.last_frame = current_time;
         return delta_time
     }
-}
\ No newline at end of file
+
+    // one dab of the brush on the static batch (painted scenery doesn't move), it is baked again only if something changed
+    // the app draws a single model, so that is the one that gets painted
+    pub fn apply_brush(&mut self, brush: &mut InstanceBrush, surface: &dyn PaintSurface, ray: &Ray) -> BrushEdit {
+        let positions = self.static_instances.matrices().iter().map(|matrix| cgmath::Vector3::new(matrix[3][0], matrix[3][1], matrix[3][2])).collect::<Vec<_>>();
+        let edit = brush.dab(surface, ray, &positions);
+        if edit.is_empty() {
+            return edit;
+        }
+
+        // the removed indices come from the back, so the ones left stay valid
+        for index in &edit.removed {
+            self.static_instances.despawn_at(*index);
+        }
+        // the painted scenery is solid like the grid, the colliders of the erased ones go with the next sync
+        let bounds = self.world.model(self.default_model).bounds();
+        for painted in &edit.added {
+            let id = self.static_instances.spawn(Instance { position: painted.position, rotation: painted.rotation, scale: painted.scale }.to_raw().model);
+            if let Some(bounds) = &bounds {
+                self.collision.attach_instance(&self.static_instances, id, bounds, PickVolume::Box);
+            }
+        }
+        edit
+    }
+}
+// the picture of the demo screen: bands of color that scroll sideways and a bright line that rolls down like an old tv
+fn screen_pattern(time: f32, pixels: &mut [u8], width: u32, height: u32) {
+    let roll = (time * 0.25).fract() * height as f32;
+    // a little moving static over the colors, like a weak signal
+    let noise = Noise::new(SCREEN_STATIC_SEED);
+    for y in 0..height {
+        let line = if (y as f32 - roll).abs() < 2.0 { 1.0 } else { 0.0 };
+        for x in 0..width {
+            let phase = x as f32 / width as f32 * std::f32::consts::TAU + time;
+            let pixel = ((y * width + x) * 4) as usize;
+            let snow = noise.fbm3(x as f32 * 0.2, y as f32 * 0.2, time * 6.0, 3, 2.0, 0.5) * 0.15;
+            let channels = [phase.sin() + snow, (phase + 2.1).sin() + snow, (phase + 4.2).sin() + snow];
+            for (channel, value) in channels.iter().enumerate() {
+                pixels[pixel + channel] = ((value * 0.5 + 0.5 + line).min(1.0) * 255.0) as u8;
+            }
+            pixels[pixel + 3] = 255;
+        }
+    }
+}
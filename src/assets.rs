@@ -0,0 +1,119 @@
+// the assets loaded on background tasks of tokio, so a big scene doesn't stop the window while it loads
+// load_* gives the handle at once with a placeholder in its place (a grey texture, a model without meshes, the
+// shader of the engine with the same name) and App::finish_assets swaps in the real one when its task is done
+// the textures and the models are read and uploaded on the task (wgpu takes the resources from any thread), the
+// shaders only read there, they are validated when the pipelines that use them are made again
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+
+use anyhow::Context;
+use wgpu::{Device, Queue};
+
+use crate::rendering::model::Model;
+use crate::rendering::textures::{Texture, TextureHandle, TextureManager};
+use crate::scene::graph::{ModelId, SceneGraph};
+
+// what a task made, for the main thread to put in place of the placeholder
+pub enum LoadedAsset {
+    Texture { handle: TextureHandle, texture: Texture },
+    Model { id: ModelId, model: Model },
+    Shader { name: String, source: String },
+}
+
+struct Finished {
+    path: PathBuf,
+    result: anyhow::Result<LoadedAsset>,
+}
+
+pub struct AssetManager {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    texture_layout: Arc<wgpu::BindGroupLayout>, // the one of the materials, the models make their bind groups with it
+    sender: Sender<Finished>,
+    receiver: Receiver<Finished>,
+    loading: usize,
+}
+
+impl AssetManager {
+    pub fn new(device: Arc<Device>, queue: Arc<Queue>, texture_layout: Arc<wgpu::BindGroupLayout>) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self { device, queue, texture_layout, sender, receiver, loading: 0 }
+    }
+
+    // the texture of the path (relative to the root of the manager), the placeholder until it's read
+    // a path the manager already has gives its handle without loading it again
+    pub fn load_texture(&mut self, textures: &mut TextureManager, path: impl AsRef<Path>) -> anyhow::Result<TextureHandle> {
+        if let Some(handle) = textures.find(&path) {
+            return Ok(handle);
+        }
+        // each one has its own, the materials of the placeholder are the ones moved to the real texture
+        let placeholder = placeholder_texture(&self.device, &self.queue)?;
+        let file = textures.resolve(&path);
+        let handle = textures.insert(&path, placeholder);
+        let (device, queue) = (self.device.clone(), self.queue.clone());
+        self.spawn(path.as_ref().to_path_buf(), move || {
+            let texture = TextureManager::read(&device, &queue, &file)?;
+            Ok(LoadedAsset::Texture { handle, texture })
+        });
+        Ok(handle)
+    }
+
+    // an obj or a gltf, without meshes until it's loaded (nothing is drawn or picked for it)
+    pub fn load_model(&mut self, world: &mut SceneGraph, path: impl AsRef<Path>) -> ModelId {
+        let path = path.as_ref().to_path_buf();
+        let id = world.add_model(Model { meshes: Vec::new(), materials: Vec::new() });
+        let (device, queue, layout) = (self.device.clone(), self.queue.clone(), self.texture_layout.clone());
+        self.spawn(path.clone(), move || {
+            let model = Model::load(&path, &device, &queue, &layout)?;
+            Ok(LoadedAsset::Model { id, model })
+        });
+        id
+    }
+
+    // a wgsl file for the library under the name, the pipelines that use the name keep the embedded source until then
+    pub fn load_shader(&mut self, name: &str, path: impl AsRef<Path>) {
+        let path = path.as_ref().to_path_buf();
+        let name = name.to_string();
+        self.spawn(path.clone(), move || {
+            let source = std::fs::read_to_string(&path).with_context(|| format!("couldn't read the shader {}", path.display()))?;
+            crate::util::content_hash::verify_load(&path, source.as_bytes())?;
+            Ok(LoadedAsset::Shader { name, source })
+        });
+    }
+
+    fn spawn(&mut self, path: PathBuf, load: impl FnOnce() -> anyhow::Result<LoadedAsset> + Send + 'static) {
+        self.loading += 1;
+        let sender = self.sender.clone();
+        // the blocking pool, the files and the decoding would hold up the tasks of the net
+        tokio::task::spawn_blocking(move || {
+            let result = load();
+            // the manager is gone when the game closed while it loaded
+            let _ = sender.send(Finished { path, result });
+        });
+    }
+
+    // the loads that finished since the last call, the failed ones are reported and keep their placeholder
+    pub fn finished(&mut self) -> Vec<(PathBuf, LoadedAsset)> {
+        let mut loaded = Vec::new();
+        while let Ok(finished) = self.receiver.try_recv() {
+            self.loading -= 1;
+            match finished.result {
+                Ok(asset) => loaded.push((finished.path, asset)),
+                Err(e) => eprintln!("{} was not loaded: {:#}", finished.path.display(), e),
+            }
+        }
+        loaded
+    }
+
+    // how many are still on the way, the debug overlay shows it
+    pub fn loading(&self) -> usize {
+        self.loading
+    }
+}
+
+fn placeholder_texture(device: &Device, queue: &Queue) -> anyhow::Result<Texture> {
+    let image = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(1, 1, image::Rgba([128, 128, 128, 255])));
+    Texture::from_image(&image, device, queue, Some("placeholder"))
+}
@@ -0,0 +1,268 @@
+// the sounds of the game through sdl2 mixer: short effects loaded whole and played on one of the mixing channels,
+// and one music track streamed from disk. every sound goes to a bus (effects, ui, music) with its own volume under
+// the master one, and the effects and the music stop while the game is paused (the ui keeps sounding)
+// gameplay gets handles: a SoundId for a loaded file and a SoundHandle for one time it plays, the handle goes stale
+// when the sound ends and its channel is used by another, so stopping an old handle never stops the wrong sound
+// without an audio device (a server, a machine without sound) everything still works and nothing is heard
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail};
+use sdl2::mixer::{self, Channel, Chunk, InitFlag, Music, Sdl2MixerContext, AUDIO_S16LSB, DEFAULT_CHANNELS, MAX_VOLUME};
+
+use crate::util::{content_hash, vfs};
+
+const FREQUENCY: i32 = 44100;
+const CHUNK_SIZE: i32 = 1024; // samples per mix, about 23ms of latency
+const CHANNELS: i32 = 32; // effects at once, one more is dropped
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AudioBus {
+    Effects,
+    Ui, // not paused with the game
+    Music,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SoundId(usize);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SoundHandle {
+    channel: i32,
+    generation: u64,
+}
+
+struct Playing {
+    generation: u64,
+    bus: AudioBus,
+    volume: f32,
+}
+
+pub struct Audio {
+    _context: Option<Sdl2MixerContext>, // none without a device
+    root: PathBuf,
+    sounds: Vec<Chunk>,
+    by_path: HashMap<PathBuf, SoundId>,
+    failed: HashSet<PathBuf>, // reported once, not every time something asks for them
+    music: HashMap<PathBuf, Music<'static>>,
+    current_music: Option<PathBuf>,
+    playing: Vec<Option<Playing>>, // by channel
+    next_generation: u64,
+    master: f32,
+    buses: HashMap<AudioBus, f32>,
+    paused: bool,
+}
+
+impl Audio {
+    // paths given to load are relative to root, "./assets" for the game
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        let context = match Self::open() {
+            Ok(context) => Some(context),
+            Err(e) => {
+                eprintln!("there will be no sound: {:#}", e);
+                None
+            }
+        };
+        Self {
+            _context: context,
+            root: root.into(),
+            sounds: Vec::new(),
+            by_path: HashMap::new(),
+            failed: HashSet::new(),
+            music: HashMap::new(),
+            current_music: None,
+            playing: (0..CHANNELS).map(|_| None).collect(),
+            next_generation: 0,
+            master: 1.0,
+            buses: HashMap::from([(AudioBus::Effects, 1.0), (AudioBus::Ui, 1.0), (AudioBus::Music, 0.7)]),
+            paused: false,
+        }
+    }
+
+    fn open() -> anyhow::Result<Sdl2MixerContext> {
+        mixer::open_audio(FREQUENCY, AUDIO_S16LSB, DEFAULT_CHANNELS, CHUNK_SIZE).map_err(|e| anyhow!("the audio device couldn't be opened: {}", e))?;
+        // wav is always there, ogg and mp3 for the music
+        let context = mixer::init(InitFlag::OGG | InitFlag::MP3).map_err(|e| anyhow!("sdl2 mixer couldn't start: {}", e))?;
+        mixer::allocate_channels(CHANNELS);
+        Ok(context)
+    }
+
+    pub fn has_device(&self) -> bool {
+        self._context.is_some()
+    }
+
+    // the sound of the path, from the cache if it was loaded before
+    pub fn load(&mut self, path: impl AsRef<Path>) -> anyhow::Result<SoundId> {
+        let path = self.root.join(path);
+        if let Some(id) = self.by_path.get(&path) {
+            return Ok(*id);
+        }
+        if !self.has_device() {
+            bail!("there is no audio device");
+        }
+        let file = vfs::resolve(&path);
+        content_hash::verify_file(&file)?;
+        let chunk = Chunk::from_file(&file).map_err(|e| anyhow!("couldn't load the sound {}: {}", path.display(), e))?;
+        let id = SoundId(self.sounds.len());
+        self.sounds.push(chunk);
+        self.by_path.insert(path, id);
+        Ok(id)
+    }
+
+    pub fn play(&mut self, sound: SoundId, bus: AudioBus) -> Option<SoundHandle> {
+        self.play_with(sound, bus, 1.0, false)
+    }
+
+    // volume is of this sound alone, the bus and the master go on top. none when every channel is busy
+    pub fn play_with(&mut self, sound: SoundId, bus: AudioBus, volume: f32, looping: bool) -> Option<SoundHandle> {
+        let chunk = self.sounds.get(sound.0)?;
+        let Channel(channel) = Channel::all().play(chunk, if looping { -1 } else { 0 }).ok()?;
+        let generation = self.next_generation;
+        self.next_generation += 1;
+        self.playing[channel as usize] = Some(Playing { generation, bus, volume });
+        self.apply_volume(channel);
+        Some(SoundHandle { channel, generation })
+    }
+
+    // load and play in one go, for the gameplay that doesn't keep ids, a file that can't load is reported once
+    pub fn play_file(&mut self, path: impl AsRef<Path>, bus: AudioBus) -> Option<SoundHandle> {
+        let path = path.as_ref();
+        if self.failed.contains(path) {
+            return None;
+        }
+        match self.load(path) {
+            Ok(sound) => self.play(sound, bus),
+            Err(e) => {
+                if self.has_device() {
+                    eprintln!("{:#}", e);
+                }
+                self.failed.insert(path.to_path_buf());
+                None
+            }
+        }
+    }
+
+    fn playing(&self, handle: SoundHandle) -> Option<&Playing> {
+        self.playing.get(handle.channel as usize)?.as_ref().filter(|playing| playing.generation == handle.generation)
+    }
+
+    pub fn stop(&mut self, handle: SoundHandle) {
+        if self.playing(handle).is_some() {
+            Channel(handle.channel).halt();
+            self.playing[handle.channel as usize] = None;
+        }
+    }
+
+    // streamed from disk, the track before fades out while this one fades in, again with the same one does nothing
+    pub fn play_music(&mut self, path: impl AsRef<Path>, fade_ms: i32) -> anyhow::Result<()> {
+        let path = self.root.join(path);
+        if !self.has_device() || self.current_music.as_ref() == Some(&path) {
+            return Ok(());
+        }
+        if !self.music.contains_key(&path) {
+            let file = vfs::resolve(&path);
+            content_hash::verify_file(&file)?;
+            let music = Music::from_file(&file).map_err(|e| anyhow!("couldn't load the music {}: {}", path.display(), e))?;
+            self.music.insert(path.clone(), music);
+        }
+        let music = &self.music[&path];
+        music.fade_in(-1, fade_ms.max(0)).map_err(|e| anyhow!("the music couldn't play: {}", e))?;
+        self.current_music = Some(path);
+        self.apply_music_volume();
+        if self.paused {
+            Music::pause();
+        }
+        Ok(())
+    }
+
+    pub fn stop_music(&mut self, fade_ms: i32) {
+        if self.current_music.take().is_some() {
+            let _ = Music::fade_out(fade_ms.max(0));
+        }
+    }
+
+    pub fn set_master_volume(&mut self, volume: f32) {
+        self.master = volume.clamp(0.0, 1.0);
+        self.apply_all_volumes();
+    }
+
+    pub fn set_bus_volume(&mut self, bus: AudioBus, volume: f32) {
+        self.buses.insert(bus, volume.clamp(0.0, 1.0));
+        self.apply_all_volumes();
+    }
+
+    pub fn bus_volume(&self, bus: AudioBus) -> f32 {
+        self.buses.get(&bus).copied().unwrap_or(1.0)
+    }
+
+    // the app calls it every frame with the state of the game, it only does something when it changes
+    pub fn set_paused(&mut self, paused: bool) {
+        if paused == self.paused || !self.has_device() {
+            self.paused = paused;
+            return;
+        }
+        self.paused = paused;
+        for (channel, playing) in self.playing.iter().enumerate() {
+            if playing.as_ref().is_some_and(|playing| playing.bus != AudioBus::Ui) {
+                if paused {
+                    Channel(channel as i32).pause();
+                } else {
+                    Channel(channel as i32).resume();
+                }
+            }
+        }
+        if paused {
+            Music::pause();
+        } else {
+            Music::resume();
+        }
+    }
+
+    // once per frame, the channels that finished are free for new handles
+    pub fn update(&mut self) {
+        if !self.has_device() {
+            return;
+        }
+        for (channel, playing) in self.playing.iter_mut().enumerate() {
+            // a paused channel is still playing for sdl
+            if playing.is_some() && !Channel(channel as i32).is_playing() {
+                *playing = None;
+            }
+        }
+    }
+
+    fn apply_volume(&self, channel: i32) {
+        if let Some(playing) = &self.playing[channel as usize] {
+            let volume = self.master * self.bus_volume(playing.bus) * playing.volume.clamp(0.0, 1.0);
+            Channel(channel).set_volume((volume * MAX_VOLUME as f32) as i32);
+        }
+    }
+
+    fn apply_music_volume(&self) {
+        Music::set_volume((self.master * self.bus_volume(AudioBus::Music) * MAX_VOLUME as f32) as i32);
+    }
+
+    fn apply_all_volumes(&self) {
+        if !self.has_device() {
+            return;
+        }
+        for channel in 0..CHANNELS {
+            self.apply_volume(channel);
+        }
+        self.apply_music_volume();
+    }
+}
+
+impl Drop for Audio {
+    fn drop(&mut self) {
+        if self.has_device() {
+            // the chunks and the music have to go before the device closes
+            Channel::all().halt();
+            Music::halt();
+            self.sounds.clear();
+            self.music.clear();
+            mixer::close_audio();
+        }
+    }
+}
@@ -0,0 +1,303 @@
+// voice chat, only with the voice feature (it needs libopus): the default mic is captured through sdl2 while push to
+// talk is held, cut in 20ms frames and encoded with opus, the packets go out through the net layer (see net/voice.rs)
+// and the ones of the other players are decoded and mixed into a stereo device
+// there is no positional audio yet, so every voice is placed here with the distance to the listener and a pan from
+// the side it is on. when the sounds get their own mixer the voices should go through it instead
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use anyhow::anyhow;
+use cgmath::{InnerSpace, MetricSpace, Point3, Vector3};
+use opus::{Application, Channels, Decoder, Encoder};
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+use sdl2::AudioSubsystem;
+
+use crate::net::voice::{VoicePacket, MAX_VOICE_FRAME};
+
+pub const SAMPLE_RATE: u32 = 48000;
+pub const FRAME_SAMPLES: usize = 960; // 20ms, the frame opus is best at for voice
+
+// a voice starts playing with this many frames queued so a late packet doesn't cut it, more than the cap is dropped
+const JITTER_FRAMES: usize = 3;
+const MAX_QUEUED_FRAMES: usize = 25;
+// the decoder makes up at most this many lost frames, a longer hole is silence
+const MAX_CONCEALED_FRAMES: u16 = 3;
+
+// the mic pushes what it heard to the main thread, the callback can't encode (or allocate much)
+struct CaptureCallback {
+    samples: Sender<Vec<f32>>,
+}
+
+impl AudioCallback for CaptureCallback {
+    type Channel = f32;
+
+    fn callback(&mut self, samples: &mut [f32]) {
+        let _ = self.samples.send(samples.to_vec());
+    }
+}
+
+pub struct VoiceCapture {
+    device: AudioDevice<CaptureCallback>,
+    samples: Receiver<Vec<f32>>,
+    pending: Vec<f32>, // what is left after the last full frame
+    encoder: Encoder,
+    sequence: u16,
+    transmitting: bool,
+}
+
+impl VoiceCapture {
+    pub fn open(audio: &AudioSubsystem) -> anyhow::Result<Self> {
+        let desired = AudioSpecDesired { freq: Some(SAMPLE_RATE as i32), channels: Some(1), samples: Some(FRAME_SAMPLES as u16) };
+        let (sender, samples) = mpsc::channel();
+        let device = audio
+            .open_capture(None, &desired, |_| CaptureCallback { samples: sender })
+            .map_err(|e| anyhow!("the microphone couldn't be opened: {}", e))?;
+        let mut encoder = Encoder::new(SAMPLE_RATE, Channels::Mono, Application::Voip)?;
+        encoder.set_bitrate(opus::Bitrate::Bits(24000))?;
+        Ok(Self { device, samples, pending: Vec::with_capacity(FRAME_SAMPLES * 2), encoder, sequence: 0, transmitting: false })
+    }
+
+    // push to talk, the mic is only listened to while it is held
+    pub fn set_transmitting(&mut self, transmitting: bool) {
+        if transmitting == self.transmitting {
+            return;
+        }
+        self.transmitting = transmitting;
+        if transmitting {
+            self.device.resume();
+        } else {
+            self.device.pause();
+            // the half frame of the end is dropped, the next time starts clean
+            while self.samples.try_recv().is_ok() {}
+            self.pending.clear();
+        }
+    }
+
+    pub fn is_transmitting(&self) -> bool {
+        self.transmitting
+    }
+
+    // the frames encoded since the last call, the sender is filled in by the server
+    pub fn poll(&mut self) -> Vec<VoicePacket> {
+        while let Ok(samples) = self.samples.try_recv() {
+            self.pending.extend_from_slice(&samples);
+        }
+        let mut packets = Vec::new();
+        let mut output = [0u8; MAX_VOICE_FRAME];
+        while self.pending.len() >= FRAME_SAMPLES {
+            let frame: Vec<f32> = self.pending.drain(..FRAME_SAMPLES).collect();
+            match self.encoder.encode_float(&frame, &mut output) {
+                Ok(len) => {
+                    packets.push(VoicePacket { sender: 0, sequence: self.sequence, frame: output[..len].to_vec() });
+                    self.sequence = self.sequence.wrapping_add(1);
+                }
+                Err(e) => eprintln!("a voice frame couldn't be encoded: {}", e),
+            }
+        }
+        packets
+    }
+}
+
+// where the voices are heard from, the camera
+#[derive(Copy, Clone, Debug)]
+pub struct Listener {
+    pub position: Point3<f32>,
+    pub right: Vector3<f32>,
+}
+
+// the gain of the left and right channels of a voice at the position
+// full volume up to the reference distance, then it falls like 1/distance and it's silent past the max distance
+pub fn spatial_gains(listener: &Listener, position: Point3<f32>, reference_distance: f32, max_distance: f32) -> (f32, f32) {
+    let distance = listener.position.distance(position);
+    if distance >= max_distance {
+        return (0.0, 0.0);
+    }
+    let attenuation = reference_distance / distance.max(reference_distance);
+    // constant power, a voice in front is not quieter than one on the side
+    let pan = if distance > 0.001 { (position - listener.position).normalize().dot(listener.right.normalize()) } else { 0.0 };
+    let angle = (pan.clamp(-1.0, 1.0) + 1.0) * std::f32::consts::FRAC_PI_4;
+    (angle.cos() * attenuation, angle.sin() * attenuation)
+}
+
+struct Speaker {
+    samples: VecDeque<f32>, // decoded, mono
+    gains: (f32, f32),
+    buffering: bool, // waiting for the jitter frames before it plays
+}
+
+// the mixer runs in the audio thread, the main thread reaches it with AudioDevice::lock
+struct MixCallback {
+    speakers: HashMap<u32, Speaker>,
+    volume: f32,
+}
+
+impl AudioCallback for MixCallback {
+    type Channel = f32;
+
+    // stereo, the samples go left right left right
+    fn callback(&mut self, output: &mut [f32]) {
+        output.fill(0.0);
+        for speaker in self.speakers.values_mut() {
+            if speaker.buffering {
+                if speaker.samples.len() < JITTER_FRAMES * FRAME_SAMPLES {
+                    continue;
+                }
+                speaker.buffering = false;
+            }
+            for pair in output.chunks_exact_mut(2) {
+                let Some(sample) = speaker.samples.pop_front() else {
+                    speaker.buffering = true;
+                    break;
+                };
+                pair[0] += sample * speaker.gains.0 * self.volume;
+                pair[1] += sample * speaker.gains.1 * self.volume;
+            }
+        }
+        for sample in output.iter_mut() {
+            *sample = sample.clamp(-1.0, 1.0);
+        }
+    }
+}
+
+struct RemoteVoice {
+    decoder: Decoder,
+    last_sequence: Option<u16>,
+    position: Option<Point3<f32>>, // none plays in the middle at full volume (a radio, the lobby)
+}
+
+pub struct VoicePlayback {
+    device: AudioDevice<MixCallback>,
+    voices: HashMap<u32, RemoteVoice>,
+    listener: Listener,
+    pub reference_distance: f32,
+    pub max_distance: f32,
+}
+
+impl VoicePlayback {
+    pub fn open(audio: &AudioSubsystem) -> anyhow::Result<Self> {
+        let desired = AudioSpecDesired { freq: Some(SAMPLE_RATE as i32), channels: Some(2), samples: Some(FRAME_SAMPLES as u16) };
+        let device = audio
+            .open_playback(None, &desired, |_| MixCallback { speakers: HashMap::new(), volume: 1.0 })
+            .map_err(|e| anyhow!("the voice output couldn't be opened: {}", e))?;
+        device.resume();
+        Ok(Self {
+            device,
+            voices: HashMap::new(),
+            listener: Listener { position: Point3::new(0.0, 0.0, 0.0), right: Vector3::unit_x() },
+            reference_distance: 2.0,
+            max_distance: 40.0,
+        })
+    }
+
+    // a packet of another player, the frames lost before it are made up by the decoder
+    pub fn receive(&mut self, packet: &VoicePacket) -> anyhow::Result<()> {
+        if !self.voices.contains_key(&packet.sender) {
+            let decoder = Decoder::new(SAMPLE_RATE, Channels::Mono)?;
+            self.voices.insert(packet.sender, RemoteVoice { decoder, last_sequence: None, position: None });
+        }
+        let voice = self.voices.get_mut(&packet.sender).expect("inserted above");
+        let lost = match voice.last_sequence {
+            Some(last) => match packet.frames_lost_since(last) {
+                Some(lost) => lost,
+                None => return Ok(()), // late or repeated, it already played as a made up frame
+            },
+            None => 0,
+        };
+        voice.last_sequence = Some(packet.sequence);
+
+        let mut decoded = Vec::with_capacity(FRAME_SAMPLES * (lost.min(MAX_CONCEALED_FRAMES) as usize + 1));
+        let mut frame = [0f32; FRAME_SAMPLES];
+        for _ in 0..lost.min(MAX_CONCEALED_FRAMES) {
+            let len = voice.decoder.decode_float(&[], &mut frame, false)?;
+            decoded.extend_from_slice(&frame[..len]);
+        }
+        let len = voice.decoder.decode_float(&packet.frame, &mut frame, false)?;
+        decoded.extend_from_slice(&frame[..len]);
+
+        let gains = self.gains(packet.sender);
+        let mut mixer = self.device.lock();
+        let speaker = mixer.speakers.entry(packet.sender).or_insert_with(|| Speaker { samples: VecDeque::new(), gains, buffering: true });
+        speaker.samples.extend(decoded);
+        // a voice that fell behind (the audio thread stalled) skips ahead instead of staying late
+        let max = MAX_QUEUED_FRAMES * FRAME_SAMPLES;
+        if speaker.samples.len() > max {
+            let extra = speaker.samples.len() - max;
+            speaker.samples.drain(..extra);
+        }
+        Ok(())
+    }
+
+    // where the player that talks is, the gains follow it from the next mix
+    pub fn set_position(&mut self, sender: u32, position: Option<Point3<f32>>) {
+        if let Some(voice) = self.voices.get_mut(&sender) {
+            voice.position = position;
+        }
+        self.update_gains();
+    }
+
+    // once per frame with the camera
+    pub fn set_listener(&mut self, listener: Listener) {
+        self.listener = listener;
+        self.update_gains();
+    }
+
+    pub fn set_volume(&mut self, volume: f32) {
+        self.device.lock().volume = volume.max(0.0);
+    }
+
+    // the player left, what was queued is dropped
+    pub fn remove(&mut self, sender: u32) {
+        self.voices.remove(&sender);
+        self.device.lock().speakers.remove(&sender);
+    }
+
+    // the ones that have samples queued, for the icon over the players that talk
+    pub fn speaking(&mut self) -> Vec<u32> {
+        self.device.lock().speakers.iter().filter(|(_, speaker)| !speaker.samples.is_empty()).map(|(sender, _)| *sender).collect()
+    }
+
+    fn gains(&self, sender: u32) -> (f32, f32) {
+        match self.voices.get(&sender).and_then(|voice| voice.position) {
+            Some(position) => spatial_gains(&self.listener, position, self.reference_distance, self.max_distance),
+            None => (std::f32::consts::FRAC_1_SQRT_2, std::f32::consts::FRAC_1_SQRT_2),
+        }
+    }
+
+    fn update_gains(&mut self) {
+        let gains: Vec<(u32, (f32, f32))> = self.voices.keys().map(|sender| (*sender, self.gains(*sender))).collect();
+        let mut mixer = self.device.lock();
+        for (sender, gains) in gains {
+            if let Some(speaker) = mixer.speakers.get_mut(&sender) {
+                speaker.gains = gains;
+            }
+        }
+    }
+}
+
+// both ends together, what the app keeps while the voice is on
+pub struct VoiceChat {
+    pub capture: VoiceCapture,
+    pub playback: VoicePlayback,
+    pub loopback: bool, // your own voice comes back as if another player said it, to test the mic
+}
+
+impl VoiceChat {
+    pub fn open(audio: &AudioSubsystem) -> anyhow::Result<Self> {
+        Ok(Self { capture: VoiceCapture::open(audio)?, playback: VoicePlayback::open(audio)?, loopback: false })
+    }
+
+    // once per frame: the listener follows the camera and the frames of the mic are handed out to be sent
+    pub fn update(&mut self, listener: Listener) -> Vec<VoicePacket> {
+        self.playback.set_listener(listener);
+        let packets = self.capture.poll();
+        if self.loopback {
+            for packet in &packets {
+                if let Err(e) = self.playback.receive(packet) {
+                    eprintln!("{}", e);
+                }
+            }
+        }
+        packets
+    }
+}
@@ -0,0 +1,148 @@
+// the light baker: loads a static scene (obj or gltf), traces its light on the cpu (see rendering/lightmap_baker.rs)
+// and writes next to it what the lightmap path of the renderer reads:
+//   scene.lightmap.png  the sun and the sky, srgb
+//   scene.ao.png        the ambient occlusion alone, for the materials that want it apart
+//   scene.lightmap.ron  the lightmap uvs of every triangle of every mesh, in the order the model loader makes them
+// run it with: cargo run --release --bin pankarta-bake -- assets/scene.glb --samples 128 --sun 0.4,1,0.3
+// the other options: --density (texels per unit), --max-size (of the lightmap), --ao-distance and --seed
+
+// the shared modules have what only the client calls
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use cgmath::{EuclideanSpace, InnerSpace, Matrix, SquareMatrix, Transform, Vector3};
+use serde::Serialize;
+
+use rendering::lightmap_baker::{self, BakeSettings, Triangle};
+
+mod rendering {
+    pub mod lightmap_baker;
+}
+
+mod util {
+    pub mod rng;
+    pub mod color;
+}
+
+// the uvs of the lightmap, meshes[mesh][triangle] has the three corners of the triangle
+#[derive(Serialize)]
+struct LightmapUvs {
+    width: u32,
+    height: u32,
+    meshes: Vec<Vec<[[f32; 2]; 3]>>,
+}
+
+fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let mut scene: Option<PathBuf> = None;
+    let mut settings = BakeSettings::default();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().with_context(|| format!("{} needs a value", name));
+        match arg.as_str() {
+            "--samples" => settings.samples = value(&arg)?.parse()?,
+            "--density" => settings.texel_density = value(&arg)?.parse()?,
+            "--max-size" => settings.max_size = value(&arg)?.parse()?,
+            "--ao-distance" => settings.ao_distance = value(&arg)?.parse()?,
+            "--seed" => settings.seed = value(&arg)?.parse()?,
+            "--sun" => {
+                let parts: Vec<f32> = value(&arg)?.split(',').map(|part| part.trim().parse()).collect::<Result<_, _>>()?;
+                let [x, y, z] = parts[..] else { bail!("--sun is three numbers, x,y,z") };
+                settings.sun_direction = Vector3::new(x, y, z).normalize();
+            }
+            _ if arg.starts_with("--") => bail!("unknown option {}", arg),
+            _ => scene = Some(PathBuf::from(arg)),
+        }
+    }
+    let Some(scene) = scene else { bail!("usage: pankarta-bake <scene.obj|scene.gltf|scene.glb> [options]") };
+
+    let triangles = match scene.extension().and_then(|extension| extension.to_str()) {
+        Some("gltf") | Some("glb") => load_gltf(&scene)?,
+        _ => load_obj(&scene)?,
+    };
+    if triangles.is_empty() {
+        bail!("{} has no triangles", scene.display());
+    }
+    println!("baking {} triangles of {} with {} samples", triangles.len(), scene.display(), settings.samples);
+
+    let baked = lightmap_baker::bake(&triangles, &settings);
+    let (width, height) = (baked.layout.width, baked.layout.height);
+    let light_path = scene.with_extension("lightmap.png");
+    image::save_buffer(&light_path, &baked.light_rgba8(), width, height, image::ColorType::Rgba8)?;
+    let ao_path = scene.with_extension("ao.png");
+    image::save_buffer(&ao_path, &baked.occlusion_rgba8(), width, height, image::ColorType::Rgba8)?;
+
+    let mesh_count = triangles.iter().map(|triangle| triangle.mesh + 1).max().unwrap_or(0);
+    let mut uvs = LightmapUvs { width, height, meshes: vec![Vec::new(); mesh_count] };
+    for (index, triangle) in triangles.iter().enumerate() {
+        uvs.meshes[triangle.mesh].push(baked.layout.uvs(index));
+    }
+    let uvs_path = scene.with_extension("lightmap.ron");
+    std::fs::write(&uvs_path, ron::to_string(&uvs)?)?;
+
+    println!("{}x{} written to {}, {} and {}", width, height, light_path.display(), ao_path.display(), uvs_path.display());
+    Ok(())
+}
+
+fn triangles_of(mesh: usize, positions: &[Vector3<f32>], normals: Option<&[Vector3<f32>]>, indices: &[u32]) -> Vec<Triangle> {
+    indices
+        .chunks_exact(3)
+        .map(|corners| {
+            let corner = |i: usize| corners[i] as usize;
+            Triangle {
+                positions: [positions[corner(0)], positions[corner(1)], positions[corner(2)]],
+                normals: normals.map_or([Vector3::new(0.0, 0.0, 0.0); 3], |normals| [normals[corner(0)], normals[corner(1)], normals[corner(2)]]),
+                mesh,
+            }
+        })
+        .collect()
+}
+
+// the same meshes Model::load_obj makes, one for each object of the file
+fn load_obj(path: &Path) -> anyhow::Result<Vec<Triangle>> {
+    let (models, _) = tobj::load_obj(path, &tobj::LoadOptions { triangulate: true, single_index: true, ..Default::default() })?;
+    let mut triangles = Vec::new();
+    for (index, model) in models.iter().enumerate() {
+        let mesh = &model.mesh;
+        let positions: Vec<Vector3<f32>> = mesh.positions.chunks_exact(3).map(|p| Vector3::new(p[0], p[1], p[2])).collect();
+        let normals: Option<Vec<Vector3<f32>>> = (mesh.normals.len() == mesh.positions.len()).then(|| mesh.normals.chunks_exact(3).map(|n| Vector3::new(n[0], n[1], n[2])).collect());
+        triangles.extend(triangles_of(index, &positions, normals.as_deref(), &mesh.indices));
+    }
+    Ok(triangles)
+}
+
+// the same walk of the nodes as Model::load_gltf, so the meshes come in the same order with the transforms baked in
+fn load_gltf(path: &Path) -> anyhow::Result<Vec<Triangle>> {
+    let (document, buffers, _) = gltf::import(path)?;
+    let scene = document.default_scene().or_else(|| document.scenes().next()).with_context(|| format!("{} has no scenes", path.display()))?;
+    let mut triangles = Vec::new();
+    let mut mesh_index = 0;
+    let mut stack: Vec<(gltf::Node, cgmath::Matrix4<f32>)> = scene.nodes().map(|node| (node, cgmath::Matrix4::identity())).collect();
+    while let Some((node, parent)) = stack.pop() {
+        let transform = parent * cgmath::Matrix4::from(node.transform().matrix());
+        stack.extend(node.children().map(|child| (child, transform)));
+
+        let Some(mesh) = node.mesh() else { continue };
+        let normal_matrix = transform.invert().map_or(transform, |inverse| inverse.transpose());
+        let mirrored = transform.determinant() < 0.0;
+        for primitive in mesh.primitives() {
+            if primitive.mode() != gltf::mesh::Mode::Triangles {
+                continue;
+            }
+            let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+            let Some(positions) = reader.read_positions() else { continue };
+            let positions: Vec<Vector3<f32>> = positions.map(|position| transform.transform_point(cgmath::Point3::from(position)).to_vec()).collect();
+            let normals: Option<Vec<Vector3<f32>>> = reader.read_normals().map(|normals| normals.map(|normal| normal_matrix.transform_vector(Vector3::from(normal)).normalize()).collect());
+            let mut indices: Vec<u32> = reader.read_indices().map_or_else(|| (0..positions.len() as u32).collect(), |indices| indices.into_u32().collect());
+            if mirrored {
+                for triangle in indices.chunks_exact_mut(3) {
+                    triangle.swap(1, 2);
+                }
+            }
+            triangles.extend(triangles_of(mesh_index, &positions, normals.as_deref(), &indices));
+            mesh_index += 1;
+        }
+    }
+    Ok(triangles)
+}
@@ -0,0 +1,182 @@
+// a small editor for a Curve in the debug ui: the curve drawn over its range with a square on each key
+// a click on the empty graph adds a key there, a key can be dragged and the right click (or delete) removes it,
+// tab goes through the interpolations. the curve changes at once, the systems that evaluate it see it the same frame
+
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::ttf::Font;
+
+use crate::input::input_state::{InputButton, InputState};
+use crate::ui::scale::UiSettings;
+use crate::ui::text::TextRenderer;
+use crate::ui::ui_renderer::UiRenderer;
+use crate::util::curve::{Curve, Interpolation};
+
+// design pixels like the rest of the ui
+const GRAPH_WIDTH: f32 = 320.0;
+const GRAPH_HEIGHT: f32 = 140.0;
+const PADDING: f32 = 8.0;
+const KEY_SIZE: f32 = 8.0;
+const SAMPLES: usize = 96; // the curve is drawn as a bar between each two samples
+
+pub struct CurveEditor {
+    open: bool,
+    pub title: String,
+    pub time_range: (f32, f32),  // the left and the right of the graph
+    pub value_range: (f32, f32), // the bottom and the top
+    selected: Option<usize>,
+    dragging: bool,
+    pub background: Color,
+    pub graph_color: Color,
+    pub line_color: Color,
+    pub key_color: Color,
+    pub selected_color: Color,
+    pub text_color: Color,
+}
+
+impl CurveEditor {
+    pub fn new(title: &str, time_range: (f32, f32), value_range: (f32, f32)) -> Self {
+        Self {
+            open: false,
+            title: title.to_string(),
+            time_range,
+            value_range,
+            selected: None,
+            dragging: false,
+            background: Color::RGBA(0, 0, 0, 170),
+            graph_color: Color::RGBA(20, 20, 20, 200),
+            line_color: Color::RGB(120, 200, 255),
+            key_color: Color::WHITE,
+            selected_color: Color::RGB(255, 200, 60),
+            text_color: Color::WHITE,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+        self.selected = None;
+        self.dragging = false;
+    }
+
+    // the graph, in the bottom left corner with the line of text over it
+    fn graph_rect(&self, screen_height: u32) -> Rect {
+        let settings = UiSettings::current();
+        let padding = settings.px(PADDING);
+        let (width, height) = (settings.px(GRAPH_WIDTH).max(1), settings.px(GRAPH_HEIGHT).max(1));
+        Rect::new(padding * 2, screen_height as i32 - height - padding * 2, width as u32, height as u32)
+    }
+
+    fn to_screen(&self, area: Rect, time: f32, value: f32) -> (i32, i32) {
+        let x = (time - self.time_range.0) / (self.time_range.1 - self.time_range.0).max(f32::EPSILON);
+        let y = (value - self.value_range.0) / (self.value_range.1 - self.value_range.0).max(f32::EPSILON);
+        (area.x() + (x * area.width() as f32) as i32, area.bottom() - (y * area.height() as f32) as i32)
+    }
+
+    // the point of the graph under the mouse, kept inside the ranges
+    fn to_graph(&self, area: Rect, (x, y): (i32, i32)) -> (f32, f32) {
+        let x = ((x - area.x()) as f32 / area.width() as f32).clamp(0.0, 1.0);
+        let y = ((area.bottom() - y) as f32 / area.height() as f32).clamp(0.0, 1.0);
+        (self.time_range.0 + x * (self.time_range.1 - self.time_range.0), self.value_range.0 + y * (self.value_range.1 - self.value_range.0))
+    }
+
+    fn key_at(&self, curve: &Curve, area: Rect, mouse: (i32, i32)) -> Option<usize> {
+        let reach = UiSettings::current().px(KEY_SIZE);
+        curve.keys().iter().position(|key| {
+            let (x, y) = self.to_screen(area, key.time, key.value);
+            (x - mouse.0).abs() <= reach && (y - mouse.1).abs() <= reach
+        })
+    }
+
+    // true while it has the mouse (over the graph or dragging a key), the game doesn't take the click then
+    pub fn handle_input(&mut self, input: &InputState, curve: &mut Curve, screen_height: u32) -> bool {
+        if !self.open {
+            return false;
+        }
+        let area = self.graph_rect(screen_height);
+        let mouse = input.mouse_position();
+        let over = area.contains_point(mouse);
+
+        if input.just_pressed(InputButton::Key(Keycode::Tab)) {
+            curve.interpolation = match curve.interpolation {
+                Interpolation::Step => Interpolation::Linear,
+                Interpolation::Linear => Interpolation::Smooth,
+                Interpolation::Smooth => Interpolation::Step,
+            };
+        }
+        if let (true, Some(selected)) = (input.just_pressed(InputButton::Key(Keycode::Delete)), self.selected) {
+            curve.remove_key(selected);
+            self.selected = None;
+            self.dragging = false;
+        }
+
+        if over && input.just_pressed(InputButton::Mouse(MouseButton::Left)) {
+            let (time, value) = self.to_graph(area, mouse);
+            self.selected = Some(self.key_at(curve, area, mouse).unwrap_or_else(|| curve.add_key(time, value)));
+            self.dragging = true;
+        } else if self.dragging && input.is_pressed(InputButton::Mouse(MouseButton::Left)) {
+            if let Some(selected) = self.selected {
+                let (time, value) = self.to_graph(area, mouse);
+                // the key keeps its place if another one is already at that time
+                if !curve.keys().iter().enumerate().any(|(index, key)| index != selected && key.time == time) {
+                    self.selected = curve.move_key(selected, time, value);
+                }
+            }
+        }
+        if input.just_released(InputButton::Mouse(MouseButton::Left)) {
+            self.dragging = false;
+        }
+        if over && input.just_pressed(InputButton::Mouse(MouseButton::Right)) {
+            if let Some(index) = self.key_at(curve, area, mouse) {
+                curve.remove_key(index);
+                self.selected = None;
+            }
+        }
+        over || self.dragging
+    }
+
+    pub fn draw(&self, ui: &mut UiRenderer, text: &mut TextRenderer, font: &Font, curve: &Curve, screen_height: u32) {
+        if !self.open {
+            return;
+        }
+        let settings = UiSettings::current();
+        let padding = settings.px(PADDING);
+        let area = self.graph_rect(screen_height);
+        let line_height = font.height();
+        let panel = Rect::new(area.x() - padding, area.y() - padding * 2 - line_height, area.width() + padding as u32 * 2, area.height() + (padding * 3 + line_height) as u32);
+        ui.draw_rect(panel, self.background);
+        ui.draw_rect(area, self.graph_color);
+
+        let interpolation = match curve.interpolation {
+            Interpolation::Step => "step",
+            Interpolation::Linear => "linear",
+            Interpolation::Smooth => "smooth",
+        };
+        let key = self.selected.and_then(|index| curve.keys().get(index)).map_or(String::new(), |key| format!("  key {:.2} = {:.2}", key.time, key.value));
+        text.draw_text(font, &format!("{} ({}){}", self.title, interpolation, key), area.x(), area.y() - padding - line_height, self.text_color);
+
+        // a bar from each sample to the next one, so the steep parts are not dots
+        let thickness = settings.px(2.0).max(1);
+        let mut previous: Option<(i32, i32)> = None;
+        for sample in 0..=SAMPLES {
+            let time = self.time_range.0 + (self.time_range.1 - self.time_range.0) * sample as f32 / SAMPLES as f32;
+            let (x, y) = self.to_screen(area, time, curve.evaluate(time));
+            let y = y.clamp(area.y(), area.bottom() - thickness);
+            if let Some((previous_x, previous_y)) = previous {
+                let top = y.min(previous_y);
+                let height = (y - previous_y).abs() + thickness;
+                ui.draw_rect(Rect::new(previous_x, top, (x - previous_x).max(thickness) as u32, height as u32), self.line_color);
+            }
+            previous = Some((x, y));
+        }
+
+        let size = settings.px(KEY_SIZE).max(2);
+        for (index, key) in curve.keys().iter().enumerate() {
+            let (x, y) = self.to_screen(area, key.time, key.value);
+            let rect = Rect::new(x - size / 2, y - size / 2, size as u32, size as u32);
+            let color = if self.selected == Some(index) { self.selected_color } else { self.key_color };
+            ui.draw_rect(rect, color);
+        }
+    }
+}
@@ -0,0 +1,182 @@
+// a record of the passes of one frame, what each one reads and writes and which passes depend on which
+// nothing is recorded until a dump is asked, then the next frame is captured and written as a graphviz file
+// (dot -Tpng frame_graph.dot -o frame_graph.png) and as text to the console
+
+use std::borrow::Cow;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PassKind {
+    Render,
+    Compute,
+    Copy,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ResourceKind {
+    Texture,
+    Buffer,
+    Surface,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResourceRef {
+    pub name: Cow<'static, str>, // the passes are declared every frame, the usual names don't allocate
+    pub kind: ResourceKind,
+}
+
+pub fn texture(name: impl Into<Cow<'static, str>>) -> ResourceRef {
+    ResourceRef { name: name.into(), kind: ResourceKind::Texture }
+}
+
+pub fn buffer(name: impl Into<Cow<'static, str>>) -> ResourceRef {
+    ResourceRef { name: name.into(), kind: ResourceKind::Buffer }
+}
+
+pub fn surface(name: impl Into<Cow<'static, str>>) -> ResourceRef {
+    ResourceRef { name: name.into(), kind: ResourceKind::Surface }
+}
+
+#[derive(Clone, Debug)]
+pub struct PassNode {
+    pub name: String,
+    pub kind: PassKind,
+    pub reads: Vec<ResourceRef>,
+    pub writes: Vec<ResourceRef>,
+}
+
+// pass `from` wrote the resource that pass `to` uses
+#[derive(Clone, Debug)]
+pub struct Dependency {
+    pub from: usize,
+    pub to: usize,
+    pub resource: String,
+}
+
+pub struct FrameGraph {
+    passes: Vec<PassNode>,
+    recording: bool,
+    dump_to: Option<PathBuf>,
+    last: Vec<PassNode>, // the last captured frame, for the debug ui
+}
+
+impl FrameGraph {
+    pub fn new() -> Self {
+        Self { passes: Vec::new(), recording: false, dump_to: None, last: Vec::new() }
+    }
+
+    // the next frame is captured and written to the path
+    pub fn request_dump(&mut self, path: impl Into<PathBuf>) {
+        self.dump_to = Some(path.into());
+    }
+
+    pub fn begin_frame(&mut self) {
+        self.passes.clear();
+        self.recording = self.dump_to.is_some();
+    }
+
+    // the passes only have to be declared when this is true, so a normal frame doesn't build strings
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    // declare the passes in the same order they are encoded
+    pub fn add_pass(&mut self, name: &str, kind: PassKind, reads: &[ResourceRef], writes: &[ResourceRef]) {
+        if !self.recording {
+            return;
+        }
+        self.passes.push(PassNode { name: name.to_string(), kind, reads: reads.to_vec(), writes: writes.to_vec() });
+    }
+
+    // the passes of a frame that were declared somewhere else (see render_passes::FramePasses), in order
+    pub fn add_nodes(&mut self, nodes: Vec<PassNode>) {
+        if self.recording {
+            self.passes.extend(nodes);
+        }
+    }
+
+    pub fn end_frame(&mut self) {
+        if !self.recording {
+            return;
+        }
+        self.recording = false;
+        self.last = std::mem::take(&mut self.passes);
+
+        let Some(path) = self.dump_to.take() else { return };
+        println!("{}", format_text(&self.last));
+        match std::fs::write(&path, to_dot(&self.last)) {
+            Ok(_) => println!("frame graph written to {}", path.display()),
+            Err(e) => eprintln!("failed to write the frame graph to {}: {}", path.display(), e),
+        }
+    }
+}
+
+// every pass depends on the last pass before it that wrote something it reads or writes
+pub fn dependencies(passes: &[PassNode]) -> Vec<Dependency> {
+    let mut dependencies = Vec::new();
+    for (to, pass) in passes.iter().enumerate() {
+        for resource in pass.reads.iter().chain(pass.writes.iter()) {
+            let writer = passes[..to].iter().rposition(|previous| previous.writes.iter().any(|written| written.name == resource.name));
+            if let Some(from) = writer {
+                let exists = dependencies.iter().any(|d: &Dependency| d.from == from && d.to == to && d.resource == resource.name);
+                if !exists {
+                    dependencies.push(Dependency { from, to, resource: resource.name.to_string() });
+                }
+            }
+        }
+    }
+    dependencies
+}
+
+// passes are boxes and resources are ellipses, the arrows go from what is read to the pass and from the pass to what it writes
+pub fn to_dot(passes: &[PassNode]) -> String {
+    let mut dot = String::from("digraph frame {\n    rankdir=LR;\n    node [fontname=\"monospace\"];\n");
+
+    let mut resources: Vec<&ResourceRef> = Vec::new();
+    for resource in passes.iter().flat_map(|pass| pass.reads.iter().chain(pass.writes.iter())) {
+        if !resources.iter().any(|known| known.name == resource.name) {
+            resources.push(resource);
+        }
+    }
+    for resource in &resources {
+        let color = match resource.kind {
+            ResourceKind::Texture => "lightblue",
+            ResourceKind::Buffer => "lightyellow",
+            ResourceKind::Surface => "lightgreen",
+        };
+        let _ = writeln!(dot, "    \"res_{}\" [label=\"{}\" shape=ellipse style=filled fillcolor={}];", resource.name, resource.name, color);
+    }
+
+    for (index, pass) in passes.iter().enumerate() {
+        let kind = match pass.kind {
+            PassKind::Render => "render",
+            PassKind::Compute => "compute",
+            PassKind::Copy => "copy",
+        };
+        let _ = writeln!(dot, "    \"pass_{}\" [label=\"{}. {}\\n({})\" shape=box style=bold];", index, index, pass.name, kind);
+        for read in &pass.reads {
+            let _ = writeln!(dot, "    \"res_{}\" -> \"pass_{}\";", read.name, index);
+        }
+        for write in &pass.writes {
+            let _ = writeln!(dot, "    \"pass_{}\" -> \"res_{}\" [color=red];", index, write.name);
+        }
+    }
+
+    dot.push_str("}\n");
+    dot
+}
+
+pub fn format_text(passes: &[PassNode]) -> String {
+    let mut text = String::from("frame graph:\n");
+    let names = |resources: &[ResourceRef]| resources.iter().map(|r| r.name.as_ref()).collect::<Vec<_>>().join(", ");
+    for (index, pass) in passes.iter().enumerate() {
+        let _ = writeln!(text, "  {}. {} ({:?})", index, pass.name, pass.kind);
+        let _ = writeln!(text, "     reads:  {}", names(&pass.reads));
+        let _ = writeln!(text, "     writes: {}", names(&pass.writes));
+    }
+    for dependency in dependencies(passes) {
+        let _ = writeln!(text, "  {} -> {} (by {})", passes[dependency.from].name, passes[dependency.to].name, dependency.resource);
+    }
+    text
+}
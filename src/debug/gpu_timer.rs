@@ -0,0 +1,180 @@
+// gpu timings for each pass using timestamp queries, only works when the adapter has TIMESTAMP_QUERY
+// the results of a frame are read some frames later so we never wait for the gpu to finish
+
+use std::cell::{Cell, RefCell};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use wgpu::{Device, Queue};
+
+use super::profiler::Profiler;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum ReadbackState {
+    Idle,      // the readback buffer is free, we can write timings of this frame
+    Resolved,  // the timings were copied this frame, we have to map after submitting
+    Mapping,   // waiting for the gpu to give us the buffer
+}
+
+pub struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    max_passes: u32,
+    period: f32, // nanoseconds per tick
+    passes: RefCell<Vec<&'static str>>, // the passes of the frame being recorded
+    mapped_passes: RefCell<Vec<&'static str>>, // the passes whose timings are inside the readback buffer
+    state: Cell<ReadbackState>,
+    ready: Arc<AtomicBool>,
+    map_ok: Arc<AtomicBool>,
+    results: RefCell<Vec<(&'static str, f32)>>,
+}
+
+impl GpuTimer {
+    // the device must have been created with Features::TIMESTAMP_QUERY, if not we return None
+    pub fn new(device: &Device, queue: &Queue, max_passes: u32) -> Option<Self> {
+        if !device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+
+        // every pass writes a timestamp at the start and at the end
+        let count = max_passes * 2;
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("GPU Timer Query Set"),
+            ty: wgpu::QueryType::Timestamp,
+            count,
+        });
+        let size = (count as u64) * std::mem::size_of::<u64>() as u64;
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("GPU Timer Resolve Buffer"),
+            size,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("GPU Timer Readback Buffer"),
+            size,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        Some(Self {
+            query_set,
+            resolve_buffer,
+            readback_buffer,
+            max_passes,
+            period: queue.get_timestamp_period(),
+            passes: RefCell::new(Vec::new()),
+            mapped_passes: RefCell::new(Vec::new()),
+            state: Cell::new(ReadbackState::Idle),
+            ready: Arc::new(AtomicBool::new(false)),
+            map_ok: Arc::new(AtomicBool::new(false)),
+            results: RefCell::new(Vec::new()),
+        })
+    }
+
+    // call before recording the passes of a frame, if the timings of an older frame arrived they are read here
+    pub fn begin_frame(&self, device: &Device) {
+        self.passes.borrow_mut().clear();
+
+        if self.state.get() != ReadbackState::Mapping {
+            return;
+        }
+        device.poll(wgpu::Maintain::Poll);
+        if !self.ready.load(Ordering::Acquire) {
+            return;
+        }
+        self.ready.store(false, Ordering::Release);
+        self.state.set(ReadbackState::Idle);
+
+        // if the map failed there is nothing to read, we just try again with a newer frame
+        if !self.map_ok.load(Ordering::Acquire) {
+            return;
+        }
+
+        {
+            let data = self.readback_buffer.slice(..).get_mapped_range();
+            let timestamps: &[u64] = bytemuck::cast_slice(&data);
+            let mut results = self.results.borrow_mut();
+            results.clear();
+            for (i, name) in self.mapped_passes.borrow().iter().enumerate() {
+                let start = timestamps[i * 2];
+                let end = timestamps[i * 2 + 1];
+                let ms = end.wrapping_sub(start) as f32 * self.period / 1_000_000.0;
+                results.push((*name, ms));
+            }
+        }
+        self.readback_buffer.unmap();
+    }
+
+    // the timestamp writes for a render pass, None when we are out of queries or still waiting for an old frame
+    pub fn render_pass_writes(&self, name: &'static str) -> Option<wgpu::RenderPassTimestampWrites<'_>> {
+        let index = self.allocate(name)?;
+        Some(wgpu::RenderPassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(index * 2),
+            end_of_pass_write_index: Some(index * 2 + 1),
+        })
+    }
+
+    pub fn compute_pass_writes(&self, name: &'static str) -> Option<wgpu::ComputePassTimestampWrites<'_>> {
+        let index = self.allocate(name)?;
+        Some(wgpu::ComputePassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(index * 2),
+            end_of_pass_write_index: Some(index * 2 + 1),
+        })
+    }
+
+    fn allocate(&self, name: &'static str) -> Option<u32> {
+        if self.state.get() != ReadbackState::Idle {
+            return None;
+        }
+        let mut passes = self.passes.borrow_mut();
+        if passes.len() as u32 >= self.max_passes {
+            return None;
+        }
+        passes.push(name);
+        Some(passes.len() as u32 - 1)
+    }
+
+    // call after every pass of the frame was recorded, this copies the timestamps into the readback buffer
+    pub fn resolve(&self, encoder: &mut wgpu::CommandEncoder) {
+        let passes = self.passes.borrow();
+        if self.state.get() != ReadbackState::Idle || passes.is_empty() {
+            return;
+        }
+        let count = passes.len() as u32 * 2;
+        encoder.resolve_query_set(&self.query_set, 0..count, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(&self.resolve_buffer, 0, &self.readback_buffer, 0, count as u64 * std::mem::size_of::<u64>() as u64);
+        *self.mapped_passes.borrow_mut() = passes.clone();
+        self.state.set(ReadbackState::Resolved);
+    }
+
+    // call after queue.submit, it starts the map of the readback buffer without waiting for it
+    pub fn after_submit(&self) {
+        if self.state.get() != ReadbackState::Resolved {
+            return;
+        }
+        let ready = self.ready.clone();
+        let map_ok = self.map_ok.clone();
+        self.readback_buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            map_ok.store(result.is_ok(), Ordering::Release);
+            ready.store(true, Ordering::Release);
+        });
+        self.state.set(ReadbackState::Mapping);
+    }
+
+    // the last timings we got from the gpu, in milliseconds
+    pub fn results(&self) -> Vec<(&'static str, f32)> {
+        self.results.borrow().clone()
+    }
+
+    // sends the last timings to the cpu profiler so they show next to the cpu zones
+    pub fn report_to_profiler(&self) {
+        for (name, ms) in self.results.borrow().iter() {
+            Profiler::record(name, Duration::from_secs_f32(ms / 1000.0));
+        }
+    }
+}
@@ -0,0 +1,163 @@
+// the debug overlay of F3: the frame times of the last seconds as a graph, where the camera is, how much is in the
+// scene and which gpu this is. drawn with the ui and text passes every frame it's on, nothing is kept between frames
+// the frame times are recorded while it's hidden too so the graph is already full when it opens
+
+use std::collections::VecDeque;
+
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::ttf::Font;
+use crate::debug::profiler::Profiler;
+
+use crate::rendering::batching::BatchStats;
+use crate::ui::scale::UiSettings;
+use crate::ui::text::TextRenderer;
+use crate::ui::ui_renderer::UiRenderer;
+
+const FRAME_SAMPLES: usize = 240;
+const GRAPH_WIDTH: f32 = 240.0; // design pixels, one sample per pixel
+const GRAPH_HEIGHT: f32 = 60.0;
+const GRAPH_MAX_MS: f32 = 50.0; // the top of the graph, a longer frame is cut there
+const PADDING: f32 = 8.0;
+
+// what the app knows this frame, the overlay doesn't reach into it
+pub struct OverlayStats<'a> {
+    pub camera_eye: [f32; 3],
+    pub camera_target: [f32; 3],
+    pub fovy: f32,
+    pub entities: usize,
+    pub static_instances: usize,
+    pub textures: usize,
+    pub assets_loading: usize,
+    pub draws: BatchStats, // of the main pass
+    pub debug_view: &'a str,
+    pub present_mode: wgpu::PresentMode, // the one the surface really uses, not the one asked for
+    pub frames_in_flight: usize,
+    pub gpu_times: Vec<(&'static str, f32)>, // empty without timestamp queries
+}
+
+pub struct DebugOverlay {
+    visible: bool,
+    profiler: bool, // the zones of the last frame under the stats (F2)
+    frame_times: VecDeque<f32>, // milliseconds, the newest last
+    adapter: String,
+    backend: String,
+    pub background: Color,
+    pub text_color: Color,
+}
+
+impl DebugOverlay {
+    pub fn new(adapter: &wgpu::AdapterInfo) -> Self {
+        Self {
+            visible: false,
+            profiler: false,
+            frame_times: VecDeque::with_capacity(FRAME_SAMPLES),
+            adapter: format!("{} ({:?})", adapter.name, adapter.device_type),
+            backend: format!("{:?} {} {}", adapter.backend, adapter.driver, adapter.driver_info).trim().to_string(),
+            background: Color::RGBA(0, 0, 0, 170),
+            text_color: Color::WHITE,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    // the profiler shows up with the overlay, so opening it opens the overlay too
+    pub fn toggle_profiler(&mut self) {
+        self.profiler = !self.profiler;
+        self.visible |= self.profiler;
+        Profiler::set_enabled(self.profiler);
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    // every frame, with the real time of the frame (not the one of the simulation, that one stops when paused)
+    pub fn record_frame(&mut self, delta_time: f32) {
+        if self.frame_times.len() == FRAME_SAMPLES {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(delta_time * 1000.0);
+    }
+
+    // the average and the worst of the samples, in milliseconds
+    pub fn frame_time(&self) -> (f32, f32) {
+        if self.frame_times.is_empty() {
+            return (0.0, 0.0);
+        }
+        let sum: f32 = self.frame_times.iter().sum();
+        let worst = self.frame_times.iter().copied().fold(0.0, f32::max);
+        (sum / self.frame_times.len() as f32, worst)
+    }
+
+    pub fn draw(&self, ui: &mut UiRenderer, text: &mut TextRenderer, font: &Font, stats: &OverlayStats, screen_width: u32) {
+        if !self.visible {
+            return;
+        }
+        let settings = UiSettings::current();
+        let padding = settings.px(PADDING);
+        let line_height = font.height();
+        let (average, worst) = self.frame_time();
+        let fps = if average > 0.0 { 1000.0 / average } else { 0.0 };
+
+        let [eye_x, eye_y, eye_z] = stats.camera_eye;
+        let [target_x, target_y, target_z] = stats.camera_target;
+        let mut lines = vec![
+            format!("{:.0} fps  {:.2} ms (worst {:.2})", fps, average, worst),
+            format!("camera ({:.2}, {:.2}, {:.2}) looking at ({:.2}, {:.2}, {:.2})", eye_x, eye_y, eye_z, target_x, target_y, target_z),
+            format!("fov {:.0}  view {}  present {:?} ({} frames in flight)", stats.fovy, stats.debug_view, stats.present_mode, stats.frames_in_flight),
+            format!("{} entities  {} static instances  {} textures  {} assets loading", stats.entities, stats.static_instances, stats.textures, stats.assets_loading),
+            format!("{} mesh draws ({} merged)  {} material binds ({} without sorting)", stats.draws.draws_after, stats.draws.saved_draws(), stats.draws.material_switches_after, stats.draws.material_switches_before),
+            self.adapter.clone(),
+            self.backend.clone(),
+        ];
+        lines.extend(stats.gpu_times.iter().map(|(name, ms)| format!("gpu {} {:.2} ms", name, ms)));
+        if self.profiler {
+            lines.extend(Profiler::last_frame().format_tree().lines().map(str::to_string));
+        }
+
+        // on the right so it doesn't cover the framerate text and the chat
+        let graph_width = settings.px(GRAPH_WIDTH);
+        let graph_height = settings.px(GRAPH_HEIGHT);
+        let text_width = lines.iter().map(|line| font.size_of(line).map_or(0, |(width, _)| width as i32)).max().unwrap_or(0);
+        let width = text_width.max(graph_width) + padding * 2;
+        let height = line_height * lines.len() as i32 + graph_height + padding * 3;
+        let x = screen_width as i32 - width - padding;
+        let y = padding;
+        ui.draw_rect(Rect::new(x, y, width.max(0) as u32, height.max(0) as u32), self.background);
+
+        let mut line_y = y + padding;
+        for line in &lines {
+            text.draw_text(font, line, x + padding, line_y, self.text_color);
+            line_y += line_height;
+        }
+        self.draw_graph(ui, Rect::new(x + padding, line_y + padding, graph_width.max(1) as u32, graph_height.max(1) as u32));
+    }
+
+    // a bar per frame, green under 60 fps worth of time, yellow under 30 and red above, with the two lines
+    fn draw_graph(&self, ui: &mut UiRenderer, area: Rect) {
+        ui.draw_rect(area, Color::RGBA(20, 20, 20, 200));
+        let scale = area.height() as f32 / GRAPH_MAX_MS;
+        let bar_width = (area.width() as f32 / FRAME_SAMPLES as f32).max(1.0);
+        // the newest on the right edge
+        let first = FRAME_SAMPLES - self.frame_times.len();
+        for (index, ms) in self.frame_times.iter().enumerate() {
+            let bar_height = (ms.min(GRAPH_MAX_MS) * scale).max(1.0) as i32;
+            let color = if *ms <= 1000.0 / 60.0 {
+                Color::RGB(80, 200, 80)
+            } else if *ms <= 1000.0 / 30.0 {
+                Color::RGB(220, 200, 60)
+            } else {
+                Color::RGB(220, 70, 60)
+            };
+            let bar_x = area.x() + ((first + index) as f32 * bar_width) as i32;
+            ui.draw_rect(Rect::new(bar_x, area.bottom() - bar_height, bar_width as u32, bar_height as u32), color);
+        }
+        for target in [1000.0 / 60.0, 1000.0 / 30.0] {
+            let line_y = area.bottom() - (target * scale) as i32;
+            ui.draw_rect(Rect::new(area.x(), line_y, area.width(), 1), Color::RGBA(255, 255, 255, 90));
+        }
+    }
+}
@@ -0,0 +1,199 @@
+// a small cpu profiler, every profile_scope! opens a zone that closes when the scope ends
+// the zones of a frame are saved as a tree so we can see where the frame time goes instead of guessing
+
+use std::cell::RefCell;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Debug)]
+pub struct ProfileZone {
+    pub name: &'static str,
+    pub start: Duration, // time since the frame started
+    pub duration: Duration,
+    pub depth: usize,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+}
+
+// one full frame of zones, the zones are stored flat and linked by index
+#[derive(Clone, Debug, Default)]
+pub struct ProfileFrame {
+    pub zones: Vec<ProfileZone>,
+    pub total: Duration,
+}
+
+impl ProfileFrame {
+    pub fn roots(&self) -> impl Iterator<Item = (usize, &ProfileZone)> {
+        self.zones.iter().enumerate().filter(|(_, zone)| zone.parent.is_none())
+    }
+
+    // the time of a zone that is not inside any of its children
+    pub fn self_time(&self, index: usize) -> Duration {
+        let zone = &self.zones[index];
+        let children: Duration = zone.children.iter().map(|&c| self.zones[c].duration).sum();
+        zone.duration.saturating_sub(children)
+    }
+
+    // a text tree of the frame, this is what the debug ui draws (or what we print when there is no ui)
+    pub fn format_tree(&self) -> String {
+        let mut out = format!("frame {:.3} ms\n", self.total.as_secs_f64() * 1000.0);
+        for (index, _) in self.roots() {
+            self.format_zone(index, &mut out);
+        }
+        out
+    }
+
+    fn format_zone(&self, index: usize, out: &mut String) {
+        let zone = &self.zones[index];
+        let percent = if self.total.is_zero() { 0.0 } else { zone.duration.as_secs_f64() / self.total.as_secs_f64() * 100.0 };
+        out.push_str(&format!(
+            "{}{} {:.3} ms ({:.1}%) self {:.3} ms\n",
+            "  ".repeat(zone.depth + 1),
+            zone.name,
+            zone.duration.as_secs_f64() * 1000.0,
+            percent,
+            self.self_time(index).as_secs_f64() * 1000.0,
+        ));
+        for &child in &zone.children {
+            self.format_zone(child, out);
+        }
+    }
+}
+
+struct ProfilerState {
+    enabled: bool,
+    frame_start: Instant,
+    current: ProfileFrame,
+    stack: Vec<usize>, // open zones, the last one is the parent of any new zone
+    last_frame: ProfileFrame,
+}
+
+thread_local! {
+    static PROFILER: RefCell<ProfilerState> = RefCell::new(ProfilerState {
+        enabled: false,
+        frame_start: Instant::now(),
+        current: ProfileFrame::default(),
+        stack: Vec::new(),
+        last_frame: ProfileFrame::default(),
+    });
+}
+
+pub struct Profiler;
+
+impl Profiler {
+    // off until the debug overlay shows the zones (F2), the zones cost nothing while it is off
+    pub fn set_enabled(enabled: bool) {
+        PROFILER.with(|p| p.borrow_mut().enabled = enabled);
+    }
+
+    // call this at the very start of every frame
+    pub fn begin_frame() {
+        PROFILER.with(|p| {
+            let mut p = p.borrow_mut();
+            p.frame_start = Instant::now();
+            p.current = ProfileFrame::default();
+            p.stack.clear();
+        });
+    }
+
+    // closes the frame and keeps it as the last complete frame
+    pub fn end_frame() {
+        PROFILER.with(|p| {
+            let mut p = p.borrow_mut();
+            let mut frame = std::mem::take(&mut p.current);
+            frame.total = p.frame_start.elapsed();
+            p.last_frame = frame;
+            p.stack.clear();
+        });
+    }
+
+    pub fn last_frame() -> ProfileFrame {
+        PROFILER.with(|p| p.borrow().last_frame.clone())
+    }
+
+    // adds a zone that was measured somewhere else (for example gpu timings) under the currently open zone
+    pub fn record(name: &'static str, duration: Duration) {
+        PROFILER.with(|p| {
+            let mut p = p.borrow_mut();
+            if !p.enabled {
+                return;
+            }
+            let start = p.frame_start.elapsed();
+            let index = p.open(name, start);
+            p.current.zones[index].duration = duration;
+            p.stack.pop();
+        });
+    }
+
+    fn begin_zone(name: &'static str) -> Option<usize> {
+        PROFILER.with(|p| {
+            let mut p = p.borrow_mut();
+            if !p.enabled {
+                return None;
+            }
+            let start = p.frame_start.elapsed();
+            Some(p.open(name, start))
+        })
+    }
+
+    fn end_zone(index: usize) {
+        PROFILER.with(|p| {
+            let mut p = p.borrow_mut();
+            let now = p.frame_start.elapsed();
+            // the frame could have been restarted while the zone was open, in that case the zone is gone
+            if p.stack.last() != Some(&index) || index >= p.current.zones.len() {
+                return;
+            }
+            let zone = &mut p.current.zones[index];
+            zone.duration = now.saturating_sub(zone.start);
+            p.stack.pop();
+        });
+    }
+}
+
+impl ProfilerState {
+    fn open(&mut self, name: &'static str, start: Duration) -> usize {
+        let parent = self.stack.last().copied();
+        let index = self.current.zones.len();
+        self.current.zones.push(ProfileZone {
+            name,
+            start,
+            duration: Duration::ZERO,
+            depth: self.stack.len(),
+            parent,
+            children: Vec::new(),
+        });
+        if let Some(parent) = parent {
+            self.current.zones[parent].children.push(index);
+        }
+        self.stack.push(index);
+        index
+    }
+}
+
+// the zone lives while this guard lives, so we don't have to close zones by hand
+pub struct ScopeGuard {
+    index: Option<usize>,
+}
+
+impl ScopeGuard {
+    pub fn new(name: &'static str) -> Self {
+        Self { index: Profiler::begin_zone(name) }
+    }
+}
+
+impl Drop for ScopeGuard {
+    fn drop(&mut self) {
+        if let Some(index) = self.index {
+            Profiler::end_zone(index);
+        }
+    }
+}
+
+// profile_scope!("culling"); measures from this line to the end of the current scope
+macro_rules! profile_scope {
+    ($name:expr) => {
+        let _profile_scope_guard = $crate::debug::profiler::ScopeGuard::new($name);
+    };
+}
+
+pub(crate) use profile_scope;
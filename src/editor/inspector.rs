@@ -0,0 +1,608 @@
+// the properties of the selected entity as rows that can be edited: its transform, the tint and scale of its instance
+// and the parameters of its custom shader material. the lights have no entity, they are selected on their own
+// F1 opens it and a click in the world picks what it shows (the point lights can be clicked too), L goes through the
+// lights. up and down pick a row, left and right (or dragging the value with the mouse) change it at once and
+// ctrl z / ctrl y undo and redo. a material is shared, changing its parameters changes every entity that uses it
+
+use cgmath::{Deg, Euler, Quaternion};
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::ttf::Font;
+
+use super::undo::UndoStack;
+use crate::input::input_state::{InputButton, InputState};
+use crate::rendering::camera::Ray;
+use crate::rendering::lights::{Light, LightKind, Lights};
+use crate::rendering::material_reflection::{MaterialParams, ParamKind, ParamValue};
+use crate::rendering::model::InstanceVariation;
+use crate::scene::graph::{EntityId, MaterialId, SceneGraph, Transform};
+use crate::ui::scale::UiSettings;
+use crate::ui::text::TextRenderer;
+use crate::ui::ui_renderer::UiRenderer;
+use crate::util::color::Color as LinearColor;
+
+// design pixels like the rest of the ui
+const WIDTH: f32 = 340.0;
+const PADDING: f32 = 8.0;
+const VISIBLE_LINES: usize = 22;
+const DRAG_PIXELS: f32 = 6.0; // how far the mouse goes for one step of the value
+const LIGHT_RADIUS: f32 = 0.3; // the point lights are clicked as a small ball at their position
+const UNDO_LIMIT: usize = 200;
+const AXES: [&str; 4] = ["x", "y", "z", "w"];
+const CHANNELS: [&str; 3] = ["r", "g", "b"];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Selection {
+    Entity(EntityId),
+    Light(usize), // the index in Lights::lights
+}
+
+// one value that can be edited, the vectors have a row for each component
+#[derive(Clone, Debug, PartialEq)]
+enum Field {
+    Position(usize),
+    Rotation(usize), // euler angles in degrees
+    Scale(usize),
+    Visible,
+    Tint(usize),
+    Size, // the scale of the instance variation, on top of the transform
+    Param { name: String, kind: ParamKind, component: Option<usize> },
+    Enabled,
+    Color(usize),
+    Intensity,
+    LightPosition(usize),
+    Range,
+    Direction(usize),
+}
+
+impl Field {
+    fn section(&self) -> &'static str {
+        match self {
+            Field::Position(_) | Field::Rotation(_) | Field::Scale(_) | Field::Visible => "transform",
+            Field::Tint(_) | Field::Size | Field::Param { .. } => "material",
+            _ => "light",
+        }
+    }
+
+    fn label(&self) -> String {
+        match self {
+            Field::Position(axis) | Field::LightPosition(axis) => format!("position {}", AXES[*axis]),
+            Field::Rotation(axis) => format!("rotation {}", AXES[*axis]),
+            Field::Scale(axis) => format!("scale {}", AXES[*axis]),
+            Field::Visible => "visible".to_string(),
+            Field::Tint(channel) => format!("tint {}", CHANNELS[*channel]),
+            Field::Size => "instance scale".to_string(),
+            Field::Param { name, component: Some(component), .. } => format!("{}.{}", name, AXES[*component]),
+            Field::Param { name, .. } => name.clone(),
+            Field::Enabled => "enabled".to_string(),
+            Field::Color(channel) => format!("color {}", CHANNELS[*channel]),
+            Field::Intensity => "intensity".to_string(),
+            Field::Range => "range".to_string(),
+            Field::Direction(axis) => format!("direction {}", AXES[*axis]),
+        }
+    }
+
+    // what left and right add, shift makes it ten times bigger
+    fn step(&self) -> f32 {
+        match self {
+            Field::Rotation(_) => 5.0,
+            Field::Param { kind: ParamKind::Int | ParamKind::UInt, .. } => 1.0,
+            Field::Tint(_) | Field::Size | Field::Param { .. } | Field::Color(_) => 0.05,
+            Field::Range => 0.5,
+            _ => 0.1,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Value {
+    Number(f32),
+    Toggle(bool),
+}
+
+// all of what an edit touched, so undo puts it back exactly (the rotation doesn't go through the angles again)
+#[derive(Clone, Debug)]
+enum Snapshot {
+    Entity(EntityId, Transform, bool, InstanceVariation),
+    Material(MaterialId, MaterialParams),
+    Light(usize, Light),
+}
+
+struct Edit {
+    field: Field,
+    before: Snapshot,
+    after: Snapshot,
+}
+
+// the value being dragged, the whole drag is one edit
+struct Drag {
+    x: i32,
+    edited: bool,
+}
+
+enum Line {
+    Section(&'static str),
+    Row(usize),
+}
+
+pub struct Inspector {
+    open: bool,
+    selection: Option<Selection>,
+    selected: usize,
+    drag: Option<Drag>,
+    history: UndoStack<Edit>,
+    pub background: Color,
+    pub text_color: Color,
+    pub section_color: Color,
+    pub selected_color: Color,
+    pub value_color: Color,
+}
+
+impl Inspector {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            selection: None,
+            selected: 0,
+            drag: None,
+            history: UndoStack::new(UNDO_LIMIT),
+            background: Color::RGBA(0, 0, 0, 170),
+            text_color: Color::WHITE,
+            section_color: Color::RGB(160, 160, 160),
+            selected_color: Color::RGB(255, 200, 60),
+            value_color: Color::RGB(120, 200, 255),
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+        self.drag = None;
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn select(&mut self, selection: Option<Selection>) {
+        if self.selection != selection {
+            self.selection = selection;
+            self.selected = 0;
+            self.drag = None;
+        }
+    }
+
+    // the closest point light the ray goes through, and how far along the ray it is
+    pub fn light_at(lights: &Lights, ray: &Ray) -> Option<(usize, f32)> {
+        lights.lights.iter().enumerate()
+            .filter_map(|(index, light)| match light.kind {
+                LightKind::Point { position, .. } => ray.intersect_sphere(position, LIGHT_RADIUS).map(|distance| (index, distance)),
+                LightKind::Directional { .. } => None,
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    // the panel, on the right side of the screen
+    fn panel_rect(&self, font: &Font, screen_width: u32) -> Rect {
+        let settings = UiSettings::current();
+        let padding = settings.px(PADDING);
+        let width = settings.px(WIDTH).min(screen_width as i32);
+        let height = self.line_height(font) * (VISIBLE_LINES as i32 + 1) + padding * 2;
+        Rect::new(screen_width as i32 - width - padding, padding, width.max(1) as u32, height.max(1) as u32)
+    }
+
+    fn line_height(&self, font: &Font) -> i32 {
+        font.height() + UiSettings::current().px(PADDING) / 2
+    }
+
+    // the rows of what is selected, none when it's gone (despawned, or the light was removed)
+    fn rows(&self, world: &SceneGraph, lights: &Lights) -> Vec<Field> {
+        let mut rows = Vec::new();
+        match self.selection {
+            Some(Selection::Entity(id)) => {
+                let Some(entity) = world.get(id) else { return rows };
+                rows.extend((0..3).map(Field::Position));
+                rows.extend((0..3).map(Field::Rotation));
+                rows.extend((0..3).map(Field::Scale));
+                rows.push(Field::Visible);
+                rows.extend((0..3).map(Field::Tint));
+                rows.push(Field::Size);
+                if let Some(custom) = entity.material.and_then(|material| world.material(material).custom.as_ref()) {
+                    // sorted so the rows don't move around between frames
+                    let mut declared: Vec<(&str, ParamKind)> = custom.params.declared().collect();
+                    declared.sort_by_key(|(name, _)| *name);
+                    for (name, kind) in declared {
+                        let components = match kind {
+                            ParamKind::Float | ParamKind::Int | ParamKind::UInt => vec![None],
+                            ParamKind::Vec2 => (0..2).map(Some).collect(),
+                            ParamKind::Vec3 => (0..3).map(Some).collect(),
+                            ParamKind::Vec4 => (0..4).map(Some).collect(),
+                            ParamKind::Mat4 => Vec::new(), // not something to edit by hand
+                        };
+                        rows.extend(components.into_iter().map(|component| Field::Param { name: name.to_string(), kind, component }));
+                    }
+                }
+            }
+            Some(Selection::Light(index)) => {
+                let Some(light) = lights.lights.get(index) else { return rows };
+                rows.push(Field::Enabled);
+                rows.extend((0..3).map(Field::Color));
+                rows.push(Field::Intensity);
+                match light.kind {
+                    LightKind::Point { .. } => {
+                        rows.extend((0..3).map(Field::LightPosition));
+                        rows.push(Field::Range);
+                    }
+                    LightKind::Directional { .. } => rows.extend((0..3).map(Field::Direction)),
+                }
+            }
+            None => {}
+        }
+        rows
+    }
+
+    // the rows with the name of their section over each group, scrolled so the selected row is on screen
+    fn lines(&self, rows: &[Field]) -> Vec<Line> {
+        let mut lines = Vec::new();
+        for (index, row) in rows.iter().enumerate() {
+            if index == 0 || rows[index - 1].section() != row.section() {
+                lines.push(Line::Section(row.section()));
+            }
+            lines.push(Line::Row(index));
+        }
+        let selected = lines.iter().position(|line| matches!(line, Line::Row(index) if *index == self.selected)).unwrap_or(0);
+        let first = selected.saturating_sub(VISIBLE_LINES - 1);
+        lines.into_iter().skip(first).take(VISIBLE_LINES).collect()
+    }
+
+    // true while it has the input (the mouse over the panel, a drag or its keys), the game doesn't take it then
+    pub fn handle_input(&mut self, input: &InputState, world: &mut SceneGraph, lights: &mut Lights, font: &Font, screen_width: u32) -> bool {
+        if !self.open {
+            return false;
+        }
+        let key = |keycode: Keycode| input.just_pressed(InputButton::Key(keycode));
+        let held = |left: Keycode, right: Keycode| input.is_pressed(InputButton::Key(left)) || input.is_pressed(InputButton::Key(right));
+        let ctrl = held(Keycode::LCtrl, Keycode::RCtrl);
+        let shift = held(Keycode::LShift, Keycode::RShift);
+
+        if ctrl && (key(Keycode::Z) || key(Keycode::Y)) {
+            self.drag = None;
+            if key(Keycode::Y) || shift {
+                if let Some(edit) = self.history.redo() {
+                    restore(&edit.after, world, lights);
+                }
+            } else if let Some(edit) = self.history.undo() {
+                restore(&edit.before, world, lights);
+            }
+            return true;
+        }
+        if key(Keycode::L) && !lights.lights.is_empty() {
+            let next = match self.selection {
+                Some(Selection::Light(index)) => (index + 1) % lights.lights.len(),
+                _ => 0,
+            };
+            self.select(Some(Selection::Light(next)));
+            return true;
+        }
+
+        let rows = self.rows(world, lights);
+        if rows.is_empty() {
+            self.selection = None;
+            self.drag = None;
+            return false;
+        }
+        self.selected = self.selected.min(rows.len() - 1);
+        let mut used = false;
+        if input.action_just_pressed("UiNext") {
+            self.selected = (self.selected + 1) % rows.len();
+            used = true;
+        }
+        if input.action_just_pressed("UiPrevious") {
+            self.selected = (self.selected + rows.len() - 1) % rows.len();
+            used = true;
+        }
+        let field = rows[self.selected].clone();
+        let steps = input.just_pressed_or_repeated(InputButton::Key(Keycode::Right)) as i32 - input.just_pressed_or_repeated(InputButton::Key(Keycode::Left)) as i32;
+        if steps != 0 || input.action_just_pressed("UiAccept") {
+            let multiplier = if shift { 10.0 } else { 1.0 };
+            self.change(&field, steps as f32 * multiplier, false, world, lights);
+            used = true;
+        }
+
+        // a click on a row selects it, a toggle flips and a number follows the mouse left and right while held
+        let settings = UiSettings::current();
+        let area = self.panel_rect(font, screen_width);
+        let mouse = input.mouse_position();
+        let over = area.contains_point(mouse);
+        if over && input.just_pressed(InputButton::Mouse(MouseButton::Left)) {
+            let line_height = self.line_height(font);
+            let line = (mouse.1 - area.y() - settings.px(PADDING) - line_height) / line_height.max(1);
+            if let Some(Line::Row(index)) = usize::try_from(line).ok().and_then(|line| self.lines(&rows).into_iter().nth(line)) {
+                self.selected = index;
+                if let Some(Value::Toggle(_)) = read(&rows[index], self.selection, world, lights) {
+                    self.change(&rows[index], 1.0, false, world, lights);
+                } else {
+                    self.drag = Some(Drag { x: mouse.0, edited: false });
+                }
+            }
+        } else if input.is_pressed(InputButton::Mouse(MouseButton::Left)) {
+            let pixels = settings.px(DRAG_PIXELS).max(1);
+            if let Some(Drag { x, edited }) = self.drag {
+                let steps = (mouse.0 - x) / pixels;
+                if steps != 0 {
+                    self.change(&field, steps as f32, edited, world, lights);
+                    self.drag = Some(Drag { x: x + steps * pixels, edited: true });
+                }
+            }
+        }
+        if input.just_released(InputButton::Mouse(MouseButton::Left)) {
+            self.drag = None;
+        }
+        used || over || self.drag.is_some()
+    }
+
+    // steps of the field for the numbers, anything flips the toggles. merge adds to the last edit instead of making one
+    fn change(&mut self, field: &Field, steps: f32, merge: bool, world: &mut SceneGraph, lights: &mut Lights) {
+        let Some(selection) = self.selection else { return };
+        let value = match read(field, Some(selection), world, lights) {
+            Some(Value::Number(number)) if steps != 0.0 => Value::Number(number + steps * field.step()),
+            Some(Value::Toggle(on)) => Value::Toggle(!on),
+            _ => return,
+        };
+        let Some(before) = snapshot(field, selection, world, lights) else { return };
+        write(field, selection, value, world, lights);
+        let Some(after) = snapshot(field, selection, world, lights) else { return };
+        match self.history.last_mut() {
+            Some(last) if merge && last.field == *field => last.after = after,
+            _ => self.history.push(Edit { field: field.clone(), before, after }),
+        }
+    }
+
+    pub fn draw(&self, ui: &mut UiRenderer, text: &mut TextRenderer, font: &Font, world: &SceneGraph, lights: &Lights, screen_width: u32) {
+        if !self.open {
+            return;
+        }
+        let padding = UiSettings::current().px(PADDING);
+        let area = self.panel_rect(font, screen_width);
+        let line_height = self.line_height(font);
+        ui.draw_rect(area, self.background);
+
+        let title = match self.selection {
+            Some(Selection::Entity(id)) => world.get(id).map(|entity| entity.name.clone()),
+            Some(Selection::Light(index)) => lights.lights.get(index).map(|light| match light.kind {
+                LightKind::Point { .. } => format!("point light {}", index),
+                LightKind::Directional { .. } => format!("directional light {}", index),
+            }),
+            None => None,
+        };
+        let (undos, redos) = self.history.counts();
+        let title = format!("{}  (undo {} / redo {})", title.as_deref().unwrap_or("click something to inspect"), undos, redos);
+        let (x, mut y) = (area.x() + padding, area.y() + padding);
+        text.draw_text(font, &title, x, y, self.text_color);
+        y += line_height;
+
+        let rows = self.rows(world, lights);
+        for line in self.lines(&rows) {
+            match line {
+                Line::Section(name) => {
+                    text.draw_text(font, name, x, y, self.section_color);
+                }
+                Line::Row(index) => {
+                    let field = &rows[index];
+                    let color = if index == self.selected { self.selected_color } else { self.text_color };
+                    if index == self.selected {
+                        ui.draw_outline(Rect::new(x - padding / 2, y - padding / 4, (area.width() as i32 - padding).max(0) as u32, line_height.max(0) as u32), 1, color);
+                    }
+                    let value = match read(field, self.selection, world, lights) {
+                        Some(Value::Number(number)) => format!("{:.2}", number),
+                        Some(Value::Toggle(on)) => if on { "on" } else { "off" }.to_string(),
+                        None => String::new(),
+                    };
+                    text.draw_text(font, &format!("  {}", field.label()), x, y, color);
+                    text.draw_text(font, &value, x + area.width() as i32 * 3 / 5, y, self.value_color);
+                }
+            }
+            y += line_height;
+        }
+    }
+}
+
+impl Default for Inspector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn channel(color: LinearColor, index: usize) -> f32 {
+    [color.r, color.g, color.b][index]
+}
+
+fn channel_mut(color: &mut LinearColor, index: usize) -> &mut f32 {
+    match index {
+        0 => &mut color.r,
+        1 => &mut color.g,
+        _ => &mut color.b,
+    }
+}
+
+fn angles(rotation: Quaternion<f32>) -> [f32; 3] {
+    let euler = Euler::from(rotation);
+    [Deg::from(euler.x).0, Deg::from(euler.y).0, Deg::from(euler.z).0]
+}
+
+// the value of a parameter, the ones nobody set yet are zero like their bytes in the uniform
+fn param(params: &MaterialParams, name: &str) -> Option<ParamValue> {
+    params.get(name).or_else(|| {
+        params.declared().find(|(declared, _)| *declared == name).map(|(_, kind)| match kind {
+            ParamKind::Float => ParamValue::Float(0.0),
+            ParamKind::Int => ParamValue::Int(0),
+            ParamKind::UInt => ParamValue::UInt(0),
+            ParamKind::Vec2 => ParamValue::Vec2([0.0; 2]),
+            ParamKind::Vec3 => ParamValue::Vec3([0.0; 3]),
+            ParamKind::Vec4 => ParamValue::Vec4([0.0; 4]),
+            ParamKind::Mat4 => ParamValue::Mat4([[0.0; 4]; 4]),
+        })
+    })
+}
+
+fn component(value: ParamValue, component: Option<usize>) -> f32 {
+    let component = component.unwrap_or(0);
+    match value {
+        ParamValue::Float(value) => value,
+        ParamValue::Int(value) => value as f32,
+        ParamValue::UInt(value) => value as f32,
+        ParamValue::Vec2(value) => value[component],
+        ParamValue::Vec3(value) => value[component],
+        ParamValue::Vec4(value) => value[component],
+        ParamValue::Mat4(_) => 0.0,
+    }
+}
+
+fn with_component(value: ParamValue, component: Option<usize>, number: f32) -> ParamValue {
+    let component = component.unwrap_or(0);
+    match value {
+        ParamValue::Float(_) => ParamValue::Float(number),
+        ParamValue::Int(_) => ParamValue::Int(number.round() as i32),
+        ParamValue::UInt(_) => ParamValue::UInt(number.round().max(0.0) as u32),
+        ParamValue::Vec2(mut value) => {
+            value[component] = number;
+            ParamValue::Vec2(value)
+        }
+        ParamValue::Vec3(mut value) => {
+            value[component] = number;
+            ParamValue::Vec3(value)
+        }
+        ParamValue::Vec4(mut value) => {
+            value[component] = number;
+            ParamValue::Vec4(value)
+        }
+        ParamValue::Mat4(value) => ParamValue::Mat4(value),
+    }
+}
+
+fn read(field: &Field, selection: Option<Selection>, world: &SceneGraph, lights: &Lights) -> Option<Value> {
+    match selection? {
+        Selection::Entity(id) => {
+            let entity = world.get(id)?;
+            let transform = &entity.transform;
+            Some(match field {
+                Field::Position(axis) => Value::Number(transform.position[*axis]),
+                Field::Rotation(axis) => Value::Number(angles(transform.rotation)[*axis]),
+                Field::Scale(axis) => Value::Number(transform.scale[*axis]),
+                Field::Visible => Value::Toggle(entity.visible),
+                Field::Tint(index) => Value::Number(channel(entity.variation.tint, *index)),
+                Field::Size => Value::Number(entity.variation.scale),
+                Field::Param { name, component: index, .. } => {
+                    let params = &world.material(entity.material?).custom.as_ref()?.params;
+                    Value::Number(component(param(params, name)?, *index))
+                }
+                _ => return None,
+            })
+        }
+        Selection::Light(index) => {
+            let light = lights.lights.get(index)?;
+            Some(match (field, light.kind) {
+                (Field::Enabled, _) => Value::Toggle(light.enabled),
+                (Field::Color(index), _) => Value::Number(channel(light.color, *index)),
+                (Field::Intensity, _) => Value::Number(light.intensity),
+                (Field::LightPosition(axis), LightKind::Point { position, .. }) => Value::Number(position[*axis]),
+                (Field::Range, LightKind::Point { range, .. }) => Value::Number(range),
+                (Field::Direction(axis), LightKind::Directional { direction }) => Value::Number(direction[*axis]),
+                _ => return None,
+            })
+        }
+    }
+}
+
+fn write(field: &Field, selection: Selection, value: Value, world: &mut SceneGraph, lights: &mut Lights) {
+    let (number, on) = match value {
+        Value::Number(number) => (number, false),
+        Value::Toggle(on) => (0.0, on),
+    };
+    match selection {
+        Selection::Entity(id) => {
+            if let Field::Param { name, component: index, .. } = field {
+                let Some(material) = world.get(id).and_then(|entity| entity.material) else { return };
+                let Some(custom) = world.material_mut(material).custom.as_mut() else { return };
+                let Some(old) = param(&custom.params, name) else { return };
+                if let Err(e) = custom.params.set(name, with_component(old, *index, number)) {
+                    eprintln!("{}", e);
+                }
+                return;
+            }
+            let Some(entity) = world.get_mut(id) else { return };
+            match field {
+                Field::Position(axis) => entity.transform.position[*axis] = number,
+                Field::Rotation(axis) => {
+                    let mut angles = angles(entity.transform.rotation);
+                    angles[*axis] = number;
+                    entity.transform.rotation = Quaternion::from(Euler::new(Deg(angles[0]), Deg(angles[1]), Deg(angles[2])));
+                }
+                Field::Scale(axis) => entity.transform.scale[*axis] = number,
+                Field::Visible => entity.visible = on,
+                Field::Tint(index) => *channel_mut(&mut entity.variation.tint, *index) = number.max(0.0),
+                Field::Size => entity.variation.scale = number.max(0.01),
+                _ => {}
+            }
+            // it jumps to the new value, it doesn't slide there like a simulated move
+            world.skip_interpolation(id);
+        }
+        Selection::Light(index) => {
+            let Some(light) = lights.lights.get_mut(index) else { return };
+            match (field, &mut light.kind) {
+                (Field::Enabled, _) => light.enabled = on,
+                (Field::Color(index), _) => *channel_mut(&mut light.color, *index) = number.max(0.0),
+                (Field::Intensity, _) => light.intensity = number.max(0.0),
+                (Field::LightPosition(axis), LightKind::Point { position, .. }) => position[*axis] = number,
+                (Field::Range, LightKind::Point { range, .. }) => *range = number.max(0.0),
+                (Field::Direction(axis), LightKind::Directional { direction }) => direction[*axis] = number,
+                _ => {}
+            }
+        }
+    }
+}
+
+// how the thing the field belongs to is now, the parameters are saved for the whole material
+fn snapshot(field: &Field, selection: Selection, world: &SceneGraph, lights: &Lights) -> Option<Snapshot> {
+    match selection {
+        Selection::Entity(id) => {
+            let entity = world.get(id)?;
+            if let Field::Param { .. } = field {
+                let material = entity.material?;
+                return Some(Snapshot::Material(material, world.material(material).custom.as_ref()?.params.clone()));
+            }
+            Some(Snapshot::Entity(id, entity.transform, entity.visible, entity.variation))
+        }
+        Selection::Light(index) => lights.lights.get(index).map(|light| Snapshot::Light(index, *light)),
+    }
+}
+
+fn restore(snapshot: &Snapshot, world: &mut SceneGraph, lights: &mut Lights) {
+    match snapshot {
+        Snapshot::Entity(id, transform, visible, variation) => {
+            if let Some(entity) = world.get_mut(*id) {
+                entity.transform = *transform;
+                entity.visible = *visible;
+                entity.variation = *variation;
+            }
+            world.skip_interpolation(*id);
+        }
+        Snapshot::Material(id, params) => {
+            // set one by one so the uniform is written again
+            let Some(custom) = world.material_mut(*id).custom.as_mut() else { return };
+            for (name, _) in params.declared() {
+                if let Some(value) = param(params, name) {
+                    if let Err(e) = custom.params.set(name, value) {
+                        eprintln!("{}", e);
+                    }
+                }
+            }
+        }
+        Snapshot::Light(index, light) => {
+            if let Some(saved) = lights.lights.get_mut(*index) {
+                *saved = *light;
+            }
+        }
+    }
+}
@@ -0,0 +1,155 @@
+// a brush that scatters instances over surfaces (grass, rocks, crowds) and erases them
+// every dab fills the circle under the cursor up to the density, so holding the brush on a spot doesn't pile instances
+// the candidates are dropped from above onto the surface, so they follow the terrain under the brush
+
+use cgmath::{InnerSpace, Rotation, Rotation3, VectorSpace};
+
+use crate::gameplay::placement;
+use crate::rendering::camera::Ray;
+use crate::util::rng::Rng;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BrushMode {
+    Paint,
+    Erase,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct SurfaceHit {
+    pub point: cgmath::Point3<f32>,
+    pub normal: cgmath::Vector3<f32>,
+}
+
+// anything the brush can paint on
+pub trait PaintSurface {
+    fn raycast(&self, ray: &Ray) -> Option<SurfaceHit>;
+}
+
+// a flat ground at the given height
+pub struct GroundSurface {
+    pub height: f32,
+}
+
+impl PaintSurface for GroundSurface {
+    fn raycast(&self, ray: &Ray) -> Option<SurfaceHit> {
+        let point = placement::ray_ground_point(ray, self.height)?;
+        Some(SurfaceHit { point, normal: cgmath::Vector3::unit_y() })
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct PaintedInstance {
+    pub position: cgmath::Vector3<f32>,
+    pub rotation: cgmath::Quaternion<f32>,
+    pub scale: f32,
+}
+
+// what a dab changes, removed are indices of the positions given to it (from the biggest to the smallest)
+#[derive(Default, Debug)]
+pub struct BrushEdit {
+    pub added: Vec<PaintedInstance>,
+    pub removed: Vec<usize>,
+}
+
+impl BrushEdit {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+pub struct InstanceBrush {
+    pub mode: BrushMode,
+    pub radius: f32,
+    pub density: f32, // instances per square unit the brush fills up to
+    pub min_spacing: f32, // no two instances closer than this
+    pub scale_range: (f32, f32),
+    pub yaw_jitter: f32, // degrees, 180 is any direction
+    pub tilt_jitter: f32, // degrees of random lean
+    pub align_to_surface: f32, // 0 stands straight up, 1 follows the normal of the surface
+    pub max_slope: f32, // degrees, steeper places get nothing
+    rng: Rng,
+}
+
+impl InstanceBrush {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            mode: BrushMode::Paint,
+            radius: 3.0,
+            density: 0.5,
+            min_spacing: 0.8,
+            scale_range: (0.8, 1.2),
+            yaw_jitter: 180.0,
+            tilt_jitter: 5.0,
+            align_to_surface: 0.0,
+            max_slope: 45.0,
+            rng: Rng::new(seed),
+        }
+    }
+
+    // one application of the brush where the ray hits the surface, existing are the positions of the instances it can touch
+    pub fn dab(&mut self, surface: &dyn PaintSurface, ray: &Ray, existing: &[cgmath::Vector3<f32>]) -> BrushEdit {
+        let mut edit = BrushEdit::default();
+        let Some(center) = surface.raycast(ray) else { return edit };
+        let center = cgmath::Vector3::new(center.point.x, center.point.y, center.point.z);
+
+        // the distance on the ground, so a brush on a slope still covers its whole circle
+        let inside = |position: &cgmath::Vector3<f32>| {
+            let offset = *position - center;
+            offset.x * offset.x + offset.z * offset.z <= self.radius * self.radius
+        };
+
+        if self.mode == BrushMode::Erase {
+            edit.removed = (0..existing.len()).rev().filter(|index| inside(&existing[*index])).collect();
+            return edit;
+        }
+
+        let area = std::f32::consts::PI * self.radius * self.radius;
+        let wanted = (self.density * area).round() as usize;
+        let nearby = existing.iter().filter(|position| inside(position)).count();
+        if nearby >= wanted {
+            return edit;
+        }
+
+        // some candidates fail (spacing, slope, missing surface), so a few extra tries are allowed
+        let missing = wanted - nearby;
+        let min_slope_cos = self.max_slope.to_radians().cos();
+        for _ in 0..missing * 4 {
+            if edit.added.len() >= missing {
+                break;
+            }
+            // uniform in the circle
+            let direction = self.rng.unit_vector_2d();
+            let distance = self.radius * self.rng.next_f32().sqrt();
+            let x = center.x + direction.x * distance;
+            let z = center.z + direction.y * distance;
+
+            let drop = Ray { origin: cgmath::Point3::new(x, center.y + self.radius * 2.0, z), direction: -cgmath::Vector3::unit_y() };
+            let Some(hit) = surface.raycast(&drop) else { continue };
+            if hit.normal.y < min_slope_cos {
+                continue;
+            }
+            let position = cgmath::Vector3::new(hit.point.x, hit.point.y, hit.point.z);
+            let spacing = self.min_spacing * self.min_spacing;
+            if existing.iter().chain(edit.added.iter().map(|added| &added.position)).any(|other| (*other - position).magnitude2() < spacing) {
+                continue;
+            }
+
+            let instance = self.jittered(position, hit.normal);
+            edit.added.push(instance);
+        }
+        edit
+    }
+
+    fn jittered(&mut self, position: cgmath::Vector3<f32>, normal: cgmath::Vector3<f32>) -> PaintedInstance {
+        let up = cgmath::Vector3::unit_y().lerp(normal, self.align_to_surface.clamp(0.0, 1.0)).normalize();
+        let align = cgmath::Quaternion::between_vectors(cgmath::Vector3::unit_y(), up);
+        let yaw = cgmath::Quaternion::from_angle_y(cgmath::Deg(self.rng.range_f32(-self.yaw_jitter, self.yaw_jitter)));
+        let lean_axis = self.rng.unit_vector_2d();
+        let tilt = cgmath::Quaternion::from_axis_angle(
+            cgmath::Vector3::new(lean_axis.x, 0.0, lean_axis.y),
+            cgmath::Deg(self.rng.range_f32(0.0, self.tilt_jitter)),
+        );
+        let (min_scale, max_scale) = self.scale_range;
+        PaintedInstance { position, rotation: align * tilt * yaw, scale: self.rng.range_f32(min_scale, max_scale.max(min_scale)) }
+    }
+}
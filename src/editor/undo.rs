@@ -0,0 +1,47 @@
+// the undo and redo of the editors: an edit keeps how the thing was before and after it, undo puts the before back
+// and redo the after. a drag is a lot of small changes, the editor keeps growing the last edit while it lasts so one
+// undo takes the whole drag back
+
+pub struct UndoStack<T> {
+    done: Vec<T>,
+    undone: Vec<T>, // what redo brings back, gone with the next new edit
+    limit: usize,   // the oldest edits are forgotten after this many
+}
+
+impl<T> UndoStack<T> {
+    pub fn new(limit: usize) -> Self {
+        Self { done: Vec::new(), undone: Vec::new(), limit: limit.max(1) }
+    }
+
+    pub fn push(&mut self, edit: T) {
+        self.undone.clear();
+        self.done.push(edit);
+        if self.done.len() > self.limit {
+            self.done.remove(0);
+        }
+    }
+
+    // the last edit, to add to it while the same change goes on
+    pub fn last_mut(&mut self) -> Option<&mut T> {
+        self.done.last_mut()
+    }
+
+    // the edit to take back, the caller applies its before
+    pub fn undo(&mut self) -> Option<&T> {
+        let edit = self.done.pop()?;
+        self.undone.push(edit);
+        self.undone.last()
+    }
+
+    // the edit to do again, the caller applies its after
+    pub fn redo(&mut self) -> Option<&T> {
+        let edit = self.undone.pop()?;
+        self.done.push(edit);
+        self.done.last()
+    }
+
+    // how many undos and redos there are, for the editors to show
+    pub fn counts(&self) -> (usize, usize) {
+        (self.done.len(), self.undone.len())
+    }
+}
@@ -0,0 +1,217 @@
+// abilities described in data instead of code: a cast time, a cooldown, a cost and a list of effects, in ron files in
+// the abilities folder of the assets (see util/data_files.rs), a mod can add or replace them
+// who casts (the player controller, an ai) keeps an AbilitySet with its cooldowns and its energy and asks it to cast
+// by name, what the ability does comes out of update as events so the same ability works for anyone, the caster
+// applies them (damage, sounds, pushes) the way its side of the game knows how
+//
+//   [
+//       (name: "dash", cooldown: 3.0, cost: 10.0, effects: [Impulse(strength: 12.0), Sound("sounds/impact.wav")]),
+//       (name: "heal", cast_time: 1.5, cooldown: 20.0, cost: 40.0, effects: [Heal(amount: 25.0)]),
+//   ]
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+
+use cgmath::Vector3;
+use serde::Deserialize;
+
+use crate::scene::graph::EntityId;
+use crate::util::data_files::DataFiles;
+
+pub const ABILITY_FOLDER: &str = "abilities";
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub enum Effect {
+    Damage { amount: f32, radius: f32 }, // radius 0 is only the target
+    Heal { amount: f32 },
+    Impulse { strength: f32 }, // a push along where the caster looks
+    Sound(String),             // a path under the assets
+    Custom(String),            // for what only the game knows, the name is up to it
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct AbilityDef {
+    pub name: String,
+    #[serde(default)]
+    pub cast_time: f32, // seconds before the effects, 0 is right away
+    #[serde(default)]
+    pub cooldown: f32, // from the end of the cast
+    #[serde(default)]
+    pub cost: f32, // energy, paid when the cast starts
+    #[serde(default)]
+    pub effects: Vec<Effect>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AbilityTarget {
+    None,
+    Point(Vector3<f32>),
+    Entity(EntityId),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum AbilityEvent {
+    CastStarted { ability: String, target: AbilityTarget }, // only for the ones with a cast time
+    Effect { ability: String, effect: Effect, target: AbilityTarget },
+    Interrupted { ability: String },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CastError {
+    Unknown,
+    Cooldown(f32), // the seconds left
+    NotEnoughEnergy,
+    Busy, // another cast is not finished
+}
+
+impl fmt::Display for CastError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CastError::Unknown => write!(f, "there is no such ability"),
+            CastError::Cooldown(left) => write!(f, "ready in {:.1}s", left),
+            CastError::NotEnoughEnergy => write!(f, "not enough energy"),
+            CastError::Busy => write!(f, "already casting"),
+        }
+    }
+}
+
+// every ability of the files, by name, the one of the last file wins when two have the same name
+pub struct AbilityLibrary {
+    files: DataFiles<AbilityDef>,
+    abilities: HashMap<String, AbilityDef>,
+}
+
+impl AbilityLibrary {
+    pub fn load() -> Self {
+        let files = DataFiles::load(ABILITY_FOLDER);
+        let abilities = Self::by_name(&files);
+        Self { files, abilities }
+    }
+
+    pub fn reload(&mut self) {
+        self.files.reload();
+        self.abilities = Self::by_name(&self.files);
+    }
+
+    fn by_name(files: &DataFiles<AbilityDef>) -> HashMap<String, AbilityDef> {
+        files.definitions().map(|def| (def.name.clone(), def.clone())).collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&AbilityDef> {
+        self.abilities.get(name)
+    }
+
+    // the files on disk, for the file watcher
+    pub fn files(&self) -> Vec<PathBuf> {
+        self.files.paths()
+    }
+}
+
+struct Casting {
+    ability: String,
+    target: AbilityTarget,
+    left: f32,
+}
+
+// the state of one caster
+pub struct AbilitySet {
+    cooldowns: HashMap<String, f32>, // seconds left, only the ones still cooling down
+    casting: Option<Casting>,
+    events: Vec<AbilityEvent>,
+    pub energy: f32,
+    pub max_energy: f32,
+    pub energy_regen: f32, // per second
+}
+
+impl AbilitySet {
+    pub fn new(max_energy: f32, energy_regen: f32) -> Self {
+        Self { cooldowns: HashMap::new(), casting: None, events: Vec::new(), energy: max_energy, max_energy, energy_regen }
+    }
+
+    pub fn cooldown(&self, ability: &str) -> f32 {
+        self.cooldowns.get(ability).copied().unwrap_or(0.0)
+    }
+
+    pub fn is_casting(&self) -> bool {
+        self.casting.is_some()
+    }
+
+    // the ability being cast and how far it is (0 to 1), for a cast bar
+    pub fn cast_progress(&self, library: &AbilityLibrary) -> Option<(&str, f32)> {
+        let casting = self.casting.as_ref()?;
+        let total = library.get(&casting.ability).map_or(0.0, |def| def.cast_time);
+        let progress = if total > 0.0 { 1.0 - casting.left / total } else { 1.0 };
+        Some((casting.ability.as_str(), progress.clamp(0.0, 1.0)))
+    }
+
+    // what an ai asks before choosing an ability, the same checks as cast
+    pub fn can_cast(&self, library: &AbilityLibrary, ability: &str) -> Result<(), CastError> {
+        let def = library.get(ability).ok_or(CastError::Unknown)?;
+        if self.casting.is_some() {
+            return Err(CastError::Busy);
+        }
+        let cooldown = self.cooldown(ability);
+        if cooldown > 0.0 {
+            return Err(CastError::Cooldown(cooldown));
+        }
+        if self.energy < def.cost {
+            return Err(CastError::NotEnoughEnergy);
+        }
+        Ok(())
+    }
+
+    // pays the cost, an ability without cast time has its effects in the events of the next update
+    pub fn cast(&mut self, library: &AbilityLibrary, ability: &str, target: AbilityTarget) -> Result<(), CastError> {
+        self.can_cast(library, ability)?;
+        let def = library.get(ability).ok_or(CastError::Unknown)?;
+        self.energy -= def.cost;
+        if def.cast_time > 0.0 {
+            self.events.push(AbilityEvent::CastStarted { ability: def.name.clone(), target });
+            self.casting = Some(Casting { ability: def.name.clone(), target, left: def.cast_time });
+        } else {
+            self.finish(def, target);
+        }
+        Ok(())
+    }
+
+    // being hit, moving, a stun: the cast stops and the energy is not given back
+    pub fn interrupt(&mut self) {
+        if let Some(casting) = self.casting.take() {
+            self.events.push(AbilityEvent::Interrupted { ability: casting.ability });
+        }
+    }
+
+    fn finish(&mut self, def: &AbilityDef, target: AbilityTarget) {
+        for effect in &def.effects {
+            self.events.push(AbilityEvent::Effect { ability: def.name.clone(), effect: effect.clone(), target });
+        }
+        if def.cooldown > 0.0 {
+            self.cooldowns.insert(def.name.clone(), def.cooldown);
+        }
+    }
+
+    // in the fixed steps of the caster, the events of the casts that started or finished since the last call
+    pub fn update(&mut self, library: &AbilityLibrary, delta_time: f32) -> Vec<AbilityEvent> {
+        self.energy = (self.energy + self.energy_regen * delta_time).min(self.max_energy);
+        self.cooldowns.retain(|_, left| {
+            *left -= delta_time;
+            *left > 0.0
+        });
+        if let Some(casting) = &mut self.casting {
+            casting.left -= delta_time;
+            if casting.left <= 0.0 {
+                let casting = self.casting.take().unwrap();
+                match library.get(&casting.ability) {
+                    Some(def) => {
+                        let def = def.clone();
+                        self.finish(&def, casting.target);
+                    }
+                    // the file changed and the ability is gone
+                    None => self.events.push(AbilityEvent::Interrupted { ability: casting.ability }),
+                }
+            }
+        }
+        std::mem::take(&mut self.events)
+    }
+}
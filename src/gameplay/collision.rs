@@ -0,0 +1,318 @@
+// collision for the 3D scene on the cpu: boxes (that don't turn) and spheres, on their own or following an entity of
+// the graph or a static instance. there is no simulation here, the gameplay asks where something can go: where a shape
+// moving in a straight line hits first, the move that slides along what it hits (the camera uses that one so it
+// doesn't go through the grid) and the way out of what it overlaps
+// the shapes come from the bounds of the models like the picking (see gameplay/picking.rs), so a thin model collides
+// with its whole box
+
+use cgmath::{EuclideanSpace, InnerSpace, Point3, Vector3};
+
+use crate::gameplay::picking::PickVolume;
+use crate::rendering::camera::Aabb;
+use crate::rendering::instance_manager::{InstanceId, InstanceManager};
+use crate::scene::graph::{Entity, EntityId, SceneGraph};
+use crate::util::pool::{Pool, PoolHandle};
+
+// overlaps smaller than this are ignored and the moves stop this far from what they hit, so something resting on a
+// surface is not pushed every frame and the next sweep doesn't start inside it
+const SKIN: f32 = 0.001;
+// how many times a move can change direction in one call, a corner takes two
+const MAX_SLIDES: usize = 4;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Collider {
+    Aabb { half_extents: Vector3<f32> },
+    Sphere { radius: f32 },
+}
+
+impl Collider {
+    pub fn sphere(radius: f32) -> Self {
+        Collider::Sphere { radius }
+    }
+
+    // the shape for bounds already in world space, the same volumes as the picking
+    pub fn fit(bounds: &Aabb, volume: PickVolume) -> Self {
+        match volume {
+            PickVolume::Box => Collider::Aabb { half_extents: bounds.extents() },
+            PickVolume::Sphere => Collider::Sphere { radius: bounds.extents().magnitude() },
+        }
+    }
+
+    fn half_extents(&self) -> Vector3<f32> {
+        match *self {
+            Collider::Aabb { half_extents } => half_extents,
+            Collider::Sphere { radius } => Vector3::new(radius, radius, radius),
+        }
+    }
+}
+
+// what a collider follows
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Attachment {
+    Entity(EntityId),
+    Instance(InstanceId), // of the static batch
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct ColliderBody {
+    pub collider: Collider,
+    pub center: Point3<f32>,
+    pub attached: Option<Attachment>,
+    pub offset: Vector3<f32>, // from the position of what it follows to the center, it doesn't turn with it
+    pub solid: bool, // false is a trigger, the sweeps and the moves go through it
+}
+
+impl ColliderBody {
+    pub fn new(collider: Collider, center: Point3<f32>) -> Self {
+        Self { collider, center, attached: None, offset: Vector3::new(0.0, 0.0, 0.0), solid: true }
+    }
+}
+
+// where a sweep hit first
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SweepHit {
+    pub handle: PoolHandle,
+    pub time: f32, // 0 is the start of the movement and 1 the end
+    pub center: Point3<f32>, // of the moving shape when it touches
+    pub normal: Vector3<f32>, // of the surface it hit, pointing back to the shape
+}
+
+// the smallest move that takes shape a out of shape b, None if they don't overlap
+pub fn penetration(a: &Collider, a_center: Point3<f32>, b: &Collider, b_center: Point3<f32>) -> Option<Vector3<f32>> {
+    match (*a, *b) {
+        (Collider::Aabb { half_extents: a_half }, Collider::Aabb { half_extents: b_half }) => {
+            let delta = a_center - b_center;
+            let mut push: Option<Vector3<f32>> = None;
+            for axis in 0..3 {
+                let overlap = a_half[axis] + b_half[axis] - delta[axis].abs();
+                if overlap <= SKIN {
+                    return None;
+                }
+                if push.is_none_or(|push| overlap < push.magnitude()) {
+                    let mut out = Vector3::new(0.0, 0.0, 0.0);
+                    out[axis] = overlap * sign(delta[axis]);
+                    push = Some(out);
+                }
+            }
+            push
+        }
+        (Collider::Sphere { radius: a_radius }, Collider::Sphere { radius: b_radius }) => {
+            let delta = a_center - b_center;
+            let distance = delta.magnitude();
+            let overlap = a_radius + b_radius - distance;
+            if overlap <= SKIN {
+                return None;
+            }
+            let normal = if distance > 0.0 { delta / distance } else { Vector3::unit_y() };
+            Some(normal * overlap)
+        }
+        (Collider::Sphere { radius }, Collider::Aabb { half_extents }) => sphere_box(a_center, radius, b_center, half_extents),
+        (Collider::Aabb { half_extents }, Collider::Sphere { radius }) => sphere_box(b_center, radius, a_center, half_extents).map(|push| -push),
+    }
+}
+
+// pushes the sphere out of the box
+fn sphere_box(center: Point3<f32>, radius: f32, box_center: Point3<f32>, half_extents: Vector3<f32>) -> Option<Vector3<f32>> {
+    let local = center - box_center;
+    let closest = Vector3::new(
+        local.x.clamp(-half_extents.x, half_extents.x),
+        local.y.clamp(-half_extents.y, half_extents.y),
+        local.z.clamp(-half_extents.z, half_extents.z),
+    );
+
+    if closest == local {
+        // the center is inside the box, go out by the closest side
+        let axis = (0..3).min_by(|a, b| (half_extents[*a] - local[*a].abs()).total_cmp(&(half_extents[*b] - local[*b].abs()))).unwrap();
+        let mut push = Vector3::new(0.0, 0.0, 0.0);
+        push[axis] = (half_extents[axis] - local[axis].abs() + radius) * sign(local[axis]);
+        return Some(push);
+    }
+
+    let delta = local - closest;
+    let distance = delta.magnitude();
+    let overlap = radius - distance;
+    if overlap <= SKIN {
+        return None;
+    }
+    Some(delta / distance * overlap)
+}
+
+// where shape a moving from its center by the movement first touches shape b, None if it doesn't during the movement
+// or if they overlap already at the start (penetration is for that). a sphere against a box is tested as the box
+// grown by the radius, so near the edges of the box the sphere stops a little before touching it
+pub fn sweep(a: &Collider, from: Point3<f32>, movement: Vector3<f32>, b: &Collider, b_center: Point3<f32>) -> Option<(f32, Vector3<f32>)> {
+    match (*a, *b) {
+        (Collider::Sphere { radius: a_radius }, Collider::Sphere { radius: b_radius }) => sweep_sphere(from, movement, b_center, a_radius + b_radius),
+        _ => {
+            let half = a.half_extents() + b.half_extents();
+            sweep_box(from, movement, b_center - half, b_center + half)
+        }
+    }
+}
+
+// the point moving against the box, slab method like Ray::intersect_aabb but with the axis it went in by
+fn sweep_box(from: Point3<f32>, movement: Vector3<f32>, min: Point3<f32>, max: Point3<f32>) -> Option<(f32, Vector3<f32>)> {
+    let mut near = f32::NEG_INFINITY;
+    let mut far = f32::INFINITY;
+    let mut normal = Vector3::new(0.0, 0.0, 0.0);
+    for axis in 0..3 {
+        let (origin, direction) = (from[axis], movement[axis]);
+        if direction.abs() < 1e-8 {
+            // parallel to these two faces, it has to be between them already
+            if origin <= min[axis] || origin >= max[axis] {
+                return None;
+            }
+            continue;
+        }
+        let a = (min[axis] - origin) / direction;
+        let b = (max[axis] - origin) / direction;
+        if a.min(b) > near {
+            near = a.min(b);
+            normal = Vector3::new(0.0, 0.0, 0.0);
+            normal[axis] = -sign(direction);
+        }
+        far = far.min(a.max(b));
+        if near > far {
+            return None;
+        }
+    }
+    // behind it, inside it already or too far for this movement
+    if !(0.0..=1.0).contains(&near) {
+        return None;
+    }
+    Some((near, normal))
+}
+
+// the point moving against the sphere of both radii
+fn sweep_sphere(from: Point3<f32>, movement: Vector3<f32>, center: Point3<f32>, radius: f32) -> Option<(f32, Vector3<f32>)> {
+    let to_start = from - center;
+    let c = to_start.magnitude2() - radius * radius;
+    let a = movement.magnitude2();
+    if c <= 0.0 || a < 1e-12 {
+        return None;
+    }
+    let b = to_start.dot(movement);
+    let discriminant = b * b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let time = (-b - discriminant.sqrt()) / a;
+    if !(0.0..=1.0).contains(&time) {
+        return None;
+    }
+    Some((time, (from + movement * time - center).normalize()))
+}
+
+fn sign(value: f32) -> f32 {
+    if value < 0.0 { -1.0 } else { 1.0 }
+}
+
+pub struct CollisionWorld {
+    bodies: Pool<ColliderBody>,
+}
+
+impl CollisionWorld {
+    pub fn new() -> Self {
+        Self { bodies: Pool::new() }
+    }
+
+    pub fn add(&mut self, body: ColliderBody) -> PoolHandle {
+        self.bodies.insert(body)
+    }
+
+    // the collider that follows it, if it has one
+    pub fn find(&self, attachment: Attachment) -> Option<PoolHandle> {
+        self.bodies.iter().find(|(_, body)| body.attached == Some(attachment)).map(|(handle, _)| handle)
+    }
+
+    // a collider around the model of the entity where it is now, None if it doesn't have a model with bounds
+    // the world transforms of the graph have to be updated
+    pub fn attach_entity(&mut self, graph: &SceneGraph, id: EntityId, volume: PickVolume) -> Option<PoolHandle> {
+        let entity = graph.get(id)?;
+        let bounds = graph.model(entity.model?).bounds()?.transformed(&entity.world_matrix());
+        let center = bounds.center();
+        let offset = center - Point3::from_vec(entity.world_position());
+        Some(self.add(ColliderBody { attached: Some(Attachment::Entity(id)), offset, ..ColliderBody::new(Collider::fit(&bounds, volume), center) }))
+    }
+
+    // the same for a static instance, the bounds are the ones of the model the static batch draws
+    pub fn attach_instance(&mut self, instances: &InstanceManager, id: InstanceId, bounds: &Aabb, volume: PickVolume) -> Option<PoolHandle> {
+        let matrix: cgmath::Matrix4<f32> = (*instances.get(id)?).into();
+        let bounds = bounds.transformed(&matrix);
+        let center = bounds.center();
+        let offset = center - Point3::from_vec(matrix.w.truncate());
+        Some(self.add(ColliderBody { attached: Some(Attachment::Instance(id)), offset, ..ColliderBody::new(Collider::fit(&bounds, volume), center) }))
+    }
+
+    // the attached colliders go where their entity or instance is, the ones of something that was despawned go too
+    // world_matrix is where the entity is drawn, the animated instances are not where the graph has them
+    pub fn sync(&mut self, graph: &SceneGraph, instances: &InstanceManager, world_matrix: impl Fn(&Entity) -> cgmath::Matrix4<f32>) {
+        self.bodies.retain(|body| {
+            let position = match body.attached {
+                None => return true,
+                Some(Attachment::Entity(id)) => graph.get(id).map(|entity| world_matrix(entity).w.truncate()),
+                Some(Attachment::Instance(id)) => instances.get(id).map(|matrix| Vector3::new(matrix[3][0], matrix[3][1], matrix[3][2])),
+            };
+            let Some(position) = position else { return false };
+            body.center = Point3::from_vec(position + body.offset);
+            true
+        });
+    }
+
+    // the first solid collider the shape hits moving in a straight line, the ignored one is usually its own
+    pub fn sweep(&self, collider: &Collider, from: Point3<f32>, movement: Vector3<f32>, ignore: Option<PoolHandle>) -> Option<SweepHit> {
+        let mut closest: Option<SweepHit> = None;
+        for (handle, body) in self.bodies.iter() {
+            if !body.solid || Some(handle) == ignore {
+                continue;
+            }
+            let Some((time, normal)) = sweep(collider, from, movement, &body.collider, body.center) else { continue };
+            if closest.is_none_or(|hit| time < hit.time) {
+                closest = Some(SweepHit { handle, time, center: from + movement * time, normal });
+            }
+        }
+        closest
+    }
+
+    // where the shape ends up trying to move, it stops at what it hits and goes on along its surface with what was
+    // left of the movement (walls and floors slide instead of stopping everything)
+    pub fn move_and_slide(&self, collider: &Collider, from: Point3<f32>, movement: Vector3<f32>, ignore: Option<PoolHandle>) -> Point3<f32> {
+        let mut position = from;
+        let mut remaining = movement;
+        for _ in 0..MAX_SLIDES {
+            let length = remaining.magnitude();
+            if length <= SKIN {
+                break;
+            }
+            let Some(hit) = self.sweep(collider, position, remaining, ignore) else {
+                position += remaining;
+                break;
+            };
+            let travel = (hit.time - SKIN / length).max(0.0);
+            position += remaining * travel;
+            let left = remaining * (1.0 - travel);
+            remaining = left - hit.normal * left.dot(hit.normal);
+        }
+        position
+    }
+
+    // the shape taken out of the solid colliders it overlaps, for something that was placed (or spawned) inside one
+    pub fn push_out(&self, collider: &Collider, center: Point3<f32>, ignore: Option<PoolHandle>) -> Point3<f32> {
+        let mut center = center;
+        for (handle, body) in self.bodies.iter() {
+            if !body.solid || Some(handle) == ignore {
+                continue;
+            }
+            if let Some(push) = penetration(collider, center, &body.collider, body.center) {
+                center += push;
+            }
+        }
+        center
+    }
+}
+
+impl Default for CollisionWorld {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,182 @@
+// branching conversations from ron files in the dialogues folder of the assets (see util/data_files.rs): a dialogue
+// is a set of named nodes, each one a line of a speaker and the choices of the player, a choice goes to another node
+// (or ends the conversation) and can raise an event the game listens to (a quest starts, a door opens)
+// the lines are localization keys (see util/localization.rs), never the text itself
+//
+//   [
+//       (id: "guard", start: "hello", nodes: {
+//           "hello": (speaker: Some("npc.guard"), text: "dialogue.guard.hello", choices: [
+//               (text: "dialogue.guard.friend", next: Some("pass"), event: Some("guard_friendly")),
+//               (text: "dialogue.guard.leave"),
+//           ]),
+//           "pass": (speaker: Some("npc.guard"), text: "dialogue.guard.pass", events: ["open_gate"]),
+//       }),
+//   ]
+//
+// a node without choices goes on to its next node (or ends) when the player continues
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use serde::Deserialize;
+
+use crate::util::data_files::DataFiles;
+
+pub const DIALOGUE_FOLDER: &str = "dialogues";
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct DialogueChoice {
+    pub text: String,
+    #[serde(default)]
+    pub next: Option<String>, // none ends the conversation
+    #[serde(default)]
+    pub event: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct DialogueNode {
+    #[serde(default)]
+    pub speaker: Option<String>,
+    pub text: String,
+    #[serde(default)]
+    pub choices: Vec<DialogueChoice>,
+    #[serde(default)]
+    pub next: Option<String>, // for the nodes without choices
+    #[serde(default)]
+    pub events: Vec<String>, // raised when the node is reached
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct DialogueDef {
+    pub id: String,
+    pub start: String,
+    pub nodes: HashMap<String, DialogueNode>,
+}
+
+impl DialogueDef {
+    // the links to nodes that don't exist, a typo in the file ends the conversation there instead of failing
+    fn broken_links(&self) -> Vec<&str> {
+        let choices = self.nodes.values().flat_map(|node| node.choices.iter().filter_map(|choice| choice.next.as_deref()));
+        let nexts = self.nodes.values().filter_map(|node| node.next.as_deref());
+        std::iter::once(self.start.as_str()).chain(choices).chain(nexts).filter(|node| !self.nodes.contains_key(*node)).collect()
+    }
+}
+
+// every dialogue of the files by id, the one of the last file wins when two have the same id
+pub struct DialogueLibrary {
+    files: DataFiles<DialogueDef>,
+    dialogues: HashMap<String, DialogueDef>,
+}
+
+impl DialogueLibrary {
+    pub fn load() -> Self {
+        let files = DataFiles::load(DIALOGUE_FOLDER);
+        let dialogues = Self::by_id(&files);
+        Self { files, dialogues }
+    }
+
+    pub fn reload(&mut self) {
+        self.files.reload();
+        self.dialogues = Self::by_id(&self.files);
+    }
+
+    fn by_id(files: &DataFiles<DialogueDef>) -> HashMap<String, DialogueDef> {
+        for def in files.definitions() {
+            for node in def.broken_links() {
+                eprintln!("the dialogue {} goes to the node {} that it doesn't have", def.id, node);
+            }
+        }
+        files.definitions().map(|def| (def.id.clone(), def.clone())).collect()
+    }
+
+    pub fn get(&self, id: &str) -> Option<&DialogueDef> {
+        self.dialogues.get(id)
+    }
+
+    // the files on disk, for the file watcher
+    pub fn files(&self) -> Vec<PathBuf> {
+        self.files.paths()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DialogueEvent {
+    Started { dialogue: String },
+    Chose { dialogue: String, node: String, choice: usize },
+    Event { dialogue: String, name: String }, // the ones of the files
+    Ended { dialogue: String },
+}
+
+// one conversation being held, the library can change under it (hot reload), a node that is gone ends it
+pub struct Conversation {
+    dialogue: String,
+    node: Option<String>, // none once it ended
+    ended: bool,
+    events: Vec<DialogueEvent>,
+}
+
+impl Conversation {
+    pub fn start(library: &DialogueLibrary, dialogue: &str) -> anyhow::Result<Self> {
+        let def = library.get(dialogue).ok_or_else(|| anyhow!("there is no dialogue {}", dialogue))?;
+        let mut conversation = Self { dialogue: def.id.clone(), node: None, ended: false, events: vec![DialogueEvent::Started { dialogue: def.id.clone() }] };
+        conversation.enter(library, Some(def.start.clone()));
+        Ok(conversation)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.ended
+    }
+
+    pub fn node<'a>(&self, library: &'a DialogueLibrary) -> Option<&'a DialogueNode> {
+        library.get(&self.dialogue)?.nodes.get(self.node.as_ref()?)
+    }
+
+    fn enter(&mut self, library: &DialogueLibrary, node: Option<String>) {
+        self.node = node;
+        match self.node(library) {
+            Some(current) => {
+                for name in &current.events {
+                    self.events.push(DialogueEvent::Event { dialogue: self.dialogue.clone(), name: name.clone() });
+                }
+            }
+            None => self.end(),
+        }
+    }
+
+    // false when the node doesn't have that choice
+    pub fn choose(&mut self, library: &DialogueLibrary, index: usize) -> bool {
+        let Some(choice) = self.node(library).and_then(|node| node.choices.get(index)) else { return false };
+        let choice = choice.clone();
+        let node = self.node.clone().unwrap_or_default();
+        self.events.push(DialogueEvent::Chose { dialogue: self.dialogue.clone(), node, choice: index });
+        if let Some(name) = choice.event {
+            self.events.push(DialogueEvent::Event { dialogue: self.dialogue.clone(), name });
+        }
+        self.enter(library, choice.next);
+        true
+    }
+
+    // a node without choices goes on, one with choices waits for one
+    pub fn advance(&mut self, library: &DialogueLibrary) {
+        let next = match self.node(library) {
+            Some(node) if !node.choices.is_empty() => return,
+            Some(node) => node.next.clone(),
+            None => None,
+        };
+        self.enter(library, next);
+    }
+
+    // walking away, the rest of the conversation doesn't happen
+    pub fn end(&mut self) {
+        self.node = None;
+        if !self.ended {
+            self.ended = true;
+            self.events.push(DialogueEvent::Ended { dialogue: self.dialogue.clone() });
+        }
+    }
+
+    pub fn take_events(&mut self) -> Vec<DialogueEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
@@ -0,0 +1,193 @@
+// what the player (or a chest, a shop) carries: the items are defined in ron files in the items folder of the assets
+// (see util/data_files.rs) with their name, icon, how many go in one slot and free properties the gameplay reads
+// (damage, weight, what a potion heals), and an Inventory is a fixed number of slots with stacks of them
+//
+//   [
+//       (id: "potion", name: "Health potion", icon: Some("textures/items/potion.png"), max_stack: 10, color: (200, 40, 60),
+//        properties: {"heals": Number(25.0)}),
+//   ]
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+use anyhow::bail;
+use serde::Deserialize;
+
+use crate::util::data_files::DataFiles;
+
+pub const ITEM_FOLDER: &str = "items";
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub enum Property {
+    Bool(bool),
+    Number(f64),
+    Text(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct ItemDef {
+    pub id: String, // what the game and the saves use, the name can change
+    pub name: String,
+    #[serde(default)]
+    pub icon: Option<String>, // a path under the assets
+    #[serde(default = "one")]
+    pub max_stack: u32,
+    #[serde(default = "grey")]
+    pub color: (u8, u8, u8), // behind the icon, and instead of it while there is none
+    #[serde(default)]
+    pub properties: BTreeMap<String, Property>,
+}
+
+fn one() -> u32 {
+    1
+}
+
+fn grey() -> (u8, u8, u8) {
+    (128, 128, 128)
+}
+
+impl ItemDef {
+    pub fn number(&self, property: &str) -> Option<f64> {
+        match self.properties.get(property)? {
+            Property::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+// every item of the files by id, the one of the last file wins when two have the same id
+pub struct ItemLibrary {
+    files: DataFiles<ItemDef>,
+    items: HashMap<String, ItemDef>,
+}
+
+impl ItemLibrary {
+    pub fn load() -> Self {
+        let files = DataFiles::load(ITEM_FOLDER);
+        let items = Self::by_id(&files);
+        Self { files, items }
+    }
+
+    pub fn reload(&mut self) {
+        self.files.reload();
+        self.items = Self::by_id(&self.files);
+    }
+
+    fn by_id(files: &DataFiles<ItemDef>) -> HashMap<String, ItemDef> {
+        files.definitions().map(|def| (def.id.clone(), def.clone())).collect()
+    }
+
+    pub fn get(&self, id: &str) -> Option<&ItemDef> {
+        self.items.get(id)
+    }
+
+    // how many fit in a slot, 1 for the items that are gone from the files
+    pub fn max_stack(&self, id: &str) -> u32 {
+        self.get(id).map_or(1, |def| def.max_stack.max(1))
+    }
+
+    // the files on disk, for the file watcher
+    pub fn files(&self) -> Vec<PathBuf> {
+        self.files.paths()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ItemStack {
+    pub item: String,
+    pub count: u32,
+}
+
+pub struct Inventory {
+    slots: Vec<Option<ItemStack>>,
+}
+
+impl Inventory {
+    pub fn new(size: usize) -> Self {
+        Self { slots: vec![None; size] }
+    }
+
+    pub fn slots(&self) -> &[Option<ItemStack>] {
+        &self.slots
+    }
+
+    pub fn slot(&self, index: usize) -> Option<&ItemStack> {
+        self.slots.get(index)?.as_ref()
+    }
+
+    pub fn count(&self, item: &str) -> u32 {
+        self.slots.iter().flatten().filter(|stack| stack.item == item).map(|stack| stack.count).sum()
+    }
+
+    // the stacks that have room first, then the empty slots, what doesn't fit is returned (it stays on the ground)
+    pub fn add(&mut self, library: &ItemLibrary, item: &str, count: u32) -> anyhow::Result<u32> {
+        if library.get(item).is_none() {
+            bail!("there is no item {}", item);
+        }
+        let max_stack = library.max_stack(item);
+        let mut left = count;
+        for stack in self.slots.iter_mut().flatten().filter(|stack| stack.item == item) {
+            let moved = left.min(max_stack.saturating_sub(stack.count));
+            stack.count += moved;
+            left -= moved;
+        }
+        for slot in self.slots.iter_mut().filter(|slot| slot.is_none()) {
+            if left == 0 {
+                break;
+            }
+            let moved = left.min(max_stack);
+            *slot = Some(ItemStack { item: item.to_string(), count: moved });
+            left -= moved;
+        }
+        Ok(left)
+    }
+
+    // from the last stacks so the first ones stay full, how many were removed
+    pub fn remove(&mut self, item: &str, count: u32) -> u32 {
+        let mut left = count;
+        for slot in self.slots.iter_mut().rev() {
+            let Some(stack) = slot.as_mut().filter(|stack| stack.item == item) else { continue };
+            let taken = left.min(stack.count);
+            stack.count -= taken;
+            left -= taken;
+            if stack.count == 0 {
+                *slot = None;
+            }
+            if left == 0 {
+                break;
+            }
+        }
+        count - left
+    }
+
+    // dropping a stack on another slot: the same item fills it up to the max and the rest stays, anything else swaps
+    pub fn move_stack(&mut self, library: &ItemLibrary, from: usize, to: usize) {
+        if from == to || from >= self.slots.len() || to >= self.slots.len() {
+            return;
+        }
+        let same_item = matches!((&self.slots[from], &self.slots[to]), (Some(a), Some(b)) if a.item == b.item);
+        if !same_item {
+            self.slots.swap(from, to);
+            return;
+        }
+        let max_stack = library.max_stack(&self.slots[to].as_ref().unwrap().item);
+        let room = max_stack.saturating_sub(self.slots[to].as_ref().unwrap().count);
+        let moved = room.min(self.slots[from].as_ref().unwrap().count);
+        self.slots[to].as_mut().unwrap().count += moved;
+        let source = self.slots[from].as_mut().unwrap();
+        source.count -= moved;
+        if source.count == 0 {
+            self.slots[from] = None;
+        }
+    }
+
+    // half of the stack (rounded down) to the first empty slot, false when there is none or nothing to split
+    pub fn split(&mut self, index: usize) -> bool {
+        let Some(half) = self.slot(index).map(|stack| stack.count / 2).filter(|half| *half > 0) else { return false };
+        let Some(empty) = self.slots.iter().position(|slot| slot.is_none()) else { return false };
+        let stack = self.slots[index].as_mut().unwrap();
+        stack.count -= half;
+        self.slots[empty] = Some(ItemStack { item: stack.item.clone(), count: half });
+        true
+    }
+}
@@ -0,0 +1,264 @@
+// simple physics for the 2D mode: boxes and circles, static colliders (walls, tilemap collision) and kinematic bodies
+// that move with their velocity and get pushed out of what they hit, enough for platformers and top down games
+// the world uses the same coordinates as the sprites, pixels with y going down, so gravity is positive y
+// it has no clock of its own, whoever owns it calls step() from the fixed steps it already runs (the ones of the app
+// in the client, see scene/level2d.rs, the tick of the server) so the 2D and 3D physics move together
+
+use cgmath::{InnerSpace, Vector2};
+
+use crate::util::pool::{Pool, PoolHandle};
+
+// overlaps smaller than this are ignored, so a body resting on the floor is not pushed every step
+const SKIN: f32 = 0.001;
+
+#[derive(Copy, Clone, Debug)]
+pub enum Shape2D {
+    Aabb { half_extents: Vector2<f32> },
+    Circle { radius: f32 },
+}
+
+impl Shape2D {
+    pub fn rect(width: f32, height: f32) -> Self {
+        Shape2D::Aabb { half_extents: Vector2::new(width * 0.5, height * 0.5) }
+    }
+
+    pub fn circle(radius: f32) -> Self {
+        Shape2D::Circle { radius }
+    }
+
+    // min and max corners around the center
+    pub fn bounds(&self, center: Vector2<f32>) -> (Vector2<f32>, Vector2<f32>) {
+        let half = match *self {
+            Shape2D::Aabb { half_extents } => half_extents,
+            Shape2D::Circle { radius } => Vector2::new(radius, radius),
+        };
+        (center - half, center + half)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BodyKind {
+    Static,    // never moves, walls and floors
+    Kinematic, // moves with its velocity and is stopped by the static bodies
+    Trigger,   // never moves and doesn't block, only reports who is inside
+}
+
+// which sides touched something in the last step, bottom is the floor because y goes down
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Contacts {
+    pub left: bool,
+    pub right: bool,
+    pub top: bool,
+    pub bottom: bool,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Body2D {
+    pub position: Vector2<f32>, // center of the shape
+    pub previous_position: Vector2<f32>,
+    pub velocity: Vector2<f32>,
+    pub shape: Shape2D,
+    pub kind: BodyKind,
+    pub gravity_scale: f32,
+    pub contacts: Contacts,
+}
+
+impl Body2D {
+    pub fn new(kind: BodyKind, shape: Shape2D, position: Vector2<f32>) -> Self {
+        Self {
+            position,
+            previous_position: position,
+            velocity: Vector2::new(0.0, 0.0),
+            shape,
+            kind,
+            gravity_scale: if kind == BodyKind::Kinematic { 1.0 } else { 0.0 },
+            contacts: Contacts::default(),
+        }
+    }
+
+    pub fn on_ground(&self) -> bool {
+        self.contacts.bottom
+    }
+}
+
+// the smallest move that takes shape a out of shape b, None if they don't overlap
+pub fn penetration(a: &Shape2D, a_position: Vector2<f32>, b: &Shape2D, b_position: Vector2<f32>) -> Option<Vector2<f32>> {
+    match (*a, *b) {
+        (Shape2D::Aabb { half_extents: a_half }, Shape2D::Aabb { half_extents: b_half }) => {
+            let delta = a_position - b_position;
+            let overlap_x = a_half.x + b_half.x - delta.x.abs();
+            let overlap_y = a_half.y + b_half.y - delta.y.abs();
+            if overlap_x <= SKIN || overlap_y <= SKIN {
+                return None;
+            }
+            if overlap_x < overlap_y {
+                Some(Vector2::new(overlap_x * sign(delta.x), 0.0))
+            } else {
+                Some(Vector2::new(0.0, overlap_y * sign(delta.y)))
+            }
+        }
+        (Shape2D::Circle { radius: a_radius }, Shape2D::Circle { radius: b_radius }) => {
+            let delta = a_position - b_position;
+            let distance = delta.magnitude();
+            let overlap = a_radius + b_radius - distance;
+            if overlap <= SKIN {
+                return None;
+            }
+            let normal = if distance > 0.0 { delta / distance } else { Vector2::new(0.0, -1.0) };
+            Some(normal * overlap)
+        }
+        (Shape2D::Circle { radius }, Shape2D::Aabb { half_extents }) => circle_box(a_position, radius, b_position, half_extents),
+        (Shape2D::Aabb { half_extents }, Shape2D::Circle { radius }) => circle_box(b_position, radius, a_position, half_extents).map(|push| -push),
+    }
+}
+
+// pushes the circle out of the box
+fn circle_box(center: Vector2<f32>, radius: f32, box_center: Vector2<f32>, half_extents: Vector2<f32>) -> Option<Vector2<f32>> {
+    let local = center - box_center;
+    let closest = Vector2::new(local.x.clamp(-half_extents.x, half_extents.x), local.y.clamp(-half_extents.y, half_extents.y));
+
+    if closest == local {
+        // the center is inside the box, go out by the closest side
+        let to_x = half_extents.x - local.x.abs();
+        let to_y = half_extents.y - local.y.abs();
+        return if to_x < to_y {
+            Some(Vector2::new((to_x + radius) * sign(local.x), 0.0))
+        } else {
+            Some(Vector2::new(0.0, (to_y + radius) * sign(local.y)))
+        };
+    }
+
+    let delta = local - closest;
+    let distance = delta.magnitude();
+    let overlap = radius - distance;
+    if overlap <= SKIN {
+        return None;
+    }
+    Some(delta / distance * overlap)
+}
+
+fn sign(value: f32) -> f32 {
+    if value < 0.0 { -1.0 } else { 1.0 }
+}
+
+// a solid rectangle in world units (pixels), the top left corner and the size, the tilemaps make them from their
+// collision objects
+#[derive(Copy, Clone, Debug)]
+pub struct MapCollider {
+    pub position: Vector2<f32>,
+    pub size: Vector2<f32>,
+}
+
+pub struct PhysicsWorld2D {
+    pub gravity: Vector2<f32>,
+    bodies: Pool<Body2D>,
+    triggered: Vec<(PoolHandle, PoolHandle)>,
+}
+
+impl PhysicsWorld2D {
+    pub fn new() -> Self {
+        Self {
+            gravity: Vector2::new(0.0, 980.0), // about 9.8 m/s² if a meter is 100 pixels
+            bodies: Pool::new(),
+            triggered: Vec::new(),
+        }
+    }
+
+    pub fn add_body(&mut self, body: Body2D) -> PoolHandle {
+        self.bodies.insert(body)
+    }
+
+    pub fn body_mut(&mut self, handle: PoolHandle) -> Option<&mut Body2D> {
+        self.bodies.get_mut(handle)
+    }
+
+    // for the status of the server
+    #[cfg(feature = "server")]
+    pub fn bodies(&self) -> impl Iterator<Item = (PoolHandle, &Body2D)> {
+        self.bodies.iter()
+    }
+
+    // the collision rectangles of a tilemap become static boxes
+    pub fn add_map_colliders(&mut self, colliders: &[MapCollider]) -> Vec<PoolHandle> {
+        colliders
+            .iter()
+            .map(|collider| {
+                let center = collider.position + collider.size * 0.5;
+                self.add_body(Body2D::new(BodyKind::Static, Shape2D::rect(collider.size.x, collider.size.y), center))
+            })
+            .collect()
+    }
+
+    // moves the kinematic bodies one step, first on x and then on y so they slide along walls and floors
+    pub fn step(&mut self, delta_time: f32) {
+        let blockers: Vec<(Shape2D, Vector2<f32>)> =
+            self.bodies.iter().filter(|(_, body)| body.kind == BodyKind::Static).map(|(_, body)| (body.shape, body.position)).collect();
+        let gravity = self.gravity;
+
+        for (_, body) in self.bodies.iter_mut() {
+            body.previous_position = body.position;
+            if body.kind != BodyKind::Kinematic {
+                continue;
+            }
+            body.velocity += gravity * body.gravity_scale * delta_time;
+            body.contacts = Contacts::default();
+
+            for axis in 0..2 {
+                let mut movement = Vector2::new(0.0, 0.0);
+                movement[axis] = body.velocity[axis] * delta_time;
+                body.position += movement;
+                resolve(body, &blockers);
+            }
+        }
+
+        self.triggered.clear();
+        for (trigger_handle, trigger) in self.bodies.iter().filter(|(_, body)| body.kind == BodyKind::Trigger) {
+            for (handle, body) in self.bodies.iter().filter(|(_, body)| body.kind == BodyKind::Kinematic) {
+                if penetration(&body.shape, body.position, &trigger.shape, trigger.position).is_some() {
+                    self.triggered.push((trigger_handle, handle));
+                }
+            }
+        }
+    }
+
+    // (trigger, body) pairs that were overlapping at the end of the last step
+    pub fn triggered(&self) -> &[(PoolHandle, PoolHandle)] {
+        &self.triggered
+    }
+
+    // where to draw a body this frame, between the last two steps so the movement is smooth at any framerate
+    // alpha is the one of the timestep that runs the steps (FixedTimestep::alpha)
+    pub fn interpolated_position(&self, handle: PoolHandle, alpha: f32) -> Option<Vector2<f32>> {
+        let body = self.bodies.get(handle)?;
+        Some(body.previous_position + (body.position - body.previous_position) * alpha)
+    }
+}
+
+fn resolve(body: &mut Body2D, blockers: &[(Shape2D, Vector2<f32>)]) {
+    for (shape, position) in blockers {
+        let (min, max) = body.shape.bounds(body.position);
+        let (other_min, other_max) = shape.bounds(*position);
+        if max.x < other_min.x || min.x > other_max.x || max.y < other_min.y || min.y > other_max.y {
+            continue;
+        }
+        let Some(push) = penetration(&body.shape, body.position, shape, *position) else { continue };
+        body.position += push;
+
+        // stop the velocity going into the surface and remember the side we touched
+        let normal = push.normalize();
+        let into = body.velocity.dot(normal);
+        if into < 0.0 {
+            body.velocity -= normal * into;
+        }
+        if normal.y < -0.5 {
+            body.contacts.bottom = true;
+        } else if normal.y > 0.5 {
+            body.contacts.top = true;
+        }
+        if normal.x > 0.5 {
+            body.contacts.left = true;
+        } else if normal.x < -0.5 {
+            body.contacts.right = true;
+        }
+    }
+}
@@ -0,0 +1,71 @@
+// what the mouse is over in the 3D scene: the mouse ray against the bounds of every entity (and of the static
+// instances), the closest one wins. the bounds are the box of the model moved to the world, not the triangles, so a
+// click near the edge of a thin model can hit the empty corner of its box
+
+use cgmath::InnerSpace;
+
+use crate::rendering::camera::{Aabb, Ray};
+use crate::scene::graph::{Entity, EntityId, SceneGraph};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PickVolume {
+    Box,    // the box of the model in world space, tight for boxy things
+    Sphere, // the sphere around that box, more forgiving for small things
+}
+
+// what the app found under the mouse (see App::pick)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Picked {
+    Entity(EntityId),
+    StaticInstance(usize), // the index in the static batch, it can change when the brush erases instances
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PickHit<T> {
+    pub target: T, // the entity, or the index of the instance
+    pub distance: f32,
+    pub point: cgmath::Point3<f32>, // where the ray enters the volume
+}
+
+// the distance along the ray to the model bounds placed with the matrix
+pub fn ray_bounds(ray: &Ray, bounds: &Aabb, matrix: &cgmath::Matrix4<f32>, volume: PickVolume) -> Option<f32> {
+    let world = bounds.transformed(matrix);
+    match volume {
+        PickVolume::Box => ray.intersect_aabb(&world),
+        PickVolume::Sphere => ray.intersect_sphere(world.center(), world.extents().magnitude()),
+    }
+}
+
+// the closest of many copies of the same model, returns the index of its matrix
+pub fn pick_instance(ray: &Ray, bounds: &Aabb, matrices: &[cgmath::Matrix4<f32>], volume: PickVolume) -> Option<PickHit<usize>> {
+    let mut closest: Option<PickHit<usize>> = None;
+    for (index, matrix) in matrices.iter().enumerate() {
+        let Some(distance) = ray_bounds(ray, bounds, matrix, volume) else { continue };
+        if closest.is_none_or(|hit| distance < hit.distance) {
+            closest = Some(PickHit { target: index, distance, point: ray.at(distance) });
+        }
+    }
+    closest
+}
+
+// the closest visible entity with a model, the world transforms of the graph have to be updated
+// world_matrix is where each one is drawn, Entity::world_matrix or the one of the animation (InstanceAnimator::entity_matrix)
+pub fn pick_entity(graph: &SceneGraph, ray: &Ray, volume: PickVolume, world_matrix: impl Fn(&Entity) -> cgmath::Matrix4<f32>) -> Option<PickHit<EntityId>> {
+    let mut closest: Option<PickHit<EntityId>> = None;
+    // from the roots like the renderer, so the children of a hidden entity can't be clicked either
+    let mut stack = graph.roots().to_vec();
+    while let Some(id) = stack.pop() {
+        let Some(entity) = graph.get(id) else { continue };
+        if !entity.visible {
+            continue;
+        }
+        stack.extend_from_slice(entity.children());
+        let Some(bounds) = entity.model.and_then(|model| graph.model(model).bounds()) else { continue };
+        let Some(distance) = ray_bounds(ray, &bounds, &world_matrix(entity), volume) else { continue };
+        if closest.is_none_or(|hit| distance < hit.distance) {
+            closest = Some(PickHit { target: id, distance, point: ray.at(distance) });
+        }
+    }
+    closest
+}
+
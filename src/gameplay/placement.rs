@@ -0,0 +1,15 @@
+// helpers to know where the mouse is pointing in the world, for click to move or placement previews
+
+use crate::rendering::camera::{Camera, Plane, Ray};
+
+// the mouse ray against a flat ground at the given height
+pub fn cursor_ground_point(camera: &Camera, mouse_x: f32, mouse_y: f32, width: f32, height: f32, ground_height: f32) -> Option<cgmath::Point3<f32>> {
+    let ray = camera.screen_to_ray(mouse_x, mouse_y, width, height)?;
+    ray_ground_point(&ray, ground_height)
+}
+
+pub fn ray_ground_point(ray: &Ray, ground_height: f32) -> Option<cgmath::Point3<f32>> {
+    let ground = Plane { normal: cgmath::Vector3::unit_y(), distance: -ground_height };
+    let t = ray.intersect_plane(&ground)?;
+    Some(ray.at(t))
+}
@@ -1,16 +1,14 @@
 use std::time::{Duration, Instant};
 
-use cgmath::InnerSpace;
-use sdl2::{event::Event, keyboard::Keycode, pixels::Color, ttf::Font};
-use wgpu::BindGroupLayoutDescriptor;
-use crate::{app::{App, AppState}, game_object::GameObject, input::button_module::{Button, TextAlign}, rendering::textures::Texture};
-
-pub struct Controller {
-    forward: bool,
-    backwards: bool,
-    left: bool,
-    right: bool
-}
+use cgmath::{EuclideanSpace, InnerSpace, Point3, Rotation3};
+use sdl2::{keyboard::Keycode, mouse::MouseButton, pixels::Color, rect::Rect, ttf::Font};
+use wgpu::{BindGroupLayoutDescriptor, PresentMode};
+use crate::{app::{App, AppState, GameState}, audio::mixer::SoundHandle, debug::{curve_editor::CurveEditor, profiler::profile_scope}, editor::{inspector::{Inspector, Selection}, instance_brush::{BrushMode, GroundSurface, InstanceBrush}}, gameplay::{abilities::{AbilityEvent, AbilitySet, AbilityTarget, Effect}, collision::Collider, dialogue::DialogueEvent, inventory::Inventory, picking::{PickHit, PickVolume, Picked}, placement}, scene::graph::EntityId, game_object::GameObject, input::{button_module::{Button, TextAlign}, input_state::InputButton}, net::chat::{ChatChannel, ChatMessage}, rendering::{blob_shadows::{BlobShadow, ShadowAnchor, ShadowShape}, camera::Camera, debug_view::DebugView, display_output::{Calibration, OutputMode}, ground_grid::GroundGrid, particles::{EmitterAnchor, EmitterSettings}, textures::Texture, trails::{TrailAnchor, TrailSettings}}, ui::{captions::Caption, scale::UiSettings, dialogue_panel::DialoguePanel, inventory_panel::InventoryPanel, options_menu::{OptionsAction, OptionsMenu}, script_screen::ScriptAction, session_browser::{BrowserAction, SessionBrowserMenu}}, util::{color::Color as LinearColor, curve::{Curve, Gradient}, cvars::CvarValue, monitors::FullscreenMode, settings::EngineSettings}};
+
+const GRID_SPIN_SPEED: f32 = 10.0; // degrees per second
+const CAMERA_RADIUS: f32 = 0.3; // how close the eye gets to the colliders
+const WHEEL_ZOOM: f32 = 0.5; // units per notch of the wheel
+const ORBIT_PER_PIXEL: f32 = 0.005; // how far the middle mouse drag turns the camera
 
 pub struct GameLogic { // here we define the data we use on our script
     fps: u32,
@@ -19,16 +17,104 @@ pub struct GameLogic { // here we define the data we use on our script
     pub start_time: Instant,
     frame_count: u32,
     frame_timer: Duration,
-    controller: Controller,
-    speed: f32
+    speed: f32,
+    brush: InstanceBrush,
+    brush_enabled: bool, // the ToggleBrush action, then the left mouse paints and the right one erases
+    calibration: Calibration, // F7 opens the display calibration
+    grid: Option<EntityId>, // the demo instance grid, it spins in the fixed steps
+    browser: SessionBrowserMenu, // F9 lists the games on the lan
+    abilities: AbilitySet, // Q dashes and R focuses, see assets/abilities/player.ron
+    inventory: Inventory,
+    inventory_panel: InventoryPanel, // I
+    dialogue: DialoguePanel, // E talks to the guard of assets/dialogues/guard.ron
+    greeting: Option<SoundHandle>, // the last greeting of the guard, talking again cuts it
+    options: OptionsMenu, // F10
+    sun_editor: CurveEditor, // F6 edits the strength of the sun over the day
+    inspector: Inspector, // F1 shows and edits what was clicked, with undo
 } 
 
 impl GameLogic {
     // this is called once
-    pub fn new(_app: &mut App, speed: f32) -> Self {
+    pub fn new(app: &mut App, speed: f32) -> Self {
         // UI ELEMENTS AND LIST
         let framerate = Button::new(GameObject {active: true, x:10 as f32, y: 10.0, width: 0.0, height: 0.0},Some(String::from("Framerate")),Color::RGBA(100, 100, 100, 0),Color::WHITE,Color::RGB(0, 200, 0),Color::RGB(0, 0, 0),None, TextAlign::Left);
 
+        // the actions of this game on top of the default ones (movement, pause and quit)
+        app.input.bind_default("Impact", InputButton::Key(Keycode::Space));
+        app.input.bind_default("ToggleBrush", InputButton::Key(Keycode::B));
+        app.input.bind_default("ToggleGpuAnimation", InputButton::Key(Keycode::G));
+        app.input.bind_default("Paint", InputButton::Mouse(MouseButton::Left));
+        app.input.bind_default("Erase", InputButton::Mouse(MouseButton::Right));
+        app.input.bind_default("ToggleCaptions", InputButton::Key(Keycode::C));
+        app.input.bind_default("Chat", InputButton::Key(Keycode::T));
+        app.input.bind_default("TeamChat", InputButton::Key(Keycode::Y));
+        app.input.bind_default("PushToTalk", InputButton::Key(Keycode::V));
+        app.input.bind_default("Dash", InputButton::Key(Keycode::Q));
+        app.input.bind_default("Focus", InputButton::Key(Keycode::R));
+        app.input.bind_default("Inventory", InputButton::Key(Keycode::I));
+        app.input.bind_default("Drink", InputButton::Key(Keycode::H));
+        app.input.bind_default("Talk", InputButton::Key(Keycode::E));
+        app.input.bind_default("Options", InputButton::Key(Keycode::F10));
+
+        // what the player starts with, from assets/items
+        let mut inventory = Inventory::new(24);
+        for (item, count) in [("potion", 3), ("stone", 12), ("sword", 1)] {
+            if let Err(e) = inventory.add(&app.items, item, count) {
+                eprintln!("{}", e);
+            }
+        }
+
+        // there are no sounds yet, the impact says what it would sound like
+        app.captions.register("impact", Caption::new("[heavy impact]", 2.0));
+
+        // the guard stands in the middle of the grid
+        let grid = app.world.find("instance grid");
+        if let Some(grid) = grid {
+            let name = app.strings.get("npc.guard").to_string();
+            app.labels.nameplate(grid, &name, 2.0);
+            // the greeting when you talk to him, with his name on the caption
+            app.captions.register("guard_greet", Caption::spoken(&name, "Hm? What do you want?", 2.5));
+        }
+        // a ribbon behind the corner of the grid while it spins, like the tip of a sword
+        if let Some(corner) = grid.and_then(|grid| app.world.get(grid)).and_then(|grid| grid.children().first().copied()) {
+            let settings = TrailSettings { width: 0.4, lifetime: 0.8, start_color: LinearColor::rgb(0.4, 0.7, 1.0), end_color: LinearColor::rgba(0.4, 0.7, 1.0, 0.0), ..TrailSettings::default() };
+            app.trails.spawn(settings, TrailAnchor::Entity { entity: corner, offset: cgmath::Vector3::new(0.0, 1.0, 0.0) });
+        }
+        // a spot under every cube of the grid for the low shadow settings
+        let cubes: Vec<EntityId> = grid.and_then(|grid| app.world.get(grid)).map(|grid| grid.children().to_vec()).unwrap_or_default();
+        for cube in cubes {
+            app.blob_shadows.add(BlobShadow::new(ShadowShape::Blob { radius: 0.8 }, ShadowAnchor::Entity { entity: cube, offset: cgmath::Vector3::new(0.0, 0.0, 0.0) }));
+        }
+        // embers going up from the middle of the grid
+        if let Some(grid) = grid {
+            let settings = EmitterSettings {
+                rate: 80.0,
+                lifetime: (1.5, 3.0),
+                speed: (0.5, 1.5),
+                spread: 0.3,
+                radius: 0.5,
+                gravity: cgmath::Vector3::new(0.0, 0.6, 0.0),
+                drag: 0.5,
+                size: Curve::linear(0.12, 0.02),
+                color: Gradient::two_colors(LinearColor::rgb(4.0, 1.6, 0.4), LinearColor::rgba(1.0, 0.2, 0.0, 0.0)),
+                ..EmitterSettings::default()
+            };
+            app.particles.spawn(&app.device, settings, EmitterAnchor::Entity { entity: grid, offset: cgmath::Vector3::new(0.0, 0.5, 0.0) });
+        }
+        // two doors at the sides of the grid that lead to each other
+        let door = cgmath::Vector2::new(1.0, 1.5);
+        app.add_portal_pair((Point3::new(-18.0, 1.5, 0.0), cgmath::Vector3::unit_x()), (Point3::new(18.0, 1.5, 0.0), -cgmath::Vector3::unit_x()), door);
+        // a long spot along the bottom of each door, they are not entities so the shadows stay where they are
+        for x in [-18.0, 18.0] {
+            let shape = ShadowShape::Capsule { half_length: cgmath::Vector3::new(0.0, 0.0, door.x), radius: 0.4 };
+            app.blob_shadows.add(BlobShadow::new(shape, ShadowAnchor::Point(Point3::new(x, 1.5, 0.0))));
+        }
+        // and a screen behind the camera with the grid seen from above, like a security camera
+        let security_camera = Camera { eye: Point3::new(12.0, 10.0, 12.0), target: Point3::new(0.0, 0.0, 0.0), ..app.camera.camera };
+        app.add_camera_screen(Point3::new(0.0, 5.0, 18.0), -cgmath::Vector3::unit_z(), cgmath::Vector2::new(2.0, 1.25), security_camera);
+        // and a mirror under it, looking at the grid
+        app.add_mirror(Point3::new(0.0, 1.5, 18.0), -cgmath::Vector3::unit_z(), cgmath::Vector2::new(3.0, 1.25));
+
         Self {
             fps: 0,
             fps_text: framerate,
@@ -36,27 +122,127 @@ impl GameLogic {
             start_time: Instant::now(),
             frame_count: 0,
             frame_timer: Duration::new(0, 0),
-            controller: Controller { forward: false, backwards: false, left: false, right: false },
-            speed
+            speed,
+            brush: InstanceBrush::new(app.rng.stream("editor").next_u64()),
+            brush_enabled: false,
+            calibration: Calibration::Off,
+            grid,
+            browser: SessionBrowserMenu::new(),
+            abilities: AbilitySet::new(100.0, 10.0),
+            inventory,
+            inventory_panel: InventoryPanel::new(6),
+            dialogue: DialoguePanel::new(),
+            greeting: None,
+            options: OptionsMenu::new(),
+            sun_editor: CurveEditor::new("sun over the day", (0.0, 24.0), (0.0, 2.0)),
+            inspector: Inspector::new(),
         }
     }
 
-    // this is called every frame
-    pub fn update(&mut self, _font: &Font, mut app_state: &mut AppState, mut event_pump: &mut sdl2::EventPump, app: &mut App) {
+    // this is called every frame, after app.input was updated with the events of the frame
+    pub fn update(&mut self, font: &Font, app_state: &mut AppState, app: &mut App) {
+        profile_scope!("gameplay");
         let delta_time = self.delta_time();
         self.display_framerate(delta_time);
+        self.fps_text.draw(&mut app.ui, &mut app.text, font);
+        // under the rest of the ui, they belong to the scene
+        app.labels.draw(&mut app.text, font, &app.camera.camera, &app.world, app.config.width, app.config.height);
+        app.captions.draw(&mut app.ui, &mut app.text, font, app.config.width, app.config.height);
+        app.chat.draw(&mut app.ui, &mut app.text, font, app.config.height);
+        app.ui_screens.draw(&mut app.ui, &mut app.text, font, &app.cvars);
+        self.inventory_panel.draw(&mut app.ui, &mut app.text, font, &self.inventory, &app.items, (app.config.width, app.config.height));
+        self.dialogue.draw(&mut app.ui, &mut app.text, font, &app.dialogues, &app.strings, (app.config.width, app.config.height));
+        self.options.draw(&mut app.ui, &mut app.text, font, app.config.width, app.config.height);
+        self.sun_editor.draw(&mut app.ui, &mut app.text, font, &app.sky.sun_curve, app.config.height);
+        self.inspector.draw(&mut app.ui, &mut app.text, font, &app.world, &app.lights, app.config.width);
+        self.draw_cast_bar(app);
 
+        if app.input.quit_requested() {
+            app_state.is_running = false;
+            return;
+        }
+        // alt enter goes between the window and the borderless fullscreen from anywhere, the enter goes to nothing else
+        let alt = app.input.is_pressed(InputButton::Key(Keycode::LAlt)) || app.input.is_pressed(InputButton::Key(Keycode::RAlt));
+        if alt && app.input.just_pressed(InputButton::Key(Keycode::Return)) {
+            let settings = EngineSettings { fullscreen: app.fullscreen() == FullscreenMode::Windowed, ..app.settings.clone() };
+            app.apply_settings(settings);
+            return;
+        }
+        if app_state.state == GameState::Calibrating {
+            self.calibration_input(app_state, app);
+            return;
+        }
+        if app_state.state == GameState::Browsing {
+            self.browser_input(app_state, app);
+            self.browser.draw(&mut app.ui, &mut app.text, font, app.config.width, app.config.height);
+            return;
+        }
+        #[cfg(feature = "voice")]
+        app.set_voice_transmitting(!app.chat.is_typing() && app.input.action_pressed("PushToTalk"));
+        // the chat has the keyboard while it's open, escape closes it instead of quitting
+        if app.chat.is_typing() {
+            if let Some(message) = app.chat.handle_input(&app.input) {
+                Self::send_chat(app, message);
+            }
+            return;
+        }
+        // the menus of the ui scripts have the input while one is open
+        for action in app.ui_screens.handle_input(&app.input, &mut app.accessibility) {
+            Self::script_action(action, app_state, app);
+        }
+        if app.ui_screens.has_input() {
+            return;
+        }
+        // the options menu has the input while it's open
+        match self.options.handle_input(&app.input) {
+            OptionsAction::Apply(settings) => app.apply_settings(settings),
+            OptionsAction::None | OptionsAction::Close => {}
+        }
+        if self.options.is_open() {
+            return;
+        }
+        // debug keys, they work with the mouse over the editors too
+        if app.input.just_pressed(InputButton::Key(Keycode::F6)) {
+            self.sun_editor.toggle();
+        }
+        if app.input.just_pressed(InputButton::Key(Keycode::F1)) {
+            self.inspector.toggle();
+        }
+        if self.sun_editor.handle_input(&app.input, &mut app.sky.sun_curve, app.config.height) {
+            return;
+        }
+        if self.inspector.handle_input(&app.input, &mut app.world, &mut app.lights, font, app.config.width) {
+            return;
+        }
+        if self.inventory_panel.handle_input(&app.input, &mut self.inventory, &app.items, app.config.width, app.config.height) {
+            return;
+        }
+        let talking = self.dialogue.handle_input(&app.input, &app.dialogues, font, app.config.width, app.config.height);
+        for event in self.dialogue.take_events() {
+            Self::dialogue_event(app, event);
+        }
+        if talking {
+            return;
+        }
+
+        // while paused only the input runs, so the pause key (and the ui) still work
+        Self::input_handler(self, app_state, app);
+        if app_state.is_paused() {
+            return;
+        }
+
+        let start = app.camera.camera.eye;
         let forward = app.camera.camera.target - app.camera.camera.eye;
         let forward_norm = forward.normalize();
         let forward_mag = forward.magnitude();
 
         // Prevents glitching when the camera gets too close to the
         // center of the scene.
-        if self.controller.forward && forward_mag > self.speed {
-            app.camera.camera.eye += forward_norm * self.speed * delta_time.as_secs_f32();
-        }
-        if self.controller.backwards {
-            app.camera.camera.eye -= forward_norm * self.speed * delta_time.as_secs_f32();
+        // a key goes at full speed, the stick at how far it is pushed
+        // and the wheel zooms a step per notch
+        let advance = app.input.axis("MoveBackward", "MoveForward") * self.speed * delta_time.as_secs_f32() + app.input.wheel().1 * WHEEL_ZOOM;
+        if (advance > 0.0 && forward_mag - advance > self.speed) || advance < 0.0 {
+            app.camera.camera.eye += forward_norm * advance;
         }
 
         let right = forward_norm.cross(app.camera.camera.up);
@@ -65,57 +251,391 @@ impl GameLogic {
         let forward = app.camera.camera.target - app.camera.camera.eye;
         let forward_mag = forward.magnitude();
 
-        if self.controller.right {
-            // Rescale the distance between the target and the eye so 
-            // that it doesn't change. The eye, therefore, still 
-            // lies on the circle made by the target and eye.
-            app.camera.camera.eye = app.camera.camera.target - (forward + right * self.speed * delta_time.as_secs_f32()).normalize() * forward_mag;
+        // Rescale the distance between the target and the eye so 
+        // that it doesn't change. The eye, therefore, still 
+        // lies on the circle made by the target and eye.
+        // dragging with the middle mouse turns it too, with the sensitivity of the settings
+        let orbit = if app.input.is_pressed(InputButton::Mouse(MouseButton::Middle)) { app.input.look_delta().0 * ORBIT_PER_PIXEL } else { 0.0 };
+        let sideways = app.input.axis("MoveLeft", "MoveRight") * self.speed * delta_time.as_secs_f32() + orbit;
+        if sideways != 0.0 {
+            app.camera.camera.eye = app.camera.camera.target - (forward + right * sideways).normalize() * forward_mag;
         }
-        if self.controller.left {
-            app.camera.camera.eye = app.camera.camera.target - (forward - right * self.speed * delta_time.as_secs_f32()).normalize() * forward_mag;
+        // the eye slides along the colliders instead of going through them
+        let movement = app.camera.camera.eye - start;
+        app.camera.camera.eye = app.collision.move_and_slide(&Collider::sphere(CAMERA_RADIUS), start, movement, None);
+        // and the grid spinning into it (or a dab of the brush over it) pushes it out
+        app.camera.camera.eye = app.collision.push_out(&Collider::sphere(CAMERA_RADIUS), app.camera.camera.eye, None);
+        // moving stops a cast, focus is cast standing still
+        if movement.magnitude2() > 0.0 && self.abilities.is_casting() {
+            self.abilities.interrupt();
         }
 
-        Self::event_handler(self, &mut app_state, &mut event_pump, app);
+        // the brush dabs every frame while the button is held, the density keeps it from piling up
+        if self.brush_enabled {
+            let painting = app.input.action_pressed("Paint");
+            if painting || app.input.action_pressed("Erase") {
+                self.brush.mode = if painting { BrushMode::Paint } else { BrushMode::Erase };
+                let (x, y) = app.input.mouse_position();
+                let ray = app.camera.camera.screen_to_ray(x as f32, y as f32, app.config.width as f32, app.config.height as f32);
+                if let Some(ray) = ray {
+                    app.apply_brush(&mut self.brush, &GroundSurface { height: 0.0 }, &ray);
+                }
+            }
+        }
     }
 
-    fn event_handler(&mut self, app_state: &mut AppState, event_pump: &mut sdl2::EventPump, app: &mut App) {
-        for event in event_pump.poll_iter() {
-            match event {
-                Event::KeyDown { keycode: Some(Keycode::Space), .. } => {
-                    
-                }
-                Event::KeyDown { keycode: Some(Keycode::W), .. } => {
-                    self.controller.forward = true
-                }
-                Event::KeyUp { keycode: Some(Keycode::W), .. } => {
-                    self.controller.forward = false
-                }
-                Event::KeyDown { keycode: Some(Keycode::A), .. } => {
-                    self.controller.left = true
-                }
-                Event::KeyUp { keycode: Some(Keycode::A), .. } => {
-                    self.controller.left = false
-                }
-                Event::KeyDown { keycode: Some(Keycode::S), .. } => {
-                    self.controller.backwards = true
+    // there is no connection yet, the message comes back the way the server would relay it
+    fn send_chat(app: &mut App, message: ChatMessage) {
+        let name = app.cvars.text("name").unwrap_or("player").to_string();
+        if let Some(relayed) = message.relayed(0, &name) {
+            app.chat.receive(relayed);
+        }
+    }
+
+    // the prints that help while working on the gameplay, quiet unless debug_log is on
+    fn debug_log(app: &App, message: impl FnOnce() -> String) {
+        if app.cvars.bool("debug_log").unwrap_or(false) {
+            println!("{}", message());
+        }
+    }
+
+    // what the buttons of the ui scripts ask for, opening and closing the menus is done by the screens
+    fn script_action(action: ScriptAction, app_state: &mut AppState, app: &mut App) {
+        match action {
+            ScriptAction::Cvar(line) => match app.cvars.execute(&line) {
+                Ok(answer) => println!("{}", answer),
+                Err(e) => eprintln!("{}", e),
+            },
+            ScriptAction::Say(text) => Self::send_chat(app, ChatMessage::new(ChatChannel::All, 0, "", &text)),
+            ScriptAction::Quit => app_state.is_running = false,
+            ScriptAction::Close | ScriptAction::Open(_) => {}
+        }
+    }
+
+    // this is called at the fixed rate of app.timestep (0 or more times per frame), step is always the same
+    // what moves the world goes here so it behaves the same at any frame rate, the renderer interpolates it
+    pub fn fixed_update(&mut self, app: &mut App, step: f32) {
+        if let Some(grid) = self.grid.and_then(|grid| app.world.get_mut(grid)) {
+            let spin = cgmath::Quaternion::from_angle_y(cgmath::Deg(GRID_SPIN_SPEED * step));
+            grid.transform.rotation = spin * grid.transform.rotation;
+        }
+        for event in self.abilities.update(&app.abilities, step) {
+            self.apply_ability(app, event);
+        }
+    }
+
+    // what the abilities of the player do, an ai would apply the same events to its own entity
+    fn apply_ability(&mut self, app: &mut App, event: AbilityEvent) {
+        let (ability, effect, target) = match event {
+            AbilityEvent::Effect { ability, effect, target } => (ability, effect, target),
+            AbilityEvent::CastStarted { ability, .. } => return Self::debug_log(app, || format!("casting {}", ability)),
+            AbilityEvent::Interrupted { ability } => return Self::debug_log(app, || format!("{} was interrupted", ability)),
+        };
+        match effect {
+            Effect::Impulse { strength } => {
+                // towards the target, never through it
+                let forward = app.camera.camera.target - app.camera.camera.eye;
+                let distance = forward.magnitude();
+                let step = strength.min(distance - self.speed).max(0.0);
+                let from = app.camera.camera.eye;
+                app.camera.camera.eye += forward.normalize() * step;
+                // a streak under the eye where it went, it only has the two ends and starts fading right away
+                let below = cgmath::Vector3::new(0.0, -0.5, 0.0);
+                let settings = TrailSettings { width: 0.2, lifetime: 0.4, start_color: LinearColor::rgb(0.8, 0.9, 1.0), end_color: LinearColor::rgba(0.8, 0.9, 1.0, 0.0), ..TrailSettings::default() };
+                let streak = app.trails.spawn(settings, TrailAnchor::Manual);
+                app.trails.move_to(streak, from + below);
+                app.trails.move_to(streak, app.camera.camera.eye + below);
+                app.trails.stop(streak);
+            }
+            Effect::Sound(path) => {
+                app.play_sound(&path);
+            }
+            Effect::Damage { amount, .. } => {
+                // where it lands, in front of the camera when it has no target
+                let position = match target {
+                    AbilityTarget::Point(point) => Some(Point3::from_vec(point)),
+                    AbilityTarget::Entity(entity) => app.world.get(entity).map(|entity| Point3::from_vec(entity.world_position())),
+                    AbilityTarget::None => None,
+                };
+                let position = position.unwrap_or(app.camera.camera.target);
+                app.labels.damage_number(position, amount);
+                // sparks once, the emitter goes away with the last of them
+                let settings = EmitterSettings { rate: 0.0, capacity: 64, lifetime: (0.3, 0.6), speed: (3.0, 6.0), spread: std::f32::consts::PI, size: Curve::linear(0.08, 0.0), color: Gradient::two_colors(LinearColor::rgb(3.0, 2.4, 1.2), LinearColor::rgba(1.0, 0.4, 0.1, 0.0)), ..EmitterSettings::default() };
+                let sparks = app.particles.spawn(&app.device, settings, EmitterAnchor::Point(position));
+                app.particles.burst(sparks, 48);
+                app.particles.stop(sparks);
+                app.clips.trigger("damage");
+            }
+            Effect::Heal { amount } => Self::debug_log(app, || format!("{} heals {}", ability, amount)),
+            Effect::Custom(name) => Self::debug_log(app, || format!("{} does {}", ability, name)),
+        }
+    }
+
+    // what the conversations raise, the game would start quests and open doors here
+    // over the captions while an ability with a cast time is being cast
+    fn draw_cast_bar(&self, app: &mut App) {
+        let Some((_, progress)) = self.abilities.cast_progress(&app.abilities) else { return };
+        let settings = UiSettings::current();
+        let (width, height) = (settings.px(200.0), settings.px(8.0));
+        let x = (app.config.width as i32 - width) / 2;
+        let y = app.config.height as i32 - settings.px(160.0);
+        app.ui.draw_rect(Rect::new(x, y, width.max(1) as u32, height.max(1) as u32), Color::RGBA(0, 0, 0, 160));
+        app.ui.draw_rect(Rect::new(x, y, (width as f32 * progress).max(1.0) as u32, height.max(1) as u32), Color::RGB(120, 180, 255));
+    }
+
+    // the abilities aim at the entity under the mouse, or at the ground there
+    fn ability_target(app: &App) -> AbilityTarget {
+        let (x, y) = app.input.mouse_position();
+        if let Some(PickHit { target: Picked::Entity(entity), .. }) = app.pick(x, y, PickVolume::Sphere) {
+            return AbilityTarget::Entity(entity);
+        }
+        let ground = placement::cursor_ground_point(&app.camera.camera, x as f32, y as f32, app.config.width as f32, app.config.height as f32, 0.0);
+        ground.map_or(AbilityTarget::None, |point| AbilityTarget::Point(point.to_vec()))
+    }
+
+    fn dialogue_event(app: &mut App, event: DialogueEvent) {
+        match event {
+            DialogueEvent::Event { dialogue, name } => app.chat.notice(&format!("[{}] {}", dialogue, name)),
+            DialogueEvent::Chose { dialogue, node, choice } => Self::debug_log(app, || format!("{}: chose {} at {}", dialogue, choice + 1, node)),
+            DialogueEvent::Started { .. } | DialogueEvent::Ended { .. } => {}
+        }
+    }
+
+    fn input_handler(&mut self, app_state: &mut AppState, app: &mut App) {
+        let input = &app.input;
+        if input.action_just_pressed("Quit") {
+            app_state.is_running = false;
+        }
+        if input.action_just_pressed("Pause") {
+            app_state.toggle_pause();
+            app.accessibility.announce(if app_state.is_paused() { "paused" } else { "playing" });
+        }
+        if app_state.is_paused() {
+            return;
+        }
+
+        if input.action_just_pressed("Impact") {
+            // a fake hit so we can see the impact feedback
+            app.camera.add_trauma(0.5);
+            app.post_process.flash(LinearColor::WHITE, 0.6, 0.15);
+            app.post_process.vignette_pulse(LinearColor::rgb(0.6, 0.0, 0.0), 0.8, 0.6);
+            app.post_process.chromatic_burst(0.03, 0.3);
+            app.play_sound("sounds/impact.wav");
+            self.abilities.interrupt();
+        }
+        for (action, ability) in [("Dash", "dash"), ("Focus", "focus")] {
+            if app.input.action_just_pressed(action) {
+                let target = Self::ability_target(app);
+                if let Err(e) = self.abilities.cast(&app.abilities, ability, target) {
+                    Self::debug_log(app, || format!("{}: {}", ability, e));
                 }
-                Event::KeyUp { keycode: Some(Keycode::S), .. } => {
-                    self.controller.backwards = false
+            }
+        }
+        // again, the sound needed all of the app
+        let input = &app.input;
+        if input.action_just_pressed("Inventory") {
+            self.inventory_panel.toggle();
+            return;
+        }
+        // a potion of the inventory, how much it heals is a property of the item
+        if input.action_just_pressed("Drink") {
+            if self.inventory.remove("potion", 1) == 0 {
+                app.chat.notice("there are no potions left");
+            } else {
+                let heals = app.items.get("potion").and_then(|item| item.number("heals")).unwrap_or(0.0);
+                app.chat.notice(&format!("the potion heals {} ({} left)", heals, self.inventory.count("potion")));
+            }
+            return;
+        }
+        if input.action_just_pressed("Options") {
+            self.options.open(&app.settings, &app.input);
+            return;
+        }
+        if input.action_just_pressed("Talk") {
+            match self.dialogue.start(&app.dialogues, "guard") {
+                Ok(()) => {
+                    if let Some(greeting) = self.greeting.take() {
+                        app.audio.stop(greeting);
+                    }
+                    self.greeting = app.play_sound("sounds/guard_greet.wav");
                 }
-                Event::KeyDown { keycode: Some(Keycode::D), .. } => {
-                    self.controller.right = true
+                Err(e) => eprintln!("{}", e),
+            }
+            return;
+        }
+        if input.action_just_pressed("ToggleCaptions") {
+            let settings = UiSettings::current();
+            app.set_ui_settings(UiSettings { captions: !settings.captions, ..settings });
+            return;
+        }
+        if input.action_just_pressed("Chat") || input.action_just_pressed("TeamChat") {
+            let channel = if input.action_just_pressed("TeamChat") { ChatChannel::Team } else { ChatChannel::All };
+            app.chat.open(channel);
+            return;
+        }
+        if input.action_just_pressed("ToggleBrush") {
+            self.brush_enabled = !self.brush_enabled;
+            // the lines of the ground while painting
+            if self.brush_enabled {
+                let grid = GroundGrid::new(&app.device, app.post_process.format(), &app.camera.camera);
+                if let Err(e) = app.add_render_pass(Box::new(grid)) {
+                    eprintln!("{}", e);
                 }
-                Event::KeyUp { keycode: Some(Keycode::D), .. } => {
-                    self.controller.right = false
+            } else {
+                app.passes.remove(GroundGrid::NAME);
+            }
+            return;
+        }
+        if !self.brush_enabled && input.just_pressed(InputButton::Mouse(MouseButton::Left)) {
+            // where the click lands on the ground, this is what click to move or placement previews use
+            let (x, y) = input.mouse_position();
+            // the spheres, the pyramids of the grid are small and spinning so the ball around them is an easier target
+            let hit = app.pick(x, y, PickVolume::Sphere);
+            match hit {
+                Some(PickHit { target: Picked::Entity(id), distance, .. }) => {
+                    Self::debug_log(app, || format!("clicked {} at {:.2}", app.world.get(id).map_or("?", |entity| entity.name.as_str()), distance));
                 }
-                Event::KeyDown { keycode: Some(Keycode::Escape), .. }  => {
-                    app_state.is_running = false;
-                }, Event::Quit { .. } => {
-                    app_state.is_running = false;
-                } 
-                _ => {}
+                Some(PickHit { target: Picked::StaticInstance(index), distance, .. }) => Self::debug_log(app, || format!("clicked the static instance {} at {:.2}", index, distance)),
+                None => {}
+            }
+            if self.inspector.is_open() {
+                // the closest of the entity and the point lights, they have no model so they are clicked as a small ball
+                let entity = match hit {
+                    Some(PickHit { target: Picked::Entity(id), distance, .. }) => Some((Selection::Entity(id), distance)),
+                    _ => None,
+                };
+                let ray = app.camera.camera.screen_to_ray(x as f32, y as f32, app.config.width as f32, app.config.height as f32);
+                let light = ray.and_then(|ray| Inspector::light_at(&app.lights, &ray)).map(|(index, distance)| (Selection::Light(index), distance));
+                let closest = match (entity, light) {
+                    (Some(entity), Some(light)) => Some(if light.1 < entity.1 { light } else { entity }),
+                    (entity, light) => entity.or(light),
+                };
+                self.inspector.select(closest.map(|(selection, _)| selection));
             }
+            let hit = placement::cursor_ground_point(&app.camera.camera, x as f32, y as f32, app.config.width as f32, app.config.height as f32, 0.0);
+            if let Some(point) = hit {
+                Self::debug_log(app, || format!("ground hit at ({:.2}, {:.2}, {:.2})", point.x, point.y, point.z));
+            }
+        }
+        if input.action_just_pressed("ToggleGpuAnimation") {
+            // stops and starts the spin and the bob of the instances, the picking follows what is drawn either way
+            app.instance_animator.enabled = !app.instance_animator.enabled;
+        }
+
+        // debug keys, these are not actions so they can't be rebound by mistake
+        let fov_step = if input.just_pressed(InputButton::Key(Keycode::PageUp)) {
+            5.0
+        } else if input.just_pressed(InputButton::Key(Keycode::PageDown)) {
+            -5.0
+        } else {
+            0.0
+        };
+        if fov_step != 0.0 {
+            // through the cvar so it is saved, its range keeps it valid
+            let fovy = app.camera.camera.fovy + fov_step;
+            let _ = app.cvars.set("r_fov", CvarValue::Float(fovy));
+        }
+        if input.just_pressed(InputButton::Key(Keycode::F7)) {
+            self.set_calibration(Calibration::Brightness, app);
+            app_state.push_state(GameState::Calibrating);
+        }
+        if app.input.just_pressed(InputButton::Key(Keycode::F9)) {
+            self.browser.open();
+            app_state.push_state(GameState::Browsing);
+            return;
+        }
+        if app.input.just_pressed(InputButton::Key(Keycode::F11)) {
+            // spans every monitor with a borderless window, for exhibitions (again to go back)
+            let result = if app.is_spanning() { app.stop_spanning() } else { app.span_monitors(&[]) };
+            if let Err(e) = result {
+                eprintln!("{}", e);
+            }
+        }
+        if app.input.just_pressed(InputButton::Key(Keycode::F8)) {
+            // goes through the present modes: vsync, mailbox, immediate and back to the auto one without vsync
+            let next = match app.display.present_mode {
+                PresentMode::Fifo | PresentMode::AutoVsync => PresentMode::Mailbox,
+                PresentMode::Mailbox => PresentMode::Immediate,
+                PresentMode::Immediate => PresentMode::AutoNoVsync,
+                _ => PresentMode::Fifo,
+            };
+            let used = app.set_present_mode(next);
+            println!("present mode {:?} (asked for {:?})", used, next);
+        }
+        let shift = app.input.is_pressed(InputButton::Key(Keycode::LShift)) || app.input.is_pressed(InputButton::Key(Keycode::RShift));
+        if app.input.just_pressed(InputButton::Key(Keycode::F3)) && !shift {
+            // the overlay with the frame times, the camera and the gpu
+            app.overlay.toggle();
+        }
+        if app.input.just_pressed(InputButton::Key(Keycode::F3)) && shift {
+            // the debug views, one after the other and back to the game
+            let next = app.next_debug_view();
+            app.set_debug_view(next);
+            println!("debug view: {}", next.name());
+        }
+        if app.input.just_pressed(InputButton::Key(Keycode::F5)) {
+            // the wireframe on and off, straight from the game without going through the other views
+            let view = if app.debug_view() == DebugView::Wireframe { DebugView::Final } else { DebugView::Wireframe };
+            if app.set_debug_view(view) {
+                println!("debug view: {}", view.name());
+            }
+        }
+        if app.input.just_pressed(InputButton::Key(Keycode::F4)) {
+            // the passes of the next frame go to the console and to a graphviz file
+            app.frame_graph.request_dump("frame_graph.dot");
         }
+        if app.input.just_pressed(InputButton::Key(Keycode::F2)) {
+            // the profiler tree of the last frame, in the debug overlay
+            app.overlay.toggle_profiler();
+        }
+    }
+
+    // up and down move the value of the step, enter goes to the next one (the peak only exists in hdr)
+    // H switches hdr for the next start, escape leaves, the values are saved when the screen closes
+    fn calibration_input(&mut self, app_state: &mut AppState, app: &mut App) {
+        let key = |keycode: Keycode| app.input.just_pressed(InputButton::Key(keycode));
+        let steps = key(Keycode::Up) as i32 as f32 - key(Keycode::Down) as i32 as f32;
+        let toggle_hdr = key(Keycode::H);
+        let next = key(Keycode::Return);
+        let leave = key(Keycode::Escape);
+
+        if steps != 0.0 {
+            app.display.adjust(self.calibration, app.output_mode, steps);
+            app.post_process.set_output(&app.display, app.output_mode);
+        }
+        if toggle_hdr {
+            app.display.hdr = !app.display.hdr;
+            println!("hdr {} on the next start", if app.display.hdr { "on" } else { "off" });
+        }
+        if next && self.calibration == Calibration::Brightness && app.output_mode == OutputMode::Hdr {
+            self.set_calibration(Calibration::Peak, app);
+        } else if next || leave {
+            self.set_calibration(Calibration::Off, app);
+            app.set_display_settings(app.display);
+            app_state.pop_state();
+        }
+    }
+
+    fn browser_input(&mut self, app_state: &mut AppState, app: &mut App) {
+        self.browser.update();
+        match self.browser.handle_input(&app.input) {
+            BrowserAction::None => {}
+            BrowserAction::Join(address) => {
+                // the connection comes with the game sockets, until then the address is all we have
+                println!("joining {}", address);
+                self.browser.close();
+                app_state.pop_state();
+            }
+            BrowserAction::Leave => {
+                self.browser.close();
+                app_state.pop_state();
+            }
+        }
+    }
+
+    fn set_calibration(&mut self, calibration: Calibration, app: &mut App) {
+        self.calibration = calibration;
+        app.post_process.set_calibration(calibration);
     }
 
     fn delta_time(&mut self) -> Duration {
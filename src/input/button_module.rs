@@ -3,6 +3,10 @@ use sdl2::pixels::Color;
 use sdl2::rect::Rect;
 
 use crate::game_object::GameObject;
+use crate::ui::scale::UiSettings;
+use crate::input::input_state::{InputButton, InputState};
+use crate::ui::text::TextRenderer;
+use crate::ui::ui_renderer::UiRenderer;
 
 #[derive(Clone)]
 pub enum TextAlign {
@@ -25,6 +29,8 @@ pub struct Button {
     pub lclicked: bool,
     pub toggle: Option<bool>,
     pub text_align: TextAlign,
+    pub accessible_label: Option<String>, // what a screen reader says when the text is not enough (an icon, "X")
+    pub focused: bool, // set by the focus navigator, it is drawn with an outline
 }
 
 impl Button {
@@ -43,9 +49,21 @@ impl Button {
             lclicked: false,
             toggle,
             text_align,
+            accessible_label: None,
+            focused: false,
         }
     }
 
+    pub fn with_accessible_label(mut self, label: &str) -> Self {
+        self.accessible_label = Some(label.to_string());
+        self
+    }
+
+    // the name of the button for the screen readers
+    pub fn label(&self) -> &str {
+        self.accessible_label.as_deref().or(self.text.as_deref()).unwrap_or("unnamed")
+    }
+
     pub fn render(&self, canvas: &mut Canvas<Window>, texture_creator: &TextureCreator<WindowContext>, font: &Font) {
         if self.game_object.active == true {
             match self.toggle {
@@ -60,7 +78,9 @@ impl Button {
                     canvas.set_draw_color(self.color); // it must be a Color::RGB() or other
                 },
             }
-            canvas.fill_rect(Rect::new(self.game_object.x as i32, self.game_object.y as i32, self.game_object.width as u32, self.game_object.height as u32)).unwrap();
+            // the layout is in design pixels, the ui scale takes it to the screen
+            let rect = UiSettings::current().rect(&self.game_object);
+            canvas.fill_rect(rect).unwrap();
 
             // Render the button text
             match &self.text {
@@ -75,12 +95,12 @@ impl Button {
 
                                     match self.text_align {
                                         TextAlign::Left => {
-                                            text_x = self.game_object.x as i32;
-                                            text_y = self.game_object.y as i32;
+                                            text_x = rect.x();
+                                            text_y = rect.y();
                                         },
                                         TextAlign::Center => {
-                                            text_x = self.game_object.x as i32 + (self.game_object.width as i32 - text_width as i32) / 2;
-                                            text_y = self.game_object.y as i32 + (self.game_object.height as i32 - text_height as i32) / 2;
+                                            text_x = rect.x() + (rect.width() as i32 - text_width as i32) / 2;
+                                            text_y = rect.y() + (rect.height() as i32 - text_height as i32) / 2;
                                         },
                                     }
                         
@@ -102,25 +122,67 @@ impl Button {
         }
     }
 
+    // the same as render but through the text renderer, this is the one that reaches the screen
+    pub fn draw(&self, ui: &mut UiRenderer, text: &mut TextRenderer, font: &Font) {
+        if !self.game_object.active {
+            return;
+        }
+        let background = match self.toggle {
+            Some(true) => self.clicked_color,
+            _ => self.color,
+        };
+        let settings = UiSettings::current();
+        let rect = settings.rect(&self.game_object);
+        ui.draw_rect(rect, background);
+        if self.focused {
+            ui.draw_outline(rect, settings.px(2.0).max(1) as u32, self.text_color);
+        }
+
+        if let Some(label) = &self.text {
+            let (text_width, text_height) = font.size_of(label).unwrap_or((0, 0));
+            let (text_x, text_y) = match self.text_align {
+                TextAlign::Left => (rect.x(), rect.y()),
+                TextAlign::Center => (rect.x() + (rect.width() as i32 - text_width as i32) / 2, rect.y() + (rect.height() as i32 - text_height as i32) / 2),
+            };
+            text.draw_text(font, label, text_x, text_y, self.text_color);
+        }
+    }
+
     pub fn is_hover(&mut self, event: &sdl2::event::Event) {
         if self.game_object.active {
-            match event { 
-                sdl2::event::Event::MouseMotion {x, y, .. } => {
-                    if (x > &(self.game_object.x as i32) && x < &(self.game_object.x as i32 + (self.game_object.width as i32))) && (y >= &(self.game_object.y as i32) && y <= &(self.game_object.y as i32 + (self.game_object.height as i32))) {
-                        self.color = self.hover_color;
-                        self.hover = true;
-                    } else {
-                        self.color = self.base_color;
-                        self.hover = false;
-                    }
-                },
-                _ => {} // in every other case we will do nothing
-            } 
+            if let sdl2::event::Event::MouseMotion {x, y, .. } = event {
+                self.hover_at(*x, *y);
+            }
+        } else {
+            self.hover = false;
+        }
+    }
+
+    fn hover_at(&mut self, x: i32, y: i32) {
+        if UiSettings::current().contains(&self.game_object, x, y) {
+            self.color = self.hover_color;
+            self.hover = true;
         } else {
+            self.color = self.base_color;
             self.hover = false;
         }
     }
 
+    // the hover and the clicks from the input of this frame instead of the events, true when it was clicked
+    pub fn update(&mut self, input: &InputState) -> bool {
+        self.clicked = false;
+        self.lclicked = false;
+        if !self.game_object.active {
+            self.hover = false;
+            return false;
+        }
+        let (x, y) = input.mouse_position();
+        self.hover_at(x, y);
+        self.clicked = self.hover && input.just_pressed(InputButton::Mouse(MouseButton::Left));
+        self.lclicked = self.hover && input.just_pressed(InputButton::Mouse(MouseButton::Right));
+        self.clicked
+    }
+
     // this function will only return true or false based on if its pressed or not
     pub fn is_clicked(&mut self, event: &sdl2::event::Event) -> bool {
         self.is_hover(event);
@@ -0,0 +1,401 @@
+// the keyboard, the mouse and the gamepads of this frame, read once from the event pump so gameplay asks questions
+// instead of matching events: is it held, was it pressed this frame, how much did the mouse move
+// actions give names to buttons ("MoveForward" -> W) so the controls can be changed without touching the game code
+// a half of a stick or a trigger is a button too, held past the middle, and its value goes from 0 to 1 so the same
+// action moves at full speed with a key and slower with the stick a bit pushed (see action_value and axis)
+// the pads are opened when they are plugged in (and the ones already there at the start), all of them drive the
+// same buttons
+
+use std::collections::{HashMap, HashSet};
+
+use sdl2::controller::{Axis, Button as PadButton, GameController};
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use sdl2::{EventPump, GameControllerSubsystem};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum InputButton {
+    Key(Keycode),
+    Mouse(MouseButton),
+    Pad(PadButton),
+    PadAxis(Axis, AxisDirection),
+}
+
+impl InputButton {
+    // for the settings file: "key:W", "mouse:left", "pad:a", "axis:lefty-"
+    pub fn name(&self) -> String {
+        match self {
+            InputButton::Key(keycode) => format!("key:{}", keycode.name()),
+            InputButton::Mouse(button) => format!("mouse:{}", mouse_button_name(*button)),
+            InputButton::Pad(button) => format!("pad:{}", button.string()),
+            InputButton::PadAxis(axis, AxisDirection::Negative) => format!("axis:{}-", axis.string()),
+            InputButton::PadAxis(axis, AxisDirection::Positive) => format!("axis:{}+", axis.string()),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<InputButton> {
+        let (kind, button) = name.split_once(':')?;
+        match kind {
+            "key" => Keycode::from_name(button).map(InputButton::Key),
+            "mouse" => MOUSE_BUTTONS.iter().copied().find(|mouse| mouse_button_name(*mouse) == button).map(InputButton::Mouse),
+            "pad" => PadButton::from_string(button).map(InputButton::Pad),
+            "axis" => {
+                let direction = if button.ends_with('-') { AxisDirection::Negative } else { AxisDirection::Positive };
+                Axis::from_string(button.trim_end_matches(['-', '+'])).map(|axis| InputButton::PadAxis(axis, direction))
+            }
+            _ => None,
+        }
+    }
+}
+
+const MOUSE_BUTTONS: [MouseButton; 5] = [MouseButton::Left, MouseButton::Middle, MouseButton::Right, MouseButton::X1, MouseButton::X2];
+
+fn mouse_button_name(button: MouseButton) -> &'static str {
+    match button {
+        MouseButton::Left => "left",
+        MouseButton::Middle => "middle",
+        MouseButton::Right => "right",
+        MouseButton::X1 => "x1",
+        MouseButton::X2 => "x2",
+        MouseButton::Unknown => "unknown",
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AxisDirection {
+    Negative, // left on the x of the sticks, up on their y
+    Positive,
+}
+
+const PAD_AXES: [Axis; 6] = [Axis::LeftX, Axis::LeftY, Axis::RightX, Axis::RightY, Axis::TriggerLeft, Axis::TriggerRight];
+
+// a half axis is held past this (after the deadzone), for the actions that are only on or off
+const AXIS_PRESS: f32 = 0.5;
+
+pub struct InputState {
+    held: HashSet<InputButton>,
+    pressed: HashSet<InputButton>,  // went down this frame
+    repeated: HashSet<InputButton>, // went down or repeated this frame, for editing text
+    released: HashSet<InputButton>, // went up this frame
+    typed: String,                  // the text typed this frame, with the layout and the input method of the system
+    mouse_position: (i32, i32),
+    mouse_delta: (i32, i32),
+    wheel: (f32, f32),
+    quit_requested: bool, // the window was closed
+    actions: HashMap<String, Vec<InputButton>>,
+    controllers: Option<GameControllerSubsystem>, // none until enable_controllers
+    pads: HashMap<u32, GameController>,          // by the instance id sdl gives them
+    pad_axes: HashMap<Axis, f32>,                // -1 to 1, before the deadzone
+    pub deadzone: f32,                           // of the sticks (radial) and the triggers, 0 to 1
+    pub sensitivity: f32,                        // of look_delta, from the settings
+}
+
+impl InputState {
+    pub fn new() -> Self {
+        Self {
+            held: HashSet::new(),
+            pressed: HashSet::new(),
+            repeated: HashSet::new(),
+            released: HashSet::new(),
+            typed: String::new(),
+            mouse_position: (0, 0),
+            mouse_delta: (0, 0),
+            wheel: (0.0, 0.0),
+            quit_requested: false,
+            actions: HashMap::new(),
+            controllers: None,
+            pads: HashMap::new(),
+            pad_axes: HashMap::new(),
+            deadzone: 0.2,
+            sensitivity: 1.0,
+        }
+    }
+
+    // the pads only work after this, sdl sends an added event for the ones already plugged in
+    pub fn enable_controllers(&mut self, controllers: GameControllerSubsystem) {
+        self.controllers = Some(controllers);
+    }
+
+    // the controls of the demo, the game can bind and rebind over them
+    pub fn with_default_actions() -> Self {
+        let mut input = Self::new();
+        input.bind("MoveForward", InputButton::Key(Keycode::W));
+        input.bind("MoveBackward", InputButton::Key(Keycode::S));
+        input.bind("MoveLeft", InputButton::Key(Keycode::A));
+        input.bind("MoveRight", InputButton::Key(Keycode::D));
+        input.bind("Pause", InputButton::Key(Keycode::P));
+        input.bind("Quit", InputButton::Key(Keycode::Escape));
+        // walking the menus without the mouse (see ui/accessibility.rs)
+        input.bind("UiNext", InputButton::Key(Keycode::Down));
+        input.bind("UiNext", InputButton::Key(Keycode::Tab));
+        input.bind("UiPrevious", InputButton::Key(Keycode::Up));
+        input.bind("UiAccept", InputButton::Key(Keycode::Return));
+        // the pad: the left stick moves, start pauses, the dpad and a walk the menus
+        input.bind("MoveForward", InputButton::PadAxis(Axis::LeftY, AxisDirection::Negative));
+        input.bind("MoveBackward", InputButton::PadAxis(Axis::LeftY, AxisDirection::Positive));
+        input.bind("MoveLeft", InputButton::PadAxis(Axis::LeftX, AxisDirection::Negative));
+        input.bind("MoveRight", InputButton::PadAxis(Axis::LeftX, AxisDirection::Positive));
+        input.bind("Pause", InputButton::Pad(PadButton::Start));
+        input.bind("UiNext", InputButton::Pad(PadButton::DPadDown));
+        input.bind("UiPrevious", InputButton::Pad(PadButton::DPadUp));
+        input.bind("UiAccept", InputButton::Pad(PadButton::A));
+        input
+    }
+
+    // once per frame before the game runs, returns the events so whatever still wants them (the ui) can read them
+    pub fn update(&mut self, event_pump: &mut EventPump) -> Vec<Event> {
+        self.pressed.clear();
+        self.repeated.clear();
+        self.released.clear();
+        self.typed.clear();
+        self.mouse_delta = (0, 0);
+        self.wheel = (0.0, 0.0);
+
+        let events: Vec<Event> = event_pump.poll_iter().collect();
+        for event in &events {
+            self.handle_event(event);
+        }
+        self.update_axis_buttons();
+        events
+    }
+
+    fn handle_event(&mut self, event: &Event) {
+        match *event {
+            // the repeats of a held key are not new presses
+            Event::KeyDown { keycode: Some(keycode), repeat: false, .. } => self.press(InputButton::Key(keycode)),
+            Event::KeyDown { keycode: Some(keycode), repeat: true, .. } => {
+                self.repeated.insert(InputButton::Key(keycode));
+            }
+            Event::KeyUp { keycode: Some(keycode), .. } => self.release(InputButton::Key(keycode)),
+            Event::MouseButtonDown { mouse_btn, x, y, .. } => {
+                self.mouse_position = (x, y);
+                self.press(InputButton::Mouse(mouse_btn));
+            }
+            Event::MouseButtonUp { mouse_btn, x, y, .. } => {
+                self.mouse_position = (x, y);
+                self.release(InputButton::Mouse(mouse_btn));
+            }
+            Event::MouseMotion { x, y, xrel, yrel, .. } => {
+                self.mouse_position = (x, y);
+                self.mouse_delta.0 += xrel;
+                self.mouse_delta.1 += yrel;
+            }
+            Event::MouseWheel { precise_x, precise_y, .. } => {
+                self.wheel.0 += precise_x;
+                self.wheel.1 += precise_y;
+            }
+            Event::TextInput { ref text, .. } => self.typed.push_str(text),
+            Event::ControllerDeviceAdded { which, .. } => self.open_pad(which),
+            Event::ControllerDeviceRemoved { which, .. } => self.close_pad(which),
+            Event::ControllerButtonDown { button, .. } => self.press(InputButton::Pad(button)),
+            Event::ControllerButtonUp { button, .. } => self.release(InputButton::Pad(button)),
+            Event::ControllerAxisMotion { axis, value, .. } => {
+                self.pad_axes.insert(axis, (value as f32 / i16::MAX as f32).clamp(-1.0, 1.0));
+            }
+            // the keys let go in another window never come back up here
+            Event::Window { win_event: WindowEvent::FocusLost, .. } => self.clear(),
+            Event::Quit { .. } => self.quit_requested = true,
+            _ => {}
+        }
+    }
+
+    // which is the index of the device here, not the instance id
+    fn open_pad(&mut self, which: u32) {
+        let Some(controllers) = &self.controllers else { return };
+        match controllers.open(which) {
+            Ok(pad) => {
+                println!("gamepad connected: {}", pad.name());
+                self.pads.insert(pad.instance_id(), pad);
+            }
+            Err(e) => eprintln!("the gamepad {} couldn't be opened: {}", which, e),
+        }
+    }
+
+    // what the pads held is let go, the ones still plugged in send it again when it changes
+    fn close_pad(&mut self, instance_id: u32) {
+        if let Some(pad) = self.pads.remove(&instance_id) {
+            println!("gamepad disconnected: {}", pad.name());
+        }
+        let held: Vec<InputButton> = self.held.iter().filter(|button| matches!(button, InputButton::Pad(_))).copied().collect();
+        for button in held {
+            self.release(button);
+        }
+        self.pad_axes.clear();
+        self.update_axis_buttons();
+    }
+
+    // the half axes go down and up like the other buttons when they cross the middle
+    fn update_axis_buttons(&mut self) {
+        for axis in PAD_AXES {
+            for direction in [AxisDirection::Negative, AxisDirection::Positive] {
+                let button = InputButton::PadAxis(axis, direction);
+                if self.value(button) > AXIS_PRESS {
+                    self.press(button);
+                } else {
+                    self.release(button);
+                }
+            }
+        }
+    }
+
+    fn press(&mut self, button: InputButton) {
+        if self.held.insert(button) {
+            self.pressed.insert(button);
+            self.repeated.insert(button);
+        }
+    }
+
+    fn release(&mut self, button: InputButton) {
+        if self.held.remove(&button) {
+            self.released.insert(button);
+        }
+    }
+
+    // forgets the held buttons, after a pause or when the window loses the focus so nothing stays stuck down
+    pub fn clear(&mut self) {
+        self.held.clear();
+        self.pressed.clear();
+        self.repeated.clear();
+        self.released.clear();
+        self.typed.clear();
+        self.pad_axes.clear();
+    }
+
+    pub fn is_pressed(&self, button: InputButton) -> bool {
+        self.held.contains(&button)
+    }
+
+    // 0 to 1, the half axes are analog and the rest are 0 or 1
+    pub fn value(&self, button: InputButton) -> f32 {
+        match button {
+            InputButton::PadAxis(axis, AxisDirection::Negative) => (-self.pad_axis(axis)).max(0.0),
+            InputButton::PadAxis(axis, AxisDirection::Positive) => self.pad_axis(axis).max(0.0),
+            button => self.is_pressed(button) as i32 as f32,
+        }
+    }
+
+    // -1 to 1 after the deadzone, the sticks take it on the distance to the center (with the other axis of the stick)
+    // so a diagonal is not cut to a cross, and what is past it is scaled so the edge of the deadzone is 0
+    pub fn pad_axis(&self, axis: Axis) -> f32 {
+        let raw = |axis: Axis| self.pad_axes.get(&axis).copied().unwrap_or(0.0);
+        let value = raw(axis);
+        let other = match axis {
+            Axis::LeftX => Some(Axis::LeftY),
+            Axis::LeftY => Some(Axis::LeftX),
+            Axis::RightX => Some(Axis::RightY),
+            Axis::RightY => Some(Axis::RightX),
+            Axis::TriggerLeft | Axis::TriggerRight => None,
+        };
+        let deadzone = self.deadzone.clamp(0.0, 0.99);
+        let magnitude = match other {
+            Some(other) => (value * value + raw(other) * raw(other)).sqrt(),
+            None => value.abs(),
+        };
+        if magnitude <= deadzone {
+            return 0.0;
+        }
+        let scaled = ((magnitude - deadzone) / (1.0 - deadzone)).min(1.0);
+        value / magnitude * scaled
+    }
+
+    pub fn just_pressed(&self, button: InputButton) -> bool {
+        self.pressed.contains(&button)
+    }
+
+    // like just_pressed but a held key repeats at the rate of the system, backspace in a text field
+    pub fn just_pressed_or_repeated(&self, button: InputButton) -> bool {
+        self.repeated.contains(&button)
+    }
+
+    pub fn typed_text(&self) -> &str {
+        &self.typed
+    }
+
+    pub fn just_released(&self, button: InputButton) -> bool {
+        self.released.contains(&button)
+    }
+
+    pub fn mouse_position(&self) -> (i32, i32) {
+        self.mouse_position
+    }
+
+    // what the mouse moved this frame, in pixels
+    pub fn mouse_delta(&self) -> (i32, i32) {
+        self.mouse_delta
+    }
+
+    // the mouse delta with the sensitivity of the player, for turning the camera
+    pub fn look_delta(&self) -> (f32, f32) {
+        (self.mouse_delta.0 as f32 * self.sensitivity, self.mouse_delta.1 as f32 * self.sensitivity)
+    }
+
+    // a button that went down this frame, for the controls menu waiting for the new one
+    pub fn any_just_pressed(&self) -> Option<InputButton> {
+        self.pressed.iter().copied().next()
+    }
+
+    // the wheel this frame, y is positive away from the player
+    pub fn wheel(&self) -> (f32, f32) {
+        self.wheel
+    }
+
+    pub fn quit_requested(&self) -> bool {
+        self.quit_requested
+    }
+
+    // an action can have many buttons, any of them triggers it
+    pub fn bind(&mut self, action: &str, button: InputButton) {
+        let buttons = self.actions.entry(action.to_string()).or_default();
+        if !buttons.contains(&button) {
+            buttons.push(button);
+        }
+    }
+
+    // only when the action has nothing yet, the games bind their controls with this so the ones of the settings win
+    pub fn bind_default(&mut self, action: &str, button: InputButton) {
+        if self.bindings(action).is_empty() {
+            self.bind(action, button);
+        }
+    }
+
+    // replaces every button of the action, for the controls menu
+    pub fn rebind(&mut self, action: &str, buttons: &[InputButton]) {
+        self.actions.insert(action.to_string(), buttons.to_vec());
+    }
+
+    pub fn bindings(&self, action: &str) -> &[InputButton] {
+        self.actions.get(action).map_or(&[], Vec::as_slice)
+    }
+
+    // every action with its buttons, in name order
+    pub fn actions(&self) -> Vec<(&str, &[InputButton])> {
+        let mut actions: Vec<(&str, &[InputButton])> = self.actions.iter().map(|(action, buttons)| (action.as_str(), buttons.as_slice())).collect();
+        actions.sort_by_key(|(action, _)| *action);
+        actions
+    }
+
+    pub fn action_pressed(&self, action: &str) -> bool {
+        self.bindings(action).iter().any(|button| self.is_pressed(*button))
+    }
+
+    pub fn action_just_pressed(&self, action: &str) -> bool {
+        self.bindings(action).iter().any(|button| self.just_pressed(*button))
+    }
+
+    // 0 to 1, the most of any of its buttons
+    pub fn action_value(&self, action: &str) -> f32 {
+        self.bindings(action).iter().map(|button| self.value(*button)).fold(0.0, f32::max)
+    }
+
+    // -1 to 1 from two actions, for movement, a stick gives what is in between
+    pub fn axis(&self, negative: &str, positive: &str) -> f32 {
+        self.action_value(positive) - self.action_value(negative)
+    }
+}
+
+impl Default for InputState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,32 +1,154 @@
 use app::App;
 
 mod app;
+mod assets;
 mod game_object;
 mod resources;
 
 mod ui {
     pub mod text;
+    pub mod scale;
+    pub mod captions;
+    pub mod ui_renderer;
+    pub mod accessibility;
+    pub mod chat;
+    pub mod session_browser;
+    pub mod script_screen;
+    pub mod inventory_panel;
+    pub mod dialogue_panel;
+    pub mod options_menu;
+    pub mod world_labels;
 }
 
 mod input {
     pub mod button_module;
+    pub mod input_state;
 }
 
 mod gameplay {
     pub mod play;
+    pub mod placement;
+    pub mod physics2d;
+    pub mod picking;
+    pub mod collision;
+    pub mod abilities;
+    pub mod inventory;
+    pub mod dialogue;
+}
+
+mod util {
+    pub mod pool;
+    pub mod rng;
+    pub mod color;
+    pub mod curve;
+    pub mod noise;
+    pub mod timestep;
+    pub mod monitors;
+    pub mod frame_limiter;
+    pub mod file_watcher;
+    pub mod cvars;
+    pub mod content_hash;
+    pub mod vfs;
+    pub mod data_files;
+    pub mod localization;
+    pub mod settings;
+}
+
+// the client has no game sockets yet, so the prediction, the interpolation, the snapshots and the voice packets
+// (net/prediction.rs, interpolation.rs, snapshot.rs, voice.rs) are only built with the server (server.rs) until a
+// connection feeds them
+mod net {
+    pub mod protocol;
+    pub mod chat;
+    pub mod discovery;
+}
+
+mod audio {
+    pub mod mixer;
+    #[cfg(feature = "voice")]
+    pub mod voice;
+}
+
+mod editor {
+    pub mod inspector;
+    pub mod instance_brush;
+    pub mod undo;
+}
+
+mod scene {
+    pub mod manager;
+    pub mod persistent;
+    pub mod graph;
+    pub mod level2d;
+}
+
+mod debug {
+    pub mod profiler;
+    pub mod gpu_timer;
+    pub mod frame_graph;
+    pub mod overlay;
+    pub mod curve_editor;
 }
 
 mod rendering {
     pub mod textures;
     pub mod camera;
+    pub mod uniforms;
+    pub mod camera_shake;
     pub mod model;
+    pub mod mesh_processing;
+    pub mod batching;
+    pub mod thumbnail;
+    pub mod readback;
+    pub mod upload_queue;
+    pub mod frames_in_flight;
+    pub mod scene_renderer;
+    pub mod instance_manager;
+    pub mod instance_animation;
+    pub mod post_process;
+    pub mod debug_view;
+    pub mod overdraw;
+    pub mod display_output;
+    pub mod post_pass;
+    pub mod render_passes;
+    pub mod ground_grid;
+    pub mod shader_preprocessor;
+    pub mod shader_manager;
+    pub mod shader_variants;
+    pub mod material_reflection;
+    pub mod external_texture;
+    pub mod sprite;
+    pub mod tilemap;
+    pub mod parallax;
+    pub mod sky;
+    pub mod skybox;
+    pub mod lights;
+    pub mod shadow_map;
+    pub mod volumetric_fog;
+    pub mod planar_reflection;
+    pub mod portal;
+    pub mod trails;
+    pub mod blob_shadows;
+    pub mod particles;
+    pub mod clip_capture;
 }
 
 
 // this tokio trait means that main WILL AND CAN be asyncronous (without tokio this is not achievable)
 #[tokio::main]
 async fn main() -> Result<(), String> {
-    let app = App::new("WGPU with SDL2", Some(1280), Some(720));
+    // packing a build: the manifests the shipped game checks its assets against, then it exits
+    if std::env::args().any(|arg| arg == "--write-asset-manifest") {
+        for root in resources::asset_roots() {
+            let manifest = util::content_hash::AssetManifest::build(&root).and_then(|manifest| manifest.save().map(|_| manifest));
+            match manifest {
+                Ok(manifest) => println!("{} files in the manifest of {}", manifest.len(), root.display()),
+                Err(e) => return Err(format!("the manifest of {} was not written: {:#}", root.display(), e)),
+            }
+        }
+        return Ok(());
+    }
+    let app = App::new("WGPU with SDL2", None, None, None); // the size of the window comes from the settings
     app.await.update();
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,112 @@
+// the chat messages, the same message goes from the client to the server and from the server to every client
+// the client sends it with its own text and channel, the server cleans the text, fills in who sent it (the sender and
+// name of the client are never trusted) and relays it to the clients of the channel
+// a connection below CHAT_VERSION (see protocol.rs) has no chat, the server doesn't relay to it
+
+#[cfg(feature = "server")]
+use anyhow::bail;
+
+#[cfg(feature = "server")]
+use super::protocol::{ByteReader, ByteWriter, CHAT_VERSION};
+
+// in characters, the rest is cut by the server
+pub const MAX_CHAT_LENGTH: usize = 200;
+pub const MAX_NAME_LENGTH: usize = 24;
+
+// the id of the server when it talks (joins, leaves, kicks)
+pub const SERVER_SENDER: u32 = u32::MAX;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ChatChannel {
+    All,
+    Team,
+    System, // only the server writes here, nobody can mute it
+}
+
+impl ChatChannel {
+    #[cfg(feature = "server")]
+    fn to_byte(self) -> u8 {
+        match self {
+            ChatChannel::All => 0,
+            ChatChannel::Team => 1,
+            ChatChannel::System => 2,
+        }
+    }
+
+    #[cfg(feature = "server")]
+    fn from_byte(byte: u8) -> anyhow::Result<Self> {
+        Ok(match byte {
+            0 => ChatChannel::All,
+            1 => ChatChannel::Team,
+            2 => ChatChannel::System,
+            _ => bail!("there is no chat channel {}", byte),
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ChatChannel::All => "all",
+            ChatChannel::Team => "team",
+            ChatChannel::System => "system",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChatMessage {
+    pub channel: ChatChannel,
+    pub sender: u32, // the network id of the client, SERVER_SENDER for the server
+    pub name: String,
+    pub text: String,
+}
+
+impl ChatMessage {
+    pub fn new(channel: ChatChannel, sender: u32, name: &str, text: &str) -> Self {
+        Self { channel, sender, name: name.to_string(), text: text.to_string() }
+    }
+
+    pub fn system(text: &str) -> Self {
+        Self::new(ChatChannel::System, SERVER_SENDER, "server", text)
+    }
+
+    pub fn is_system(&self) -> bool {
+        self.channel == ChatChannel::System
+    }
+
+    // the wire side waits for the game sockets of the client, see protocol.rs
+    #[cfg(feature = "server")]
+    pub fn write(&self, version: u16, writer: &mut ByteWriter) -> anyhow::Result<()> {
+        if version < CHAT_VERSION {
+            bail!("the chat needs protocol {}, the connection is on {}", CHAT_VERSION, version);
+        }
+        writer.u8(self.channel.to_byte());
+        writer.varint(self.sender);
+        writer.string(&self.name);
+        writer.string(&self.text);
+        Ok(())
+    }
+
+    #[cfg(feature = "server")]
+    pub fn read(reader: &mut ByteReader, version: u16) -> anyhow::Result<Self> {
+        if version < CHAT_VERSION {
+            bail!("the chat needs protocol {}, the connection is on {}", CHAT_VERSION, version);
+        }
+        Ok(Self { channel: ChatChannel::from_byte(reader.u8()?)?, sender: reader.varint()?, name: reader.string()?, text: reader.string()? })
+    }
+
+    // what the server relays of a message a client sent, none when nothing is left to say
+    // a client can't write to the system channel, it goes to everyone like any other message
+    pub fn relayed(&self, sender: u32, name: &str) -> Option<ChatMessage> {
+        let text = sanitize(&self.text, MAX_CHAT_LENGTH)?;
+        let channel = if self.channel == ChatChannel::System { ChatChannel::All } else { self.channel };
+        let name = sanitize(name, MAX_NAME_LENGTH).unwrap_or_else(|| format!("player {}", sender));
+        Some(ChatMessage { channel, sender, name, text })
+    }
+}
+
+// without the control characters (new lines, escapes) and the spaces around, cut to the length
+pub fn sanitize(text: &str, max_length: usize) -> Option<String> {
+    let clean: String = text.trim().chars().filter(|c| !c.is_control()).take(max_length).collect();
+    let clean = clean.trim_end();
+    (!clean.is_empty()).then(|| clean.to_string())
+}
@@ -0,0 +1,150 @@
+// finding the games of the local network without typing addresses: every server broadcasts an advertisement (its
+// name, the port of the game, how many players and the protocol range) to the discovery port once a second, and the
+// session browser of the client listens on that port and keeps what it heard. a session that stops advertising is
+// dropped after a few seconds
+// the advertisement has the handshake of the server so the browser can tell which ones it can join before trying
+
+use std::io::ErrorKind;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+use anyhow::bail;
+
+use super::protocol::{ByteReader, Handshake};
+#[cfg(feature = "server")]
+use super::protocol::ByteWriter;
+
+pub const DISCOVERY_PORT: u16 = 27016;
+#[cfg(feature = "server")]
+pub const ADVERTISE_INTERVAL: Duration = Duration::from_secs(1);
+pub const SESSION_TIMEOUT: Duration = Duration::from_secs(4);
+
+// not the magic of the handshake, an advertisement never reaches the game port
+const DISCOVERY_MAGIC: [u8; 4] = *b"PNKD";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Advertisement {
+    pub name: String,
+    pub port: u16, // where the game listens, the address is the one the broadcast came from
+    pub players: u16,
+    pub max_players: u16,
+    pub handshake: Handshake,
+}
+
+impl Advertisement {
+    #[cfg(feature = "server")]
+    pub fn write(&self, writer: &mut ByteWriter) {
+        writer.bytes(&DISCOVERY_MAGIC);
+        writer.u16(self.handshake.min_version);
+        writer.u16(self.handshake.max_version);
+        writer.u16(self.port);
+        writer.u16(self.players);
+        writer.u16(self.max_players);
+        writer.string(&self.name);
+    }
+
+    pub fn read(reader: &mut ByteReader) -> anyhow::Result<Self> {
+        if reader.bytes(DISCOVERY_MAGIC.len())? != DISCOVERY_MAGIC {
+            bail!("not an advertisement of this game");
+        }
+        let handshake = Handshake { min_version: reader.u16()?, max_version: reader.u16()? };
+        Ok(Self { handshake, port: reader.u16()?, players: reader.u16()?, max_players: reader.u16()?, name: reader.string()? })
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.players >= self.max_players
+    }
+}
+
+// the server side, update it every tick and it sends when it's time
+#[cfg(feature = "server")]
+pub struct Advertiser {
+    socket: UdpSocket,
+    pub advertisement: Advertisement,
+    last_sent: Option<Instant>,
+}
+
+#[cfg(feature = "server")]
+impl Advertiser {
+    pub fn new(advertisement: Advertisement) -> anyhow::Result<Self> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        socket.set_broadcast(true)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self { socket, advertisement, last_sent: None })
+    }
+
+    pub fn update(&mut self) -> anyhow::Result<()> {
+        if self.last_sent.is_some_and(|sent| sent.elapsed() < ADVERTISE_INTERVAL) {
+            return Ok(());
+        }
+        self.last_sent = Some(Instant::now());
+        let mut writer = ByteWriter::new();
+        self.advertisement.write(&mut writer);
+        match self.socket.send_to(&writer.finish(), (Ipv4Addr::BROADCAST, DISCOVERY_PORT)) {
+            Ok(_) => Ok(()),
+            // no network yet (the cable, the wifi), it tries again next time
+            Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Session {
+    pub address: SocketAddr, // of the game, ready to join
+    pub advertisement: Advertisement,
+    pub version: Option<u16>, // what the connection would speak, none when the versions don't meet
+    pub last_seen: Instant,
+}
+
+impl Session {
+    pub fn can_join(&self) -> bool {
+        self.version.is_some() && !self.advertisement.is_full()
+    }
+}
+
+// the client side, it only listens so the list fills up within a second of opening it
+// only one browser per machine can have the port, a second one gets the error
+pub struct SessionBrowser {
+    socket: UdpSocket,
+    sessions: Vec<Session>,
+}
+
+impl SessionBrowser {
+    pub fn new() -> anyhow::Result<Self> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, DISCOVERY_PORT))?;
+        socket.set_nonblocking(true)?;
+        Ok(Self { socket, sessions: Vec::new() })
+    }
+
+    // reads what arrived and forgets the sessions that went quiet, once per frame while the browser is open
+    pub fn poll(&mut self) {
+        let mut buffer = [0u8; 512];
+        loop {
+            let (len, from) = match self.socket.recv_from(&mut buffer) {
+                Ok(received) => received,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    eprintln!("lan discovery: {}", e);
+                    break;
+                }
+            };
+            // anything that is not an advertisement is ignored, the port is open to the whole network
+            let Ok(advertisement) = Advertisement::read(&mut ByteReader::new(&buffer[..len])) else { continue };
+            let address = SocketAddr::new(from.ip(), advertisement.port);
+            let version = Handshake::ours().negotiate(&advertisement.handshake).ok();
+            let session = Session { address, advertisement, version, last_seen: Instant::now() };
+            match self.sessions.iter_mut().find(|known| known.address == address) {
+                Some(known) => *known = session,
+                None => self.sessions.push(session),
+            }
+        }
+        self.sessions.retain(|session| session.last_seen.elapsed() < SESSION_TIMEOUT);
+        self.sessions.sort_by(|a, b| a.advertisement.name.cmp(&b.advertisement.name).then(a.address.cmp(&b.address)));
+    }
+
+    // by name, the order doesn't jump around while the list is open
+    pub fn sessions(&self) -> &[Session] {
+        &self.sessions
+    }
+}
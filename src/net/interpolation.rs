@@ -0,0 +1,168 @@
+// the other entities are drawn a little in the past, between the two snapshots of the server around that time, so
+// they move smoothly even when the snapshots come at 20 per second and some arrive late or never. the delay is the
+// price: with 100ms the remote players are seen where they were 100ms ago, that is what lag compensation undoes on
+// the server by rewinding the history to what the shooter saw
+
+use std::collections::VecDeque;
+
+use cgmath::{Vector2, Vector3, VectorSpace};
+
+// what can be blended between two snapshots
+pub trait Interpolate {
+    fn interpolate(&self, other: &Self, t: f32) -> Self;
+}
+
+impl Interpolate for f32 {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Interpolate for Vector2<f32> {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        self.lerp(*other, t)
+    }
+}
+
+impl Interpolate for Vector3<f32> {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        self.lerp(*other, t)
+    }
+}
+
+// the snapshots of one entity by the time of the server, in seconds, oldest first
+pub struct SnapshotBuffer<S> {
+    snapshots: VecDeque<(f64, S)>,
+    capacity: usize,
+}
+
+impl<S: Interpolate + Clone> SnapshotBuffer<S> {
+    pub fn new(capacity: usize) -> Self {
+        Self { snapshots: VecDeque::with_capacity(capacity), capacity: capacity.max(2) }
+    }
+
+    // a snapshot older than the newest one arrived out of order, it goes in its place
+    pub fn push(&mut self, time: f64, state: S) {
+        let index = self.snapshots.iter().rposition(|(at, _)| *at <= time).map_or(0, |index| index + 1);
+        if index > 0 && self.snapshots[index - 1].0 == time {
+            self.snapshots[index - 1].1 = state; // the same snapshot twice
+            return;
+        }
+        self.snapshots.insert(index, (time, state));
+        if self.snapshots.len() > self.capacity {
+            self.snapshots.pop_front();
+        }
+    }
+
+    pub fn latest(&self) -> Option<&(f64, S)> {
+        self.snapshots.back()
+    }
+
+    // the state at a time, between the two snapshots around it. before the first it is the first, after the last
+    // it holds the last (no extrapolation, a late packet freezes the entity for a moment instead of overshooting)
+    pub fn sample(&self, time: f64) -> Option<S> {
+        let after = self.snapshots.iter().position(|(at, _)| *at >= time);
+        match after {
+            Some(0) => self.snapshots.front().map(|(_, state)| state.clone()),
+            Some(index) => {
+                let (from_time, from) = &self.snapshots[index - 1];
+                let (to_time, to) = &self.snapshots[index];
+                let t = ((time - from_time) / (to_time - from_time).max(f64::EPSILON)) as f32;
+                Some(from.interpolate(to, t))
+            }
+            None => self.snapshots.back().map(|(_, state)| state.clone()),
+        }
+    }
+
+    // the snapshots too old for any sample from now on
+    pub fn discard_before(&mut self, time: f64) {
+        // the last one before the time stays, it is the start of the next interpolation
+        while self.snapshots.len() > 2 && self.snapshots[1].0 <= time {
+            self.snapshots.pop_front();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+}
+
+// the clock of the remote entities on the client: the time of the server minus the delay
+pub struct InterpolationClock {
+    pub delay: f64, // seconds, about two snapshot intervals so one lost packet doesn't show
+    server_time: Option<f64>, // the estimate of the time of the server now
+}
+
+impl InterpolationClock {
+    pub fn new(delay: f64) -> Self {
+        Self { delay, server_time: None }
+    }
+
+    // every snapshot brings the time of the server, the clock follows it softly so the jitter of the network
+    // doesn't make the entities speed up and slow down
+    pub fn on_snapshot(&mut self, server_time: f64) {
+        self.server_time = Some(match self.server_time {
+            Some(estimate) if (server_time - estimate).abs() < 0.25 => estimate + (server_time - estimate) * 0.1,
+            _ => server_time, // the first one or too far off, jump
+        });
+    }
+
+    pub fn advance(&mut self, delta_time: f64) {
+        if let Some(time) = &mut self.server_time {
+            *time += delta_time;
+        }
+    }
+
+    // the time to sample the snapshot buffers at
+    pub fn render_time(&self) -> Option<f64> {
+        self.server_time.map(|time| time - self.delay)
+    }
+}
+
+// lag compensation on the server: the states of every entity in the last ticks, so a shot can be checked against
+// the world the shooter saw (its time minus its interpolation delay) and not the one the server has now
+pub struct LagCompensation<K, S> {
+    ticks: VecDeque<(f64, Vec<(K, S)>)>,
+    max_rewind: f64, // seconds, a client with more lag than this is checked against the oldest tick
+}
+
+impl<K: PartialEq + Clone, S: Interpolate + Clone> LagCompensation<K, S> {
+    pub fn new(max_rewind: f64) -> Self {
+        Self { ticks: VecDeque::new(), max_rewind }
+    }
+
+    // after every tick of the server, with the states the snapshot sends
+    pub fn record(&mut self, time: f64, states: Vec<(K, S)>) {
+        self.ticks.push_back((time, states));
+        while self.ticks.front().is_some_and(|(at, _)| time - at > self.max_rewind) {
+            self.ticks.pop_front();
+        }
+    }
+
+    // the state of one entity at a past time, blended between the two ticks around it
+    pub fn rewind(&self, key: &K, time: f64) -> Option<S> {
+        let find = |states: &Vec<(K, S)>| states.iter().find(|(k, _)| k == key).map(|(_, state)| state.clone());
+        let after = self.ticks.iter().position(|(at, _)| *at >= time);
+        match after {
+            Some(0) => find(&self.ticks.front()?.1),
+            None => find(&self.ticks.back()?.1),
+            Some(index) => {
+                let (from_time, from_states) = &self.ticks[index - 1];
+                let (to_time, to_states) = &self.ticks[index];
+                // an entity that spawned between the two ticks has no earlier state
+                match (find(from_states), find(to_states)) {
+                    (Some(from), Some(to)) => {
+                        let t = ((time - from_time) / (to_time - from_time).max(f64::EPSILON)) as f32;
+                        Some(from.interpolate(&to, t))
+                    }
+                    (from, to) => to.or(from),
+                }
+            }
+        }
+    }
+
+    // the time a client was seeing when it sent a command: its estimate of the server time minus its delay
+    pub fn client_view_time(command_server_time: f64, interpolation_delay: f64) -> f64 {
+        command_server_time - interpolation_delay
+    }
+}
@@ -0,0 +1,83 @@
+// client side prediction of the local player: the client applies its own inputs right away instead of waiting a round
+// trip for the server, and keeps them until the server says it has simulated them. when a state of the server arrives
+// (reconciliation) the client starts from it and applies again the inputs the server hasn't seen yet, so a prediction
+// that was wrong is corrected without throwing away what the player did since
+// it doesn't know about sockets or the game, the input and the state are whatever the game sends, and the step
+// has to be the same code the server runs (the fixed step of the simulation) or the predictions never match
+
+use std::collections::VecDeque;
+
+// the server answers with the last sequence it applied, so the client knows which inputs are done
+pub type InputSequence = u32;
+
+#[derive(Clone, Debug)]
+pub struct PendingInput<I> {
+    pub sequence: InputSequence,
+    pub input: I,
+}
+
+pub struct ClientPrediction<I, S> {
+    state: S, // the predicted state, what the local player is drawn with
+    pending: VecDeque<PendingInput<I>>, // sent and not yet confirmed, oldest first
+    next_sequence: InputSequence,
+    max_pending: usize, // with a very long lag the oldest are dropped, the server is too far behind to matter
+    correction: f32,    // how far the last reconciliation moved the prediction, see last_correction
+}
+
+impl<I: Clone, S: Clone> ClientPrediction<I, S> {
+    // max_pending is in steps, 2 seconds of inputs at the simulation rate is plenty
+    pub fn new(state: S, max_pending: usize) -> Self {
+        Self { state, pending: VecDeque::new(), next_sequence: 0, max_pending: max_pending.max(1), correction: 0.0 }
+    }
+
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+
+    // one fixed step of the local player, the returned input (with its sequence) is what goes to the server
+    pub fn predict(&mut self, input: I, step: impl Fn(&mut S, &I)) -> PendingInput<I> {
+        step(&mut self.state, &input);
+        let pending = PendingInput { sequence: self.next_sequence, input };
+        self.next_sequence = self.next_sequence.wrapping_add(1);
+        if self.pending.len() == self.max_pending {
+            self.pending.pop_front();
+        }
+        self.pending.push_back(pending.clone());
+        pending
+    }
+
+    // the authoritative state after the server applied the input acknowledged, the inputs after it are applied again
+    // distance says how far apart two states are, it is only kept for last_correction (for smoothing or debug)
+    pub fn reconcile(&mut self, server_state: S, acknowledged: InputSequence, step: impl Fn(&mut S, &I), distance: impl Fn(&S, &S) -> f32) {
+        // wrapping, so the comparison still works after the sequence goes around
+        while let Some(front) = self.pending.front() {
+            if (acknowledged.wrapping_sub(front.sequence) as i32) < 0 {
+                break;
+            }
+            self.pending.pop_front();
+        }
+
+        let mut state = server_state;
+        for pending in &self.pending {
+            step(&mut state, &pending.input);
+        }
+        self.correction = distance(&self.state, &state);
+        self.state = state;
+    }
+
+    // 0 when the prediction was right, the game can blend the drawn position when it is small and snap when big
+    pub fn last_correction(&self) -> f32 {
+        self.correction
+    }
+
+    pub fn pending(&self) -> impl Iterator<Item = &PendingInput<I>> {
+        self.pending.iter()
+    }
+
+    // after a teleport or a respawn the server state is the truth and nothing is replayed
+    pub fn reset(&mut self, state: S) {
+        self.state = state;
+        self.pending.clear();
+        self.correction = 0.0;
+    }
+}
@@ -0,0 +1,186 @@
+// the bytes of the messages and the handshake that agrees on the version of the protocol
+// both sides say the range they speak, the highest version in both ranges is used for the whole connection, so an old
+// client can still join a newer server while the older version is in the supported range
+// every change to the layout of a message needs a new version, the writers and readers check it where they differ
+// the client has no game sockets yet and only reads the lan advertisements, the rest is built with the server feature
+
+use anyhow::{anyhow, bail};
+
+// 1 sends every snapshot whole, 2 adds the delta against a baseline (see snapshot.rs), 3 adds the chat (see chat.rs)
+// and 4 the voice (see voice.rs)
+pub const PROTOCOL_VERSION: u16 = 4;
+pub const MIN_PROTOCOL_VERSION: u16 = 1;
+#[cfg(feature = "server")]
+pub const DELTA_SNAPSHOTS_VERSION: u16 = 2;
+#[cfg(feature = "server")]
+pub const CHAT_VERSION: u16 = 3;
+#[cfg(feature = "server")]
+pub const VOICE_VERSION: u16 = 4;
+
+// the first bytes of the handshake, anything else that reaches the port is ignored
+#[cfg(feature = "server")]
+const MAGIC: [u8; 4] = *b"PNKT";
+
+// little endian, the numbers that are usually small (ids, counts, masks) go as varints
+#[cfg(feature = "server")]
+#[derive(Default)]
+pub struct ByteWriter {
+    bytes: Vec<u8>,
+}
+
+#[cfg(feature = "server")]
+impl ByteWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn f32(&mut self, value: f32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    // 7 bits per byte, the high bit says another byte follows
+    pub fn varint(&mut self, mut value: u32) {
+        while value >= 0x80 {
+            self.bytes.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        self.bytes.push(value as u8);
+    }
+
+    pub fn bytes(&mut self, value: &[u8]) {
+        self.bytes.extend_from_slice(value);
+    }
+
+    // utf8 after its length in bytes
+    pub fn string(&mut self, value: &str) {
+        self.varint(value.len() as u32);
+        self.bytes(value.as_bytes());
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+pub struct ByteReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    fn take(&mut self, count: usize) -> anyhow::Result<&'a [u8]> {
+        let end = self.position + count;
+        let slice = self.bytes.get(self.position..end).ok_or_else(|| anyhow!("the message ends at byte {}", self.bytes.len()))?;
+        self.position = end;
+        Ok(slice)
+    }
+
+    pub fn u8(&mut self) -> anyhow::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> anyhow::Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into()?))
+    }
+
+    #[cfg(feature = "server")]
+    pub fn u32(&mut self) -> anyhow::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    #[cfg(feature = "server")]
+    pub fn f32(&mut self) -> anyhow::Result<f32> {
+        Ok(f32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    pub fn varint(&mut self) -> anyhow::Result<u32> {
+        let mut value = 0u32;
+        for shift in (0..35).step_by(7) {
+            let byte = self.u8()?;
+            value |= ((byte & 0x7f) as u32) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        bail!("a varint longer than 5 bytes")
+    }
+
+    pub fn bytes(&mut self, count: usize) -> anyhow::Result<&'a [u8]> {
+        self.take(count)
+    }
+
+    pub fn string(&mut self) -> anyhow::Result<String> {
+        let len = self.varint()? as usize;
+        Ok(std::str::from_utf8(self.take(len)?)?.to_string())
+    }
+
+    #[cfg(feature = "server")]
+    pub fn is_empty(&self) -> bool {
+        self.position >= self.bytes.len()
+    }
+}
+
+// the first message of both sides, the client sends its range and the server answers with the one it picked
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Handshake {
+    pub min_version: u16,
+    pub max_version: u16,
+}
+
+impl Handshake {
+    // what this build speaks
+    pub fn ours() -> Self {
+        Self { min_version: MIN_PROTOCOL_VERSION, max_version: PROTOCOL_VERSION }
+    }
+
+    #[cfg(feature = "server")]
+    pub fn write(&self, writer: &mut ByteWriter) {
+        writer.bytes(&MAGIC);
+        writer.u16(self.min_version);
+        writer.u16(self.max_version);
+    }
+
+    #[cfg(feature = "server")]
+    pub fn read(reader: &mut ByteReader) -> anyhow::Result<Self> {
+        if reader.bytes(MAGIC.len())? != MAGIC {
+            bail!("not a handshake of this game");
+        }
+        let handshake = Self { min_version: reader.u16()?, max_version: reader.u16()? };
+        if handshake.min_version > handshake.max_version {
+            bail!("the handshake range {}..{} is empty", handshake.min_version, handshake.max_version);
+        }
+        Ok(handshake)
+    }
+
+    // the highest version both speak, the error is what the player sees when they can't connect
+    pub fn negotiate(&self, other: &Handshake) -> anyhow::Result<u16> {
+        let version = self.max_version.min(other.max_version);
+        if version < self.min_version.max(other.min_version) {
+            if other.max_version < self.min_version {
+                bail!("the other side is too old (protocol {} to {}, this one needs {})", other.min_version, other.max_version, self.min_version);
+            }
+            bail!("the other side is too new (protocol {} to {}, this one speaks up to {})", other.min_version, other.max_version, self.max_version);
+        }
+        Ok(version)
+    }
+}
@@ -0,0 +1,134 @@
+// snapshots of the world for the clients, sent as the difference against one the client already has (the baseline)
+// every entity writes a mask of the fields that changed and then only those, an entity that didn't change costs
+// nothing and one that only moved costs its position. the baseline is the last snapshot the client acknowledged,
+// so a lost packet only means the next delta is against an older one, nothing is resent
+// the version of the connection (see protocol.rs) decides if deltas can be used at all
+
+use std::collections::{BTreeMap, VecDeque};
+
+use anyhow::{anyhow, bail};
+
+use super::protocol::{ByteReader, ByteWriter, DELTA_SNAPSHOTS_VERSION};
+
+pub type Tick = u32;
+
+// written in the place of the baseline tick when the snapshot is whole
+const NO_BASELINE: u32 = u32::MAX;
+
+// the state of an entity that goes in the snapshots, split in fields that are sent one by one
+// a new entity is written as the delta against the default, so the fields at their default are free too
+pub trait DeltaFields: Clone + Default {
+    const FIELD_COUNT: usize; // up to 32, the mask is one u32
+
+    fn field_changed(&self, baseline: &Self, field: usize) -> bool;
+    fn write_field(&self, field: usize, writer: &mut ByteWriter);
+    fn read_field(&mut self, field: usize, reader: &mut ByteReader) -> anyhow::Result<()>;
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Snapshot<S> {
+    pub tick: Tick,
+    pub entities: BTreeMap<u32, S>, // by the network id of the entity, in order so both sides walk them the same
+}
+
+impl<S: DeltaFields> Snapshot<S> {
+    pub fn new(tick: Tick) -> Self {
+        Self { tick, entities: BTreeMap::new() }
+    }
+
+    // against the baseline when the version allows it and there is one, whole otherwise
+    pub fn write(&self, baseline: Option<&Snapshot<S>>, version: u16, writer: &mut ByteWriter) {
+        let baseline = baseline.filter(|_| version >= DELTA_SNAPSHOTS_VERSION);
+        let empty = Snapshot::new(0);
+        let base = baseline.unwrap_or(&empty);
+
+        writer.u32(self.tick);
+        if version >= DELTA_SNAPSHOTS_VERSION {
+            writer.u32(baseline.map_or(NO_BASELINE, |baseline| baseline.tick));
+        }
+
+        // the ones of the baseline that are gone
+        let removed: Vec<u32> = base.entities.keys().filter(|id| !self.entities.contains_key(id)).copied().collect();
+        writer.varint(removed.len() as u32);
+        for id in removed {
+            writer.varint(id);
+        }
+
+        let default = S::default();
+        let changed: Vec<(u32, &S, u32)> = self
+            .entities
+            .iter()
+            .filter_map(|(id, state)| {
+                let previous = base.entities.get(id).unwrap_or(&default);
+                let mask = (0..S::FIELD_COUNT).filter(|field| state.field_changed(previous, *field)).fold(0u32, |mask, field| mask | 1 << field);
+                // a new entity is written even when it is all defaults, or the client would never know it exists
+                (mask != 0 || !base.entities.contains_key(id)).then_some((*id, state, mask))
+            })
+            .collect();
+        writer.varint(changed.len() as u32);
+        for (id, state, mask) in changed {
+            writer.varint(id);
+            writer.varint(mask);
+            for field in (0..S::FIELD_COUNT).filter(|field| mask & (1 << field) != 0) {
+                state.write_field(field, writer);
+            }
+        }
+    }
+
+    // the baselines are the snapshots the client received before, it finds the one the message was written against
+    pub fn read(reader: &mut ByteReader, version: u16, baselines: &SnapshotHistory<S>) -> anyhow::Result<Self> {
+        let tick = reader.u32()?;
+        let baseline_tick = if version >= DELTA_SNAPSHOTS_VERSION { reader.u32()? } else { NO_BASELINE };
+        let mut snapshot = match baseline_tick {
+            NO_BASELINE => Snapshot::new(tick),
+            baseline_tick => {
+                let baseline = baselines.get(baseline_tick).ok_or_else(|| anyhow!("the baseline {} of snapshot {} is gone", baseline_tick, tick))?;
+                Snapshot { tick, entities: baseline.entities.clone() }
+            }
+        };
+
+        for _ in 0..reader.varint()? {
+            let id = reader.varint()?;
+            snapshot.entities.remove(&id);
+        }
+        for _ in 0..reader.varint()? {
+            let id = reader.varint()?;
+            let mask = reader.varint()?;
+            if S::FIELD_COUNT < 32 && mask >> S::FIELD_COUNT != 0 {
+                bail!("entity {} has fields past the {} it has", id, S::FIELD_COUNT);
+            }
+            let state = snapshot.entities.entry(id).or_default();
+            for field in (0..S::FIELD_COUNT).filter(|field| mask & (1 << field) != 0) {
+                state.read_field(field, reader)?;
+            }
+        }
+        Ok(snapshot)
+    }
+}
+
+// the last snapshots, on the server to find the baseline a client acknowledged and on the client to apply the deltas
+pub struct SnapshotHistory<S> {
+    snapshots: VecDeque<Snapshot<S>>,
+    capacity: usize, // a client that hasn't acknowledged anything this old gets a whole snapshot
+}
+
+impl<S: DeltaFields> SnapshotHistory<S> {
+    pub fn new(capacity: usize) -> Self {
+        Self { snapshots: VecDeque::with_capacity(capacity), capacity: capacity.max(1) }
+    }
+
+    pub fn push(&mut self, snapshot: Snapshot<S>) {
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+
+    pub fn get(&self, tick: Tick) -> Option<&Snapshot<S>> {
+        self.snapshots.iter().rev().find(|snapshot| snapshot.tick == tick)
+    }
+
+    pub fn latest(&self) -> Option<&Snapshot<S>> {
+        self.snapshots.back()
+    }
+}
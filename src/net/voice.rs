@@ -0,0 +1,52 @@
+// the voice packets, one opus frame each (20ms of a mono mic at 48khz, see audio/voice.rs)
+// the client sends its frames while push to talk is held, the server fills in the sender and relays them to the
+// clients that speak VOICE_VERSION. they go unreliable: a late frame is worse than a lost one, the decoder covers
+// the holes it sees in the sequence
+// the server never decodes them, so this is shared and doesn't need the voice feature
+
+use anyhow::bail;
+
+use super::protocol::{ByteReader, ByteWriter, VOICE_VERSION};
+
+// a 20ms frame at the bitrate of the encoder is far under this, anything bigger is not ours
+pub const MAX_VOICE_FRAME: usize = 1275;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct VoicePacket {
+    pub sender: u32,   // the network id of the client that talks
+    pub sequence: u16, // one more every frame of the sender, it wraps
+    pub frame: Vec<u8>,
+}
+
+impl VoicePacket {
+    pub fn write(&self, version: u16, writer: &mut ByteWriter) -> anyhow::Result<()> {
+        if version < VOICE_VERSION {
+            bail!("the voice needs protocol {}, the connection is on {}", VOICE_VERSION, version);
+        }
+        writer.varint(self.sender);
+        writer.u16(self.sequence);
+        writer.varint(self.frame.len() as u32);
+        writer.bytes(&self.frame);
+        Ok(())
+    }
+
+    pub fn read(reader: &mut ByteReader, version: u16) -> anyhow::Result<Self> {
+        if version < VOICE_VERSION {
+            bail!("the voice needs protocol {}, the connection is on {}", VOICE_VERSION, version);
+        }
+        let sender = reader.varint()?;
+        let sequence = reader.u16()?;
+        let len = reader.varint()? as usize;
+        if len > MAX_VOICE_FRAME {
+            bail!("a voice frame of {} bytes", len);
+        }
+        Ok(Self { sender, sequence, frame: reader.bytes(len)?.to_vec() })
+    }
+
+    // how many frames were lost between the last one that arrived and this, none when it's old or repeated
+    pub fn frames_lost_since(&self, last: u16) -> Option<u16> {
+        let gap = self.sequence.wrapping_sub(last);
+        // half of the range behind is an old frame that arrived late
+        (gap != 0 && gap < u16::MAX / 2).then(|| gap - 1)
+    }
+}
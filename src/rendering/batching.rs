@@ -0,0 +1,132 @@
+// the draws of the main pass are sorted by state and merged: draws that share the same pipeline, material and mesh
+// and whose instances follow each other in the same buffer become one instanced draw, so we switch state less and
+// call the gpu less
+
+use std::ops::Range;
+
+use super::model::{InstancedDraw, Material, Mesh, Model};
+
+// the numbers before and after optimizing, so we can actually see if batching is worth it
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct BatchStats {
+    pub draws_before: usize,
+    pub draws_after: usize,
+    pub pipeline_switches_before: usize,
+    pub pipeline_switches_after: usize,
+    pub material_switches_before: usize,
+    pub material_switches_after: usize,
+}
+
+impl BatchStats {
+    pub fn saved_draws(&self) -> usize {
+        self.draws_before.saturating_sub(self.draws_after)
+    }
+}
+
+// counts how many times the pipeline or the material would change while walking the list in order
+fn count_switches(states: impl Iterator<Item = (usize, usize)>) -> (usize, usize) {
+    let mut pipeline_switches = 0;
+    let mut material_switches = 0;
+    let mut last: Option<(usize, usize)> = None;
+
+    for (pipeline, material) in states {
+        match last {
+            Some((last_pipeline, last_material)) => {
+                if last_pipeline != pipeline {
+                    pipeline_switches += 1;
+                }
+                if last_pipeline != pipeline || last_material != material {
+                    material_switches += 1;
+                }
+            }
+            None => {
+                pipeline_switches += 1;
+                material_switches += 1;
+            }
+        }
+        last = Some((pipeline, material));
+    }
+
+    (pipeline_switches, material_switches)
+}
+
+// one mesh of an instanced draw with the material it ends up with, and the variant of the material
+// the bind group is the one of the material for that pipeline, the custom shader materials have their own
+struct MeshDraw<'a> {
+    pipeline: Option<&'a wgpu::RenderPipeline>,
+    bind_group: &'a wgpu::BindGroup,
+    mesh: &'a Mesh,
+    buffer: &'a wgpu::Buffer,
+    instances: Range<u32>,
+}
+
+// the draws of a pass (the static batch and the groups of the scene graph) in the order that switches the least:
+// every mesh of every draw is sorted by its pipeline, then its material, then by instance buffer and mesh, so the bind
+// group 0 changes once per material of the frame instead of once per mesh
+// the materials are told apart by their bind groups, they live in the models and the graph for the whole frame
+// with a variant the pipeline of each material comes from it (see shader_variants.rs) and the custom shader materials
+// go with their own bind group, without one the pipeline has to be set before and every material uses the standard one. the bind groups after the camera always have to be set before, like for draw_instanced
+pub fn draw_by_material<'a>(
+    render_pass: &mut wgpu::RenderPass<'a>,
+    draws: &[InstancedDraw<'a>],
+    camera_bind_group: &'a wgpu::BindGroup,
+    variant: Option<&dyn Fn(&Material) -> &'a wgpu::RenderPipeline>,
+) -> BatchStats {
+    let mut meshes: Vec<MeshDraw<'a>> = Vec::new();
+    for draw in draws.iter().filter(|draw| !draw.instances.is_empty()) {
+        let model: &'a Model = draw.model;
+        for mesh in &model.meshes {
+            let material: &'a Material = draw.material.unwrap_or(&model.materials[mesh.material]);
+            let pipeline = variant.map(|variant| variant(material));
+            let bind_group = if pipeline.is_some() { material.scene_bind_group() } else { &material.bind_group };
+            meshes.push(MeshDraw { pipeline, bind_group, mesh, buffer: draw.buffer, instances: draw.instances.clone() });
+        }
+    }
+    let key = |draw: &MeshDraw| (
+        draw.pipeline.map_or(0, |pipeline| pipeline as *const wgpu::RenderPipeline as usize),
+        draw.bind_group as *const wgpu::BindGroup as usize,
+        draw.buffer as *const wgpu::Buffer as usize,
+        draw.mesh as *const Mesh as usize,
+    );
+
+    let mut stats = BatchStats { draws_before: meshes.len(), ..Default::default() };
+    (stats.pipeline_switches_before, stats.material_switches_before) = count_switches(meshes.iter().map(|draw| (key(draw).0, key(draw).1)));
+    meshes.sort_by_key(|draw| (key(draw), draw.instances.start));
+    // the same state with the instances right after the last ones is the same draw
+    let mut merged: Vec<MeshDraw<'a>> = Vec::with_capacity(meshes.len());
+    for draw in meshes {
+        match merged.last_mut() {
+            Some(last) if key(last) == key(&draw) && last.instances.end == draw.instances.start => last.instances.end = draw.instances.end,
+            _ => merged.push(draw),
+        }
+    }
+    stats.draws_after = merged.len();
+    (stats.pipeline_switches_after, stats.material_switches_after) = count_switches(merged.iter().map(|draw| (key(draw).0, key(draw).1)));
+
+    let mut current_pipeline: Option<*const wgpu::RenderPipeline> = None;
+    let mut current_bind_group: Option<*const wgpu::BindGroup> = None;
+    let mut current_buffer: Option<*const wgpu::Buffer> = None;
+    let mut current_mesh: Option<*const Mesh> = None;
+    render_pass.set_bind_group(1, camera_bind_group, &[]);
+    for draw in merged {
+        if let Some(pipeline) = draw.pipeline.filter(|pipeline| current_pipeline != Some(*pipeline as *const wgpu::RenderPipeline)) {
+            render_pass.set_pipeline(pipeline);
+            current_pipeline = Some(pipeline as *const wgpu::RenderPipeline);
+        }
+        if current_bind_group != Some(draw.bind_group as *const wgpu::BindGroup) {
+            render_pass.set_bind_group(0, draw.bind_group, &[]);
+            current_bind_group = Some(draw.bind_group as *const wgpu::BindGroup);
+        }
+        if current_buffer != Some(draw.buffer as *const wgpu::Buffer) {
+            render_pass.set_vertex_buffer(1, draw.buffer.slice(..));
+            current_buffer = Some(draw.buffer as *const wgpu::Buffer);
+        }
+        if current_mesh != Some(draw.mesh as *const Mesh) {
+            render_pass.set_vertex_buffer(0, draw.mesh.vertex_buffer.slice(..));
+            render_pass.set_index_buffer(draw.mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            current_mesh = Some(draw.mesh as *const Mesh);
+        }
+        render_pass.draw_indexed(0..draw.mesh.num_elements, 0, draw.instances);
+    }
+    stats
+}
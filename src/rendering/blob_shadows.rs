@@ -0,0 +1,312 @@
+// the cheap shadows for the low settings, where the shadow map is off: a soft dark spot under each caster (a blob) or
+// a stretched one under a long thing lying down (a capsule, the shadow of its segment). they are decals, each caster
+// draws the box under it and every pixel inside reads the depth of the scene, goes back to the world and darkens it by
+// how close it is to the caster, so the spot follows the floor, the stairs and the other objects under it
+// only the surfaces facing up get it, the walls and the caster itself don't
+
+use cgmath::{EuclideanSpace, Point3, SquareMatrix, Transform, Vector3};
+use wgpu::{util::DeviceExt, Device, Queue};
+
+use super::camera::{Aabb, Camera};
+use super::shader_preprocessor::ShaderLibrary;
+use super::textures::Texture;
+use crate::scene::graph::{EntityId, SceneGraph};
+use crate::util::pool::{Pool, PoolHandle};
+
+// the shadows of the scene, the "r_shadows" cvar
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ShadowQuality {
+    Off,
+    Blob, // the decals of this file
+    Map,  // the shadow map, the scene shader doesn't read it yet so only the fog has shadows with it
+}
+
+impl ShadowQuality {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ShadowQuality::Off => "off",
+            ShadowQuality::Blob => "blob",
+            ShadowQuality::Map => "map",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "off" => Some(ShadowQuality::Off),
+            "blob" => Some(ShadowQuality::Blob),
+            "map" => Some(ShadowQuality::Map),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ShadowShape {
+    Blob { radius: f32 },
+    Capsule { half_length: Vector3<f32>, radius: f32 }, // from the center to one end, it turns with the entity
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ShadowAnchor {
+    Point(Point3<f32>),
+    Entity { entity: EntityId, offset: Vector3<f32> },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BlobShadow {
+    pub shape: ShadowShape,
+    pub anchor: ShadowAnchor,
+    pub opacity: f32,
+    pub max_height: f32, // how far under the caster it reaches, it fades on the way
+}
+
+impl BlobShadow {
+    pub fn new(shape: ShadowShape, anchor: ShadowAnchor) -> Self {
+        Self { shape, anchor, opacity: 0.6, max_height: 4.0 }
+    }
+
+    // the two ends of the segment in the world (the same point for a blob) and the radius
+    fn segment(&self, graph: &SceneGraph) -> Option<(Point3<f32>, Point3<f32>, f32)> {
+        let (matrix, offset) = match self.anchor {
+            ShadowAnchor::Point(point) => (cgmath::Matrix4::from_translation(point.to_vec()), Vector3::new(0.0, 0.0, 0.0)),
+            ShadowAnchor::Entity { entity, offset } => (graph.get(entity)?.world_matrix(), offset),
+        };
+        let (half, radius) = match self.shape {
+            ShadowShape::Blob { radius } => (Vector3::new(0.0, 0.0, 0.0), radius),
+            ShadowShape::Capsule { half_length, radius } => (half_length, radius),
+        };
+        let center = Point3::from_vec(offset);
+        Some((matrix.transform_point(center - half), matrix.transform_point(center + half), radius))
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct BlobUniform {
+    view_proj: [[f32; 4]; 4],
+    inverse_view_proj: [[f32; 4]; 4],
+    far_depth: [f32; 4], // the depth of the far plane (where the sky is) and unused
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct CasterInstance {
+    start: [f32; 4], // xyz and the radius
+    end: [f32; 4],   // xyz and the opacity
+    reach: [f32; 4], // the max height and unused
+}
+
+impl CasterInstance {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<CasterInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[
+                wgpu::VertexAttribute { offset: 0, shader_location: 0, format: wgpu::VertexFormat::Float32x4 },
+                wgpu::VertexAttribute { offset: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress, shader_location: 1, format: wgpu::VertexFormat::Float32x4 },
+                wgpu::VertexAttribute { offset: std::mem::size_of::<[f32; 8]>() as wgpu::BufferAddress, shader_location: 2, format: wgpu::VertexFormat::Float32x4 },
+            ],
+        }
+    }
+}
+
+pub struct BlobShadows {
+    pub enabled: bool,
+    casters: Pool<BlobShadow>,
+    uniform_buffer: wgpu::Buffer,
+    instance_buffer: wgpu::Buffer,
+    instance_capacity: usize,
+    instance_count: u32,
+    layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl BlobShadows {
+    pub fn new(device: &Device, format: wgpu::TextureFormat, depth: &Texture) -> Self {
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Blob Shadow Buffer"),
+            contents: bytemuck::cast_slice(&[<BlobUniform as bytemuck::Zeroable>::zeroed()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("blob_shadow_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let shader = ShaderLibrary::builtin().create_module(device, "Blob Shadow Shader", "blob_shadow.wgsl", &[]);
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Blob Shadow Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Blob Shadow Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[CasterInstance::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                // the inside of the box, so each pixel is darkened once and it still works with the camera inside it
+                cull_mode: Some(wgpu::Face::Front),
+                ..Default::default()
+            },
+            depth_stencil: None, // the depth is read, the decal doesn't need the test
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let bind_group = Self::create_bind_group(device, &layout, &uniform_buffer, depth);
+        let instance_capacity = 64;
+        Self {
+            enabled: true,
+            casters: Pool::new(),
+            uniform_buffer,
+            instance_buffer: Self::create_instance_buffer(device, instance_capacity),
+            instance_capacity,
+            instance_count: 0,
+            layout,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    fn create_bind_group(device: &Device, layout: &wgpu::BindGroupLayout, uniform_buffer: &wgpu::Buffer, depth: &Texture) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("blob_shadow_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: uniform_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&depth.view) },
+            ],
+        })
+    }
+
+    fn create_instance_buffer(device: &Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Blob Shadow Instance Buffer"),
+            size: (capacity * std::mem::size_of::<CasterInstance>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    // the pass reads the depth of the scene, so it follows it when the screen changes size
+    pub fn resize(&mut self, device: &Device, depth: &Texture) {
+        self.bind_group = Self::create_bind_group(device, &self.layout, &self.uniform_buffer, depth);
+    }
+
+    pub fn add(&mut self, shadow: BlobShadow) -> PoolHandle {
+        self.casters.insert(shadow)
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.enabled && self.instance_count > 0
+    }
+
+    // the casters where their entities are now, the ones of despawned entities go
+    // the world transforms of the graph have to be updated
+    pub fn update(&mut self, device: &Device, queue: &Queue, camera: &Camera, graph: &SceneGraph) {
+        self.casters.retain(|shadow| match shadow.anchor {
+            ShadowAnchor::Entity { entity, .. } => graph.get(entity).is_some(),
+            ShadowAnchor::Point(_) => true,
+        });
+        if !self.enabled {
+            self.instance_count = 0;
+            return;
+        }
+        let frustum = camera.frustum();
+        let instances: Vec<CasterInstance> = self
+            .casters
+            .iter()
+            .filter_map(|(_, shadow)| {
+                let (start, end, radius) = shadow.segment(graph)?;
+                // the box the shader draws under the caster (see blob_shadow.wgsl), the ones out of the view are skipped
+                let spread = radius * 1.5;
+                let bounds = Aabb::new(
+                    Point3::new(start.x.min(end.x) - spread, start.y.min(end.y) - shadow.max_height.max(0.01), start.z.min(end.z) - spread),
+                    Point3::new(start.x.max(end.x) + spread, start.y.max(end.y), start.z.max(end.z) + spread),
+                );
+                if !frustum.intersects_aabb(&bounds) {
+                    return None;
+                }
+                Some(CasterInstance {
+                    start: [start.x, start.y, start.z, radius],
+                    end: [end.x, end.y, end.z, shadow.opacity.clamp(0.0, 1.0)],
+                    reach: [shadow.max_height.max(0.01), 0.0, 0.0, 0.0],
+                })
+            })
+            .collect();
+        self.instance_count = instances.len() as u32;
+        if instances.is_empty() {
+            return;
+        }
+        if instances.len() > self.instance_capacity {
+            self.instance_capacity = instances.len().next_power_of_two();
+            self.instance_buffer = Self::create_instance_buffer(device, self.instance_capacity);
+        }
+        queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&instances));
+
+        let view_proj = camera.view_projection_matrix();
+        let uniform = BlobUniform {
+            view_proj: view_proj.into(),
+            inverse_view_proj: view_proj.invert().unwrap_or(cgmath::Matrix4::identity()).into(),
+            far_depth: [camera.depth_clear_value(), 0.0, 0.0, 0.0],
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    // over the scene after the main pass, before the fog
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        if !self.is_active() {
+            return;
+        }
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Blob Shadow Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+        // the box under each caster, 12 triangles
+        render_pass.draw(0..36, 0..self.instance_count);
+    }
+}
@@ -1,5 +1,8 @@
-use cgmath::SquareMatrix;
-use wgpu::{util::DeviceExt, BindGroup, BindGroupLayout, BindGroupLayoutDescriptor, Buffer, Device};
+use anyhow::bail;
+use cgmath::{InnerSpace, Matrix, SquareMatrix};
+use super::camera_shake::CameraShake;
+use super::frames_in_flight::{FrameSlot, UniformRing, MAX_FRAMES_IN_FLIGHT};
+use wgpu::{BindGroup, BindGroupLayout, Device, Queue};
 
 #[rustfmt::skip]
 pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
@@ -9,12 +12,22 @@ pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
     0.0, 0.0, 0.0, 1.0,
 );
 
+// the values the camera starts with, the r_fov, r_znear and r_zfar cvars go through the setters of CameraRenderizable
+pub const DEFAULT_FOVY: f32 = 45.0;
+pub const DEFAULT_ZNEAR: f32 = 0.1;
+pub const DEFAULT_ZFAR: f32 = 100.0;
+
+// limits for the setters, outside of these the projection breaks or the depth buffer is useless
+pub const MIN_FOVY: f32 = 1.0;
+pub const MAX_FOVY: f32 = 170.0;
+
 pub struct CameraRenderizable {
     pub camera: Camera,
     pub uniform: CameraUniform,
-    pub buffer: Buffer,
     pub bind_group_layout: BindGroupLayout,
-    pub bind_group: BindGroup
+    ring: UniformRing, // the matrix changes every frame, so each frame in flight has its own buffer
+    slot: FrameSlot,
+    pub shake: CameraShake,
 }
 
 impl CameraRenderizable {
@@ -24,50 +37,124 @@ impl CameraRenderizable {
             target: (0.0, 0.0, 0.0).into(), // we are looking at (0,0,0)
             up: cgmath::Vector3::unit_y(),
             aspect: config.width as f32 / config.height as f32,
-            fovy: 45.0,
-            znear: 0.1,
-            zfar: 100.0,
+            fovy: DEFAULT_FOVY,
+            znear: DEFAULT_ZNEAR,
+            zfar: DEFAULT_ZFAR,
+            projection: Projection::Perspective,
+            reverse_z: false,
         };
 
-        // we create the 4x4 matrix of the camera
-        let uniform = CameraUniform::new();
-
-        // we create a buffer and a bind group
-        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Camera Buffer"),
-            contents: bytemuck::cast_slice(&[uniform]),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
-
-        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+        // the 4x4 matrix of the camera, in a buffer with its bind group for each slot the pacer can have, so changing
+        // the frames in flight doesn't make them again
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("camera_bind_group_layout"),
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform, // its a uniform buffer, duh
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<CameraUniform>() as u64),
                 },
-            ],
+                count: None,
+            }],
         });
+        let ring = UniformRing::new(device, &bind_group_layout, std::mem::size_of::<CameraUniform>() as wgpu::BufferAddress, MAX_FRAMES_IN_FLIGHT, "Camera Buffer");
 
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-                label: Some("camera_bind_group"),
-                layout: &bind_group_layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: buffer.as_entire_binding(),
-                    },
-                ],
-            }
-        );
+        return CameraRenderizable { camera, uniform: CameraUniform::new(), bind_group_layout, ring, slot: FrameSlot::default(), shake: CameraShake::new() };
+    }
+
+    // the bind group of the frame being built, the passes set this one
+    pub fn bind_group(&self) -> &BindGroup {
+        self.ring.bind_group(self.slot)
+    }
+
+    // the frame moved to another slot of the pacer, its buffer gets the matrix even if nothing moves the camera
+    pub fn begin_frame(&mut self, queue: &Queue, slot: FrameSlot) {
+        self.slot = slot;
+        self.ring.write(queue, slot, bytemuck::bytes_of(&self.uniform));
+    }
+
+    // rebuilds the matrix and sends it to the gpu, the setters call it so the change is visible on the next frame
+    pub fn refresh(&mut self, queue: &Queue) {
+        // the shake goes on top of whatever the controller did, without changing the real camera
+        let camera = self.shake.apply(&self.camera);
+        self.uniform.update_view_proj(&camera);
+        self.ring.write(queue, self.slot, bytemuck::bytes_of(&self.uniform));
+    }
+
+    // called once per frame after the gameplay moved the camera
+    pub fn update(&mut self, queue: &Queue, delta_time: f32) {
+        self.shake.update(delta_time);
+        self.refresh(queue);
+    }
+
+    // for impacts and explosions, 0.5 is a strong hit and 1 is the max
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.shake.add_trauma(amount);
+    }
+
+    // vertical field of view in degrees
+    pub fn set_fovy(&mut self, queue: &Queue, fovy: f32) -> anyhow::Result<()> {
+        if !(MIN_FOVY..=MAX_FOVY).contains(&fovy) {
+            bail!("the fov must be between {} and {} degrees, got {}", MIN_FOVY, MAX_FOVY, fovy);
+        }
+        self.camera.fovy = fovy;
+        self.refresh(queue);
+        Ok(())
+    }
+
+    // the depth mode can't change here since the pipeline depth compare depends on it, only the far plane behaviour
+    pub fn set_projection(&mut self, queue: &Queue, projection: Projection) {
+        self.camera.projection = projection;
+        self.refresh(queue);
+    }
+
+    // switches to an orthographic isometric view of the current target, keeping the aspect and depth mode
+    pub fn set_isometric(&mut self, queue: &Queue, angle: IsometricAngle, yaw: cgmath::Rad<f32>, height: f32) {
+        self.camera.projection = Projection::Orthographic { height };
+        self.camera.set_isometric_angle(angle, yaw);
+        self.refresh(queue);
+    }
+
+    // both planes at once, so we can move them without going through an invalid state
+    pub fn set_clip_planes(&mut self, queue: &Queue, znear: f32, zfar: f32) -> anyhow::Result<()> {
+        if !znear.is_finite() || znear <= 0.0 {
+            bail!("znear must be bigger than 0, got {}", znear);
+        }
+        if !zfar.is_finite() || zfar <= znear {
+            bail!("zfar must be bigger than znear ({}), got {}", znear, zfar);
+        }
+        self.camera.znear = znear;
+        self.camera.zfar = zfar;
+        self.refresh(queue);
+        Ok(())
+    }
+}
+
+// how far the camera can see, the infinite one ignores zfar so open worlds never get cut at the horizon
+// the orthographic one has no perspective at all (isometric and strategy views), height is how many world units fit vertically
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Projection {
+    Perspective,
+    InfinitePerspective,
+    Orthographic { height: f32 },
+}
 
-        return CameraRenderizable { camera, uniform, buffer, bind_group, bind_group_layout };
+// the usual angles for isometric games, the yaw is always 45 degrees and the pitch changes
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum IsometricAngle {
+    True,                       // the real isometric, the three axes look the same length (35.26 degrees down)
+    PixelArt,                   // the 2:1 dimetric of pixel art games, tiles are twice as wide as tall (30 degrees down)
+}
+
+impl IsometricAngle {
+    pub fn pitch(&self) -> cgmath::Rad<f32> {
+        match *self {
+            IsometricAngle::True => cgmath::Rad((1.0f32 / 2.0f32.sqrt()).atan()),
+            // sin(30) = 0.5 squashes the ground to half its height, atan(0.5) would be the slope of the tile edges instead
+            IsometricAngle::PixelArt => cgmath::Rad(0.5f32.asin()),
+        }
     }
 }
 
@@ -81,13 +168,315 @@ pub struct Camera {
     pub fovy: f32,
     pub znear: f32,
     pub zfar: f32,
+    pub projection: Projection,
+    // reverse z puts the near plane at depth 1 and the far at 0, it gives a lot more precision far away
+    // the pipeline has to use depth_compare() and the pass has to clear with depth_clear_value()
+    pub reverse_z: bool,
 }
 
 impl Camera {
+    // moves the eye around the target keeping the distance far enough so nothing gets cut by the near plane,
+    // the yaw turns the view (45, 135, 225 and 315 degrees are the four classic views)
+    pub fn set_isometric_angle(&mut self, angle: IsometricAngle, yaw: cgmath::Rad<f32>) {
+        let pitch = angle.pitch();
+        let distance = self.zfar * 0.5;
+        let direction = cgmath::Vector3::new(yaw.0.sin() * pitch.0.cos(), pitch.0.sin(), yaw.0.cos() * pitch.0.cos());
+        self.eye = self.target + direction * distance;
+        self.up = cgmath::Vector3::unit_y();
+    }
+
     fn build_view_projection_matrix(&self) -> cgmath::Matrix4<f32> {
         let view = cgmath::Matrix4::look_at_rh(self.eye, self.target, self.up);
-        let proj = cgmath::perspective(cgmath::Deg(self.fovy), self.aspect, self.znear, self.zfar); 
-        return OPENGL_TO_WGPU_MATRIX * proj * view;
+        return self.build_projection_matrix() * view;
+    }
+
+    // the projection already in wgpu clip space (depth from 0 to 1)
+    pub fn build_projection_matrix(&self) -> cgmath::Matrix4<f32> {
+        if let Projection::Orthographic { height } = self.projection {
+            return self.build_orthographic_matrix(height);
+        }
+        if self.projection == Projection::Perspective && !self.reverse_z {
+            let proj = cgmath::perspective(cgmath::Deg(self.fovy), self.aspect, self.znear, self.zfar);
+            return OPENGL_TO_WGPU_MATRIX * proj;
+        }
+
+        let f = 1.0 / (self.fovy.to_radians() * 0.5).tan();
+        let near = self.znear;
+        let far = self.zfar;
+
+        // only the depth row changes between the modes, (a, b) are the z and w terms of that row
+        let (a, b) = match (self.projection, self.reverse_z) {
+            (Projection::Perspective, false) => (far / (near - far), near * far / (near - far)),
+            (Projection::Perspective, true) => (near / (far - near), near * far / (far - near)),
+            (Projection::InfinitePerspective, false) => (-1.0, -near),
+            (Projection::InfinitePerspective, true) => (0.0, near),
+            (Projection::Orthographic { .. }, _) => unreachable!(),
+        };
+
+        // cgmath takes the values column by column
+        #[rustfmt::skip]
+        let proj = cgmath::Matrix4::new(
+            f / self.aspect, 0.0, 0.0, 0.0,
+            0.0, f, 0.0, 0.0,
+            0.0, 0.0, a, -1.0,
+            0.0, 0.0, b, 0.0,
+        );
+        proj
+    }
+
+    fn build_orthographic_matrix(&self, height: f32) -> cgmath::Matrix4<f32> {
+        let half_height = height.max(f32::EPSILON) * 0.5;
+        let half_width = half_height * self.aspect;
+        let near = self.znear;
+        let far = self.zfar;
+        let depth = far - near;
+
+        // the depth is linear here, so reverse z only flips it
+        let (a, b) = if self.reverse_z { (1.0 / depth, far / depth) } else { (-1.0 / depth, -near / depth) };
+
+        #[rustfmt::skip]
+        let proj = cgmath::Matrix4::new(
+            1.0 / half_width, 0.0, 0.0, 0.0,
+            0.0, 1.0 / half_height, 0.0, 0.0,
+            0.0, 0.0, a, 0.0,
+            0.0, 0.0, b, 1.0,
+        );
+        proj
+    }
+
+    pub fn view_projection_matrix(&self) -> cgmath::Matrix4<f32> {
+        self.build_view_projection_matrix()
+    }
+
+    // the volume the camera sees right now, for culling and spatial queries
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_matrix(&self.build_view_projection_matrix())
+    }
+
+    // a ray from the camera through a pixel, x and y are window coordinates (0,0 is the top left)
+    pub fn screen_to_ray(&self, x: f32, y: f32, width: f32, height: f32) -> Option<Ray> {
+        let inverse = self.build_view_projection_matrix().invert()?;
+
+        // from pixels to normalized device coordinates, in wgpu y goes up
+        let ndc_x = 2.0 * x / width - 1.0;
+        let ndc_y = 1.0 - 2.0 * y / height;
+
+        // a point on the near plane and one further away, the far plane can be at infinity so we don't use it
+        let near_depth = if self.reverse_z { 1.0 } else { 0.0 };
+        let near = inverse * cgmath::Vector4::new(ndc_x, ndc_y, near_depth, 1.0);
+        let further = inverse * cgmath::Vector4::new(ndc_x, ndc_y, 0.5, 1.0);
+        if near.w.abs() < f32::EPSILON || further.w.abs() < f32::EPSILON {
+            return None;
+        }
+
+        let origin = cgmath::Point3::new(near.x / near.w, near.y / near.w, near.z / near.w);
+        let target = cgmath::Point3::new(further.x / further.w, further.y / further.w, further.z / further.w);
+        let direction = target - origin;
+        if direction.magnitude2() < f32::EPSILON {
+            return None;
+        }
+
+        Some(Ray { origin, direction: direction.normalize() })
+    }
+
+    // the pixel where a world point lands, z is the depth buffer value of that point
+    // None when the point is behind the camera
+    pub fn world_to_screen(&self, point: cgmath::Point3<f32>, width: f32, height: f32) -> Option<cgmath::Point3<f32>> {
+        let clip = self.build_view_projection_matrix() * cgmath::Vector4::new(point.x, point.y, point.z, 1.0);
+        if clip.w <= 0.0 {
+            return None;
+        }
+
+        let ndc = cgmath::Vector3::new(clip.x / clip.w, clip.y / clip.w, clip.z / clip.w);
+        Some(cgmath::Point3::new(
+            (ndc.x + 1.0) * 0.5 * width,
+            (1.0 - ndc.y) * 0.5 * height,
+            ndc.z,
+        ))
+    }
+
+    pub fn depth_compare(&self) -> wgpu::CompareFunction {
+        if self.reverse_z { wgpu::CompareFunction::Greater } else { wgpu::CompareFunction::Less }
+    }
+
+    pub fn depth_clear_value(&self) -> f32 {
+        if self.reverse_z { 0.0 } else { 1.0 }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Ray {
+    pub origin: cgmath::Point3<f32>,
+    pub direction: cgmath::Vector3<f32>, // always normalized
+}
+
+impl Ray {
+    pub fn at(&self, distance: f32) -> cgmath::Point3<f32> {
+        self.origin + self.direction * distance
+    }
+
+    // distance along the ray to the plane, None if it is parallel or the plane is behind
+    pub fn intersect_plane(&self, plane: &Plane) -> Option<f32> {
+        let denominator = plane.normal.dot(self.direction);
+        if denominator.abs() < 1e-6 {
+            return None;
+        }
+        let t = -plane.signed_distance(self.origin) / denominator;
+        if t < 0.0 {
+            return None;
+        }
+        Some(t)
+    }
+
+    // distance to where the ray enters the box (0 when it starts inside), slab method
+    pub fn intersect_aabb(&self, aabb: &Aabb) -> Option<f32> {
+        let mut near = 0.0_f32;
+        let mut far = f32::INFINITY;
+        for axis in 0..3 {
+            let (origin, direction) = (self.origin[axis], self.direction[axis]);
+            if direction.abs() < 1e-8 {
+                // parallel to these two faces, it has to be between them already
+                if origin < aabb.min[axis] || origin > aabb.max[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let a = (aabb.min[axis] - origin) / direction;
+            let b = (aabb.max[axis] - origin) / direction;
+            near = near.max(a.min(b));
+            far = far.min(a.max(b));
+            if near > far {
+                return None;
+            }
+        }
+        Some(near)
+    }
+
+    // distance to where the ray enters the sphere (0 when it starts inside)
+    pub fn intersect_sphere(&self, center: cgmath::Point3<f32>, radius: f32) -> Option<f32> {
+        let to_center = center - self.origin;
+        let along = to_center.dot(self.direction);
+        let closest_squared = to_center.magnitude2() - along * along;
+        let radius_squared = radius * radius;
+        if closest_squared > radius_squared {
+            return None;
+        }
+        let half_chord = (radius_squared - closest_squared).sqrt();
+        let (enter, exit) = (along - half_chord, along + half_chord);
+        if exit < 0.0 {
+            return None;
+        }
+        Some(enter.max(0.0))
+    }
+}
+
+// a plane as normal·p + distance = 0, the normal points to the inside of the frustum
+#[derive(Copy, Clone, Debug)]
+pub struct Plane {
+    pub normal: cgmath::Vector3<f32>,
+    pub distance: f32,
+}
+
+impl Plane {
+    // positive in front of the plane (inside), negative behind it
+    pub fn signed_distance(&self, point: cgmath::Point3<f32>) -> f32 {
+        self.normal.x * point.x + self.normal.y * point.y + self.normal.z * point.z + self.distance
+    }
+}
+
+// axis aligned bounding box, the basic volume for culling and picking
+#[derive(Copy, Clone, Debug)]
+pub struct Aabb {
+    pub min: cgmath::Point3<f32>,
+    pub max: cgmath::Point3<f32>,
+}
+
+impl Aabb {
+    pub fn new(min: cgmath::Point3<f32>, max: cgmath::Point3<f32>) -> Self {
+        Self { min, max }
+    }
+
+    pub fn from_points(points: impl IntoIterator<Item = cgmath::Point3<f32>>) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        let mut aabb = Self { min: first, max: first };
+        for p in points {
+            aabb.min = cgmath::Point3::new(aabb.min.x.min(p.x), aabb.min.y.min(p.y), aabb.min.z.min(p.z));
+            aabb.max = cgmath::Point3::new(aabb.max.x.max(p.x), aabb.max.y.max(p.y), aabb.max.z.max(p.z));
+        }
+        Some(aabb)
+    }
+
+    pub fn center(&self) -> cgmath::Point3<f32> {
+        cgmath::Point3::new((self.min.x + self.max.x) * 0.5, (self.min.y + self.max.y) * 0.5, (self.min.z + self.max.z) * 0.5)
+    }
+
+    pub fn extents(&self) -> cgmath::Vector3<f32> {
+        (self.max - self.min) * 0.5
+    }
+
+    // the box that contains this one after moving/rotating/scaling it, used to put model bounds in world space
+    pub fn transformed(&self, matrix: &cgmath::Matrix4<f32>) -> Self {
+        let corners = [
+            cgmath::Point3::new(self.min.x, self.min.y, self.min.z),
+            cgmath::Point3::new(self.max.x, self.min.y, self.min.z),
+            cgmath::Point3::new(self.min.x, self.max.y, self.min.z),
+            cgmath::Point3::new(self.max.x, self.max.y, self.min.z),
+            cgmath::Point3::new(self.min.x, self.min.y, self.max.z),
+            cgmath::Point3::new(self.max.x, self.min.y, self.max.z),
+            cgmath::Point3::new(self.min.x, self.max.y, self.max.z),
+            cgmath::Point3::new(self.max.x, self.max.y, self.max.z),
+        ];
+        Self::from_points(corners.iter().map(|c| cgmath::Transform::transform_point(matrix, *c))).unwrap()
+    }
+}
+
+// the six planes of the camera volume taken from the view projection matrix (gribb/hartmann method)
+#[derive(Clone, Debug)]
+pub struct Frustum {
+    // with an infinite projection the far plane doesn't exist, so a plane can be missing
+    pub planes: [Option<Plane>; 6],
+}
+
+impl Frustum {
+    pub fn from_matrix(view_proj: &cgmath::Matrix4<f32>) -> Self {
+        let r0 = view_proj.row(0);
+        let r1 = view_proj.row(1);
+        let r2 = view_proj.row(2);
+        let r3 = view_proj.row(3);
+
+        // wgpu depth goes from 0 to w, so near/far are r2 and r3 - r2 (with reverse z they swap, but it's the same pair)
+        let raw = [r3 + r0, r3 - r0, r3 + r1, r3 - r1, r2, r3 - r2];
+
+        let mut planes = [None; 6];
+        for (i, p) in raw.iter().enumerate() {
+            let normal = cgmath::Vector3::new(p.x, p.y, p.z);
+            let length = normal.magnitude();
+            // a zero normal means that plane is at infinity
+            if length > 1e-6 {
+                planes[i] = Some(Plane { normal: normal / length, distance: p.w / length });
+            }
+        }
+
+        Self { planes }
+    }
+
+    pub fn contains_point(&self, point: cgmath::Point3<f32>) -> bool {
+        self.planes.iter().flatten().all(|plane| plane.signed_distance(point) >= 0.0)
+    }
+
+    pub fn intersects_sphere(&self, center: cgmath::Point3<f32>, radius: f32) -> bool {
+        self.planes.iter().flatten().all(|plane| plane.signed_distance(center) >= -radius)
+    }
+
+    // conservative test, a box near a corner of the frustum can pass even if it is outside
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        let center = aabb.center();
+        let extents = aabb.extents();
+        self.planes.iter().flatten().all(|plane| {
+            let radius = extents.x * plane.normal.x.abs() + extents.y * plane.normal.y.abs() + extents.z * plane.normal.z.abs();
+            plane.signed_distance(center) >= -radius
+        })
     }
 }
 
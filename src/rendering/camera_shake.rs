@@ -0,0 +1,86 @@
+// trauma based camera shake: impacts add trauma, the shake is trauma squared so small hits barely move
+// the camera and big ones really shake it, and trauma goes down with time
+// the offsets come from noise so the movement is smooth and not a random jump every frame
+
+use cgmath::{InnerSpace, Rotation, Rotation3};
+
+use super::camera::Camera;
+use crate::util::noise::Noise;
+
+pub struct CameraShake {
+    pub trauma: f32,         // from 0 to 1
+    pub decay: f32,          // trauma lost per second
+    pub max_angle: f32,      // degrees of yaw/pitch/roll at full trauma
+    pub max_offset: f32,     // world units of translation at full trauma
+    pub frequency: f32,      // how fast the noise moves
+    time: f32,
+    noise: Noise,
+}
+
+impl CameraShake {
+    pub fn new() -> Self {
+        Self {
+            trauma: 0.0,
+            decay: 0.8,
+            max_angle: 4.0,
+            max_offset: 0.15,
+            frequency: 18.0,
+            time: 0.0,
+            noise: Noise::new(0x5eed),
+        }
+    }
+
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).clamp(0.0, 1.0);
+    }
+
+    pub fn update(&mut self, delta_time: f32) {
+        self.time += delta_time;
+        self.trauma = (self.trauma - self.decay * delta_time).max(0.0);
+    }
+
+    // the camera that goes to the gpu, the original one (the one the controller moves) is not touched
+    pub fn apply(&self, camera: &Camera) -> Camera {
+        if self.trauma <= 0.0 {
+            return *camera;
+        }
+
+        let shake = self.trauma * self.trauma;
+        let t = self.time * self.frequency;
+        // every channel reads the noise on a different row so they don't move together
+        let channel = |row: f32| self.noise.perlin2(t, row * 17.0);
+
+        let yaw = cgmath::Deg(self.max_angle * shake * channel(0.0));
+        let pitch = cgmath::Deg(self.max_angle * shake * channel(1.0));
+        let roll = cgmath::Deg(self.max_angle * shake * channel(2.0));
+        let offset = cgmath::Vector3::new(channel(3.0), channel(4.0), channel(5.0)) * self.max_offset * shake;
+
+        let forward = camera.target - camera.eye;
+        let distance = forward.magnitude();
+        if distance <= f32::EPSILON {
+            return *camera;
+        }
+        let forward = forward / distance;
+        let right = forward.cross(camera.up).normalize();
+        let up = right.cross(forward);
+
+        let rotation = cgmath::Quaternion::from_axis_angle(up, yaw)
+            * cgmath::Quaternion::from_axis_angle(right, pitch)
+            * cgmath::Quaternion::from_axis_angle(forward, roll);
+
+        let translation = right * offset.x + up * offset.y + forward * offset.z;
+        let eye = camera.eye + translation;
+
+        let mut shaken = *camera;
+        shaken.eye = eye;
+        shaken.target = eye + rotation.rotate_vector(forward) * distance;
+        shaken.up = rotation.rotate_vector(up);
+        shaken
+    }
+}
+
+impl Default for CameraShake {
+    fn default() -> Self {
+        Self::new()
+    }
+}
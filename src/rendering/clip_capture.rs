@@ -0,0 +1,263 @@
+// highlight clips: while it's on, a few times per second the post processed scene is drawn again into a small target
+// and read back, the last seconds of those frames stay in memory. when the game says something happened (trigger)
+// it keeps recording a little more and then writes the frames to captures/<event>_<time>/ as pngs, or as an mp4
+// when ffmpeg is there. the ui is not in the clips, they show the game alone
+// only the 8 bit surfaces are read, the hdr ones leave it off
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::thread::JoinHandle;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context};
+use wgpu::{Device, Queue};
+
+use super::post_process::PostProcess;
+use super::readback::{self, Readback};
+use super::textures::Texture;
+
+pub const CAPTURES_DIR: &str = "captures";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ClipFormat {
+    Images, // frame_0000.png, frame_0001.png...
+    Video,  // clip.mp4 through ffmpeg, the pngs stay if it fails
+}
+
+impl ClipFormat {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ClipFormat::Images => "images",
+            ClipFormat::Video => "video",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "images" => Some(ClipFormat::Images),
+            "video" => Some(ClipFormat::Video),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ClipSettings {
+    pub seconds: f32, // what the memory keeps before the event
+    pub after: f32,   // what it records after the event before writing it
+    pub fps: f32,
+    pub scale: u32,   // the clip is the window divided by this, 4 is 480x270 on a 1080p window
+    pub format: ClipFormat,
+}
+
+impl Default for ClipSettings {
+    fn default() -> Self {
+        Self { seconds: 10.0, after: 2.0, fps: 15.0, scale: 4, format: ClipFormat::Images }
+    }
+}
+
+struct PendingClip {
+    name: String,
+    remaining: f32,
+}
+
+pub struct ClipCapture {
+    enabled: bool,
+    settings: ClipSettings,
+    target: Option<Texture>, // None when the surface format can't be read
+    format: wgpu::TextureFormat,
+    size: (u32, u32),
+    since_frame: f32,
+    capture_this_frame: bool,
+    in_flight: VecDeque<Readback>, // in the order they were read, a frame only goes in once the ones before it did
+    frames: VecDeque<Vec<u8>>, // rgba8, the oldest first
+    pending: Vec<PendingClip>,
+    writers: Vec<JoinHandle<anyhow::Result<PathBuf>>>,
+}
+
+impl ClipCapture {
+    pub fn new(device: &Device, config: &wgpu::SurfaceConfiguration, settings: ClipSettings) -> Self {
+        let mut capture = Self {
+            enabled: false,
+            settings,
+            target: None,
+            format: config.format,
+            size: (0, 0),
+            since_frame: 0.0,
+            capture_this_frame: false,
+            in_flight: VecDeque::new(),
+            frames: VecDeque::new(),
+            pending: Vec::new(),
+            writers: Vec::new(),
+        };
+        capture.resize(device, config);
+        capture
+    }
+
+    // off drops what is in memory, the clips already being written still finish
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.frames.clear();
+            self.in_flight.clear();
+            self.pending.clear();
+            self.capture_this_frame = false;
+        }
+    }
+
+    pub fn settings(&self) -> &ClipSettings {
+        &self.settings
+    }
+
+    pub fn set_settings(&mut self, device: &Device, config: &wgpu::SurfaceConfiguration, settings: ClipSettings) {
+        let rescaled = settings.scale != self.settings.scale;
+        self.settings = settings;
+        if rescaled {
+            self.resize(device, config);
+        }
+    }
+
+    // the frames of the old size can't go in the same clip, so they are dropped
+    pub fn resize(&mut self, device: &Device, config: &wgpu::SurfaceConfiguration) {
+        self.frames.clear();
+        self.in_flight.clear();
+        self.format = config.format;
+        // even sizes, the video encoders want them
+        let scale = self.settings.scale.max(1);
+        self.size = (((config.width / scale).max(2)) & !1, ((config.height / scale).max(2)) & !1);
+        self.target = match config.format {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb | wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => {
+                Some(Texture::create_render_target(device, self.size.0, self.size.1, config.format, "clip_target"))
+            }
+            _ => None,
+        };
+    }
+
+    // the event of the game, the clip is written `after` seconds from now
+    pub fn trigger(&mut self, name: &str) {
+        if !self.enabled {
+            return;
+        }
+        if self.target.is_none() {
+            eprintln!("the clip {} is not captured, the surface format {:?} can't be read", name, self.format);
+            return;
+        }
+        self.pending.push(PendingClip { name: name.to_string(), remaining: self.settings.after });
+    }
+
+    pub fn is_capturing_this_frame(&self) -> bool {
+        self.capture_this_frame
+    }
+
+    // once per frame with the time of the game, the paused frames are not recorded
+    pub fn update(&mut self, delta_time: f32) {
+        self.finish_writers();
+        if !self.enabled || self.target.is_none() {
+            return;
+        }
+
+        let interval = 1.0 / self.settings.fps.max(1.0);
+        self.since_frame += delta_time;
+        self.capture_this_frame = delta_time > 0.0 && self.since_frame >= interval;
+        if self.capture_this_frame {
+            // a slow frame doesn't make a burst of them
+            self.since_frame %= interval;
+        }
+
+        while let Some(readback) = self.in_flight.front_mut() {
+            let Some(result) = readback.try_take() else { break };
+            self.in_flight.pop_front();
+            match result {
+                Ok(mut pixels) => {
+                    if matches!(self.format, wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb) {
+                        for pixel in pixels.chunks_exact_mut(4) {
+                            pixel.swap(0, 2);
+                        }
+                    }
+                    self.frames.push_back(pixels);
+                }
+                Err(e) => eprintln!("a frame of the clip was lost: {}", e),
+            }
+        }
+        let max_frames = ((self.settings.seconds + self.settings.after) * self.settings.fps).ceil().max(1.0) as usize;
+        while self.frames.len() > max_frames {
+            self.frames.pop_front();
+        }
+
+        for clip in &mut self.pending {
+            clip.remaining -= delta_time;
+        }
+        let (ready, waiting): (Vec<PendingClip>, Vec<PendingClip>) = self.pending.drain(..).partition(|clip| clip.remaining <= 0.0);
+        self.pending = waiting;
+        for clip in ready {
+            self.write(clip.name);
+        }
+    }
+
+    // after the post process of the frame, the same chain again into the small target
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, post_process: &PostProcess) {
+        if let (true, Some(target)) = (self.capture_this_frame, &self.target) {
+            post_process.render(encoder, &target.view);
+        }
+    }
+
+    // after the frame was submitted, the copy has to go after the draw
+    pub fn after_submit(&mut self, device: &Device, queue: &Queue) {
+        if let (true, Some(target)) = (self.capture_this_frame, &self.target) {
+            self.in_flight.push_back(readback::read_texture(device, queue, &target.texture, 4));
+        }
+    }
+
+    // the pngs are made on their own thread, the frame doesn't wait for them
+    fn write(&mut self, name: String) {
+        if self.frames.is_empty() {
+            return;
+        }
+        let frames: Vec<Vec<u8>> = self.frames.iter().cloned().collect();
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs());
+        let name: String = name.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' }).collect();
+        let directory = Path::new(CAPTURES_DIR).join(format!("{}_{}", name, time));
+        let (size, fps, format) = (self.size, self.settings.fps, self.settings.format);
+        self.writers.push(std::thread::spawn(move || write_clip(&directory, &frames, size, fps, format)));
+    }
+
+    fn finish_writers(&mut self) {
+        let (done, running): (Vec<_>, Vec<_>) = self.writers.drain(..).partition(|writer| writer.is_finished());
+        self.writers = running;
+        for writer in done {
+            match writer.join() {
+                Ok(Ok(path)) => println!("clip written to {}", path.display()),
+                Ok(Err(e)) => eprintln!("the clip couldn't be written: {:#}", e),
+                Err(_) => eprintln!("the clip writer panicked"),
+            }
+        }
+    }
+}
+
+fn write_clip(directory: &Path, frames: &[Vec<u8>], (width, height): (u32, u32), fps: f32, format: ClipFormat) -> anyhow::Result<PathBuf> {
+    std::fs::create_dir_all(directory).with_context(|| format!("failed to create {}", directory.display()))?;
+    for (index, pixels) in frames.iter().enumerate() {
+        let path = directory.join(format!("frame_{:04}.png", index));
+        image::save_buffer(&path, pixels, width, height, image::ColorType::Rgba8).with_context(|| format!("failed to write {}", path.display()))?;
+    }
+    if format == ClipFormat::Images {
+        return Ok(directory.to_path_buf());
+    }
+
+    let video = directory.with_extension("mp4");
+    let status = Command::new("ffmpeg")
+        .args(["-y", "-loglevel", "error", "-framerate", &fps.to_string(), "-i"])
+        .arg(directory.join("frame_%04d.png"))
+        .args(["-c:v", "libx264", "-pix_fmt", "yuv420p"])
+        .arg(&video)
+        .status();
+    match status {
+        Ok(status) if status.success() => {
+            std::fs::remove_dir_all(directory)?;
+            Ok(video)
+        }
+        Ok(status) => bail!("ffmpeg failed ({}), the frames are in {}", status, directory.display()),
+        Err(e) => bail!("ffmpeg couldn't run ({}), the frames are in {}", e, directory.display()),
+    }
+}
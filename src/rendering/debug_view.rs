@@ -0,0 +1,411 @@
+// the debug views: the scene without the lighting (albedo), its normals, its triangles (wireframe), the depth as a
+// distance, how many times every pixel was drawn (overdraw), how full the 2x2 quads of the gpu were and what the
+// shadow map covers. they are picked at runtime with App::set_debug_view
+// albedo, normals, wireframe and overdraw are the main pipeline compiled with a define (see depth_map.wgsl), the depth and
+// shadow views draw the normal scene, and all of them end in a resolve pass that writes the surface instead of the
+// post process, so the screen effects and the fog never change what is shown
+// the overdraw views count every shaded pixel in an OverdrawCounter, the sprites and the ui count there too
+
+use cgmath::SquareMatrix;
+use wgpu::{util::DeviceExt, Device, Queue};
+
+use super::camera::Camera;
+use super::display_output::{DisplaySettings, OutputMode};
+use super::overdraw::OverdrawCounter;
+use super::render_passes::FramePasses;
+use super::shader_preprocessor::ShaderLibrary;
+use super::shadow_map::ShadowMap;
+use super::textures::Texture;
+use crate::debug::frame_graph::{self, PassKind};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DebugView {
+    Final, // the game as it is
+    Albedo,
+    Normals,
+    Wireframe,      // the edges of the triangles, the back faces too, only where the gpu can draw lines (POLYGON_MODE_LINE)
+    Depth,          // the distance to the camera, linear up to the far distance
+    Overdraw,       // a heatmap of how many times every pixel was shaded, blue is one and red is many
+    QuadOccupancy,  // how many of the 4 pixels of each 2x2 quad were really covered, red is 1 of 4 (tiny triangles)
+    ShadowCascades, // green where the shadow map reaches, red where the scene has no shadows
+}
+
+impl DebugView {
+    pub const ALL: [DebugView; 8] = [
+        DebugView::Final,
+        DebugView::Albedo,
+        DebugView::Normals,
+        DebugView::Wireframe,
+        DebugView::Depth,
+        DebugView::Overdraw,
+        DebugView::QuadOccupancy,
+        DebugView::ShadowCascades,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DebugView::Final => "final",
+            DebugView::Albedo => "albedo",
+            DebugView::Normals => "normals",
+            DebugView::Wireframe => "wireframe",
+            DebugView::Depth => "depth",
+            DebugView::Overdraw => "overdraw",
+            DebugView::QuadOccupancy => "quad occupancy",
+            DebugView::ShadowCascades => "shadow cascades",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<DebugView> {
+        Self::ALL.iter().copied().find(|view| view.name() == name)
+    }
+
+    // the one after it, for a key that goes through all of them
+    pub fn next(&self) -> DebugView {
+        let index = Self::ALL.iter().position(|view| view == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    // the views that draw the scene with a pipeline of their own
+    pub fn replaces_scene(&self) -> bool {
+        matches!(self, DebugView::Albedo | DebugView::Normals | DebugView::Wireframe) || self.counts_overdraw()
+    }
+
+    // the views that read the overdraw counter, every pass that draws has to count into it
+    pub fn counts_overdraw(&self) -> bool {
+        matches!(self, DebugView::Overdraw | DebugView::QuadOccupancy)
+    }
+
+    // the views that need the shadow map even when nothing else uses it
+    pub fn needs_shadow_map(&self) -> bool {
+        *self == DebugView::ShadowCascades
+    }
+
+    fn resolve_mode(&self) -> f32 {
+        match self {
+            DebugView::Depth => 1.0,
+            DebugView::Overdraw => 2.0,
+            DebugView::ShadowCascades => 3.0,
+            DebugView::QuadOccupancy => 4.0,
+            _ => 0.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct DebugUniform {
+    inverse_view_proj: [[f32; 4]; 4],
+    light_view_proj: [[f32; 4]; 4],
+    eye: [f32; 4],
+    params: [f32; 4],
+    output: [f32; 4],
+}
+
+pub struct DebugViewRenderer {
+    view: DebugView,
+    pub depth_distance: f32,   // the distance that is white in the depth view, 0 uses the zfar of the camera
+    pub overdraw_layers: f32,  // how many layers are red in the overdraw view
+    output: [f32; 4],          // see DisplaySettings::output_params
+    albedo_pipeline: wgpu::RenderPipeline,
+    normals_pipeline: wgpu::RenderPipeline,
+    wireframe_pipeline: Option<wgpu::RenderPipeline>, // none when the device can't draw lines
+    overdraw_pipeline: wgpu::RenderPipeline,
+    overdraw: OverdrawCounter,
+    uniform_buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    resolve_pipeline: wgpu::RenderPipeline,
+}
+
+// what the depth, shadow and overdraw views read, the bind group is made again with them on resize
+pub struct DebugViewSources<'a> {
+    pub scene_target: &'a Texture,
+    pub depth: &'a Texture,
+    pub shadow_map: &'a ShadowMap,
+}
+
+fn texture_entry(binding: u32, sample_type: wgpu::TextureSampleType) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            multisampled: false,
+            view_dimension: wgpu::TextureViewDimension::D2,
+            sample_type,
+        },
+        count: None,
+    }
+}
+
+impl DebugViewRenderer {
+    // the scene layouts and the vertex layouts are the ones of the main pipeline, scene_format the format of the scene target
+    pub fn new(
+        device: &Device,
+        config: &wgpu::SurfaceConfiguration,
+        scene_layouts: &[&wgpu::BindGroupLayout],
+        scene_format: wgpu::TextureFormat,
+        vertex_layouts: &[wgpu::VertexBufferLayout],
+        camera: &Camera,
+        sources: &DebugViewSources,
+    ) -> Self {
+        let overdraw = OverdrawCounter::new(device, config.width, config.height);
+        let albedo_pipeline = Self::create_scene_pipeline(device, scene_layouts, scene_format, vertex_layouts, camera, "DEBUG_ALBEDO");
+        let normals_pipeline = Self::create_scene_pipeline(device, scene_layouts, scene_format, vertex_layouts, camera, "DEBUG_NORMALS");
+        let wireframe_pipeline = device
+            .features()
+            .contains(wgpu::Features::POLYGON_MODE_LINE)
+            .then(|| Self::create_scene_pipeline(device, scene_layouts, scene_format, vertex_layouts, camera, "DEBUG_WIREFRAME"));
+        // the counter goes after the groups of the scene
+        let counting_layouts: Vec<&wgpu::BindGroupLayout> = scene_layouts.iter().copied().chain(std::iter::once(overdraw.layout())).collect();
+        let overdraw_pipeline = Self::create_scene_pipeline(device, &counting_layouts, scene_format, vertex_layouts, camera, "DEBUG_OVERDRAW");
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Debug View Buffer"),
+            contents: bytemuck::cast_slice(&[<DebugUniform as bytemuck::Zeroable>::zeroed()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("debug_view_bind_group_layout"),
+            entries: &[
+                texture_entry(0, wgpu::TextureSampleType::Float { filterable: false }),
+                texture_entry(1, wgpu::TextureSampleType::Depth),
+                texture_entry(2, wgpu::TextureSampleType::Depth),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = Self::create_bind_group(device, &bind_group_layout, &uniform_buffer, sources, &overdraw);
+
+        let shader = ShaderLibrary::builtin().create_module(device, "Debug View Shader", "debug_view.wgsl", &[]);
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Debug View Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let resolve_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Debug View Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            view: DebugView::Final,
+            depth_distance: 0.0,
+            overdraw_layers: 8.0,
+            output: [0.0, 1.0, 1.0, 1.0],
+            albedo_pipeline,
+            normals_pipeline,
+            wireframe_pipeline,
+            overdraw_pipeline,
+            overdraw,
+            uniform_buffer,
+            bind_group_layout,
+            bind_group,
+            resolve_pipeline,
+        }
+    }
+
+    // the main pipeline with the define, overdraw counts every layer so it doesn't test or write the depth (nor the color)
+    // and the wireframe draws the edges of every triangle, the ones facing away too so a flipped face shows up
+    fn create_scene_pipeline(device: &Device, layouts: &[&wgpu::BindGroupLayout], format: wgpu::TextureFormat, vertex_layouts: &[wgpu::VertexBufferLayout], camera: &Camera, define: &str) -> wgpu::RenderPipeline {
+        let overdraw = define == "DEBUG_OVERDRAW";
+        let wireframe = define == "DEBUG_WIREFRAME";
+        let shader = ShaderLibrary::builtin().create_module(device, define, "depth_map.wgsl", &[define, "NORMAL_MAP"]);
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(define),
+            bind_group_layouts: layouts,
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(define),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: vertex_layouts,
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: if overdraw { wgpu::ColorWrites::empty() } else { wgpu::ColorWrites::ALL },
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: if wireframe { None } else { Some(wgpu::Face::Back) },
+                polygon_mode: if wireframe { wgpu::PolygonMode::Line } else { wgpu::PolygonMode::Fill },
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: !overdraw,
+                depth_compare: if overdraw { wgpu::CompareFunction::Always } else { camera.depth_compare() },
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    }
+
+    fn create_bind_group(
+        device: &Device,
+        layout: &wgpu::BindGroupLayout,
+        uniform_buffer: &wgpu::Buffer,
+        sources: &DebugViewSources,
+        overdraw: &OverdrawCounter,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("debug_view_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&sources.scene_target.view) },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&sources.depth.view) },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::TextureView(&sources.shadow_map.texture.view) },
+                wgpu::BindGroupEntry { binding: 3, resource: uniform_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 4, resource: overdraw.counts().as_entire_binding() },
+            ],
+        })
+    }
+
+    // the scene target and the depth are made again on resize, after the post process resized
+    pub fn resize(&mut self, device: &Device, config: &wgpu::SurfaceConfiguration, sources: &DebugViewSources) {
+        self.overdraw.resize(device, config.width, config.height);
+        self.bind_group = Self::create_bind_group(device, &self.bind_group_layout, &self.uniform_buffer, sources, &self.overdraw);
+    }
+
+    // the passes that draw in the overdraw views count here, cleared by the app before the first of them
+    pub fn overdraw_counter(&self) -> &OverdrawCounter {
+        &self.overdraw
+    }
+
+    pub fn view(&self) -> DebugView {
+        self.view
+    }
+
+    pub fn set_view(&mut self, view: DebugView) {
+        self.view = view;
+    }
+
+    // the wireframe needs a feature of the device, the rest work everywhere
+    pub fn supports(&self, view: DebugView) -> bool {
+        view != DebugView::Wireframe || self.wireframe_pipeline.is_some()
+    }
+
+    // the next view this device can show, for the key that goes through them
+    pub fn next_supported(&self, view: DebugView) -> DebugView {
+        let mut next = view.next();
+        while !self.supports(next) {
+            next = next.next();
+        }
+        next
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.view != DebugView::Final
+    }
+
+    // the views show display values like the post process does
+    pub fn set_output(&mut self, settings: &DisplaySettings, mode: OutputMode) {
+        self.output = settings.output_params(mode);
+    }
+
+    // the pipeline the main pass draws the scene with in this view, none is the normal one
+    pub fn scene_pipeline(&self) -> Option<&wgpu::RenderPipeline> {
+        match self.view {
+            DebugView::Albedo => Some(&self.albedo_pipeline),
+            DebugView::Normals => Some(&self.normals_pipeline),
+            DebugView::Wireframe => self.wireframe_pipeline.as_ref(),
+            DebugView::Overdraw | DebugView::QuadOccupancy => Some(&self.overdraw_pipeline),
+            _ => None,
+        }
+    }
+
+    pub fn update(&self, queue: &Queue, camera: &Camera, shadow_map: &ShadowMap) {
+        if !self.is_active() {
+            return;
+        }
+        let distance = if self.depth_distance > 0.0 { self.depth_distance } else { camera.zfar };
+        let uniform = DebugUniform {
+            inverse_view_proj: camera.view_projection_matrix().invert().unwrap_or(cgmath::Matrix4::identity()).into(),
+            light_view_proj: shadow_map.light_view_proj().into(),
+            eye: [camera.eye.x, camera.eye.y, camera.eye.z, camera.depth_clear_value()],
+            params: [self.view.resolve_mode(), distance.max(0.01), 0.0, self.overdraw_layers.max(1.0)],
+            output: self.output,
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    // takes the place of the post process, the target is the surface
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Debug View Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(&self.resolve_pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    // the pass of the frame in the place of the post process
+    pub fn add_pass<'a>(&'a self, frame: &mut FramePasses<'a>) {
+        frame.add(
+            "debug view",
+            PassKind::Render,
+            &[frame_graph::texture("scene_target"), frame_graph::texture("depth"), frame_graph::texture("shadow_map"), frame_graph::buffer("overdraw_counts")],
+            &[frame_graph::surface("surface")],
+            move |encoder, targets| self.render(encoder, targets.surface),
+        );
+    }
+}
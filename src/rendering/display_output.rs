@@ -0,0 +1,148 @@
+// what the final pass writes for the monitor: sdr (the first format of the surface, like always) or hdr when the
+// surface offers a float format, wgpu gives that one as extended linear srgb (scRGB) where 1.0 is 80 nits
+// the values from the calibration screen and the present mode (vsync) live here and are saved in a small key=value
+// file next to the game
+
+use std::fs;
+use std::path::Path;
+
+pub const DISPLAY_SETTINGS_PATH: &str = "display.cfg";
+
+// 1.0 in scRGB
+const SCRGB_NITS: f32 = 80.0;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OutputMode {
+    Sdr,
+    Hdr,
+}
+
+// the step of the calibration screen that is showing, off is the game
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Calibration {
+    Off,
+    Brightness, // sdr: the darkest square has to be barely visible, hdr: the paper white (how bright the ui and a white wall look)
+    Peak,       // hdr only: the inner square has to disappear in the outer one, that is where the monitor clips
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DisplaySettings {
+    pub hdr: bool, // wanted, the surface is picked when the app starts so a change applies on the next start
+    pub brightness: f32, // sdr gamma tweak, 1 leaves the image as it is and more lifts the darks
+    pub paper_white_nits: f32,
+    pub peak_nits: f32,
+    pub present_mode: wgpu::PresentMode, // the vsync, it can change while the game runs (App::set_present_mode)
+}
+
+impl Default for DisplaySettings {
+    fn default() -> Self {
+        Self { hdr: true, brightness: 1.0, paper_white_nits: 200.0, peak_nits: 1000.0, present_mode: wgpu::PresentMode::AutoNoVsync }
+    }
+}
+
+impl DisplaySettings {
+    pub const BRIGHTNESS_RANGE: (f32, f32) = (0.5, 2.0);
+    pub const PAPER_WHITE_RANGE: (f32, f32) = (80.0, 500.0);
+    pub const PEAK_RANGE: (f32, f32) = (400.0, 10000.0);
+
+    // the defaults when the file doesn't exist yet, the lines it doesn't know are skipped
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let mut settings = Self::default();
+        let Ok(text) = fs::read_to_string(path) else { return settings };
+        for line in text.lines() {
+            let Some((key, value)) = line.split_once('=') else { continue };
+            let value = value.trim();
+            match key.trim() {
+                "hdr" => settings.hdr = value == "true",
+                "brightness" => settings.brightness = value.parse().unwrap_or(settings.brightness),
+                "paper_white_nits" => settings.paper_white_nits = value.parse().unwrap_or(settings.paper_white_nits),
+                "peak_nits" => settings.peak_nits = value.parse().unwrap_or(settings.peak_nits),
+                "present_mode" => settings.present_mode = present_mode_from_name(value).unwrap_or(settings.present_mode),
+                _ => {}
+            }
+        }
+        settings.clamped()
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let text = format!(
+            "hdr={}\nbrightness={}\npaper_white_nits={}\npeak_nits={}\npresent_mode={}\n",
+            self.hdr, self.brightness, self.paper_white_nits, self.peak_nits, present_mode_name(self.present_mode)
+        );
+        fs::write(path, text)?;
+        Ok(())
+    }
+
+    pub fn clamped(mut self) -> Self {
+        self.brightness = self.brightness.clamp(Self::BRIGHTNESS_RANGE.0, Self::BRIGHTNESS_RANGE.1);
+        self.paper_white_nits = self.paper_white_nits.clamp(Self::PAPER_WHITE_RANGE.0, Self::PAPER_WHITE_RANGE.1);
+        // the peak can't be under the paper white or the whites would clip
+        self.peak_nits = self.peak_nits.clamp(Self::PEAK_RANGE.0.max(self.paper_white_nits), Self::PEAK_RANGE.1);
+        self
+    }
+
+    // the calibration screen moves the value of its step by steps (up is +1, down is -1)
+    pub fn adjust(&mut self, calibration: Calibration, mode: OutputMode, steps: f32) {
+        match (calibration, mode) {
+            (Calibration::Brightness, OutputMode::Sdr) => self.brightness += steps * 0.05,
+            (Calibration::Brightness, OutputMode::Hdr) => self.paper_white_nits += steps * 10.0,
+            (Calibration::Peak, OutputMode::Hdr) => self.peak_nits += steps * 50.0,
+            _ => {}
+        }
+        *self = self.clamped();
+    }
+
+    // what the final pass needs: x is the mode (0 sdr, 1 hdr), y what 1.0 of the scene becomes, z the sdr gamma
+    // and w the biggest value the monitor shows (both in scRGB units for hdr)
+    pub fn output_params(&self, mode: OutputMode) -> [f32; 4] {
+        match mode {
+            OutputMode::Sdr => [0.0, 1.0, 1.0 / self.brightness, 1.0],
+            OutputMode::Hdr => [1.0, self.paper_white_nits / SCRGB_NITS, 1.0, self.peak_nits / SCRGB_NITS],
+        }
+    }
+}
+
+// the format for the surface and the mode it gives, hdr only when it is wanted and the surface has a float format
+// the rest of the renderer follows config.format so the whole scene is float (no clipping over 1.0) in hdr
+pub fn pick_surface_format(formats: &[wgpu::TextureFormat], want_hdr: bool) -> (wgpu::TextureFormat, OutputMode) {
+    if want_hdr && formats.contains(&wgpu::TextureFormat::Rgba16Float) {
+        return (wgpu::TextureFormat::Rgba16Float, OutputMode::Hdr);
+    }
+    // sdr is the first one like before, but never the float one (it would be hdr without the output transform)
+    let sdr = formats.iter().copied().find(|format| *format != wgpu::TextureFormat::Rgba16Float).unwrap_or(formats[0]);
+    (sdr, OutputMode::Sdr)
+}
+
+// the present mode that is used for the wanted one: the auto ones and fifo always work, mailbox falls back to fifo
+// (it still has no tearing) and immediate to the auto one without vsync
+pub fn pick_present_mode(supported: &[wgpu::PresentMode], wanted: wgpu::PresentMode) -> wgpu::PresentMode {
+    match wanted {
+        wgpu::PresentMode::AutoVsync | wgpu::PresentMode::AutoNoVsync | wgpu::PresentMode::Fifo => wanted,
+        _ if supported.contains(&wanted) => wanted,
+        wgpu::PresentMode::Mailbox => wgpu::PresentMode::Fifo,
+        _ => wgpu::PresentMode::AutoNoVsync,
+    }
+}
+
+pub fn present_mode_name(mode: wgpu::PresentMode) -> &'static str {
+    match mode {
+        wgpu::PresentMode::AutoVsync => "auto_vsync",
+        wgpu::PresentMode::AutoNoVsync => "auto_no_vsync",
+        wgpu::PresentMode::Fifo => "fifo",
+        wgpu::PresentMode::FifoRelaxed => "fifo_relaxed",
+        wgpu::PresentMode::Immediate => "immediate",
+        wgpu::PresentMode::Mailbox => "mailbox",
+    }
+}
+
+pub fn present_mode_from_name(name: &str) -> Option<wgpu::PresentMode> {
+    match name {
+        "auto_vsync" => Some(wgpu::PresentMode::AutoVsync),
+        "auto_no_vsync" => Some(wgpu::PresentMode::AutoNoVsync),
+        "fifo" => Some(wgpu::PresentMode::Fifo),
+        "fifo_relaxed" => Some(wgpu::PresentMode::FifoRelaxed),
+        "immediate" => Some(wgpu::PresentMode::Immediate),
+        "mailbox" => Some(wgpu::PresentMode::Mailbox),
+        _ => None,
+    }
+}
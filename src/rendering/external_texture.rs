@@ -0,0 +1,119 @@
+// textures whose pixels come from the cpu every frame: video decoders, webcams, streams or procedural images
+// the source writes into a reused rgba buffer, the texture behind is double buffered so the upload goes to the one
+// that is not on screen and then they swap, materials hold the front one (see ShaderMaterial::bind_external)
+
+use std::sync::Arc;
+
+use wgpu::{Device, Queue};
+
+use super::textures::Texture;
+use crate::debug::frame_graph::{self, FrameGraph, PassKind};
+
+// where the frames come from, the size is fixed for the life of the texture
+pub trait ExternalSource {
+    fn size(&self) -> (u32, u32);
+
+    // writes the next frame as rgba8 (width * height * 4 bytes) into pixels, false if there is no new frame yet
+    // the vector is the same every call so sources can write in place or swap in one they already have
+    fn next_frame(&mut self, time: f32, pixels: &mut Vec<u8>) -> bool;
+}
+
+// a procedural texture made by a closure on the main thread, it gets the time, the pixels and the size
+pub struct ProceduralSource<F: FnMut(f32, &mut [u8], u32, u32)> {
+    size: (u32, u32),
+    generate: F,
+}
+
+impl<F: FnMut(f32, &mut [u8], u32, u32)> ProceduralSource<F> {
+    pub fn new(width: u32, height: u32, generate: F) -> Self {
+        Self { size: (width, height), generate }
+    }
+}
+
+impl<F: FnMut(f32, &mut [u8], u32, u32)> ExternalSource for ProceduralSource<F> {
+    fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    fn next_frame(&mut self, time: f32, pixels: &mut Vec<u8>) -> bool {
+        let (width, height) = self.size;
+        pixels.resize((width * height * 4) as usize, 0);
+        (self.generate)(time, pixels, width, height);
+        true
+    }
+}
+
+pub struct ExternalTexture {
+    pub label: String,
+    buffers: [Arc<Texture>; 2],
+    front: usize,
+    pixels: Vec<u8>,
+    source: Box<dyn ExternalSource>,
+    time: f32,
+    uploaded: bool, // a frame was uploaded in the last update, for the frame graph
+}
+
+impl ExternalTexture {
+    pub fn new(device: &Device, source: impl ExternalSource + 'static, label: &str) -> Self {
+        let (width, height) = source.size();
+        let buffers = [
+            Arc::new(Texture::create_dynamic(device, width, height, &format!("{} front", label))),
+            Arc::new(Texture::create_dynamic(device, width, height, &format!("{} back", label))),
+        ];
+        Self {
+            label: label.to_string(),
+            buffers,
+            front: 0,
+            pixels: vec![0; (width * height * 4) as usize],
+            source: Box::new(source),
+            time: 0.0,
+            uploaded: false,
+        }
+    }
+
+    // asks the source for a frame and uploads it to the back texture, true when the front changed
+    // a frame with the wrong number of bytes is skipped, the size can't change after the texture is made
+    pub fn update(&mut self, queue: &Queue, delta_time: f32) -> bool {
+        self.time += delta_time;
+        self.uploaded = false;
+        if !self.source.next_frame(self.time, &mut self.pixels) {
+            return false;
+        }
+        let (width, height) = self.source.size();
+        if self.pixels.len() != (width * height * 4) as usize {
+            return false;
+        }
+
+        let back = 1 - self.front;
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.buffers[back].texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &self.pixels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * width),
+                rows_per_image: Some(height),
+            },
+            wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+        );
+        self.front = back;
+        self.uploaded = true;
+        true
+    }
+
+    // the texture with the last complete frame, bind this one
+    pub fn current(&self) -> &Arc<Texture> {
+        &self.buffers[self.front]
+    }
+
+    // the upload is a copy before the passes that sample the texture, so they show up as depending on it
+    pub fn record_frame_graph(&self, graph: &mut FrameGraph) {
+        if self.uploaded {
+            graph.add_pass(&format!("upload {}", self.label), PassKind::Copy, &[frame_graph::buffer("cpu_pixels")], &[frame_graph::texture(self.label.clone())]);
+        }
+    }
+}
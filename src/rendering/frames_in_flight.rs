@@ -0,0 +1,113 @@
+// the cpu works on the next frames while the gpu still draws the older ones, at most frames_in_flight of them
+// everything that is written every frame and read by the gpu later (uniforms like the one of the camera, bind groups
+// that change per frame) has one copy per frame in flight in a FrameRing, and the pacer waits for the gpu before a copy
+// is reused
+// queue.write_buffer is already safe because wgpu copies the data, the rings are for the memory we map or fill ourselves
+
+use std::cell::RefCell;
+
+use wgpu::{Device, Queue, SubmissionIndex};
+
+pub const DEFAULT_FRAMES_IN_FLIGHT: usize = 2;
+pub const MAX_FRAMES_IN_FLIGHT: usize = 4;
+
+// which copy of the rings this frame uses
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct FrameSlot(usize);
+
+pub struct FramePacer {
+    slot: usize,
+    submissions: RefCell<Vec<Option<SubmissionIndex>>>, // the last submit of each slot, render only has &self
+}
+
+impl FramePacer {
+    // 2 is double buffering and 3 triple, more only adds latency
+    pub fn new(frames_in_flight: usize) -> Self {
+        let count = frames_in_flight.clamp(1, MAX_FRAMES_IN_FLIGHT);
+        Self { slot: 0, submissions: RefCell::new((0..count).map(|_| None).collect()) }
+    }
+
+    pub fn frames_in_flight(&self) -> usize {
+        self.submissions.borrow().len()
+    }
+
+    pub fn slot(&self) -> FrameSlot {
+        FrameSlot(self.slot)
+    }
+
+    // at the start of the frame, moves to the next slot and waits until the gpu finished the frame that used it last
+    // with enough frames in flight this returns right away, it only blocks when the cpu is that far ahead
+    pub fn begin_frame(&mut self, device: &Device) {
+        self.slot = (self.slot + 1) % self.frames_in_flight();
+        if let Some(submission) = self.submissions.borrow_mut()[self.slot].take() {
+            device.poll(wgpu::Maintain::WaitForSubmissionIndex(submission));
+        }
+    }
+
+    // the submit of this frame, the slot is free again once the gpu is past it
+    pub fn submit(&self, queue: &Queue, command_buffers: impl IntoIterator<Item = wgpu::CommandBuffer>) {
+        let submission = queue.submit(command_buffers);
+        self.submissions.borrow_mut()[self.slot] = Some(submission);
+    }
+
+    // waits for every frame in flight, before the rings are destroyed or resized
+    pub fn wait_idle(&mut self, device: &Device) {
+        for submission in self.submissions.borrow_mut().iter_mut() {
+            if let Some(submission) = submission.take() {
+                device.poll(wgpu::Maintain::WaitForSubmissionIndex(submission));
+            }
+        }
+    }
+}
+
+// one copy of something per frame in flight, made with the same count as the pacer
+pub struct FrameRing<T> {
+    items: Vec<T>,
+}
+
+impl<T> FrameRing<T> {
+    pub fn new(frames_in_flight: usize, mut make: impl FnMut(usize) -> T) -> Self {
+        Self { items: (0..frames_in_flight.max(1)).map(&mut make).collect() }
+    }
+
+    // the copy of the slot, a ring made for a different count wraps around
+    pub fn get(&self, slot: FrameSlot) -> &T {
+        &self.items[slot.0 % self.items.len()]
+    }
+}
+
+// a uniform that changes every frame: a buffer and its bind group per frame in flight
+pub struct UniformRing {
+    ring: FrameRing<(wgpu::Buffer, wgpu::BindGroup)>,
+    size: wgpu::BufferAddress,
+}
+
+impl UniformRing {
+    // the layout has the uniform at binding 0
+    pub fn new(device: &Device, layout: &wgpu::BindGroupLayout, size: wgpu::BufferAddress, frames_in_flight: usize, label: &str) -> Self {
+        let ring = FrameRing::new(frames_in_flight, |index| {
+            let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(&format!("{} {}", label, index)),
+                size,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(&format!("{} bind group {}", label, index)),
+                layout,
+                entries: &[wgpu::BindGroupEntry { binding: 0, resource: buffer.as_entire_binding() }],
+            });
+            (buffer, bind_group)
+        });
+        Self { ring, size }
+    }
+
+    pub fn write(&self, queue: &Queue, slot: FrameSlot, data: &[u8]) {
+        debug_assert!(data.len() as wgpu::BufferAddress <= self.size, "the uniform is bigger than its buffer");
+        queue.write_buffer(&self.ring.get(slot).0, 0, data);
+    }
+
+    pub fn bind_group(&self, slot: FrameSlot) -> &wgpu::BindGroup {
+        &self.ring.get(slot).1
+    }
+}
@@ -0,0 +1,102 @@
+// a grid of one unit squares on the ground, a game pass (see render_passes.rs) the demo adds while the instance brush
+// is on so it's easier to see where the dabs land, and removes when it's off
+
+use wgpu::Device;
+
+use super::camera::Camera;
+use super::planar_reflection::create_camera_layout;
+use super::render_passes::{PassSlot, PassTargets, RenderPass};
+use super::shader_preprocessor::ShaderLibrary;
+use super::textures::Texture;
+use crate::debug::frame_graph::{self, ResourceRef};
+
+pub struct GroundGrid {
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl GroundGrid {
+    pub const NAME: &'static str = "ground grid";
+
+    // the depth test is the one of the camera when it's made
+    pub fn new(device: &Device, format: wgpu::TextureFormat, camera: &Camera) -> Self {
+        let camera_layout = create_camera_layout(device);
+        let shader = ShaderLibrary::builtin().create_module(device, "Ground Grid Shader", "ground_grid.wgsl", &[]);
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Ground Grid Pipeline Layout"),
+            bind_group_layouts: &[&camera_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Ground Grid Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                cull_mode: None, // seen from under the ground too
+                ..Default::default()
+            },
+            // hidden by the scene, it doesn't hide anything
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: camera.depth_compare(),
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+        Self { pipeline }
+    }
+}
+
+impl RenderPass for GroundGrid {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn slot(&self) -> PassSlot {
+        PassSlot::AfterScene
+    }
+
+    fn reads(&self) -> Vec<ResourceRef> {
+        vec![frame_graph::buffer("camera"), frame_graph::texture("depth")]
+    }
+
+    fn writes(&self) -> Vec<ResourceRef> {
+        vec![frame_graph::texture("scene_target")]
+    }
+
+    fn encode(&self, encoder: &mut wgpu::CommandEncoder, targets: &PassTargets) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Ground Grid Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: targets.scene,
+                resolve_target: None,
+                ops: wgpu::Operations { load: wgpu::LoadOp::Load, store: wgpu::StoreOp::Store },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &targets.depth.view,
+                depth_ops: Some(wgpu::Operations { load: wgpu::LoadOp::Load, store: wgpu::StoreOp::Store }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, targets.camera, &[]);
+        render_pass.draw(0..6, 0..1);
+    }
+}
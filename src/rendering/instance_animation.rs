@@ -0,0 +1,167 @@
+// animates the instances on the gpu with a compute shader, from the instances the graph uploaded into the buffer
+// that is drawn. the cpu only sends a tiny uniform every frame, so the cost stays flat even with 100k instances
+// the animation is a function of the time alone, matrix() gives the same result on the cpu for picking and the
+// colliders. the bob goes with the phase of each instance (see InstanceVariation), so they don't all go up together
+
+use cgmath::{Matrix3, Matrix4, Rad};
+use wgpu::{util::DeviceExt, Device, Queue};
+
+use super::model::InstanceVariation;
+use super::shader_preprocessor::ShaderLibrary;
+use crate::scene::graph::Entity;
+
+const WORKGROUP_SIZE: u32 = 64;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct AnimationParams {
+    speed: f32,
+    count: u32,
+    time: f32,
+    enabled: u32,
+    bob_height: f32,
+    bob_speed: f32,
+    _padding: [u32; 2],
+}
+
+pub struct InstanceAnimator {
+    pipeline: wgpu::ComputePipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    params_buffer: wgpu::Buffer,
+    pub enabled: bool, // off draws the instances where the graph has them
+    pub speed: f32, // radians per second around the y axis
+    pub bob_height: f32, // how far they go up and down, 0 doesn't move them
+    pub bob_speed: f32, // radians per second
+    time: f32,
+}
+
+impl InstanceAnimator {
+    // the source has the instances of the graph, the shader writes the animated ones on the instance buffer
+    // (it needs the STORAGE usage)
+    pub fn new(device: &Device, source_buffer: &wgpu::Buffer, instance_buffer: &wgpu::Buffer) -> Self {
+        let shader = ShaderLibrary::builtin().create_module(device, "Instance Animation Shader", "instance_animation.wgsl", &[]);
+
+        let storage = |binding: u32, read_only: bool| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("instance_animation_bind_group_layout"),
+            entries: &[
+                storage(0, true),
+                storage(1, false),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Instance Animation Params"),
+            contents: bytemuck::cast_slice(&[AnimationParams { speed: 0.0, count: 0, time: 0.0, enabled: 0, bob_height: 0.0, bob_speed: 0.0, _padding: [0; 2] }]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group = Self::create_bind_group(device, &bind_group_layout, source_buffer, instance_buffer, &params_buffer);
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Instance Animation Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Instance Animation Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: "cs_main",
+        });
+
+        Self { pipeline, bind_group_layout, bind_group, params_buffer, enabled: true, speed: 1.0, bob_height: 0.0, bob_speed: 2.0, time: 0.0 }
+    }
+
+    // if the buffers get recreated (for example because they grew) the bind group has to point to the new ones
+    pub fn set_instance_buffers(&mut self, device: &Device, source_buffer: &wgpu::Buffer, instance_buffer: &wgpu::Buffer) {
+        self.bind_group = Self::create_bind_group(device, &self.bind_group_layout, source_buffer, instance_buffer, &self.params_buffer);
+    }
+
+    fn create_bind_group(device: &Device, layout: &wgpu::BindGroupLayout, source_buffer: &wgpu::Buffer, instance_buffer: &wgpu::Buffer, params_buffer: &wgpu::Buffer) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("instance_animation_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: source_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: instance_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: params_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    // once per frame with the time of the game, before anything asks for matrix(), it stops while it's off
+    pub fn update(&mut self, delta_time: f32) {
+        if self.enabled {
+            self.time += delta_time;
+        }
+    }
+
+    // where the instance of the graph is drawn, the same math as the shader (the scale is the one of the vertex shader)
+    pub fn matrix(&self, model: Matrix4<f32>, variation: &InstanceVariation) -> Matrix4<f32> {
+        let (angle, bob) = if self.enabled {
+            (self.speed * self.time, ((self.time + variation.phase) * self.bob_speed).sin() * self.bob_height)
+        } else {
+            (0.0, 0.0)
+        };
+        let rotation = Matrix3::from_angle_y(Rad(angle)) * Matrix3::from_cols(model.x.truncate(), model.y.truncate(), model.z.truncate()) * variation.scale;
+        Matrix4::from_cols(rotation.x.extend(model.x.w), rotation.y.extend(model.y.w), rotation.z.extend(model.z.w), model.w + cgmath::Vector4::new(0.0, bob, 0.0, 0.0))
+    }
+
+    // the entities with a model are the ones the shader moves, the rest are where the graph says
+    pub fn entity_matrix(&self, entity: &Entity) -> Matrix4<f32> {
+        match entity.model {
+            Some(_) => self.matrix(entity.world_matrix(), &entity.variation),
+            None => entity.world_matrix(),
+        }
+    }
+
+    // records the compute pass, it has to be submitted before the render pass that draws the instances
+    // it runs while the animation is off too, it is what copies the instances into the buffer that is drawn
+    pub fn animate(&self, encoder: &mut wgpu::CommandEncoder, queue: &Queue, count: u32, timestamp_writes: Option<wgpu::ComputePassTimestampWrites>) {
+        if count == 0 {
+            return;
+        }
+
+        let params = AnimationParams { speed: self.speed, count, time: self.time, enabled: self.enabled as u32, bob_height: self.bob_height, bob_speed: self.bob_speed, _padding: [0; 2] };
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[params]));
+
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Instance Animation Pass"),
+            timestamp_writes,
+        });
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, &self.bind_group, &[]);
+        compute_pass.dispatch_workgroups(count.div_ceil(WORKGROUP_SIZE), 1, 1);
+    }
+}
@@ -0,0 +1,161 @@
+// instances of one model that are spawned and despawned while the game runs (the static batch, props, debris)
+// the matrices are kept packed at the start of the buffer so a single draw covers them all, a despawn moves the
+// last one into the hole. only the range that changed since the last prepare is uploaded, and the buffer grows
+// (and shrinks back when most of it is empty) by powers of two so it is not made again every few spawns
+// every spawn takes a variation (phase, tint, scale) from the range of the manager, so the foliage and the crowds
+// painted with it don't look like copies of one another
+
+use std::ops::Range;
+
+use wgpu::{Device, Queue};
+
+use super::model::{InstanceRaw, InstanceVariation, VariationRange};
+use crate::util::pool::{Pool, PoolHandle};
+use crate::util::rng::Rng;
+
+type Matrix = [[f32; 4]; 4];
+
+// stays valid while the instance lives, even when despawns move it to another index
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct InstanceId(PoolHandle);
+
+pub struct InstanceManager {
+    label: String,
+    matrices: Vec<Matrix>,
+    variations: Vec<InstanceVariation>, // one for each matrix
+    owners: Vec<InstanceId>, // the id of the instance at each index
+    indices: Pool<usize>,    // the index of each id
+    buffer: wgpu::Buffer,
+    capacity: usize, // in instances
+    min_capacity: usize,
+    dirty: Option<Range<usize>>, // the indices that changed since the last prepare
+    pub variation: VariationRange, // what spawn gives the new instances, none by default
+    rng: Rng,
+}
+
+impl InstanceManager {
+    const INSTANCE_SIZE: usize = std::mem::size_of::<InstanceRaw>();
+
+    pub fn new(device: &Device, capacity: usize, label: &str) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            label: label.to_string(),
+            matrices: Vec::with_capacity(capacity),
+            variations: Vec::with_capacity(capacity),
+            owners: Vec::with_capacity(capacity),
+            indices: Pool::with_capacity(capacity),
+            buffer: Self::create_buffer(device, capacity, label),
+            capacity,
+            min_capacity: capacity,
+            dirty: None,
+            variation: VariationRange::default(),
+            rng: Rng::new(capacity as u64),
+        }
+    }
+
+    fn create_buffer(device: &Device, capacity: usize, label: &str) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: (capacity * Self::INSTANCE_SIZE) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn mark_dirty(&mut self, index: usize) {
+        self.dirty = Some(match self.dirty.take() {
+            Some(range) => range.start.min(index)..range.end.max(index + 1),
+            None => index..index + 1,
+        });
+    }
+
+    pub fn spawn(&mut self, matrix: impl Into<Matrix>) -> InstanceId {
+        let variation = self.variation.sample(&mut self.rng);
+        self.spawn_with(matrix, variation)
+    }
+
+    // with a variation of its own instead of one from the range
+    pub fn spawn_with(&mut self, matrix: impl Into<Matrix>, variation: InstanceVariation) -> InstanceId {
+        let index = self.matrices.len();
+        let id = InstanceId(self.indices.insert(index));
+        self.matrices.push(matrix.into());
+        self.variations.push(variation);
+        self.owners.push(id);
+        self.mark_dirty(index);
+        id
+    }
+
+    // by the index in the buffer (what picking returns), the last instance takes its place
+    pub fn despawn_at(&mut self, index: usize) -> Option<InstanceId> {
+        if index >= self.matrices.len() {
+            return None;
+        }
+        let id = self.owners.swap_remove(index);
+        self.matrices.swap_remove(index);
+        self.variations.swap_remove(index);
+        self.indices.despawn(id.0);
+        if index < self.matrices.len() {
+            let moved = self.owners[index];
+            if let Some(moved_index) = self.indices.get_mut(moved.0) {
+                *moved_index = index;
+            }
+            self.mark_dirty(index);
+        }
+        Some(id)
+    }
+
+    pub fn get(&self, id: InstanceId) -> Option<&Matrix> {
+        self.indices.get(id.0).map(|index| &self.matrices[*index])
+    }
+
+    // in the order of the buffer
+    pub fn matrices(&self) -> &[Matrix] {
+        &self.matrices
+    }
+
+    pub fn len(&self) -> usize {
+        self.matrices.len()
+    }
+
+    // the instances to draw, the buffer always starts with them
+    pub fn range(&self) -> Range<u32> {
+        0..self.matrices.len() as u32
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    // uploads what changed since the last call, once per frame before render
+    // true when the buffer was made again (it grew or shrank), whatever holds the old one has to take it again
+    pub fn prepare(&mut self, device: &Device, queue: &Queue) -> bool {
+        let len = self.matrices.len();
+        let wanted = if len > self.capacity {
+            len.next_power_of_two()
+        } else if len < self.capacity / 4 && self.capacity > self.min_capacity {
+            // half of what is left free after shrinking, so a few spawns right after don't grow it again
+            (len * 2).next_power_of_two().max(self.min_capacity)
+        } else {
+            self.capacity
+        };
+
+        let remade = wanted != self.capacity;
+        if remade {
+            self.capacity = wanted;
+            self.buffer = Self::create_buffer(device, self.capacity, &self.label);
+            // the new buffer is empty, everything goes up
+            self.dirty = if len > 0 { Some(0..len) } else { None };
+        }
+
+        if let Some(range) = self.dirty.take() {
+            let range = range.start.min(len)..range.end.min(len);
+            if !range.is_empty() {
+                let offset = (range.start * Self::INSTANCE_SIZE) as wgpu::BufferAddress;
+                // the instances of a manager are all the same model, they keep its materials
+                let instances: Vec<InstanceRaw> = self.matrices[range.clone()].iter().zip(&self.variations[range]).map(|(matrix, variation)| InstanceRaw::with_variation(*matrix, 0, variation)).collect();
+                queue.write_buffer(&self.buffer, offset, bytemuck::cast_slice(&instances));
+            }
+        }
+        remade
+    }
+}
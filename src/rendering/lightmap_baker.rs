@@ -0,0 +1,358 @@
+// the offline light of the static scenes, on the cpu: every triangle gets its own square of the lightmap (a chart, the
+// triangle fills half of it and the other half repeats its long edge so the filtering doesn't bleed), and every texel
+// traces rays from its point of the triangle: a hemisphere of them for the ambient occlusion and the sky, and one to the
+// sun for its shadow. the pankarta-bake tool (src/bake.rs) loads the scene, runs this and writes the images
+// only the geometry of the scene is used, the materials don't change the light (no bounces yet)
+
+use cgmath::{InnerSpace, Vector3};
+
+use crate::util::color::Color;
+use crate::util::rng::Rng;
+
+// the rays start this far over the surface, so they don't hit the triangle they come from
+const RAY_BIAS: f32 = 0.001;
+const LEAF_SIZE: usize = 4;
+// the texels around the triangle in its chart, the bilinear filter reads them at the edges
+const CHART_PADDING: u32 = 1;
+const MIN_CHART_SIZE: u32 = 4;
+const MAX_CHART_SIZE: u32 = 64;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Triangle {
+    pub positions: [Vector3<f32>; 3],
+    pub normals: [Vector3<f32>; 3], // zero when the file has none, the face normal is used then
+    pub mesh: usize,
+}
+
+impl Triangle {
+    fn face_normal(&self) -> Vector3<f32> {
+        let [a, b, c] = self.positions;
+        let normal = (b - a).cross(c - a);
+        if normal.magnitude2() > 0.0 { normal.normalize() } else { Vector3::unit_y() }
+    }
+
+    fn area(&self) -> f32 {
+        let [a, b, c] = self.positions;
+        (b - a).cross(c - a).magnitude() * 0.5
+    }
+
+    fn centroid(&self) -> Vector3<f32> {
+        (self.positions[0] + self.positions[1] + self.positions[2]) / 3.0
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BakeSettings {
+    pub texel_density: f32, // texels per unit along the edges of an average triangle
+    pub max_size: u32,      // of the lightmap, the charts shrink to fit in it
+    pub samples: u32,       // rays of the hemisphere per texel
+    pub ao_distance: f32,   // what is farther doesn't darken the occlusion (it still blocks the sky)
+    pub sun_direction: Vector3<f32>, // towards the sun
+    pub sun_color: Color,
+    pub sky_color: Color,
+    pub seed: u64,
+}
+
+impl Default for BakeSettings {
+    fn default() -> Self {
+        Self {
+            texel_density: 8.0,
+            max_size: 2048,
+            samples: 64,
+            ao_distance: 2.0,
+            sun_direction: Vector3::new(0.4, 1.0, 0.3).normalize(),
+            sun_color: Color::rgb(1.0, 0.95, 0.85),
+            sky_color: Color::rgb(0.35, 0.45, 0.6),
+            seed: 1,
+        }
+    }
+}
+
+// where the charts are in the lightmap, all of them the same size in a grid, in the order of the triangles
+#[derive(Clone, Debug)]
+pub struct LightmapLayout {
+    pub width: u32,
+    pub height: u32,
+    pub chart_size: u32,
+    pub columns: u32,
+}
+
+impl LightmapLayout {
+    pub fn new(triangles: &[Triangle], settings: &BakeSettings) -> Self {
+        let count = triangles.len().max(1) as u32;
+        let columns = (count as f32).sqrt().ceil() as u32;
+        let rows = count.div_ceil(columns);
+        let average_area = triangles.iter().map(Triangle::area).sum::<f32>() / count as f32;
+        // the side of a right triangle with the average area
+        let wanted = ((2.0 * average_area).sqrt() * settings.texel_density).ceil() as u32 + CHART_PADDING * 2;
+        let fits = settings.max_size / columns.max(rows);
+        let chart_size = wanted.clamp(MIN_CHART_SIZE, MAX_CHART_SIZE).min(fits).max(CHART_PADDING * 2 + 1);
+        Self { width: columns * chart_size, height: rows * chart_size, chart_size, columns }
+    }
+
+    // the corner of the chart of a triangle, in texels
+    pub fn chart_origin(&self, triangle: usize) -> (u32, u32) {
+        let triangle = triangle as u32;
+        ((triangle % self.columns) * self.chart_size, (triangle / self.columns) * self.chart_size)
+    }
+
+    // the lightmap uvs of the three corners of a triangle
+    pub fn uvs(&self, triangle: usize) -> [[f32; 2]; 3] {
+        let (x, y) = self.chart_origin(triangle);
+        let inner = (self.chart_size - CHART_PADDING * 2) as f32;
+        let (x, y) = ((x + CHART_PADDING) as f32, (y + CHART_PADDING) as f32);
+        let (width, height) = (self.width as f32, self.height as f32);
+        [[x / width, y / height], [(x + inner) / width, y / height], [x / width, (y + inner) / height]]
+    }
+
+    // the barycentric weights of the b and c corners for a texel of a chart, the half past the long edge is folded
+    // back onto it and the padding is clamped to the border
+    fn barycentric(&self, texel_x: u32, texel_y: u32) -> (f32, f32) {
+        let inner = (self.chart_size - CHART_PADDING * 2) as f32;
+        let u = ((texel_x as f32 - CHART_PADDING as f32 + 0.5) / inner).clamp(0.0, 1.0);
+        let v = ((texel_y as f32 - CHART_PADDING as f32 + 0.5) / inner).clamp(0.0, 1.0);
+        let sum = u + v;
+        if sum > 1.0 { (u / sum, v / sum) } else { (u, v) }
+    }
+}
+
+struct BvhNode {
+    min: Vector3<f32>,
+    max: Vector3<f32>,
+    start: usize, // the first triangle of a leaf, the left child of the others (the right one is the next)
+    count: usize, // 0 for the inner nodes
+}
+
+// the bounding volumes of the triangles, median splits on the longest axis
+struct Bvh {
+    nodes: Vec<BvhNode>,
+    order: Vec<usize>, // the triangles in the order of the leaves
+}
+
+impl Bvh {
+    fn new(triangles: &[Triangle]) -> Self {
+        let mut bvh = Self { nodes: Vec::new(), order: (0..triangles.len()).collect() };
+        if !triangles.is_empty() {
+            bvh.nodes.push(BvhNode { min: Vector3::new(0.0, 0.0, 0.0), max: Vector3::new(0.0, 0.0, 0.0), start: 0, count: 0 });
+            bvh.build(triangles, 0, 0, triangles.len());
+        }
+        bvh
+    }
+
+    fn build(&mut self, triangles: &[Triangle], node: usize, start: usize, end: usize) {
+        let mut min = Vector3::new(f32::MAX, f32::MAX, f32::MAX);
+        let mut max = Vector3::new(f32::MIN, f32::MIN, f32::MIN);
+        for &index in &self.order[start..end] {
+            for position in triangles[index].positions {
+                min = Vector3::new(min.x.min(position.x), min.y.min(position.y), min.z.min(position.z));
+                max = Vector3::new(max.x.max(position.x), max.y.max(position.y), max.z.max(position.z));
+            }
+        }
+        self.nodes[node].min = min;
+        self.nodes[node].max = max;
+        if end - start <= LEAF_SIZE {
+            self.nodes[node].start = start;
+            self.nodes[node].count = end - start;
+            return;
+        }
+
+        let extent = max - min;
+        let axis = if extent.x >= extent.y && extent.x >= extent.z { 0 } else if extent.y >= extent.z { 1 } else { 2 };
+        let middle = (start + end) / 2;
+        self.order[start..end].select_nth_unstable_by(middle - start, |a, b| triangles[*a].centroid()[axis].total_cmp(&triangles[*b].centroid()[axis]));
+
+        let left = self.nodes.len();
+        for _ in 0..2 {
+            self.nodes.push(BvhNode { min, max, start: 0, count: 0 });
+        }
+        self.nodes[node].start = left;
+        self.build(triangles, left, start, middle);
+        self.build(triangles, left + 1, middle, end);
+    }
+
+    // the distance to the closest triangle along the ray, both sides of the triangles block it
+    fn trace(&self, triangles: &[Triangle], origin: Vector3<f32>, direction: Vector3<f32>, max_distance: f32) -> Option<f32> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let inverse = Vector3::new(1.0 / direction.x, 1.0 / direction.y, 1.0 / direction.z);
+        let mut closest = max_distance;
+        let mut hit = false;
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !ray_hits_box(origin, inverse, node.min, node.max, closest) {
+                continue;
+            }
+            if node.count == 0 {
+                stack.push(node.start);
+                stack.push(node.start + 1);
+                continue;
+            }
+            for &triangle in &self.order[node.start..node.start + node.count] {
+                if let Some(distance) = ray_triangle(origin, direction, &triangles[triangle]) {
+                    if distance < closest {
+                        closest = distance;
+                        hit = true;
+                    }
+                }
+            }
+        }
+        hit.then_some(closest)
+    }
+}
+
+// the slab test
+fn ray_hits_box(origin: Vector3<f32>, inverse: Vector3<f32>, min: Vector3<f32>, max: Vector3<f32>, max_distance: f32) -> bool {
+    let mut near = 0.0f32;
+    let mut far = max_distance;
+    for axis in 0..3 {
+        let a = (min[axis] - origin[axis]) * inverse[axis];
+        let b = (max[axis] - origin[axis]) * inverse[axis];
+        near = near.max(a.min(b));
+        far = far.min(a.max(b));
+    }
+    near <= far
+}
+
+// moller-trumbore
+fn ray_triangle(origin: Vector3<f32>, direction: Vector3<f32>, triangle: &Triangle) -> Option<f32> {
+    let [a, b, c] = triangle.positions;
+    let edge1 = b - a;
+    let edge2 = c - a;
+    let p = direction.cross(edge2);
+    let determinant = edge1.dot(p);
+    if determinant.abs() < 1e-8 {
+        return None;
+    }
+    let inverse = 1.0 / determinant;
+    let t = origin - a;
+    let u = t.dot(p) * inverse;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = t.cross(edge1);
+    let v = direction.dot(q) * inverse;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let distance = edge2.dot(q) * inverse;
+    (distance > 0.0).then_some(distance)
+}
+
+// the light and the openness of each texel of a chart, row by row
+type ChartTexels = Vec<([f32; 3], f32)>;
+
+pub struct BakedLightmap {
+    pub layout: LightmapLayout,
+    pub light: Vec<[f32; 3]>, // linear, row by row, the sun and the sky together
+    pub occlusion: Vec<f32>,  // 1 open, 0 covered
+}
+
+impl BakedLightmap {
+    pub fn light_rgba8(&self) -> Vec<u8> {
+        self.light.iter().flat_map(|[r, g, b]| Color::rgb(*r, *g, *b).to_srgb8()).collect()
+    }
+
+    pub fn occlusion_rgba8(&self) -> Vec<u8> {
+        self.occlusion
+            .iter()
+            .flat_map(|value| {
+                let byte = (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+                [byte, byte, byte, 255]
+            })
+            .collect()
+    }
+}
+
+// the light of every chart, the triangles are split between the threads of the machine
+pub fn bake(triangles: &[Triangle], settings: &BakeSettings) -> BakedLightmap {
+    let layout = LightmapLayout::new(triangles, settings);
+    let bvh = Bvh::new(triangles);
+    let texel_count = (layout.width * layout.height) as usize;
+    let mut light = vec![[0.0; 3]; texel_count];
+    let mut occlusion = vec![1.0; texel_count];
+
+    let threads = std::thread::available_parallelism().map_or(1, |count| count.get());
+    let per_thread = (triangles.len() + threads - 1) / threads.max(1);
+    let charts: Vec<Vec<(usize, ChartTexels)>> = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|thread| {
+                let (layout, bvh) = (&layout, &bvh);
+                scope.spawn(move || {
+                    let mut rng = Rng::new(settings.seed.wrapping_add(thread as u64));
+                    let first = thread * per_thread;
+                    let last = ((thread + 1) * per_thread).min(triangles.len());
+                    (first..last).map(|index| (index, bake_chart(triangles, bvh, layout, index, settings, &mut rng))).collect()
+                })
+            })
+            .collect();
+        workers.into_iter().map(|worker| worker.join().expect("a bake thread panicked")).collect()
+    });
+
+    for (index, texels) in charts.into_iter().flatten() {
+        let (x, y) = layout.chart_origin(index);
+        for (i, (color, open)) in texels.into_iter().enumerate() {
+            let texel_x = x + i as u32 % layout.chart_size;
+            let texel_y = y + i as u32 / layout.chart_size;
+            let texel = (texel_y * layout.width + texel_x) as usize;
+            light[texel] = color;
+            occlusion[texel] = open;
+        }
+    }
+    BakedLightmap { layout, light, occlusion }
+}
+
+// uniform direction on the sphere
+fn unit_vector(rng: &mut Rng) -> Vector3<f32> {
+    let z = rng.range_f32(-1.0, 1.0);
+    let angle = rng.range_f32(0.0, std::f32::consts::TAU);
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    Vector3::new(r * angle.cos(), r * angle.sin(), z)
+}
+
+// the texels of the chart of one triangle, row by row
+fn bake_chart(triangles: &[Triangle], bvh: &Bvh, layout: &LightmapLayout, index: usize, settings: &BakeSettings, rng: &mut Rng) -> ChartTexels {
+    let triangle = &triangles[index];
+    let face_normal = triangle.face_normal();
+    let sun = settings.sun_direction.normalize();
+    let samples = settings.samples.max(1);
+    let mut texels = Vec::with_capacity((layout.chart_size * layout.chart_size) as usize);
+    for texel_y in 0..layout.chart_size {
+        for texel_x in 0..layout.chart_size {
+            let (u, v) = layout.barycentric(texel_x, texel_y);
+            let [a, b, c] = triangle.positions;
+            let position = a + (b - a) * u + (c - a) * v;
+            let [na, nb, nc] = triangle.normals;
+            let interpolated = na * (1.0 - u - v) + nb * u + nc * v;
+            let normal = if interpolated.magnitude2() > 0.0 { interpolated.normalize() } else { face_normal };
+            let origin = position + face_normal * RAY_BIAS * if face_normal.dot(normal) < 0.0 { -1.0 } else { 1.0 };
+
+            // cosine weighted: a point of the unit sphere over the normal
+            let mut open = 0;
+            let mut sky = 0;
+            for _ in 0..samples {
+                let direction = (normal + unit_vector(rng)).normalize();
+                if !direction.x.is_finite() {
+                    continue;
+                }
+                match bvh.trace(triangles, origin, direction, f32::MAX) {
+                    None => {
+                        open += 1;
+                        sky += 1;
+                    }
+                    Some(distance) if distance > settings.ao_distance => open += 1,
+                    Some(_) => {}
+                }
+            }
+            let occlusion = open as f32 / samples as f32;
+            let sky = sky as f32 / samples as f32;
+
+            let facing = normal.dot(sun).max(0.0);
+            let direct = if facing > 0.0 && bvh.trace(triangles, origin, sun, f32::MAX).is_none() { facing } else { 0.0 };
+            let color = settings.sun_color * direct + settings.sky_color * sky;
+            texels.push(([color.r, color.g, color.b], occlusion));
+        }
+    }
+    texels
+}
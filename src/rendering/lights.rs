@@ -0,0 +1,334 @@
+// the lights the game places (lamps, torches, a flashlight) on top of the sun and ambient of the sky
+// they are shaded with blinn-phong in the scene shader, the buffer rides in the sky bind group (binding 1) so every
+// pipeline that is lit by the sky gets them too without another bind group
+// the game changes the lights it wants every frame and update uploads them all
+// the forward path loops over the first MAX_LIGHTS of them in every pixel. the clustered path (r_lighting) has
+// hundreds: the view is cut in a grid of clusters (tiles of the screen times slices of the depth), every point light
+// goes in the clusters its sphere touches and a pixel only loops over the lights of its cluster. the lights and the
+// lists of the clusters are storage buffers in the same sky group (bindings 2 to 4), made on the cpu every frame
+
+use cgmath::InnerSpace;
+use wgpu::{Device, Queue};
+
+use super::camera::{Camera, Frustum, Projection};
+use super::uniforms::UniformBuffer;
+use crate::util::color::Color;
+
+// it has to match MAX_LIGHTS in common/lights.wgsl, the lights after these are ignored
+pub const MAX_LIGHTS: usize = 16;
+// the clustered path, the grid has to match common/clustered_lights.wgsl
+pub const MAX_CLUSTERED_LIGHTS: usize = 1024;
+const CLUSTERS_X: u32 = 16;
+const CLUSTERS_Y: u32 = 9;
+const CLUSTERS_Z: u32 = 24;
+const CLUSTER_COUNT: usize = (CLUSTERS_X * CLUSTERS_Y * CLUSTERS_Z) as usize;
+// the entries of all the lists together, what doesn't fit is dropped from the farthest clusters
+const MAX_LIGHT_INDICES: usize = CLUSTER_COUNT * 32;
+
+// how the lights are shaded, the "r_lighting" cvar
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LightingPath {
+    Forward,   // every light in every pixel, up to MAX_LIGHTS
+    Clustered, // the lights of the cluster of the pixel, up to MAX_CLUSTERED_LIGHTS
+}
+
+impl LightingPath {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LightingPath::Forward => "forward",
+            LightingPath::Clustered => "clustered",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "forward" => Some(LightingPath::Forward),
+            "clustered" => Some(LightingPath::Clustered),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum LightKind {
+    Directional { direction: cgmath::Vector3<f32> }, // where the light goes, like the sun
+    Point { position: cgmath::Point3<f32>, range: f32 }, // it fades to nothing at range
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Light {
+    pub kind: LightKind,
+    pub color: Color,
+    pub intensity: f32,
+    pub enabled: bool,
+}
+
+impl Light {
+    pub fn directional(direction: cgmath::Vector3<f32>, color: Color, intensity: f32) -> Self {
+        Self { kind: LightKind::Directional { direction }, color, intensity, enabled: true }
+    }
+
+    pub fn point(position: cgmath::Point3<f32>, range: f32, color: Color, intensity: f32) -> Self {
+        Self { kind: LightKind::Point { position, range }, color, intensity, enabled: true }
+    }
+
+    // a point light whose sphere is out of the view lights nothing on screen, the directional ones light everything
+    fn is_visible(&self, frustum: &Frustum) -> bool {
+        match self.kind {
+            LightKind::Directional { .. } => true,
+            LightKind::Point { position, range } => frustum.intersects_sphere(position, range),
+        }
+    }
+
+    fn to_raw(self) -> LightRaw {
+        let color = [self.color.r * self.intensity, self.color.g * self.intensity, self.color.b * self.intensity];
+        match self.kind {
+            // the shader wants the direction towards the light
+            LightKind::Directional { direction } => LightRaw { position: [-direction.x, -direction.y, -direction.z, 0.0], color: [color[0], color[1], color[2], 0.0] },
+            LightKind::Point { position, range } => LightRaw { position: [position.x, position.y, position.z, 1.0], color: [color[0], color[1], color[2], range.max(0.001)] },
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightRaw {
+    position: [f32; 4], // w 0 is a directional light (xyz is the direction to it) and 1 a point light
+    color: [f32; 4],    // times the intensity, w is the range of the point lights
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightsUniform {
+    view_position: [f32; 4], // the eye, for the highlights
+    count: u32,
+    shininess: f32,
+    specular: f32,
+    _padding: f32,
+    lights: [LightRaw; MAX_LIGHTS],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ClusterUniform {
+    eye: [f32; 4],
+    forward: [f32; 4], // the view direction, the depth of a pixel is along it
+    screen: [f32; 2],  // the size of the target in pixels
+    near: f32,         // where the first slice starts
+    log_depth_ratio: f32, // ln(far / near), the slices are exponential
+    directional_count: u32, // the first lights of the buffer, they light every cluster
+    light_count: u32,
+    _padding: [u32; 2],
+}
+
+// where the sphere of a light lands in the grid, the ranges are inclusive
+struct ClusterBounds {
+    x: (u32, u32),
+    y: (u32, u32),
+    z: (u32, u32),
+}
+
+pub struct Lights {
+    pub lights: Vec<Light>,
+    pub shininess: f32, // the exponent of the highlight, bigger is smaller and sharper
+    pub specular: f32,  // how strong the highlights are, the same for every material until they have their own
+    pub path: LightingPath,
+    uniform: UniformBuffer<LightsUniform>,
+    cluster_uniform: UniformBuffer<ClusterUniform>,
+    light_buffer: wgpu::Buffer,   // LightRaw, the directional lights first
+    cluster_buffer: wgpu::Buffer, // the offset and count of every cluster, then the indices of the lists
+    clusters_full: bool, // to say it once and not every frame
+}
+
+impl Lights {
+    pub fn new(device: &Device) -> Self {
+        let light_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Clustered Lights Buffer"),
+            size: (MAX_CLUSTERED_LIGHTS * std::mem::size_of::<LightRaw>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let cluster_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Light Clusters Buffer"),
+            size: ((CLUSTER_COUNT * 2 + MAX_LIGHT_INDICES) * std::mem::size_of::<u32>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Self {
+            lights: Vec::new(),
+            shininess: 32.0,
+            specular: 0.3,
+            path: LightingPath::Forward,
+            uniform: UniformBuffer::zeroed(device, "Lights Buffer"),
+            cluster_uniform: UniformBuffer::zeroed(device, "Light Clusters Uniform"),
+            light_buffer,
+            cluster_buffer,
+            clusters_full: false,
+        }
+    }
+
+    // the index of the light, to change it later
+    pub fn add(&mut self, light: Light) -> usize {
+        let max = match self.path {
+            LightingPath::Forward => MAX_LIGHTS,
+            LightingPath::Clustered => MAX_CLUSTERED_LIGHTS,
+        };
+        if self.lights.len() >= max {
+            eprintln!("there are more than {} lights, the last ones won't light anything", max);
+        }
+        self.lights.push(light);
+        self.lights.len() - 1
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        self.uniform.buffer()
+    }
+
+    // the bindings 2, 3 and 4 of the sky group, for the clustered path
+    pub fn cluster_uniform_buffer(&self) -> &wgpu::Buffer {
+        self.cluster_uniform.buffer()
+    }
+
+    pub fn light_buffer(&self) -> &wgpu::Buffer {
+        &self.light_buffer
+    }
+
+    pub fn cluster_buffer(&self) -> &wgpu::Buffer {
+        &self.cluster_buffer
+    }
+
+    pub fn update(&mut self, queue: &Queue, camera: &Camera, width: u32, height: u32) {
+        let mut uniform = LightsUniform {
+            view_position: [camera.eye.x, camera.eye.y, camera.eye.z, 1.0],
+            count: 0,
+            shininess: self.shininess.max(1.0),
+            specular: self.specular.max(0.0),
+            _padding: 0.0,
+            lights: [<LightRaw as bytemuck::Zeroable>::zeroed(); MAX_LIGHTS],
+        };
+        // the slots go to the lights that can touch the screen
+        let frustum = camera.frustum();
+        for light in self.lights.iter().filter(|light| light.enabled && light.is_visible(&frustum)).take(MAX_LIGHTS) {
+            uniform.lights[uniform.count as usize] = light.to_raw();
+            uniform.count += 1;
+        }
+        self.uniform.set(queue, uniform);
+        if self.path == LightingPath::Clustered {
+            self.update_clusters(queue, camera, width, height);
+        }
+    }
+
+    fn update_clusters(&mut self, queue: &Queue, camera: &Camera, width: u32, height: u32) {
+        let enabled = || self.lights.iter().filter(|light| light.enabled);
+        let directional = enabled().filter(|light| matches!(light.kind, LightKind::Directional { .. }));
+        let point = enabled().filter(|light| matches!(light.kind, LightKind::Point { .. }));
+        let lights: Vec<&Light> = directional.clone().chain(point).take(MAX_CLUSTERED_LIGHTS).collect();
+        let directional_count = directional.count().min(MAX_CLUSTERED_LIGHTS);
+
+        let near = camera.znear.max(0.01);
+        let far = camera.zfar.max(near * 2.0);
+        let forward = (camera.target - camera.eye).normalize();
+        self.cluster_uniform.set(queue, ClusterUniform {
+            eye: [camera.eye.x, camera.eye.y, camera.eye.z, 1.0],
+            forward: [forward.x, forward.y, forward.z, 0.0],
+            screen: [width.max(1) as f32, height.max(1) as f32],
+            near,
+            log_depth_ratio: (far / near).ln(),
+            directional_count: directional_count as u32,
+            light_count: lights.len() as u32,
+            _padding: [0; 2],
+        });
+        let raw: Vec<LightRaw> = lights.iter().map(|light| light.to_raw()).collect();
+        if !raw.is_empty() {
+            queue.write_buffer(&self.light_buffer, 0, bytemuck::cast_slice(&raw));
+        }
+
+        // the clusters of every point light, then the lists: counted first so each one gets its place
+        let bounds: Vec<(u32, ClusterBounds)> = lights
+            .iter()
+            .enumerate()
+            .skip(directional_count)
+            .filter_map(|(index, light)| cluster_bounds(light, camera, near, far).map(|bounds| (index as u32, bounds)))
+            .collect();
+        let cluster_index = |x: u32, y: u32, z: u32| ((z * CLUSTERS_Y + y) * CLUSTERS_X + x) as usize;
+        let mut counts = vec![0u32; CLUSTER_COUNT];
+        for (_, bounds) in &bounds {
+            for z in bounds.z.0..=bounds.z.1 {
+                for y in bounds.y.0..=bounds.y.1 {
+                    for x in bounds.x.0..=bounds.x.1 {
+                        counts[cluster_index(x, y, z)] += 1;
+                    }
+                }
+            }
+        }
+        // the near clusters first, when the lists don't fit the far ones lose their lights
+        let mut data = vec![0u32; CLUSTER_COUNT * 2];
+        let mut total = 0u32;
+        for (cluster, count) in counts.iter_mut().enumerate() {
+            *count = (*count).min(MAX_LIGHT_INDICES as u32 - total);
+            data[cluster * 2] = total;
+            total += *count;
+        }
+        let full = total as usize == MAX_LIGHT_INDICES;
+        if full && !self.clusters_full {
+            eprintln!("the light clusters are full, some lights are missing far from the camera");
+        }
+        self.clusters_full = full;
+        data.resize(CLUSTER_COUNT * 2 + total as usize, 0);
+        for (light, bounds) in &bounds {
+            for z in bounds.z.0..=bounds.z.1 {
+                for y in bounds.y.0..=bounds.y.1 {
+                    for x in bounds.x.0..=bounds.x.1 {
+                        let cluster = cluster_index(x, y, z);
+                        let filled = data[cluster * 2 + 1];
+                        if filled < counts[cluster] {
+                            let slot = CLUSTER_COUNT * 2 + (data[cluster * 2] + filled) as usize;
+                            data[slot] = *light;
+                            data[cluster * 2 + 1] += 1;
+                        }
+                    }
+                }
+            }
+        }
+        queue.write_buffer(&self.cluster_buffer, 0, bytemuck::cast_slice(&data));
+    }
+}
+
+// the clusters the sphere of a point light can touch, none when it is out of the view
+// the box around the sphere in view space is projected by its corners, x / z is the biggest and smallest at them
+fn cluster_bounds(light: &Light, camera: &Camera, near: f32, far: f32) -> Option<ClusterBounds> {
+    let LightKind::Point { position, range } = light.kind else { return None };
+    let forward = (camera.target - camera.eye).normalize();
+    let right = forward.cross(camera.up).normalize();
+    let up = right.cross(forward);
+    let offset = position - camera.eye;
+    let center = cgmath::Vector3::new(offset.dot(right), offset.dot(up), offset.dot(forward));
+    if center.z + range < near || center.z - range > far {
+        return None;
+    }
+
+    let slice = |depth: f32| (((depth.max(near) / near).ln() / (far / near).ln()) * CLUSTERS_Z as f32).floor().clamp(0.0, (CLUSTERS_Z - 1) as f32) as u32;
+    let z = (slice(center.z - range), slice(center.z + range));
+
+    let (mut min, mut max) = ((f32::MAX, f32::MAX), (f32::MIN, f32::MIN));
+    let half_height = (camera.fovy.to_radians() * 0.5).tan();
+    for corner in 0..8 {
+        let x = center.x + if corner & 1 == 0 { -range } else { range };
+        let y = center.y + if corner & 2 == 0 { -range } else { range };
+        let depth = (center.z + if corner & 4 == 0 { -range } else { range }).max(near);
+        let ndc = match camera.projection {
+            Projection::Orthographic { height } => (x / (height * 0.5 * camera.aspect), y / (height * 0.5)),
+            _ => (x / (depth * half_height * camera.aspect), y / (depth * half_height)),
+        };
+        min = (min.0.min(ndc.0), min.1.min(ndc.1));
+        max = (max.0.max(ndc.0), max.1.max(ndc.1));
+    }
+    if min.0 > 1.0 || max.0 < -1.0 || min.1 > 1.0 || max.1 < -1.0 {
+        return None;
+    }
+    // the tiles go from the top left like the pixels
+    let tile = |value: f32, count: u32| ((value * count as f32).floor().clamp(0.0, (count - 1) as f32)) as u32;
+    let x = (tile((min.0 + 1.0) * 0.5, CLUSTERS_X), tile((max.0 + 1.0) * 0.5, CLUSTERS_X));
+    let y = (tile((1.0 - max.1) * 0.5, CLUSTERS_Y), tile((1.0 - min.1) * 0.5, CLUSTERS_Y));
+    Some(ClusterBounds { x, y, z })
+}
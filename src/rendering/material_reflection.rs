@@ -0,0 +1,365 @@
+// materials for custom shaders without writing rust for each one: the shader is parsed with naga, the bindings of the
+// material group are read from it (uniform struct fields, textures, samplers) and the layout, buffer and bind group are made
+// from that, the game only sets values by name like material.params.set("tint", ParamValue::Vec4(..))
+// samplers follow the naming of the engine shaders: s_name samples the texture t_name
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail};
+use wgpu::{util::DeviceExt, Device, Queue};
+
+use super::external_texture::ExternalTexture;
+use super::shader_manager::ShaderPipelineId;
+use super::textures::Texture;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ParamKind {
+    Float,
+    Int,
+    UInt,
+    Vec2,
+    Vec3,
+    Vec4,
+    Mat4,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ParamValue {
+    Float(f32),
+    Int(i32),
+    UInt(u32),
+    Vec2([f32; 2]),
+    Vec3([f32; 3]),
+    Vec4([f32; 4]),
+    Mat4([[f32; 4]; 4]),
+}
+
+impl ParamValue {
+    pub fn kind(&self) -> ParamKind {
+        match self {
+            ParamValue::Float(_) => ParamKind::Float,
+            ParamValue::Int(_) => ParamKind::Int,
+            ParamValue::UInt(_) => ParamKind::UInt,
+            ParamValue::Vec2(_) => ParamKind::Vec2,
+            ParamValue::Vec3(_) => ParamKind::Vec3,
+            ParamValue::Vec4(_) => ParamKind::Vec4,
+            ParamValue::Mat4(_) => ParamKind::Mat4,
+        }
+    }
+
+    fn write(&self, bytes: &mut [u8]) {
+        let source: &[u8] = match self {
+            ParamValue::Float(value) => bytemuck::bytes_of(value),
+            ParamValue::Int(value) => bytemuck::bytes_of(value),
+            ParamValue::UInt(value) => bytemuck::bytes_of(value),
+            ParamValue::Vec2(value) => bytemuck::bytes_of(value),
+            ParamValue::Vec3(value) => bytemuck::bytes_of(value),
+            ParamValue::Vec4(value) => bytemuck::bytes_of(value),
+            ParamValue::Mat4(value) => bytemuck::bytes_of(value),
+        };
+        bytes[..source.len()].copy_from_slice(source);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct UniformField {
+    pub name: String,
+    pub offset: u32,
+    pub kind: ParamKind,
+}
+
+#[derive(Clone, Debug)]
+pub enum BindingKind {
+    Uniform { size: u32, fields: Vec<UniformField> },
+    Texture { dimension: wgpu::TextureViewDimension, sample_type: wgpu::TextureSampleType, multisampled: bool },
+    Sampler { comparison: bool },
+}
+
+#[derive(Clone, Debug)]
+pub struct ReflectedBinding {
+    pub binding: u32,
+    pub name: String,
+    pub kind: BindingKind,
+}
+
+// everything a shader declares in one bind group
+#[derive(Clone, Debug)]
+pub struct ShaderReflection {
+    pub bindings: Vec<ReflectedBinding>,
+}
+
+impl ShaderReflection {
+    // the source has to be already preprocessed (no #include left)
+    pub fn from_wgsl(source: &str, group: u32) -> anyhow::Result<Self> {
+        let module = naga::front::wgsl::parse_str(source).map_err(|e| anyhow!("{}", e.emit_to_string(source)))?;
+
+        let mut bindings = Vec::new();
+        for (_, variable) in module.global_variables.iter() {
+            let Some(binding) = &variable.binding else { continue };
+            if binding.group != group {
+                continue;
+            }
+            let name = variable.name.clone().unwrap_or_else(|| format!("binding_{}", binding.binding));
+            let kind = match (&variable.space, &module.types[variable.ty].inner) {
+                (naga::AddressSpace::Uniform, naga::TypeInner::Struct { members, span }) => {
+                    let mut fields = Vec::new();
+                    for member in members {
+                        let Some(kind) = param_kind(&module.types[member.ty].inner) else { continue }; // arrays and nested structs are not exposed
+                        fields.push(UniformField { name: member.name.clone().unwrap_or_default(), offset: member.offset, kind });
+                    }
+                    BindingKind::Uniform { size: *span, fields }
+                }
+                (naga::AddressSpace::Uniform, inner) => {
+                    // a uniform that is a single value, it is exposed with the name of the variable
+                    let kind = param_kind(inner).ok_or_else(|| anyhow!("the uniform {} has a type we can't expose", name))?;
+                    let size = match kind {
+                        ParamKind::Float | ParamKind::Int | ParamKind::UInt => 4,
+                        ParamKind::Vec2 => 8,
+                        ParamKind::Vec3 | ParamKind::Vec4 => 16,
+                        ParamKind::Mat4 => 64,
+                    };
+                    BindingKind::Uniform { size, fields: vec![UniformField { name: name.clone(), offset: 0, kind }] }
+                }
+                (_, naga::TypeInner::Image { dim, arrayed, class }) => {
+                    let dimension = match (dim, arrayed) {
+                        (naga::ImageDimension::D1, _) => wgpu::TextureViewDimension::D1,
+                        (naga::ImageDimension::D2, false) => wgpu::TextureViewDimension::D2,
+                        (naga::ImageDimension::D2, true) => wgpu::TextureViewDimension::D2Array,
+                        (naga::ImageDimension::D3, _) => wgpu::TextureViewDimension::D3,
+                        (naga::ImageDimension::Cube, false) => wgpu::TextureViewDimension::Cube,
+                        (naga::ImageDimension::Cube, true) => wgpu::TextureViewDimension::CubeArray,
+                    };
+                    let (sample_type, multisampled) = match class {
+                        naga::ImageClass::Sampled { kind, multi } => {
+                            let sample_type = match kind {
+                                naga::ScalarKind::Sint => wgpu::TextureSampleType::Sint,
+                                naga::ScalarKind::Uint => wgpu::TextureSampleType::Uint,
+                                _ => wgpu::TextureSampleType::Float { filterable: true },
+                            };
+                            (sample_type, *multi)
+                        }
+                        naga::ImageClass::Depth { multi } => (wgpu::TextureSampleType::Depth, *multi),
+                        naga::ImageClass::Storage { .. } => bail!("{} is a storage texture, materials only support sampled ones", name),
+                    };
+                    BindingKind::Texture { dimension, sample_type, multisampled }
+                }
+                (_, naga::TypeInner::Sampler { comparison }) => BindingKind::Sampler { comparison: *comparison },
+                _ => bail!("the binding {} of group {} is not a uniform, texture or sampler", name, group),
+            };
+            bindings.push(ReflectedBinding { binding: binding.binding, name, kind });
+        }
+        bindings.sort_by_key(|binding| binding.binding);
+
+        Ok(Self { bindings })
+    }
+
+    pub fn create_layout(&self, device: &Device, label: &str) -> wgpu::BindGroupLayout {
+        let entries: Vec<wgpu::BindGroupLayoutEntry> = self
+            .bindings
+            .iter()
+            .map(|binding| wgpu::BindGroupLayoutEntry {
+                binding: binding.binding,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: match &binding.kind {
+                    BindingKind::Uniform { .. } => wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    BindingKind::Texture { dimension, sample_type, multisampled } => wgpu::BindingType::Texture {
+                        sample_type: *sample_type,
+                        view_dimension: *dimension,
+                        multisampled: *multisampled,
+                    },
+                    BindingKind::Sampler { comparison: true } => wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                    BindingKind::Sampler { comparison: false } => wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                },
+                count: None,
+            })
+            .collect();
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor { label: Some(label), entries: &entries })
+    }
+}
+
+fn param_kind(inner: &naga::TypeInner) -> Option<ParamKind> {
+    match inner {
+        naga::TypeInner::Scalar { kind: naga::ScalarKind::Float, width: 4 } => Some(ParamKind::Float),
+        naga::TypeInner::Scalar { kind: naga::ScalarKind::Sint, width: 4 } => Some(ParamKind::Int),
+        naga::TypeInner::Scalar { kind: naga::ScalarKind::Uint, width: 4 } => Some(ParamKind::UInt),
+        naga::TypeInner::Vector { size, kind: naga::ScalarKind::Float, width: 4 } => Some(match size {
+            naga::VectorSize::Bi => ParamKind::Vec2,
+            naga::VectorSize::Tri => ParamKind::Vec3,
+            naga::VectorSize::Quad => ParamKind::Vec4,
+        }),
+        naga::TypeInner::Matrix { columns: naga::VectorSize::Quad, rows: naga::VectorSize::Quad, width: 4 } => Some(ParamKind::Mat4),
+        _ => None,
+    }
+}
+
+// the values of the material by name, they are checked against the shader when set
+#[derive(Clone, Debug, Default)]
+pub struct MaterialParams {
+    values: HashMap<String, ParamValue>,
+    kinds: HashMap<String, ParamKind>,
+    dirty: bool,
+}
+
+impl MaterialParams {
+    pub fn set(&mut self, name: &str, value: ParamValue) -> anyhow::Result<()> {
+        match self.kinds.get(name) {
+            None => bail!("the shader has no parameter called {}", name),
+            Some(kind) if *kind != value.kind() => bail!("{} is a {:?}, not a {:?}", name, kind, value.kind()),
+            Some(_) => {
+                self.values.insert(name.to_string(), value);
+                self.dirty = true;
+                Ok(())
+            }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<ParamValue> {
+        self.values.get(name).copied()
+    }
+
+    // the names and types the shader declares, for editors and debug ui
+    pub fn declared(&self) -> impl Iterator<Item = (&str, ParamKind)> {
+        self.kinds.iter().map(|(name, kind)| (name.as_str(), *kind))
+    }
+
+    pub(crate) fn declare(&mut self, fields: &[UniformField]) {
+        for field in fields {
+            self.kinds.entry(field.name.clone()).or_insert(field.kind);
+        }
+    }
+
+    // copies the values that were set into the bytes of a uniform with these fields
+    pub(crate) fn write_fields(&self, fields: &[UniformField], data: &mut [u8]) {
+        for field in fields {
+            if let Some(value) = self.values.get(&field.name) {
+                value.write(&mut data[field.offset as usize..]);
+            }
+        }
+    }
+
+    // true once after something was set, so the buffers are only written when needed
+    pub(crate) fn take_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.dirty)
+    }
+}
+
+struct UniformSlot {
+    binding: u32,
+    buffer: wgpu::Buffer,
+    data: Vec<u8>,
+    fields: Vec<UniformField>,
+}
+
+// a material made only from the reflection of its shader
+pub struct ShaderMaterial {
+    pub reflection: ShaderReflection,
+    pub layout: wgpu::BindGroupLayout,
+    pub params: MaterialParams,
+    pub pipeline: Option<ShaderPipelineId>, // made by whoever draws it, with this layout at the group of the material
+    uniforms: Vec<UniformSlot>,
+    textures: HashMap<String, Arc<Texture>>, // shared so external textures can be bound without copying them
+    fallback: Texture, // a white pixel for the textures nobody set
+    bind_group: wgpu::BindGroup,
+    bindings_dirty: bool,
+}
+
+impl ShaderMaterial {
+    pub fn new(device: &Device, queue: &Queue, source: &str, group: u32, label: &str) -> anyhow::Result<Self> {
+        let reflection = ShaderReflection::from_wgsl(source, group)?;
+        let layout = reflection.create_layout(device, label);
+
+        let mut params = MaterialParams::default();
+        let mut uniforms = Vec::new();
+        for binding in &reflection.bindings {
+            if let BindingKind::Uniform { size, fields } = &binding.kind {
+                params.declare(fields);
+                let data = vec![0u8; *size as usize];
+                let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(&format!("{} {}", label, binding.name)),
+                    contents: &data,
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                });
+                uniforms.push(UniformSlot { binding: binding.binding, buffer, data, fields: fields.clone() });
+            }
+        }
+
+        let white = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(1, 1, image::Rgba([255, 255, 255, 255])));
+        let fallback = Texture::from_image(&white, device, queue, Some("material_fallback"))?;
+
+        let bind_group = Self::create_bind_group(device, &reflection, &layout, &uniforms, &HashMap::new(), &fallback, label);
+        Ok(Self { reflection, layout, params, pipeline: None, uniforms, textures: HashMap::new(), fallback, bind_group, bindings_dirty: false })
+    }
+
+    // the texture for t_name (its sampler goes to s_name if the shader has it)
+    pub fn set_texture(&mut self, name: &str, texture: impl Into<Arc<Texture>>) -> anyhow::Result<()> {
+        let declared = self.reflection.bindings.iter().any(|binding| binding.name == name && matches!(binding.kind, BindingKind::Texture { .. }));
+        if !declared {
+            bail!("the shader has no texture called {}", name);
+        }
+        self.textures.insert(name.to_string(), texture.into());
+        self.bindings_dirty = true;
+        Ok(())
+    }
+
+    // call it every frame after the external texture updated, the bind group is only remade when its front swapped
+    pub fn bind_external(&mut self, name: &str, external: &ExternalTexture) -> anyhow::Result<()> {
+        if self.textures.get(name).is_some_and(|bound| Arc::ptr_eq(bound, external.current())) {
+            return Ok(());
+        }
+        self.set_texture(name, external.current().clone())
+    }
+
+    // writes the changed values, and remakes the bind group if a texture changed
+    pub fn update(&mut self, device: &Device, queue: &Queue) {
+        if self.params.take_dirty() {
+            for slot in &mut self.uniforms {
+                self.params.write_fields(&slot.fields, &mut slot.data);
+                queue.write_buffer(&slot.buffer, 0, &slot.data);
+            }
+        }
+        if self.bindings_dirty {
+            self.bind_group = Self::create_bind_group(device, &self.reflection, &self.layout, &self.uniforms, &self.textures, &self.fallback, "shader_material");
+            self.bindings_dirty = false;
+        }
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    fn create_bind_group(
+        device: &Device,
+        reflection: &ShaderReflection,
+        layout: &wgpu::BindGroupLayout,
+        uniforms: &[UniformSlot],
+        textures: &HashMap<String, Arc<Texture>>,
+        fallback: &Texture,
+        label: &str,
+    ) -> wgpu::BindGroup {
+        let entries: Vec<wgpu::BindGroupEntry> = reflection
+            .bindings
+            .iter()
+            .map(|binding| {
+                let resource = match &binding.kind {
+                    BindingKind::Uniform { .. } => {
+                        let slot = uniforms.iter().find(|slot| slot.binding == binding.binding).expect("every uniform binding has a slot");
+                        slot.buffer.as_entire_binding()
+                    }
+                    BindingKind::Texture { .. } => wgpu::BindingResource::TextureView(&textures.get(&binding.name).map_or(fallback, |texture| texture.as_ref()).view),
+                    BindingKind::Sampler { .. } => {
+                        let texture_name = binding.name.strip_prefix("s_").map(|name| format!("t_{}", name));
+                        let texture = texture_name.and_then(|name| textures.get(&name)).map_or(fallback, |texture| texture.as_ref());
+                        wgpu::BindingResource::Sampler(&texture.sampler)
+                    }
+                };
+                wgpu::BindGroupEntry { binding: binding.binding, resource }
+            })
+            .collect();
+        device.create_bind_group(&wgpu::BindGroupDescriptor { label: Some(label), layout, entries: &entries })
+    }
+}
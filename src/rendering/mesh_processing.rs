@@ -0,0 +1,115 @@
+// cleanup of mesh data before it goes to the gpu, for imported models and for the ones made in code
+// welding joins the vertices that are the same, the normals are rebuilt from the faces and the tangents come from mikktspace
+// (the same tangent space the bakers of normal maps use, so the maps look right)
+
+use std::collections::HashMap;
+
+use cgmath::InnerSpace;
+
+use super::model::ModelVertex;
+
+// the cell of a position at the precision of the epsilon, vertices in the same cell count as the same point
+// two points closer than the epsilon can still land on different cells, it is fine for cleaning exports
+fn position_key(position: [f32; 3], epsilon: f32) -> [i64; 3] {
+    let scale = 1.0 / epsilon.max(f32::EPSILON);
+    position.map(|value| (value * scale).round() as i64)
+}
+
+fn close(a: &[f32], b: &[f32], epsilon: f32) -> bool {
+    a.iter().zip(b).all(|(a, b)| (a - b).abs() <= epsilon)
+}
+
+// joins the vertices that have the same position, uvs and normal (within epsilon) and remaps the indices to them
+pub fn weld_vertices(vertices: &[ModelVertex], indices: &[u32], epsilon: f32) -> (Vec<ModelVertex>, Vec<u32>) {
+    let mut welded: Vec<ModelVertex> = Vec::with_capacity(vertices.len());
+    // the welded vertices of every position cell, the other attributes are compared inside the cell
+    let mut cells: HashMap<[i64; 3], Vec<u32>> = HashMap::new();
+    let mut remap = Vec::with_capacity(vertices.len());
+
+    for vertex in vertices {
+        let candidates = cells.entry(position_key(vertex.position, epsilon)).or_default();
+        let found = candidates.iter().copied().find(|index| {
+            let other = &welded[*index as usize];
+            close(&other.tex_coords, &vertex.tex_coords, epsilon) && close(&other.normal, &vertex.normal, epsilon)
+        });
+        let index = found.unwrap_or_else(|| {
+            welded.push(*vertex);
+            let index = welded.len() as u32 - 1;
+            candidates.push(index);
+            index
+        });
+        remap.push(index);
+    }
+
+    let indices = indices.iter().map(|index| remap[*index as usize]).collect();
+    (welded, indices)
+}
+
+// the normal of every vertex is the sum of the faces around it, bigger faces weight more
+// the vertices on the same position share the normal, so the seams of the uvs don't show in the lighting
+pub fn recompute_normals(vertices: &mut [ModelVertex], indices: &[u32], epsilon: f32) {
+    let mut sums: HashMap<[i64; 3], cgmath::Vector3<f32>> = HashMap::new();
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|index| cgmath::Vector3::from(vertices[index as usize].position));
+        let face = (b - a).cross(c - a);
+        for index in triangle {
+            *sums.entry(position_key(vertices[*index as usize].position, epsilon)).or_insert(cgmath::Vector3::new(0.0, 0.0, 0.0)) += face;
+        }
+    }
+
+    for vertex in vertices.iter_mut() {
+        let sum = sums.get(&position_key(vertex.position, epsilon)).copied().unwrap_or(cgmath::Vector3::new(0.0, 0.0, 0.0));
+        // a vertex that is not part of any face (or only of degenerate ones) points up
+        let normal = if sum.magnitude2() > 0.0 { sum.normalize() } else { cgmath::Vector3::unit_y() };
+        vertex.normal = normal.into();
+    }
+}
+
+// what mikktspace reads and writes, the faces are the triangles of the index list
+struct TangentGeometry<'a> {
+    vertices: &'a mut [ModelVertex],
+    indices: &'a [u32],
+}
+
+impl TangentGeometry<'_> {
+    fn vertex(&self, face: usize, vert: usize) -> &ModelVertex {
+        &self.vertices[self.indices[face * 3 + vert] as usize]
+    }
+}
+
+impl mikktspace::Geometry for TangentGeometry<'_> {
+    fn num_faces(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.vertex(face, vert).position
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.vertex(face, vert).normal
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        self.vertex(face, vert).tex_coords
+    }
+
+    // w is the sign of the bitangent, the shader builds it as cross(normal, tangent) * w
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        let index = self.indices[face * 3 + vert] as usize;
+        self.vertices[index].tangent = tangent;
+    }
+}
+
+// needs the normals and the uvs, a vertex shared by faces that want different tangents keeps the last one
+// (weld after, not before, when that matters); false when mikktspace couldn't make them
+pub fn generate_tangents(vertices: &mut [ModelVertex], indices: &[u32]) -> bool {
+    if indices.len() < 3 {
+        return false;
+    }
+    mikktspace::generate_tangents(&mut TangentGeometry { vertices, indices })
+}
@@ -1,8 +1,26 @@
 // 3D model vertexers need a position, texture coordinates and a normal.
 
-use std::{mem, ops::Range};
+use std::{collections::HashMap, mem, ops::Range, path::Path, sync::Arc};
 
+use cgmath::{InnerSpace, Matrix, SquareMatrix, Transform};
+use wgpu::util::DeviceExt;
+
+use super::material_reflection::ShaderMaterial;
+use super::mesh_processing;
+use super::shader_variants::ShaderFeatures;
+use super::camera::Aabb;
 use super::textures::Texture;
+use crate::util::color::Color as LinearColor;
+use crate::util::content_hash::{self, ContentHash, ProcessedCache};
+use crate::util::rng::Rng;
+
+// the distance under which two imported vertices are the same one
+const WELD_EPSILON: f32 = 1e-5;
+
+// what complete_vertices made, by the hash of what it got, so a big model without tangents is only processed once
+const MESH_CACHE_FOLDER: &str = "./cache/meshes";
+// one more when complete_vertices makes something different, the old entries stop matching
+const MESH_PROCESSING_VERSION: u32 = 1;
 
 pub trait Vertex {
     fn desc() -> wgpu::VertexBufferLayout<'static>;
@@ -14,6 +32,7 @@ pub struct ModelVertex {
     pub position: [f32; 3],
     pub tex_coords: [f32; 2],
     pub normal: [f32; 3],
+    pub tangent: [f32; 4], // w is the sign of the bitangent, see mesh_processing::generate_tangents
 }
 
 impl Vertex for ModelVertex {
@@ -38,6 +57,133 @@ impl Vertex for ModelVertex {
                     shader_location: 2,
                     format: wgpu::VertexFormat::Float32x3,
                 },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+            ],
+        }
+    }
+}
+
+// the per instance data of the instanced pipelines (see shaders/common/instancing.wgsl), the instance buffers of the
+// scene and of the instance managers are arrays of these
+// the material is the one that replaces the materials of the meshes: 0 keeps the ones of the model, and n is the
+// material n - 1 of the scene graph (see MaterialId::instance_index)
+// the variation fills what was the padding (the compute passes read the buffer as a storage array, its structs are
+// aligned to 16 bytes), so the instances didn't get bigger
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct InstanceRaw {
+    pub model: [[f32; 4]; 4],
+    pub material: u32,
+    pub phase: f32, // see InstanceVariation
+    pub tint: u32,  // linear rgba8, the shader reads it as 4 unorm values
+    pub scale: f32,
+}
+
+impl InstanceRaw {
+    pub fn new(model: [[f32; 4]; 4], material: u32) -> Self {
+        Self::with_variation(model, material, &InstanceVariation::default())
+    }
+
+    pub fn with_variation(model: [[f32; 4]; 4], material: u32, variation: &InstanceVariation) -> Self {
+        let tint = variation.tint.to_array().map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+        Self { model, material, phase: variation.phase, tint: u32::from_le_bytes(tint), scale: variation.scale }
+    }
+}
+
+// what makes the copies of a model in a crowd or a field of grass not look like clones, the shaders apply it
+// only the look changes: the picking and the collisions use the matrix without the scale, so keep that one small
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct InstanceVariation {
+    pub phase: f32,        // seconds added to the time of the animations of this instance
+    pub tint: LinearColor, // multiplies the color of the materials
+    pub scale: f32,        // on top of the model matrix
+}
+
+impl Default for InstanceVariation {
+    fn default() -> Self {
+        Self { phase: 0.0, tint: LinearColor::WHITE, scale: 1.0 }
+    }
+}
+
+// how far the variations made at spawn go, the default makes none
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub struct VariationRange {
+    pub phase: f32, // the phase goes from 0 to this, the period of the animation spreads them over the whole cycle
+    pub tint: f32,  // how much darker each channel can get, 0.15 gives shades of the same color
+    pub scale: f32, // plus or minus, 0.1 is between 90% and 110%
+}
+
+impl VariationRange {
+    pub fn is_none(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn sample(&self, rng: &mut Rng) -> InstanceVariation {
+        if self.is_none() {
+            return InstanceVariation::default();
+        }
+        let mut channel = || 1.0 - self.tint * rng.next_f32();
+        let tint = LinearColor::rgb(channel(), channel(), channel());
+        InstanceVariation {
+            phase: rng.range_f32(0.0, self.phase),
+            tint,
+            scale: 1.0 + rng.range_f32(-self.scale, self.scale),
+        }
+    }
+}
+
+impl Vertex for InstanceRaw {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<InstanceRaw>() as wgpu::BufferAddress, // the shader only moves to the next one with the next instance
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[
+                // a mat4 is 4 vec4s, every one is its own attribute
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                    shader_location: 6,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
+                    shader_location: 7,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 12]>() as wgpu::BufferAddress,
+                    shader_location: 8,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 16]>() as wgpu::BufferAddress,
+                    shader_location: 9,
+                    format: wgpu::VertexFormat::Uint32,
+                },
+                // the variation, the pipelines that don't read it (the shadows only take the scale) skip them
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 17]>() as wgpu::BufferAddress,
+                    shader_location: 10,
+                    format: wgpu::VertexFormat::Float32,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 18]>() as wgpu::BufferAddress,
+                    shader_location: 11,
+                    format: wgpu::VertexFormat::Unorm8x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 19]>() as wgpu::BufferAddress,
+                    shader_location: 12,
+                    format: wgpu::VertexFormat::Float32,
+                },
             ],
         }
     }
@@ -45,8 +191,11 @@ impl Vertex for ModelVertex {
 
 pub struct Material {
     pub name: String,
-    pub diffuse_texture: Texture,
+    pub diffuse_texture: Arc<Texture>, // shared with the other materials that use the same image (see TextureManager)
+    pub normal_texture: Arc<Texture>, // in tangent space, the flat one of the model when the material has none
     pub bind_group: wgpu::BindGroup,
+    pub features: ShaderFeatures, // what the material brings to the shader variant (a normal map, for example)
+    pub custom: Option<ShaderMaterial>, // materials of custom shaders, their parameters are found by reflection
 }
 
 pub struct Mesh {
@@ -54,6 +203,7 @@ pub struct Mesh {
     pub vertex_buffer: wgpu::Buffer,
     pub index_buffer: wgpu::Buffer,
     pub num_elements: u32,
+    pub bounds: Option<Aabb>, // in model space, for picking
     pub material: usize,
 }
 
@@ -62,6 +212,410 @@ pub struct Model {
     pub materials: Vec<Material>
 }
 
+impl Model {
+    // obj or gltf by the extension
+    pub fn load(path: impl AsRef<Path>, device: &wgpu::Device, queue: &wgpu::Queue, layout: &wgpu::BindGroupLayout) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        // the loaders read the files themselves, a shipped build checks them first
+        for file in Self::source_files(path) {
+            content_hash::verify_file(&file)?;
+        }
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("gltf") | Some("glb") => Self::load_gltf(path, device, queue, layout),
+            _ => Self::load_obj(path, device, queue, layout),
+        }
+    }
+
+    // the files a model is read from: itself and the mtl of an obj or the buffers of a gltf next to it
+    // the box around every mesh in model space, none for a model without vertices
+    pub fn bounds(&self) -> Option<Aabb> {
+        let corners = self.meshes.iter().filter_map(|mesh| mesh.bounds).flat_map(|bounds| [bounds.min, bounds.max]);
+        Aabb::from_points(corners)
+    }
+
+    pub fn source_files(path: impl AsRef<Path>) -> Vec<std::path::PathBuf> {
+        let path = path.as_ref();
+        let mut files = vec![path.to_path_buf()];
+        let companion = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("obj") => Some(path.with_extension("mtl")),
+            Some("gltf") => Some(path.with_extension("bin")),
+            _ => None,
+        };
+        files.extend(companion.filter(|companion| companion.exists()));
+        files
+    }
+
+    // a wavefront obj from any path, the mtl and the textures are looked for next to it
+    // the layout is the texture layout of the pipeline that draws it (texture in 0, sampler in 1)
+    pub fn load_obj(path: impl AsRef<Path>, device: &wgpu::Device, queue: &wgpu::Queue, layout: &wgpu::BindGroupLayout) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let directory = path.parent().unwrap_or(Path::new(""));
+        let label = path.display().to_string();
+
+        let (models, obj_materials) = tobj::load_obj(
+            path,
+            &tobj::LoadOptions {
+                triangulate: true,
+                single_index: true,
+                ..Default::default()
+            },
+        )?;
+
+        // a broken or missing mtl still gives a model, drawn with the white material
+        let obj_materials = obj_materials.unwrap_or_else(|e| {
+            eprintln!("the materials of {} couldn't be loaded: {}", label, e);
+            Vec::new()
+        });
+
+        let mut materials = Vec::new();
+        let flat_normal = Arc::new(Texture::flat_normal_map(device, queue)?);
+        // the materials that point to the same image share the texture
+        let mut loaded: HashMap<String, Arc<Texture>> = HashMap::new();
+        let mut loaded_normals: HashMap<String, Arc<Texture>> = HashMap::new();
+        for m in obj_materials {
+            let diffuse_texture = if m.diffuse_texture.is_empty() {
+                Arc::new(Self::white_texture(device, queue, &m.name)?)
+            } else if let Some(texture) = loaded.get(&m.diffuse_texture) {
+                texture.clone()
+            } else {
+                let bytes = std::fs::read(directory.join(&m.diffuse_texture))?;
+                let texture = Arc::new(Texture::from_bytes(&bytes, device, queue, &m.diffuse_texture)?);
+                loaded.insert(m.diffuse_texture.clone(), texture.clone());
+                texture
+            };
+            // map_Bump (or bump) in the mtl, tobj calls it the normal texture
+            if m.normal_texture.is_empty() {
+                materials.push(Material::new(device, layout, m.name, diffuse_texture, &flat_normal));
+                continue;
+            }
+            let normal_texture = match loaded_normals.get(&m.normal_texture) {
+                Some(texture) => texture.clone(),
+                None => {
+                    let image = image::open(directory.join(&m.normal_texture))?;
+                    let texture = Arc::new(Texture::normal_map_from_image(&image, device, queue, Some(&m.normal_texture))?);
+                    loaded_normals.insert(m.normal_texture.clone(), texture.clone());
+                    texture
+                }
+            };
+            materials.push(Material::with_normal_map(device, layout, m.name, diffuse_texture, normal_texture));
+        }
+        if materials.is_empty() {
+            materials.push(Material::new(device, layout, "default".to_string(), Self::white_texture(device, queue, "default")?, &flat_normal));
+        }
+
+        let meshes = models
+            .into_iter()
+            .map(|m| {
+                let mesh = m.mesh;
+                let vertex_count = mesh.positions.len() / 3;
+                // some exporters leave out the uvs or the normals
+                let has_normals = mesh.normals.len() == mesh.positions.len();
+                let mut vertices = (0..vertex_count)
+                    .map(|i| ModelVertex {
+                        position: [mesh.positions[i * 3], mesh.positions[i * 3 + 1], mesh.positions[i * 3 + 2]],
+                        tex_coords: if mesh.texcoords.len() >= (i + 1) * 2 { [mesh.texcoords[i * 2], 1.0 - mesh.texcoords[i * 2 + 1]] } else { [0.0, 0.0] },
+                        normal: if has_normals { [mesh.normals[i * 3], mesh.normals[i * 3 + 1], mesh.normals[i * 3 + 2]] } else { [0.0; 3] },
+                        tangent: [1.0, 0.0, 0.0, 1.0],
+                    })
+                    .collect::<Vec<_>>();
+                let mut indices = mesh.indices;
+                complete_vertices(&mut vertices, &mut indices, has_normals, false);
+
+                let material = mesh.material_id.filter(|id| *id < materials.len()).unwrap_or(0);
+                Mesh::new(device, &m.name, &vertices, &indices, material)
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Self { meshes, materials })
+    }
+
+    // a gltf or glb (the binary one), with the meshes of the default scene placed by their nodes
+    // the transforms of the nodes are baked into the vertices, a mesh used by two nodes is loaded twice
+    // the materials keep the base color (texture times factor) and the normal map, the other pbr values have nowhere to go yet
+    pub fn load_gltf(path: impl AsRef<Path>, device: &wgpu::Device, queue: &wgpu::Queue, layout: &wgpu::BindGroupLayout) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let (document, buffers, images) = gltf::import(path)?;
+
+        let mut materials = Vec::new();
+        let flat_normal = Arc::new(Texture::flat_normal_map(device, queue)?);
+        for material in document.materials() {
+            let name = material.name().map_or_else(|| format!("material {}", materials.len()), str::to_string);
+            let pbr = material.pbr_metallic_roughness();
+            let factor = LinearColor::from(pbr.base_color_factor());
+            let texture = pbr
+                .base_color_texture()
+                .and_then(|info| images.get(info.texture().source().index()))
+                .and_then(gltf_image_to_rgba);
+            let diffuse_texture = match texture {
+                Some(mut image) => {
+                    // the factor is linear, the pixels are srgb
+                    if factor.to_array() != [1.0; 4] {
+                        for pixel in image.pixels_mut() {
+                            let [r, g, b, a] = pixel.0;
+                            pixel.0 = (LinearColor::from_srgb8(r, g, b, a) * factor).to_srgb8();
+                        }
+                    }
+                    Texture::from_image(&image::DynamicImage::ImageRgba8(image), device, queue, Some(&name))?
+                }
+                None => Self::solid_texture(device, queue, &name, factor)?,
+            };
+            // the gltf normal maps are in the same tangent space mikktspace makes, the scale of the spec is ignored
+            let normal_image = material
+                .normal_texture()
+                .and_then(|info| images.get(info.texture().source().index()))
+                .and_then(gltf_image_to_rgba);
+            let material = match normal_image {
+                Some(image) => {
+                    let normal_texture = Texture::normal_map_from_image(&image::DynamicImage::ImageRgba8(image), device, queue, Some(&name))?;
+                    Material::with_normal_map(device, layout, name, diffuse_texture, normal_texture)
+                }
+                None => Material::new(device, layout, name, diffuse_texture, &flat_normal),
+            };
+            materials.push(material);
+        }
+        // the primitives without a material use the last one, like the default material of the spec
+        let default_material = materials.len();
+        materials.push(Material::new(device, layout, "default".to_string(), Self::solid_texture(device, queue, "default", LinearColor::WHITE)?, &flat_normal));
+
+        let mut meshes = Vec::new();
+        let scene = document.default_scene().or_else(|| document.scenes().next()).ok_or_else(|| anyhow::anyhow!("{} has no scenes", path.display()))?;
+        let mut stack: Vec<(gltf::Node, cgmath::Matrix4<f32>)> = scene.nodes().map(|node| (node, cgmath::Matrix4::identity())).collect();
+        while let Some((node, parent)) = stack.pop() {
+            let transform = parent * cgmath::Matrix4::from(node.transform().matrix());
+            stack.extend(node.children().map(|child| (child, transform)));
+
+            let Some(mesh) = node.mesh() else { continue };
+            // the normals go with the inverse transpose, and a mirrored node flips the winding
+            let normal_matrix = transform.invert().map_or(transform, |inverse| inverse.transpose());
+            let mirrored = transform.determinant() < 0.0;
+
+            for primitive in mesh.primitives() {
+                if primitive.mode() != gltf::mesh::Mode::Triangles {
+                    continue;
+                }
+                let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+                let Some(positions) = reader.read_positions() else { continue };
+                let positions: Vec<[f32; 3]> = positions.collect();
+                let normals: Option<Vec<[f32; 3]>> = reader.read_normals().map(Iterator::collect);
+                let tangents: Option<Vec<[f32; 4]>> = reader.read_tangents().map(Iterator::collect);
+                let tex_coords: Option<Vec<[f32; 2]>> = reader.read_tex_coords(0).map(|coords| coords.into_f32().collect());
+
+                let mut vertices = positions
+                    .iter()
+                    .enumerate()
+                    .map(|(i, position)| {
+                        let position = transform.transform_point(cgmath::Point3::from(*position));
+                        let normal = normals.as_ref().map_or(cgmath::Vector3::new(0.0, 0.0, 0.0), |normals| {
+                            normal_matrix.transform_vector(cgmath::Vector3::from(normals[i])).normalize()
+                        });
+                        let tangent = tangents.as_ref().map_or([1.0, 0.0, 0.0, 1.0], |tangents| {
+                            let [x, y, z, w] = tangents[i];
+                            let direction = transform.transform_vector(cgmath::Vector3::new(x, y, z)).normalize();
+                            [direction.x, direction.y, direction.z, if mirrored { -w } else { w }]
+                        });
+                        ModelVertex {
+                            position: position.into(),
+                            tex_coords: tex_coords.as_ref().map_or([0.0, 0.0], |coords| coords[i]), // gltf already has the origin on the top left
+                            normal: normal.into(),
+                            tangent,
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                let mut indices: Vec<u32> = reader.read_indices().map_or_else(|| (0..vertices.len() as u32).collect(), |indices| indices.into_u32().collect());
+                if mirrored {
+                    for triangle in indices.chunks_exact_mut(3) {
+                        triangle.swap(1, 2);
+                    }
+                }
+                complete_vertices(&mut vertices, &mut indices, normals.is_some(), tangents.is_some());
+
+                let name = format!("{} {}", mesh.name().unwrap_or("mesh"), primitive.index());
+                let material = primitive.material().index().unwrap_or(default_material);
+                meshes.push(Mesh::new(device, &name, &vertices, &indices, material));
+            }
+        }
+
+        Ok(Self { meshes, materials })
+    }
+
+    fn white_texture(device: &wgpu::Device, queue: &wgpu::Queue, label: &str) -> anyhow::Result<Texture> {
+        Self::solid_texture(device, queue, label, LinearColor::WHITE)
+    }
+
+    fn solid_texture(device: &wgpu::Device, queue: &wgpu::Queue, label: &str, color: LinearColor) -> anyhow::Result<Texture> {
+        let image = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(1, 1, image::Rgba(color.to_srgb8())));
+        Texture::from_image(&image, device, queue, Some(label))
+    }
+}
+
+// the 8 bit formats of the gltf images, the rest (16 and 32 bit) is not used for base colors
+fn gltf_image_to_rgba(data: &gltf::image::Data) -> Option<image::RgbaImage> {
+    use gltf::image::Format;
+    let pixels: Vec<u8> = match data.format {
+        Format::R8G8B8A8 => data.pixels.clone(),
+        Format::R8G8B8 => data.pixels.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], 255]).collect(),
+        Format::R8G8 => data.pixels.chunks_exact(2).flat_map(|p| [p[0], p[0], p[0], p[1]]).collect(),
+        Format::R8 => data.pixels.iter().flat_map(|p| [*p, *p, *p, 255]).collect(),
+        _ => return None,
+    };
+    image::RgbaImage::from_raw(data.width, data.height, pixels)
+}
+
+// fills in what the file didn't have
+fn complete_vertices(vertices: &mut Vec<ModelVertex>, indices: &mut Vec<u32>, has_normals: bool, has_tangents: bool) {
+    if has_normals && has_tangents {
+        return;
+    }
+    let cache = ProcessedCache::new(MESH_CACHE_FOLDER);
+    let flags = [has_normals as u8, has_tangents as u8];
+    let key = ContentHash::of_parts(&[&MESH_PROCESSING_VERSION.to_le_bytes(), &flags, bytemuck::cast_slice(vertices), bytemuck::cast_slice(indices)]);
+    if let Some((cached_vertices, cached_indices)) = cache.get(&key).and_then(|payload| decode_processed_mesh(&payload)) {
+        (*vertices, *indices) = (cached_vertices, cached_indices);
+        return;
+    }
+
+    // without normals every face is its own vertices, welding first lets the rebuilt normals be smooth
+    if !has_normals {
+        (*vertices, *indices) = mesh_processing::weld_vertices(vertices, indices, WELD_EPSILON);
+        mesh_processing::recompute_normals(vertices, indices, WELD_EPSILON);
+    }
+    mesh_processing::generate_tangents(vertices, indices);
+
+    // a cache that can't be written (a read only install) only means processing again next time
+    if let Err(e) = cache.put(&key, &encode_processed_mesh(vertices, indices)) {
+        eprintln!("the processed mesh was not cached: {}", e);
+    }
+}
+
+// the vertex count and then the vertices and the indices as they go to the gpu
+fn encode_processed_mesh(vertices: &[ModelVertex], indices: &[u32]) -> Vec<u8> {
+    let mut bytes = (vertices.len() as u32).to_le_bytes().to_vec();
+    bytes.extend_from_slice(bytemuck::cast_slice(vertices));
+    bytes.extend_from_slice(bytemuck::cast_slice(indices));
+    bytes
+}
+
+fn decode_processed_mesh(bytes: &[u8]) -> Option<(Vec<ModelVertex>, Vec<u32>)> {
+    let count = u32::from_le_bytes(bytes.get(..4)?.try_into().ok()?) as usize;
+    let vertex_bytes = bytes.get(4..4 + count * mem::size_of::<ModelVertex>())?;
+    let index_bytes = &bytes[4 + vertex_bytes.len()..];
+    if !index_bytes.len().is_multiple_of(mem::size_of::<u32>()) {
+        return None;
+    }
+    // the payload is not aligned for the vertices, they are copied out
+    Some((bytemuck::pod_collect_to_vec(vertex_bytes), bytemuck::pod_collect_to_vec(index_bytes)))
+}
+
+impl Mesh {
+    // uploads the vertices as they are, procedural meshes go through mesh_processing before if they need it
+    pub fn new(device: &wgpu::Device, name: &str, vertices: &[ModelVertex], indices: &[u32], material: usize) -> Self {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{} Vertex Buffer", name)),
+            contents: bytemuck::cast_slice(vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{} Index Buffer", name)),
+            contents: bytemuck::cast_slice(indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        Self {
+            name: name.to_string(),
+            vertex_buffer,
+            index_buffer,
+            num_elements: indices.len() as u32,
+            bounds: Aabb::from_points(vertices.iter().map(|vertex| cgmath::Point3::from(vertex.position))),
+            material,
+        }
+    }
+}
+
+impl Material {
+    // a material with only the diffuse texture, bound with the texture layout of the pipeline
+    // the texture can be an owned one or one shared from the TextureManager, the flat normal map goes in the slot of the
+    // normal map (Texture::flat_normal_map, one for every material of the model is enough)
+    pub fn new(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, name: String, diffuse_texture: impl Into<Arc<Texture>>, flat_normal: &Arc<Texture>) -> Self {
+        let diffuse_texture = diffuse_texture.into();
+        let bind_group = Self::create_bind_group(device, layout, &name, &diffuse_texture, flat_normal);
+
+        Self {
+            name,
+            diffuse_texture,
+            normal_texture: flat_normal.clone(),
+            bind_group,
+            features: ShaderFeatures::NONE,
+            custom: None,
+        }
+    }
+
+    // a material of a custom shader, drawn with its own bind group where its pipeline is used (see draw_by_material)
+    // the standard bind group is a white texture for the passes that draw every material the same (shadows, debug views)
+    pub fn from_shader(device: &wgpu::Device, queue: &wgpu::Queue, layout: &wgpu::BindGroupLayout, name: String, custom: ShaderMaterial) -> anyhow::Result<Self> {
+        let flat_normal = Arc::new(Texture::flat_normal_map(device, queue)?);
+        let mut material = Self::new(device, layout, name.clone(), Model::white_texture(device, queue, &name)?, &flat_normal);
+        material.custom = Some(custom);
+        Ok(material)
+    }
+
+    // the normal map has to be made with Texture::normal_map_from_image, the srgb curve would bend the normals
+    pub fn with_normal_map(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, name: String, diffuse_texture: impl Into<Arc<Texture>>, normal_texture: impl Into<Arc<Texture>>) -> Self {
+        let normal_texture = normal_texture.into();
+        let mut material = Self::new(device, layout, name, diffuse_texture, &normal_texture);
+        material.features.insert(ShaderFeatures::NORMAL_MAP);
+        material
+    }
+
+    // the diffuse texture at 0 and 1, the normal map at 2 and 3, like texture_bind_group_layout
+    fn create_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, name: &str, diffuse_texture: &Texture, normal_texture: &Texture) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&diffuse_texture.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&normal_texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&normal_texture.sampler),
+                },
+            ],
+            label: Some(&format!("{} Material", name)),
+        })
+    }
+
+    // the bind group for the scene pipeline of the material, the one of the custom shader when it has its pipeline
+    pub fn scene_bind_group(&self) -> &wgpu::BindGroup {
+        match &self.custom {
+            Some(custom) if custom.pipeline.is_some() => custom.bind_group(),
+            _ => &self.bind_group,
+        }
+    }
+
+    // swaps the diffuse texture (it was reloaded), the layout has to be the one the material was made with
+    pub fn set_diffuse_texture(&mut self, device: &wgpu::Device, layout: &wgpu::BindGroupLayout, diffuse_texture: Arc<Texture>) {
+        self.bind_group = Self::create_bind_group(device, layout, &self.name, &diffuse_texture, &self.normal_texture);
+        self.diffuse_texture = diffuse_texture;
+    }
+}
+
+// one instanced draw of a model, the instances are a range of the buffer in the slot 1
+// the material replaces the ones of the meshes when there is one
+pub struct InstancedDraw<'a> {
+    pub model: &'a Model,
+    pub material: Option<&'a Material>,
+    pub buffer: &'a wgpu::Buffer,
+    pub instances: Range<u32>,
+}
+
 pub trait DrawModel<'a> {
     // these will let me only draw one shape of our model
     fn draw_mesh(&mut self, mesh: &'a Mesh, material: &'a Material, camera_bind_group: &'a wgpu::BindGroup);
@@ -81,6 +635,7 @@ pub trait DrawModel<'a> {
         instances: Range<u32>,
         camera_bind_group: &'a wgpu::BindGroup,
     );
+    fn draw_instanced(&mut self, draw: &InstancedDraw<'a>, camera_bind_group: &'a wgpu::BindGroup);
 }
 impl<'a, 'b> DrawModel<'b> for wgpu::RenderPass<'a>
 where
@@ -108,4 +663,16 @@ where
             self.draw_mesh_instanced(mesh, material, instances.clone(), camera_bind_group);
         }
     }
+
+    fn draw_instanced(&mut self, draw: &InstancedDraw<'b>, camera_bind_group: &'b wgpu::BindGroup) {
+        if draw.instances.is_empty() {
+            return;
+        }
+        let model: &'b Model = draw.model;
+        self.set_vertex_buffer(1, draw.buffer.slice(..));
+        for mesh in &model.meshes {
+            let material = draw.material.unwrap_or(&model.materials[mesh.material]);
+            self.draw_mesh_instanced(mesh, material, draw.instances.clone(), camera_bind_group);
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,93 @@
+// how many times every pixel was shaded this frame, for the overdraw and quad occupancy debug views
+// the shaders compiled with COUNT_OVERDRAW add 1 to their pixel in a storage buffer (see common/overdraw.wgsl), so the
+// scene, the sprites (particles are the usual problem) and the ui all count, even the parts that are blended away
+
+use wgpu::{util::DeviceExt, Device};
+
+pub struct OverdrawCounter {
+    counts: wgpu::Buffer, // one u32 per pixel, row by row
+    _size_buffer: wgpu::Buffer, // only read through the bind group
+    layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    size: (u32, u32),
+}
+
+impl OverdrawCounter {
+    pub fn new(device: &Device, width: u32, height: u32) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("overdraw_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let (counts, size_buffer, bind_group) = Self::create_buffers(device, &layout, width, height);
+        Self { counts, _size_buffer: size_buffer, layout, bind_group, size: (width, height) }
+    }
+
+    fn create_buffers(device: &Device, layout: &wgpu::BindGroupLayout, width: u32, height: u32) -> (wgpu::Buffer, wgpu::Buffer, wgpu::BindGroup) {
+        let (width, height) = (width.max(1), height.max(1));
+        let counts = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Overdraw Counts"),
+            size: (width as u64) * (height as u64) * std::mem::size_of::<u32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let size_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Overdraw Size"),
+            contents: bytemuck::cast_slice(&[width, height, 0, 0]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("overdraw_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: counts.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: size_buffer.as_entire_binding() },
+            ],
+        });
+        (counts, size_buffer, bind_group)
+    }
+
+    pub fn resize(&mut self, device: &Device, width: u32, height: u32) {
+        (self.counts, self._size_buffer, self.bind_group) = Self::create_buffers(device, &self.layout, width, height);
+        self.size = (width, height);
+    }
+
+    // the layout the counting pipelines put at the group their shader declares
+    pub fn layout(&self) -> &wgpu::BindGroupLayout {
+        &self.layout
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    // for the resolve, it only reads them
+    pub fn counts(&self) -> &wgpu::Buffer {
+        &self.counts
+    }
+
+    // before the first pass that counts
+    pub fn clear(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.clear_buffer(&self.counts, 0, None);
+    }
+}
@@ -0,0 +1,112 @@
+// parallax backgrounds for 2D scenes: every layer follows the camera only a fraction of what it moves, so far away
+// layers look slower, they are queued as normal sprites on very low layers so they are drawn before everything else
+
+use cgmath::Vector2;
+
+use super::sprite::{AtlasHandle, AtlasRegion, Sprite, SpriteRenderer};
+use crate::util::color::Color;
+
+// the first parallax layer goes here and the next ones one above, far below the layers the game uses
+pub const PARALLAX_LAYER_BASE: i32 = -10_000;
+
+#[derive(Copy, Clone, Debug)]
+pub struct ParallaxLayer {
+    pub atlas: AtlasHandle,
+    pub region: AtlasRegion,
+    pub size: Vector2<f32>,   // world size of one copy of the image
+    pub offset: Vector2<f32>, // top left of the image when the camera is at the origin
+    // how much of the camera movement the layer follows: 0 is stuck to the screen (the sky), 1 moves like the world
+    pub factor: Vector2<f32>,
+    pub scroll_speed: Vector2<f32>, // extra movement on its own, in world units per second (clouds, fog)
+    pub repeat_x: bool,
+    pub repeat_y: bool,
+    pub color: Color,
+    scroll: Vector2<f32>,
+}
+
+impl ParallaxLayer {
+    pub fn new(atlas: AtlasHandle, region: AtlasRegion, size: Vector2<f32>, factor: f32) -> Self {
+        Self {
+            atlas,
+            region,
+            size,
+            offset: Vector2::new(0.0, 0.0),
+            factor: Vector2::new(factor, factor),
+            scroll_speed: Vector2::new(0.0, 0.0),
+            repeat_x: true,
+            repeat_y: false,
+            color: Color::WHITE,
+            scroll: Vector2::new(0.0, 0.0),
+        }
+    }
+}
+
+pub struct ParallaxBackground {
+    pub layers: Vec<ParallaxLayer>, // the first one is the farthest
+    pub enabled: bool,
+}
+
+impl ParallaxBackground {
+    pub fn new() -> Self {
+        Self { layers: Vec::new(), enabled: true }
+    }
+
+    pub fn update(&mut self, delta_time: f32) {
+        for layer in &mut self.layers {
+            layer.scroll += layer.scroll_speed * delta_time;
+            // keep the scroll small so the floats don't lose precision after a long time
+            if layer.repeat_x && layer.size.x > 0.0 {
+                layer.scroll.x %= layer.size.x;
+            }
+            if layer.repeat_y && layer.size.y > 0.0 {
+                layer.scroll.y %= layer.size.y;
+            }
+        }
+    }
+
+    // queues the copies of every layer that are on screen, call it every frame before prepare
+    pub fn draw(&self, sprites: &mut SpriteRenderer) {
+        if !self.enabled {
+            return;
+        }
+        let camera = sprites.camera;
+        let zoom = camera.zoom.max(f32::EPSILON);
+        let half_view = Vector2::new(camera.viewport_width * 0.5 / zoom, camera.viewport_height * 0.5 / zoom);
+        let view_min = camera.position - half_view;
+        let view_max = camera.position + half_view;
+
+        for (index, layer) in self.layers.iter().enumerate() {
+            if layer.size.x <= 0.0 || layer.size.y <= 0.0 {
+                continue;
+            }
+            // the part of the camera movement the layer doesn't follow is added to it
+            let origin = Vector2::new(
+                layer.offset.x + camera.position.x * (1.0 - layer.factor.x) + layer.scroll.x,
+                layer.offset.y + camera.position.y * (1.0 - layer.factor.y) + layer.scroll.y,
+            );
+
+            let (first_x, last_x) = if layer.repeat_x { copies(origin.x, layer.size.x, view_min.x, view_max.x) } else { (0, 0) };
+            let (first_y, last_y) = if layer.repeat_y { copies(origin.y, layer.size.y, view_min.y, view_max.y) } else { (0, 0) };
+
+            for y in first_y..=last_y {
+                for x in first_x..=last_x {
+                    let position = Vector2::new(
+                        origin.x + (x as f32 + 0.5) * layer.size.x,
+                        origin.y + (y as f32 + 0.5) * layer.size.y,
+                    );
+                    let mut sprite = Sprite::new(layer.atlas, layer.region, position, layer.size);
+                    sprite.color = layer.color;
+                    sprite.layer = PARALLAX_LAYER_BASE + index as i32;
+                    sprites.draw(sprite);
+                }
+            }
+        }
+    }
+}
+
+// the indices of the first and last copy of a repeating image that touch the view
+fn copies(origin: f32, size: f32, view_min: f32, view_max: f32) -> (i32, i32) {
+    let first = ((view_min - origin) / size).floor() as i32;
+    let last = ((view_max - origin) / size).floor() as i32;
+    (first, last.max(first))
+}
@@ -0,0 +1,351 @@
+// the particles live on the gpu: every emitter has a storage buffer with room for all of its particles and a compute
+// pass moves them every frame (gravity, drag, age), the cpu only sends a uniform with how many to spawn and where
+// the buffer is a ring, the new particles take the slots after the last ones so the oldest are the ones replaced
+// they are drawn in the main pass as camera facing quads, one instance per slot, the dead ones collapse to nothing
+// the size and the color over the life are a curve and a gradient, sampled into the uniform every frame
+
+use std::f32::consts::PI;
+use std::mem;
+
+use cgmath::{EuclideanSpace, InnerSpace, Point3, Transform, Vector3};
+use wgpu::{Device, Queue};
+
+use super::camera::Camera;
+use super::planar_reflection::create_camera_layout;
+use super::shader_preprocessor::ShaderLibrary;
+use super::textures::Texture;
+use crate::scene::graph::{EntityId, SceneGraph};
+use crate::util::color::Color;
+use crate::util::curve::{Curve, Gradient};
+use crate::util::pool::{Pool, PoolHandle};
+
+const WORKGROUP_SIZE: u32 = 64;
+const LIFE_SAMPLES: usize = 16; // of the size and the color, the shader mixes between them
+
+// the same as Particle in common/particles.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ParticleRaw {
+    position: [f32; 4], // w is the age
+    velocity: [f32; 4], // w is the lifetime, a particle is dead once the age gets there (all zeros at the start)
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct EmitterUniform {
+    origin: [f32; 4],    // w is the spread
+    direction: [f32; 4], // w is the drag
+    gravity: [f32; 4],   // w is the delta time
+    camera_right: [f32; 4], // w is not used
+    camera_up: [f32; 4],    // w is not used
+    speed: [f32; 2],
+    lifetime: [f32; 2],
+    spawn_start: u32, // the first slot of the new ones
+    spawn_count: u32,
+    capacity: u32,
+    seed: u32,
+    radius: f32, // of the sphere they spawn in
+    _padding: [f32; 3],
+    sizes: [[f32; 4]; LIFE_SAMPLES / 4], // four in each vector, the arrays of a uniform go in steps of 16 bytes
+    colors: [[f32; 4]; LIFE_SAMPLES],
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EmitterSettings {
+    pub rate: f32,     // particles per second while it emits
+    pub capacity: u32, // the most alive at once, only read when the emitter is spawned
+    pub lifetime: (f32, f32), // seconds, each one gets a random value between them
+    pub speed: (f32, f32),
+    pub direction: Vector3<f32>, // where they go, in world space
+    pub spread: f32,   // radians around the direction, PI is every direction
+    pub radius: f32,   // they start anywhere in a sphere this big around the emitter
+    pub gravity: Vector3<f32>,
+    pub drag: f32,     // how fast they lose the speed, 0 keeps it
+    pub size: Curve,      // from the birth (0) to the death (1) of each particle
+    pub color: Gradient,  // the same
+}
+
+impl Default for EmitterSettings {
+    fn default() -> Self {
+        Self {
+            rate: 50.0,
+            capacity: 1024,
+            lifetime: (1.0, 2.0),
+            speed: (1.0, 3.0),
+            direction: Vector3::unit_y(),
+            spread: 0.4,
+            radius: 0.0,
+            gravity: Vector3::new(0.0, -9.8, 0.0),
+            drag: 0.0,
+            size: Curve::linear(0.2, 0.0),
+            color: Gradient::two_colors(Color::WHITE, Color::WHITE.with_alpha(0.0)),
+        }
+    }
+}
+
+// where the particles come from
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum EmitterAnchor {
+    Point(Point3<f32>),
+    Entity { entity: EntityId, offset: Vector3<f32> }, // the offset turns with the entity
+}
+
+pub struct Emitter {
+    pub settings: EmitterSettings,
+    pub anchor: EmitterAnchor,
+    emitting: bool, // false lets the ones alive finish, then the emitter goes away
+    accumulated: f32, // the fraction of a particle the rate left for the next frame
+    burst: u32,
+    head: u32, // the slot the next particle takes
+    stopped_for: f32,
+    capacity: u32,
+    _particle_buffer: wgpu::Buffer, // only used through the bind groups
+    uniform_buffer: wgpu::Buffer,
+    compute_bind_group: wgpu::BindGroup,
+    render_bind_group: wgpu::BindGroup,
+}
+
+impl Emitter {
+    fn origin(&self, graph: &SceneGraph) -> Option<Point3<f32>> {
+        match self.anchor {
+            EmitterAnchor::Point(point) => Some(point),
+            EmitterAnchor::Entity { entity, offset } => graph.get(entity).map(|entity| entity.world_matrix().transform_point(Point3::from_vec(offset))),
+        }
+    }
+}
+
+pub struct ParticleSystem {
+    emitters: Pool<Emitter>,
+    compute_pipeline: wgpu::ComputePipeline,
+    render_pipeline: wgpu::RenderPipeline,
+    compute_layout: wgpu::BindGroupLayout,
+    render_layout: wgpu::BindGroupLayout,
+    frame: u32, // the seed of the spawns changes every frame
+}
+
+impl ParticleSystem {
+    // the main pass draws into the scene target with the depth of the main camera
+    pub fn new(device: &Device, format: wgpu::TextureFormat, main_camera: &Camera) -> Self {
+        let buffer_entry = |binding: u32, visibility: wgpu::ShaderStages, ty: wgpu::BufferBindingType| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer { ty, has_dynamic_offset: false, min_binding_size: None },
+            count: None,
+        };
+        let compute_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("particle_compute_bind_group_layout"),
+            entries: &[
+                buffer_entry(0, wgpu::ShaderStages::COMPUTE, wgpu::BufferBindingType::Storage { read_only: false }),
+                buffer_entry(1, wgpu::ShaderStages::COMPUTE, wgpu::BufferBindingType::Uniform),
+            ],
+        });
+        let render_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("particle_render_bind_group_layout"),
+            entries: &[
+                buffer_entry(0, wgpu::ShaderStages::VERTEX, wgpu::BufferBindingType::Storage { read_only: true }),
+                buffer_entry(1, wgpu::ShaderStages::VERTEX, wgpu::BufferBindingType::Uniform),
+            ],
+        });
+
+        let library = ShaderLibrary::builtin();
+        let simulation_shader = library.create_module(device, "Particle Simulation Shader", "particle_simulation.wgsl", &[]);
+        let shader = library.create_module(device, "Particle Shader", "particles.wgsl", &[]);
+        let compute_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Particle Compute Pipeline Layout"),
+            bind_group_layouts: &[&compute_layout],
+            push_constant_ranges: &[],
+        });
+        let compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Particle Compute Pipeline"),
+            layout: Some(&compute_pipeline_layout),
+            module: &simulation_shader,
+            entry_point: "cs_main",
+        });
+
+        let camera_layout = create_camera_layout(device);
+        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Particle Render Pipeline Layout"),
+            bind_group_layouts: &[&camera_layout, &render_layout],
+            push_constant_ranges: &[],
+        });
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Particle Render Pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[], // the quads come from the vertex and instance indices
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                cull_mode: None,
+                ..Default::default()
+            },
+            // hidden by the scene but they don't hide each other, like the trails
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: main_camera.depth_compare(),
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self { emitters: Pool::new(), compute_pipeline, render_pipeline, compute_layout, render_layout, frame: 0 }
+    }
+
+    pub fn spawn(&mut self, device: &Device, settings: EmitterSettings, anchor: EmitterAnchor) -> PoolHandle {
+        let capacity = settings.capacity.max(1);
+        // zeros are dead particles
+        let particle_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Particle Buffer"),
+            size: (capacity as usize * mem::size_of::<ParticleRaw>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Particle Emitter Buffer"),
+            size: mem::size_of::<EmitterUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = |label: &str, layout: &wgpu::BindGroupLayout| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(label),
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry { binding: 0, resource: particle_buffer.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 1, resource: uniform_buffer.as_entire_binding() },
+                ],
+            })
+        };
+        let compute_bind_group = bind_group("particle_compute_bind_group", &self.compute_layout);
+        let render_bind_group = bind_group("particle_render_bind_group", &self.render_layout);
+
+        let emitter = Emitter {
+            settings,
+            anchor,
+            emitting: true,
+            accumulated: 0.0,
+            burst: 0,
+            head: 0,
+            stopped_for: 0.0,
+            capacity,
+            _particle_buffer: particle_buffer,
+            uniform_buffer,
+            compute_bind_group,
+            render_bind_group,
+        };
+        self.emitters.insert(emitter)
+    }
+
+    // this many at once on the next frame, on top of the rate (an explosion, the sparks of a hit)
+    pub fn burst(&mut self, handle: PoolHandle, count: u32) {
+        if let Some(emitter) = self.emitters.get_mut(handle) {
+            emitter.burst += count;
+        }
+    }
+
+    // no new ones, it goes away when the last one dies and the handle stops working then
+    pub fn stop(&mut self, handle: PoolHandle) {
+        if let Some(emitter) = self.emitters.get_mut(handle) {
+            emitter.emitting = false;
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.emitters.iter().next().is_some()
+    }
+
+    // how many spawn this frame and where, the emitters of despawned entities stop there
+    // the world transforms of the graph have to be updated
+    pub fn update(&mut self, queue: &Queue, delta_time: f32, camera: &Camera, graph: &SceneGraph) {
+        self.frame = self.frame.wrapping_add(1);
+        let frame = self.frame;
+        let forward = (camera.target - camera.eye).normalize();
+        let right = forward.cross(camera.up).normalize();
+        let up = right.cross(forward);
+        self.emitters.retain(|emitter| {
+            let origin = emitter.origin(graph);
+            if origin.is_none() {
+                emitter.emitting = false;
+            }
+            let settings = &emitter.settings;
+            let mut spawn_count = 0;
+            if emitter.emitting {
+                emitter.accumulated += settings.rate.max(0.0) * delta_time;
+                spawn_count = emitter.accumulated.floor() as u32;
+                emitter.accumulated -= spawn_count as f32;
+            } else {
+                emitter.stopped_for += delta_time;
+            }
+            spawn_count = (spawn_count + mem::take(&mut emitter.burst)).min(emitter.capacity);
+            let spawn_start = emitter.head;
+            emitter.head = (emitter.head + spawn_count) % emitter.capacity;
+
+            let origin = origin.unwrap_or(Point3::new(0.0, 0.0, 0.0));
+            let direction = if settings.direction.magnitude2() > 0.0 { settings.direction.normalize() } else { Vector3::unit_y() };
+            let life = |sample: usize| sample as f32 / (LIFE_SAMPLES - 1) as f32;
+            let mut sizes = [[0.0; 4]; LIFE_SAMPLES / 4];
+            for sample in 0..LIFE_SAMPLES {
+                sizes[sample / 4][sample % 4] = settings.size.evaluate(life(sample)).max(0.0);
+            }
+            let colors = std::array::from_fn(|sample| settings.color.evaluate(life(sample)).to_array());
+            let uniform = EmitterUniform {
+                origin: [origin.x, origin.y, origin.z, settings.spread.clamp(0.0, PI)],
+                direction: [direction.x, direction.y, direction.z, settings.drag.max(0.0)],
+                gravity: [settings.gravity.x, settings.gravity.y, settings.gravity.z, delta_time],
+                camera_right: [right.x, right.y, right.z, 0.0],
+                camera_up: [up.x, up.y, up.z, 0.0],
+                speed: [settings.speed.0, settings.speed.1],
+                lifetime: [settings.lifetime.0.max(0.0), settings.lifetime.1.max(settings.lifetime.0).max(0.0)],
+                spawn_start,
+                spawn_count,
+                capacity: emitter.capacity,
+                seed: frame,
+                radius: settings.radius.max(0.0),
+                _padding: [0.0; 3],
+                sizes,
+                colors,
+            };
+            queue.write_buffer(&emitter.uniform_buffer, 0, bytemuck::bytes_of(&uniform));
+            emitter.emitting || emitter.stopped_for < settings.lifetime.0.max(settings.lifetime.1)
+        });
+    }
+
+    // before the main pass, in the same encoder
+    pub fn simulate(&self, encoder: &mut wgpu::CommandEncoder, timestamp_writes: Option<wgpu::ComputePassTimestampWrites>) {
+        if !self.is_active() {
+            return;
+        }
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("Particle Pass"), timestamp_writes });
+        compute_pass.set_pipeline(&self.compute_pipeline);
+        for (_, emitter) in self.emitters.iter() {
+            compute_pass.set_bind_group(0, &emitter.compute_bind_group, &[]);
+            compute_pass.dispatch_workgroups(emitter.capacity.div_ceil(WORKGROUP_SIZE), 1, 1);
+        }
+    }
+
+    // inside the main pass, after everything opaque, six vertices for every slot
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, camera_bind_group: &'a wgpu::BindGroup) {
+        if !self.is_active() {
+            return;
+        }
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        for (_, emitter) in self.emitters.iter() {
+            render_pass.set_bind_group(1, &emitter.render_bind_group, &[]);
+            render_pass.draw(0..6, 0..emitter.capacity);
+        }
+    }
+}
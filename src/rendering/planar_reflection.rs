@@ -0,0 +1,422 @@
+// mirrors: the scene is rendered again from the camera reflected about the plane of each mirror into its own target,
+// then the mirror quad projects its points with that camera to read it
+// the projection of the reflected camera has its near plane moved onto the mirror (oblique clipping, Lengyel), so what
+// is behind the mirror doesn't show up in the reflection
+
+use cgmath::{InnerSpace, Matrix, SquareMatrix};
+use wgpu::{util::DeviceExt, Device, Queue};
+
+use super::camera::{Camera, CameraUniform, Plane, Projection};
+use super::model::{DrawModel, InstancedDraw};
+use super::shader_preprocessor::ShaderLibrary;
+use super::textures::Texture;
+
+// how far behind the mirror the clip plane goes, so the things touching it are not cut
+pub(crate) const CLIP_OFFSET: f32 = 0.01;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct MirrorUniform {
+    reflection_view_proj: [[f32; 4]; 4],
+    center: [f32; 4],
+    axis_u: [f32; 4],
+    axis_v: [f32; 4],
+    normal: [f32; 4],
+    camera_position: [f32; 4],
+    tint: [f32; 4],
+}
+
+pub struct Mirror {
+    pub center: cgmath::Point3<f32>,
+    pub normal: cgmath::Vector3<f32>, // the side that reflects, normalized
+    pub half_size: cgmath::Vector2<f32>,
+    pub tint: [f32; 3],
+    pub reflectivity: f32,
+    facing_camera: bool, // seen from behind there is nothing to render
+    target: Texture,
+    depth: Texture,
+    camera_buffer: wgpu::Buffer,
+    camera_bind_group: wgpu::BindGroup,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl Mirror {
+    pub fn plane(&self) -> Plane {
+        Plane { normal: self.normal, distance: -self.normal.dot(cgmath::Vector3::new(self.center.x, self.center.y, self.center.z)) }
+    }
+
+    // the two half axes of the quad
+    fn axes(&self) -> (cgmath::Vector3<f32>, cgmath::Vector3<f32>) {
+        let (u, v) = surface_axes(self.normal);
+        (u * self.half_size.x, v * self.half_size.y)
+    }
+}
+
+// the unit axes along a flat surface facing normal, u is horizontal unless it faces up or down
+pub(crate) fn surface_axes(normal: cgmath::Vector3<f32>) -> (cgmath::Vector3<f32>, cgmath::Vector3<f32>) {
+    let up = if normal.y.abs() > 0.99 { cgmath::Vector3::unit_z() } else { cgmath::Vector3::unit_y() };
+    let u = up.cross(normal).normalize();
+    (u, normal.cross(u))
+}
+
+fn reflect_point(plane: &Plane, point: cgmath::Point3<f32>) -> cgmath::Point3<f32> {
+    point - plane.normal * (2.0 * plane.signed_distance(point))
+}
+
+fn reflect_vector(plane: &Plane, vector: cgmath::Vector3<f32>) -> cgmath::Vector3<f32> {
+    vector - plane.normal * (2.0 * plane.normal.dot(vector))
+}
+
+// replaces the near plane of a projection (depth from 0 to 1) with a plane given in view space,
+// from "Oblique View Frustum Depth Projection and Clipping" by Eric Lengyel
+pub(crate) fn oblique_projection(projection: cgmath::Matrix4<f32>, clip_plane: cgmath::Vector4<f32>) -> cgmath::Matrix4<f32> {
+    let Some(inverse) = projection.invert() else { return projection };
+    // the corner of the frustum opposite to the plane, it has to stay at the far plane
+    let corner = inverse * cgmath::Vector4::new(clip_plane.x.signum(), clip_plane.y.signum(), 1.0, 1.0);
+    let scaled = clip_plane * (1.0 / clip_plane.dot(corner));
+
+    // cgmath is column major, the depth row is the z of every column
+    let mut oblique = projection;
+    oblique.x.z = scaled.x;
+    oblique.y.z = scaled.y;
+    oblique.z.z = scaled.z;
+    oblique.w.z = scaled.w;
+    oblique
+}
+
+// a layout like the one of the main camera, for the cameras of the offscreen passes
+pub(crate) fn create_camera_layout(device: &Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("offscreen_camera_bind_group_layout"),
+        entries: &[wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::VERTEX,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }],
+    })
+}
+
+// the same shader as the main pass for the scene seen by other cameras, they never use reverse z so the depth test is fixed
+// the layouts are texture, camera and sky like the main pipeline
+pub(crate) fn create_offscreen_scene_pipeline(
+    device: &Device,
+    label: &str,
+    format: wgpu::TextureFormat,
+    layouts: &[&wgpu::BindGroupLayout],
+    vertex_layouts: &[wgpu::VertexBufferLayout],
+) -> wgpu::RenderPipeline {
+    let shader = ShaderLibrary::builtin().create_module(device, &format!("{} Shader", label), "depth_map.wgsl", &["NORMAL_MAP"]);
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some(&format!("{} Pipeline Layout", label)),
+        bind_group_layouts: layouts,
+        push_constant_ranges: &[],
+    });
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(&format!("{} Pipeline", label)),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: vertex_layouts,
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            cull_mode: Some(wgpu::Face::Back),
+            ..Default::default()
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: Texture::DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    })
+}
+
+pub struct PlanarReflections {
+    pub mirrors: Vec<Mirror>,
+    pub resolution_scale: f32, // size of the reflection targets compared to the screen
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+    camera_layout: wgpu::BindGroupLayout,
+    mirror_layout: wgpu::BindGroupLayout,
+    reflection_pipeline: wgpu::RenderPipeline,
+    mirror_pipeline: wgpu::RenderPipeline,
+}
+
+impl PlanarReflections {
+    // the layouts and buffers are the ones of the main pipeline, the reflection draws the scene the same way
+    pub fn new(
+        device: &Device,
+        format: wgpu::TextureFormat,
+        config: &wgpu::SurfaceConfiguration,
+        main_camera: &Camera,
+        texture_layout: &wgpu::BindGroupLayout,
+        sky_layout: &wgpu::BindGroupLayout,
+        vertex_layouts: &[wgpu::VertexBufferLayout],
+    ) -> Self {
+        let camera_layout = create_camera_layout(device);
+
+        let mirror_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("mirror_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let reflection_pipeline = create_offscreen_scene_pipeline(device, "Reflection", format, &[texture_layout, &camera_layout, sky_layout], vertex_layouts);
+
+        let mirror_shader = ShaderLibrary::builtin().create_module(device, "Mirror Shader", "mirror.wgsl", &[]);
+        let mirror_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Mirror Pipeline Layout"),
+            bind_group_layouts: &[&camera_layout, &mirror_layout, sky_layout],
+            push_constant_ranges: &[],
+        });
+        let mirror_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Mirror Pipeline"),
+            layout: Some(&mirror_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &mirror_shader,
+                entry_point: "vs_main",
+                buffers: &[], // the quad is made in the shader from the uniform
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &mirror_shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                cull_mode: None, // the quad winding depends on the normal, the back is skipped on the cpu
+                ..Default::default()
+            },
+            // it is drawn inside the main pass, so it follows its depth setup
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: main_camera.depth_compare(),
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            mirrors: Vec::new(),
+            resolution_scale: 0.5,
+            width: config.width,
+            height: config.height,
+            format,
+            camera_layout,
+            mirror_layout,
+            reflection_pipeline,
+            mirror_pipeline,
+        }
+    }
+
+    fn target_size(&self) -> (u32, u32) {
+        let scale = self.resolution_scale.clamp(0.1, 1.0);
+        (((self.width as f32 * scale) as u32).max(1), ((self.height as f32 * scale) as u32).max(1))
+    }
+
+    // returns the index of the mirror, its fields can be changed after through mirrors
+    pub fn add(&mut self, device: &Device, center: cgmath::Point3<f32>, normal: cgmath::Vector3<f32>, half_size: cgmath::Vector2<f32>) -> usize {
+        let (width, height) = self.target_size();
+        let target = Texture::create_render_target(device, width, height, self.format, "mirror_reflection");
+        let depth = Texture::create_depth_texture_sized(device, width, height, "mirror_depth");
+
+        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Mirror Camera Buffer"),
+            contents: bytemuck::cast_slice(&[CameraUniform::new()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("mirror_camera_bind_group"),
+            layout: &self.camera_layout,
+            entries: &[wgpu::BindGroupEntry { binding: 0, resource: camera_buffer.as_entire_binding() }],
+        });
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Mirror Buffer"),
+            contents: bytemuck::cast_slice(&[<MirrorUniform as bytemuck::Zeroable>::zeroed()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = Self::create_bind_group(device, &self.mirror_layout, &uniform_buffer, &target);
+
+        self.mirrors.push(Mirror {
+            center,
+            normal: normal.normalize(),
+            half_size,
+            tint: [1.0; 3],
+            reflectivity: 0.9,
+            facing_camera: false,
+            target,
+            depth,
+            camera_buffer,
+            camera_bind_group,
+            uniform_buffer,
+            bind_group,
+        });
+        self.mirrors.len() - 1
+    }
+
+    fn create_bind_group(device: &Device, layout: &wgpu::BindGroupLayout, uniform_buffer: &wgpu::Buffer, target: &Texture) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("mirror_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: uniform_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&target.view) },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::Sampler(&target.sampler) },
+            ],
+        })
+    }
+
+    // the reflection targets follow the size of the screen
+    pub fn resize(&mut self, device: &Device, config: &wgpu::SurfaceConfiguration) {
+        self.width = config.width;
+        self.height = config.height;
+        let (width, height) = self.target_size();
+        for mirror in &mut self.mirrors {
+            mirror.target = Texture::create_render_target(device, width, height, self.format, "mirror_reflection");
+            mirror.depth = Texture::create_depth_texture_sized(device, width, height, "mirror_depth");
+            mirror.bind_group = Self::create_bind_group(device, &self.mirror_layout, &mirror.uniform_buffer, &mirror.target);
+        }
+    }
+
+    // builds the reflected camera of every mirror the camera can see the front of
+    pub fn update(&mut self, queue: &Queue, camera: &Camera) {
+        for mirror in &mut self.mirrors {
+            let plane = mirror.plane();
+            mirror.facing_camera = plane.signed_distance(camera.eye) > 0.0;
+            if !mirror.facing_camera {
+                continue;
+            }
+
+            let mut reflected = *camera;
+            reflected.eye = reflect_point(&plane, camera.eye);
+            reflected.target = reflect_point(&plane, camera.target);
+            reflected.up = reflect_vector(&plane, camera.up);
+            reflected.reverse_z = false;
+            if reflected.projection == Projection::InfinitePerspective {
+                reflected.projection = Projection::Perspective;
+            }
+
+            let view = cgmath::Matrix4::look_at_rh(reflected.eye, reflected.target, reflected.up);
+            let clip_plane = cgmath::Vector4::new(plane.normal.x, plane.normal.y, plane.normal.z, plane.distance + CLIP_OFFSET);
+            let view_plane = view.invert().map_or(clip_plane, |inverse| inverse.transpose() * clip_plane);
+            let view_proj = oblique_projection(reflected.build_projection_matrix(), view_plane) * view;
+
+            let camera_uniform: [[f32; 4]; 4] = view_proj.into();
+            queue.write_buffer(&mirror.camera_buffer, 0, bytemuck::cast_slice(&[camera_uniform]));
+
+            let (axis_u, axis_v) = mirror.axes();
+            let uniform = MirrorUniform {
+                reflection_view_proj: view_proj.into(),
+                center: [mirror.center.x, mirror.center.y, mirror.center.z, 1.0],
+                axis_u: [axis_u.x, axis_u.y, axis_u.z, 0.0],
+                axis_v: [axis_v.x, axis_v.y, axis_v.z, 0.0],
+                normal: [mirror.normal.x, mirror.normal.y, mirror.normal.z, 0.0],
+                camera_position: [camera.eye.x, camera.eye.y, camera.eye.z, 1.0],
+                tint: [mirror.tint[0], mirror.tint[1], mirror.tint[2], mirror.reflectivity],
+            };
+            queue.write_buffer(&mirror.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+        }
+    }
+
+    // renders the reflections, before the main pass that draws the mirrors
+    // the targets are cleared transparent, the mirrors show the sky where nothing was drawn
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, draws: &[InstancedDraw], sky_bind_group: &wgpu::BindGroup) {
+        for mirror in self.mirrors.iter().filter(|mirror| mirror.facing_camera) {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Reflection Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &mirror.target.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &mirror.depth.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+            render_pass.set_pipeline(&self.reflection_pipeline);
+            render_pass.set_bind_group(2, sky_bind_group, &[]);
+            for draw in draws {
+                render_pass.draw_instanced(draw, &mirror.camera_bind_group);
+            }
+        }
+    }
+
+    // draws the mirror surfaces, inside the main pass after the opaque objects
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, camera_bind_group: &'a wgpu::BindGroup, sky_bind_group: &'a wgpu::BindGroup) {
+        let mut visible = self.mirrors.iter().filter(|mirror| mirror.facing_camera).peekable();
+        if visible.peek().is_none() {
+            return;
+        }
+        render_pass.set_pipeline(&self.mirror_pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_bind_group(2, sky_bind_group, &[]);
+        for mirror in visible {
+            render_pass.set_bind_group(1, &mirror.bind_group, &[]);
+            render_pass.draw(0..6, 0..1);
+        }
+    }
+}
@@ -0,0 +1,463 @@
+// portals: surfaces that show the scene from another camera, rendered offscreen before the main pass
+// a linked portal moves the camera from its side to the exit, so looking into it shows what is in front of the exit
+// (doors to far away rooms, rooms bigger inside than outside), a fixed portal shows a camera that doesn't move (security screens)
+// a linked portal seen through itself is rendered again from one step further, up to max_depth levels,
+// the deepest level is drawn first so every level can show the one after it
+
+use cgmath::{InnerSpace, Matrix, SquareMatrix, Transform};
+use wgpu::{util::DeviceExt, Device, Queue};
+
+use super::camera::{Camera, CameraUniform, Plane, Projection};
+use super::model::{DrawModel, InstancedDraw};
+use super::planar_reflection::{create_camera_layout, create_offscreen_scene_pipeline, oblique_projection, surface_axes, CLIP_OFFSET};
+use super::shader_preprocessor::ShaderLibrary;
+use super::textures::Texture;
+
+// every level is one more render of the scene, this is the most a portal can ask for
+pub const MAX_RECURSION: u32 = 4;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct PortalUniform {
+    view_proj: [[f32; 4]; 4], // the camera of the image the surface shows
+    inverse_view_proj: [[f32; 4]; 4],
+    center: [f32; 4],
+    axis_u: [f32; 4],
+    axis_v: [f32; 4],
+    image_eye: [f32; 4],
+    fallback: [f32; 4],
+    mode: [u32; 4], // x: 1 stretches the image over the quad, y: 1 there is no image (the end of the recursion)
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum PortalView {
+    // the other side, its normal points to where the camera comes out
+    Linked { exit_center: cgmath::Point3<f32>, exit_normal: cgmath::Vector3<f32> },
+    // its aspect is set from the size of the surface
+    Fixed(Camera),
+}
+
+// one render of the scene through the portal and the surface drawn inside it, if the portal can be seen from there
+struct PortalLevel {
+    camera_buffer: wgpu::Buffer,
+    camera_bind_group: wgpu::BindGroup,
+    surface_buffer: wgpu::Buffer,
+    surface_bind_group: wgpu::BindGroup,
+    draws_surface: bool,
+}
+
+pub struct Portal {
+    pub center: cgmath::Point3<f32>,
+    pub normal: cgmath::Vector3<f32>, // the side that shows the view, normalized
+    pub half_size: cgmath::Vector2<f32>,
+    pub view: PortalView,
+    pub max_depth: u32, // how many times a linked portal can be seen inside itself, at least 1
+    pub fallback: [f32; 3], // what the deepest level shows instead of the portal
+    facing_camera: bool,
+    active_levels: usize, // the levels rendered this frame, less than max_depth when the portal goes out of view
+    // the levels take turns, each one is written to one target while reading the next level from the other
+    targets: [Texture; 2],
+    depth: Texture,
+    levels: Vec<PortalLevel>,
+    surface_buffer: wgpu::Buffer,
+    surface_bind_group: wgpu::BindGroup, // for the main pass
+}
+
+impl Portal {
+    pub fn plane(&self) -> Plane {
+        Plane { normal: self.normal, distance: -self.normal.dot(cgmath::Vector3::new(self.center.x, self.center.y, self.center.z)) }
+    }
+
+    fn axes(&self) -> (cgmath::Vector3<f32>, cgmath::Vector3<f32>) {
+        let (u, v) = surface_axes(self.normal);
+        (u * self.half_size.x, v * self.half_size.y)
+    }
+
+    fn uniform(&self, image: Option<(cgmath::Matrix4<f32>, cgmath::Point3<f32>)>) -> PortalUniform {
+        let (axis_u, axis_v) = self.axes();
+        let (view_proj, eye) = image.unwrap_or((cgmath::Matrix4::identity(), self.center));
+        PortalUniform {
+            view_proj: view_proj.into(),
+            inverse_view_proj: view_proj.invert().unwrap_or(cgmath::Matrix4::identity()).into(),
+            center: [self.center.x, self.center.y, self.center.z, 1.0],
+            axis_u: [axis_u.x, axis_u.y, axis_u.z, 0.0],
+            axis_v: [axis_v.x, axis_v.y, axis_v.z, 0.0],
+            image_eye: [eye.x, eye.y, eye.z, 1.0],
+            fallback: [self.fallback[0], self.fallback[1], self.fallback[2], 1.0],
+            mode: [matches!(self.view, PortalView::Fixed(_)) as u32, image.is_none() as u32, 0, 0],
+        }
+    }
+}
+
+// from the frame of the entry to the frame of the exit, turned around so what goes in comes out of the front of the exit
+fn portal_transform(center: cgmath::Point3<f32>, normal: cgmath::Vector3<f32>, exit_center: cgmath::Point3<f32>, exit_normal: cgmath::Vector3<f32>) -> cgmath::Matrix4<f32> {
+    let frame = |center: cgmath::Point3<f32>, normal: cgmath::Vector3<f32>| {
+        let (u, v) = surface_axes(normal);
+        cgmath::Matrix4::from_cols(u.extend(0.0), v.extend(0.0), normal.extend(0.0), cgmath::Vector4::new(center.x, center.y, center.z, 1.0))
+    };
+    let entry = frame(center, normal);
+    let exit = frame(exit_center, exit_normal.normalize());
+    let turn = cgmath::Matrix4::from_angle_y(cgmath::Deg(180.0));
+    exit * turn * entry.invert().unwrap_or(cgmath::Matrix4::identity())
+}
+
+// the offscreen cameras never use reverse z, like the reflections
+fn offscreen_camera(camera: &Camera) -> Camera {
+    let mut camera = *camera;
+    camera.reverse_z = false;
+    if camera.projection == Projection::InfinitePerspective {
+        camera.projection = Projection::Perspective;
+    }
+    camera
+}
+
+pub struct Portals {
+    pub portals: Vec<Portal>,
+    pub resolution_scale: f32, // size of the portal targets compared to the screen
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+    camera_layout: wgpu::BindGroupLayout,
+    surface_layout: wgpu::BindGroupLayout,
+    scene_pipeline: wgpu::RenderPipeline,
+    surface_pipeline: wgpu::RenderPipeline, // in the main pass
+    offscreen_surface_pipeline: wgpu::RenderPipeline, // inside the levels, without reverse z
+}
+
+impl Portals {
+    // the layouts and buffers are the ones of the main pipeline, the levels draw the scene the same way
+    pub fn new(
+        device: &Device,
+        format: wgpu::TextureFormat,
+        config: &wgpu::SurfaceConfiguration,
+        main_camera: &Camera,
+        texture_layout: &wgpu::BindGroupLayout,
+        sky_layout: &wgpu::BindGroupLayout,
+        vertex_layouts: &[wgpu::VertexBufferLayout],
+    ) -> Self {
+        let camera_layout = create_camera_layout(device);
+
+        let surface_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("portal_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let scene_pipeline = create_offscreen_scene_pipeline(device, "Portal Scene", format, &[texture_layout, &camera_layout, sky_layout], vertex_layouts);
+
+        let surface_shader = ShaderLibrary::builtin().create_module(device, "Portal Shader", "portal.wgsl", &[]);
+        let surface_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Portal Pipeline Layout"),
+            bind_group_layouts: &[&camera_layout, &surface_layout, sky_layout],
+            push_constant_ranges: &[],
+        });
+        let create_surface_pipeline = |label: &str, depth_compare: wgpu::CompareFunction| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&surface_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &surface_shader,
+                    entry_point: "vs_main",
+                    buffers: &[], // the quad is made in the shader from the uniform
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &surface_shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    cull_mode: None, // the back is skipped on the cpu
+                    ..Default::default()
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: Texture::DEPTH_FORMAT,
+                    depth_write_enabled: true,
+                    depth_compare,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+        };
+        let surface_pipeline = create_surface_pipeline("Portal Pipeline", main_camera.depth_compare());
+        let offscreen_surface_pipeline = create_surface_pipeline("Portal Offscreen Pipeline", wgpu::CompareFunction::Less);
+
+        Self {
+            portals: Vec::new(),
+            resolution_scale: 0.5,
+            width: config.width,
+            height: config.height,
+            format,
+            camera_layout,
+            surface_layout,
+            scene_pipeline,
+            surface_pipeline,
+            offscreen_surface_pipeline,
+        }
+    }
+
+    fn target_size(&self) -> (u32, u32) {
+        let scale = self.resolution_scale.clamp(0.1, 1.0);
+        (((self.width as f32 * scale) as u32).max(1), ((self.height as f32 * scale) as u32).max(1))
+    }
+
+    fn create_targets(&self, device: &Device) -> ([Texture; 2], Texture) {
+        let (width, height) = self.target_size();
+        let targets = [
+            Texture::create_render_target(device, width, height, self.format, "portal_view_a"),
+            Texture::create_render_target(device, width, height, self.format, "portal_view_b"),
+        ];
+        (targets, Texture::create_depth_texture_sized(device, width, height, "portal_depth"))
+    }
+
+    fn create_bind_group(device: &Device, layout: &wgpu::BindGroupLayout, uniform_buffer: &wgpu::Buffer, target: &Texture) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("portal_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: uniform_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&target.view) },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::Sampler(&target.sampler) },
+            ],
+        })
+    }
+
+    fn create_surface_buffer(device: &Device) -> wgpu::Buffer {
+        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Portal Buffer"),
+            contents: bytemuck::cast_slice(&[<PortalUniform as bytemuck::Zeroable>::zeroed()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        })
+    }
+
+    // returns the index of the portal, its fields can be changed after through portals
+    pub fn add(&mut self, device: &Device, center: cgmath::Point3<f32>, normal: cgmath::Vector3<f32>, half_size: cgmath::Vector2<f32>, view: PortalView) -> usize {
+        let (targets, depth) = self.create_targets(device);
+
+        // a fixed camera only renders once, there is no portal inside its image
+        let level_count = match view {
+            PortalView::Linked { .. } => MAX_RECURSION,
+            PortalView::Fixed(_) => 1,
+        };
+        let levels = (0..level_count as usize)
+            .map(|index| {
+                let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Portal Camera Buffer"),
+                    contents: bytemuck::cast_slice(&[CameraUniform::new()]),
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                });
+                let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("portal_camera_bind_group"),
+                    layout: &self.camera_layout,
+                    entries: &[wgpu::BindGroupEntry { binding: 0, resource: camera_buffer.as_entire_binding() }],
+                });
+                let surface_buffer = Self::create_surface_buffer(device);
+                // the surface inside a level shows the next one, that is written to the other target
+                let surface_bind_group = Self::create_bind_group(device, &self.surface_layout, &surface_buffer, &targets[(index + 1) % 2]);
+                PortalLevel { camera_buffer, camera_bind_group, surface_buffer, surface_bind_group, draws_surface: false }
+            })
+            .collect();
+
+        let surface_buffer = Self::create_surface_buffer(device);
+        let surface_bind_group = Self::create_bind_group(device, &self.surface_layout, &surface_buffer, &targets[0]);
+
+        self.portals.push(Portal {
+            center,
+            normal: normal.normalize(),
+            half_size,
+            view,
+            max_depth: 2,
+            fallback: [0.0; 3],
+            facing_camera: false,
+            active_levels: 0,
+            targets,
+            depth,
+            levels,
+            surface_buffer,
+            surface_bind_group,
+        });
+        self.portals.len() - 1
+    }
+
+    // two portals that lead to each other, going into one comes out of the other
+    pub fn add_pair(
+        &mut self,
+        device: &Device,
+        (center_a, normal_a): (cgmath::Point3<f32>, cgmath::Vector3<f32>),
+        (center_b, normal_b): (cgmath::Point3<f32>, cgmath::Vector3<f32>),
+        half_size: cgmath::Vector2<f32>,
+    ) -> (usize, usize) {
+        let a = self.add(device, center_a, normal_a, half_size, PortalView::Linked { exit_center: center_b, exit_normal: normal_b });
+        let b = self.add(device, center_b, normal_b, half_size, PortalView::Linked { exit_center: center_a, exit_normal: normal_a });
+        (a, b)
+    }
+
+    // the portal targets follow the size of the screen
+    pub fn resize(&mut self, device: &Device, config: &wgpu::SurfaceConfiguration) {
+        self.width = config.width;
+        self.height = config.height;
+        for index in 0..self.portals.len() {
+            let (targets, depth) = self.create_targets(device);
+            let portal = &mut self.portals[index];
+            for (level_index, level) in portal.levels.iter_mut().enumerate() {
+                level.surface_bind_group = Self::create_bind_group(device, &self.surface_layout, &level.surface_buffer, &targets[(level_index + 1) % 2]);
+            }
+            portal.surface_bind_group = Self::create_bind_group(device, &self.surface_layout, &portal.surface_buffer, &targets[0]);
+            portal.targets = targets;
+            portal.depth = depth;
+        }
+    }
+
+    // builds the cameras of every level of the portals the camera can see the front of
+    pub fn update(&mut self, queue: &Queue, camera: &Camera) {
+        for portal in &mut self.portals {
+            let plane = portal.plane();
+            portal.facing_camera = plane.signed_distance(camera.eye) > 0.0;
+            portal.active_levels = 0;
+            if !portal.facing_camera {
+                continue;
+            }
+
+            // the camera and eye of every level, the first is the image the main pass shows
+            let mut images = Vec::with_capacity(portal.levels.len());
+            match portal.view {
+                PortalView::Linked { exit_center, exit_normal } => {
+                    let transform = portal_transform(portal.center, portal.normal, exit_center, exit_normal);
+                    let exit_normal = exit_normal.normalize();
+                    let exit_distance = -exit_normal.dot(cgmath::Vector3::new(exit_center.x, exit_center.y, exit_center.z));
+                    // what is between the camera and the exit is not part of the view
+                    let clip_plane = cgmath::Vector4::new(exit_normal.x, exit_normal.y, exit_normal.z, exit_distance + CLIP_OFFSET);
+
+                    let depth = portal.max_depth.clamp(1, portal.levels.len() as u32) as usize;
+                    let mut level_camera = offscreen_camera(camera);
+                    for _ in 0..depth {
+                        level_camera.eye = transform.transform_point(level_camera.eye);
+                        level_camera.target = transform.transform_point(level_camera.target);
+                        level_camera.up = transform.transform_vector(level_camera.up);
+
+                        let view = cgmath::Matrix4::look_at_rh(level_camera.eye, level_camera.target, level_camera.up);
+                        let view_plane = view.invert().map_or(clip_plane, |inverse| inverse.transpose() * clip_plane);
+                        images.push((oblique_projection(level_camera.build_projection_matrix(), view_plane) * view, level_camera.eye));
+
+                        // when the portal can't be seen from this level there is nothing deeper to render
+                        if plane.signed_distance(level_camera.eye) <= 0.0 {
+                            break;
+                        }
+                    }
+                }
+                PortalView::Fixed(fixed) => {
+                    let mut fixed = offscreen_camera(&fixed);
+                    fixed.aspect = portal.half_size.x / portal.half_size.y;
+                    let view = cgmath::Matrix4::look_at_rh(fixed.eye, fixed.target, fixed.up);
+                    images.push((fixed.build_projection_matrix() * view, fixed.eye));
+                }
+            }
+            portal.active_levels = images.len();
+
+            let uniform = portal.uniform(images.first().copied());
+            queue.write_buffer(&portal.surface_buffer, 0, bytemuck::cast_slice(&[uniform]));
+            for index in 0..images.len() {
+                let (view_proj, eye) = images[index];
+                let camera_uniform: [[f32; 4]; 4] = view_proj.into();
+                queue.write_buffer(&portal.levels[index].camera_buffer, 0, bytemuck::cast_slice(&[camera_uniform]));
+
+                // past the last level the surface shows the fallback color
+                let draws_surface = matches!(portal.view, PortalView::Linked { .. }) && plane.signed_distance(eye) > 0.0;
+                let uniform = portal.uniform(images.get(index + 1).copied());
+                portal.levels[index].draws_surface = draws_surface;
+                queue.write_buffer(&portal.levels[index].surface_buffer, 0, bytemuck::cast_slice(&[uniform]));
+            }
+        }
+    }
+
+    // renders the levels of every visible portal, before the main pass that draws the surfaces
+    // the targets are cleared transparent, the surfaces show the sky where nothing was drawn
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, draws: &[InstancedDraw], sky_bind_group: &wgpu::BindGroup) {
+        for portal in self.portals.iter().filter(|portal| portal.facing_camera) {
+            for index in (0..portal.active_levels).rev() {
+                let level = &portal.levels[index];
+                let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Portal Pass"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &portal.targets[index % 2].view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                        view: &portal.depth.view,
+                        depth_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(1.0),
+                            store: wgpu::StoreOp::Store,
+                        }),
+                        stencil_ops: None,
+                    }),
+                    occlusion_query_set: None,
+                    timestamp_writes: None,
+                });
+
+                render_pass.set_pipeline(&self.scene_pipeline);
+                render_pass.set_bind_group(2, sky_bind_group, &[]);
+                for draw in draws {
+                    render_pass.draw_instanced(draw, &level.camera_bind_group);
+                }
+
+                if level.draws_surface {
+                    render_pass.set_pipeline(&self.offscreen_surface_pipeline);
+                    render_pass.set_bind_group(0, &level.camera_bind_group, &[]);
+                    render_pass.set_bind_group(1, &level.surface_bind_group, &[]);
+                    render_pass.set_bind_group(2, sky_bind_group, &[]);
+                    render_pass.draw(0..6, 0..1);
+                }
+            }
+        }
+    }
+
+    // draws the portal surfaces, inside the main pass after the opaque objects
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, camera_bind_group: &'a wgpu::BindGroup, sky_bind_group: &'a wgpu::BindGroup) {
+        let mut visible = self.portals.iter().filter(|portal| portal.facing_camera).peekable();
+        if visible.peek().is_none() {
+            return;
+        }
+        render_pass.set_pipeline(&self.surface_pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_bind_group(2, sky_bind_group, &[]);
+        for portal in visible {
+            render_pass.set_bind_group(1, &portal.surface_bind_group, &[]);
+            render_pass.draw(0..6, 0..1);
+        }
+    }
+}
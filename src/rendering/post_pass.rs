@@ -0,0 +1,217 @@
+// post process passes written by the game, they run in the chain of PostProcess without touching the engine
+// the game only writes fs_main, common/post_pass.wgsl is included before it with the inputs (scene color, depth and
+// the globals) and the vertex shader, binding 4 is free for a uniform of the pass that is set by name like a ShaderMaterial
+
+use anyhow::bail;
+use wgpu::{util::DeviceExt, Device, Queue};
+
+use super::material_reflection::{BindingKind, MaterialParams, ShaderReflection, UniformField};
+use super::shader_preprocessor::ShaderLibrary;
+use super::textures::Texture;
+
+const PARAMS_BINDING: u32 = 4;
+
+// where a pass goes in the chain, passes in the same slot run in the order they were added
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PostSlot {
+    BeforeEffects, // on the clean scene, the engine effects (flash, vignette, fade) go over the result
+    AfterEffects,  // on the final image, right before the surface
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct PostGlobals {
+    pub resolution: [f32; 2],
+    pub inverse_resolution: [f32; 2],
+    pub time: f32,
+    pub delta_time: f32,
+    pub frame: u32,
+    pub _padding: u32,
+}
+
+struct PassUniform {
+    buffer: wgpu::Buffer,
+    data: Vec<u8>,
+    fields: Vec<UniformField>,
+}
+
+pub struct CustomPostPass {
+    pub name: String,
+    pub slot: PostSlot,
+    pub enabled: bool,
+    pub params: MaterialParams,
+    uniform: Option<PassUniform>,
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_groups: Vec<wgpu::BindGroup>, // one for each texture of the chain that can be its input
+}
+
+impl CustomPostPass {
+    pub fn new(device: &Device, name: &str, slot: PostSlot, source: &str, format: wgpu::TextureFormat) -> anyhow::Result<Self> {
+        // the pass goes through the preprocessor too, so it can include the engine shaders (noise, lighting, ...)
+        let mut library = ShaderLibrary::builtin();
+        let shader_name = format!("post/{}.wgsl", name);
+        library.add(&shader_name, &format!("#include \"common/post_pass.wgsl\"\n{}", source));
+        let source = library.preprocess(&shader_name, &[]).map_err(|e| e.context(format!("post pass {}", name)))?;
+
+        let reflection = ShaderReflection::from_wgsl(&source, 0).map_err(|e| e.context(format!("post pass {}", name)))?;
+        if let Some(extra) = reflection.bindings.iter().find(|binding| binding.binding > PARAMS_BINDING) {
+            bail!("post pass {}: binding {} ({}) is not allowed, only binding {} is free for the pass", name, extra.binding, extra.name, PARAMS_BINDING);
+        }
+
+        let mut params = MaterialParams::default();
+        let uniform = match reflection.bindings.iter().find(|binding| binding.binding == PARAMS_BINDING) {
+            None => None,
+            Some(binding) => {
+                let BindingKind::Uniform { size, fields } = &binding.kind else {
+                    bail!("post pass {}: binding {} has to be a uniform", name, PARAMS_BINDING);
+                };
+                params.declare(fields);
+                let data = vec![0u8; *size as usize];
+                let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(&format!("Post Pass {} Buffer", name)),
+                    contents: &data,
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                });
+                Some(PassUniform { buffer, data, fields: fields.clone() })
+            }
+        };
+
+        let bind_group_layout = Self::create_layout(device, uniform.is_some());
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&format!("Post Pass {} Shader", name)),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(&format!("Post Pass {} Pipeline Layout", name)),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(&format!("Post Pass {} Pipeline", name)),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: "vs_main",
+                buffers: &[], // the fullscreen triangle is made in the shader
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Ok(Self { name: name.to_string(), slot, enabled: true, params, uniform, pipeline, bind_group_layout, bind_groups: Vec::new() })
+    }
+
+    fn create_layout(device: &Device, with_params: bool) -> wgpu::BindGroupLayout {
+        let uniform = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let mut entries = vec![
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Depth,
+                },
+                count: None,
+            },
+            uniform(3),
+        ];
+        if with_params {
+            entries.push(uniform(PARAMS_BINDING));
+        }
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor { label: Some("post_pass_bind_group_layout"), entries: &entries })
+    }
+
+    // a bind group for every possible input, so turning passes on and off doesn't remake them
+    // the owner calls this again when the textures change (resize, or the chain targets were made)
+    pub fn rebind(&mut self, device: &Device, inputs: &[&Texture], depth: &Texture, globals: &wgpu::Buffer) {
+        self.bind_groups = inputs
+            .iter()
+            .map(|color| {
+                let mut entries = vec![
+                    wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&color.view) },
+                    wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(&color.sampler) },
+                    wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::TextureView(&depth.view) },
+                    wgpu::BindGroupEntry { binding: 3, resource: globals.as_entire_binding() },
+                ];
+                if let Some(uniform) = &self.uniform {
+                    entries.push(wgpu::BindGroupEntry { binding: PARAMS_BINDING, resource: uniform.buffer.as_entire_binding() });
+                }
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("post_pass_bind_group"),
+                    layout: &self.bind_group_layout,
+                    entries: &entries,
+                })
+            })
+            .collect();
+    }
+
+    pub fn update(&mut self, queue: &Queue) {
+        let Some(uniform) = &mut self.uniform else { return };
+        if self.params.take_dirty() {
+            self.params.write_fields(&uniform.fields, &mut uniform.data);
+            queue.write_buffer(&uniform.buffer, 0, &uniform.data);
+        }
+    }
+
+    // input is the index of the texture in the list given to rebind()
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, input: usize, target: &wgpu::TextureView) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(&self.name),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_groups[input], &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
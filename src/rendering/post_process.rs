@@ -0,0 +1,434 @@
+// the scene is not drawn on the surface directly anymore, it goes to an offscreen texture and this
+// final pass copies it to the surface adding the screen effects the gameplay asks for (damage flash, vignette, etc)
+// games can put their own passes before or after the effects (see post_pass.rs), then the chain goes
+// scene_target -> passes before -> effects -> passes after -> surface, bouncing between two chain targets
+
+use wgpu::{util::DeviceExt, Device, Queue};
+
+use super::textures::Texture;
+use super::shader_preprocessor::ShaderLibrary;
+use super::post_pass::{CustomPostPass, PostGlobals, PostSlot};
+use super::display_output::{Calibration, DisplaySettings, OutputMode};
+use super::render_passes::FramePasses;
+use crate::debug::frame_graph::{self, PassKind};
+use crate::util::color::Color;
+
+// the textures a stage of the chain can read, the bind groups are made for each one in this order
+const CHAIN_INPUT_NAMES: [&str; 3] = ["scene_target", "post_chain_a", "post_chain_b"];
+
+// one step of the chain, the engine effects or a pass of the game
+#[derive(Copy, Clone)]
+enum Stage<'a> {
+    Effects,
+    Custom(&'a CustomPostPass),
+}
+
+// an effect that starts strong and fades out with a quadratic falloff
+#[derive(Copy, Clone, Debug)]
+struct TimedEffect {
+    color: Color,
+    peak: f32,
+    duration: f32,
+    elapsed: f32,
+}
+
+impl TimedEffect {
+    fn none() -> Self {
+        Self { color: Color::BLACK, peak: 0.0, duration: 0.0, elapsed: 0.0 }
+    }
+
+    fn start(&mut self, color: Color, peak: f32, duration: f32) {
+        // a weaker effect doesn't cut a stronger one that is still going
+        if self.strength() > peak {
+            return;
+        }
+        *self = Self { color, peak, duration: duration.max(0.001), elapsed: 0.0 };
+    }
+
+    fn update(&mut self, delta_time: f32) {
+        self.elapsed = (self.elapsed + delta_time).min(self.duration);
+    }
+
+    fn strength(&self) -> f32 {
+        if self.duration <= 0.0 {
+            return 0.0;
+        }
+        let left = 1.0 - self.elapsed / self.duration;
+        self.peak * left * left
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct EffectsUniform {
+    flash: [f32; 4],
+    vignette_color: [f32; 4],
+    fade: [f32; 4],
+    output: [f32; 4], // see DisplaySettings::output_params
+    aberration: f32,
+    aspect: f32,
+    calibration: f32, // 0 off, 1 brightness pattern, 2 peak pattern
+    _padding: f32,
+}
+
+pub struct PostProcess {
+    pub scene_target: Texture, // the main passes render here
+    format: wgpu::TextureFormat,
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_groups: Vec<wgpu::BindGroup>, // the effects reading each texture of CHAIN_INPUT_NAMES
+    uniform_buffer: wgpu::Buffer,
+    flash: TimedEffect,
+    vignette: TimedEffect,
+    aberration: TimedEffect,
+    fade: [f32; 4],
+    output: [f32; 4],
+    calibration: Calibration,
+    aspect: f32,
+    passes: Vec<CustomPostPass>,
+    chain_targets: Vec<Texture>, // only made once there is a custom pass, the effects alone go straight to the surface
+    globals_buffer: wgpu::Buffer,
+    size: (u32, u32),
+    time: f32,
+    frame: u32,
+}
+
+impl PostProcess {
+    pub fn new(device: &Device, config: &wgpu::SurfaceConfiguration) -> Self {
+        let scene_target = Texture::create_render_target(device, config.width, config.height, config.format, "scene_target");
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Post Effects Buffer"),
+            contents: bytemuck::cast_slice(&[EffectsUniform {
+                flash: [0.0; 4],
+                vignette_color: [0.0; 4],
+                fade: [0.0; 4],
+                output: [0.0, 1.0, 1.0, 1.0],
+                aberration: 0.0,
+                aspect: 1.0,
+                calibration: 0.0,
+                _padding: 0.0,
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("post_effects_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let bind_groups = vec![Self::create_bind_group(device, &bind_group_layout, &scene_target, &uniform_buffer)];
+
+        let globals_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Post Globals Buffer"),
+            contents: bytemuck::cast_slice(&[Self::globals(config.width, config.height, 0.0, 0.0, 0)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let shader = ShaderLibrary::builtin().create_module(device, "Post Effects Shader", "post_effects.wgsl", &[]);
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Post Effects Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Post Effects Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[], // the fullscreen triangle is made in the shader
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            scene_target,
+            format: config.format,
+            pipeline,
+            bind_group_layout,
+            bind_groups,
+            uniform_buffer,
+            flash: TimedEffect::none(),
+            vignette: TimedEffect::none(),
+            aberration: TimedEffect::none(),
+            fade: [0.0; 4],
+            output: [0.0, 1.0, 1.0, 1.0],
+            calibration: Calibration::Off,
+            aspect: config.width as f32 / config.height.max(1) as f32,
+            passes: Vec::new(),
+            chain_targets: Vec::new(),
+            globals_buffer,
+            size: (config.width, config.height),
+            time: 0.0,
+            frame: 0,
+        }
+    }
+
+    fn globals(width: u32, height: u32, time: f32, delta_time: f32, frame: u32) -> PostGlobals {
+        let resolution = [width.max(1) as f32, height.max(1) as f32];
+        PostGlobals {
+            resolution,
+            inverse_resolution: [1.0 / resolution[0], 1.0 / resolution[1]],
+            time,
+            delta_time,
+            frame,
+            _padding: 0,
+        }
+    }
+
+    fn create_bind_group(device: &Device, layout: &wgpu::BindGroupLayout, scene_target: &Texture, uniform_buffer: &wgpu::Buffer) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("post_effects_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&scene_target.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&scene_target.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    // the scene target has to follow the size of the surface, the depth is the one the custom passes read
+    pub fn resize(&mut self, device: &Device, config: &wgpu::SurfaceConfiguration, depth: &Texture) {
+        self.scene_target = Texture::create_render_target(device, config.width, config.height, self.format, "scene_target");
+        self.aspect = config.width as f32 / config.height.max(1) as f32;
+        self.size = (config.width, config.height);
+        if !self.chain_targets.is_empty() {
+            self.chain_targets.clear();
+            self.create_chain_targets(device);
+        }
+        self.rebind(device, depth);
+    }
+
+    fn create_chain_targets(&mut self, device: &Device) {
+        let (width, height) = self.size;
+        for name in &CHAIN_INPUT_NAMES[1..] {
+            self.chain_targets.push(Texture::create_render_target(device, width, height, self.format, name));
+        }
+    }
+
+    // remakes the bind groups of every stage for every texture it could read
+    fn rebind(&mut self, device: &Device, depth: &Texture) {
+        let inputs: Vec<&Texture> = std::iter::once(&self.scene_target).chain(self.chain_targets.iter()).collect();
+        self.bind_groups = inputs.iter().map(|input| Self::create_bind_group(device, &self.bind_group_layout, input, &self.uniform_buffer)).collect();
+        for pass in &mut self.passes {
+            pass.rebind(device, &inputs, depth, &self.globals_buffer);
+        }
+    }
+
+    // adds a pass of the game at the end of its slot, the source only has fs_main (see common/post_pass.wgsl)
+    // it fails if the source doesn't parse, declares bindings other than 4 or the name is already used
+    pub fn add_pass(&mut self, device: &Device, depth: &Texture, name: &str, slot: PostSlot, source: &str) -> anyhow::Result<()> {
+        if self.passes.iter().any(|pass| pass.name == name) {
+            anyhow::bail!("there is already a post pass called {}", name);
+        }
+        let pass = CustomPostPass::new(device, name, slot, source, self.format)?;
+        if self.chain_targets.is_empty() {
+            self.create_chain_targets(device);
+        }
+        self.passes.push(pass);
+        self.rebind(device, depth);
+        Ok(())
+    }
+
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.format
+    }
+
+    // the whole screen goes to a color and fades back, for hits or explosions
+    pub fn flash(&mut self, color: Color, strength: f32, duration: f32) {
+        self.flash.start(color, strength.clamp(0.0, 1.0), duration);
+    }
+
+    // darkens (or tints) the borders of the screen and fades back, the usual "you got hurt"
+    pub fn vignette_pulse(&mut self, color: Color, strength: f32, duration: f32) {
+        self.vignette.start(color, strength.clamp(0.0, 1.0), duration);
+    }
+
+    // splits the color channels towards the borders, strength around 0.01 to 0.05 looks good
+    pub fn chromatic_burst(&mut self, strength: f32, duration: f32) {
+        self.aberration.start(Color::BLACK, strength, duration);
+    }
+
+    // covers the screen with a color, 0 is the scene and 1 is only the color, it stays until it is changed again
+    // unlike the other effects this one is driven from outside (the scene transitions)
+    pub fn set_fade(&mut self, color: Color, amount: f32) {
+        self.fade = [color.r, color.g, color.b, amount.clamp(0.0, 1.0)];
+    }
+
+    // how the final color goes to the monitor, the effects pass does it so the passes after the effects get display values
+    pub fn set_output(&mut self, settings: &DisplaySettings, mode: OutputMode) {
+        self.output = settings.output_params(mode);
+    }
+
+    // draws the test pattern of a calibration step over the scene
+    pub fn set_calibration(&mut self, calibration: Calibration) {
+        self.calibration = calibration;
+    }
+
+    pub fn update(&mut self, queue: &Queue, delta_time: f32) {
+        self.flash.update(delta_time);
+        self.vignette.update(delta_time);
+        self.aberration.update(delta_time);
+
+        let flash = self.flash.color;
+        let vignette = self.vignette.color;
+        let uniform = EffectsUniform {
+            flash: [flash.r, flash.g, flash.b, self.flash.strength()],
+            vignette_color: [vignette.r, vignette.g, vignette.b, self.vignette.strength()],
+            fade: self.fade,
+            output: self.output,
+            aberration: self.aberration.strength(),
+            aspect: self.aspect,
+            calibration: match self.calibration {
+                Calibration::Off => 0.0,
+                Calibration::Brightness => 1.0,
+                Calibration::Peak => 2.0,
+            },
+            _padding: 0.0,
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+
+        self.time += delta_time;
+        self.frame = self.frame.wrapping_add(1);
+        let (width, height) = self.size;
+        let globals = Self::globals(width, height, self.time, delta_time, self.frame);
+        queue.write_buffer(&self.globals_buffer, 0, bytemuck::cast_slice(&[globals]));
+        for pass in &mut self.passes {
+            pass.update(queue);
+        }
+    }
+
+    fn stages(&self) -> Vec<Stage<'_>> {
+        let custom = |slot: PostSlot| self.passes.iter().filter(move |pass| pass.enabled && pass.slot == slot).map(Stage::Custom);
+        custom(PostSlot::BeforeEffects).chain(std::iter::once(Stage::Effects)).chain(custom(PostSlot::AfterEffects)).collect()
+    }
+
+    // the stage at index reads CHAIN_INPUT_NAMES[input] and, if it is not the last one, writes the chain target after that
+    fn chain_input(index: usize) -> usize {
+        if index == 0 {
+            0
+        } else {
+            1 + (index - 1) % 2
+        }
+    }
+
+    // one pass of the frame per stage, the last one writes the surface
+    pub fn add_passes<'a>(&'a self, frame: &mut FramePasses<'a>) {
+        let stages = self.stages();
+        let last = stages.len() - 1;
+        for (index, stage) in stages.into_iter().enumerate() {
+            let input = frame_graph::texture(CHAIN_INPUT_NAMES[Self::chain_input(index)]);
+            let output = if index == last {
+                frame_graph::surface("surface")
+            } else {
+                frame_graph::texture(CHAIN_INPUT_NAMES[Self::chain_input(index + 1)])
+            };
+            match stage {
+                Stage::Effects => frame.add("post effects", PassKind::Render, &[input, frame_graph::buffer("post_effects")], &[output], move |encoder, targets| {
+                    self.render_stage(encoder, stage, index, last, targets.surface);
+                }),
+                Stage::Custom(pass) => {
+                    frame.add(&pass.name, PassKind::Render, &[input, frame_graph::texture("depth"), frame_graph::buffer("post_globals")], &[output], move |encoder, targets| {
+                        self.render_stage(encoder, stage, index, last, targets.surface);
+                    })
+                }
+            }
+        }
+    }
+
+    // runs the chain from the scene target to the final view, the frame goes through add_passes (the clips use this)
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        let stages = self.stages();
+        let last = stages.len() - 1;
+        for (index, stage) in stages.into_iter().enumerate() {
+            self.render_stage(encoder, stage, index, last, target);
+        }
+    }
+
+    // the stage at index of a chain that ends at last, the last one writes the target and the rest the chain textures
+    fn render_stage(&self, encoder: &mut wgpu::CommandEncoder, stage: Stage, index: usize, last: usize, target: &wgpu::TextureView) {
+        let input = Self::chain_input(index);
+        let output = if index == last {
+            target
+        } else {
+            &self.chain_targets[Self::chain_input(index + 1) - 1].view
+        };
+        match stage {
+            Stage::Effects => self.render_effects(encoder, input, output),
+            Stage::Custom(pass) => pass.render(encoder, input, output),
+        }
+    }
+
+    fn render_effects(&self, encoder: &mut wgpu::CommandEncoder, input: usize, target: &wgpu::TextureView) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Post Effects Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_groups[input], &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
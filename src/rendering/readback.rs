@@ -0,0 +1,148 @@
+// reading data back from the gpu without stopping everything
+// a readback copies the source into a staging buffer and maps it, the main loop polls the device every frame
+// and when the map finishes the future wakes up (or try_take gives the bytes for code that is not async)
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use anyhow::anyhow;
+use wgpu::{Device, Queue};
+
+#[derive(Default)]
+struct MapState {
+    result: Option<Result<(), wgpu::BufferAsyncError>>,
+    waker: Option<Waker>,
+}
+
+// the layout of the rows when the readback comes from a texture, so we can remove the 256 bytes padding
+#[derive(Copy, Clone)]
+struct RowLayout {
+    unpadded_bytes_per_row: u32,
+    padded_bytes_per_row: u32,
+}
+
+pub struct Readback {
+    buffer: wgpu::Buffer,
+    rows: Option<RowLayout>,
+    state: Arc<Mutex<MapState>>,
+    taken: bool,
+}
+
+impl Readback {
+    fn start(buffer: wgpu::Buffer, rows: Option<RowLayout>) -> Self {
+        let state = Arc::new(Mutex::new(MapState::default()));
+        let callback_state = state.clone();
+        buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            let mut state = callback_state.lock().unwrap();
+            state.result = Some(result);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        });
+
+        Self { buffer, rows, state, taken: false }
+    }
+
+    // for the game loop: gives the bytes once if the map already finished, None while it is still on the way
+    pub fn try_take(&mut self) -> Option<anyhow::Result<Vec<u8>>> {
+        if self.taken {
+            return None;
+        }
+        let result = self.state.lock().unwrap().result.take()?;
+        self.taken = true;
+        Some(self.read(result))
+    }
+
+    // blocks until the gpu finished, only for tools and loading code where waiting is fine
+    pub fn wait(mut self, device: &Device) -> anyhow::Result<Vec<u8>> {
+        device.poll(wgpu::Maintain::Wait);
+        match self.try_take() {
+            Some(result) => result,
+            None => Err(anyhow!("the readback finished without a result")),
+        }
+    }
+
+    fn read(&self, result: Result<(), wgpu::BufferAsyncError>) -> anyhow::Result<Vec<u8>> {
+        result.map_err(|e| anyhow!("failed to map the readback buffer: {}", e))?;
+
+        let bytes = {
+            let data = self.buffer.slice(..).get_mapped_range();
+            match self.rows {
+                Some(rows) => {
+                    let mut bytes = Vec::with_capacity(data.len() / rows.padded_bytes_per_row as usize * rows.unpadded_bytes_per_row as usize);
+                    for row in data.chunks(rows.padded_bytes_per_row as usize) {
+                        bytes.extend_from_slice(&row[..rows.unpadded_bytes_per_row as usize]);
+                    }
+                    bytes
+                }
+                None => data.to_vec(),
+            }
+        };
+        self.buffer.unmap();
+
+        Ok(bytes)
+    }
+}
+
+impl Future for Readback {
+    type Output = anyhow::Result<Vec<u8>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        {
+            let mut state = this.state.lock().unwrap();
+            if state.result.is_none() {
+                state.waker = Some(cx.waker().clone());
+                return Poll::Pending;
+            }
+        }
+        match this.try_take() {
+            Some(result) => Poll::Ready(result),
+            None => Poll::Ready(Err(anyhow!("the readback was already taken"))),
+        }
+    }
+}
+
+// copies a whole 2D texture (it needs COPY_SRC) and starts reading it, the bytes come without row padding
+pub fn read_texture(device: &Device, queue: &Queue, texture: &wgpu::Texture, bytes_per_pixel: u32) -> Readback {
+    let width = texture.width();
+    let height = texture.height();
+
+    // rows copied to a buffer have to be aligned to 256 bytes
+    let unpadded_bytes_per_row = width * bytes_per_pixel;
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(align) * align;
+
+    let staging = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Readback Texture Staging Buffer"),
+        size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Readback Texture Encoder"),
+    });
+    encoder.copy_texture_to_buffer(
+        wgpu::ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        wgpu::ImageCopyBuffer {
+            buffer: &staging,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_bytes_per_row),
+                rows_per_image: Some(height),
+            },
+        },
+        wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+    );
+    queue.submit(std::iter::once(encoder.finish()));
+
+    Readback::start(staging, Some(RowLayout { unpadded_bytes_per_row, padded_bytes_per_row }))
+}
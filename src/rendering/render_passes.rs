@@ -0,0 +1,153 @@
+// passes of the game that go into the frame without touching App::render: each one says at which point of the frame
+// it runs (the slot), what it reads and writes (the same names the frame graph dump uses, see debug/frame_graph.rs)
+// and encodes itself on the encoder of the frame. passes in the same slot run in the order they were added
+// the engine passes (the main pass, the fog, the sprites, the post process, the ui) are declared by App::render in the
+// same list (FramePasses), the slots are the places between them
+
+use wgpu::{Device, Queue};
+
+use super::camera::Camera;
+use super::textures::Texture;
+use crate::debug::frame_graph::{PassKind, PassNode, ResourceRef};
+
+// where a pass goes in the frame
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PassSlot {
+    BeforeScene, // after the shadow map and the reflections, before the main pass (its own targets, the scene is not drawn yet)
+    AfterScene,  // on the 3D scene with its depth, before the fog and the sprites (decals, outlines)
+    AfterSprites, // on the whole scene, before the post process
+    Overlay,     // on the surface after the post process, before the ui
+}
+
+// what a pass can draw to and read, for the frame being encoded
+pub struct PassTargets<'a> {
+    pub scene: &'a wgpu::TextureView, // the hdr target of the scene (see PostProcess::format)
+    pub depth: &'a Texture,
+    pub surface: &'a wgpu::TextureView,
+    pub camera: &'a wgpu::BindGroup, // the layout of planar_reflection::create_camera_layout
+}
+
+pub trait RenderPass {
+    fn name(&self) -> &str;
+
+    fn slot(&self) -> PassSlot;
+
+    fn kind(&self) -> PassKind {
+        PassKind::Render
+    }
+
+    // an inactive pass is skipped and not recorded in the frame graph
+    fn is_active(&self) -> bool {
+        true
+    }
+
+    fn reads(&self) -> Vec<ResourceRef> {
+        Vec::new()
+    }
+
+    fn writes(&self) -> Vec<ResourceRef>;
+
+    // the depth texture was made again, the bind groups that read it have to be too (also called when it's added)
+    fn resize(&mut self, _device: &Device, _depth: &Texture) {}
+
+    // once per frame before render, to upload what it needs
+    fn prepare(&mut self, _device: &Device, _queue: &Queue, _camera: &Camera) {}
+
+    fn encode(&self, encoder: &mut wgpu::CommandEncoder, targets: &PassTargets);
+}
+
+pub struct RenderPasses {
+    passes: Vec<Box<dyn RenderPass>>,
+}
+
+impl RenderPasses {
+    pub fn new() -> Self {
+        Self { passes: Vec::new() }
+    }
+
+    // adds the pass at the end of its slot, it fails if the name is already used
+    pub fn add(&mut self, device: &Device, depth: &Texture, mut pass: Box<dyn RenderPass>) -> anyhow::Result<()> {
+        if self.passes.iter().any(|existing| existing.name() == pass.name()) {
+            anyhow::bail!("there is already a render pass called {}", pass.name());
+        }
+        pass.resize(device, depth);
+        self.passes.push(pass);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Box<dyn RenderPass>> {
+        let index = self.passes.iter().position(|pass| pass.name() == name)?;
+        Some(self.passes.remove(index))
+    }
+
+    pub fn resize(&mut self, device: &Device, depth: &Texture) {
+        for pass in &mut self.passes {
+            pass.resize(device, depth);
+        }
+    }
+
+    pub fn prepare(&mut self, device: &Device, queue: &Queue, camera: &Camera) {
+        for pass in self.passes.iter_mut().filter(|pass| pass.is_active()) {
+            pass.prepare(device, queue, camera);
+        }
+    }
+
+    fn in_slot(&self, slot: PassSlot) -> impl Iterator<Item = &dyn RenderPass> {
+        self.passes.iter().map(|pass| pass.as_ref()).filter(move |pass| pass.slot() == slot && pass.is_active())
+    }
+}
+
+impl Default for RenderPasses {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+type EncodePass<'a> = Box<dyn Fn(&mut wgpu::CommandEncoder, &PassTargets) + 'a>;
+
+// the whole frame as a list of passes: App::render adds the engine passes and the slots of the game in the order they
+// run and then runs the list. the frame graph dump is made from the same list, so it always shows what was encoded
+// the resources are only kept while the graph records, like FrameGraph::add_pass
+pub struct FramePasses<'a> {
+    recording: bool,
+    nodes: Vec<PassNode>,
+    passes: Vec<EncodePass<'a>>,
+}
+
+impl<'a> FramePasses<'a> {
+    pub fn new(recording: bool) -> Self {
+        Self { recording, nodes: Vec::new(), passes: Vec::new() }
+    }
+
+    pub fn add(&mut self, name: &str, kind: PassKind, reads: &[ResourceRef], writes: &[ResourceRef], encode: impl Fn(&mut wgpu::CommandEncoder, &PassTargets) + 'a) {
+        if self.recording {
+            self.nodes.push(PassNode { name: name.to_string(), kind, reads: reads.to_vec(), writes: writes.to_vec() });
+        }
+        self.push(encode);
+    }
+
+    // the passes of the game in the slot, after the ones added so far
+    pub fn add_slot(&mut self, passes: &'a RenderPasses, slot: PassSlot) {
+        for pass in passes.in_slot(slot) {
+            if self.recording {
+                self.nodes.push(PassNode { name: pass.name().to_string(), kind: pass.kind(), reads: pass.reads(), writes: pass.writes() });
+            }
+            self.push(move |encoder, targets| pass.encode(encoder, targets));
+        }
+    }
+
+    fn push(&mut self, encode: impl Fn(&mut wgpu::CommandEncoder, &PassTargets) + 'a) {
+        self.passes.push(Box::new(encode));
+    }
+
+    pub fn run(&self, encoder: &mut wgpu::CommandEncoder, targets: &PassTargets) {
+        for pass in &self.passes {
+            pass(encoder, targets);
+        }
+    }
+
+    // what the frame graph records, empty when it isn't recording
+    pub fn into_nodes(self) -> Vec<PassNode> {
+        self.nodes
+    }
+}
@@ -0,0 +1,114 @@
+// the gpu side of the scene graph: the world matrices of the visible entities (with the index of their material) go
+// into one instance buffer, grouped by model and material so each group is a single instanced draw over its range
+// only the instances that changed since the last frame are uploaded, into the source buffer. the animation
+// (see InstanceAnimator) reads it and writes the buffer that is drawn, so a still graph costs no uploads at all
+
+use std::ops::Range;
+
+use wgpu::{Device, Queue};
+
+use super::model::{InstanceRaw, InstancedDraw};
+use crate::scene::graph::{MaterialId, ModelId, SceneGraph};
+
+struct SceneBatch {
+    model: ModelId,
+    material: Option<MaterialId>,
+    instances: Range<u32>,
+}
+
+pub struct SceneRenderer {
+    source: wgpu::Buffer, // what the graph has, the animation doesn't change it
+    buffer: wgpu::Buffer, // what is drawn, written by the animation every frame
+    uploaded: Vec<InstanceRaw>, // what the source has, to upload only what changed
+    capacity: usize, // in instances
+    batches: Vec<SceneBatch>,
+    instance_count: u32,
+}
+
+impl SceneRenderer {
+    const INSTANCE_SIZE: usize = std::mem::size_of::<InstanceRaw>();
+
+    pub fn new(device: &Device, capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        let (source, buffer) = Self::create_buffers(device, capacity);
+        Self { source, buffer, uploaded: Vec::new(), capacity, batches: Vec::new(), instance_count: 0 }
+    }
+
+    fn create_buffers(device: &Device, capacity: usize) -> (wgpu::Buffer, wgpu::Buffer) {
+        let size = (capacity * Self::INSTANCE_SIZE) as wgpu::BufferAddress;
+        let source = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Scene Instance Source Buffer"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Scene Instance Buffer"),
+            size,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE, // storage so the compute animation can write it
+            mapped_at_creation: false,
+        });
+        (source, buffer)
+    }
+
+    // uploads what changed in the graph since the last call, the world transforms have to be updated before
+    // true when the buffers grew (they are new ones), whatever holds the old ones has to take them again
+    pub fn prepare(&mut self, device: &Device, queue: &Queue, graph: &SceneGraph) -> bool {
+        let groups = graph.draw_groups();
+        let total: usize = groups.iter().map(|group| group.matrices.len()).sum();
+
+        let grew = total > self.capacity;
+        if grew {
+            self.capacity = total.next_power_of_two();
+            (self.source, self.buffer) = Self::create_buffers(device, self.capacity);
+            // the new source is empty, everything goes up
+            self.uploaded.clear();
+        }
+
+        self.batches.clear();
+        let mut data: Vec<InstanceRaw> = Vec::with_capacity(total);
+        for group in groups {
+            let start = data.len() as u32;
+            let material = group.material.map_or(0, |material| material.instance_index());
+            data.extend(group.matrices.iter().zip(&group.variations).map(|(matrix, variation)| InstanceRaw::with_variation(*matrix, material, variation)));
+            self.batches.push(SceneBatch { model: group.model, material: group.material, instances: start..data.len() as u32 });
+        }
+        self.instance_count = data.len() as u32;
+
+        // from the first instance that changed to the last one, a moving entity only sends its own matrix
+        let changed = |(new, old): (&InstanceRaw, &InstanceRaw)| bytemuck::bytes_of(new) != bytemuck::bytes_of(old);
+        let start = data.iter().zip(&self.uploaded).position(changed).unwrap_or(data.len().min(self.uploaded.len()));
+        let end = if data.len() == self.uploaded.len() {
+            data.iter().zip(&self.uploaded).rposition(changed).map_or(start, |last| last + 1)
+        } else {
+            data.len()
+        };
+        if start < end {
+            queue.write_buffer(&self.source, (start * Self::INSTANCE_SIZE) as wgpu::BufferAddress, bytemuck::cast_slice(&data[start..end]));
+        }
+        self.uploaded = data;
+        grew
+    }
+
+    pub fn source_buffer(&self) -> &wgpu::Buffer {
+        &self.source
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    pub fn instance_count(&self) -> u32 {
+        self.instance_count
+    }
+
+    // the draws of the last prepare, the graph has to be the same one
+    pub fn draws<'a>(&'a self, graph: &'a SceneGraph) -> impl Iterator<Item = InstancedDraw<'a>> + 'a {
+        self.batches.iter().map(move |batch| InstancedDraw {
+            model: graph.model(batch.model),
+            material: batch.material.map(|material| graph.material(material)),
+            buffer: &self.buffer,
+            instances: batch.instances.clone(),
+        })
+    }
+}
@@ -0,0 +1,133 @@
+// the shaders read from disk while the game runs, so a change to a .wgsl shows up without building the crate again
+// the library starts with the embedded shaders and the files of src/shaders replace them when the game runs next to
+// its sources. every file is watched, a change is validated with naga and the pipelines that use the file (directly
+// or through an #include) are made again. a shader that doesn't validate keeps the last good pipeline on screen
+// only naga errors are caught, a binding that no longer matches the layout of the pipeline still goes to the wgpu
+// error handler (it panics), so keep the layouts in rust when the bindings change
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use wgpu::Device;
+
+use super::shader_preprocessor::ShaderLibrary;
+use crate::util::file_watcher::FileWatcher;
+
+// makes the pipeline from the compiled module, it is kept and called again after every reload
+pub type ReloadableBuilder = Box<dyn Fn(&Device, &wgpu::ShaderModule) -> wgpu::RenderPipeline>;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ShaderPipelineId(usize);
+
+struct ReloadablePipeline {
+    label: String,
+    shader: String,
+    defines: Vec<String>,
+    builder: ReloadableBuilder,
+    pipeline: wgpu::RenderPipeline,
+}
+
+pub struct ShaderManager {
+    library: ShaderLibrary,
+    root: Option<PathBuf>, // none in a build that runs away from its sources, the embedded shaders are all there is
+    watcher: FileWatcher,
+    pipelines: Vec<ReloadablePipeline>,
+}
+
+impl ShaderManager {
+    // the folder the embedded shaders come from, only there when the game runs where it was built
+    pub fn source_dir() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("src").join("shaders")
+    }
+
+    pub fn new(root: Option<PathBuf>) -> Self {
+        let root = root.filter(|root| root.is_dir());
+        let mut library = ShaderLibrary::builtin();
+        let watcher = FileWatcher::new(Duration::from_millis(250));
+        if let Some(root) = &root {
+            let names: Vec<String> = library.names().map(str::to_string).collect();
+            for name in names {
+                let path = root.join(&name);
+                // the generated ones (noise.wgsl) have no file
+                if let Ok(source) = std::fs::read_to_string(&path) {
+                    library.add(&name, &source);
+                    watcher.watch(&path);
+                }
+            }
+        }
+        Self { library, root, watcher, pipelines: Vec::new() }
+    }
+
+    // the sources as they are now, for the systems that make their modules once
+    pub fn library(&self) -> &ShaderLibrary {
+        &self.library
+    }
+
+    // a pipeline that follows its shader, a file on disk that doesn't validate at the start falls back to the
+    // embedded one (that one failing is a bug of the engine, it panics like ShaderLibrary::create_module)
+    pub fn create_pipeline(&mut self, device: &Device, label: &str, shader: &str, defines: &[&str], builder: ReloadableBuilder) -> ShaderPipelineId {
+        let module = self.library.try_create_module(device, label, shader, defines).unwrap_or_else(|e| {
+            eprintln!("{} from disk doesn't validate, using the embedded one: {:#}", shader, e);
+            ShaderLibrary::builtin().create_module(device, label, shader, defines)
+        });
+        let pipeline = builder(device, &module);
+        self.pipelines.push(ReloadablePipeline {
+            label: label.to_string(),
+            shader: shader.to_string(),
+            defines: defines.iter().map(|define| define.to_string()).collect(),
+            builder,
+            pipeline,
+        });
+        ShaderPipelineId(self.pipelines.len() - 1)
+    }
+
+    pub fn pipeline(&self, id: ShaderPipelineId) -> &wgpu::RenderPipeline {
+        &self.pipelines[id.0].pipeline
+    }
+
+    // once per frame, reads the files that changed and makes their pipelines again, returns how many were made
+    pub fn reload(&mut self, device: &Device) -> usize {
+        let Some(root) = &self.root else { return 0 };
+        let mut changed = Vec::new();
+        for path in self.watcher.changes() {
+            let Ok(relative) = path.strip_prefix(root) else { continue };
+            // the library names always use /, like the includes
+            let name = relative.to_string_lossy().replace('\\', "/");
+            match std::fs::read_to_string(&path) {
+                Ok(source) => {
+                    self.library.add(&name, &source);
+                    changed.push(name);
+                }
+                // a half saved file, the next save tries again
+                Err(e) => eprintln!("{} was not reloaded: {}", path.display(), e),
+            }
+        }
+        self.rebuild(device, &changed)
+    }
+
+    // a source that didn't come from the root (a shader of the game loaded on a task, see assets.rs), it replaces the
+    // one with the same name and the pipelines that use it are made again, returns how many
+    pub fn add_source(&mut self, device: &Device, name: &str, source: &str) -> usize {
+        self.library.add(name, source);
+        self.rebuild(device, &[name.to_string()])
+    }
+
+    fn rebuild(&mut self, device: &Device, changed: &[String]) -> usize {
+        let mut rebuilt = 0;
+        for reloadable in &mut self.pipelines {
+            if !changed.iter().any(|name| self.library.depends_on(&reloadable.shader, name)) {
+                continue;
+            }
+            let defines: Vec<&str> = reloadable.defines.iter().map(String::as_str).collect();
+            match self.library.try_create_module(device, &reloadable.label, &reloadable.shader, &defines) {
+                Ok(module) => {
+                    reloadable.pipeline = (reloadable.builder)(device, &module);
+                    rebuilt += 1;
+                    println!("reloaded {} ({})", reloadable.shader, reloadable.label);
+                }
+                Err(e) => eprintln!("{} was not reloaded, the last good pipeline stays: {:#}", reloadable.shader, e),
+            }
+        }
+        rebuilt
+    }
+}
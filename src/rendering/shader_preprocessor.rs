@@ -0,0 +1,218 @@
+// wgsl has no includes, so the shaders go through this before reaching wgpu
+// supported, always at the start of a line:
+//   #include "common/camera.wgsl"   pastes another shader of the library, each one only once per result
+//   #define NAME / #undef NAME      flags for the conditionals (they are not replaced in the code)
+//   #ifdef NAME / #ifndef NAME / #else / #endif
+// the shaders of the engine are embedded in the library, games can add their own with add()
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{anyhow, bail};
+use wgpu::Device;
+
+use crate::util::noise::NOISE_WGSL;
+
+pub struct ShaderLibrary {
+    sources: HashMap<String, String>,
+}
+
+impl ShaderLibrary {
+    pub fn new() -> Self {
+        Self { sources: HashMap::new() }
+    }
+
+    // the engine shaders, the names are the paths inside src/shaders
+    pub fn builtin() -> Self {
+        let mut library = Self::new();
+        library.add("common/camera.wgsl", include_str!("../shaders/common/camera.wgsl"));
+        library.add("common/instancing.wgsl", include_str!("../shaders/common/instancing.wgsl"));
+        library.add("common/fullscreen.wgsl", include_str!("../shaders/common/fullscreen.wgsl"));
+        library.add("common/lighting.wgsl", include_str!("../shaders/common/lighting.wgsl"));
+        library.add("common/sky.wgsl", include_str!("../shaders/common/sky.wgsl"));
+        library.add("common/lights.wgsl", include_str!("../shaders/common/lights.wgsl"));
+        library.add("common/clustered_lights.wgsl", include_str!("../shaders/common/clustered_lights.wgsl"));
+        library.add("common/fog.wgsl", include_str!("../shaders/common/fog.wgsl"));
+        library.add("common/post_pass.wgsl", include_str!("../shaders/common/post_pass.wgsl"));
+        library.add("common/overdraw.wgsl", include_str!("../shaders/common/overdraw.wgsl"));
+        library.add("common/particles.wgsl", include_str!("../shaders/common/particles.wgsl"));
+        library.add("noise.wgsl", NOISE_WGSL);
+        library.add("depth_map.wgsl", include_str!("../shaders/depth_map.wgsl"));
+        library.add("screen.wgsl", include_str!("../shaders/screen.wgsl"));
+        library.add("thumbnail.wgsl", include_str!("../shaders/thumbnail.wgsl"));
+        library.add("sprite.wgsl", include_str!("../shaders/sprite.wgsl"));
+        library.add("text.wgsl", include_str!("../shaders/text.wgsl"));
+        library.add("ui.wgsl", include_str!("../shaders/ui.wgsl"));
+        library.add("post_effects.wgsl", include_str!("../shaders/post_effects.wgsl"));
+        library.add("debug_view.wgsl", include_str!("../shaders/debug_view.wgsl"));
+        library.add("sky.wgsl", include_str!("../shaders/sky.wgsl"));
+        library.add("skybox.wgsl", include_str!("../shaders/skybox.wgsl"));
+        library.add("shadow.wgsl", include_str!("../shaders/shadow.wgsl"));
+        library.add("fog_inject.wgsl", include_str!("../shaders/fog_inject.wgsl"));
+        library.add("fog_integrate.wgsl", include_str!("../shaders/fog_integrate.wgsl"));
+        library.add("fog_apply.wgsl", include_str!("../shaders/fog_apply.wgsl"));
+        library.add("mirror.wgsl", include_str!("../shaders/mirror.wgsl"));
+        library.add("portal.wgsl", include_str!("../shaders/portal.wgsl"));
+        library.add("trail.wgsl", include_str!("../shaders/trail.wgsl"));
+        library.add("ground_grid.wgsl", include_str!("../shaders/ground_grid.wgsl"));
+        library.add("blob_shadow.wgsl", include_str!("../shaders/blob_shadow.wgsl"));
+        library.add("instance_animation.wgsl", include_str!("../shaders/instance_animation.wgsl"));
+        library.add("particle_simulation.wgsl", include_str!("../shaders/particle_simulation.wgsl"));
+        library.add("particles.wgsl", include_str!("../shaders/particles.wgsl"));
+        library
+    }
+
+    // adding a name that already exists replaces it, that is how a game overrides an engine shader
+    pub fn add(&mut self, name: &str, source: &str) {
+        self.sources.insert(name.to_string(), source.to_string());
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.sources.keys().map(String::as_str)
+    }
+
+    // true when the shader is the file or pastes it somewhere down its includes, whatever the defines are
+    // for the hot reload, to know which pipelines a changed file touches
+    pub fn depends_on(&self, name: &str, file: &str) -> bool {
+        let mut pending = vec![name];
+        let mut seen = HashSet::new();
+        while let Some(current) = pending.pop() {
+            if current == file {
+                return true;
+            }
+            if !seen.insert(current) {
+                continue;
+            }
+            let Some(source) = self.sources.get(current) else { continue };
+            for line in source.lines() {
+                if let Some(include) = line.trim_start().strip_prefix("#include") {
+                    pending.push(include.trim().trim_matches('"'));
+                }
+            }
+        }
+        false
+    }
+
+    // preprocessed and checked by naga, the same checks wgpu does when it makes the module but as an error instead
+    // of the panic of the device, so a shader edited while the game runs can be refused
+    pub fn validate(&self, name: &str, defines: &[&str]) -> anyhow::Result<String> {
+        let source = self.preprocess(name, defines)?;
+        let module = naga::front::wgsl::parse_str(&source).map_err(|e| anyhow!("{}", e.emit_to_string(&source)))?;
+        naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::all())
+            .validate(&module)
+            .map_err(|e| anyhow!("{}: {}", name, e))?;
+        Ok(source)
+    }
+
+    // create_module for the sources that can be broken (the ones read from disk), nothing reaches wgpu on an error
+    pub fn try_create_module(&self, device: &Device, label: &str, name: &str, defines: &[&str]) -> anyhow::Result<wgpu::ShaderModule> {
+        let source = self.validate(name, defines)?;
+        Ok(device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(label),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        }))
+    }
+
+    // the final wgsl of a shader, the defines are set before the first line
+    pub fn preprocess(&self, name: &str, defines: &[&str]) -> anyhow::Result<String> {
+        let mut state = PreprocessState {
+            defines: defines.iter().map(|define| define.to_string()).collect(),
+            included: HashSet::new(),
+            stack: Vec::new(),
+            output: String::new(),
+        };
+        self.expand(name, &mut state)?;
+        Ok(state.output)
+    }
+
+    // for the embedded shaders, a failure here is a bug in the engine so it panics with the reason
+    pub fn create_module(&self, device: &Device, label: &str, name: &str, defines: &[&str]) -> wgpu::ShaderModule {
+        let source = self.preprocess(name, defines).unwrap_or_else(|e| panic!("failed to preprocess {}: {:?}", name, e));
+        device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(label),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        })
+    }
+
+    fn expand(&self, name: &str, state: &mut PreprocessState) -> anyhow::Result<()> {
+        if state.stack.iter().any(|open| open == name) {
+            bail!("include cycle: {} -> {}", state.stack.join(" -> "), name);
+        }
+        if !state.included.insert(name.to_string()) {
+            return Ok(()); // already pasted
+        }
+        let source = self.sources.get(name).ok_or_else(|| anyhow!("there is no shader called {}", name))?;
+        state.stack.push(name.to_string());
+
+        // every open #if, true when its lines are kept (it is false too if a parent is false)
+        let mut conditions: Vec<Condition> = Vec::new();
+        for (index, line) in source.lines().enumerate() {
+            let at = || format!("{}:{}", name, index + 1);
+            let active = conditions.last().is_none_or(|condition| condition.active);
+            let trimmed = line.trim_start();
+
+            if !trimmed.starts_with('#') {
+                if active {
+                    state.output.push_str(line);
+                    state.output.push('\n');
+                }
+                continue;
+            }
+
+            let mut parts = trimmed.splitn(2, char::is_whitespace);
+            let directive = parts.next().unwrap_or("");
+            let argument = parts.next().unwrap_or("").trim();
+            match directive {
+                "#ifdef" | "#ifndef" => {
+                    let defined = state.defines.contains(argument);
+                    let wanted = if directive == "#ifdef" { defined } else { !defined };
+                    conditions.push(Condition { active: active && wanted, parent_active: active, in_else: false });
+                }
+                "#else" => {
+                    let condition = conditions.last_mut().ok_or_else(|| anyhow!("{}: #else without #ifdef", at()))?;
+                    if condition.in_else {
+                        bail!("{}: two #else for the same #ifdef", at());
+                    }
+                    condition.in_else = true;
+                    condition.active = condition.parent_active && !condition.active;
+                }
+                "#endif" => {
+                    conditions.pop().ok_or_else(|| anyhow!("{}: #endif without #ifdef", at()))?;
+                }
+                _ if !active => {} // the other directives are ignored inside a false block
+                "#define" => {
+                    state.defines.insert(argument.to_string());
+                }
+                "#undef" => {
+                    state.defines.remove(argument);
+                }
+                "#include" => {
+                    let include = argument.trim_matches('"');
+                    if include.is_empty() {
+                        bail!("{}: #include needs a shader name", at());
+                    }
+                    self.expand(include, state).map_err(|e| e.context(format!("included from {}", at())))?;
+                }
+                _ => bail!("{}: unknown directive {}", at(), directive),
+            }
+        }
+        if !conditions.is_empty() {
+            bail!("{}: {} #ifdef without #endif", name, conditions.len());
+        }
+
+        state.stack.pop();
+        Ok(())
+    }
+}
+
+struct PreprocessState {
+    defines: HashSet<String>,
+    included: HashSet<String>,
+    stack: Vec<String>,
+    output: String,
+}
+
+struct Condition {
+    active: bool,
+    parent_active: bool,
+    in_else: bool,
+}
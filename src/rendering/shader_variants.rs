@@ -0,0 +1,88 @@
+// one shader, many pipelines: every combination of features (normal map, fog...) is a different permutation
+// the permutations go through the ShaderManager so they reload with their file like any other pipeline. they are few,
+// so all of them are made at the start, render() only looks up the one of each material
+
+use std::collections::HashMap;
+
+use wgpu::Device;
+
+use super::model::Material;
+use super::shader_manager::{ReloadableBuilder, ShaderManager, ShaderPipelineId};
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ShaderFeatures(u32);
+
+impl ShaderFeatures {
+    pub const NONE: ShaderFeatures = ShaderFeatures(0);
+    pub const NORMAL_MAP: ShaderFeatures = ShaderFeatures(1 << 0);
+    pub const FOG: ShaderFeatures = ShaderFeatures(1 << 1);
+
+    // the name of every flag as the #define the shaders check
+    const NAMES: [(ShaderFeatures, &'static str); 2] = [
+        (ShaderFeatures::NORMAL_MAP, "NORMAL_MAP"),
+        (ShaderFeatures::FOG, "FOG"),
+    ];
+
+    pub fn contains(&self, other: ShaderFeatures) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn insert(&mut self, other: ShaderFeatures) {
+        self.0 |= other.0;
+    }
+
+    pub fn intersection(&self, other: ShaderFeatures) -> ShaderFeatures {
+        ShaderFeatures(self.0 & other.0)
+    }
+
+    pub fn defines(&self) -> Vec<&'static str> {
+        Self::NAMES.iter().filter(|(flag, _)| self.contains(*flag)).map(|(_, name)| *name).collect()
+    }
+
+    // every subset of these flags, NONE included
+    fn combinations(&self) -> impl Iterator<Item = ShaderFeatures> {
+        let all = self.0;
+        (0..=all).filter(move |bits| bits & !all == 0).map(ShaderFeatures)
+    }
+}
+
+impl std::ops::BitOr for ShaderFeatures {
+    type Output = ShaderFeatures;
+
+    fn bitor(self, other: ShaderFeatures) -> ShaderFeatures {
+        ShaderFeatures(self.0 | other.0)
+    }
+}
+
+// what a draw needs: what the material has and what the scene turned on for everything (the fog)
+pub fn draw_features(material: &Material, scene: ShaderFeatures) -> ShaderFeatures {
+    material.features | scene
+}
+
+// makes the builder of one permutation, the defines are the only thing that changes between them
+pub type VariantBuilder = Box<dyn Fn() -> ReloadableBuilder>;
+
+pub struct ShaderVariants {
+    // the features the shader actually has #ifdef for, the rest are ignored so we never make two equal variants
+    supported: ShaderFeatures,
+    pipelines: HashMap<ShaderFeatures, ShaderPipelineId>,
+}
+
+impl ShaderVariants {
+    // the defines are always there, on top of the ones of the features (CLUSTERED_LIGHTS, for example)
+    pub fn new(shaders: &mut ShaderManager, device: &Device, label: &str, shader: &str, defines: &[&str], supported: ShaderFeatures, builder: VariantBuilder) -> Self {
+        let mut pipelines = HashMap::new();
+        for features in supported.combinations() {
+            let mut all = defines.to_vec();
+            all.extend(features.defines());
+            let label = format!("{} [{}]", label, all.join(" "));
+            pipelines.insert(features, shaders.create_pipeline(device, &label, shader, &all, builder()));
+        }
+        Self { supported, pipelines }
+    }
+
+    // the pipeline for these features, the ones the shader doesn't have are left out
+    pub fn get(&self, features: ShaderFeatures) -> ShaderPipelineId {
+        self.pipelines[&features.intersection(self.supported)]
+    }
+}
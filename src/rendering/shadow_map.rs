@@ -0,0 +1,151 @@
+// the shadow of the sun: the scene is drawn from the light with an orthographic camera centered on what the player sees
+// only the depth is kept, the passes that need to know if a point is lit compare against it (the volumetric fog for now)
+
+use cgmath::{InnerSpace, SquareMatrix};
+use wgpu::{util::DeviceExt, Device, Queue};
+
+use super::camera::OPENGL_TO_WGPU_MATRIX;
+use super::model::{InstancedDraw, ModelVertex, Vertex};
+use super::shader_preprocessor::ShaderLibrary;
+use super::textures::Texture;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ShadowUniform {
+    light_view_proj: [[f32; 4]; 4],
+}
+
+pub struct ShadowMap {
+    pub texture: Texture,
+    pub extent: f32, // world units covered on each side of the focus, bigger covers more but with blurrier shadows
+    light_view_proj: cgmath::Matrix4<f32>,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl ShadowMap {
+    // the instance layout is the one of the pipelines that draw the same instance buffers
+    pub fn new(device: &Device, size: u32, instance_layout: wgpu::VertexBufferLayout) -> Self {
+        let texture = Texture::create_shadow_map(device, size, "shadow_map");
+
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Shadow Buffer"),
+            contents: bytemuck::cast_slice(&[ShadowUniform { light_view_proj: cgmath::Matrix4::identity().into() }]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("shadow_bind_group_layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("shadow_bind_group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry { binding: 0, resource: buffer.as_entire_binding() }],
+        });
+
+        let shader = ShaderLibrary::builtin().create_module(device, "Shadow Shader", "shadow.wgsl", &[]);
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Shadow Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Shadow Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[ModelVertex::desc(), instance_layout],
+            },
+            fragment: None, // only the depth
+            primitive: wgpu::PrimitiveState {
+                cull_mode: None, // open meshes would leak light with back faces culled
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                // pushes the depth a bit away from the light so the surfaces don't shadow themselves (acne)
+                bias: wgpu::DepthBiasState { constant: 2, slope_scale: 2.0, clamp: 0.0 },
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            texture,
+            extent: 30.0,
+            light_view_proj: cgmath::Matrix4::identity(),
+            buffer,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    // sun_direction points towards the sun, focus is the center of the area that gets shadows (normally the camera target)
+    pub fn update(&mut self, queue: &Queue, sun_direction: cgmath::Vector3<f32>, focus: cgmath::Point3<f32>) {
+        let sun = sun_direction.normalize();
+        // look_at breaks if the up is parallel to the view
+        let up = if sun.y.abs() > 0.99 { cgmath::Vector3::unit_z() } else { cgmath::Vector3::unit_y() };
+        let eye = focus + sun * self.extent * 2.0;
+        let view = cgmath::Matrix4::look_at_rh(eye, focus, up);
+        let projection = cgmath::ortho(-self.extent, self.extent, -self.extent, self.extent, 0.1, self.extent * 4.0);
+        self.light_view_proj = OPENGL_TO_WGPU_MATRIX * projection * view;
+
+        let uniform = ShadowUniform { light_view_proj: self.light_view_proj.into() };
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    // from world to the shadow map clip space, for the passes that read the map
+    pub fn light_view_proj(&self) -> cgmath::Matrix4<f32> {
+        self.light_view_proj
+    }
+
+    // only the geometry of the draws matters here, the materials are skipped
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, draws: &[InstancedDraw]) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Shadow Pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        for draw in draws {
+            if draw.instances.is_empty() {
+                continue;
+            }
+            render_pass.set_vertex_buffer(1, draw.buffer.slice(..));
+            for mesh in &draw.model.meshes {
+                render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+                render_pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                render_pass.draw_indexed(0..mesh.num_elements, 0, draw.instances.clone());
+            }
+        }
+    }
+}
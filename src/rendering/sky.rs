@@ -0,0 +1,318 @@
+// an analytic sky (Preetham, "A Practical Analytic Model for Daylight") instead of a fixed cubemap
+// the sun follows the day/night cycle, the same model is drawn behind the scene and gives the lighting its sun and
+// ambient colors, so the objects get the orange of the sunset and the blue of the night without more setup
+// the coefficients that depend on the sun and the turbidity are made here, the shader only evaluates the perez function
+// the strength of the sun and the light of the night follow a curve and a gradient over the hours, for the designers
+
+use std::f32::consts::PI;
+
+use cgmath::{InnerSpace, SquareMatrix};
+use wgpu::{Device, Queue};
+
+use super::camera::Camera;
+use super::lights::Lights;
+use super::shader_preprocessor::ShaderLibrary;
+use super::textures::Texture;
+use super::uniforms::{UniformBindGroup, UniformBuffer};
+use crate::util::color::Color;
+use crate::util::curve::{Curve, Gradient, Interpolation};
+
+// the light left when the sun is under the horizon, a dark blue so the night is still readable
+const NIGHT_AMBIENT: Color = Color::rgb(0.02, 0.03, 0.06);
+// a little more at midnight, the moon is up
+const MIDNIGHT_AMBIENT: Color = Color::rgb(0.03, 0.04, 0.08);
+
+// the time of the day moves the sun: it rises at 6 in the east (+x), is at its highest at 12 and sets at 18 in the west
+#[derive(Copy, Clone, Debug)]
+pub struct DayNightCycle {
+    pub hour: f32,          // from 0 to 24
+    pub day_length: f32,    // real seconds for a full day, 0 stops the clock
+    pub max_elevation: f32, // the height of the sun at noon, in radians
+}
+
+impl DayNightCycle {
+    pub fn new(hour: f32, day_length: f32) -> Self {
+        Self { hour: hour.rem_euclid(24.0), day_length, max_elevation: 65f32.to_radians() }
+    }
+
+    // it uses the simulation delta, so the day stops when the game is paused
+    pub fn update(&mut self, delta_time: f32) {
+        if self.day_length > 0.0 {
+            self.hour = (self.hour + delta_time * 24.0 / self.day_length).rem_euclid(24.0);
+        }
+    }
+
+    // the direction towards the sun, normalized
+    pub fn sun_direction(&self) -> cgmath::Vector3<f32> {
+        let angle = (self.hour - 12.0) / 24.0 * 2.0 * PI;
+        let (sin_elevation, cos_elevation) = self.max_elevation.sin_cos();
+        cgmath::Vector3::new(-angle.sin(), angle.cos() * sin_elevation, -angle.cos() * cos_elevation).normalize()
+    }
+
+    // 1 in the day, 0 at night, it fades while the sun crosses the horizon
+    pub fn daylight(&self) -> f32 {
+        smoothstep(-0.1, 0.1, self.sun_direction().y)
+    }
+}
+
+// the five perez coefficients for luminance (Y) and chromaticity (x, y)
+#[derive(Copy, Clone, Debug)]
+struct Perez {
+    a: [f32; 3],
+    b: [f32; 3],
+    c: [f32; 3],
+    d: [f32; 3],
+    e: [f32; 3],
+}
+
+impl Perez {
+    fn from_turbidity(t: f32) -> Self {
+        Self {
+            a: [0.1787 * t - 1.4630, -0.0193 * t - 0.2592, -0.0167 * t - 0.2608],
+            b: [-0.3554 * t + 0.4275, -0.0665 * t + 0.0008, -0.0950 * t + 0.0092],
+            c: [-0.0227 * t + 5.3251, -0.0004 * t + 0.2125, -0.0079 * t + 0.2102],
+            d: [0.1206 * t - 2.5771, -0.0641 * t - 0.8989, -0.0441 * t - 1.6537],
+            e: [-0.0670 * t + 0.3703, -0.0033 * t + 0.0452, -0.0109 * t + 0.0529],
+        }
+    }
+
+    // theta is the angle from the zenith to the view, gamma the angle from the view to the sun
+    fn evaluate(&self, channel: usize, theta: f32, gamma: f32) -> f32 {
+        let cos_gamma = gamma.cos();
+        (1.0 + self.a[channel] * (self.b[channel] / theta.cos().max(0.01)).exp())
+            * (1.0 + self.c[channel] * (self.d[channel] * gamma).exp() + self.e[channel] * cos_gamma * cos_gamma)
+    }
+}
+
+// luminance and chromaticity at the zenith for the sun at theta_sun from the zenith
+fn zenith(turbidity: f32, theta_sun: f32) -> [f32; 3] {
+    let t = turbidity;
+    let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_sun);
+    let luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+
+    let theta = theta_sun;
+    let theta2 = theta * theta;
+    let theta3 = theta2 * theta;
+    let x = t * t * (0.00166 * theta3 - 0.00375 * theta2 + 0.00209 * theta)
+        + t * (-0.02903 * theta3 + 0.06377 * theta2 - 0.03202 * theta + 0.00394)
+        + (0.11693 * theta3 - 0.21196 * theta2 + 0.06052 * theta + 0.25886);
+    let y = t * t * (0.00275 * theta3 - 0.00610 * theta2 + 0.00317 * theta)
+        + t * (-0.04214 * theta3 + 0.08970 * theta2 - 0.04153 * theta + 0.00516)
+        + (0.15346 * theta3 - 0.26756 * theta2 + 0.06670 * theta + 0.26688);
+    [luminance.max(0.0), x, y]
+}
+
+// the same conversion the shader does, from Yxy to linear rgb with the exposure and a soft tonemap
+fn yxy_to_rgb(value: [f32; 3], exposure: f32) -> [f32; 3] {
+    let [luminance, x, y] = value;
+    let luminance = 1.0 - (-luminance * exposure).exp();
+    let y = y.max(0.0001);
+    let big_x = x / y * luminance;
+    let big_z = (1.0 - x - y) / y * luminance;
+    [
+        (3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z).max(0.0),
+        (-0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z).max(0.0),
+        (0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z).max(0.0),
+    ]
+}
+
+fn default_night_ambient() -> Gradient {
+    let mut gradient = Gradient::new(Interpolation::Smooth);
+    gradient.add_key(0.0, MIDNIGHT_AMBIENT);
+    gradient.add_key(6.0, NIGHT_AMBIENT);
+    gradient.add_key(18.0, NIGHT_AMBIENT);
+    gradient.add_key(24.0, MIDNIGHT_AMBIENT);
+    gradient
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct SkyUniform {
+    inverse_view_proj: [[f32; 4]; 4],
+    sun_direction: [f32; 4], // w is the daylight
+    sun_color: [f32; 4],
+    ambient_color: [f32; 4],
+    perez_a: [f32; 4], // the xyz of each perez vector are the Y, x and y channels
+    perez_b: [f32; 4],
+    perez_c: [f32; 4],
+    perez_d: [f32; 4],
+    perez_e: [f32; 4],
+    zenith: [f32; 4], // zenith Yxy divided by the perez value at the zenith, w is the exposure
+    fog: [f32; 4],    // the eye of the camera and the fog density
+    near_depth: f32,  // to find the view ray in the shader, it changes with reverse z
+    _padding: [f32; 3],
+}
+
+pub struct Sky {
+    pub cycle: DayNightCycle,
+    pub turbidity: f32, // haze of the air, 2 is a very clear day and 10 a hazy one
+    pub exposure: f32,
+    pub sun_intensity: f32,
+    pub ambient_intensity: f32,
+    pub visible: bool, // false keeps the lighting but shows the clear color behind the scene
+    pub sun_curve: Curve, // the hour (0 to 24) to how strong the sun is, on top of sun_intensity
+    pub night_ambient: Gradient, // the hour to the light that is there without the sun
+    pub fog_density: f32, // the distance fog of the scene shader, 0 turns it off (the FOG variant, see shader_variants.rs)
+    sun_color: [f32; 3],
+    ambient_color: [f32; 3],
+    buffer: UniformBuffer<SkyUniform>,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl Sky {
+    // the lights of the game go in the same bind group (binding 1, and 2 to 4 for the clustered path), the sky and them
+    // are all the light of the scene
+    pub fn new(device: &Device, format: wgpu::TextureFormat, lights: &Lights) -> Self {
+        let buffer = UniformBuffer::<SkyUniform>::zeroed(device, "Sky Buffer");
+        let UniformBindGroup { layout: bind_group_layout, bind_group } = UniformBindGroup::builder("sky")
+            .uniform(&buffer, wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT)
+            .buffer(lights.buffer(), wgpu::ShaderStages::FRAGMENT)
+            .buffer(lights.cluster_uniform_buffer(), wgpu::ShaderStages::FRAGMENT)
+            .storage(lights.light_buffer(), wgpu::ShaderStages::FRAGMENT)
+            .storage(lights.cluster_buffer(), wgpu::ShaderStages::FRAGMENT)
+            .build(device);
+
+        let shader = ShaderLibrary::builtin().create_module(device, "Sky Shader", "sky.wgsl", &[]);
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Sky Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Sky Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[], // the fullscreen triangle is made in the shader
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            // it is drawn first in the main pass, it doesn't touch the depth so the scene covers it
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            cycle: DayNightCycle::new(10.0, 600.0),
+            turbidity: 2.5,
+            exposure: 0.05,
+            sun_intensity: 1.0,
+            ambient_intensity: 0.6,
+            visible: true,
+            sun_curve: Curve::constant(1.0),
+            night_ambient: default_night_ambient(),
+            fog_density: 0.0,
+            sun_color: [0.0; 3],
+            ambient_color: [NIGHT_AMBIENT.r, NIGHT_AMBIENT.g, NIGHT_AMBIENT.b],
+            buffer,
+            bind_group_layout,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    pub fn update(&mut self, queue: &Queue, camera: &Camera, delta_time: f32) {
+        self.cycle.update(delta_time);
+
+        let turbidity = self.turbidity.clamp(1.7, 10.0); // the fit of the model is only good in this range
+        let sun = self.cycle.sun_direction();
+        let daylight = self.cycle.daylight();
+        // the model breaks with the sun under the horizon, we keep it just above and fade everything with the daylight
+        let theta_sun = sun.y.max(0.02).acos();
+
+        let perez = Perez::from_turbidity(turbidity);
+        let zenith = zenith(turbidity, theta_sun);
+        let mut normalized = [0.0; 3];
+        for channel in 0..3 {
+            normalized[channel] = zenith[channel] / perez.evaluate(channel, 0.0, theta_sun);
+        }
+        let sky_at = |theta: f32, gamma: f32| {
+            let value = [0, 1, 2].map(|channel| normalized[channel] * perez.evaluate(channel, theta, gamma));
+            yxy_to_rgb(value, self.exposure).map(|component| component * daylight)
+        };
+
+        // the sun takes the color of the sky around it (white at noon, orange at sunset)
+        let around_sun = sky_at(theta_sun, 0.0);
+        let brightest = around_sun.iter().cloned().fold(0.0001, f32::max);
+        let sun_strength = self.sun_intensity * self.sun_curve.evaluate(self.cycle.hour).max(0.0);
+        let sun_color = around_sun.map(|component| component / brightest * daylight * sun_strength);
+
+        // the ambient is the average of the sky straight up and around the horizon
+        let mut ambient = sky_at(0.0, theta_sun);
+        for step in 0..4 {
+            let azimuth = step as f32 * PI * 0.5;
+            let direction = cgmath::Vector3::new(azimuth.cos(), 0.2, azimuth.sin()).normalize();
+            let sky = sky_at(direction.y.acos(), direction.dot(sun).clamp(-1.0, 1.0).acos());
+            for channel in 0..3 {
+                ambient[channel] += sky[channel];
+            }
+        }
+        let night = self.night_ambient.evaluate(self.cycle.hour);
+        let night = [night.r, night.g, night.b];
+        let ambient = [0, 1, 2].map(|channel| ambient[channel] / 5.0 * self.ambient_intensity + night[channel]);
+
+        self.sun_color = sun_color;
+        self.ambient_color = ambient;
+
+        let inverse_view_proj = camera.view_projection_matrix().invert().unwrap_or(cgmath::Matrix4::identity());
+        self.buffer.set(queue, SkyUniform {
+            inverse_view_proj: inverse_view_proj.into(),
+            sun_direction: [sun.x, sun.y, sun.z, daylight],
+            sun_color: [sun_color[0], sun_color[1], sun_color[2], 1.0],
+            ambient_color: [ambient[0], ambient[1], ambient[2], 1.0],
+            perez_a: [perez.a[0], perez.a[1], perez.a[2], 0.0],
+            perez_b: [perez.b[0], perez.b[1], perez.b[2], 0.0],
+            perez_c: [perez.c[0], perez.c[1], perez.c[2], 0.0],
+            perez_d: [perez.d[0], perez.d[1], perez.d[2], 0.0],
+            perez_e: [perez.e[0], perez.e[1], perez.e[2], 0.0],
+            zenith: [normalized[0], normalized[1], normalized[2], self.exposure],
+            fog: [camera.eye.x, camera.eye.y, camera.eye.z, self.fog_density.max(0.0)],
+            near_depth: if camera.reverse_z { 1.0 } else { 0.0 },
+            _padding: [0.0; 3],
+        });
+    }
+
+    // the light of the last update, for the systems that light things outside the main pass (fog, particles)
+    pub fn sun_color(&self) -> [f32; 3] {
+        self.sun_color
+    }
+
+    pub fn ambient_color(&self) -> [f32; 3] {
+        self.ambient_color
+    }
+
+    // draws the sky behind everything, call it first in a pass that has the scene depth attached
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        if !self.visible {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
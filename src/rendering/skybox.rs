@@ -0,0 +1,174 @@
+// a cubemap as the background of the scene, for the games that want a painted sky (or space, or a room) instead of
+// the analytic one in sky.rs. the lighting still comes from the Sky, this only changes what is seen behind the scene
+// nothing is drawn until a cubemap is set, see Texture::load_cubemap for the files it takes
+
+use bytemuck::Zeroable;
+use cgmath::{Matrix4, Rad};
+use wgpu::{util::DeviceExt, Device, Queue};
+
+use super::camera::Camera;
+use super::shader_preprocessor::ShaderLibrary;
+use super::textures::Texture;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct SkyboxUniform {
+    view_proj: [[f32; 4]; 4],
+    params: [f32; 4], // x = intensity, y = the depth of the far plane
+}
+
+pub struct Skybox {
+    pub intensity: f32, // the scene target is hdr, above 1 the skybox can go through the bloom
+    pub rotation: f32,  // around the y axis in radians, to turn the sun of the images to where the real one is
+    pub visible: bool,
+    cubemap: Option<Texture>,
+    buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: Option<wgpu::BindGroup>,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl Skybox {
+    // the depth test follows the camera, reverse z flips the far plane and the comparison
+    pub fn new(device: &Device, format: wgpu::TextureFormat, camera: &Camera) -> Self {
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Skybox Buffer"),
+            contents: bytemuck::cast_slice(&[SkyboxUniform::zeroed()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("skybox_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::Cube,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let shader = ShaderLibrary::builtin().create_module(device, "Skybox Shader", "skybox.wgsl", &[]);
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Skybox Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Skybox Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[], // the cube is made in the shader
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            // it sits on the far plane, the pixels where the depth is still the clear value pass the test (hence the
+            // equal) and the rest is already covered by the scene, it never writes so the later passes see the scene
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: if camera.reverse_z { wgpu::CompareFunction::GreaterEqual } else { wgpu::CompareFunction::LessEqual },
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            intensity: 1.0,
+            rotation: 0.0,
+            visible: true,
+            cubemap: None,
+            buffer,
+            bind_group_layout,
+            bind_group: None,
+            pipeline,
+        }
+    }
+
+    // replaces the cubemap, it has to be made with Texture::cubemap_from_images (or load_cubemap)
+    pub fn set_cubemap(&mut self, device: &Device, cubemap: Texture) {
+        self.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("skybox_bind_group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: self.buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&cubemap.view) },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::Sampler(&cubemap.sampler) },
+            ],
+        }));
+        self.cubemap = Some(cubemap);
+    }
+
+    // back to the clear color (or the analytic sky)
+    pub fn clear_cubemap(&mut self) {
+        self.bind_group = None;
+        self.cubemap = None;
+    }
+
+    // true when draw will show something, the analytic sky doesn't need to be drawn under it
+    pub fn is_active(&self) -> bool {
+        self.visible && self.cubemap.is_some()
+    }
+
+    pub fn update(&self, queue: &Queue, camera: &Camera) {
+        if !self.is_active() {
+            return;
+        }
+        // the view with the position removed, so the cube moves with the camera and only turns
+        // it is meant for the perspective cameras, an orthographic one sees the cube as a small box in the middle
+        let mut view = Matrix4::look_at_rh(camera.eye, camera.target, camera.up);
+        view.w = cgmath::Vector4::new(0.0, 0.0, 0.0, 1.0);
+        let view_proj = camera.build_projection_matrix() * view * Matrix4::from_angle_y(Rad(self.rotation));
+        let uniform = SkyboxUniform {
+            view_proj: view_proj.into(),
+            params: [self.intensity, camera.depth_clear_value(), 0.0, 0.0],
+        };
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    // call it after the opaque meshes in a pass that has the scene depth attached, before anything transparent
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        if !self.visible {
+            return;
+        }
+        let Some(bind_group) = &self.bind_group else { return };
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.draw(0..36, 0..1);
+    }
+}
@@ -0,0 +1,498 @@
+// the 2D mode: an orthographic camera, texture atlases and a sprite batcher
+// sprites are collected during the frame, sorted by layer and drawn with one instanced draw per atlas run
+
+use std::mem;
+use std::sync::Arc;
+
+use wgpu::{util::DeviceExt, Device, Queue};
+
+use super::camera::OPENGL_TO_WGPU_MATRIX;
+use super::textures::Texture;
+use super::shader_preprocessor::ShaderLibrary;
+use super::upload_queue::UploadQueue;
+use crate::util::color::Color;
+
+// 2D camera, one world unit is one pixel at zoom 1 and y goes down like on the screen
+#[derive(Copy, Clone, Debug)]
+pub struct OrthographicCamera {
+    pub position: cgmath::Vector2<f32>, // the world point in the middle of the screen
+    pub zoom: f32,
+    pub viewport_width: f32,
+    pub viewport_height: f32,
+    pub pixel_perfect: bool, // snaps the camera and sprites to whole screen pixels, for pixel art
+}
+
+impl OrthographicCamera {
+    pub fn new(viewport_width: f32, viewport_height: f32) -> Self {
+        Self {
+            position: cgmath::Vector2::new(0.0, 0.0),
+            zoom: 1.0,
+            viewport_width,
+            viewport_height,
+            pixel_perfect: false,
+        }
+    }
+
+    pub fn build_view_projection_matrix(&self) -> cgmath::Matrix4<f32> {
+        let position = if self.pixel_perfect { self.snap(self.position) } else { self.position };
+        let half_width = self.viewport_width * 0.5 / self.zoom;
+        let half_height = self.viewport_height * 0.5 / self.zoom;
+        // bottom is bigger than top so y grows downwards
+        let proj = cgmath::ortho(
+            position.x - half_width,
+            position.x + half_width,
+            position.y + half_height,
+            position.y - half_height,
+            -1.0,
+            1.0,
+        );
+        OPENGL_TO_WGPU_MATRIX * proj
+    }
+
+    // rounds a world position to the closest screen pixel
+    pub fn snap(&self, position: cgmath::Vector2<f32>) -> cgmath::Vector2<f32> {
+        let zoom = self.zoom.max(f32::EPSILON);
+        cgmath::Vector2::new((position.x * zoom).round() / zoom, (position.y * zoom).round() / zoom)
+    }
+}
+
+// a rectangle of the atlas in uv (0 to 1)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AtlasRegion {
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+}
+
+impl AtlasRegion {
+    // flipped regions are useful to mirror a character without another image
+    pub fn flipped_x(&self) -> Self {
+        Self { min: [self.max[0], self.min[1]], max: [self.min[0], self.max[1]] }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AtlasHandle(usize);
+
+pub struct TextureAtlas {
+    _texture: Texture, // only its view is in the bind group, this keeps the image alive
+    bind_group: wgpu::BindGroup,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Sprite {
+    pub atlas: AtlasHandle,
+    pub region: AtlasRegion,
+    pub position: cgmath::Vector2<f32>,
+    pub size: cgmath::Vector2<f32>,
+    pub rotation: f32, // radians
+    pub color: Color,  // multiplies the texture
+    pub layer: i32,    // lower layers are drawn first
+}
+
+impl Sprite {
+    pub fn new(atlas: AtlasHandle, region: AtlasRegion, position: cgmath::Vector2<f32>, size: cgmath::Vector2<f32>) -> Self {
+        Self { atlas, region, position, size, rotation: 0.0, color: Color::WHITE, layer: 0 }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct SpriteInstance {
+    position: [f32; 2],
+    size: [f32; 2],
+    rotation: f32,
+    uv_rect: [f32; 4],
+    color: [f32; 4],
+}
+
+impl SpriteInstance {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<SpriteInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[
+                wgpu::VertexAttribute { offset: 0, shader_location: 0, format: wgpu::VertexFormat::Float32x2 },
+                wgpu::VertexAttribute { offset: mem::size_of::<[f32; 2]>() as wgpu::BufferAddress, shader_location: 1, format: wgpu::VertexFormat::Float32x2 },
+                wgpu::VertexAttribute { offset: mem::size_of::<[f32; 4]>() as wgpu::BufferAddress, shader_location: 2, format: wgpu::VertexFormat::Float32 },
+                wgpu::VertexAttribute { offset: mem::size_of::<[f32; 5]>() as wgpu::BufferAddress, shader_location: 3, format: wgpu::VertexFormat::Float32x4 },
+                wgpu::VertexAttribute { offset: mem::size_of::<[f32; 9]>() as wgpu::BufferAddress, shader_location: 4, format: wgpu::VertexFormat::Float32x4 },
+            ],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct SpriteCameraUniform {
+    view_proj: [[f32; 4]; 4],
+}
+
+// a run of sprites of the same atlas and layer inside the instance buffer
+struct SpriteBatch {
+    atlas: AtlasHandle,
+    layer: i32,
+    instances: std::ops::Range<u32>,
+}
+
+// sprites that never change (tilemap chunks, backgrounds), uploaded once to their own buffer
+struct StaticSpriteBatch {
+    atlas: AtlasHandle,
+    layer: i32,
+    buffer: Arc<wgpu::Buffer>, // shared with the upload queue while it is going up
+    count: u32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct StaticBatchHandle(usize);
+
+pub struct SpriteRenderer {
+    pub camera: OrthographicCamera,
+    pipeline: wgpu::RenderPipeline,
+    counting_pipeline: Option<wgpu::RenderPipeline>, // the overdraw debug view, made the first time it is used
+    target_format: wgpu::TextureFormat,
+    camera_bind_group_layout: wgpu::BindGroupLayout,
+    atlas_bind_group_layout: wgpu::BindGroupLayout,
+    camera_buffer: wgpu::Buffer,
+    camera_bind_group: wgpu::BindGroup,
+    instance_buffer: wgpu::Buffer,
+    instance_capacity: usize,
+    atlases: Vec<TextureAtlas>,
+    queued: Vec<Sprite>,
+    batches: Vec<SpriteBatch>,
+    static_batches: Vec<Option<StaticSpriteBatch>>,
+}
+
+impl SpriteRenderer {
+    pub fn new(device: &Device, config: &wgpu::SurfaceConfiguration, target_format: wgpu::TextureFormat) -> Self {
+        let camera = OrthographicCamera::new(config.width as f32, config.height as f32);
+
+        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Sprite Camera Buffer"),
+            contents: bytemuck::cast_slice(&[SpriteCameraUniform { view_proj: camera.build_view_projection_matrix().into() }]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let camera_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("sprite_camera_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("sprite_camera_bind_group"),
+            layout: &camera_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let atlas_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("sprite_atlas_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline = Self::create_pipeline(device, &[&camera_bind_group_layout, &atlas_bind_group_layout], target_format, &[]);
+
+        let instance_capacity = 256;
+        let instance_buffer = Self::create_instance_buffer(device, instance_capacity);
+
+        Self {
+            camera,
+            pipeline,
+            counting_pipeline: None,
+            target_format,
+            camera_bind_group_layout,
+            atlas_bind_group_layout,
+            camera_buffer,
+            camera_bind_group,
+            instance_buffer,
+            instance_capacity,
+            atlases: Vec::new(),
+            queued: Vec::new(),
+            batches: Vec::new(),
+            static_batches: Vec::new(),
+        }
+    }
+
+    // the counting one (COUNT_OVERDRAW) has the overdraw counter as a third group and writes no color
+    fn create_pipeline(device: &Device, layouts: &[&wgpu::BindGroupLayout], target_format: wgpu::TextureFormat, defines: &[&str]) -> wgpu::RenderPipeline {
+        let counting = defines.contains(&"COUNT_OVERDRAW");
+        let shader = ShaderLibrary::builtin().create_module(device, "Sprite Shader", "sprite.wgsl", defines);
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Sprite Pipeline Layout"),
+            bind_group_layouts: layouts,
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Sprite Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[SpriteInstance::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: target_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: if counting { wgpu::ColorWrites::empty() } else { wgpu::ColorWrites::ALL },
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                cull_mode: None, // flipped sprites have their triangles the other way around
+                ..Default::default()
+            },
+            depth_stencil: None, // the layers give the order, so no depth test
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    }
+
+    // makes the pipeline of render_counting_overdraw, once
+    pub fn enable_overdraw_count(&mut self, device: &Device, counter_layout: &wgpu::BindGroupLayout) {
+        if self.counting_pipeline.is_none() {
+            let layouts = [&self.camera_bind_group_layout, &self.atlas_bind_group_layout, counter_layout];
+            self.counting_pipeline = Some(Self::create_pipeline(device, &layouts, self.target_format, &["COUNT_OVERDRAW"]));
+        }
+    }
+
+    fn create_instance_buffer(device: &Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Sprite Instance Buffer"),
+            size: (capacity * mem::size_of::<SpriteInstance>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    // pixel art wants nearest filtering, smooth sprites want linear
+    pub fn add_atlas(&mut self, device: &Device, texture: Texture, nearest: bool) -> AtlasHandle {
+        let filter = if nearest { wgpu::FilterMode::Nearest } else { wgpu::FilterMode::Linear };
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: filter,
+            min_filter: filter,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("sprite_atlas_bind_group"),
+            layout: &self.atlas_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        self.atlases.push(TextureAtlas { _texture: texture, bind_group });
+        AtlasHandle(self.atlases.len() - 1)
+    }
+
+    pub fn load_atlas(&mut self, device: &Device, queue: &Queue, bytes: &[u8], label: &str, nearest: bool) -> anyhow::Result<AtlasHandle> {
+        let image = image::load_from_memory(bytes)?;
+        let texture = Texture::from_image(&image, device, queue, Some(label))?;
+        Ok(self.add_atlas(device, texture, nearest))
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.camera.viewport_width = width as f32;
+        self.camera.viewport_height = height as f32;
+    }
+
+    // queues a sprite for this frame
+    pub fn draw(&mut self, sprite: Sprite) {
+        self.queued.push(sprite);
+    }
+
+    // sorts the queued sprites, uploads them and builds the batches, call once per frame before render
+    pub fn prepare(&mut self, device: &Device, queue: &Queue) {
+        queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[SpriteCameraUniform {
+            view_proj: self.camera.build_view_projection_matrix().into(),
+        }]));
+
+        self.batches.clear();
+        if self.queued.is_empty() {
+            return;
+        }
+
+        // stable sort, inside a layer the sprites keep the order they were drawn in
+        self.queued.sort_by_key(|sprite| (sprite.layer, sprite.atlas.0));
+
+        let camera = self.camera;
+        let instances: Vec<SpriteInstance> = self.queued.iter().map(|sprite| to_instance(&camera, sprite)).collect();
+
+        for (i, sprite) in self.queued.iter().enumerate() {
+            match self.batches.last_mut() {
+                Some(batch) if batch.atlas == sprite.atlas && batch.layer == sprite.layer => batch.instances.end = i as u32 + 1,
+                _ => self.batches.push(SpriteBatch { atlas: sprite.atlas, layer: sprite.layer, instances: i as u32..i as u32 + 1 }),
+            }
+        }
+
+        if instances.len() > self.instance_capacity {
+            self.instance_capacity = instances.len().next_power_of_two();
+            self.instance_buffer = Self::create_instance_buffer(device, self.instance_capacity);
+        }
+        queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&instances));
+
+        self.queued.clear();
+    }
+
+    // uploads a group of sprites that all use the same atlas and layer, they are drawn every frame until removed
+    // the pixel perfect snap is applied now, so static sprites should already be on whole pixels
+    // the instances go up through the upload queue, until they get there the buffer is zeros and nothing is drawn
+    pub fn add_static_batch(&mut self, device: &Device, uploads: &mut UploadQueue, atlas: AtlasHandle, layer: i32, sprites: &[Sprite]) -> StaticBatchHandle {
+        let camera = self.camera;
+        let instances: Vec<SpriteInstance> = sprites.iter().map(|sprite| to_instance(&camera, sprite)).collect();
+        let buffer = Arc::new(device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Static Sprite Buffer"),
+            size: (instances.len() * mem::size_of::<SpriteInstance>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        }));
+        if !instances.is_empty() {
+            if let Err(e) = uploads.upload_buffer(buffer.clone(), 0, bytemuck::cast_slice(&instances).to_vec()) {
+                eprintln!("{}", e);
+            }
+        }
+
+        let batch = StaticSpriteBatch { atlas, layer, buffer, count: instances.len() as u32 };
+        // reuse a free slot so the handles of other batches stay the same
+        match self.static_batches.iter().position(|b| b.is_none()) {
+            Some(index) => {
+                self.static_batches[index] = Some(batch);
+                StaticBatchHandle(index)
+            }
+            None => {
+                self.static_batches.push(Some(batch));
+                StaticBatchHandle(self.static_batches.len() - 1)
+            }
+        }
+    }
+
+    pub fn remove_static_batch(&mut self, handle: StaticBatchHandle) {
+        if let Some(slot) = self.static_batches.get_mut(handle.0) {
+            *slot = None;
+        }
+    }
+
+    pub fn has_work(&self) -> bool {
+        !self.batches.is_empty() || self.static_batches.iter().flatten().any(|b| b.count > 0)
+    }
+
+    // draws on top of what the target already has
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        self.render_with(encoder, target, &self.pipeline, None);
+    }
+
+    // the same draws but they only add to the overdraw counter, the target is not touched
+    // enable_overdraw_count has to be called before, without it nothing is counted
+    pub fn render_counting_overdraw(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView, counter: &wgpu::BindGroup) {
+        if let Some(pipeline) = &self.counting_pipeline {
+            self.render_with(encoder, target, pipeline, Some(counter));
+        }
+    }
+
+    fn render_with(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView, pipeline: &wgpu::RenderPipeline, counter: Option<&wgpu::BindGroup>) {
+        if !self.has_work() {
+            return;
+        }
+
+        // the static batches are mixed with the dynamic ones by layer, on the same layer the static ones go first
+        let mut static_batches: Vec<&StaticSpriteBatch> = self.static_batches.iter().flatten().filter(|b| b.count > 0).collect();
+        static_batches.sort_by_key(|b| b.layer);
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Sprite Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+        if let Some(counter) = counter {
+            render_pass.set_bind_group(2, counter, &[]);
+        }
+
+        let mut next_static = 0;
+        for batch in &self.batches {
+            while next_static < static_batches.len() && static_batches[next_static].layer <= batch.layer {
+                let static_batch = static_batches[next_static];
+                render_pass.set_bind_group(1, &self.atlases[static_batch.atlas.0].bind_group, &[]);
+                render_pass.set_vertex_buffer(0, static_batch.buffer.slice(..));
+                render_pass.draw(0..6, 0..static_batch.count);
+                next_static += 1;
+            }
+            render_pass.set_bind_group(1, &self.atlases[batch.atlas.0].bind_group, &[]);
+            render_pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+            render_pass.draw(0..6, batch.instances.clone());
+        }
+        for static_batch in &static_batches[next_static..] {
+            render_pass.set_bind_group(1, &self.atlases[static_batch.atlas.0].bind_group, &[]);
+            render_pass.set_vertex_buffer(0, static_batch.buffer.slice(..));
+            render_pass.draw(0..6, 0..static_batch.count);
+        }
+    }
+}
+
+fn to_instance(camera: &OrthographicCamera, sprite: &Sprite) -> SpriteInstance {
+    let position = if camera.pixel_perfect { camera.snap(sprite.position) } else { sprite.position };
+    SpriteInstance {
+        position: position.into(),
+        size: sprite.size.into(),
+        rotation: sprite.rotation,
+        uv_rect: [sprite.region.min[0], sprite.region.min[1], sprite.region.max[0], sprite.region.max[1]],
+        color: sprite.color.to_array(),
+    }
+}
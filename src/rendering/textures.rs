@@ -1,7 +1,13 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
 use image::{DynamicImage, GenericImageView};
 use wgpu::{Device, Extent3d, Queue, Sampler, TextureView};
 use anyhow::*;
 
+use crate::util::vfs;
+
 pub struct Texture {
     pub texture: wgpu::Texture,
     pub view: TextureView,
@@ -11,13 +17,66 @@ pub struct Texture {
 impl Texture {
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float; // this is the format the depth will get into the render pipeline
     
-    pub fn create_depth_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, label: &str) -> Self {
-        let size = wgpu::Extent3d { // the depth texture needs to be the same size of our screen (like our surface)
-            width: config.width,
-            height: config.height,
+    // a color texture we can render into and sample later, for offscreen passes and post processing
+    pub fn create_render_target(device: &wgpu::Device, width: u32, height: u32, format: wgpu::TextureFormat, label: &str) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self { texture, view, sampler }
+    }
+
+    // an empty srgb texture the cpu writes every frame (see external_texture.rs)
+    pub fn create_dynamic(device: &wgpu::Device, width: u32, height: u32, label: &str) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self { texture, view, sampler }
+    }
+
+    // a depth texture for targets that are not the surface (offscreen renders, thumbnails, etc)
+    pub fn create_depth_texture_sized(device: &wgpu::Device, width: u32, height: u32, label: &str) -> Self {
+        let size = wgpu::Extent3d {
+            width,
+            height,
             depth_or_array_layers: 1,
         };
-        let desc = wgpu::TextureDescriptor {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
             label: Some(label),
             size,
             mip_level_count: 1,
@@ -26,26 +85,44 @@ impl Texture {
             format: Self::DEPTH_FORMAT,
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
             view_formats: &[],
-        };
-        let texture = device.create_texture(&desc);
-
+        });
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
 
-        // we tecnically do not need to sample our depth texture, but if we want to display it is needed to be like this
-        let sampler = device.create_sampler(
-            &wgpu::SamplerDescriptor {
-                address_mode_u: wgpu::AddressMode::ClampToEdge,
-                address_mode_v: wgpu::AddressMode::ClampToEdge,
-                address_mode_w: wgpu::AddressMode::ClampToEdge,
-                mag_filter: wgpu::FilterMode::Linear,
-                min_filter: wgpu::FilterMode::Linear,
-                mipmap_filter: wgpu::FilterMode::Nearest,
-                compare: Some(wgpu::CompareFunction::LessEqual),
-                lod_min_clamp: 0.0,
-                lod_max_clamp: 100.0,
-                ..Default::default()
-            }
-        );
+        Self { texture, view, sampler }
+    }
+
+    // a square depth texture rendered from a light, its sampler compares so the shaders get the lit amount directly
+    pub fn create_shadow_map(device: &wgpu::Device, size: u32, label: &str) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d { width: size, height: size, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
 
         Self { texture, view, sampler }
     }
@@ -95,6 +172,21 @@ impl Texture {
     }
 
     pub fn from_image(image: &DynamicImage, device: &Device, queue: &Queue, label: Option<&str>) -> Result<Self> {
+        Self::from_image_with_format(image, device, queue, label, wgpu::TextureFormat::Rgba8UnormSrgb)
+    }
+
+    // a normal map stores directions and not colors, so it is read without the srgb curve
+    pub fn normal_map_from_image(image: &DynamicImage, device: &Device, queue: &Queue, label: Option<&str>) -> Result<Self> {
+        Self::from_image_with_format(image, device, queue, label, wgpu::TextureFormat::Rgba8Unorm)
+    }
+
+    // the normal map of the materials that don't have one, every pixel points straight out of the surface
+    pub fn flat_normal_map(device: &Device, queue: &Queue) -> Result<Self> {
+        let image = DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(1, 1, image::Rgba([128, 128, 255, 255])));
+        Self::normal_map_from_image(&image, device, queue, Some("flat normal map"))
+    }
+
+    fn from_image_with_format(image: &DynamicImage, device: &Device, queue: &Queue, label: Option<&str>, format: wgpu::TextureFormat) -> Result<Self> {
         let rgba = image.to_rgba8(); // transform the image to an array of rgba bytes
         let dimensions = image.dimensions(); // get the size/dimensions of the image
 
@@ -111,10 +203,10 @@ impl Texture {
                 mip_level_count: 1,
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::Rgba8UnormSrgb, // we store the image as sRGB
+                format, // the colors are stored as sRGB, the normal maps as they are
                 // texture_binding tells wgpu that this texture will be used in shaders and the copy_dst means that we will copy data to this texture
                 usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST, 
-                label: Some("diffuse_texture"),
+                label: Some(label.unwrap_or("diffuse_texture")),
                 view_formats: &[],
             }
         );
@@ -149,4 +241,166 @@ impl Texture {
 
         Ok(Self { texture, view, sampler })
     }
-}
\ No newline at end of file
+
+    // the six faces of a cubemap in the order the gpu takes them: +x, -x, +y, -y, +z, -z
+    // they have to be square and all of the same size, the view is a cube so the shaders sample it with a direction
+    pub fn cubemap_from_images(faces: &[DynamicImage; 6], device: &Device, queue: &Queue, label: &str) -> Result<Self> {
+        let size = faces[0].dimensions();
+        if size.0 != size.1 {
+            bail!("{}: the faces of a cubemap have to be square, the first one is {}x{}", label, size.0, size.1);
+        }
+        if let Some(face) = faces.iter().position(|face| face.dimensions() != size) {
+            bail!("{}: face {} is {:?} and the first one is {:?}", label, CUBEMAP_FACES[face], faces[face].dimensions(), size);
+        }
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: Extent3d { width: size.0, height: size.1, depth_or_array_layers: 6 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        // every face is one layer of the texture
+        for (layer, face) in faces.iter().enumerate() {
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d { x: 0, y: 0, z: layer as u32 },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &face.to_rgba8(),
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * size.0),
+                    rows_per_image: Some(size.1),
+                },
+                Extent3d { width: size.0, height: size.1, depth_or_array_layers: 1 },
+            );
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some(label),
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        });
+        // linear so the seams between the faces don't show
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Ok(Self { texture, view, sampler })
+    }
+
+    // a folder with one image per face named like CUBEMAP_FACES (right.png, left.png, ...), the extension is given
+    pub fn load_cubemap(device: &Device, queue: &Queue, folder: impl AsRef<Path>, extension: &str) -> Result<Self> {
+        let folder = folder.as_ref();
+        let mut faces = Vec::with_capacity(6);
+        for name in CUBEMAP_FACES {
+            let path = vfs::resolve(folder.join(name).with_extension(extension));
+            let face = image::open(&path).with_context(|| format!("the cubemap face {} can't be loaded", path.display()))?;
+            faces.push(face);
+        }
+        let faces: [DynamicImage; 6] = faces.try_into().map_err(|_| anyhow!("a cubemap has six faces"))?;
+        Self::cubemap_from_images(&faces, device, queue, &folder.display().to_string())
+    }
+}
+
+// the file names of the faces for load_cubemap, in the order of the layers (+x, -x, +y, -y, +z, -z)
+pub const CUBEMAP_FACES: [&str; 6] = ["right", "left", "top", "bottom", "front", "back"];
+
+// a texture of the manager, it stays valid while the manager lives
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TextureHandle(usize);
+
+struct ManagedTexture {
+    path: PathBuf,
+    texture: Arc<Texture>,
+}
+
+// the images of the assets directory loaded at runtime, each file is read and uploaded once and every material
+// that asks for the same path gets the same gpu texture (materials keep an Arc of it, see Material::new)
+pub struct TextureManager {
+    root: PathBuf,
+    textures: Vec<ManagedTexture>,
+    by_path: HashMap<PathBuf, TextureHandle>,
+}
+
+impl TextureManager {
+    // paths given to load are relative to root, "./assets" for the game
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into(), textures: Vec::new(), by_path: HashMap::new() }
+    }
+
+    // the file that is read for the path: the one of the mod that replaces it if there is one, that is also the one
+    // the watcher follows
+    pub fn resolve(&self, path: impl AsRef<Path>) -> PathBuf {
+        vfs::resolve(self.root.join(path))
+    }
+
+    // a texture made somewhere else for the path (the placeholder of an asset that is still loading, see assets.rs),
+    // find gives it from now on. a path that was already loaded keeps its texture
+    pub fn insert(&mut self, path: impl AsRef<Path>, texture: Texture) -> TextureHandle {
+        if let Some(handle) = self.find(&path) {
+            return handle;
+        }
+        let handle = TextureHandle(self.textures.len());
+        self.textures.push(ManagedTexture { path: self.resolve(&path), texture: Arc::new(texture) });
+        self.by_path.insert(self.root.join(path), handle);
+        handle
+    }
+
+    // the file read and uploaded, without the cache, any thread can call it
+    pub fn read(device: &Device, queue: &Queue, path: &Path) -> Result<Texture> {
+        let bytes = std::fs::read(path).with_context(|| format!("couldn't read the texture {}", path.display()))?;
+        crate::util::content_hash::verify_load(path, &bytes)?;
+        let image = image::load_from_memory(&bytes).with_context(|| format!("{} is not an image we can load", path.display()))?;
+        Texture::from_image(&image, device, queue, path.to_str())
+    }
+
+    pub fn find(&self, path: impl AsRef<Path>) -> Option<TextureHandle> {
+        self.by_path.get(&self.root.join(path)).copied()
+    }
+
+    // a new owner of the texture, for materials
+    pub fn shared(&self, handle: TextureHandle) -> Arc<Texture> {
+        self.textures[handle.0].texture.clone()
+    }
+
+    // reads the file again (it changed on disk), the handle stays the same but the materials made before keep the old
+    // texture, it returns that one so they can be found and moved to the new one (see SceneGraph::replace_texture)
+    pub fn reload(&mut self, device: &Device, queue: &Queue, handle: TextureHandle) -> Result<Arc<Texture>> {
+        let texture = Self::read(device, queue, &self.textures[handle.0].path)?;
+        Ok(self.replace(handle, texture))
+    }
+
+    // the same as reload with a texture made somewhere else, it returns the old one
+    pub fn replace(&mut self, handle: TextureHandle, texture: Texture) -> Arc<Texture> {
+        let managed = &mut self.textures[handle.0];
+        std::mem::replace(&mut managed.texture, Arc::new(texture))
+    }
+
+    // the texture of the file on disk (with the root already in it, or the one of the mod), for the file watcher
+    pub fn find_loaded(&self, path: &Path) -> Option<TextureHandle> {
+        self.textures.iter().position(|managed| managed.path == path).map(TextureHandle)
+    }
+
+    // every loaded file with its handle
+    pub fn paths(&self) -> impl Iterator<Item = (TextureHandle, &Path)> {
+        self.textures.iter().enumerate().map(|(index, managed)| (TextureHandle(index), managed.path.as_path()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.textures.len()
+    }
+}
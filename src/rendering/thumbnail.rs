@@ -0,0 +1,188 @@
+// renders a model into a small offscreen texture so asset browsers can show a preview of it
+// it has its own pipeline and camera, so it doesn't touch anything of the main render
+
+use cgmath::InnerSpace;
+use wgpu::{util::DeviceExt, BindGroupLayoutDescriptor, Device, Queue};
+
+use super::{camera::{Camera, CameraUniform, Projection}, model::{DrawModel, Model, ModelVertex, Vertex}, readback, textures::Texture};
+use super::shader_preprocessor::ShaderLibrary;
+
+pub const THUMBNAIL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+pub struct Thumbnail {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>, // tightly packed rgba8, width * height * 4 bytes
+}
+
+pub struct ThumbnailRenderer {
+    pipeline: wgpu::RenderPipeline,
+    camera_buffer: wgpu::Buffer,
+    camera_bind_group: wgpu::BindGroup,
+}
+
+impl ThumbnailRenderer {
+    // the texture layout has to be the same one used to load the model, since we reuse the material bind groups
+    pub fn new(device: &Device, texture_bind_group_layout: &wgpu::BindGroupLayout) -> Self {
+        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Thumbnail Camera Buffer"),
+            contents: bytemuck::cast_slice(&[CameraUniform::new()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let camera_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("thumbnail_camera_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("thumbnail_camera_bind_group"),
+            layout: &camera_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let shader = ShaderLibrary::builtin().create_module(device, "Thumbnail Shader", "thumbnail.wgsl", &[]);
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Thumbnail Pipeline Layout"),
+            bind_group_layouts: &[texture_bind_group_layout, &camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Thumbnail Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[ModelVertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: THUMBNAIL_FORMAT,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+
+        Self { pipeline, camera_buffer, camera_bind_group }
+    }
+
+    // center and radius describe the sphere the model fits in, the camera is placed so the whole sphere is visible
+    // this blocks until the gpu finished, so call it from loading screens or the asset browser, not every frame
+    pub fn render(&self, device: &Device, queue: &Queue, model: &Model, center: cgmath::Point3<f32>, radius: f32, size: u32) -> Thumbnail {
+        let fovy: f32 = 45.0;
+        // distance needed for the bounding sphere to touch the borders of the view
+        let distance = radius / (fovy.to_radians() * 0.5).sin();
+        let direction = cgmath::Vector3::<f32>::new(0.6, 0.45, 1.0).normalize();
+
+        let camera = Camera {
+            eye: center + direction * distance,
+            target: center,
+            up: cgmath::Vector3::unit_y(),
+            aspect: 1.0,
+            fovy,
+            znear: (distance - radius).max(0.01) * 0.5,
+            zfar: distance + radius * 2.0,
+            projection: Projection::Perspective,
+            reverse_z: false,
+        };
+        let mut uniform = CameraUniform::new();
+        uniform.update_view_proj(&camera);
+        queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[uniform]));
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Thumbnail Texture"),
+            size: wgpu::Extent3d { width: size, height: size, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: THUMBNAIL_FORMAT,
+            // we render into it and copy it to the cpu
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let depth_texture = Texture::create_depth_texture_sized(device, size, size, "thumbnail_depth_texture");
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Thumbnail Encoder"),
+        });
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Thumbnail Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        // a neutral gray so dark and bright models are both readable
+                        load: wgpu::LoadOp::Clear(wgpu::Color { r: 0.18, g: 0.18, b: 0.18, a: 1.0 }),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth_texture.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+            render_pass.set_pipeline(&self.pipeline);
+            render_pass.draw_model(model, &self.camera_bind_group);
+        }
+
+        queue.submit(std::iter::once(encoder.finish()));
+
+        // the ui wants the pixels right away, so here we wait for the readback instead of polling it every frame
+        let pixels = readback::read_texture(device, queue, &texture, 4).wait(device).unwrap_or_default();
+
+        Thumbnail { width: size, height: size, pixels }
+    }
+}
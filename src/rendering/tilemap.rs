@@ -0,0 +1,555 @@
+// tilemaps for the 2D mode and an importer for the maps made with Tiled (.tmx)
+// the tiles never move so they are cut in chunks and every chunk goes to the sprite renderer as a static batch,
+// the object layers are not drawn, gameplay reads them to know where to spawn things and where the walls are
+// the image layers become parallax backgrounds, with the parallax factor tiled saves for them
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context};
+use wgpu::{Device, Queue};
+
+use super::parallax::ParallaxLayer;
+use super::sprite::{AtlasHandle, AtlasRegion, Sprite, SpriteRenderer, StaticBatchHandle};
+use super::upload_queue::UploadQueue;
+
+// tiles per side of a chunk, a chunk is one draw call for each tileset it uses
+pub const CHUNK_SIZE: u32 = 16;
+
+// tiled keeps the flips of a tile in the highest bits of the gid
+const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+const FLIPPED_DIAGONALLY: u32 = 0x2000_0000;
+const GID_MASK: u32 = 0x1FFF_FFFF;
+
+#[derive(Clone, Debug)]
+pub struct Tileset {
+    pub name: String,
+    pub first_gid: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub tile_count: u32,
+    pub columns: u32,
+    pub spacing: u32,
+    pub margin: u32,
+    pub image: PathBuf, // already relative to the working directory, not to the map
+    pub image_width: u32,
+    pub image_height: u32,
+}
+
+impl Tileset {
+    pub fn contains(&self, gid: u32) -> bool {
+        gid >= self.first_gid && gid < self.first_gid + self.tile_count
+    }
+
+    // the uv rect of a tile of this tileset (the id is local, gid - first_gid)
+    pub fn region(&self, id: u32) -> AtlasRegion {
+        let columns = self.columns.max(1);
+        let x = self.margin + (id % columns) * (self.tile_width + self.spacing);
+        let y = self.margin + (id / columns) * (self.tile_height + self.spacing);
+        let width = self.image_width.max(1) as f32;
+        let height = self.image_height.max(1) as f32;
+        AtlasRegion {
+            min: [x as f32 / width, y as f32 / height],
+            max: [(x + self.tile_width) as f32 / width, (y + self.tile_height) as f32 / height],
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TileLayer {
+    pub width: u32,
+    pub height: u32,
+    pub visible: bool,
+    pub opacity: f32,
+    pub tiles: Vec<u32>, // raw gids (with the flip bits), row by row, 0 is an empty cell
+}
+
+#[derive(Clone, Debug)]
+pub struct MapObject {
+    pub name: String,
+    pub class: String, // "type" in older versions of tiled
+    pub x: f32,        // top left corner, in pixels (tile objects are moved from their bottom left)
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub properties: HashMap<String, String>,
+}
+
+impl MapObject {
+    pub fn center(&self) -> cgmath::Vector2<f32> {
+        cgmath::Vector2::new(self.x + self.width * 0.5, self.y + self.height * 0.5)
+    }
+
+    pub fn property(&self, name: &str) -> Option<&str> {
+        self.properties.get(name).map(|value| value.as_str())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ObjectLayer {
+    pub name: String,
+    pub class: String,
+    pub objects: Vec<MapObject>,
+}
+
+// a picture behind the tiles, always drawn under them even if it is above them in tiled
+#[derive(Clone, Debug)]
+pub struct ImageLayer {
+    pub image: PathBuf, // already relative to the working directory, like the tileset images
+    pub image_width: u32,
+    pub image_height: u32,
+    pub offset: cgmath::Vector2<f32>,
+    pub parallax: cgmath::Vector2<f32>, // 1 moves with the map, 0 stays on the screen
+    pub repeat_x: bool,
+    pub repeat_y: bool,
+    pub opacity: f32,
+}
+
+#[derive(Clone, Debug)]
+pub struct SpawnPoint {
+    pub name: String,
+    pub position: cgmath::Vector2<f32>,
+    pub properties: HashMap<String, String>,
+}
+
+// it lives with the physics so the server builds it without the renderer
+pub use crate::gameplay::physics2d::MapCollider;
+
+#[derive(Clone, Debug)]
+pub struct TileMap {
+    pub width: u32, // in tiles
+    pub height: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub tilesets: Vec<Tileset>,
+    pub layers: Vec<TileLayer>,
+    pub object_layers: Vec<ObjectLayer>,
+    pub image_layers: Vec<ImageLayer>,
+}
+
+impl TileMap {
+    // only orthogonal maps with csv or uncompressed base64 layers, that is what tiled saves by default
+    pub fn load_tmx(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).with_context(|| format!("failed to read the map {}", path.display()))?;
+        let directory = path.parent().unwrap_or(Path::new(""));
+        Self::parse_tmx(&text, directory).with_context(|| format!("failed to load the map {}", path.display()))
+    }
+
+    // the directory is where the tilesets and images are searched
+    pub fn parse_tmx(text: &str, directory: &Path) -> anyhow::Result<Self> {
+        let document = roxmltree::Document::parse(text)?;
+        let map = document.root_element();
+        if !map.has_tag_name("map") {
+            bail!("the root element is <{}>, expected <map>", map.tag_name().name());
+        }
+        if let Some(orientation) = map.attribute("orientation") {
+            if orientation != "orthogonal" {
+                bail!("{} maps are not supported", orientation);
+            }
+        }
+        if map.attribute("infinite") == Some("1") {
+            bail!("infinite maps are not supported");
+        }
+
+        let mut tilemap = Self {
+            width: number(map, "width")?,
+            height: number(map, "height")?,
+            tile_width: number(map, "tilewidth")?,
+            tile_height: number(map, "tileheight")?,
+            tilesets: Vec::new(),
+            layers: Vec::new(),
+            object_layers: Vec::new(),
+            image_layers: Vec::new(),
+        };
+
+        for node in map.children().filter(|node| node.is_element()) {
+            match node.tag_name().name() {
+                "tileset" => tilemap.tilesets.push(parse_tileset(node, directory)?),
+                "layer" => tilemap.layers.push(parse_tile_layer(node)?),
+                "objectgroup" => tilemap.object_layers.push(parse_object_layer(node, &tilemap.tilesets)?),
+                "imagelayer" => tilemap.image_layers.extend(parse_image_layer(node, directory)),
+                _ => {} // groups, etc are ignored for now
+            }
+        }
+        tilemap.tilesets.sort_by_key(|tileset| tileset.first_gid);
+
+        Ok(tilemap)
+    }
+
+    pub fn tileset_for(&self, gid: u32) -> Option<usize> {
+        let gid = gid & GID_MASK;
+        self.tilesets.iter().position(|tileset| tileset.contains(gid))
+    }
+
+    pub fn objects(&self) -> impl Iterator<Item = &MapObject> {
+        self.object_layers.iter().flat_map(|layer| layer.objects.iter())
+    }
+
+    // the objects with the class "spawn" (or "spawn_point"), the position is the center of the object
+    pub fn spawn_points(&self) -> Vec<SpawnPoint> {
+        self.objects()
+            .filter(|object| object.class == "spawn" || object.class == "spawn_point")
+            .map(|object| SpawnPoint { name: object.name.clone(), position: object.center(), properties: object.properties.clone() })
+            .collect()
+    }
+
+    // every object of a layer named or classed "collision", plus any object with the class "collider"
+    // or the property collider = true, polygons and ellipses become their bounding box
+    pub fn colliders(&self) -> Vec<MapCollider> {
+        let mut colliders = Vec::new();
+        for layer in &self.object_layers {
+            let collision_layer = layer.name.eq_ignore_ascii_case("collision") || layer.class == "collision";
+            for object in &layer.objects {
+                let is_collider = collision_layer || object.class == "collider" || object.property("collider") == Some("true");
+                if is_collider && object.width > 0.0 && object.height > 0.0 {
+                    colliders.push(MapCollider {
+                        position: cgmath::Vector2::new(object.x, object.y),
+                        size: cgmath::Vector2::new(object.width, object.height),
+                    });
+                }
+            }
+        }
+        colliders
+    }
+
+    // the files of the tileset images, in the same order as self.tilesets, this part can run on a loading thread
+    pub fn read_images(&self) -> anyhow::Result<Vec<Vec<u8>>> {
+        self.tilesets
+            .iter()
+            .map(|tileset| std::fs::read(&tileset.image).with_context(|| format!("failed to read the tileset image {}", tileset.image.display())))
+            .collect()
+    }
+
+    // the images of read_images as atlases of the sprite renderer
+    pub fn load_atlases(&self, device: &Device, queue: &Queue, sprites: &mut SpriteRenderer, images: &[Vec<u8>]) -> anyhow::Result<Vec<AtlasHandle>> {
+        self.tilesets.iter().zip(images).map(|(tileset, bytes)| sprites.load_atlas(device, queue, bytes, &tileset.name, true)).collect()
+    }
+
+    // the files of the image layers, in the same order as self.image_layers
+    pub fn read_backgrounds(&self) -> anyhow::Result<Vec<Vec<u8>>> {
+        self.image_layers
+            .iter()
+            .map(|layer| std::fs::read(&layer.image).with_context(|| format!("failed to read the layer image {}", layer.image.display())))
+            .collect()
+    }
+
+    // the images of read_backgrounds as parallax layers, the first one is the farthest like in tiled
+    pub fn load_backgrounds(&self, device: &Device, queue: &Queue, sprites: &mut SpriteRenderer, images: &[Vec<u8>]) -> anyhow::Result<Vec<ParallaxLayer>> {
+        let full = AtlasRegion { min: [0.0, 0.0], max: [1.0, 1.0] };
+        self.image_layers
+            .iter()
+            .zip(images)
+            .map(|(layer, bytes)| {
+                let atlas = sprites.load_atlas(device, queue, bytes, &layer.image.to_string_lossy(), true)?;
+                let size = cgmath::Vector2::new(layer.image_width as f32, layer.image_height as f32);
+                let mut background = ParallaxLayer::new(atlas, full, size, 1.0);
+                background.offset = layer.offset;
+                background.factor = layer.parallax;
+                background.repeat_x = layer.repeat_x;
+                background.repeat_y = layer.repeat_y;
+                background.color.a = layer.opacity;
+                Ok(background)
+            })
+            .collect()
+    }
+
+    // the sprites of the tiles inside a chunk of a layer, grouped by tileset
+    pub fn chunk_sprites(&self, layer: &TileLayer, chunk_x: u32, chunk_y: u32, atlases: &[AtlasHandle]) -> HashMap<usize, Vec<Sprite>> {
+        let mut sprites: HashMap<usize, Vec<Sprite>> = HashMap::new();
+        let start_x = chunk_x * CHUNK_SIZE;
+        let start_y = chunk_y * CHUNK_SIZE;
+        for y in start_y..(start_y + CHUNK_SIZE).min(layer.height) {
+            for x in start_x..(start_x + CHUNK_SIZE).min(layer.width) {
+                let raw = layer.tiles[(y * layer.width + x) as usize];
+                let gid = raw & GID_MASK;
+                if gid == 0 {
+                    continue;
+                }
+                let Some(index) = self.tileset_for(gid) else { continue };
+                let Some(&atlas) = atlases.get(index) else { continue };
+                let tileset = &self.tilesets[index];
+
+                let mut region = tileset.region(gid - tileset.first_gid);
+                let mut size = cgmath::Vector2::new(tileset.tile_width as f32, tileset.tile_height as f32);
+                let mut rotation = 0.0;
+                let mut flip_x = raw & FLIPPED_HORIZONTALLY != 0;
+                let mut flip_y = raw & FLIPPED_VERTICALLY != 0;
+                if raw & FLIPPED_DIAGONALLY != 0 {
+                    // tiled flips on the diagonal before the other flips, that is the same as
+                    // flipping the image vertically and turning it 90 degrees, then the other flips swap axis
+                    rotation = std::f32::consts::FRAC_PI_2;
+                    size = cgmath::Vector2::new(size.y, size.x);
+                    let (horizontal, vertical) = (flip_x, flip_y);
+                    flip_x = vertical;
+                    flip_y = !horizontal;
+                }
+                if flip_x {
+                    region = region.flipped_x();
+                }
+                if flip_y {
+                    region = AtlasRegion { min: [region.min[0], region.max[1]], max: [region.max[0], region.min[1]] };
+                }
+
+                // tiles bigger than the grid are aligned to the bottom left of their cell, like in tiled
+                let position = cgmath::Vector2::new(
+                    (x * self.tile_width) as f32 + tileset.tile_width as f32 * 0.5,
+                    ((y + 1) * self.tile_height) as f32 - tileset.tile_height as f32 * 0.5,
+                );
+                let mut sprite = Sprite::new(atlas, region, position, size);
+                sprite.rotation = rotation;
+                sprite.color.a = layer.opacity;
+                sprites.entry(index).or_default().push(sprite);
+            }
+        }
+        sprites
+    }
+
+    // sends every visible layer to the sprite renderer as chunks, the first layer goes to base_layer and every next one on top
+    pub fn build(&self, device: &Device, uploads: &mut UploadQueue, sprites: &mut SpriteRenderer, atlases: &[AtlasHandle], base_layer: i32) -> TileMapInstance {
+        let mut batches = Vec::new();
+        let chunks_x = self.width.div_ceil(CHUNK_SIZE);
+        let chunks_y = self.height.div_ceil(CHUNK_SIZE);
+        for (index, layer) in self.layers.iter().enumerate() {
+            if !layer.visible {
+                continue;
+            }
+            for chunk_y in 0..chunks_y {
+                for chunk_x in 0..chunks_x {
+                    let mut groups: Vec<_> = self.chunk_sprites(layer, chunk_x, chunk_y, atlases).into_iter().collect();
+                    groups.sort_by_key(|(tileset, _)| *tileset);
+                    for (tileset, tiles) in groups {
+                        batches.push(sprites.add_static_batch(device, uploads, atlases[tileset], base_layer + index as i32, &tiles));
+                    }
+                }
+            }
+        }
+        TileMapInstance { batches }
+    }
+}
+
+// what a built map left in the sprite renderer, so it can be removed when the level changes
+pub struct TileMapInstance {
+    pub batches: Vec<StaticBatchHandle>,
+}
+
+impl TileMapInstance {
+    pub fn unload(self, sprites: &mut SpriteRenderer) {
+        for batch in self.batches {
+            sprites.remove_static_batch(batch);
+        }
+    }
+}
+
+fn number<T: std::str::FromStr>(node: roxmltree::Node, name: &str) -> anyhow::Result<T> {
+    let value = node.attribute(name).ok_or_else(|| anyhow!("<{}> has no {} attribute", node.tag_name().name(), name))?;
+    value.trim().parse().map_err(|_| anyhow!("the {} attribute of <{}> is not a number: {}", name, node.tag_name().name(), value))
+}
+
+fn number_or<T: std::str::FromStr>(node: roxmltree::Node, name: &str, default: T) -> T {
+    node.attribute(name).and_then(|value| value.trim().parse().ok()).unwrap_or(default)
+}
+
+fn parse_tileset(node: roxmltree::Node, directory: &Path) -> anyhow::Result<Tileset> {
+    let first_gid = number(node, "firstgid")?;
+
+    // external tilesets (.tsx) have the same <tileset> inside, and their image is relative to the tsx
+    if let Some(source) = node.attribute("source") {
+        let path = directory.join(source);
+        let text = std::fs::read_to_string(&path).with_context(|| format!("failed to read the tileset {}", path.display()))?;
+        let document = roxmltree::Document::parse(&text)?;
+        let tsx_directory = path.parent().unwrap_or(Path::new(""));
+        return parse_tileset_body(document.root_element(), first_gid, tsx_directory);
+    }
+
+    parse_tileset_body(node, first_gid, directory)
+}
+
+fn parse_tileset_body(node: roxmltree::Node, first_gid: u32, directory: &Path) -> anyhow::Result<Tileset> {
+    let image = node
+        .children()
+        .find(|child| child.has_tag_name("image"))
+        .ok_or_else(|| anyhow!("tilesets made of separate images are not supported"))?;
+    let source = image.attribute("source").ok_or_else(|| anyhow!("the tileset image has no source"))?;
+
+    let tile_width = number::<u32>(node, "tilewidth")?;
+    let tile_height = number::<u32>(node, "tileheight")?;
+    let image_width = number(image, "width")?;
+    let image_height = number(image, "height")?;
+    let spacing = number_or::<u32>(node, "spacing", 0);
+    let margin = number_or::<u32>(node, "margin", 0);
+    // old files don't save columns and tilecount
+    // a broken tileset can have margins wider than its image, that is an error and not a wrapped around number
+    let fit = |size: u32, tile: u32| -> anyhow::Result<u32> {
+        let inside = margin.checked_mul(2).and_then(|margins| size.checked_sub(margins)).ok_or_else(|| anyhow!("the margin {} of the tileset doesn't fit in its {}px image", margin, size))?;
+        Ok((inside + spacing) / (tile + spacing).max(1))
+    };
+    let columns = number_or(node, "columns", fit(image_width, tile_width)?);
+    let rows = fit(image_height, tile_height)?;
+    let tile_count = number_or(node, "tilecount", columns * rows);
+
+    Ok(Tileset {
+        name: node.attribute("name").unwrap_or("tileset").to_string(),
+        first_gid,
+        tile_width,
+        tile_height,
+        tile_count,
+        columns,
+        spacing,
+        margin,
+        image: directory.join(source),
+        image_width,
+        image_height,
+    })
+}
+
+fn parse_tile_layer(node: roxmltree::Node) -> anyhow::Result<TileLayer> {
+    let name = node.attribute("name").unwrap_or("").to_string();
+    let width: u32 = number(node, "width")?;
+    let height: u32 = number(node, "height")?;
+    let data = node.children().find(|child| child.has_tag_name("data")).ok_or_else(|| anyhow!("the layer {} has no data", name))?;
+    let text = data.text().unwrap_or("");
+
+    let tiles: Vec<u32> = match (data.attribute("encoding"), data.attribute("compression")) {
+        (_, Some(compression)) => bail!("the layer {} uses {} compression, save the map as csv or uncompressed base64", name, compression),
+        (Some("csv"), None) => text
+            .split(',')
+            .map(|value| value.trim())
+            .filter(|value| !value.is_empty())
+            .map(|value| value.parse::<u32>().map_err(|_| anyhow!("bad tile in the layer {}: {}", name, value)))
+            .collect::<anyhow::Result<_>>()?,
+        (Some("base64"), None) => decode_base64(text)?
+            .chunks_exact(4)
+            .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect(),
+        // the old xml format, one <tile gid=".."/> per cell
+        (None, None) => data.children().filter(|child| child.has_tag_name("tile")).map(|tile| number_or(tile, "gid", 0)).collect(),
+        (Some(encoding), None) => bail!("unknown encoding {} in the layer {}", encoding, name),
+    };
+    if tiles.len() != (width * height) as usize {
+        bail!("the layer {} has {} tiles but it is {}x{}", name, tiles.len(), width, height);
+    }
+
+    Ok(TileLayer {
+        width,
+        height,
+        visible: node.attribute("visible") != Some("0"),
+        opacity: number_or(node, "opacity", 1.0),
+        tiles,
+    })
+}
+
+// none for the hidden ones and the ones without an image, tiled lets you make them empty
+// the image size is the one tiled saves, a layer without it is never drawn
+fn parse_image_layer(node: roxmltree::Node, directory: &Path) -> Option<ImageLayer> {
+    if node.attribute("visible") == Some("0") {
+        return None;
+    }
+    let image = node.children().find(|child| child.has_tag_name("image"))?;
+    Some(ImageLayer {
+        image: directory.join(image.attribute("source")?),
+        image_width: number_or(image, "width", 0),
+        image_height: number_or(image, "height", 0),
+        offset: cgmath::Vector2::new(number_or(node, "offsetx", 0.0), number_or(node, "offsety", 0.0)),
+        parallax: cgmath::Vector2::new(number_or(node, "parallaxx", 1.0), number_or(node, "parallaxy", 1.0)),
+        repeat_x: node.attribute("repeatx") == Some("1"),
+        repeat_y: node.attribute("repeaty") == Some("1"),
+        opacity: number_or(node, "opacity", 1.0),
+    })
+}
+
+fn parse_object_layer(node: roxmltree::Node, tilesets: &[Tileset]) -> anyhow::Result<ObjectLayer> {
+    let mut objects = Vec::new();
+    for object in node.children().filter(|child| child.has_tag_name("object")) {
+        let gid = object.attribute("gid").and_then(|value| value.parse::<u32>().ok()).map(|gid| gid & GID_MASK);
+        let mut x = number_or(object, "x", 0.0);
+        let mut y = number_or(object, "y", 0.0);
+        let mut width = number_or(object, "width", 0.0);
+        let mut height = number_or(object, "height", 0.0);
+
+        if let Some(gid) = gid {
+            // tile objects are placed by their bottom left corner and take the size of the tile if none is given
+            if let Some(tileset) = tilesets.iter().find(|tileset| tileset.contains(gid)) {
+                if width == 0.0 && height == 0.0 {
+                    width = tileset.tile_width as f32;
+                    height = tileset.tile_height as f32;
+                }
+            }
+            y -= height;
+        } else if let Some(points) = object.children().find(|child| child.has_tag_name("polygon") || child.has_tag_name("polyline")) {
+            // the points are relative to the object, we keep the bounding box
+            let points: Vec<(f32, f32)> = points
+                .attribute("points")
+                .unwrap_or("")
+                .split_whitespace()
+                .filter_map(|point| {
+                    let (px, py) = point.split_once(',')?;
+                    Some((px.parse().ok()?, py.parse().ok()?))
+                })
+                .collect();
+            if !points.is_empty() {
+                let min_x = points.iter().map(|p| p.0).fold(f32::MAX, f32::min);
+                let min_y = points.iter().map(|p| p.1).fold(f32::MAX, f32::min);
+                let max_x = points.iter().map(|p| p.0).fold(f32::MIN, f32::max);
+                let max_y = points.iter().map(|p| p.1).fold(f32::MIN, f32::max);
+                x += min_x;
+                y += min_y;
+                width = max_x - min_x;
+                height = max_y - min_y;
+            }
+        }
+
+        let mut properties = HashMap::new();
+        if let Some(list) = object.children().find(|child| child.has_tag_name("properties")) {
+            for property in list.children().filter(|child| child.has_tag_name("property")) {
+                let Some(name) = property.attribute("name") else { continue };
+                // long strings are saved as the text of the element instead of the value attribute
+                let value = property.attribute("value").or_else(|| property.text()).unwrap_or("");
+                properties.insert(name.to_string(), value.to_string());
+            }
+        }
+
+        objects.push(MapObject {
+            name: object.attribute("name").unwrap_or("").to_string(),
+            class: object.attribute("class").or_else(|| object.attribute("type")).unwrap_or("").to_string(),
+            x,
+            y,
+            width,
+            height,
+            properties,
+        });
+    }
+
+    Ok(ObjectLayer {
+        name: node.attribute("name").unwrap_or("").to_string(),
+        class: node.attribute("class").unwrap_or("").to_string(),
+        objects,
+    })
+}
+
+// the standard alphabet, whitespace is skipped because tiled puts the data on its own lines
+fn decode_base64(text: &str) -> anyhow::Result<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in text.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' => break,
+            c if c.is_ascii_whitespace() => continue,
+            _ => bail!("invalid base64 character {:?}", c as char),
+        };
+        buffer = ((buffer << 6) | value as u32) & 0xFF_FFFF;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    Ok(bytes)
+}
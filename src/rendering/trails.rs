@@ -0,0 +1,374 @@
+// the ribbons behind what moves fast (projectiles, the tip of a sword): a trail keeps the points its transform went
+// through for a while and every frame they become a strip facing the camera, thinner and more transparent as they
+// get older. the mesh of every trail is made again each frame into one vertex buffer, drawn in the main pass after the
+// opaque scene with the depth test on and without writing it
+// the texture goes along the trail (u is the distance from the head) and can scroll, v goes across it
+
+use std::collections::VecDeque;
+use std::mem;
+use std::ops::Range;
+
+use cgmath::{EuclideanSpace, InnerSpace, Point3, Transform, Vector3};
+use wgpu::{Device, Queue};
+
+use super::camera::Camera;
+use super::planar_reflection::create_camera_layout;
+use super::shader_preprocessor::ShaderLibrary;
+use super::textures::Texture;
+use crate::scene::graph::{EntityId, SceneGraph};
+use crate::util::color::Color;
+use crate::util::pool::{Pool, PoolHandle};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct TrailVertex {
+    position: [f32; 3],
+    uv: [f32; 2],
+    color: [f32; 4],
+}
+
+impl TrailVertex {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<TrailVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute { offset: 0, shader_location: 0, format: wgpu::VertexFormat::Float32x3 },
+                wgpu::VertexAttribute { offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress, shader_location: 1, format: wgpu::VertexFormat::Float32x2 },
+                wgpu::VertexAttribute { offset: mem::size_of::<[f32; 5]>() as wgpu::BufferAddress, shader_location: 2, format: wgpu::VertexFormat::Float32x4 },
+            ],
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TrailTexture(usize);
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TrailSettings {
+    pub width: f32, // at the head, it goes to 0 at the end
+    pub lifetime: f32, // seconds a point stays
+    pub min_distance: f32, // a new point every this much movement, the head follows the transform between them
+    pub start_color: Color,
+    pub end_color: Color,
+    pub texture: Option<TrailTexture>, // none is the soft white one
+    pub texture_length: f32, // world units one repeat of the texture covers
+    pub scroll_speed: f32, // repeats per second the texture moves towards the end
+}
+
+impl Default for TrailSettings {
+    fn default() -> Self {
+        Self {
+            width: 0.3,
+            lifetime: 0.5,
+            min_distance: 0.1,
+            start_color: Color::WHITE,
+            end_color: Color::WHITE.with_alpha(0.0),
+            texture: None,
+            texture_length: 1.0,
+            scroll_speed: 0.0,
+        }
+    }
+}
+
+// what the head of the trail follows
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TrailAnchor {
+    Manual, // the game moves it with TrailRenderer::move_to (a projectile that is not an entity)
+    Entity { entity: EntityId, offset: Vector3<f32> }, // the offset turns with the entity, for the tip of a blade
+}
+
+#[derive(Copy, Clone, Debug)]
+struct TrailPoint {
+    position: Point3<f32>,
+    age: f32,
+}
+
+pub struct Trail {
+    pub settings: TrailSettings,
+    pub anchor: TrailAnchor,
+    emitting: bool, // false lets the points that are left fade, then the trail goes away
+    points: VecDeque<TrailPoint>, // the head first
+}
+
+impl Trail {
+    // the head goes to the position, a new point stays behind once it moved far enough from the last one
+    fn follow(&mut self, position: Point3<f32>) {
+        let far_enough = match self.points.get(1) {
+            Some(last) => (position - last.position).magnitude() >= self.settings.min_distance,
+            None => true,
+        };
+        match self.points.front_mut() {
+            Some(head) if !far_enough => *head = TrailPoint { position, age: 0.0 },
+            _ => self.points.push_front(TrailPoint { position, age: 0.0 }),
+        }
+    }
+}
+
+pub struct TrailRenderer {
+    trails: Pool<Trail>,
+    pipeline: wgpu::RenderPipeline,
+    texture_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    textures: Vec<(Texture, wgpu::BindGroup)>, // the soft white one is the first
+    vertex_buffer: wgpu::Buffer,
+    vertex_capacity: usize,
+    draws: Vec<(usize, Range<u32>)>, // the texture and the vertices of each trail of this frame
+    time: f32,
+}
+
+impl TrailRenderer {
+    // the main pass draws into the scene target with the depth of the main camera
+    pub fn new(device: &Device, queue: &Queue, format: wgpu::TextureFormat, main_camera: &Camera) -> Self {
+        let camera_layout = create_camera_layout(device);
+        let texture_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("trail_texture_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let shader = ShaderLibrary::builtin().create_module(device, "Trail Shader", "trail.wgsl", &[]);
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Trail Pipeline Layout"),
+            bind_group_layouts: &[&camera_layout, &texture_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Trail Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[TrailVertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                cull_mode: None, // the strip turns with the camera, both sides are seen
+                ..Default::default()
+            },
+            // hidden by the scene but they don't hide each other
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: main_camera.depth_compare(),
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        // repeats along the trail for the scroll, clamps across it so the edges don't bleed
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let vertex_capacity = 1024;
+        let mut renderer = Self {
+            trails: Pool::new(),
+            pipeline,
+            texture_layout,
+            sampler,
+            textures: Vec::new(),
+            vertex_buffer: Self::create_vertex_buffer(device, vertex_capacity),
+            vertex_capacity,
+            draws: Vec::new(),
+            time: 0.0,
+        };
+
+        // white in the middle and fading to the edges
+        let soft = image::RgbaImage::from_fn(1, 32, |_, y| {
+            let v = (y as f32 + 0.5) / 32.0;
+            image::Rgba([255, 255, 255, ((v * std::f32::consts::PI).sin() * 255.0) as u8])
+        });
+        let soft = Texture::from_image(&image::DynamicImage::ImageRgba8(soft), device, queue, Some("trail_soft_texture")).expect("the trail texture is made in memory");
+        renderer.add_texture(device, soft);
+        renderer
+    }
+
+    fn create_vertex_buffer(device: &Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Trail Vertex Buffer"),
+            size: (capacity * mem::size_of::<TrailVertex>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    pub fn add_texture(&mut self, device: &Device, texture: Texture) -> TrailTexture {
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("trail_texture_bind_group"),
+            layout: &self.texture_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+        self.textures.push((texture, bind_group));
+        TrailTexture(self.textures.len() - 1)
+    }
+
+    pub fn spawn(&mut self, settings: TrailSettings, anchor: TrailAnchor) -> PoolHandle {
+        let trail = Trail { settings, anchor, emitting: true, points: VecDeque::new() };
+        self.trails.insert(trail)
+    }
+
+    // it stops growing and goes away once what it left behind faded, the handle stops working then
+    pub fn stop(&mut self, handle: PoolHandle) {
+        if let Some(trail) = self.trails.get_mut(handle) {
+            trail.emitting = false;
+        }
+    }
+
+    // the head of a manual trail, the projectile calls it every frame with where it is
+    pub fn move_to(&mut self, handle: PoolHandle, position: Point3<f32>) {
+        if let Some(trail) = self.trails.get_mut(handle).filter(|trail| trail.emitting) {
+            trail.follow(position);
+        }
+    }
+
+    // the points get older and the heads follow their entities, the trail of a despawned entity stops there
+    // the world transforms of the graph have to be updated
+    pub fn update(&mut self, delta_time: f32, graph: &SceneGraph) {
+        self.time += delta_time;
+        self.trails.retain(|trail| {
+            for point in trail.points.iter_mut() {
+                point.age += delta_time;
+            }
+            let lifetime = trail.settings.lifetime;
+            while trail.points.back().is_some_and(|point| point.age >= lifetime) {
+                trail.points.pop_back();
+            }
+            if let (true, TrailAnchor::Entity { entity, offset }) = (trail.emitting, trail.anchor) {
+                match graph.get(entity) {
+                    Some(entity) => trail.follow(entity.world_matrix().transform_point(Point3::from_vec(offset))),
+                    None => trail.emitting = false,
+                }
+            }
+            trail.emitting || !trail.points.is_empty()
+        });
+    }
+
+    // the strips of every trail facing the camera, into the vertex buffer (it grows when they don't fit)
+    pub fn prepare(&mut self, device: &Device, queue: &Queue, camera: &Camera) {
+        self.draws.clear();
+        let mut vertices: Vec<TrailVertex> = Vec::new();
+        for (_, trail) in self.trails.iter() {
+            let start = vertices.len() as u32;
+            push_ribbon(&mut vertices, trail, camera.eye, self.time);
+            if vertices.len() as u32 > start {
+                let texture = trail.settings.texture.map_or(0, |texture| texture.0).min(self.textures.len() - 1);
+                self.draws.push((texture, start..vertices.len() as u32));
+            }
+        }
+        if vertices.is_empty() {
+            return;
+        }
+        if vertices.len() > self.vertex_capacity {
+            self.vertex_capacity = vertices.len().next_power_of_two();
+            self.vertex_buffer = Self::create_vertex_buffer(device, self.vertex_capacity);
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertices));
+    }
+
+    // inside the main pass, after everything opaque (and the skybox, it would cover them)
+    pub fn has_work(&self) -> bool {
+        !self.draws.is_empty()
+    }
+
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, camera_bind_group: &'a wgpu::BindGroup) {
+        if self.draws.is_empty() {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        let mut bound = None;
+        for (texture, vertices) in &self.draws {
+            if bound != Some(*texture) {
+                render_pass.set_bind_group(1, &self.textures[*texture].1, &[]);
+                bound = Some(*texture);
+            }
+            render_pass.draw(vertices.clone(), 0..1);
+        }
+    }
+}
+
+// the left and right edge of the ribbon at a point, with its u and its color
+type RibbonSide = (Point3<f32>, Point3<f32>, f32, [f32; 4]);
+
+// two triangles between each pair of points, the sides of each point across its direction and the view
+fn push_ribbon(vertices: &mut Vec<TrailVertex>, trail: &Trail, eye: Point3<f32>, time: f32) {
+    let points = &trail.points;
+    if points.len() < 2 {
+        return;
+    }
+    let settings = &trail.settings;
+    let lifetime = settings.lifetime.max(f32::EPSILON);
+    let mut distance = 0.0;
+    let mut sides: Vec<RibbonSide> = Vec::with_capacity(points.len());
+    for (index, point) in points.iter().enumerate() {
+        if index > 0 {
+            distance += (point.position - points[index - 1].position).magnitude();
+        }
+        let previous = points[index.saturating_sub(1)].position;
+        let next = points[(index + 1).min(points.len() - 1)].position;
+        let across = (previous - next).cross(eye - point.position);
+        // seen straight along its direction there is no side, the strip is a line there
+        let across = if across.magnitude2() > 1e-12 { across.normalize() } else { Vector3::new(0.0, 0.0, 0.0) };
+        let life = (point.age / lifetime).min(1.0);
+        let half_width = settings.width * 0.5 * (1.0 - life);
+        let u = distance / settings.texture_length.max(f32::EPSILON) - time * settings.scroll_speed;
+        let color = settings.start_color.lerp(settings.end_color, life).to_array();
+        sides.push((point.position + across * half_width, point.position - across * half_width, u, color));
+    }
+    for pair in sides.windows(2) {
+        let (a_left, a_right, a_u, a_color) = pair[0];
+        let (b_left, b_right, b_u, b_color) = pair[1];
+        let vertex = |position: Point3<f32>, u: f32, v: f32, color: [f32; 4]| TrailVertex { position: position.into(), uv: [u, v], color };
+        vertices.extend_from_slice(&[
+            vertex(a_left, a_u, 0.0, a_color),
+            vertex(a_right, a_u, 1.0, a_color),
+            vertex(b_left, b_u, 0.0, b_color),
+            vertex(b_left, b_u, 0.0, b_color),
+            vertex(a_right, a_u, 1.0, a_color),
+            vertex(b_right, b_u, 1.0, b_color),
+        ]);
+    }
+}
@@ -0,0 +1,120 @@
+// the uniform buffers and their bind groups without writing the descriptors by hand every time: a UniformBuffer keeps
+// the value next to the buffer it goes to, and UniformBindGroup makes the layout and the bind group from the list of
+// buffers, numbered in the order they are given (binding 0, 1, ...) so the layout and the group can't disagree
+// for the camera, the sky and the lights, and whatever per object data comes next
+
+use std::num::NonZeroU64;
+
+use bytemuck::Pod;
+use wgpu::{util::DeviceExt, BindGroup, BindGroupLayout, Buffer, Device, Queue, ShaderStages};
+
+pub struct UniformBuffer<T: Pod> {
+    value: T,
+    buffer: Buffer,
+}
+
+impl<T: Pod> UniformBuffer<T> {
+    pub fn new(device: &Device, label: &str, value: T) -> Self {
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(label),
+            contents: bytemuck::bytes_of(&value),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        Self { value, buffer }
+    }
+
+    // all zeros until the first update, for the uniforms that are filled every frame
+    pub fn zeroed(device: &Device, label: &str) -> Self {
+        Self::new(device, label, T::zeroed())
+    }
+
+    pub fn set(&mut self, queue: &Queue, value: T) {
+        self.value = value;
+        self.write(queue);
+    }
+
+    pub fn write(&self, queue: &Queue) {
+        queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&self.value));
+    }
+
+    pub fn buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+
+    // the layout checks the buffer is big enough when the bind group is made, not when the shader reads it
+    pub fn min_binding_size() -> Option<NonZeroU64> {
+        NonZeroU64::new(std::mem::size_of::<T>() as u64)
+    }
+}
+
+struct Entry<'a> {
+    buffer: &'a Buffer,
+    visibility: ShaderStages,
+    min_binding_size: Option<NonZeroU64>,
+    ty: wgpu::BufferBindingType,
+}
+
+// the layout and the bind group of a list of uniform buffers
+pub struct UniformBindGroup {
+    pub layout: BindGroupLayout,
+    pub bind_group: BindGroup,
+}
+
+impl UniformBindGroup {
+    pub fn builder(label: &str) -> UniformBindGroupBuilder<'_> {
+        UniformBindGroupBuilder { label, entries: Vec::new() }
+    }
+}
+
+pub struct UniformBindGroupBuilder<'a> {
+    label: &'a str,
+    entries: Vec<Entry<'a>>,
+}
+
+impl<'a> UniformBindGroupBuilder<'a> {
+    // the next binding
+    pub fn uniform<T: Pod>(mut self, uniform: &'a UniformBuffer<T>, visibility: ShaderStages) -> Self {
+        self.entries.push(Entry { buffer: uniform.buffer(), visibility, min_binding_size: UniformBuffer::<T>::min_binding_size(), ty: wgpu::BufferBindingType::Uniform });
+        self
+    }
+
+    // a uniform buffer made somewhere else, without a size to check
+    pub fn buffer(mut self, buffer: &'a Buffer, visibility: ShaderStages) -> Self {
+        self.entries.push(Entry { buffer, visibility, min_binding_size: None, ty: wgpu::BufferBindingType::Uniform });
+        self
+    }
+
+    // a read only storage buffer, for the arrays that don't fit in a uniform
+    pub fn storage(mut self, buffer: &'a Buffer, visibility: ShaderStages) -> Self {
+        self.entries.push(Entry { buffer, visibility, min_binding_size: None, ty: wgpu::BufferBindingType::Storage { read_only: true } });
+        self
+    }
+
+    pub fn build(self, device: &Device) -> UniformBindGroup {
+        let layout_entries: Vec<wgpu::BindGroupLayoutEntry> = self.entries.iter().enumerate().map(|(binding, entry)| wgpu::BindGroupLayoutEntry {
+            binding: binding as u32,
+            visibility: entry.visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: entry.ty,
+                has_dynamic_offset: false,
+                min_binding_size: entry.min_binding_size,
+            },
+            count: None,
+        }).collect();
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(&format!("{}_bind_group_layout", self.label)),
+            entries: &layout_entries,
+        });
+
+        let entries: Vec<wgpu::BindGroupEntry> = self.entries.iter().enumerate().map(|(binding, entry)| wgpu::BindGroupEntry {
+            binding: binding as u32,
+            resource: entry.buffer.as_entire_binding(),
+        }).collect();
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(&format!("{}_bind_group", self.label)),
+            layout: &layout,
+            entries: &entries,
+        });
+        UniformBindGroup { layout, bind_group }
+    }
+}
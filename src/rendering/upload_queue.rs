@@ -0,0 +1,89 @@
+// uploads that don't need to be on the gpu this frame (streamed chunks, the tiles of a level) wait here and go up a
+// piece at a time, at most budget bytes per frame, so a big upload costs a few frames of a little instead of one long hitch
+// the buffers are cut in aligned chunks, the data is copied in the queue so the caller can drop it
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use wgpu::Queue;
+
+use crate::debug::frame_graph::{self, FrameGraph, PassKind};
+
+// 4 MB, what a mid range gpu takes in well under a millisecond
+pub const DEFAULT_BUDGET: usize = 4 * 1024 * 1024;
+
+struct Upload {
+    buffer: Arc<wgpu::Buffer>,
+    offset: u64,
+    data: Vec<u8>,
+    written: usize, // bytes of data already sent
+}
+
+pub struct UploadQueue {
+    pub budget: usize, // bytes per frame, at least one chunk always goes so nothing gets stuck
+    pending: VecDeque<Upload>,
+}
+
+impl UploadQueue {
+    pub fn new(budget: usize) -> Self {
+        Self { budget, pending: VecDeque::new() }
+    }
+
+    // the offset and the size of the data have to be multiples of 4 (wgpu::COPY_BUFFER_ALIGNMENT)
+    pub fn upload_buffer(&mut self, buffer: Arc<wgpu::Buffer>, offset: u64, data: Vec<u8>) -> anyhow::Result<()> {
+        if data.is_empty() {
+            anyhow::bail!("the buffer upload has no data");
+        }
+        if !offset.is_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT) || !(data.len() as u64).is_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT) {
+            anyhow::bail!("buffer uploads need an offset and a size aligned to {} bytes", wgpu::COPY_BUFFER_ALIGNMENT);
+        }
+        if offset + data.len() as u64 > buffer.size() {
+            anyhow::bail!("the upload of {} bytes at {} doesn't fit in a buffer of {} bytes", data.len(), offset, buffer.size());
+        }
+        self.pending.push_back(Upload { buffer, offset, data, written: 0 });
+        Ok(())
+    }
+
+    // once per frame, sends the oldest uploads first until the budget runs out
+    pub fn flush(&mut self, queue: &Queue) -> usize {
+        let mut sent = 0;
+        while let Some(upload) = self.pending.front_mut() {
+            // nothing to send, it would hold up everything behind it
+            if upload.written >= upload.data.len() {
+                self.pending.pop_front();
+                continue;
+            }
+            let left = self.budget.saturating_sub(sent);
+            // the first piece of the frame always goes, even if it is bigger than the whole budget
+            let piece = Self::write_piece(queue, upload, if sent == 0 { left.max(1) } else { left });
+            if piece == 0 {
+                break;
+            }
+            sent += piece;
+            if upload.written == upload.data.len() {
+                self.pending.pop_front();
+            }
+        }
+        sent
+    }
+
+    // writes up to max_bytes of the upload (rounded to whole chunks, at least one), returns the bytes sent
+    fn write_piece(queue: &Queue, upload: &mut Upload, max_bytes: usize) -> usize {
+        if max_bytes == 0 {
+            return 0;
+        }
+        let alignment = wgpu::COPY_BUFFER_ALIGNMENT as usize;
+        let left = upload.data.len() - upload.written;
+        let size = (max_bytes / alignment * alignment).max(alignment).min(left);
+        let start = upload.written;
+        queue.write_buffer(&upload.buffer, upload.offset + start as u64, &upload.data[start..start + size]);
+        upload.written += size;
+        size
+    }
+
+    pub fn record_frame_graph(&self, graph: &mut FrameGraph) {
+        if !self.pending.is_empty() {
+            graph.add_pass("streaming uploads", PassKind::Copy, &[frame_graph::buffer("cpu_uploads")], &[frame_graph::buffer("streamed_resources")]);
+        }
+    }
+}
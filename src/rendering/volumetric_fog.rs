@@ -0,0 +1,417 @@
+// volumetric fog in a froxel grid (cells of the view frustum): a compute pass fills every froxel with the density and
+// the light of the sun (checked against the shadow map) and the sky, a second one accumulates them along the view,
+// and a fullscreen pass blends the result over the scene using its depth, the shadows in the fog are the light shafts
+
+use cgmath::SquareMatrix;
+use wgpu::{util::DeviceExt, Device, Queue};
+
+use super::camera::Camera;
+use super::shader_preprocessor::ShaderLibrary;
+use super::shadow_map::ShadowMap;
+use super::sky::Sky;
+use super::textures::Texture;
+
+const VOLUME_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+const WORKGROUP_SIZE: u32 = 8;
+
+// the size of the froxel grid, more froxels are sharper shafts for more gpu time
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FogQuality {
+    Low,
+    Medium,
+    High,
+}
+
+impl FogQuality {
+    // froxels in x and y (they follow the screen, not its pixels) and slices along the view
+    pub fn grid(&self) -> [u32; 3] {
+        match self {
+            FogQuality::Low => [80, 45, 32],
+            FogQuality::Medium => [160, 90, 64],
+            FogQuality::High => [240, 135, 128],
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "low" => Some(FogQuality::Low),
+            "medium" => Some(FogQuality::Medium),
+            "high" => Some(FogQuality::High),
+            _ => None,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct FogUniform {
+    inverse_view_proj: [[f32; 4]; 4],
+    light_view_proj: [[f32; 4]; 4],
+    sun_direction: [f32; 4],
+    sun_color: [f32; 4],
+    ambient_color: [f32; 4],
+    medium: [f32; 4],
+    shape: [f32; 4],
+    volume: [f32; 4],
+    grid: [u32; 4],
+}
+
+pub struct VolumetricFog {
+    pub enabled: bool,
+    pub density: f32,          // extinction per world unit at the base height
+    pub height_falloff: f32,   // how fast it thins going up, 0 is the same everywhere
+    pub base_height: f32,      // under this height the fog has the full density
+    pub anisotropy: f32,       // from -1 to 1, above 0 glows towards the sun (the shafts need it)
+    pub noise_amount: f32,     // 0 is a smooth fog, 1 is all patches
+    pub noise_scale: f32,
+    pub wind_speed: f32,
+    pub ambient_strength: f32, // how much the sky lights the fog outside of the sun
+    pub max_distance: f32,     // the grid ends here, further away the fog stays as it is at this distance
+    quality: FogQuality,
+    time: f32,
+    uniform_buffer: wgpu::Buffer,
+    scattering: wgpu::TextureView,
+    integrated: wgpu::TextureView,
+    volume_sampler: wgpu::Sampler,
+    inject_layout: wgpu::BindGroupLayout,
+    integrate_layout: wgpu::BindGroupLayout,
+    apply_layout: wgpu::BindGroupLayout,
+    inject_bind_group: wgpu::BindGroup,
+    integrate_bind_group: wgpu::BindGroup,
+    apply_bind_group: wgpu::BindGroup,
+    inject_pipeline: wgpu::ComputePipeline,
+    integrate_pipeline: wgpu::ComputePipeline,
+    apply_pipeline: wgpu::RenderPipeline,
+}
+
+fn entry(binding: u32, visibility: wgpu::ShaderStages, ty: wgpu::BindingType) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry { binding, visibility, ty, count: None }
+}
+
+fn uniform_entry(visibility: wgpu::ShaderStages) -> wgpu::BindGroupLayoutEntry {
+    entry(0, visibility, wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None })
+}
+
+fn volume_entry(binding: u32, visibility: wgpu::ShaderStages) -> wgpu::BindGroupLayoutEntry {
+    entry(
+        binding,
+        visibility,
+        wgpu::BindingType::Texture {
+            multisampled: false,
+            view_dimension: wgpu::TextureViewDimension::D3,
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+        },
+    )
+}
+
+fn storage_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    entry(
+        binding,
+        wgpu::ShaderStages::COMPUTE,
+        wgpu::BindingType::StorageTexture {
+            access: wgpu::StorageTextureAccess::WriteOnly,
+            format: VOLUME_FORMAT,
+            view_dimension: wgpu::TextureViewDimension::D3,
+        },
+    )
+}
+
+fn depth_entry(binding: u32, visibility: wgpu::ShaderStages) -> wgpu::BindGroupLayoutEntry {
+    entry(
+        binding,
+        visibility,
+        wgpu::BindingType::Texture {
+            multisampled: false,
+            view_dimension: wgpu::TextureViewDimension::D2,
+            sample_type: wgpu::TextureSampleType::Depth,
+        },
+    )
+}
+
+impl VolumetricFog {
+    pub fn new(device: &Device, format: wgpu::TextureFormat, quality: FogQuality, shadow_map: &ShadowMap, depth: &Texture) -> Self {
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Fog Buffer"),
+            contents: bytemuck::cast_slice(&[<FogUniform as bytemuck::Zeroable>::zeroed()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let inject_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("fog_inject_bind_group_layout"),
+            entries: &[
+                uniform_entry(wgpu::ShaderStages::COMPUTE),
+                depth_entry(1, wgpu::ShaderStages::COMPUTE),
+                entry(2, wgpu::ShaderStages::COMPUTE, wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison)),
+                storage_entry(3),
+            ],
+        });
+        let integrate_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("fog_integrate_bind_group_layout"),
+            entries: &[uniform_entry(wgpu::ShaderStages::COMPUTE), volume_entry(1, wgpu::ShaderStages::COMPUTE), storage_entry(2)],
+        });
+        let apply_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("fog_apply_bind_group_layout"),
+            entries: &[
+                uniform_entry(wgpu::ShaderStages::FRAGMENT),
+                volume_entry(1, wgpu::ShaderStages::FRAGMENT),
+                entry(2, wgpu::ShaderStages::FRAGMENT, wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering)),
+                depth_entry(3, wgpu::ShaderStages::FRAGMENT),
+            ],
+        });
+
+        let library = ShaderLibrary::builtin();
+        let compute_pipeline = |label: &str, shader: &str, layout: &wgpu::BindGroupLayout| {
+            let module = library.create_module(device, &format!("{} Shader", label), shader, &[]);
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(&format!("{} Pipeline Layout", label)),
+                bind_group_layouts: &[layout],
+                push_constant_ranges: &[],
+            });
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(&format!("{} Pipeline", label)),
+                layout: Some(&pipeline_layout),
+                module: &module,
+                entry_point: "cs_main",
+            })
+        };
+        let inject_pipeline = compute_pipeline("Fog Inject", "fog_inject.wgsl", &inject_layout);
+        let integrate_pipeline = compute_pipeline("Fog Integrate", "fog_integrate.wgsl", &integrate_layout);
+
+        let apply_shader = library.create_module(device, "Fog Apply Shader", "fog_apply.wgsl", &[]);
+        let apply_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Fog Apply Pipeline Layout"),
+            bind_group_layouts: &[&apply_layout],
+            push_constant_ranges: &[],
+        });
+        let apply_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Fog Apply Pipeline"),
+            layout: Some(&apply_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &apply_shader,
+                entry_point: "vs_main",
+                buffers: &[], // the fullscreen triangle is made in the shader
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &apply_shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    // the shader gives the light in rgb and the transmittance in alpha: light + scene * transmittance
+                    blend: Some(wgpu::BlendState {
+                        color: wgpu::BlendComponent {
+                            src_factor: wgpu::BlendFactor::One,
+                            dst_factor: wgpu::BlendFactor::SrcAlpha,
+                            operation: wgpu::BlendOperation::Add,
+                        },
+                        alpha: wgpu::BlendComponent {
+                            src_factor: wgpu::BlendFactor::Zero,
+                            dst_factor: wgpu::BlendFactor::One,
+                            operation: wgpu::BlendOperation::Add,
+                        },
+                    }),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let volume_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let scattering = Self::create_volume(device, quality, "fog_scattering");
+        let integrated = Self::create_volume(device, quality, "fog_integrated");
+        let inject_bind_group = Self::create_inject_bind_group(device, &inject_layout, &uniform_buffer, shadow_map, &scattering);
+        let integrate_bind_group = Self::create_integrate_bind_group(device, &integrate_layout, &uniform_buffer, &scattering, &integrated);
+        let apply_bind_group = Self::create_apply_bind_group(device, &apply_layout, &uniform_buffer, &integrated, &volume_sampler, depth);
+
+        Self {
+            enabled: true,
+            density: 0.03,
+            height_falloff: 0.15,
+            base_height: 0.0,
+            anisotropy: 0.6,
+            noise_amount: 0.5,
+            noise_scale: 0.08,
+            wind_speed: 0.5,
+            ambient_strength: 1.0,
+            max_distance: 64.0,
+            quality,
+            time: 0.0,
+            uniform_buffer,
+            scattering,
+            integrated,
+            volume_sampler,
+            inject_layout,
+            integrate_layout,
+            apply_layout,
+            inject_bind_group,
+            integrate_bind_group,
+            apply_bind_group,
+            inject_pipeline,
+            integrate_pipeline,
+            apply_pipeline,
+        }
+    }
+
+    fn create_volume(device: &Device, quality: FogQuality, label: &str) -> wgpu::TextureView {
+        let [width, height, depth] = quality.grid();
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d { width, height, depth_or_array_layers: depth },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format: VOLUME_FORMAT,
+            usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        texture.create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    fn create_inject_bind_group(device: &Device, layout: &wgpu::BindGroupLayout, uniform: &wgpu::Buffer, shadow_map: &ShadowMap, scattering: &wgpu::TextureView) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("fog_inject_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: uniform.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&shadow_map.texture.view) },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::Sampler(&shadow_map.texture.sampler) },
+                wgpu::BindGroupEntry { binding: 3, resource: wgpu::BindingResource::TextureView(scattering) },
+            ],
+        })
+    }
+
+    fn create_integrate_bind_group(device: &Device, layout: &wgpu::BindGroupLayout, uniform: &wgpu::Buffer, scattering: &wgpu::TextureView, integrated: &wgpu::TextureView) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("fog_integrate_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: uniform.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(scattering) },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::TextureView(integrated) },
+            ],
+        })
+    }
+
+    fn create_apply_bind_group(
+        device: &Device,
+        layout: &wgpu::BindGroupLayout,
+        uniform: &wgpu::Buffer,
+        integrated: &wgpu::TextureView,
+        sampler: &wgpu::Sampler,
+        depth: &Texture,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("fog_apply_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: uniform.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(integrated) },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::Sampler(sampler) },
+                wgpu::BindGroupEntry { binding: 3, resource: wgpu::BindingResource::TextureView(&depth.view) },
+            ],
+        })
+    }
+
+    // remakes the froxel grid, the next frame already uses it
+    pub fn set_quality(&mut self, device: &Device, quality: FogQuality, shadow_map: &ShadowMap, depth: &Texture) {
+        if quality == self.quality {
+            return;
+        }
+        self.quality = quality;
+        self.scattering = Self::create_volume(device, quality, "fog_scattering");
+        self.integrated = Self::create_volume(device, quality, "fog_integrated");
+        self.inject_bind_group = Self::create_inject_bind_group(device, &self.inject_layout, &self.uniform_buffer, shadow_map, &self.scattering);
+        self.integrate_bind_group = Self::create_integrate_bind_group(device, &self.integrate_layout, &self.uniform_buffer, &self.scattering, &self.integrated);
+        self.resize(device, depth);
+    }
+
+    // the apply pass reads the depth of the scene, so it follows it when the screen changes size
+    pub fn resize(&mut self, device: &Device, depth: &Texture) {
+        self.apply_bind_group = Self::create_apply_bind_group(device, &self.apply_layout, &self.uniform_buffer, &self.integrated, &self.volume_sampler, depth);
+    }
+
+    pub fn update(&mut self, queue: &Queue, camera: &Camera, sky: &Sky, shadow_map: &ShadowMap, delta_time: f32) {
+        self.time += delta_time;
+        let sun = sky.cycle.sun_direction();
+        let sun_color = sky.sun_color();
+        let ambient = sky.ambient_color();
+        let [width, height, slices] = self.quality.grid();
+        let near = camera.znear.max(0.01);
+
+        let uniform = FogUniform {
+            inverse_view_proj: camera.view_projection_matrix().invert().unwrap_or(cgmath::Matrix4::identity()).into(),
+            light_view_proj: shadow_map.light_view_proj().into(),
+            sun_direction: [sun.x, sun.y, sun.z, 0.0],
+            sun_color: [sun_color[0], sun_color[1], sun_color[2], 1.0],
+            ambient_color: [ambient[0], ambient[1], ambient[2], 1.0],
+            medium: [self.density.max(0.0), self.height_falloff.max(0.0), self.base_height, self.anisotropy.clamp(-0.95, 0.95)],
+            shape: [self.noise_amount.clamp(0.0, 1.0), self.noise_scale, self.wind_speed, self.ambient_strength],
+            volume: [near, self.max_distance.max(near * 2.0), if camera.reverse_z { 1.0 } else { 0.0 }, self.time],
+            grid: [width, height, slices, 0],
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    // the workgroups of the grid in x and y, and its slices
+    fn groups(&self) -> (u32, u32, u32) {
+        let [width, height, slices] = self.quality.grid();
+        (width.div_ceil(WORKGROUP_SIZE), height.div_ceil(WORKGROUP_SIZE), slices)
+    }
+
+    // the three passes go in this order (inject, integrate, apply), each one is its own pass of the frame
+    // the light that each froxel scatters, the shadow map has to be rendered before
+    pub fn inject(&self, encoder: &mut wgpu::CommandEncoder) {
+        let (groups_x, groups_y, slices) = self.groups();
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Fog Inject Pass"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_pipeline(&self.inject_pipeline);
+        compute_pass.set_bind_group(0, &self.inject_bind_group, &[]);
+        compute_pass.dispatch_workgroups(groups_x, groups_y, slices);
+    }
+
+    // adds the slices up along the view rays
+    pub fn integrate(&self, encoder: &mut wgpu::CommandEncoder) {
+        let (groups_x, groups_y, _) = self.groups();
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Fog Integrate Pass"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_pipeline(&self.integrate_pipeline);
+        compute_pass.set_bind_group(0, &self.integrate_bind_group, &[]);
+        compute_pass.dispatch_workgroups(groups_x, groups_y, 1);
+    }
+
+    // the blend over the color target
+    pub fn apply(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Fog Apply Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(&self.apply_pipeline);
+        render_pass.set_bind_group(0, &self.apply_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,281 @@
+// the 3D world: entities with a transform, a parent and children, and the model (and material) they draw
+// the transforms are local to the parent, update_world_transforms walks from the roots down once per frame and the
+// renderer reads the result, the entities that share model and material go in the same instanced draw
+// the simulation moves the transforms in fixed steps, the world matrices can be placed between the last two steps
+// (see update_world_transforms_interpolated) so the motion is smooth at any frame rate
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use cgmath::{InnerSpace, SquareMatrix, VectorSpace};
+
+use crate::rendering::model::{InstanceVariation, Material, Model};
+use crate::rendering::textures::Texture;
+use crate::util::pool::{Pool, PoolHandle};
+
+pub type EntityId = PoolHandle;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ModelId(usize);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MaterialId(usize);
+
+impl MaterialId {
+    // what the instances of the entities that use it carry, 0 is for the ones without a material
+    pub fn instance_index(&self) -> u32 {
+        self.0 as u32 + 1
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Transform {
+    pub position: cgmath::Vector3<f32>,
+    pub rotation: cgmath::Quaternion<f32>,
+    pub scale: cgmath::Vector3<f32>,
+}
+
+impl Transform {
+    pub fn from_position(position: cgmath::Vector3<f32>) -> Self {
+        Self { position, ..Default::default() }
+    }
+
+    pub fn with_rotation(mut self, rotation: cgmath::Quaternion<f32>) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    // scale first, then rotation, then the position
+    pub fn matrix(&self) -> cgmath::Matrix4<f32> {
+        cgmath::Matrix4::from_translation(self.position)
+            * cgmath::Matrix4::from(self.rotation)
+            * cgmath::Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+    }
+
+    // between this one (0) and the other (1)
+    pub fn lerp(&self, other: &Transform, t: f32) -> Transform {
+        // the short way around, q and -q are the same rotation
+        let target = if self.rotation.dot(other.rotation) < 0.0 { -other.rotation } else { other.rotation };
+        Transform {
+            position: self.position.lerp(other.position, t),
+            rotation: self.rotation.nlerp(target, t),
+            scale: self.scale.lerp(other.scale, t),
+        }
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            position: cgmath::Vector3::new(0.0, 0.0, 0.0),
+            rotation: cgmath::Quaternion::new(1.0, 0.0, 0.0, 0.0),
+            scale: cgmath::Vector3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+pub struct Entity {
+    pub name: String,
+    pub transform: Transform, // relative to the parent
+    previous: Transform, // the transform before the last fixed step, for the interpolation
+    pub model: Option<ModelId>,
+    pub material: Option<MaterialId>, // replaces the materials of every mesh of the model
+    pub visible: bool, // false hides the children too
+    pub variation: InstanceVariation, // phase, tint and scale of its instance, so a crowd of the same model doesn't look cloned
+    children: Vec<EntityId>,
+    world: cgmath::Matrix4<f32>,
+}
+
+impl Entity {
+    pub fn children(&self) -> &[EntityId] {
+        &self.children
+    }
+
+    // as of the last update_world_transforms
+    pub fn world_matrix(&self) -> cgmath::Matrix4<f32> {
+        self.world
+    }
+
+    pub fn world_position(&self) -> cgmath::Vector3<f32> {
+        self.world.w.truncate()
+    }
+}
+
+// the world matrices of the visible entities that draw the same model with the same material
+pub struct DrawGroup {
+    pub model: ModelId,
+    pub material: Option<MaterialId>,
+    pub matrices: Vec<[[f32; 4]; 4]>,
+    pub variations: Vec<InstanceVariation>, // one for each matrix
+}
+
+// a draw group while it is collected, the matrices and their variations
+type GroupInstances = (Vec<[[f32; 4]; 4]>, Vec<InstanceVariation>);
+
+pub struct SceneGraph {
+    entities: Pool<Entity>,
+    roots: Vec<EntityId>,
+    models: Vec<Model>,
+    materials: Vec<Material>,
+}
+
+impl SceneGraph {
+    pub fn new() -> Self {
+        Self { entities: Pool::new(), roots: Vec::new(), models: Vec::new(), materials: Vec::new() }
+    }
+
+    // the models and materials live as long as the graph, entities point to them by id
+    pub fn add_model(&mut self, model: Model) -> ModelId {
+        self.models.push(model);
+        ModelId(self.models.len() - 1)
+    }
+
+    pub fn model(&self, id: ModelId) -> &Model {
+        &self.models[id.0]
+    }
+
+    // puts another model (the same file loaded again) where the old one was, the entities keep drawing it by the same id
+    pub fn replace_model(&mut self, id: ModelId, model: Model) -> Model {
+        std::mem::replace(&mut self.models[id.0], model)
+    }
+
+    pub fn add_material(&mut self, material: Material) -> MaterialId {
+        self.materials.push(material);
+        MaterialId(self.materials.len() - 1)
+    }
+
+    pub fn material(&self, id: MaterialId) -> &Material {
+        &self.materials[id.0]
+    }
+
+    pub fn material_mut(&mut self, id: MaterialId) -> &mut Material {
+        &mut self.materials[id.0]
+    }
+
+    // writes the parameters of the custom shader materials that changed since the last frame
+    pub fn update_materials(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        for custom in self.materials.iter_mut().filter_map(|material| material.custom.as_mut()) {
+            custom.update(device, queue);
+        }
+    }
+
+    // every material (of the graph and inside the models) that uses the old texture moves to the new one
+    // the custom shader materials have their own bind groups and are skipped, it returns how many changed
+    pub fn replace_texture(&mut self, device: &wgpu::Device, layout: &wgpu::BindGroupLayout, old: &Arc<Texture>, new: &Arc<Texture>) -> usize {
+        let mut replaced = 0;
+        let model_materials = self.models.iter_mut().flat_map(|model| model.materials.iter_mut());
+        for material in self.materials.iter_mut().chain(model_materials) {
+            if material.custom.is_none() && Arc::ptr_eq(&material.diffuse_texture, old) {
+                material.set_diffuse_texture(device, layout, new.clone());
+                replaced += 1;
+            }
+        }
+        replaced
+    }
+
+    pub fn spawn(&mut self, name: &str, transform: Transform) -> EntityId {
+        let id = self.insert(name, transform, None);
+        self.roots.push(id);
+        id
+    }
+
+    // None if the parent doesn't exist anymore
+    pub fn spawn_child(&mut self, parent: EntityId, name: &str, transform: Transform) -> Option<EntityId> {
+        if !self.entities.contains(parent) {
+            return None;
+        }
+        let id = self.insert(name, transform, Some(parent));
+        self.entities.get_mut(parent)?.children.push(id);
+        Some(id)
+    }
+
+    fn insert(&mut self, name: &str, transform: Transform, parent: Option<EntityId>) -> EntityId {
+        let world = match parent.and_then(|parent| self.entities.get(parent)) {
+            Some(parent) => parent.world * transform.matrix(),
+            None => transform.matrix(),
+        };
+        let entity = Entity { name: name.to_string(), transform, previous: transform, model: None, material: None, visible: true, variation: InstanceVariation::default(), children: Vec::new(), world };
+        self.entities.insert(entity)
+    }
+
+    pub fn get(&self, id: EntityId) -> Option<&Entity> {
+        self.entities.get(id)
+    }
+
+    pub fn get_mut(&mut self, id: EntityId) -> Option<&mut Entity> {
+        self.entities.get_mut(id)
+    }
+
+    // the first entity with the name, names don't have to be unique
+    pub fn find(&self, name: &str) -> Option<EntityId> {
+        self.entities.iter().find(|(_, entity)| entity.name == name).map(|(id, _)| id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn roots(&self) -> &[EntityId] {
+        &self.roots
+    }
+
+    // the world matrix of every entity from its parent, parents are always done before their children
+    pub fn update_world_transforms(&mut self) {
+        self.update_world_transforms_interpolated(1.0);
+    }
+
+    // the same, but placed alpha of the way from the transforms before the last fixed step to the current ones
+    // alpha is FixedTimestep::alpha, 1 is the current transforms
+    pub fn update_world_transforms_interpolated(&mut self, alpha: f32) {
+        let mut stack: Vec<(EntityId, cgmath::Matrix4<f32>)> = self.roots.iter().map(|root| (*root, cgmath::Matrix4::identity())).collect();
+        while let Some((id, parent_world)) = stack.pop() {
+            let Some(entity) = self.entities.get_mut(id) else { continue };
+            let local = if alpha >= 1.0 { entity.transform } else { entity.previous.lerp(&entity.transform, alpha) };
+            entity.world = parent_world * local.matrix();
+            let world = entity.world;
+            stack.extend(entity.children.iter().map(|child| (*child, world)));
+        }
+    }
+
+    // before every fixed step, what the step changes is then interpolated from here
+    pub fn begin_fixed_step(&mut self) {
+        for (_, entity) in self.entities.iter_mut() {
+            entity.previous = entity.transform;
+        }
+    }
+
+    // for teleports, the entity jumps to its transform instead of sliding there during the next frames
+    pub fn skip_interpolation(&mut self, id: EntityId) {
+        if let Some(entity) = self.entities.get_mut(id) {
+            entity.previous = entity.transform;
+        }
+    }
+
+    // what the renderer draws, in the same order every frame (by model and then material)
+    pub fn draw_groups(&self) -> Vec<DrawGroup> {
+        let mut groups: HashMap<(ModelId, Option<MaterialId>), GroupInstances> = HashMap::new();
+        let mut stack = self.roots.clone();
+        while let Some(id) = stack.pop() {
+            let Some(entity) = self.entities.get(id) else { continue };
+            if !entity.visible {
+                continue;
+            }
+            if let Some(model) = entity.model {
+                let (matrices, variations) = groups.entry((model, entity.material)).or_default();
+                matrices.push(entity.world.into());
+                variations.push(entity.variation);
+            }
+            stack.extend_from_slice(&entity.children);
+        }
+
+        let mut groups: Vec<DrawGroup> = groups.into_iter().map(|((model, material), (matrices, variations))| DrawGroup { model, material, matrices, variations }).collect();
+        groups.sort_by_key(|group| (group.model, group.material));
+        groups
+    }
+}
+
+impl Default for SceneGraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
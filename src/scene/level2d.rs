@@ -0,0 +1,131 @@
+// the 2D levels: a map made with Tiled goes to the sprite renderer in chunks and its collision objects become the
+// walls of the 2D physics, the player is a body on the "player" spawn point (or the first one) that walks with the
+// movement actions and jumps with MoveForward. the objects with the class "respawn" are triggers that send the
+// player back to the spawn point (pits, spikes)
+// the image layers of the map are the parallax backgrounds behind it
+// the map cvar loads one from the maps folder of the assets ("map level.tmx"), the mods can bring their own
+
+use std::path::Path;
+
+use cgmath::Vector2;
+
+use crate::gameplay::physics2d::{Body2D, BodyKind, PhysicsWorld2D, Shape2D};
+use crate::rendering::sprite::{AtlasHandle, AtlasRegion, Sprite};
+use crate::rendering::tilemap::{TileMap, TileMapInstance};
+use crate::scene::manager::{Scene, SceneContext, SceneData};
+use crate::util::pool::PoolHandle;
+use crate::util::vfs;
+
+pub const MAPS_FOLDER: &str = "maps";
+const PLAYER_SIZE: f32 = 24.0; // pixels
+const PLAYER_SPEED: f32 = 180.0; // pixels per second
+const JUMP_SPEED: f32 = 420.0;
+const PLAYER_LAYER: i32 = 100; // over the layers of the map
+const PLAYER_LOOK: &str = "player_look"; // the persistent object with the tile the player is drawn with
+
+// what the loading thread reads: the map and the images of its tilesets
+pub struct Level2DData {
+    name: String,
+    map: TileMap,
+    images: Vec<Vec<u8>>,
+    backgrounds: Vec<Vec<u8>>,
+}
+
+impl Level2DData {
+    // the file is under the maps folder, the vfs finds it in the mods first
+    pub fn load(file: &str) -> anyhow::Result<Box<dyn SceneData>> {
+        let path = vfs::resolve(Path::new(vfs::DEFAULT_BASE).join(MAPS_FOLDER).join(file));
+        let map = TileMap::load_tmx(&path)?;
+        let images = map.read_images()?;
+        let backgrounds = map.read_backgrounds()?;
+        Ok(Box::new(Self { name: file.to_string(), map, images, backgrounds }))
+    }
+}
+
+impl SceneData for Level2DData {
+    fn upload(self: Box<Self>, ctx: &mut SceneContext) -> anyhow::Result<Box<dyn Scene>> {
+        let Self { name, map, images, backgrounds } = *self;
+        let atlases = map.load_atlases(ctx.device, ctx.queue, ctx.sprites, &images)?;
+        let tiles = map.build(ctx.device, ctx.uploads, ctx.sprites, &atlases, 0);
+        ctx.parallax.layers = map.load_backgrounds(ctx.device, ctx.queue, ctx.sprites, &backgrounds)?;
+
+        let mut physics = PhysicsWorld2D::new();
+        physics.add_map_colliders(&map.colliders());
+        for object in map.objects().filter(|object| object.class == "respawn" && object.width > 0.0 && object.height > 0.0) {
+            physics.add_body(Body2D::new(BodyKind::Trigger, Shape2D::rect(object.width, object.height), object.center()));
+        }
+
+        // a spawn with shape = circle makes a ball instead of a box, it rolls off the corners
+        let spawn_points = map.spawn_points();
+        let spawn = spawn_points.iter().find(|spawn| spawn.name == "player").or(spawn_points.first());
+        let start = spawn.map_or(Vector2::new(0.0, 0.0), |spawn| spawn.position);
+        let shape = match spawn.and_then(|spawn| spawn.properties.get("shape")).map(|shape| shape.as_str()) {
+            Some("circle") => Shape2D::circle(PLAYER_SIZE * 0.5),
+            _ => Shape2D::rect(PLAYER_SIZE, PLAYER_SIZE),
+        };
+        let player = physics.add_body(Body2D::new(BodyKind::Kinematic, shape, start));
+
+        // the player is drawn with the first tile of the first tileset, the maps don't have characters yet
+        let look = map.tilesets.first().zip(atlases.first()).map(|(tileset, atlas)| (*atlas, tileset.region(0)));
+        Ok(Box::new(Level2D { name, tiles: Some(tiles), physics, player, start, look }))
+    }
+}
+
+pub struct Level2D {
+    name: String,
+    tiles: Option<TileMapInstance>, // taken out of the sprite renderer on unload
+    physics: PhysicsWorld2D,
+    player: PoolHandle,
+    start: Vector2<f32>,
+    look: Option<(AtlasHandle, AtlasRegion)>,
+}
+
+impl Scene for Level2D {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    // the player keeps the look of the first level that had one, its atlas stays in the sprite renderer
+    fn on_enter(&mut self, ctx: &mut SceneContext) {
+        let look = match self.look {
+            Some(look) => Some(ctx.persistent.keep(PLAYER_LOOK, look)),
+            None => ctx.persistent.find(PLAYER_LOOK),
+        };
+        self.look = look.and_then(|handle| ctx.persistent.get(handle)).copied();
+    }
+
+    fn fixed_update(&mut self, ctx: &mut SceneContext, step: f32) {
+        if let Some(player) = self.physics.body_mut(self.player) {
+            player.velocity.x = ctx.input.axis("MoveLeft", "MoveRight") * PLAYER_SPEED;
+            if player.on_ground() && ctx.input.action_pressed("MoveForward") {
+                player.velocity.y = -JUMP_SPEED;
+            }
+        }
+        self.physics.step(step);
+
+        if self.physics.triggered().iter().any(|(_, body)| *body == self.player) {
+            if let Some(player) = self.physics.body_mut(self.player) {
+                player.position = self.start;
+                player.previous_position = self.start;
+                player.velocity = Vector2::new(0.0, 0.0);
+            }
+        }
+    }
+
+    fn update(&mut self, ctx: &mut SceneContext, _delta_time: f32) {
+        let Some(position) = self.physics.interpolated_position(self.player, ctx.alpha) else { return };
+        ctx.sprites.camera.position = position;
+        if let Some((atlas, region)) = self.look {
+            let mut sprite = Sprite::new(atlas, region, position, Vector2::new(PLAYER_SIZE, PLAYER_SIZE));
+            sprite.layer = PLAYER_LAYER;
+            ctx.sprites.draw(sprite);
+        }
+    }
+
+    fn unload(&mut self, ctx: &mut SceneContext) {
+        if let Some(tiles) = self.tiles.take() {
+            tiles.unload(ctx.sprites);
+        }
+        ctx.parallax.layers.clear();
+    }
+}
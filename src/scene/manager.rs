@@ -0,0 +1,180 @@
+// scenes (levels, menus) and the switch between them
+// the slow part of loading (reading files, decoding images, parsing maps) runs on a worker thread while the
+// current scene keeps running, only the upload to the gpu happens on the main thread, at the darkest point of the fade
+// the old scene is unloaded right before the new one is uploaded, so two levels are never in vram at the same time
+
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+
+use wgpu::{Device, Queue};
+
+use crate::input::input_state::InputState;
+use crate::rendering::parallax::ParallaxBackground;
+use crate::rendering::post_process::PostProcess;
+use crate::rendering::sprite::SpriteRenderer;
+use crate::rendering::upload_queue::UploadQueue;
+use crate::scene::persistent::PersistentObjects;
+use crate::util::color::Color;
+
+// what a scene can touch while it is uploaded, updated or unloaded
+pub struct SceneContext<'a> {
+    pub device: &'a Device,
+    pub queue: &'a Queue,
+    pub sprites: &'a mut SpriteRenderer,
+    pub parallax: &'a mut ParallaxBackground, // the backgrounds of the 2D scenes, emptied when they unload
+    pub persistent: &'a mut PersistentObjects, // what survives the switches, see persistent.rs for the order
+    pub uploads: &'a mut UploadQueue, // for the big data of a scene, it goes up over a few frames
+    pub input: &'a InputState,
+    pub alpha: f32, // how far the frame is between the last two fixed steps, to draw what they move
+}
+
+pub trait Scene {
+    fn name(&self) -> &str;
+
+    // called once after the upload, with the persistent objects of the previous scenes already there
+    // this is where the scene takes the handles of the player, music, etc again
+    fn on_enter(&mut self, _ctx: &mut SceneContext) {}
+
+    // the fixed steps of the app (see util/timestep.rs), for the physics of the scene
+    fn fixed_update(&mut self, _ctx: &mut SceneContext, _step: f32) {}
+
+    fn update(&mut self, _ctx: &mut SceneContext, _delta_time: f32) {}
+
+    // gives back everything the scene put in shared places (sprite batches, atlases, etc), the buffers and
+    // textures it owns are dropped right after this, anything that has to live on goes to ctx.persistent here
+    fn unload(&mut self, ctx: &mut SceneContext);
+}
+
+// the result of the worker thread, only cpu data so it can cross threads
+pub trait SceneData: Send {
+    fn upload(self: Box<Self>, ctx: &mut SceneContext) -> anyhow::Result<Box<dyn Scene>>;
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum Transition {
+    Cut,
+    Fade { color: Color, duration: f32 }, // the duration is for each half, out and in
+}
+
+impl Transition {
+    pub fn fade(duration: f32) -> Self {
+        Transition::Fade { color: Color::BLACK, duration }
+    }
+}
+
+enum SceneState {
+    Idle,
+    Loading { receiver: Receiver<anyhow::Result<Box<dyn SceneData>>>, transition: Transition },
+    FadingOut { data: Box<dyn SceneData>, color: Color, duration: f32, elapsed: f32 },
+    FadingIn { color: Color, duration: f32, elapsed: f32 },
+}
+
+pub struct SceneManager {
+    current: Option<Box<dyn Scene>>,
+    state: SceneState,
+    pub last_error: Option<anyhow::Error>, // the last load that failed, the current scene stays when that happens
+}
+
+impl SceneManager {
+    pub fn new() -> Self {
+        Self { current: None, state: SceneState::Idle, last_error: None }
+    }
+
+    // starts loading on a worker thread, the current scene keeps running until the data is ready
+    // a load that is already going on is forgotten (its thread finishes and the result is dropped)
+    pub fn load<F>(&mut self, transition: Transition, load: F)
+    where
+        F: FnOnce() -> anyhow::Result<Box<dyn SceneData>> + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            // if the manager already moved on nobody is listening, that is fine
+            let _ = sender.send(load());
+        });
+        self.state = SceneState::Loading { receiver, transition };
+    }
+
+    // call once per frame, it runs the current scene and moves the switch forward
+    // the transitions use the real delta so a switch started from the pause menu still finishes, the scene gets the simulation one
+    pub fn update(&mut self, ctx: &mut SceneContext, post_process: &mut PostProcess, delta_time: f32, simulation_delta: f32) {
+        self.state = match std::mem::replace(&mut self.state, SceneState::Idle) {
+            SceneState::Idle => SceneState::Idle,
+            SceneState::Loading { receiver, transition } => match receiver.try_recv() {
+                Ok(Ok(data)) => match transition {
+                    Transition::Cut => {
+                        self.switch(ctx, data);
+                        SceneState::Idle
+                    }
+                    Transition::Fade { color, duration } => SceneState::FadingOut { data, color, duration, elapsed: 0.0 },
+                },
+                Ok(Err(e)) => {
+                    eprintln!("failed to load the scene: {:?}", e);
+                    self.last_error = Some(e);
+                    SceneState::Idle
+                }
+                Err(TryRecvError::Empty) => SceneState::Loading { receiver, transition },
+                Err(TryRecvError::Disconnected) => {
+                    self.last_error = Some(anyhow::anyhow!("the scene loading thread stopped without a result"));
+                    SceneState::Idle
+                }
+            },
+            SceneState::FadingOut { data, color, duration, elapsed } => {
+                let elapsed = elapsed + delta_time;
+                let amount = (elapsed / duration.max(f32::EPSILON)).min(1.0);
+                post_process.set_fade(color, amount);
+                if amount < 1.0 {
+                    SceneState::FadingOut { data, color, duration, elapsed }
+                } else {
+                    // the screen is covered, the hitch of the upload is not visible now
+                    self.switch(ctx, data);
+                    SceneState::FadingIn { color, duration, elapsed: 0.0 }
+                }
+            }
+            SceneState::FadingIn { color, duration, elapsed } => {
+                let elapsed = elapsed + delta_time;
+                let amount = 1.0 - (elapsed / duration.max(f32::EPSILON)).min(1.0);
+                post_process.set_fade(color, amount);
+                if amount > 0.0 {
+                    SceneState::FadingIn { color, duration, elapsed }
+                } else {
+                    SceneState::Idle
+                }
+            }
+        };
+
+        if let Some(scene) = &mut self.current {
+            scene.update(ctx, simulation_delta);
+        }
+    }
+
+    // one fixed step of the current scene, not while it is being switched in or out
+    pub fn fixed_update(&mut self, ctx: &mut SceneContext, step: f32) {
+        if let Some(scene) = &mut self.current {
+            scene.fixed_update(ctx, step);
+        }
+    }
+
+    // unloads the old scene before uploading the new one, if the upload fails there is no scene until the next load
+    fn switch(&mut self, ctx: &mut SceneContext, data: Box<dyn SceneData>) {
+        self.unload_current(ctx);
+        match data.upload(ctx) {
+            Ok(mut scene) => {
+                println!("entered the scene {}", scene.name());
+                scene.on_enter(ctx);
+                self.current = Some(scene);
+            }
+            Err(e) => {
+                eprintln!("failed to upload the scene: {:?}", e);
+                self.last_error = Some(e);
+            }
+        }
+    }
+
+    // drops the scene now instead of whenever, and waits for the gpu so its memory is really free before going on
+    pub fn unload_current(&mut self, ctx: &mut SceneContext) {
+        if let Some(mut scene) = self.current.take() {
+            scene.unload(ctx);
+            drop(scene);
+            ctx.device.poll(wgpu::Maintain::Wait);
+        }
+    }
+}
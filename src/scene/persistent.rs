@@ -0,0 +1,74 @@
+// objects that survive scene switches ("don't destroy on load"): the player, the music, the ui root...
+// a scene moves an object here with keep() and from then on the manager owns it, not the scene
+// the handles stay valid across every switch, the order of a switch is always:
+//   1. old scene unload(), the last chance to keep() something
+//   2. new scene upload()
+//   3. new scene on_enter(), where it finds the persistent objects by name and stores their handles again
+
+use std::any::Any;
+use std::marker::PhantomData;
+
+use crate::util::pool::{Pool, PoolHandle};
+
+// a typed handle so get() can't give back the wrong type
+pub struct PersistentHandle<T> {
+    handle: PoolHandle,
+    _marker: PhantomData<fn() -> T>,
+}
+
+// derive would ask T to be Copy too
+impl<T> Copy for PersistentHandle<T> {}
+
+impl<T> Clone for PersistentHandle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> PartialEq for PersistentHandle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.handle == other.handle
+    }
+}
+
+impl<T> std::fmt::Debug for PersistentHandle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "PersistentHandle({})", self.handle.index())
+    }
+}
+
+struct PersistentEntry {
+    name: String,
+    value: Box<dyn Any>,
+}
+
+pub struct PersistentObjects {
+    objects: Pool<PersistentEntry>,
+}
+
+impl PersistentObjects {
+    pub fn new() -> Self {
+        Self { objects: Pool::new() }
+    }
+
+    // marks an object as persistent, the name has to be unique: if there is already one with this name and type the
+    // new value is dropped and the old handle is returned, so going back to the first level doesn't make a second player
+    pub fn keep<T: 'static>(&mut self, name: &str, value: T) -> PersistentHandle<T> {
+        if let Some(handle) = self.find::<T>(name) {
+            return handle;
+        }
+        let handle = self.objects.insert(PersistentEntry { name: name.to_string(), value: Box::new(value) });
+        PersistentHandle { handle, _marker: PhantomData }
+    }
+
+    pub fn find<T: 'static>(&self, name: &str) -> Option<PersistentHandle<T>> {
+        self.objects
+            .iter()
+            .find(|(_, entry)| entry.name == name && entry.value.is::<T>())
+            .map(|(handle, _)| PersistentHandle { handle, _marker: PhantomData })
+    }
+
+    pub fn get<T: 'static>(&self, handle: PersistentHandle<T>) -> Option<&T> {
+        self.objects.get(handle.handle)?.value.downcast_ref()
+    }
+}
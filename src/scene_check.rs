@@ -0,0 +1,256 @@
+// the checks of the scenes for the content pipeline: loads every file it gets (obj or gltf) without a window or a gpu
+// and tells what is in it (meshes, triangles, materials and what the textures take in memory) and what is wrong with it
+// the errors (a texture or a buffer that isn't there, an index past the vertices, a file that doesn't parse) make it
+// exit with 1, the warnings (degenerate triangles, materials no mesh uses) only with --strict
+// run it with: cargo run --release --bin pankarta-scene-check -- res/Revolver.obj assets/scene.glb --strict
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use cgmath::{InnerSpace, Vector3};
+use image::GenericImageView;
+
+// a triangle with less area than this is a line or a point
+const DEGENERATE_AREA: f32 = 1e-10;
+
+struct MeshStats {
+    name: String,
+    vertices: usize,
+    triangles: usize,
+    degenerate: usize,
+}
+
+#[derive(Default)]
+struct Report {
+    meshes: Vec<MeshStats>,
+    materials: usize,
+    textures: Vec<(String, u32, u32)>, // the name and the size in pixels
+    embedded_textures: usize,          // in data uris, the size is not read
+    errors: Vec<String>,
+    warnings: Vec<String>,
+}
+
+impl Report {
+    fn error(&mut self, message: String) {
+        self.errors.push(message);
+    }
+
+    fn warning(&mut self, message: String) {
+        self.warnings.push(message);
+    }
+
+    // the texture of a material, read only for its size
+    fn texture_file(&mut self, path: &Path, material: &str) {
+        match image::image_dimensions(path) {
+            Ok((width, height)) => self.textures.push((path.display().to_string(), width, height)),
+            Err(e) => self.error(format!("the texture {} of the material {} can't be read: {}", path.display(), material, e)),
+        }
+    }
+
+    // the triangles of a mesh, the indices are checked before the positions are read
+    fn mesh(&mut self, name: String, positions: &[Vector3<f32>], indices: &[u32]) {
+        let mut degenerate = 0;
+        let mut out_of_range = 0;
+        for triangle in indices.chunks_exact(3) {
+            if triangle.iter().any(|index| *index as usize >= positions.len()) {
+                out_of_range += 1;
+                continue;
+            }
+            let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|index| positions[index as usize]);
+            if (b - a).cross(c - a).magnitude() * 0.5 <= DEGENERATE_AREA {
+                degenerate += 1;
+            }
+        }
+        if !indices.len().is_multiple_of(3) {
+            self.error(format!("the mesh {} has {} indices, not a multiple of 3", name, indices.len()));
+        }
+        if out_of_range > 0 {
+            self.error(format!("the mesh {} has {} triangles with indices past its {} vertices", name, out_of_range, positions.len()));
+        }
+        if degenerate > 0 {
+            self.warning(format!("the mesh {} has {} degenerate triangles", name, degenerate));
+        }
+        self.meshes.push(MeshStats { name, vertices: positions.len(), triangles: indices.len() / 3, degenerate });
+    }
+
+    fn unused_materials(&mut self, names: &[String], used: &HashSet<usize>) {
+        for (index, name) in names.iter().enumerate() {
+            if !used.contains(&index) {
+                self.warning(format!("the material {} is not used by any mesh", name));
+            }
+        }
+    }
+
+    fn print(&self, path: &Path) {
+        let vertices: usize = self.meshes.iter().map(|mesh| mesh.vertices).sum();
+        let triangles: usize = self.meshes.iter().map(|mesh| mesh.triangles).sum();
+        // the textures are rgba8 without mips (see Texture::from_image)
+        let texture_bytes: u64 = self.textures.iter().map(|(_, width, height)| *width as u64 * *height as u64 * 4).sum();
+        println!("{}", path.display());
+        println!("  {} meshes, {} vertices, {} triangles", self.meshes.len(), vertices, triangles);
+        println!("  {} materials, {} textures ({:.1} MiB)", self.materials, self.textures.len() + self.embedded_textures, texture_bytes as f64 / (1024.0 * 1024.0));
+        if self.embedded_textures > 0 {
+            println!("  {} textures are in data uris, they are not counted in the memory", self.embedded_textures);
+        }
+        for mesh in &self.meshes {
+            println!("    {}: {} vertices, {} triangles{}", mesh.name, mesh.vertices, mesh.triangles, if mesh.degenerate > 0 { format!(" ({} degenerate)", mesh.degenerate) } else { String::new() });
+        }
+        for (name, width, height) in &self.textures {
+            println!("    {}: {}x{}", name, width, height);
+        }
+        for warning in &self.warnings {
+            println!("  warning: {}", warning);
+        }
+        for error in &self.errors {
+            println!("  error: {}", error);
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let mut strict = false;
+    let mut files: Vec<PathBuf> = Vec::new();
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--strict" => strict = true,
+            _ if arg.starts_with("--") => {
+                eprintln!("unknown option {}", arg);
+                return ExitCode::from(2);
+            }
+            _ => files.push(PathBuf::from(arg)),
+        }
+    }
+    if files.is_empty() {
+        eprintln!("usage: pankarta-scene-check <scene.obj|scene.gltf|scene.glb>... [--strict]");
+        return ExitCode::from(2);
+    }
+
+    let mut failed = false;
+    for file in &files {
+        let report = match file.extension().and_then(|extension| extension.to_str()) {
+            Some("gltf") | Some("glb") => check_gltf(file),
+            _ => check_obj(file),
+        };
+        report.print(file);
+        failed |= !report.errors.is_empty() || (strict && !report.warnings.is_empty());
+    }
+    if failed { ExitCode::FAILURE } else { ExitCode::SUCCESS }
+}
+
+fn check_obj(path: &Path) -> Report {
+    let mut report = Report::default();
+    let directory = path.parent().unwrap_or(Path::new(""));
+    let (models, materials) = match tobj::load_obj(path, &tobj::LoadOptions { triangulate: true, single_index: true, ..Default::default() }) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            report.error(format!("it doesn't load: {}", e));
+            return report;
+        }
+    };
+    // the game still draws it with the white material, but the file is broken
+    let materials = materials.unwrap_or_else(|e| {
+        report.error(format!("the materials don't load: {}", e));
+        Vec::new()
+    });
+
+    report.materials = materials.len();
+    for material in &materials {
+        for texture in [&material.diffuse_texture, &material.normal_texture] {
+            if !texture.is_empty() {
+                report.texture_file(&directory.join(texture), &material.name);
+            }
+        }
+    }
+
+    let mut used = HashSet::new();
+    for model in &models {
+        let mesh = &model.mesh;
+        let positions: Vec<Vector3<f32>> = mesh.positions.chunks_exact(3).map(|p| Vector3::new(p[0], p[1], p[2])).collect();
+        report.mesh(model.name.clone(), &positions, &mesh.indices);
+        match mesh.material_id {
+            Some(id) if id < materials.len() => {
+                used.insert(id);
+            }
+            Some(id) => report.error(format!("the mesh {} uses the material {} and there are {}", model.name, id, materials.len())),
+            None => {}
+        }
+    }
+    let names: Vec<String> = materials.iter().map(|material| material.name.clone()).collect();
+    report.unused_materials(&names, &used);
+    report
+}
+
+fn check_gltf(path: &Path) -> Report {
+    let mut report = Report::default();
+    let directory = path.parent().unwrap_or(Path::new(""));
+    let gltf = match gltf::Gltf::open(path) {
+        Ok(gltf) => gltf,
+        Err(e) => {
+            report.error(format!("it doesn't load: {}", e));
+            return report;
+        }
+    };
+    let document = &gltf.document;
+    let buffers = match gltf::import_buffers(document, Some(directory), gltf.blob.clone()) {
+        Ok(buffers) => buffers,
+        Err(e) => {
+            report.error(format!("the buffers don't load: {}", e));
+            return report;
+        }
+    };
+
+    for image in document.images() {
+        match image.source() {
+            gltf::image::Source::Uri { uri, .. } if uri.starts_with("data:") => report.embedded_textures += 1,
+            gltf::image::Source::Uri { uri, .. } => report.texture_file(&directory.join(uri), image.name().unwrap_or("(unnamed)")),
+            gltf::image::Source::View { view, .. } => {
+                let name = image.name().map_or_else(|| format!("image {}", image.index()), str::to_string);
+                let bytes = buffers.get(view.buffer().index()).and_then(|data| data.0.get(view.offset()..view.offset() + view.length()));
+                match bytes.map(image::load_from_memory) {
+                    Some(Ok(decoded)) => {
+                        let (width, height) = decoded.dimensions();
+                        report.textures.push((name, width, height));
+                    }
+                    Some(Err(e)) => report.error(format!("the {} doesn't decode: {}", name, e)),
+                    None => report.error(format!("the {} is past the end of its buffer", name)),
+                }
+            }
+        }
+    }
+
+    // the meshes the loader makes: every primitive of every node of the scene, with the transforms of the nodes
+    let Some(scene) = document.default_scene().or_else(|| document.scenes().next()) else {
+        report.error("it has no scenes".to_string());
+        return report;
+    };
+    let mut used = HashSet::new();
+    let mut stack: Vec<gltf::Node> = scene.nodes().collect();
+    while let Some(node) = stack.pop() {
+        stack.extend(node.children());
+        let Some(mesh) = node.mesh() else { continue };
+        for primitive in mesh.primitives() {
+            let name = format!("{} {}", mesh.name().unwrap_or("mesh"), primitive.index());
+            if primitive.mode() != gltf::mesh::Mode::Triangles {
+                report.warning(format!("the mesh {} is not triangles ({:?}), the game skips it", name, primitive.mode()));
+                continue;
+            }
+            let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+            let Some(positions) = reader.read_positions() else {
+                report.warning(format!("the mesh {} has no positions, the game skips it", name));
+                continue;
+            };
+            let positions: Vec<Vector3<f32>> = positions.map(Vector3::from).collect();
+            let indices: Vec<u32> = reader.read_indices().map_or_else(|| (0..positions.len() as u32).collect(), |indices| indices.into_u32().collect());
+            report.mesh(name, &positions, &indices);
+            if let Some(material) = primitive.material().index() {
+                used.insert(material);
+            }
+        }
+    }
+
+    let names: Vec<String> = document.materials().map(|material| material.name().map_or_else(|| format!("material {}", material.index().unwrap_or(0)), str::to_string)).collect();
+    report.materials = names.len();
+    report.unused_materials(&names, &used);
+    report
+}
@@ -0,0 +1,290 @@
+// the dedicated server: the simulation of the game without a window, a gpu or sdl2, for the multiplayer
+// it shares the gameplay modules with the client (the pool, the cvars, the fixed timestep, the 2D physics), anything
+// under rendering/ or ui/ can't be declared here. the game sockets come next, for now it ticks the world at sv_tickrate
+// and advertises itself to the session browsers of the local network (sv_lan, see net/discovery.rs)
+// build it with: cargo run --release --no-default-features --features server --bin pankarta-server
+// stdin works as the console, every line goes to the cvars ("sv_tickrate 64", "reset sv_tickrate") and "status",
+// "say text" and "quit" are the server commands
+
+// the shared modules have what only the client calls
+#![allow(dead_code)]
+
+use std::io::BufRead;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::time::Instant;
+
+use cgmath::Vector2;
+use gameplay::physics2d::PhysicsWorld2D;
+use net::chat::{sanitize, ChatMessage, MAX_CHAT_LENGTH};
+use net::discovery::{Advertisement, Advertiser};
+use net::interpolation::LagCompensation;
+use net::protocol::{ByteReader, ByteWriter, Handshake, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION};
+use net::snapshot::{DeltaFields, Snapshot, SnapshotHistory};
+use util::cvars::{CvarFlags, CvarRegistry, CvarValue};
+use util::frame_limiter::FrameLimiter;
+use util::pool::PoolHandle;
+use util::timestep::FixedTimestep;
+
+mod gameplay {
+    pub mod physics2d;
+}
+
+mod net {
+    pub mod prediction;
+    pub mod interpolation;
+    pub mod protocol;
+    pub mod snapshot;
+    pub mod chat;
+    pub mod voice;
+    pub mod discovery;
+}
+
+mod util {
+    pub mod pool;
+    pub mod rng;
+    pub mod color;
+    pub mod curve;
+    pub mod noise;
+    pub mod timestep;
+    pub mod frame_limiter;
+    pub mod file_watcher;
+    pub mod cvars;
+    pub mod content_hash;
+}
+
+mod scene {
+    pub mod persistent;
+}
+
+// the archived cvars of the server, apart from the cvars.cfg of the client so both can run from the same folder
+const SERVER_CVARS_PATH: &str = "server.cfg";
+const DEFAULT_TICK_RATE: i64 = 30;
+const DEFAULT_PORT: i64 = 27015;
+const SNAPSHOT_HISTORY: usize = 32; // ticks, a client that acknowledged nothing this recent gets a whole snapshot
+const MAX_REWIND: f64 = 1.0; // seconds of lag compensation, more lag than this is checked against the oldest tick
+
+// what the snapshots carry of a 2D body, its position is enough to draw it
+#[derive(Clone, Debug, Default)]
+struct BodyState {
+    position: [f32; 2],
+}
+
+impl DeltaFields for BodyState {
+    const FIELD_COUNT: usize = 2;
+
+    fn field_changed(&self, baseline: &Self, field: usize) -> bool {
+        self.position[field] != baseline.position[field]
+    }
+
+    fn write_field(&self, field: usize, writer: &mut ByteWriter) {
+        writer.f32(self.position[field]);
+    }
+
+    fn read_field(&mut self, field: usize, reader: &mut ByteReader) -> anyhow::Result<()> {
+        self.position[field] = reader.f32()?;
+        Ok(())
+    }
+}
+
+struct Server {
+    cvars: CvarRegistry,
+    physics: PhysicsWorld2D,
+    snapshots: SnapshotHistory<BodyState>,
+    snapshot_bytes: usize, // the size of the last delta, what a client that keeps up would get every tick
+    history: LagCompensation<PoolHandle, Vector2<f32>>, // where the 2D bodies were in the last ticks, for the hits of laggy clients
+    timestep: FixedTimestep,
+    limiter: FrameLimiter,
+    ticks: u64,
+    started: Instant,
+    advertiser: Option<Advertiser>, // while sv_lan is on, so the browsers of the local network list this server
+}
+
+impl Server {
+    fn new() -> Self {
+        let mut cvars = CvarRegistry::new();
+        cvars.register_ranged("sv_tickrate", CvarValue::Int(DEFAULT_TICK_RATE), (1.0, 128.0), CvarFlags::ARCHIVE, "simulation steps per second");
+        cvars.register("sv_gravity", CvarValue::Float(980.0), CvarFlags::ARCHIVE, "gravity of the 2D physics, in pixels per second squared");
+        cvars.register("sv_name", CvarValue::Text("pankarta server".to_string()), CvarFlags::ARCHIVE, "the name the session browsers show");
+        cvars.register_ranged("sv_port", CvarValue::Int(DEFAULT_PORT), (1024.0, 65535.0), CvarFlags::ARCHIVE, "the port of the game");
+        cvars.register_ranged("sv_max_players", CvarValue::Int(8), (1.0, 64.0), CvarFlags::ARCHIVE, "how many players can join");
+        cvars.register("sv_lan", CvarValue::Bool(true), CvarFlags::ARCHIVE, "advertise the server on the local network");
+        cvars.load(SERVER_CVARS_PATH);
+
+        let mut server = Self {
+            cvars,
+            physics: PhysicsWorld2D::new(),
+            snapshots: SnapshotHistory::new(SNAPSHOT_HISTORY),
+            snapshot_bytes: 0,
+            history: LagCompensation::new(MAX_REWIND),
+            timestep: FixedTimestep::new(DEFAULT_TICK_RATE as f32),
+            limiter: FrameLimiter::new(Some(DEFAULT_TICK_RATE as u32)),
+            ticks: 0,
+            started: Instant::now(),
+            advertiser: None,
+        };
+        server.apply_cvars();
+        server.update_advertiser();
+        server
+    }
+
+    // the same idea as App::apply_cvars, what changed since the last tick goes to the systems
+    fn apply_cvars(&mut self) {
+        let changed = self.cvars.take_changed();
+        for name in &changed {
+            match name.as_str() {
+                "sv_tickrate" => {
+                    let rate = self.cvars.int(name).unwrap_or(DEFAULT_TICK_RATE);
+                    self.timestep = FixedTimestep::new(rate as f32);
+                    self.limiter.set_fps(Some(rate as u32));
+                }
+                "sv_gravity" => self.physics.gravity.y = self.cvars.float(name).unwrap_or(980.0),
+                "sv_name" | "sv_port" | "sv_max_players" | "sv_lan" => self.update_advertiser(),
+                _ => {}
+            }
+        }
+        if changed.iter().any(|name| self.cvars.get(name).is_some_and(|cvar| cvar.flags.archive)) {
+            if let Err(e) = self.cvars.save(SERVER_CVARS_PATH) {
+                eprintln!("the cvars were not saved: {}", e);
+            }
+        }
+    }
+
+    // the advertiser is made when sv_lan goes on and its advertisement follows the cvars
+    fn update_advertiser(&mut self) {
+        if !self.cvars.bool("sv_lan").unwrap_or(true) {
+            self.advertiser = None;
+            return;
+        }
+        let advertisement = Advertisement {
+            name: self.cvars.text("sv_name").unwrap_or_default().to_string(),
+            port: self.cvars.int("sv_port").unwrap_or(DEFAULT_PORT) as u16,
+            players: 0, // there are no connections yet
+            max_players: self.cvars.int("sv_max_players").unwrap_or(8) as u16,
+            handshake: Handshake::ours(),
+        };
+        match &mut self.advertiser {
+            Some(advertiser) => advertiser.advertisement = advertisement,
+            None => match Advertiser::new(advertisement) {
+                Ok(advertiser) => self.advertiser = Some(advertiser),
+                Err(e) => eprintln!("the server can't be advertised on the lan: {}", e),
+            },
+        }
+    }
+
+    // false when the line asked to stop
+    fn execute(&mut self, line: &str) -> bool {
+        match line.trim() {
+            "" => {}
+            "quit" | "exit" => return false,
+            "status" => println!(
+                "{} ticks in {:.0}s at {} per second, {} bodies, {} bytes per snapshot",
+                self.ticks,
+                self.started.elapsed().as_secs_f32(),
+                self.cvars.int("sv_tickrate").unwrap_or(DEFAULT_TICK_RATE),
+                self.physics.bodies().count(),
+                self.snapshot_bytes
+            ),
+            line if line.starts_with("say ") => {
+                // a system message, until there are clients to relay it to it only reaches the log
+                if let Some(text) = sanitize(&line["say ".len()..], MAX_CHAT_LENGTH) {
+                    let message = ChatMessage::system(&text);
+                    println!("[{}] {}", message.channel.name(), message.text);
+                }
+            }
+            line => match self.cvars.execute(line) {
+                Ok(answer) => println!("{}", answer),
+                Err(e) => println!("{}", e),
+            },
+        }
+        true
+    }
+
+    // the world of this tick for the clients, written against the previous one like for a client that keeps up
+    // until the game sockets send it the size is all that is used (status)
+    fn take_snapshot(&mut self) {
+        let mut snapshot = Snapshot::new(self.ticks as u32);
+        for (handle, body) in self.physics.bodies() {
+            snapshot.entities.insert(handle.index() as u32, BodyState { position: body.position.into() });
+        }
+        let mut writer = ByteWriter::new();
+        snapshot.write(self.snapshots.latest(), PROTOCOL_VERSION, &mut writer);
+        self.snapshot_bytes = writer.len();
+        self.snapshots.push(snapshot);
+    }
+
+    // ticks until running goes false (ctrl+c) or the console says quit
+    fn run(&mut self, running: &AtomicBool, console: Receiver<String>) {
+        let mut last = Instant::now();
+        while running.load(Ordering::Relaxed) {
+            for line in console.try_iter() {
+                if !self.execute(&line) {
+                    running.store(false, Ordering::Relaxed);
+                }
+            }
+            self.apply_cvars();
+
+            let now = Instant::now();
+            let delta = (now - last).as_secs_f32();
+            last = now;
+            let steps = self.timestep.advance(delta);
+            for _ in 0..steps {
+                self.physics.step(self.timestep.step);
+                self.ticks += 1;
+                let positions = self.physics.bodies().map(|(handle, body)| (handle, body.position)).collect();
+                self.history.record(self.started.elapsed().as_secs_f64(), positions);
+                self.take_snapshot();
+            }
+            if let Some(advertiser) = &mut self.advertiser {
+                if let Err(e) = advertiser.update() {
+                    eprintln!("lan advertisement: {}", e);
+                }
+            }
+
+            // nothing is drawn, the limiter sleeps until the next tick
+            self.limiter.wait();
+        }
+    }
+}
+
+// the lines of stdin, read on their own thread so the ticks never wait for them
+fn spawn_console() -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let running = Arc::new(AtomicBool::new(true));
+    let stop = running.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            stop.store(false, Ordering::Relaxed);
+        }
+    });
+
+    let console = spawn_console();
+    let mut server = Server::new();
+    println!(
+        "server running at {} ticks per second (protocol {} to {}), \"quit\" stops it",
+        server.cvars.int("sv_tickrate").unwrap_or(DEFAULT_TICK_RATE),
+        MIN_PROTOCOL_VERSION,
+        PROTOCOL_VERSION
+    );
+    let server = tokio::task::spawn_blocking(move || {
+        server.run(&running, console);
+        server
+    })
+    .await?;
+    println!("server stopped after {} ticks", server.ticks);
+    Ok(())
+}
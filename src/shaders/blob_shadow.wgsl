@@ -0,0 +1,95 @@
+// the blob and capsule shadows: the box under each caster is drawn, every pixel inside goes back to the world with the
+// depth of the scene and is darkened by how close it is (seen from above) to the segment of the caster and how far
+// under it
+
+struct BlobUniform {
+    view_proj: mat4x4<f32>,
+    inverse_view_proj: mat4x4<f32>,
+    far_depth: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> blob: BlobUniform;
+@group(0) @binding(1)
+var t_depth: texture_depth_2d;
+
+struct CasterInput {
+    @location(0) start: vec4<f32>, // xyz and the radius
+    @location(1) end: vec4<f32>,   // xyz and the opacity
+    @location(2) reach: vec4<f32>, // the max height
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) start: vec4<f32>,
+    @location(1) end: vec4<f32>,
+    @location(2) reach: vec4<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32, caster: CasterInput) -> VertexOutput {
+    // the corners of the box are the bits of the index (x, y, z), each face counter clockwise seen from outside
+    var corners = array<u32, 36>(
+        0u, 4u, 6u, 0u, 6u, 2u, // -x
+        5u, 1u, 3u, 5u, 3u, 7u, // +x
+        0u, 1u, 5u, 0u, 5u, 4u, // -y
+        6u, 7u, 3u, 6u, 3u, 2u, // +y
+        1u, 0u, 2u, 1u, 2u, 3u, // -z
+        4u, 5u, 7u, 4u, 7u, 6u, // +z
+    );
+    let corner = corners[index];
+    let radius = caster.start.w;
+    let low = min(caster.start.xyz, caster.end.xyz);
+    let high = max(caster.start.xyz, caster.end.xyz);
+    // the spot grows a little with the height, the box leaves room for it
+    let spread = radius * 1.5;
+    let box_min = vec3<f32>(low.x - spread, low.y - caster.reach.x, low.z - spread);
+    let box_max = vec3<f32>(high.x + spread, high.y, high.z + spread);
+    let position = vec3<f32>(
+        select(box_min.x, box_max.x, (corner & 1u) != 0u),
+        select(box_min.y, box_max.y, (corner & 2u) != 0u),
+        select(box_min.z, box_max.z, (corner & 4u) != 0u),
+    );
+
+    var out: VertexOutput;
+    out.clip_position = blob.view_proj * vec4<f32>(position, 1.0);
+    out.start = caster.start;
+    out.end = caster.end;
+    out.reach = caster.reach;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let size = textureDimensions(t_depth);
+    let pixel = min(vec2<u32>(in.clip_position.xy), size - 1u);
+    let depth = textureLoad(t_depth, pixel, 0);
+    let uv = (vec2<f32>(pixel) + 0.5) / vec2<f32>(size);
+    let ndc = vec2<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0);
+    let unprojected = blob.inverse_view_proj * vec4<f32>(ndc, depth, 1.0);
+    let world = unprojected.xyz / unprojected.w;
+    // only what faces up gets it, the normal from the neighbouring pixels (before any discard, the derivatives
+    // need every pixel of the quad)
+    let normal = normalize(cross(dpdy(world), dpdx(world)));
+    let facing = smoothstep(0.4, 0.8, abs(normal.y));
+
+    // the closest point of the segment seen from above
+    let segment = in.end.xz - in.start.xz;
+    let length_squared = dot(segment, segment);
+    var t = 0.0;
+    if (length_squared > 0.0001) {
+        t = clamp(dot(world.xz - in.start.xz, segment) / length_squared, 0.0, 1.0);
+    }
+    let closest = mix(in.start.xyz, in.end.xyz, t);
+    let height = closest.y - world.y;
+    // the sky, and what is over the caster
+    if (depth == blob.far_depth.x || height < 0.0) {
+        discard;
+    }
+    let fade = 1.0 - clamp(height / in.reach.x, 0.0, 1.0);
+    let radius = in.start.w * (1.0 + 0.5 * (1.0 - fade));
+    let distance = length(world.xz - closest.xz);
+    let spot = 1.0 - smoothstep(radius * 0.3, radius, distance);
+
+    return vec4<f32>(0.0, 0.0, 0.0, in.end.w * spot * fade * facing);
+}
@@ -0,0 +1,5 @@
+// the camera uniform every pass uses, the binding is declared by each shader since the group changes between pipelines
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+};
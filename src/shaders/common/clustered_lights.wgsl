@@ -0,0 +1,47 @@
+// the clustered path of the lights (lights.rs): the view is a grid of clusters, 16x9 tiles of the screen times 24
+// slices of the depth that grow with the distance, and every cluster has the list of the point lights that reach it
+// it needs common/lights.wgsl, and the shader declares the bindings 2 to 4 of the sky group with these names:
+//   clusters: ClusterUniform, cluster_lights: array<Light>, cluster_data: array<u32>
+// cluster_data has the offset and the count of every cluster first, then the indices of all the lists
+
+const CLUSTERS_X: u32 = 16u;
+const CLUSTERS_Y: u32 = 9u;
+const CLUSTERS_Z: u32 = 24u;
+const CLUSTER_COUNT: u32 = 3456u; // x * y * z
+
+struct ClusterUniform {
+    eye: vec4<f32>,
+    forward: vec4<f32>,
+    screen: vec2<f32>,
+    near: f32,
+    log_depth_ratio: f32,
+    directional_count: u32, // the first lights, they light every cluster
+    light_count: u32,
+    _padding: vec2<u32>,
+};
+
+// pixel is the clip_position of the fragment
+fn cluster_of(pixel: vec2<f32>, position: vec3<f32>) -> u32 {
+    let grid = vec2<f32>(f32(CLUSTERS_X), f32(CLUSTERS_Y));
+    let tile = vec2<u32>(clamp(floor(pixel / clusters.screen * grid), vec2<f32>(0.0), grid - 1.0));
+    let depth = max(dot(position - clusters.eye.xyz, clusters.forward.xyz), clusters.near);
+    let slice = u32(clamp(floor(log(depth / clusters.near) / clusters.log_depth_ratio * f32(CLUSTERS_Z)), 0.0, f32(CLUSTERS_Z - 1u)));
+    return (slice * CLUSTERS_Y + tile.y) * CLUSTERS_X + tile.x;
+}
+
+// the same as scene_lights, with the lights of the cluster of the pixel
+fn clustered_scene_lights(lights: LightsUniform, albedo: vec3<f32>, position: vec3<f32>, normal: vec3<f32>, pixel: vec2<f32>) -> vec3<f32> {
+    let view = normalize(lights.view_position.xyz - position);
+    var result = vec3<f32>(0.0);
+    for (var i = 0u; i < clusters.directional_count; i++) {
+        result += shade_light(lights, cluster_lights[i], albedo, position, normal, view);
+    }
+    let cluster = cluster_of(pixel, position);
+    let offset = cluster_data[cluster * 2u];
+    let count = cluster_data[cluster * 2u + 1u];
+    for (var i = 0u; i < count; i++) {
+        let index = cluster_data[CLUSTER_COUNT * 2u + offset + i];
+        result += shade_light(lights, cluster_lights[index], albedo, position, normal, view);
+    }
+    return result;
+}
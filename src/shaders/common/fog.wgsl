@@ -0,0 +1,45 @@
+// what the three passes of the volumetric fog share: the uniform, the view rays and the slices of the froxel grid
+// the slices are spread exponentially along the ray, thin near the camera where the detail is seen and thick far away
+
+struct FogUniform {
+    inverse_view_proj: mat4x4<f32>,
+    light_view_proj: mat4x4<f32>,
+    sun_direction: vec4<f32>, // towards the sun
+    sun_color: vec4<f32>,
+    ambient_color: vec4<f32>,
+    medium: vec4<f32>, // density, height falloff, base height, anisotropy
+    shape: vec4<f32>,  // noise amount, noise scale, wind speed, ambient strength
+    volume: vec4<f32>, // near distance, far distance, near depth of the camera, time
+    grid: vec4<u32>,   // froxels in x, y and slices
+};
+
+struct FogRay {
+    origin: vec3<f32>,
+    direction: vec3<f32>,
+};
+
+fn fog_unproject(uv: vec2<f32>, depth: f32) -> vec3<f32> {
+    let ndc = vec2<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0);
+    let point = fog.inverse_view_proj * vec4<f32>(ndc, depth, 1.0);
+    return point.xyz / point.w;
+}
+
+// the ray of a screen position, it works for perspective and orthographic cameras
+fn fog_view_ray(uv: vec2<f32>) -> FogRay {
+    let origin = fog_unproject(uv, fog.volume.z);
+    let further = fog_unproject(uv, 0.5);
+    return FogRay(origin, normalize(further - origin));
+}
+
+// the distance along the ray where a slice starts, slice can have decimals
+fn fog_slice_distance(slice: f32) -> f32 {
+    let near = fog.volume.x;
+    let far = fog.volume.y;
+    return near * pow(far / near, slice / f32(fog.grid.z));
+}
+
+fn fog_distance_slice(distance: f32) -> f32 {
+    let near = fog.volume.x;
+    let far = fog.volume.y;
+    return log(max(distance, near) / near) / log(far / near) * f32(fog.grid.z);
+}
@@ -0,0 +1,14 @@
+// one triangle bigger than the screen, so fullscreen passes don't need a vertex buffer, draw it with 0..3
+
+struct FullscreenOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+fn fullscreen_vertex(index: u32) -> FullscreenOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: FullscreenOutput;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
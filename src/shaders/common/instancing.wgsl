@@ -0,0 +1,22 @@
+// the per instance data of the instanced pipelines, it matches InstanceRaw on the rust side
+
+struct InstanceInput {
+    @location(5) model_matrix_0: vec4<f32>,
+    @location(6) model_matrix_1: vec4<f32>,
+    @location(7) model_matrix_2: vec4<f32>,
+    @location(8) model_matrix_3: vec4<f32>,
+    @location(9) material: u32, // 0 is the materials of the model, see InstanceRaw
+    // the variation of the instance (see InstanceVariation), so a crowd isn't made of clones
+    @location(10) phase: f32, // seconds, add it to the time of the animations
+    @location(11) tint: vec4<f32>, // multiplies the color
+    @location(12) scale: f32, // already in the matrix of instance_model_matrix
+};
+
+fn instance_model_matrix(instance: InstanceInput) -> mat4x4<f32> {
+    return mat4x4<f32>(
+        instance.model_matrix_0 * instance.scale,
+        instance.model_matrix_1 * instance.scale,
+        instance.model_matrix_2 * instance.scale,
+        instance.model_matrix_3,
+    );
+}
@@ -0,0 +1,9 @@
+// a neutral rig: one key light from the top left front and a soft fill from the opposite side, plus some ambient
+// it is what we use until the scene has real lights
+
+fn studio_lighting(normal: vec3<f32>) -> f32 {
+    let key = max(dot(normal, normalize(vec3<f32>(-0.5, 1.0, 0.8))), 0.0);
+    let fill = max(dot(normal, normalize(vec3<f32>(0.6, 0.2, -0.7))), 0.0) * 0.35;
+    let ambient = 0.25;
+    return ambient + key + fill;
+}
@@ -0,0 +1,55 @@
+// the lights of the game (lights.rs), blinn-phong: lambert for the diffuse and the half vector for the highlight
+// the binding is declared by each shader, it is binding 1 of the sky group
+
+const MAX_LIGHTS: u32 = 16u;
+
+struct Light {
+    position: vec4<f32>, // w 0 is directional (xyz is the direction towards it), 1 is a point light
+    color: vec4<f32>,    // times the intensity, w is the range of the point lights
+};
+
+struct LightsUniform {
+    view_position: vec4<f32>,
+    count: u32,
+    shininess: f32,
+    specular: f32,
+    _padding: f32,
+    lights: array<Light, MAX_LIGHTS>,
+};
+
+// goes to 0 at the range without a hard edge, and falls with the square of the distance before that
+fn light_attenuation(distance: f32, range: f32) -> f32 {
+    let ratio = distance / range;
+    let window = clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0);
+    return window * window / (distance * distance + 1.0);
+}
+
+// what one light adds, view is the direction towards the eye (the clustered path uses it too)
+fn shade_light(lights: LightsUniform, light: Light, albedo: vec3<f32>, position: vec3<f32>, normal: vec3<f32>, view: vec3<f32>) -> vec3<f32> {
+    var to_light = light.position.xyz;
+    var strength = 1.0;
+    if light.position.w > 0.5 {
+        let offset = light.position.xyz - position;
+        let distance = length(offset);
+        to_light = offset / max(distance, 0.0001);
+        strength = light_attenuation(distance, light.color.w);
+    } else {
+        to_light = normalize(to_light);
+    }
+
+    let diffuse = max(dot(normal, to_light), 0.0);
+    let half_vector = normalize(to_light + view);
+    // no highlight on the side the light doesn't reach
+    let specular = pow(max(dot(normal, half_vector), 0.0), lights.shininess) * lights.specular * step(0.0, diffuse);
+    return (albedo * diffuse + vec3<f32>(specular)) * light.color.rgb * strength;
+}
+
+// what all the lights add to a surface of the albedo color, normal normalized
+fn scene_lights(lights: LightsUniform, albedo: vec3<f32>, position: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    let view = normalize(lights.view_position.xyz - position);
+    var result = vec3<f32>(0.0);
+    for (var i = 0u; i < min(lights.count, MAX_LIGHTS); i++) {
+        result += shade_light(lights, lights.lights[i], albedo, position, normal, view);
+    }
+    return result;
+}
@@ -0,0 +1,10 @@
+// the pixel counter of the overdraw debug views, the bindings are declared by each shader since the group changes
+// between pipelines:
+//   var<storage, read_write> overdraw_counts: array<atomic<u32>>;
+//   var<uniform> overdraw_size: vec4<u32>; // width and height of the screen
+
+// one more time this pixel was shaded, position is the @builtin(position) of the fragment
+fn count_overdraw(position: vec4<f32>) {
+    let pixel = min(vec2<u32>(position.xy), overdraw_size.xy - 1u);
+    atomicAdd(&overdraw_counts[pixel.y * overdraw_size.x + pixel.x], 1u);
+}
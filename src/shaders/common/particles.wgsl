@@ -0,0 +1,30 @@
+// the particles of an emitter and its settings for the frame, the same as ParticleRaw and EmitterUniform in particles.rs
+
+struct Particle {
+    position: vec4<f32>, // w is the age
+    velocity: vec4<f32>, // w is the lifetime, dead once the age gets there
+};
+
+struct Emitter {
+    origin: vec4<f32>,       // w is the spread
+    direction: vec4<f32>,    // w is the drag
+    gravity: vec4<f32>,      // w is the delta time
+    camera_right: vec4<f32>, // w is not used
+    camera_up: vec4<f32>,    // w is not used
+    speed: vec2<f32>,
+    lifetime: vec2<f32>,
+    spawn_start: u32,
+    spawn_count: u32,
+    capacity: u32,
+    seed: u32,
+    radius: f32,
+    _padding0: f32,
+    _padding1: f32,
+    _padding2: f32,
+    sizes: array<vec4<f32>, 4>, // 16 over the life, four in each vector
+    colors: array<vec4<f32>, 16>,
+};
+
+fn is_alive(particle: Particle) -> bool {
+    return particle.position.w < particle.velocity.w;
+}
@@ -0,0 +1,30 @@
+// what every user post process pass gets, it is included before the pass source so the pass only writes fs_main:
+//   @fragment fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> { ... }
+// t_color is the image so far (the scene, or the result of the pass before), t_depth the depth of the scene (use textureLoad)
+
+#include "common/fullscreen.wgsl"
+
+struct PostGlobals {
+    resolution: vec2<f32>,
+    inverse_resolution: vec2<f32>,
+    time: f32,       // seconds since the start, it keeps going while paused
+    delta_time: f32,
+    frame: u32,
+    _padding: u32,
+};
+
+@group(0) @binding(0)
+var t_color: texture_2d<f32>;
+@group(0) @binding(1)
+var s_color: sampler;
+@group(0) @binding(2)
+var t_depth: texture_depth_2d;
+@group(0) @binding(3)
+var<uniform> globals: PostGlobals;
+// binding 4 is the uniform of the pass itself, declare it with the struct you need:
+//   @group(0) @binding(4) var<uniform> params: MyParams;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> FullscreenOutput {
+    return fullscreen_vertex(index);
+}
@@ -0,0 +1,60 @@
+// the analytic sky (Preetham), the values that depend on the sun and the turbidity come from sky.rs every frame
+// the binding is declared by each shader since the group changes between pipelines
+
+struct SkyUniform {
+    inverse_view_proj: mat4x4<f32>,
+    sun_direction: vec4<f32>, // w is the daylight, 0 at night
+    sun_color: vec4<f32>,
+    ambient_color: vec4<f32>,
+    perez_a: vec4<f32>, // xyz are the Y, x and y channels
+    perez_b: vec4<f32>,
+    perez_c: vec4<f32>,
+    perez_d: vec4<f32>,
+    perez_e: vec4<f32>,
+    zenith: vec4<f32>, // w is the exposure
+    fog: vec4<f32>, // the eye of the camera, w is the density of the distance fog
+    near_depth: f32,
+};
+
+fn sky_perez(sky: SkyUniform, cos_theta: f32, gamma: f32) -> vec3<f32> {
+    let cos_gamma = cos(gamma);
+    return (1.0 + sky.perez_a.xyz * exp(sky.perez_b.xyz / max(cos_theta, 0.01)))
+        * (1.0 + sky.perez_c.xyz * exp(sky.perez_d.xyz * gamma) + sky.perez_e.xyz * cos_gamma * cos_gamma);
+}
+
+// the color of the sky looking in a direction (normalized), linear rgb
+fn sky_radiance(sky: SkyUniform, direction: vec3<f32>) -> vec3<f32> {
+    let sun = sky.sun_direction.xyz;
+    // under the horizon we repeat the horizon, the scene is supposed to cover it
+    let up = vec3<f32>(direction.x, max(direction.y, 0.0), direction.z);
+    let view = normalize(up + vec3<f32>(0.0, 0.001, 0.0));
+    let gamma = acos(clamp(dot(view, sun), -1.0, 1.0));
+    let yxy = sky.zenith.xyz * sky_perez(sky, view.y, gamma);
+
+    let luminance = 1.0 - exp(-yxy.x * sky.zenith.w);
+    let y = max(yxy.z, 0.0001);
+    let xyz = vec3<f32>(yxy.y / y * luminance, luminance, (1.0 - yxy.y - yxy.z) / y * luminance);
+    let rgb = max(vec3<f32>(
+        3.2406 * xyz.x - 1.5372 * xyz.y - 0.4986 * xyz.z,
+        -0.9689 * xyz.x + 1.8758 * xyz.y + 0.0415 * xyz.z,
+        0.0557 * xyz.x - 0.2040 * xyz.y + 1.0570 * xyz.z,
+    ), vec3<f32>(0.0));
+
+    let night = vec3<f32>(0.005, 0.008, 0.02) * (1.0 - sky.sun_direction.w);
+    return rgb * sky.sun_direction.w + night;
+}
+
+// the distance fog of the FOG variant of the scene shader, far away things fade to the sky behind them
+fn sky_fog(sky: SkyUniform, color: vec3<f32>, world_position: vec3<f32>) -> vec3<f32> {
+    let to_point = world_position - sky.fog.xyz;
+    let distance = length(to_point);
+    let amount = 1.0 - exp(-distance * sky.fog.w);
+    return mix(color, sky_radiance(sky, to_point / max(distance, 0.0001)), amount);
+}
+
+// the light the sky gives a surface: the sun as a directional light plus the ambient, brighter facing up
+fn sky_lighting(sky: SkyUniform, normal: vec3<f32>) -> vec3<f32> {
+    let sun = max(dot(normal, sky.sun_direction.xyz), 0.0) * sky.sun_color.rgb;
+    let hemisphere = 0.75 + 0.25 * normal.y;
+    return sun + sky.ambient_color.rgb * hemisphere;
+}
@@ -0,0 +1,133 @@
+// the final pass of the debug views, it replaces the post process so nothing (effects, fog) changes what is shown
+// the albedo and normals views were already drawn by the scene variants, the overdraw views read the counts of every
+// pixel, the depth and shadow views read the depth of the normal scene and rebuild the world position of every pixel
+
+struct DebugUniform {
+    inverse_view_proj: mat4x4<f32>,
+    light_view_proj: mat4x4<f32>,
+    eye: vec4<f32>,    // xyz = camera position, w = the depth the sky has (the clear value)
+    params: vec4<f32>, // x = mode, y = far distance of the depth view, w = layers that are red in the overdraw view
+    output: vec4<f32>, // the same as in post_effects.wgsl
+};
+
+@group(0) @binding(0)
+var t_scene: texture_2d<f32>;
+@group(0) @binding(1)
+var t_depth: texture_depth_2d;
+@group(0) @binding(2)
+var t_shadow: texture_depth_2d;
+@group(0) @binding(3)
+var<uniform> debug: DebugUniform;
+@group(0) @binding(4)
+var<storage, read> overdraw_counts: array<u32>; // how many times each pixel was shaded, row by row
+
+#include "common/fullscreen.wgsl"
+
+const MODE_COLOR: u32 = 0u;
+const MODE_DEPTH: u32 = 1u;
+const MODE_OVERDRAW: u32 = 2u;
+const MODE_SHADOW: u32 = 3u;
+const MODE_QUADS: u32 = 4u;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> FullscreenOutput {
+    return fullscreen_vertex(index);
+}
+
+fn unproject(uv: vec2<f32>, depth: f32) -> vec3<f32> {
+    let ndc = vec2<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0);
+    let point = debug.inverse_view_proj * vec4<f32>(ndc, depth, 1.0);
+    return point.xyz / point.w;
+}
+
+// blue for a single layer, then green, yellow and red at the limit
+fn heat(amount: f32) -> vec3<f32> {
+    let t = clamp(amount, 0.0, 1.0);
+    if t < 0.33 {
+        return mix(vec3<f32>(0.0, 0.0, 1.0), vec3<f32>(0.0, 1.0, 0.0), t / 0.33);
+    }
+    if t < 0.66 {
+        return mix(vec3<f32>(0.0, 1.0, 0.0), vec3<f32>(1.0, 1.0, 0.0), (t - 0.33) / 0.33);
+    }
+    return mix(vec3<f32>(1.0, 1.0, 0.0), vec3<f32>(1.0, 0.0, 0.0), (t - 0.66) / 0.34);
+}
+
+fn overdraw_count(pixel: vec2<u32>, size: vec2<u32>) -> f32 {
+    let clamped = min(pixel, size - 1u);
+    return f32(overdraw_counts[clamped.y * size.x + clamped.x]);
+}
+
+// the gpu shades the pixels in 2x2 quads, a triangle that covers 1 pixel of a quad still pays for the 4
+// the counts don't say which triangle covered what, so this is an estimate: every layer of the most covered pixel
+// launched at least one quad, the pixels that were really shaded are the sum, 1 is every quad full
+fn quad_occupancy(pixel: vec2<u32>, size: vec2<u32>) -> f32 {
+    let quad = pixel & vec2<u32>(~1u);
+    let a = overdraw_count(quad, size);
+    let b = overdraw_count(quad + vec2<u32>(1u, 0u), size);
+    let c = overdraw_count(quad + vec2<u32>(0u, 1u), size);
+    let d = overdraw_count(quad + vec2<u32>(1u, 1u), size);
+    let most = max(max(a, b), max(c, d));
+    if most == 0.0 {
+        return 0.0;
+    }
+    return (a + b + c + d) / (4.0 * most);
+}
+
+// green where the shadow map covers the scene (darker in its shadow) and red outside of it
+// there is one cascade for now, with more each one gets its own color
+fn shadow_coverage(uv: vec2<f32>, depth: f32, color: vec3<f32>) -> vec3<f32> {
+    if depth == debug.eye.w {
+        return color;
+    }
+    let clip = debug.light_view_proj * vec4<f32>(unproject(uv, depth), 1.0);
+    let ndc = clip.xyz / clip.w;
+    let shadow_uv = ndc.xy * vec2<f32>(0.5, -0.5) + 0.5;
+    if any(shadow_uv < vec2<f32>(0.0)) || any(shadow_uv > vec2<f32>(1.0)) || ndc.z > 1.0 {
+        return mix(color, vec3<f32>(1.0, 0.1, 0.1), 0.5);
+    }
+    let size = textureDimensions(t_shadow);
+    let texel = min(vec2<u32>(shadow_uv * vec2<f32>(size)), size - 1u);
+    let lit = select(0.4, 1.0, ndc.z - 0.001 <= textureLoad(t_shadow, texel, 0));
+    return mix(color, vec3<f32>(0.1, 1.0, 0.2), 0.4) * lit;
+}
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let size = textureDimensions(t_depth);
+    let pixel = min(vec2<u32>(in.uv * vec2<f32>(size)), size - 1u);
+    let color = textureLoad(t_scene, pixel, 0).rgb;
+    let depth = textureLoad(t_depth, pixel, 0);
+
+    var result = color;
+    switch u32(debug.params.x) {
+        case MODE_DEPTH: {
+            // the distance to the camera, black at the eye and white at the far distance (and the sky)
+            var distance = debug.params.y;
+            if depth != debug.eye.w {
+                distance = length(unproject(in.uv, depth) - debug.eye.xyz);
+            }
+            result = vec3<f32>(clamp(distance / debug.params.y, 0.0, 1.0));
+        }
+        case MODE_OVERDRAW: {
+            let layers = overdraw_count(pixel, size);
+            result = select(heat((layers - 1.0) / max(debug.params.w - 1.0, 1.0)), vec3<f32>(0.0), layers < 0.5);
+        }
+        case MODE_QUADS: {
+            // blue where the quads were full, up to red at 1 of 4 pixels, black where nothing was drawn
+            let occupancy = quad_occupancy(pixel, size);
+            result = select(heat(1.0 - (occupancy - 0.25) / 0.75), vec3<f32>(0.0), occupancy == 0.0);
+        }
+        case MODE_SHADOW: {
+            result = shadow_coverage(in.uv, depth, color);
+        }
+        default: {}
+    }
+    return vec4<f32>(display_output(result), 1.0);
+}
+
+fn display_output(color: vec3<f32>) -> vec3<f32> {
+    if debug.output.x > 0.5 {
+        return min(max(color, vec3<f32>(0.0)) * debug.output.y, vec3<f32>(debug.output.w));
+    }
+    return pow(clamp(color, vec3<f32>(0.0), vec3<f32>(1.0)), vec3<f32>(debug.output.z));
+}
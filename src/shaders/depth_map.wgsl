@@ -1,51 +1,119 @@
-
-struct CameraUniform {
-    view_proj: mat4x4<f32>,
-};
+#include "common/camera.wgsl"
+#include "common/instancing.wgsl"
+#include "common/sky.wgsl"
+#include "common/lights.wgsl"
 
 @group(1) @binding(0) // on our render pipeline layout we have 2 values, the first is the texture and the second is the camera, thats why the camera is group 0 instead of 1
 var<uniform> camera: CameraUniform;
 
+@group(2) @binding(0) // the sky lights the scene, its sun and ambient follow the day/night cycle
+var<uniform> sky: SkyUniform;
+@group(2) @binding(1) // the lamps and torches of the game, they go with the sky
+var<uniform> lights: LightsUniform;
+#ifdef CLUSTERED_LIGHTS
+#include "common/clustered_lights.wgsl"
+@group(2) @binding(2) // the hundreds of lights of the clustered path, in the lists of the clusters
+var<uniform> clusters: ClusterUniform;
+@group(2) @binding(3)
+var<storage, read> cluster_lights: array<Light>;
+@group(2) @binding(4)
+var<storage, read> cluster_data: array<u32>;
+#endif
+
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) tex_coords: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) tangent: vec4<f32>, // w is the sign of the bitangent
 }
 
-struct InstanceInput {
-    @location(5) model_matrix_0: vec4<f32>,
-    @location(6) model_matrix_1: vec4<f32>,
-    @location(7) model_matrix_2: vec4<f32>,
-    @location(8) model_matrix_3: vec4<f32>,
-};
-
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) world_position: vec3<f32>,
+    @location(3) tangent: vec4<f32>,
+    @location(4) tint: vec4<f32>, // of the instance
 }
 
 @vertex
 fn vs_main(model: VertexInput, instance: InstanceInput,) -> VertexOutput {
-    let model_matrix = mat4x4<f32>( // i define the instances to show
-        instance.model_matrix_0,
-        instance.model_matrix_1,
-        instance.model_matrix_2,
-        instance.model_matrix_3,
-    );
+    let model_matrix = instance_model_matrix(instance); // i define the instances to show
 
     var out: VertexOutput;
     out.tex_coords = model.tex_coords;
-    out.clip_position = camera.view_proj * model_matrix * vec4<f32>(model.position, 1.0);
+    out.normal = (model_matrix * vec4<f32>(model.normal, 0.0)).xyz; // the instances only rotate, no need for the normal matrix
+    out.tangent = vec4<f32>((model_matrix * vec4<f32>(model.tangent.xyz, 0.0)).xyz, model.tangent.w);
+    let world_position = model_matrix * vec4<f32>(model.position, 1.0);
+    out.world_position = world_position.xyz;
+    out.clip_position = camera.view_proj * world_position;
+    out.tint = instance.tint;
     return out;
 }
 
+#ifdef DEBUG_OVERDRAW
+@group(3) @binding(0)
+var<storage, read_write> overdraw_counts: array<atomic<u32>>;
+@group(3) @binding(1)
+var<uniform> overdraw_size: vec4<u32>;
+#include "common/overdraw.wgsl"
+#endif
+
 // this is for getting color from our texture
 @group(0) @binding(0)
 var t_diffuse: texture_2d<f32>;
 @group(0) @binding(1)
 var s_diffuse: sampler;
+@group(0) @binding(2) // tangent space, the materials without one have a flat map so this never changes the normal
+var t_normal: texture_2d<f32>;
+@group(0) @binding(3)
+var s_normal: sampler;
+
+#ifdef NORMAL_MAP
+// the normal of the map moved from the tangent space of the surface to the world
+fn mapped_normal(in: VertexOutput) -> vec3<f32> {
+    let normal = normalize(in.normal);
+    // the interpolation bends the tangent, gram-schmidt makes it perpendicular to the normal again
+    let tangent = normalize(in.tangent.xyz - normal * dot(in.tangent.xyz, normal));
+    let bitangent = cross(normal, tangent) * in.tangent.w;
+    let sampled = textureSample(t_normal, s_normal, in.tex_coords).xyz * 2.0 - 1.0;
+    return normalize(mat3x3<f32>(tangent, bitangent, normal) * sampled);
+}
+#endif
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    let color = textureSample(t_diffuse, s_diffuse, in.tex_coords) * in.tint;
+    // the materials without a normal map skip the sample (the variant without NORMAL_MAP)
+#ifdef NORMAL_MAP
+    let normal = mapped_normal(in);
+#else
+    let normal = normalize(in.normal);
+#endif
+    // the debug views (see rendering/debug_view.rs) replace the lighting with what they show
+#ifdef DEBUG_ALBEDO
+    return vec4<f32>(color.rgb, 1.0);
+#endif
+#ifdef DEBUG_NORMALS
+    return vec4<f32>(normal * 0.5 + 0.5, 1.0);
+#endif
+#ifdef DEBUG_WIREFRAME
+    // the edges take the color of the normal a bit, so the faces that meet at an edge can be told apart
+    return vec4<f32>(mix(vec3<f32>(0.2, 1.0, 0.4), normal * 0.5 + 0.5, 0.3), 1.0);
+#endif
+#ifdef DEBUG_OVERDRAW
+    count_overdraw(in.clip_position);
+    return vec4<f32>(0.0); // the pipeline doesn't write the color, the counts are the result
+#endif
+#ifdef CLUSTERED_LIGHTS
+    let lit = color.rgb * sky_lighting(sky, normal) + clustered_scene_lights(lights, color.rgb, in.world_position, normal, in.clip_position.xy);
+#else
+    let lit = color.rgb * sky_lighting(sky, normal) + scene_lights(lights, color.rgb, in.world_position, normal);
+#endif
+#ifdef FOG
+    return vec4<f32>(sky_fog(sky, lit, in.world_position), color.a);
+#else
+    return vec4<f32>(lit, color.a);
+#endif
 }
 
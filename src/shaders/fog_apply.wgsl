@@ -0,0 +1,38 @@
+// last pass of the volumetric fog: every pixel reads the fog up to its depth, the blending does
+// scene * transmittance + light, so the pass draws straight over the scene
+
+#include "common/fullscreen.wgsl"
+
+@group(0) @binding(0)
+var<uniform> fog: FogUniform;
+@group(0) @binding(1)
+var t_volume: texture_3d<f32>;
+@group(0) @binding(2)
+var s_volume: sampler;
+@group(0) @binding(3)
+var t_depth: texture_depth_2d;
+
+#include "common/fog.wgsl"
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> FullscreenOutput {
+    return fullscreen_vertex(index);
+}
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let size = textureDimensions(t_depth);
+    let pixel = min(vec2<u32>(in.uv * vec2<f32>(size)), size - 1u);
+    let depth = textureLoad(t_depth, pixel, 0);
+
+    var distance = fog.volume.y;
+    if (depth != 1.0 - fog.volume.z) { // the far plane can be at infinity, the sky gets the whole fog
+        let ray = fog_view_ray(in.uv);
+        distance = length(fog_unproject(in.uv, depth) - ray.origin);
+    }
+
+    // the froxel of a slice stores the fog at its end, hence the half slice back
+    let slice = (fog_distance_slice(distance) - 0.5) / f32(fog.grid.z);
+    let sample = textureSampleLevel(t_volume, s_volume, vec3<f32>(in.uv, clamp(slice, 0.0, 1.0)), 0.0);
+    return vec4<f32>(sample.rgb, sample.a);
+}
@@ -0,0 +1,57 @@
+// first pass of the volumetric fog: the density and the light that scatters towards the camera in every froxel
+// the sun is checked against the shadow map, that is what cuts the fog into light shafts
+
+#include "noise.wgsl"
+
+@group(0) @binding(0)
+var<uniform> fog: FogUniform;
+@group(0) @binding(1)
+var t_shadow: texture_depth_2d;
+@group(0) @binding(2)
+var s_shadow: sampler_comparison;
+@group(0) @binding(3)
+var scattering: texture_storage_3d<rgba16float, write>;
+
+#include "common/fog.wgsl"
+
+const PI: f32 = 3.14159265;
+
+fn fog_density(position: vec3<f32>) -> f32 {
+    let height = exp(-max(position.y - fog.medium.z, 0.0) * fog.medium.y);
+    let wind = vec3<f32>(fog.volume.w * fog.shape.z, 0.0, fog.volume.w * fog.shape.z * 0.5);
+    let noise = 0.5 + 0.5 * fbm3(position * fog.shape.y + wind, 11u, 3u, 2.0, 0.5);
+    return fog.medium.x * height * mix(1.0, noise, fog.shape.x);
+}
+
+// 1 when the sun reaches the point, 0 when something is in the way
+fn sun_visibility(position: vec3<f32>) -> f32 {
+    let clip = fog.light_view_proj * vec4<f32>(position, 1.0);
+    let ndc = clip.xyz / clip.w;
+    let uv = ndc.xy * vec2<f32>(0.5, -0.5) + 0.5;
+    if (any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) || ndc.z > 1.0) {
+        return 1.0; // outside of the shadow map everything is lit
+    }
+    return textureSampleCompareLevel(t_shadow, s_shadow, uv, ndc.z - 0.001);
+}
+
+// henyey-greenstein, g above 0 scatters forward so looking towards the sun the fog glows
+fn phase(cos_theta: f32, g: f32) -> f32 {
+    let g2 = g * g;
+    return (1.0 - g2) / (4.0 * PI * pow(1.0 + g2 - 2.0 * g * cos_theta, 1.5));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (any(id >= fog.grid.xyz)) {
+        return;
+    }
+
+    let uv = (vec2<f32>(id.xy) + 0.5) / vec2<f32>(fog.grid.xy);
+    let ray = fog_view_ray(uv);
+    let position = ray.origin + ray.direction * fog_slice_distance(f32(id.z) + 0.5);
+
+    let density = fog_density(position);
+    let sun = fog.sun_color.rgb * sun_visibility(position) * phase(dot(ray.direction, fog.sun_direction.xyz), fog.medium.w);
+    let ambient = fog.ambient_color.rgb * fog.shape.w / (4.0 * PI);
+    textureStore(scattering, id, vec4<f32>((sun + ambient) * density, density));
+}
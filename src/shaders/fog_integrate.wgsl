@@ -0,0 +1,32 @@
+// second pass of the volumetric fog: walks every column of froxels from the camera and accumulates the light and
+// how much of the scene is still seen, so each froxel has the whole fog between it and the camera
+
+@group(0) @binding(0)
+var<uniform> fog: FogUniform;
+@group(0) @binding(1)
+var scattering: texture_3d<f32>;
+@group(0) @binding(2)
+var integrated: texture_storage_3d<rgba16float, write>;
+
+#include "common/fog.wgsl"
+
+@compute @workgroup_size(8, 8, 1)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (any(id.xy >= fog.grid.xy)) {
+        return;
+    }
+
+    var light = vec3<f32>(0.0);
+    var transmittance = 1.0;
+    for (var slice = 0u; slice < fog.grid.z; slice++) {
+        let coords = vec3<i32>(vec2<i32>(id.xy), i32(slice));
+        let sample = textureLoad(scattering, coords, 0);
+        let thickness = fog_slice_distance(f32(slice + 1u)) - fog_slice_distance(f32(slice));
+        let extinction = max(sample.a, 0.00001);
+        let slice_transmittance = exp(-extinction * thickness);
+        // the integral of the light along the slice instead of light * thickness, it keeps the energy with thick slices
+        light += transmittance * (sample.rgb - sample.rgb * slice_transmittance) / extinction;
+        transmittance *= slice_transmittance;
+        textureStore(integrated, coords, vec4<f32>(light, transmittance));
+    }
+}
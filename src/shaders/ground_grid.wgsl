@@ -0,0 +1,34 @@
+// the lines of the ground under the instance brush, one quad at the height of the ground and the lines are made here
+// with the screen derivatives so they stay one pixel wide at any distance
+
+#include "common/camera.wgsl"
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+const HALF_SIZE: f32 = 50.0;
+const HEIGHT: f32 = 0.01; // over the ground so it doesn't fight with it
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) ground: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(vec2(-1.0, -1.0), vec2(1.0, -1.0), vec2(1.0, 1.0), vec2(-1.0, -1.0), vec2(1.0, 1.0), vec2(-1.0, 1.0));
+    let ground = corners[index] * HALF_SIZE;
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4<f32>(ground.x, HEIGHT, ground.y, 1.0);
+    out.ground = ground;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let distance = abs(fract(in.ground - 0.5) - 0.5) / fwidth(in.ground);
+    let line = 1.0 - min(min(distance.x, distance.y), 1.0);
+    // it fades before the edge of the quad
+    let fade = 1.0 - smoothstep(HALF_SIZE * 0.6, HALF_SIZE, length(in.ground));
+    return vec4<f32>(0.8, 0.8, 0.8, line * fade * 0.35);
+}
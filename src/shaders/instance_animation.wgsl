@@ -0,0 +1,65 @@
+// rotates every instance around its own y axis and moves it up and down, from the instances of the graph into the
+// buffer that is drawn. it only depends on the time, InstanceAnimator::matrix does the same on the cpu for picking
+
+struct Params {
+    speed: f32, // radians per second
+    count: u32,
+    time: f32,
+    enabled: u32, // 0 copies the instances as they are
+    bob_height: f32,
+    bob_speed: f32, // radians per second
+    _padding0: u32,
+    _padding1: u32,
+};
+
+// the same as InstanceRaw on the rust side, only the matrix changes here
+struct Instance {
+    model: mat4x4<f32>,
+    material: u32,
+    phase: f32,
+    tint: u32,
+    scale: f32,
+};
+
+@group(0) @binding(0)
+var<storage, read> source: array<Instance>;
+@group(0) @binding(1)
+var<storage, read_write> instances: array<Instance>;
+@group(0) @binding(2)
+var<uniform> params: Params;
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if (index >= params.count) {
+        return;
+    }
+
+    let instance = source[index];
+    let model = instance.model;
+    instances[index] = instance;
+    if (params.enabled == 0u) {
+        return;
+    }
+
+    let angle = params.speed * params.time;
+    let c = cos(angle);
+    let s = sin(angle);
+    let rotation_y = mat3x3<f32>(
+        vec3<f32>(c, 0.0, -s),
+        vec3<f32>(0.0, 1.0, 0.0),
+        vec3<f32>(s, 0.0, c),
+    );
+
+    // up and down with the phase of the instance, so they don't all go up at the same time
+    let bob = sin((params.time + instance.phase) * params.bob_speed) * params.bob_height;
+
+    // the model is translation * rotation, so we only rotate the 3x3 part and keep the translation column
+    let rotation = rotation_y * mat3x3<f32>(model[0].xyz, model[1].xyz, model[2].xyz);
+    instances[index].model = mat4x4<f32>(
+        vec4<f32>(rotation[0], model[0].w),
+        vec4<f32>(rotation[1], model[1].w),
+        vec4<f32>(rotation[2], model[2].w),
+        model[3] + vec4<f32>(0.0, bob, 0.0, 0.0),
+    );
+}
@@ -0,0 +1,61 @@
+// the surface of a mirror: a quad made from its uniform that shows the scene rendered from the reflected camera
+// the point of the mirror is projected with the matrix of that camera, where nothing was drawn it reflects the sky
+
+#include "common/camera.wgsl"
+#include "common/sky.wgsl"
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct MirrorUniform {
+    reflection_view_proj: mat4x4<f32>,
+    center: vec4<f32>,
+    axis_u: vec4<f32>, // half the width, along the surface
+    axis_v: vec4<f32>, // half the height
+    normal: vec4<f32>,
+    camera_position: vec4<f32>,
+    tint: vec4<f32>, // a is how much it reflects, the rest is the tint color
+};
+
+@group(1) @binding(0)
+var<uniform> mirror: MirrorUniform;
+@group(1) @binding(1)
+var t_reflection: texture_2d<f32>;
+@group(1) @binding(2)
+var s_reflection: sampler;
+
+@group(2) @binding(0)
+var<uniform> sky: SkyUniform;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    // two triangles, the corners in -1..1 of each axis
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0), vec2<f32>(1.0, -1.0), vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0), vec2<f32>(1.0, 1.0), vec2<f32>(-1.0, 1.0),
+    );
+    let corner = corners[index];
+    let position = mirror.center.xyz + mirror.axis_u.xyz * corner.x + mirror.axis_v.xyz * corner.y;
+
+    var out: VertexOutput;
+    out.world_position = position;
+    out.clip_position = camera.view_proj * vec4<f32>(position, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let clip = mirror.reflection_view_proj * vec4<f32>(in.world_position, 1.0);
+    let uv = clip.xy / clip.w * vec2<f32>(0.5, -0.5) + 0.5;
+    let reflection = textureSample(t_reflection, s_reflection, uv);
+
+    let view = normalize(in.world_position - mirror.camera_position.xyz);
+    let sky_color = sky_radiance(sky, reflect(view, mirror.normal.xyz));
+    let reflected = mix(sky_color, reflection.rgb, reflection.a);
+    return vec4<f32>(reflected * mirror.tint.rgb * mirror.tint.a, 1.0);
+}
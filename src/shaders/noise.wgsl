@@ -0,0 +1,163 @@
+// the same noise as util/noise.rs, keep both files in sync so cpu and gpu give the same values
+
+fn noise_hash3(x: i32, y: i32, z: i32, seed: u32) -> u32 {
+    var h = seed
+        ^ (bitcast<u32>(x) * 0x8da6b343u)
+        ^ (bitcast<u32>(y) * 0xd8163841u)
+        ^ (bitcast<u32>(z) * 0xcb1ab31fu);
+    h = h * 747796405u + 2891336453u;
+    h = ((h >> ((h >> 28u) + 4u)) ^ h) * 277803737u;
+    return (h >> 22u) ^ h;
+}
+
+fn noise_fade(t: f32) -> f32 {
+    return t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+}
+
+fn noise_grad2(h: u32, x: f32, y: f32) -> f32 {
+    let d = 0.70710677;
+    switch (h & 7u) {
+        case 0u: { return x; }
+        case 1u: { return -x; }
+        case 2u: { return y; }
+        case 3u: { return -y; }
+        case 4u: { return d * (x + y); }
+        case 5u: { return d * (-x + y); }
+        case 6u: { return d * (x - y); }
+        default: { return d * (-x - y); }
+    }
+}
+
+fn noise_grad3(h: u32, x: f32, y: f32, z: f32) -> f32 {
+    switch (h % 12u) {
+        case 0u: { return x + y; }
+        case 1u: { return -x + y; }
+        case 2u: { return x - y; }
+        case 3u: { return -x - y; }
+        case 4u: { return x + z; }
+        case 5u: { return -x + z; }
+        case 6u: { return x - z; }
+        case 7u: { return -x - z; }
+        case 8u: { return y + z; }
+        case 9u: { return -y + z; }
+        case 10u: { return y - z; }
+        default: { return -y - z; }
+    }
+}
+
+fn perlin2(p: vec2<f32>, seed: u32) -> f32 {
+    let i = floor(p);
+    let f = p - i;
+    let xi = i32(i.x);
+    let yi = i32(i.y);
+
+    let g00 = noise_grad2(noise_hash3(xi, yi, 0, seed), f.x, f.y);
+    let g10 = noise_grad2(noise_hash3(xi + 1, yi, 0, seed), f.x - 1.0, f.y);
+    let g01 = noise_grad2(noise_hash3(xi, yi + 1, 0, seed), f.x, f.y - 1.0);
+    let g11 = noise_grad2(noise_hash3(xi + 1, yi + 1, 0, seed), f.x - 1.0, f.y - 1.0);
+
+    let u = noise_fade(f.x);
+    let v = noise_fade(f.y);
+    return mix(mix(g00, g10, u), mix(g01, g11, u), v);
+}
+
+fn perlin3(p: vec3<f32>, seed: u32) -> f32 {
+    let i = floor(p);
+    let f = p - i;
+    let xi = i32(i.x);
+    let yi = i32(i.y);
+    let zi = i32(i.z);
+
+    let c000 = noise_grad3(noise_hash3(xi, yi, zi, seed), f.x, f.y, f.z);
+    let c100 = noise_grad3(noise_hash3(xi + 1, yi, zi, seed), f.x - 1.0, f.y, f.z);
+    let c010 = noise_grad3(noise_hash3(xi, yi + 1, zi, seed), f.x, f.y - 1.0, f.z);
+    let c110 = noise_grad3(noise_hash3(xi + 1, yi + 1, zi, seed), f.x - 1.0, f.y - 1.0, f.z);
+    let c001 = noise_grad3(noise_hash3(xi, yi, zi + 1, seed), f.x, f.y, f.z - 1.0);
+    let c101 = noise_grad3(noise_hash3(xi + 1, yi, zi + 1, seed), f.x - 1.0, f.y, f.z - 1.0);
+    let c011 = noise_grad3(noise_hash3(xi, yi + 1, zi + 1, seed), f.x, f.y - 1.0, f.z - 1.0);
+    let c111 = noise_grad3(noise_hash3(xi + 1, yi + 1, zi + 1, seed), f.x - 1.0, f.y - 1.0, f.z - 1.0);
+
+    let u = noise_fade(f.x);
+    let v = noise_fade(f.y);
+    let w = noise_fade(f.z);
+    let x00 = mix(c000, c100, u);
+    let x10 = mix(c010, c110, u);
+    let x01 = mix(c001, c101, u);
+    let x11 = mix(c011, c111, u);
+    return mix(mix(x00, x10, v), mix(x01, x11, v), w);
+}
+
+fn simplex2(p: vec2<f32>, seed: u32) -> f32 {
+    let F2 = 0.36602542;
+    let G2 = 0.21132487;
+
+    let s = (p.x + p.y) * F2;
+    let i = floor(p.x + s);
+    let j = floor(p.y + s);
+    let t = (i + j) * G2;
+    let x0 = p.x - (i - t);
+    let y0 = p.y - (j - t);
+
+    var i1 = 0;
+    var j1 = 1;
+    if (x0 > y0) {
+        i1 = 1;
+        j1 = 0;
+    }
+    let x1 = x0 - f32(i1) + G2;
+    let y1 = y0 - f32(j1) + G2;
+    let x2 = x0 - 1.0 + 2.0 * G2;
+    let y2 = y0 - 1.0 + 2.0 * G2;
+
+    let ii = i32(i);
+    let jj = i32(j);
+
+    var n = 0.0;
+    let t0 = 0.5 - x0 * x0 - y0 * y0;
+    if (t0 > 0.0) {
+        n += t0 * t0 * t0 * t0 * noise_grad2(noise_hash3(ii, jj, 0, seed), x0, y0);
+    }
+    let t1 = 0.5 - x1 * x1 - y1 * y1;
+    if (t1 > 0.0) {
+        n += t1 * t1 * t1 * t1 * noise_grad2(noise_hash3(ii + i1, jj + j1, 0, seed), x1, y1);
+    }
+    let t2 = 0.5 - x2 * x2 - y2 * y2;
+    if (t2 > 0.0) {
+        n += t2 * t2 * t2 * t2 * noise_grad2(noise_hash3(ii + 1, jj + 1, 0, seed), x2, y2);
+    }
+    return 70.0 * n;
+}
+
+fn fbm2(p: vec2<f32>, seed: u32, octaves: u32, lacunarity: f32, gain: f32) -> f32 {
+    var sum = 0.0;
+    var amplitude = 1.0;
+    var frequency = 1.0;
+    var total_amplitude = 0.0;
+    for (var octave = 0u; octave < octaves; octave++) {
+        sum += perlin2(p * frequency, seed + octave) * amplitude;
+        total_amplitude += amplitude;
+        amplitude *= gain;
+        frequency *= lacunarity;
+    }
+    if (total_amplitude > 0.0) {
+        return sum / total_amplitude;
+    }
+    return 0.0;
+}
+
+fn fbm3(p: vec3<f32>, seed: u32, octaves: u32, lacunarity: f32, gain: f32) -> f32 {
+    var sum = 0.0;
+    var amplitude = 1.0;
+    var frequency = 1.0;
+    var total_amplitude = 0.0;
+    for (var octave = 0u; octave < octaves; octave++) {
+        sum += perlin3(p * frequency, seed + octave) * amplitude;
+        total_amplitude += amplitude;
+        amplitude *= gain;
+        frequency *= lacunarity;
+    }
+    if (total_amplitude > 0.0) {
+        return sum / total_amplitude;
+    }
+    return 0.0;
+}
@@ -0,0 +1,72 @@
+// moves every particle of an emitter one frame and puts the new ones in the slots the cpu gave (a ring from spawn_start)
+
+#include "common/particles.wgsl"
+
+@group(0) @binding(0)
+var<storage, read_write> particles: array<Particle>;
+@group(0) @binding(1)
+var<uniform> emitter: Emitter;
+
+const TAU: f32 = 6.28318530718;
+
+// pcg, good enough for where the sparks go
+fn hash(value: u32) -> u32 {
+    let state = value * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+fn random(state: ptr<function, u32>) -> f32 {
+    *state = hash(*state);
+    return f32(*state) / 4294967295.0;
+}
+
+// uniform in the cone of the spread around the direction
+fn random_direction(state: ptr<function, u32>) -> vec3<f32> {
+    let direction = emitter.direction.xyz;
+    let cos_angle = mix(1.0, cos(emitter.origin.w), random(state));
+    let sin_angle = sqrt(max(1.0 - cos_angle * cos_angle, 0.0));
+    let around = random(state) * TAU;
+    let helper = select(vec3<f32>(0.0, 1.0, 0.0), vec3<f32>(1.0, 0.0, 0.0), abs(direction.y) > 0.99);
+    let tangent = normalize(cross(helper, direction));
+    let bitangent = cross(direction, tangent);
+    return direction * cos_angle + (tangent * cos(around) + bitangent * sin(around)) * sin_angle;
+}
+
+fn spawn(index: u32) -> Particle {
+    var state = hash(index ^ hash(emitter.seed));
+    var particle: Particle;
+    // a point in the sphere of the radius, the cube root keeps them even inside it
+    let z = random(&state) * 2.0 - 1.0;
+    let around = random(&state) * TAU;
+    let outward = vec3<f32>(sqrt(max(1.0 - z * z, 0.0)) * vec2<f32>(cos(around), sin(around)), z);
+    let position = emitter.origin.xyz + outward * emitter.radius * pow(random(&state), 1.0 / 3.0);
+    let speed = mix(emitter.speed.x, emitter.speed.y, random(&state));
+    let lifetime = mix(emitter.lifetime.x, emitter.lifetime.y, random(&state));
+    particle.position = vec4<f32>(position, 0.0);
+    particle.velocity = vec4<f32>(random_direction(&state) * speed, lifetime);
+    return particle;
+}
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if (index >= emitter.capacity) {
+        return;
+    }
+    // how far the slot is from the first new one, going around the ring
+    let slot = (index + emitter.capacity - emitter.spawn_start) % emitter.capacity;
+    if (slot < emitter.spawn_count) {
+        particles[index] = spawn(index);
+        return;
+    }
+
+    let particle = particles[index];
+    if (!is_alive(particle)) {
+        return;
+    }
+    let delta_time = emitter.gravity.w;
+    let velocity = (particle.velocity.xyz + emitter.gravity.xyz * delta_time) / (1.0 + emitter.direction.w * delta_time);
+    particles[index].position = vec4<f32>(particle.position.xyz + velocity * delta_time, particle.position.w + delta_time);
+    particles[index].velocity = vec4<f32>(velocity, particle.velocity.w);
+}
@@ -0,0 +1,68 @@
+// the particles as soft round quads facing the camera, one instance per slot of the emitter
+// the dead ones put the six vertices in the same place so nothing is drawn
+
+#include "common/camera.wgsl"
+#include "common/particles.wgsl"
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(1) @binding(0)
+var<storage, read> particles: array<Particle>;
+@group(1) @binding(1)
+var<uniform> emitter: Emitter;
+
+// the samples of the curve and the gradient of the emitter, life goes from 0 to 1
+fn size_at(life: f32) -> f32 {
+    let x = clamp(life, 0.0, 1.0) * 15.0;
+    let a = u32(floor(x));
+    let b = min(a + 1u, 15u);
+    return mix(emitter.sizes[a / 4u][a % 4u], emitter.sizes[b / 4u][b % 4u], fract(x));
+}
+
+fn color_at(life: f32) -> vec4<f32> {
+    let x = clamp(life, 0.0, 1.0) * 15.0;
+    let a = u32(floor(x));
+    return mix(emitter.colors[a], emitter.colors[min(a + 1u, 15u)], fract(x));
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>, // -1 to 1 across the quad
+    @location(1) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32, @builtin(instance_index) instance_index: u32) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    var out: VertexOutput;
+    let particle = particles[instance_index];
+    if (!is_alive(particle)) {
+        out.clip_position = vec4<f32>(2.0, 2.0, 2.0, 1.0);
+        out.uv = vec2<f32>(0.0);
+        out.color = vec4<f32>(0.0);
+        return out;
+    }
+
+    let life = particle.position.w / max(particle.velocity.w, 1e-5);
+    let size = size_at(life);
+    let corner = corners[vertex_index];
+    let position = particle.position.xyz + (emitter.camera_right.xyz * corner.x + emitter.camera_up.xyz * corner.y) * size * 0.5;
+    out.clip_position = camera.view_proj * vec4<f32>(position, 1.0);
+    out.uv = corner;
+    out.color = color_at(life);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let fade = 1.0 - smoothstep(0.5, 1.0, length(in.uv));
+    return vec4<f32>(in.color.rgb, in.color.a * fade);
+}
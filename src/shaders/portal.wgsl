@@ -0,0 +1,74 @@
+// the surface of a portal: a quad made from its uniform that shows the scene rendered from another camera
+// linked portals project the point with that camera, fixed ones stretch the whole image over the quad
+// where nothing was drawn it shows the sky in the direction the other camera looks through that pixel
+
+#include "common/camera.wgsl"
+#include "common/sky.wgsl"
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct PortalUniform {
+    view_proj: mat4x4<f32>, // the camera of the image
+    inverse_view_proj: mat4x4<f32>,
+    center: vec4<f32>,
+    axis_u: vec4<f32>, // half the width, along the surface
+    axis_v: vec4<f32>, // half the height
+    image_eye: vec4<f32>,
+    fallback: vec4<f32>,
+    mode: vec4<u32>, // x: 1 uses the uv of the quad, y: 1 there is no image and the fallback color is shown
+};
+
+@group(1) @binding(0)
+var<uniform> portal: PortalUniform;
+@group(1) @binding(1)
+var t_view: texture_2d<f32>;
+@group(1) @binding(2)
+var s_view: sampler;
+
+@group(2) @binding(0)
+var<uniform> sky: SkyUniform;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+    @location(1) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    // two triangles, the corners in -1..1 of each axis
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0), vec2<f32>(1.0, -1.0), vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0), vec2<f32>(1.0, 1.0), vec2<f32>(-1.0, 1.0),
+    );
+    let corner = corners[index];
+    let position = portal.center.xyz + portal.axis_u.xyz * corner.x + portal.axis_v.xyz * corner.y;
+
+    var out: VertexOutput;
+    out.world_position = position;
+    out.uv = vec2<f32>(corner.x, -corner.y) * 0.5 + 0.5;
+    out.clip_position = camera.view_proj * vec4<f32>(position, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    var uv = in.uv;
+    if portal.mode.x == 0u {
+        let clip = portal.view_proj * vec4<f32>(in.world_position, 1.0);
+        uv = clip.xy / clip.w * vec2<f32>(0.5, -0.5) + 0.5;
+    }
+    let image = textureSample(t_view, s_view, uv);
+
+    // back from the uv to the world, the depth doesn't matter for a direction
+    let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.5, 1.0);
+    let point = portal.inverse_view_proj * ndc;
+    let direction = normalize(point.xyz / point.w - portal.image_eye.xyz);
+    let color = mix(sky_radiance(sky, direction), image.rgb, image.a);
+
+    if portal.mode.y == 1u {
+        return vec4<f32>(portal.fallback.rgb, 1.0);
+    }
+    return vec4<f32>(color, 1.0);
+}
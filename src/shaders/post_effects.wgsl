@@ -0,0 +1,89 @@
+// the final pass: takes the scene and puts the screen effects on top (flash, vignette, chromatic aberration, fade)
+// and then takes the color to the monitor, sdr with the brightness of the calibration or hdr in scRGB (1.0 is 80 nits)
+
+struct Effects {
+    flash: vec4<f32>,          // rgb color, a = strength
+    vignette_color: vec4<f32>, // rgb color, a = strength
+    fade: vec4<f32>,           // rgb color, a = how much of the screen is covered, for scene transitions
+    output: vec4<f32>,         // x = 0 sdr / 1 hdr, y = scale of 1.0, z = sdr gamma, w = peak of the monitor
+    aberration: f32,
+    aspect: f32,
+    calibration: f32,          // 0 off, 1 brightness pattern, 2 peak pattern
+    _padding: f32,
+};
+
+@group(0) @binding(0)
+var t_scene: texture_2d<f32>;
+@group(0) @binding(1)
+var s_scene: sampler;
+@group(0) @binding(2)
+var<uniform> effects: Effects;
+
+#include "common/fullscreen.wgsl"
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> FullscreenOutput {
+    return fullscreen_vertex(index);
+}
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let from_center = in.uv - vec2<f32>(0.5, 0.5);
+
+    // the red and blue channels are pulled apart towards the borders
+    let offset = from_center * effects.aberration;
+    let r = textureSample(t_scene, s_scene, in.uv + offset).r;
+    let g = textureSample(t_scene, s_scene, in.uv).g;
+    let b = textureSample(t_scene, s_scene, in.uv - offset).b;
+    var color = vec3<f32>(r, g, b);
+
+    let distance = length(from_center * vec2<f32>(effects.aspect, 1.0));
+    let vignette = smoothstep(0.35, 0.9, distance) * effects.vignette_color.a;
+    color = mix(color, effects.vignette_color.rgb, vignette);
+
+    color = mix(color, effects.flash.rgb, effects.flash.a);
+    color = mix(color, effects.fade.rgb, effects.fade.a);
+
+    if effects.calibration > 0.5 {
+        return vec4<f32>(calibration_pattern(in.uv), 1.0);
+    }
+    return vec4<f32>(display_output(color), 1.0);
+}
+
+fn display_output(color: vec3<f32>) -> vec3<f32> {
+    if effects.output.x > 0.5 {
+        return min(max(color, vec3<f32>(0.0)) * effects.output.y, vec3<f32>(effects.output.w));
+    }
+    return pow(clamp(color, vec3<f32>(0.0), vec3<f32>(1.0)), vec3<f32>(effects.output.z));
+}
+
+// true inside a square of the given half size (in screen heights) around center
+fn in_square(uv: vec2<f32>, center: vec2<f32>, half_size: f32) -> bool {
+    let offset = abs((uv - center) * vec2<f32>(effects.aspect, 1.0));
+    return max(offset.x, offset.y) < half_size;
+}
+
+fn calibration_pattern(uv: vec2<f32>) -> vec3<f32> {
+    // peak: the inner square is at the peak of the settings and the outer one far over any monitor,
+    // once the peak reaches what the monitor does both clip to the same and the inner one disappears
+    if effects.calibration > 1.5 {
+        if in_square(uv, vec2<f32>(0.5), 0.08) {
+            return vec3<f32>(effects.output.w);
+        }
+        if in_square(uv, vec2<f32>(0.5), 0.25) {
+            return vec3<f32>(10000.0 / 80.0);
+        }
+        return vec3<f32>(0.0);
+    }
+
+    // brightness: three squares from very dark to dark on black and a white one, the darkest has to be barely visible
+    // they go through the same output as the game so the slider moves them
+    var levels = array<f32, 4>(0.002, 0.01, 0.04, 1.0);
+    for (var i = 0; i < 4; i++) {
+        let center = vec2<f32>(0.5 + (f32(i) - 1.5) * 0.3 / effects.aspect, 0.5);
+        if in_square(uv, center, 0.1) {
+            return display_output(vec3<f32>(levels[i]));
+        }
+    }
+    return vec3<f32>(0.0);
+}
@@ -0,0 +1,50 @@
+#include "common/camera.wgsl"
+#include "common/instancing.wgsl"
+
+// a screen that shows a texture made on the cpu (see external_texture.rs), it is unlit so it glows in the dark
+// the group 0 is not the one of the scene materials, the rust side reads it from here (see material_reflection.rs)
+
+@group(1) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct ScreenParams {
+    tint: vec4<f32>,
+    brightness: f32,
+    scanlines: f32, // how many lines from the top to the bottom, 0 for none
+}
+
+@group(0) @binding(0)
+var t_screen: texture_2d<f32>;
+@group(0) @binding(1)
+var s_screen: sampler;
+@group(0) @binding(2)
+var<uniform> screen: ScreenParams;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) tint: vec4<f32>, // of the instance
+}
+
+@vertex
+fn vs_main(model: VertexInput, instance: InstanceInput,) -> VertexOutput {
+    var out: VertexOutput;
+    out.tex_coords = model.tex_coords;
+    out.clip_position = camera.view_proj * instance_model_matrix(instance) * vec4<f32>(model.position, 1.0);
+    out.tint = instance.tint;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    var color = textureSample(t_screen, s_screen, in.tex_coords).rgb * screen.tint.rgb * in.tint.rgb * screen.brightness;
+    if screen.scanlines > 0.0 {
+        color *= 0.75 + 0.25 * sin(in.tex_coords.y * screen.scanlines * 6.2831853);
+    }
+    return vec4<f32>(color, 1.0);
+}
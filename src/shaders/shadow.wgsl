@@ -0,0 +1,19 @@
+// the depth of the scene seen from the sun, only the vertex stage is needed
+
+#include "common/instancing.wgsl"
+
+struct ShadowUniform {
+    light_view_proj: mat4x4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> shadow: ShadowUniform;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+}
+
+@vertex
+fn vs_main(model: VertexInput, instance: InstanceInput) -> @builtin(position) vec4<f32> {
+    return shadow.light_view_proj * instance_model_matrix(instance) * vec4<f32>(model.position, 1.0);
+}
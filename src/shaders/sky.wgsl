@@ -0,0 +1,22 @@
+// the sky behind the scene, a fullscreen triangle where every pixel looks up the sky in the direction of its view ray
+
+#include "common/fullscreen.wgsl"
+#include "common/sky.wgsl"
+
+@group(0) @binding(0)
+var<uniform> sky: SkyUniform;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> FullscreenOutput {
+    return fullscreen_vertex(index);
+}
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    // two points on the view ray of the pixel, the far plane can be at infinity so the second is halfway
+    let ndc = vec2<f32>(in.uv.x * 2.0 - 1.0, 1.0 - in.uv.y * 2.0);
+    let near = sky.inverse_view_proj * vec4<f32>(ndc, sky.near_depth, 1.0);
+    let further = sky.inverse_view_proj * vec4<f32>(ndc, 0.5, 1.0);
+    let direction = normalize(further.xyz / further.w - near.xyz / near.w);
+    return vec4<f32>(sky_radiance(sky, direction), 1.0);
+}
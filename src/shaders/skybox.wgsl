@@ -0,0 +1,55 @@
+// a cubemap behind the scene, a cube around the camera that is always at the far plane
+// it is drawn after the opaque meshes with the depth test, so only the pixels nothing covered look it up
+
+struct SkyboxUniform {
+    view_proj: mat4x4<f32>, // the camera without its position, the cube never gets closer
+    params: vec4<f32>,      // x = intensity, y = the depth of the far plane
+};
+
+@group(0) @binding(0)
+var<uniform> skybox: SkyboxUniform;
+@group(0) @binding(1)
+var t_cubemap: texture_cube<f32>;
+@group(0) @binding(2)
+var s_cubemap: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) direction: vec3<f32>,
+};
+
+// the 36 corners of the 12 triangles of a cube, made from the index so there is no vertex buffer
+// the pipeline doesn't cull, the camera is inside the cube and the winding doesn't matter
+fn cube_corner(index: u32) -> vec3<f32> {
+    // each face is two triangles of a quad, the faces go +x, -x, +y, -y, +z, -z
+    var quad = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0), vec2<f32>(1.0, -1.0), vec2<f32>(1.0, 1.0),
+        vec2<f32>(1.0, 1.0), vec2<f32>(-1.0, 1.0), vec2<f32>(-1.0, -1.0),
+    );
+    let corner = quad[index % 6u];
+    switch index / 6u {
+        case 0u: { return vec3<f32>(1.0, corner.y, corner.x); }
+        case 1u: { return vec3<f32>(-1.0, corner.y, -corner.x); }
+        case 2u: { return vec3<f32>(corner.x, 1.0, corner.y); }
+        case 3u: { return vec3<f32>(corner.x, -1.0, -corner.y); }
+        case 4u: { return vec3<f32>(-corner.x, corner.y, 1.0); }
+        default: { return vec3<f32>(corner.x, corner.y, -1.0); }
+    }
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let corner = cube_corner(index);
+    let clip = skybox.view_proj * vec4<f32>(corner, 1.0);
+    var out: VertexOutput;
+    // z = w * far depth puts every pixel of the cube on the far plane after the divide
+    out.clip_position = vec4<f32>(clip.xy, clip.w * skybox.params.y, clip.w);
+    out.direction = corner;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_cubemap, s_cubemap, normalize(in.direction)).rgb;
+    return vec4<f32>(color * skybox.params.x, 1.0);
+}
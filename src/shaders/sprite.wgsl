@@ -0,0 +1,70 @@
+// 2D sprites, every instance is a quad made in the vertex shader from the vertex index
+
+#include "common/camera.wgsl"
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(1) @binding(0)
+var t_atlas: texture_2d<f32>;
+@group(1) @binding(1)
+var s_atlas: sampler;
+
+#ifdef COUNT_OVERDRAW
+@group(2) @binding(0)
+var<storage, read_write> overdraw_counts: array<atomic<u32>>;
+@group(2) @binding(1)
+var<uniform> overdraw_size: vec4<u32>;
+#include "common/overdraw.wgsl"
+#endif
+
+struct SpriteInput {
+    @location(0) position: vec2<f32>, // center of the sprite in world units
+    @location(1) size: vec2<f32>,
+    @location(2) rotation: f32,       // radians
+    @location(3) uv_rect: vec4<f32>,  // min uv in xy, max uv in zw
+    @location(4) color: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32, sprite: SpriteInput) -> VertexOutput {
+    // two triangles: 0 1 2, 2 1 3 of the corners (0,0) (1,0) (0,1) (1,1)
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+    );
+    let corner = corners[index];
+
+    let local = (corner - vec2<f32>(0.5, 0.5)) * sprite.size;
+    let c = cos(sprite.rotation);
+    let s = sin(sprite.rotation);
+    let rotated = vec2<f32>(local.x * c - local.y * s, local.x * s + local.y * c);
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4<f32>(sprite.position + rotated, 0.0, 1.0);
+    out.uv = mix(sprite.uv_rect.xy, sprite.uv_rect.zw, corner);
+    out.color = sprite.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_atlas, s_atlas, in.uv) * in.color;
+    if (color.a <= 0.0) {
+        discard;
+    }
+#ifdef COUNT_OVERDRAW
+    count_overdraw(in.clip_position);
+#endif
+    return color;
+}
@@ -0,0 +1,59 @@
+// the ui text and its backgrounds, quads in screen pixels over the final image
+// the atlas only has the coverage of the glyphs, the color comes from the instance
+
+struct TextUniform {
+    screen_size: vec2<f32>,
+    white_level: f32, // what 1.0 is on the surface, more than 1 in hdr so the ui is as bright as the paper white
+    _padding: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> text: TextUniform;
+@group(0) @binding(1)
+var t_glyphs: texture_2d<f32>;
+@group(0) @binding(2)
+var s_glyphs: sampler;
+
+struct GlyphInput {
+    @location(0) position: vec2<f32>, // top left corner in pixels
+    @location(1) size: vec2<f32>,
+    @location(2) uv_rect: vec4<f32>,  // min uv in xy, max uv in zw
+    @location(3) color: vec4<f32>,    // linear
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32, glyph: GlyphInput) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+    );
+    let corner = corners[index];
+    let pixel = glyph.position + corner * glyph.size;
+
+    var out: VertexOutput;
+    // pixels to clip space, y goes down on the screen
+    out.clip_position = vec4<f32>(pixel.x / text.screen_size.x * 2.0 - 1.0, 1.0 - pixel.y / text.screen_size.y * 2.0, 0.0, 1.0);
+    out.uv = mix(glyph.uv_rect.xy, glyph.uv_rect.zw, corner);
+    out.color = glyph.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let coverage = textureSample(t_glyphs, s_glyphs, in.uv).r;
+    let alpha = in.color.a * coverage;
+    if (alpha <= 0.0) {
+        discard;
+    }
+    return vec4<f32>(in.color.rgb * text.white_level, alpha);
+}
@@ -0,0 +1,37 @@
+// the ribbons of the trails, the strips are made on the cpu every frame already facing the camera
+
+#include "common/camera.wgsl"
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(1) @binding(0)
+var t_trail: texture_2d<f32>;
+@group(1) @binding(1)
+var s_trail: sampler;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) uv: vec2<f32>, // u along the trail (it repeats), v across it
+    @location(2) color: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(vertex: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4<f32>(vertex.position, 1.0);
+    out.uv = vertex.uv;
+    out.color = vertex.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(t_trail, s_trail, in.uv) * in.color;
+}
@@ -0,0 +1,44 @@
+// the flat shapes of the ui (button backgrounds, panels, outlines), vertices in screen pixels over the final image
+
+struct UiUniform {
+    screen_size: vec2<f32>,
+    white_level: f32, // what 1.0 is on the surface, more than 1 in hdr so the ui is as bright as the paper white
+    _padding: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> ui: UiUniform;
+
+#ifdef COUNT_OVERDRAW
+@group(1) @binding(0)
+var<storage, read_write> overdraw_counts: array<atomic<u32>>;
+@group(1) @binding(1)
+var<uniform> overdraw_size: vec4<u32>;
+#include "common/overdraw.wgsl"
+#endif
+
+struct VertexInput {
+    @location(0) position: vec2<f32>, // pixels, y goes down
+    @location(1) color: vec4<f32>,    // linear
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(vertex: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(vertex.position.x / ui.screen_size.x * 2.0 - 1.0, 1.0 - vertex.position.y / ui.screen_size.y * 2.0, 0.0, 1.0);
+    out.color = vertex.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+#ifdef COUNT_OVERDRAW
+    count_overdraw(in.clip_position);
+#endif
+    return vec4<f32>(in.color.rgb * ui.white_level, in.color.a);
+}
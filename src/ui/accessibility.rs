@@ -0,0 +1,212 @@
+// the ui for players that can't see it: every change of the focus, a press or an announcement becomes an event with
+// the text a screen reader would say ("Play, button, 1 of 3"), the game (or a tts integration) drains them every frame
+// and when a speech hook is set they are also spoken right away
+// menus are walked with the keyboard through the focus, so everything that can be clicked can be reached without the mouse
+
+use std::process::{Child, Command};
+
+use crate::input::button_module::Button;
+use crate::input::input_state::InputState;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Role {
+    Button,
+    Toggle,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum UiEvent {
+    Focused { label: String, role: Role, checked: Option<bool>, position: usize, count: usize },
+    Activated { label: String },
+    Announcement(String), // something that changed without the focus moving (paused, saved, a new objective)
+}
+
+impl UiEvent {
+    // what a screen reader says for it
+    pub fn describe(&self) -> String {
+        match self {
+            UiEvent::Focused { label, role, checked, position, count } => {
+                let role = match (role, checked) {
+                    (Role::Toggle, Some(true)) => "toggle, on",
+                    (Role::Toggle, _) => "toggle, off",
+                    (Role::Button, _) => "button",
+                };
+                format!("{}, {}, {} of {}", label, role, position + 1, count)
+            }
+            UiEvent::Activated { label } => format!("{} pressed", label),
+            UiEvent::Announcement(text) => text.clone(),
+        }
+    }
+
+    // a new focus cuts what was being said, nobody wants to hear the whole menu they skipped
+    pub fn interrupts(&self) -> bool {
+        matches!(self, UiEvent::Focused { .. })
+    }
+}
+
+// where the events go to be spoken, the platform tts (or a test that collects them) implements it
+pub trait SpeechHook {
+    fn speak(&mut self, text: &str, interrupt: bool);
+}
+
+// the text to speech of the system through its command line program, a new focus stops what was being said
+// the ui_screen_reader cvar turns it on
+pub struct SystemSpeech {
+    speaking: Option<Child>,
+}
+
+impl SystemSpeech {
+    pub fn new() -> Self {
+        Self { speaking: None }
+    }
+
+    fn command(text: &str) -> Command {
+        if cfg!(target_os = "macos") {
+            let mut command = Command::new("say");
+            command.arg(text);
+            command
+        } else if cfg!(target_os = "windows") {
+            let mut command = Command::new("powershell");
+            let text = text.replace('\'', "''");
+            command.args(["-NoProfile", "-Command", &format!("Add-Type -AssemblyName System.Speech; (New-Object System.Speech.Synthesis.SpeechSynthesizer).Speak('{}')", text)]);
+            command
+        } else {
+            let mut command = Command::new("spd-say");
+            command.arg("--").arg(text);
+            command
+        }
+    }
+}
+
+impl SpeechHook for SystemSpeech {
+    fn speak(&mut self, text: &str, interrupt: bool) {
+        if interrupt {
+            if let Some(mut speaking) = self.speaking.take() {
+                let _ = speaking.kill();
+                let _ = speaking.wait();
+            }
+        }
+        match Self::command(text).spawn() {
+            Ok(child) => self.speaking = Some(child),
+            Err(e) => eprintln!("the screen reader couldn't speak: {}", e),
+        }
+    }
+}
+
+impl Drop for SystemSpeech {
+    fn drop(&mut self) {
+        if let Some(mut speaking) = self.speaking.take() {
+            let _ = speaking.kill();
+            let _ = speaking.wait();
+        }
+    }
+}
+
+pub struct UiAccessibility {
+    events: Vec<UiEvent>,
+    speech: Option<Box<dyn SpeechHook>>,
+}
+
+impl UiAccessibility {
+    pub fn new() -> Self {
+        Self { events: Vec::new(), speech: None }
+    }
+
+    pub fn set_speech_hook(&mut self, hook: Option<Box<dyn SpeechHook>>) {
+        self.speech = hook;
+    }
+
+    pub fn emit(&mut self, event: UiEvent) {
+        if let Some(speech) = &mut self.speech {
+            speech.speak(&event.describe(), event.interrupts());
+        }
+        self.events.push(event);
+    }
+
+    pub fn announce(&mut self, text: &str) {
+        self.emit(UiEvent::Announcement(text.to_string()));
+    }
+
+    // the events since the last call, a tts integration reads them once per frame
+    pub fn drain_events(&mut self) -> Vec<UiEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
+
+impl Default for UiAccessibility {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// the focused button of a menu, it moves with the UiNext and UiPrevious actions (or the mouse) and UiAccept presses it
+pub struct FocusNavigator {
+    focused: Option<usize>,
+}
+
+impl FocusNavigator {
+    pub fn new() -> Self {
+        Self { focused: None }
+    }
+
+    // the menu is the buttons in the order they are walked, the inactive ones are skipped
+    // returns the index of the button that was pressed this frame, with the keyboard or clicked
+    pub fn update(&mut self, input: &InputState, buttons: &mut [&mut Button], accessibility: &mut UiAccessibility) -> Option<usize> {
+        let reachable: Vec<usize> = (0..buttons.len()).filter(|index| buttons[*index].game_object.active).collect();
+        if reachable.is_empty() {
+            self.focused = None;
+            return None;
+        }
+
+        let mut pressed = None;
+        for (index, button) in buttons.iter_mut().enumerate() {
+            if button.update(input) {
+                pressed = Some(index);
+            }
+        }
+
+        let current = self.focused.and_then(|focused| reachable.iter().position(|index| *index == focused));
+        let mut next = current;
+        if input.action_just_pressed("UiNext") {
+            next = Some(current.map_or(0, |position| (position + 1) % reachable.len()));
+        } else if input.action_just_pressed("UiPrevious") {
+            next = Some(current.map_or(reachable.len() - 1, |position| (position + reachable.len() - 1) % reachable.len()));
+        } else if input.mouse_delta() != (0, 0) {
+            // the mouse takes the focus to what it is over, so the reader follows it too
+            if let Some(hovered) = reachable.iter().position(|index| buttons[*index].hover) {
+                next = Some(hovered);
+            }
+        }
+
+        if next != current {
+            if let Some(position) = next {
+                let button = &buttons[reachable[position]];
+                accessibility.emit(UiEvent::Focused {
+                    label: button.label().to_string(),
+                    role: if button.toggle.is_some() { Role::Toggle } else { Role::Button },
+                    checked: button.toggle,
+                    position,
+                    count: reachable.len(),
+                });
+            }
+            self.focused = next.map(|position| reachable[position]);
+        }
+        for (index, button) in buttons.iter_mut().enumerate() {
+            button.focused = self.focused == Some(index);
+        }
+
+        if pressed.is_none() && input.action_just_pressed("UiAccept") {
+            pressed = self.focused;
+        }
+        if let Some(index) = pressed {
+            accessibility.emit(UiEvent::Activated { label: buttons[index].label().to_string() });
+        }
+        pressed
+    }
+}
+
+impl Default for FocusNavigator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
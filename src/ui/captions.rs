@@ -0,0 +1,138 @@
+// captions for the sounds: a sound can carry the text of what it is ("[door creaks]") or what is said, with the one
+// that says it, and when it plays the caption goes to a queue drawn at the bottom of the screen for a while
+// whatever plays the sounds calls on_sound with their name (App::play_sound does it with the name of the file) and
+// the captions registered for that name show up. they are only queued when the captions are on in the ui settings
+
+use std::collections::{HashMap, VecDeque};
+
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::ttf::Font;
+
+use crate::ui::scale::UiSettings;
+use crate::ui::text::TextRenderer;
+use crate::ui::ui_renderer::UiRenderer;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Caption {
+    pub speaker: Option<String>, // none for the sounds that are not somebody talking
+    pub text: String,
+    pub duration: f32, // seconds on screen
+}
+
+impl Caption {
+    pub fn new(text: &str, duration: f32) -> Self {
+        Self { speaker: None, text: text.to_string(), duration }
+    }
+
+    pub fn spoken(speaker: &str, text: &str, duration: f32) -> Self {
+        Self { speaker: Some(speaker.to_string()), text: text.to_string(), duration }
+    }
+}
+
+struct ShownCaption {
+    caption: Caption,
+    remaining: f32,
+}
+
+pub struct Captions {
+    sounds: HashMap<String, Caption>, // the caption each sound carries
+    queue: VecDeque<ShownCaption>,
+    pub max_lines: usize, // the ones after these wait their turn
+    pub speaker_color: Color,
+    pub text_color: Color,
+    pub background: Color,
+}
+
+impl Captions {
+    // the layout is in design pixels like the rest of the ui
+    const BOTTOM_MARGIN: f32 = 60.0;
+    const PADDING: f32 = 8.0;
+
+    pub fn new() -> Self {
+        Self {
+            sounds: HashMap::new(),
+            queue: VecDeque::new(),
+            max_lines: 3,
+            speaker_color: Color::RGB(255, 210, 90),
+            text_color: Color::WHITE,
+            background: Color::RGBA(0, 0, 0, 160),
+        }
+    }
+
+    // the caption a sound carries, it shows every time the sound plays
+    pub fn register(&mut self, sound: &str, caption: Caption) {
+        self.sounds.insert(sound.to_string(), caption);
+    }
+
+    // a sound started playing, the sounds without a caption are skipped
+    pub fn on_sound(&mut self, sound: &str) {
+        if let Some(caption) = self.sounds.get(sound).cloned() {
+            self.push(caption);
+        }
+    }
+
+    pub fn push(&mut self, caption: Caption) {
+        if !UiSettings::current().captions {
+            return;
+        }
+        // a sound that repeats (steps, a loop) keeps its line on screen instead of filling the queue
+        if let Some(shown) = self.queue.iter_mut().find(|shown| shown.caption.speaker == caption.speaker && shown.caption.text == caption.text) {
+            shown.remaining = shown.remaining.max(caption.duration);
+            return;
+        }
+        let remaining = caption.duration.max(0.0);
+        self.queue.push_back(ShownCaption { caption, remaining });
+    }
+
+    // only the lines on screen count down, the waiting ones start when there is room for them
+    pub fn update(&mut self, delta_time: f32) {
+        if !UiSettings::current().captions {
+            self.queue.clear();
+            return;
+        }
+        for shown in self.queue.iter_mut().take(self.max_lines) {
+            shown.remaining -= delta_time;
+        }
+        self.queue.retain(|shown| shown.remaining > 0.0);
+    }
+
+    // the captions on screen now, the oldest first
+    pub fn visible(&self) -> impl Iterator<Item = &Caption> {
+        self.queue.iter().take(self.max_lines).map(|shown| &shown.caption)
+    }
+
+    // the lines stacked over the bottom of the screen, the newest one at the bottom
+    pub fn draw(&self, ui: &mut UiRenderer, text: &mut TextRenderer, font: &Font, screen_width: u32, screen_height: u32) {
+        let settings = UiSettings::current();
+        if !settings.captions {
+            return;
+        }
+        let padding = settings.px(Self::PADDING);
+        let line_height = font.height() + padding * 2;
+        let lines: Vec<&Caption> = self.visible().collect();
+        let mut y = screen_height as i32 - settings.px(Self::BOTTOM_MARGIN) - line_height * lines.len() as i32;
+
+        for caption in lines {
+            let speaker = caption.speaker.as_ref().map(|speaker| format!("{}: ", speaker));
+            let speaker_width = speaker.as_ref().and_then(|speaker| font.size_of(speaker).ok()).map_or(0, |(width, _)| width as i32);
+            let text_width = font.size_of(&caption.text).map_or(0, |(width, _)| width as i32);
+            let width = speaker_width + text_width + padding * 2;
+            let x = (screen_width as i32 - width) / 2;
+
+            ui.draw_rect(Rect::new(x, y, width.max(0) as u32, line_height.max(0) as u32), self.background);
+            let mut text_x = x + padding;
+            if let Some(speaker) = &speaker {
+                text_x += text.draw_text(font, speaker, text_x, y + padding, self.speaker_color);
+            }
+            text.draw_text(font, &caption.text, text_x, y + padding, self.text_color);
+            y += line_height;
+        }
+    }
+}
+
+impl Default for Captions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,262 @@
+// the chat of the game: the last messages over the bottom left of the screen and the line that is being typed
+// the lines fade out a while after they arrive and come back while typing. every name gets a color from a palette
+// so the same player always has the same one, and the players that are muted (by name, so it survives a reconnect)
+// don't show up at all. the messages come from and go to the net layer (see net/chat.rs), the chat box only
+// hands out what was typed, whoever owns the connection sends it
+// "/mute name", "/unmute name", "/muted" and "/clear" are handled here and never sent
+
+use std::collections::{HashSet, VecDeque};
+use std::hash::{Hash, Hasher};
+
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::ttf::Font;
+
+use crate::input::input_state::{InputButton, InputState};
+use crate::net::chat::{sanitize, ChatChannel, ChatMessage, MAX_CHAT_LENGTH};
+use crate::ui::scale::UiSettings;
+use crate::ui::text::TextRenderer;
+use crate::ui::ui_renderer::UiRenderer;
+
+// bright enough to read over the dark background, none of them is the color of the system messages
+const NAME_COLORS: [Color; 8] = [
+    Color::RGB(255, 120, 120),
+    Color::RGB(120, 200, 255),
+    Color::RGB(140, 235, 130),
+    Color::RGB(255, 190, 90),
+    Color::RGB(210, 150, 255),
+    Color::RGB(90, 230, 210),
+    Color::RGB(255, 140, 210),
+    Color::RGB(230, 230, 110),
+];
+
+struct ChatLine {
+    message: ChatMessage,
+    age: f32, // seconds since it arrived
+}
+
+pub struct ChatBox {
+    history: VecDeque<ChatLine>,
+    muted: HashSet<String>,
+    input: Option<(ChatChannel, String)>, // what is being typed and where it goes, none while closed
+    pub max_history: usize,   // the older ones are dropped
+    pub visible_lines: usize, // on screen at once
+    pub show_time: f32,       // seconds a line stays before it fades
+    pub fade_time: f32,
+    pub text_color: Color,
+    pub system_color: Color,
+    pub background: Color,
+}
+
+impl ChatBox {
+    // the layout is in design pixels like the rest of the ui
+    const LEFT_MARGIN: f32 = 20.0;
+    const BOTTOM_MARGIN: f32 = 140.0;
+    const PADDING: f32 = 6.0;
+    const WIDTH: f32 = 520.0;
+
+    pub fn new() -> Self {
+        Self {
+            history: VecDeque::new(),
+            muted: HashSet::new(),
+            input: None,
+            max_history: 100,
+            visible_lines: 8,
+            show_time: 8.0,
+            fade_time: 2.0,
+            text_color: Color::WHITE,
+            system_color: Color::RGB(255, 230, 150),
+            background: Color::RGBA(0, 0, 0, 140),
+        }
+    }
+
+    // a message from the net layer, the muted ones are dropped here (the system never is)
+    pub fn receive(&mut self, message: ChatMessage) {
+        if !message.is_system() && self.is_muted(&message.name) {
+            return;
+        }
+        self.history.push_back(ChatLine { message, age: 0.0 });
+        while self.history.len() > self.max_history {
+            self.history.pop_front();
+        }
+    }
+
+    // a line only this player sees (the answers to the commands)
+    pub fn notice(&mut self, text: &str) {
+        self.receive(ChatMessage::system(text));
+    }
+
+    pub fn mute(&mut self, name: &str) {
+        let name = name.to_lowercase();
+        // what they already said goes too
+        self.history.retain(|line| line.message.is_system() || line.message.name.to_lowercase() != name);
+        self.muted.insert(name);
+    }
+
+    pub fn unmute(&mut self, name: &str) {
+        self.muted.remove(&name.to_lowercase());
+    }
+
+    pub fn is_muted(&self, name: &str) -> bool {
+        self.muted.contains(&name.to_lowercase())
+    }
+
+    pub fn muted(&self) -> impl Iterator<Item = &String> {
+        self.muted.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.history.clear();
+    }
+
+    pub fn open(&mut self, channel: ChatChannel) {
+        self.input = Some((channel, String::new()));
+    }
+
+    pub fn close(&mut self) {
+        self.input = None;
+    }
+
+    // while typing the game shouldn't read the keys
+    pub fn is_typing(&self) -> bool {
+        self.input.is_some()
+    }
+
+    // the keys of the frame while typing: the text, backspace, enter sends and escape closes
+    // the message to send when enter was pressed, the sender and name are filled in by the server
+    pub fn handle_input(&mut self, input: &InputState) -> Option<ChatMessage> {
+        if self.is_typing() && input.just_pressed(InputButton::Key(Keycode::Escape)) {
+            self.close();
+            return None;
+        }
+        let (channel, text) = self.input.as_mut()?;
+        let channel = *channel;
+        if input.just_pressed_or_repeated(InputButton::Key(Keycode::Backspace)) {
+            text.pop();
+        }
+        for c in input.typed_text().chars() {
+            if text.chars().count() < MAX_CHAT_LENGTH {
+                text.push(c);
+            }
+        }
+        if !input.just_pressed(InputButton::Key(Keycode::Return)) {
+            return None;
+        }
+        let text = self.input.take().map(|(_, text)| text).unwrap_or_default();
+        if let Some(command) = text.strip_prefix('/') {
+            self.command(command);
+            return None;
+        }
+        sanitize(&text, MAX_CHAT_LENGTH).map(|text| ChatMessage::new(channel, 0, "", &text))
+    }
+
+    fn command(&mut self, command: &str) {
+        let (name, argument) = command.split_once(' ').map_or((command, ""), |(name, argument)| (name, argument.trim()));
+        match (name, argument) {
+            ("mute", "") | ("unmute", "") => self.notice("who? /mute name"),
+            ("mute", player) => {
+                self.mute(player);
+                self.notice(&format!("{} is muted", player));
+            }
+            ("unmute", player) => {
+                self.unmute(player);
+                self.notice(&format!("{} is not muted anymore", player));
+            }
+            ("muted", _) => {
+                let mut names: Vec<&str> = self.muted().map(String::as_str).collect();
+                names.sort();
+                let line = if names.is_empty() { "nobody is muted".to_string() } else { format!("muted: {}", names.join(", ")) };
+                self.notice(&line);
+            }
+            ("clear", _) => self.clear(),
+            _ => self.notice(&format!("there is no command /{}", name)),
+        }
+    }
+
+    pub fn update(&mut self, delta_time: f32) {
+        for line in self.history.iter_mut() {
+            line.age += delta_time;
+        }
+    }
+
+    // the same name always gets the same color, in every client
+    pub fn name_color(name: &str) -> Color {
+        // fnv, the hasher of std changes between runs
+        let mut hasher = Fnv(0xcbf29ce484222325);
+        name.to_lowercase().hash(&mut hasher);
+        NAME_COLORS[(hasher.finish() % NAME_COLORS.len() as u64) as usize]
+    }
+
+    // 1 while the line is new, down to 0 when it's gone, always 1 while typing
+    fn opacity(&self, line: &ChatLine) -> f32 {
+        if self.is_typing() {
+            return 1.0;
+        }
+        let faded = (line.age - self.show_time) / self.fade_time.max(0.001);
+        (1.0 - faded).clamp(0.0, 1.0)
+    }
+
+    pub fn draw(&self, ui: &mut UiRenderer, text: &mut TextRenderer, font: &Font, screen_height: u32) {
+        let settings = UiSettings::current();
+        let padding = settings.px(Self::PADDING);
+        let line_height = font.height() + padding;
+        let x = settings.px(Self::LEFT_MARGIN);
+        let width = settings.px(Self::WIDTH).max(0) as u32;
+        let bottom = screen_height as i32 - settings.px(Self::BOTTOM_MARGIN);
+
+        // the input goes under the history
+        if let Some((channel, typed)) = &self.input {
+            let y = bottom - line_height;
+            ui.draw_rect(Rect::new(x, y, width, line_height.max(0) as u32), self.background);
+            let prompt = format!("[{}] ", channel.name());
+            let text_x = x + padding + text.draw_text(font, &prompt, x + padding, y + padding / 2, self.system_color);
+            text.draw_text(font, &format!("{}_", typed), text_x, y + padding / 2, self.text_color);
+        }
+
+        let mut y = bottom - line_height * 2;
+        for line in self.history.iter().rev().take(self.visible_lines) {
+            let opacity = self.opacity(line);
+            if opacity <= 0.0 {
+                // the older ones faded before this one
+                break;
+            }
+            let fade = |color: Color| Color::RGBA(color.r, color.g, color.b, (color.a as f32 * opacity) as u8);
+            ui.draw_rect(Rect::new(x, y, width, line_height.max(0) as u32), fade(self.background));
+
+            let message = &line.message;
+            let mut text_x = x + padding;
+            if message.is_system() {
+                text.draw_text(font, &message.text, text_x, y + padding / 2, fade(self.system_color));
+            } else {
+                if message.channel == ChatChannel::Team {
+                    text_x += text.draw_text(font, "[team] ", text_x, y + padding / 2, fade(self.system_color));
+                }
+                text_x += text.draw_text(font, &format!("{}: ", message.name), text_x, y + padding / 2, fade(Self::name_color(&message.name)));
+                text.draw_text(font, &message.text, text_x, y + padding / 2, fade(self.text_color));
+            }
+            y -= line_height;
+        }
+    }
+}
+
+impl Default for ChatBox {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct Fnv(u64);
+
+impl Hasher for Fnv {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
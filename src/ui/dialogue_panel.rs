@@ -0,0 +1,165 @@
+// the conversation being held, at the bottom of the screen: who speaks, the line and the choices of the player
+// the arrows (or the mouse) pick a choice, enter (or a click, or its number) takes it, a line without choices goes on
+// with enter and escape walks away. everything it shows goes through the localization, the files only have keys
+
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::ttf::Font;
+
+use crate::gameplay::dialogue::{Conversation, DialogueEvent, DialogueLibrary};
+use crate::input::input_state::{InputButton, InputState};
+use crate::ui::scale::UiSettings;
+use crate::ui::text::TextRenderer;
+use crate::ui::ui_renderer::UiRenderer;
+use crate::util::localization::Localization;
+
+const NUMBER_KEYS: [Keycode; 9] = [Keycode::Num1, Keycode::Num2, Keycode::Num3, Keycode::Num4, Keycode::Num5, Keycode::Num6, Keycode::Num7, Keycode::Num8, Keycode::Num9];
+
+pub struct DialoguePanel {
+    conversation: Option<Conversation>,
+    selected: usize,
+    events: Vec<DialogueEvent>, // of the conversations that ended too
+    pub background: Color,
+    pub speaker_color: Color,
+    pub text_color: Color,
+    pub choice_color: Color,
+    pub selected_color: Color,
+}
+
+impl DialoguePanel {
+    // design pixels like the rest of the ui
+    const WIDTH: f32 = 720.0;
+    const BOTTOM_MARGIN: f32 = 40.0;
+    const PADDING: f32 = 14.0;
+
+    pub fn new() -> Self {
+        Self {
+            conversation: None,
+            selected: 0,
+            events: Vec::new(),
+            background: Color::RGBA(10, 10, 20, 220),
+            speaker_color: Color::RGB(255, 210, 90),
+            text_color: Color::WHITE,
+            choice_color: Color::RGB(180, 180, 190),
+            selected_color: Color::RGB(120, 200, 255),
+        }
+    }
+
+    // a conversation already going ends first
+    pub fn start(&mut self, library: &DialogueLibrary, dialogue: &str) -> anyhow::Result<()> {
+        let conversation = Conversation::start(library, dialogue)?;
+        self.end();
+        self.conversation = Some(conversation);
+        self.selected = 0;
+        self.collect();
+        Ok(())
+    }
+
+    pub fn end(&mut self) {
+        if let Some(conversation) = &mut self.conversation {
+            conversation.end();
+        }
+        self.collect();
+    }
+
+    // moves the events of the conversation out, and lets it go once it ended
+    fn collect(&mut self) {
+        let Some(conversation) = &mut self.conversation else { return };
+        self.events.extend(conversation.take_events());
+        if conversation.is_finished() {
+            self.conversation = None;
+        }
+    }
+
+    // what happened since the last call, for the game to react to
+    pub fn take_events(&mut self) -> Vec<DialogueEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    // the screen rectangles of the panel and of each choice
+    fn layout(&self, font: &Font, choices: usize, screen_width: u32, screen_height: u32) -> (Rect, Vec<Rect>) {
+        let settings = UiSettings::current();
+        let padding = settings.px(Self::PADDING);
+        let width = settings.px(Self::WIDTH).min(screen_width as i32);
+        let line_height = font.height() + padding / 2;
+        // the speaker, the line, a gap and the choices (or the hint to continue)
+        let height = padding * 2 + line_height * (3 + choices.max(1) as i32);
+        let x = (screen_width as i32 - width) / 2;
+        let y = screen_height as i32 - settings.px(Self::BOTTOM_MARGIN) - height;
+        let choices = (0..choices).map(|index| Rect::new(x + padding, y + padding + line_height * (3 + index as i32), (width - padding * 2).max(1) as u32, line_height.max(1) as u32)).collect();
+        (Rect::new(x, y, width.max(0) as u32, height.max(0) as u32), choices)
+    }
+
+    // true while it has the input (the game doesn't move under it)
+    pub fn handle_input(&mut self, input: &InputState, library: &DialogueLibrary, font: &Font, screen_width: u32, screen_height: u32) -> bool {
+        let Some(conversation) = &mut self.conversation else { return false };
+        if input.just_pressed(InputButton::Key(Keycode::Escape)) {
+            self.end();
+            return true;
+        }
+        let choices = conversation.node(library).map_or(0, |node| node.choices.len());
+        if choices == 0 {
+            if input.action_just_pressed("UiAccept") || input.just_pressed(InputButton::Mouse(MouseButton::Left)) {
+                conversation.advance(library);
+                self.selected = 0;
+            }
+            self.collect();
+            return true;
+        }
+
+        let key = |keycode: Keycode| input.just_pressed_or_repeated(InputButton::Key(keycode));
+        let mut selected = self.selected as i32;
+        if input.action_just_pressed("UiNext") {
+            selected += 1;
+        }
+        if key(Keycode::Up) || input.action_just_pressed("UiPrevious") {
+            selected -= 1;
+        }
+        self.selected = selected.rem_euclid(choices as i32) as usize;
+
+        let mut chosen = NUMBER_KEYS.iter().take(choices).position(|keycode| input.just_pressed(InputButton::Key(*keycode)));
+        if input.action_just_pressed("UiAccept") {
+            chosen = Some(self.selected);
+        }
+        if input.just_pressed(InputButton::Mouse(MouseButton::Left)) {
+            let (x, y) = input.mouse_position();
+            let (_, rects) = self.layout(font, choices, screen_width, screen_height);
+            chosen = rects.iter().position(|rect| rect.contains_point((x, y))).or(chosen);
+        }
+        if let Some(choice) = chosen {
+            if let Some(conversation) = &mut self.conversation {
+                conversation.choose(library, choice);
+            }
+            self.selected = 0;
+        }
+        self.collect();
+        true
+    }
+
+    pub fn draw(&self, ui: &mut UiRenderer, text: &mut TextRenderer, font: &Font, library: &DialogueLibrary, strings: &Localization, (screen_width, screen_height): (u32, u32)) {
+        let Some(node) = self.conversation.as_ref().and_then(|conversation| conversation.node(library)) else { return };
+        let settings = UiSettings::current();
+        let padding = settings.px(Self::PADDING);
+        let line_height = font.height() + padding / 2;
+        let (panel, choices) = self.layout(font, node.choices.len(), screen_width, screen_height);
+        ui.draw_rect(panel, self.background);
+
+        let x = panel.x() + padding;
+        let y = panel.y() + padding;
+        if let Some(speaker) = &node.speaker {
+            text.draw_text(font, strings.get(speaker), x, y, self.speaker_color);
+        }
+        text.draw_text(font, strings.get(&node.text), x, y + line_height, self.text_color);
+
+        if node.choices.is_empty() {
+            text.draw_text(font, strings.get("ui.dialogue.continue"), x, y + line_height * 3, self.choice_color);
+            return;
+        }
+        for (index, (choice, rect)) in node.choices.iter().zip(choices).enumerate() {
+            let color = if index == self.selected { self.selected_color } else { self.choice_color };
+            text.draw_text(font, &format!("{}. {}", index + 1, strings.get(&choice.text)), rect.x(), rect.y(), color);
+        }
+    }
+}
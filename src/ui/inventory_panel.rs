@@ -0,0 +1,171 @@
+// the inventory of the player as a grid of slots in the middle of the screen, I opens and closes it
+// the arrows (or the mouse) pick a slot, enter (or a click) picks the stack up and again on another slot drops it
+// there (merging or swapping, see Inventory::move_stack), S splits the stack in two
+// the ui pass only draws flat rectangles for now, so an item is the color of its definition with the first letters of
+// its name until the icons can be drawn
+
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::ttf::Font;
+
+use crate::gameplay::inventory::{Inventory, ItemLibrary, Property};
+use crate::input::input_state::{InputButton, InputState};
+use crate::ui::scale::UiSettings;
+use crate::ui::text::TextRenderer;
+use crate::ui::ui_renderer::UiRenderer;
+
+pub struct InventoryPanel {
+    open: bool,
+    selected: usize,
+    held: Option<usize>, // the slot picked up, it moves where the next accept lands
+    pub columns: usize,
+    pub background: Color,
+    pub slot_color: Color,
+    pub selected_color: Color,
+    pub text_color: Color,
+}
+
+impl InventoryPanel {
+    // design pixels like the rest of the ui
+    const SLOT_SIZE: f32 = 56.0;
+    const GAP: f32 = 6.0;
+    const PADDING: f32 = 12.0;
+
+    pub fn new(columns: usize) -> Self {
+        Self {
+            open: false,
+            selected: 0,
+            held: None,
+            columns: columns.max(1),
+            background: Color::RGBA(10, 10, 20, 220),
+            slot_color: Color::RGBA(40, 40, 55, 255),
+            selected_color: Color::RGB(120, 200, 255),
+            text_color: Color::WHITE,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+        self.held = None;
+    }
+
+    // the screen rectangle of a slot, the grid is centered
+    fn slot_rect(&self, index: usize, slots: usize, screen_width: u32, screen_height: u32) -> Rect {
+        let settings = UiSettings::current();
+        let size = settings.px(Self::SLOT_SIZE);
+        let gap = settings.px(Self::GAP);
+        let rows = slots.div_ceil(self.columns);
+        let width = self.columns as i32 * (size + gap) - gap;
+        let height = rows as i32 * (size + gap) - gap;
+        let x = (screen_width as i32 - width) / 2 + (index % self.columns) as i32 * (size + gap);
+        let y = (screen_height as i32 - height) / 2 + (index / self.columns) as i32 * (size + gap);
+        Rect::new(x, y, size.max(1) as u32, size.max(1) as u32)
+    }
+
+    // true while it has the input (the game doesn't move under it)
+    pub fn handle_input(&mut self, input: &InputState, inventory: &mut Inventory, library: &ItemLibrary, screen_width: u32, screen_height: u32) -> bool {
+        if !self.open {
+            return false;
+        }
+        if input.just_pressed(InputButton::Key(Keycode::Escape)) || input.action_just_pressed("Inventory") {
+            self.toggle();
+            return true;
+        }
+        let slots = inventory.slots().len();
+        if slots == 0 {
+            return true;
+        }
+        let key = |keycode: Keycode| input.just_pressed_or_repeated(InputButton::Key(keycode));
+        let mut selected = self.selected as i32;
+        if key(Keycode::Right) {
+            selected += 1;
+        }
+        if key(Keycode::Left) {
+            selected -= 1;
+        }
+        if key(Keycode::Down) || input.action_just_pressed("UiNext") {
+            selected += self.columns as i32;
+        }
+        if key(Keycode::Up) || input.action_just_pressed("UiPrevious") {
+            selected -= self.columns as i32;
+        }
+        self.selected = selected.rem_euclid(slots as i32) as usize;
+
+        let mut accept = input.action_just_pressed("UiAccept");
+        if input.just_pressed(InputButton::Mouse(MouseButton::Left)) {
+            let (x, y) = input.mouse_position();
+            if let Some(index) = (0..slots).find(|index| self.slot_rect(*index, slots, screen_width, screen_height).contains_point((x, y))) {
+                self.selected = index;
+                accept = true;
+            }
+        }
+        if accept {
+            match self.held.take() {
+                Some(from) => inventory.move_stack(library, from, self.selected),
+                None if inventory.slot(self.selected).is_some() => self.held = Some(self.selected),
+                None => {}
+            }
+        }
+        if key(Keycode::S) && self.held.is_none() {
+            inventory.split(self.selected);
+        }
+        true
+    }
+
+    pub fn draw(&self, ui: &mut UiRenderer, text: &mut TextRenderer, font: &Font, inventory: &Inventory, library: &ItemLibrary, (screen_width, screen_height): (u32, u32)) {
+        if !self.open {
+            return;
+        }
+        let settings = UiSettings::current();
+        let padding = settings.px(Self::PADDING);
+        let slots = inventory.slots().len();
+        if slots == 0 {
+            return;
+        }
+        let first = self.slot_rect(0, slots, screen_width, screen_height);
+        let last = self.slot_rect(slots - 1, slots, screen_width, screen_height);
+        let right = self.slot_rect(self.columns.min(slots) - 1, slots, screen_width, screen_height).right();
+        // the description of the selected item goes under the grid
+        let details_height = font.height() * 2 + padding;
+        let panel = Rect::new(first.x() - padding, first.y() - padding, (right - first.x() + padding * 2).max(0) as u32, (last.bottom() - first.y() + padding * 2 + details_height).max(0) as u32);
+        ui.draw_rect(panel, self.background);
+
+        let outline = settings.px(2.0).max(1) as u32;
+        for (index, slot) in inventory.slots().iter().enumerate() {
+            let rect = self.slot_rect(index, slots, screen_width, screen_height);
+            ui.draw_rect(rect, self.slot_color);
+            if let Some(stack) = slot {
+                let (r, g, b) = library.get(&stack.item).map_or((128, 128, 128), |def| def.color);
+                let inset = settings.px(6.0);
+                let icon = Rect::new(rect.x() + inset, rect.y() + inset, (rect.width() as i32 - inset * 2).max(1) as u32, (rect.height() as i32 - inset * 2).max(1) as u32);
+                // the held stack is dimmed where it was
+                let alpha = if self.held == Some(index) { 90 } else { 255 };
+                ui.draw_rect(icon, Color::RGBA(r, g, b, alpha));
+                let name = library.get(&stack.item).map_or(stack.item.as_str(), |def| def.name.as_str());
+                let short: String = name.chars().take(2).collect();
+                text.draw_text(font, &short, icon.x() + inset / 2, icon.y(), self.text_color);
+                if stack.count > 1 {
+                    let count = stack.count.to_string();
+                    let (count_width, count_height) = font.size_of(&count).unwrap_or((0, 0));
+                    text.draw_text(font, &count, rect.right() - count_width as i32 - inset / 2, rect.bottom() - count_height as i32, self.text_color);
+                }
+            }
+            if index == self.selected {
+                ui.draw_outline(rect, outline, self.selected_color);
+            }
+        }
+
+        let details_y = last.bottom() + padding;
+        if let Some(def) = inventory.slot(self.selected).and_then(|stack| library.get(&stack.item)) {
+            text.draw_text(font, &def.name, first.x(), details_y, self.text_color);
+            let properties: Vec<String> = def.properties.iter().map(|(name, value)| match value {
+                Property::Bool(value) => format!("{} {}", name, if *value { "yes" } else { "no" }),
+                Property::Number(value) => format!("{} {}", name, value),
+                Property::Text(value) => format!("{} {}", name, value),
+            }).collect();
+            text.draw_text(font, &properties.join("  "), first.x(), details_y + font.height(), self.text_color);
+        }
+    }
+}
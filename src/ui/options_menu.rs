@@ -0,0 +1,204 @@
+// the options of the engine settings (see util/settings.rs) as a panel over the game, F10 opens it
+// up and down pick a row, left and right change its value, enter on a control waits for the new button (escape
+// keeps the old one). nothing changes until apply, which hands the settings to the game to apply and save
+
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::ttf::Font;
+
+use crate::input::input_state::{InputButton, InputState};
+use crate::ui::scale::UiSettings;
+use crate::ui::text::TextRenderer;
+use crate::ui::ui_renderer::UiRenderer;
+use crate::util::settings::EngineSettings;
+
+pub enum OptionsAction {
+    None,
+    Apply(EngineSettings),
+    Close,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Row {
+    Resolution,
+    Fullscreen,
+    Vsync,
+    Sensitivity,
+    Binding(String),
+    Apply,
+    Back,
+}
+
+pub struct OptionsMenu {
+    open: bool,
+    draft: EngineSettings, // what the rows show, the real settings only change on apply
+    rows: Vec<Row>,
+    selected: usize,
+    waiting: Option<String>, // the action that takes the next button pressed
+    pub background: Color,
+    pub text_color: Color,
+    pub selected_color: Color,
+    pub value_color: Color,
+}
+
+impl OptionsMenu {
+    // the layout is in design pixels like the rest of the ui
+    const WIDTH: f32 = 560.0;
+    const PADDING: f32 = 12.0;
+    const VISIBLE_ROWS: usize = 14;
+
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            draft: EngineSettings::default(),
+            rows: Vec::new(),
+            selected: 0,
+            waiting: None,
+            background: Color::RGBA(10, 10, 20, 230),
+            text_color: Color::WHITE,
+            selected_color: Color::RGB(120, 200, 255),
+            value_color: Color::RGB(255, 210, 90),
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    // starts from the settings in use, with the controls the input has now
+    pub fn open(&mut self, settings: &EngineSettings, input: &InputState) {
+        self.draft = settings.clone();
+        self.draft.store_bindings(input);
+        self.rows = vec![Row::Resolution, Row::Fullscreen, Row::Vsync, Row::Sensitivity];
+        self.rows.extend(self.draft.bindings.keys().cloned().map(Row::Binding));
+        self.rows.extend([Row::Apply, Row::Back]);
+        self.selected = 0;
+        self.waiting = None;
+        self.open = true;
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+        self.waiting = None;
+    }
+
+    // left is -1 and right is +1
+    fn change(&mut self, row: &Row, step: i32) {
+        let draft = &mut self.draft;
+        match row {
+            Row::Resolution => {
+                // none (the monitor) and then the usual sizes
+                let sizes: Vec<Option<(u32, u32)>> = std::iter::once(None).chain(EngineSettings::RESOLUTIONS.iter().copied().map(Some)).collect();
+                let index = sizes.iter().position(|size| *size == draft.resolution).unwrap_or(0) as i32;
+                draft.resolution = sizes[(index + step).rem_euclid(sizes.len() as i32) as usize];
+            }
+            Row::Fullscreen => draft.fullscreen = !draft.fullscreen,
+            Row::Vsync => draft.vsync = !draft.vsync,
+            Row::Sensitivity => {
+                draft.sensitivity = ((draft.sensitivity + step as f32 * 0.1) * 10.0).round() / 10.0;
+                *draft = draft.clone().clamped();
+            }
+            Row::Binding(_) | Row::Apply | Row::Back => {}
+        }
+    }
+
+    pub fn handle_input(&mut self, input: &InputState) -> OptionsAction {
+        if !self.open {
+            return OptionsAction::None;
+        }
+        if let Some(action) = self.waiting.clone() {
+            if input.just_pressed(InputButton::Key(Keycode::Escape)) {
+                self.waiting = None;
+            } else if let Some(button) = input.any_just_pressed() {
+                self.draft.bindings.insert(action, vec![button.name()]);
+                self.waiting = None;
+            }
+            return OptionsAction::None;
+        }
+        if input.just_pressed(InputButton::Key(Keycode::Escape)) || input.action_just_pressed("Options") {
+            self.close();
+            return OptionsAction::Close;
+        }
+        let count = self.rows.len();
+        if input.action_just_pressed("UiNext") {
+            self.selected = (self.selected + 1) % count;
+        }
+        if input.action_just_pressed("UiPrevious") {
+            self.selected = (self.selected + count - 1) % count;
+        }
+        let row = self.rows[self.selected].clone();
+        if input.just_pressed_or_repeated(InputButton::Key(Keycode::Left)) {
+            self.change(&row, -1);
+        }
+        if input.just_pressed_or_repeated(InputButton::Key(Keycode::Right)) {
+            self.change(&row, 1);
+        }
+        if input.action_just_pressed("UiAccept") {
+            match row {
+                Row::Binding(action) => self.waiting = Some(action),
+                Row::Apply => {
+                    self.close();
+                    return OptionsAction::Apply(self.draft.clone());
+                }
+                Row::Back => {
+                    self.close();
+                    return OptionsAction::Close;
+                }
+                row => self.change(&row, 1),
+            }
+        }
+        OptionsAction::None
+    }
+
+    fn describe(&self, row: &Row) -> (String, String) {
+        let on_off = |value: bool| if value { "on" } else { "off" }.to_string();
+        match row {
+            Row::Resolution => ("resolution".to_string(), self.draft.resolution.map_or("monitor".to_string(), |(width, height)| format!("{}x{}", width, height))),
+            Row::Fullscreen => ("fullscreen".to_string(), on_off(self.draft.fullscreen)),
+            Row::Vsync => ("vsync".to_string(), on_off(self.draft.vsync)),
+            Row::Sensitivity => ("mouse sensitivity".to_string(), format!("{:.1}", self.draft.sensitivity)),
+            Row::Binding(action) if self.waiting.as_ref() == Some(action) => (action.clone(), "press a button...".to_string()),
+            Row::Binding(action) => (action.clone(), self.draft.bindings.get(action).map_or(String::new(), |buttons| buttons.join(", "))),
+            Row::Apply => ("apply and save".to_string(), String::new()),
+            Row::Back => ("back".to_string(), String::new()),
+        }
+    }
+
+    pub fn draw(&self, ui: &mut UiRenderer, text: &mut TextRenderer, font: &Font, screen_width: u32, screen_height: u32) {
+        if !self.open {
+            return;
+        }
+        let settings = UiSettings::current();
+        let padding = settings.px(Self::PADDING);
+        let line_height = font.height() + padding / 2;
+        let width = settings.px(Self::WIDTH).min(screen_width as i32);
+        let height = line_height * (Self::VISIBLE_ROWS as i32 + 2) + padding * 2;
+        let x = (screen_width as i32 - width) / 2;
+        let y = (screen_height as i32 - height) / 2;
+        ui.draw_rect(Rect::new(x, y, width.max(0) as u32, height.max(0) as u32), self.background);
+
+        let mut line_y = y + padding;
+        text.draw_text(font, "options", x + padding, line_y, self.text_color);
+        line_y += line_height * 2;
+
+        // the selected row stays on screen when the controls make the list longer than the panel
+        let first = self.selected.saturating_sub(Self::VISIBLE_ROWS - 1);
+        for (index, row) in self.rows.iter().enumerate().skip(first).take(Self::VISIBLE_ROWS) {
+            let (name, value) = self.describe(row);
+            let color = if index == self.selected { self.selected_color } else { self.text_color };
+            if index == self.selected {
+                ui.draw_outline(Rect::new(x + padding / 2, line_y - padding / 4, (width - padding).max(0) as u32, line_height.max(0) as u32), 1, color);
+            }
+            text.draw_text(font, &name, x + padding, line_y, color);
+            text.draw_text(font, &value, x + width / 2, line_y, self.value_color);
+            line_y += line_height;
+        }
+    }
+}
+
+impl Default for OptionsMenu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,104 @@
+// the size of the ui: the positions and sizes of the ui elements are in design pixels (a 1080p screen at 96 dpi)
+// and every element goes through the current scale when it is drawn or hit, so a 4K monitor or a player that needs
+// bigger text only changes this, not the layout. the text never goes under the minimum font size
+// the settings are saved in a small key=value file like the display ones
+
+use std::cell::Cell;
+use std::fs;
+use std::path::Path;
+
+use sdl2::rect::Rect;
+use sdl2::VideoSubsystem;
+
+use crate::game_object::GameObject;
+
+pub const UI_SETTINGS_PATH: &str = "ui.cfg";
+
+// what a scale of 1 is made for
+const BASE_DPI: f32 = 96.0;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct UiSettings {
+    pub scale: f32,
+    pub min_font_size: u16, // in real pixels, after the scale, for the players that need big text
+    pub captions: bool,     // the captions of the sounds at the bottom of the screen (see ui/captions.rs)
+}
+
+impl UiSettings {
+    pub const DEFAULT: UiSettings = UiSettings { scale: 1.0, min_font_size: 12, captions: false };
+    pub const SCALE_RANGE: (f32, f32) = (0.5, 4.0);
+
+    // the scale from the dpi of the monitor, 1 when sdl doesn't know it
+    pub fn for_display(video: &VideoSubsystem, display: i32) -> Self {
+        let scale = video.display_dpi(display).map_or(1.0, |(diagonal, _, _)| diagonal / BASE_DPI);
+        Self { scale, ..Self::DEFAULT }.clamped()
+    }
+
+    // the values of the file over the fallback (the one for the monitor), the lines it doesn't know are skipped
+    pub fn load(path: impl AsRef<Path>, fallback: Self) -> Self {
+        let mut settings = fallback;
+        let Ok(text) = fs::read_to_string(path) else { return settings };
+        for line in text.lines() {
+            let Some((key, value)) = line.split_once('=') else { continue };
+            let value = value.trim();
+            match key.trim() {
+                "scale" => settings.scale = value.parse().unwrap_or(settings.scale),
+                "min_font_size" => settings.min_font_size = value.parse().unwrap_or(settings.min_font_size),
+                "captions" => settings.captions = value == "true",
+                _ => {}
+            }
+        }
+        settings.clamped()
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        fs::write(path, format!("scale={}\nmin_font_size={}\ncaptions={}\n", self.scale, self.min_font_size, self.captions))?;
+        Ok(())
+    }
+
+    pub fn clamped(mut self) -> Self {
+        self.scale = if self.scale.is_finite() { self.scale.clamp(Self::SCALE_RANGE.0, Self::SCALE_RANGE.1) } else { 1.0 };
+        self.min_font_size = self.min_font_size.clamp(6, 96);
+        self
+    }
+
+    // the settings every element uses, for this thread (the ui only lives on the main one)
+    pub fn current() -> Self {
+        CURRENT.with(Cell::get)
+    }
+
+    pub fn set_current(settings: Self) {
+        CURRENT.with(|current| current.set(settings.clamped()));
+    }
+
+    // design pixels to real ones
+    pub fn px(&self, value: f32) -> i32 {
+        (value * self.scale).round() as i32
+    }
+
+    // the real size of a font made for the design size, never under the minimum
+    pub fn font_size(&self, design_size: u16) -> u16 {
+        ((design_size as f32 * self.scale).round() as u16).max(self.min_font_size)
+    }
+
+    // where an element is on the screen
+    pub fn rect(&self, game_object: &GameObject) -> Rect {
+        Rect::new(self.px(game_object.x), self.px(game_object.y), self.px(game_object.width).max(0) as u32, self.px(game_object.height).max(0) as u32)
+    }
+
+    // a point of the screen (the mouse) over an element
+    pub fn contains(&self, game_object: &GameObject, x: i32, y: i32) -> bool {
+        let rect = self.rect(game_object);
+        x > rect.x() && x < rect.x() + rect.width() as i32 && y >= rect.y() && y <= rect.y() + rect.height() as i32
+    }
+}
+
+impl Default for UiSettings {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+thread_local! {
+    static CURRENT: Cell<UiSettings> = const { Cell::new(UiSettings::DEFAULT) };
+}
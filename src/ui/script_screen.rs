@@ -0,0 +1,343 @@
+// menus and hud elements written by the mods instead of compiled in: a .ui file in the ui folder of the assets (or
+// of a mod, see util/vfs.rs) describes a screen with panels, texts and buttons, one per line, in design pixels like
+// the rest of the ui. a hud is always drawn, a menu is opened by its key or by a button of another screen and has
+// the input while it's open
+// the files are read again when one of them changes on disk (or a mod brings a new one), the screens that are gone
+// close. a file that doesn't parse keeps the screen it had before and the error goes to the console
+//
+//   # the pause menu of the mod
+//   screen mod_menu menu F6
+//   panel 40 40 320 200 #101020dd
+//   text 56 56 "hello {name}"
+//   button 56 100 288 40 "louder" cvar volume 1
+//   button 56 150 288 40 "back" close
+//
+// the {cvar} in a text is the value of the cvar, the actions of the buttons are: close, open <screen>,
+// cvar <name> <value>, say <text> and quit
+// the buttons of a menu are walked with the keyboard in the order of the file, the focus is told to the screen readers
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context};
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::ttf::Font;
+
+use crate::game_object::GameObject;
+use crate::input::button_module::{Button, TextAlign};
+use crate::input::input_state::{InputButton, InputState};
+use crate::ui::accessibility::{FocusNavigator, UiAccessibility};
+use crate::ui::scale::UiSettings;
+use crate::ui::text::TextRenderer;
+use crate::ui::ui_renderer::UiRenderer;
+use crate::util::cvars::{CvarRegistry, CvarValue};
+use crate::util::vfs;
+
+pub const SCRIPT_FOLDER: &str = "ui";
+const EXTENSION: &str = ".ui";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScriptAction {
+    Close,
+    Open(String),
+    Cvar(String), // the line for the console, "name value"
+    Say(String),
+    Quit,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ScreenKind {
+    Menu,
+    Hud,
+}
+
+enum Widget {
+    Panel { rect: GameObject, color: Color },
+    Text { x: f32, y: f32, text: String, color: Color },
+    Button { button: Button, action: ScriptAction },
+}
+
+pub struct ScriptScreen {
+    pub name: String,
+    pub kind: ScreenKind,
+    pub key: Option<Keycode>, // opens and closes a menu
+    file: String,            // under the assets, "ui/mod_menu.ui"
+    widgets: Vec<Widget>,
+    focus: FocusNavigator,
+}
+
+impl ScriptScreen {
+    pub fn parse(file: &str, source: &str) -> anyhow::Result<Self> {
+        let stem = file.rsplit('/').next().unwrap_or(file).trim_end_matches(EXTENSION);
+        let mut screen = Self { name: stem.to_string(), kind: ScreenKind::Menu, key: None, file: file.to_string(), widgets: Vec::new(), focus: FocusNavigator::new() };
+        for (number, line) in source.lines().enumerate() {
+            screen.parse_line(line).with_context(|| format!("{} line {}", file, number + 1))?;
+        }
+        Ok(screen)
+    }
+
+    fn parse_line(&mut self, line: &str) -> anyhow::Result<()> {
+        let words = split_words(line)?;
+        let Some((command, arguments)) = words.split_first() else { return Ok(()) };
+        match command.as_str() {
+            "screen" => {
+                let [name, rest @ ..] = arguments else { bail!("screen needs a name") };
+                self.name = name.clone();
+                if let Some(kind) = rest.first() {
+                    self.kind = match kind.as_str() {
+                        "menu" => ScreenKind::Menu,
+                        "hud" => ScreenKind::Hud,
+                        other => bail!("a screen is a menu or a hud, not {}", other),
+                    };
+                }
+                if let Some(key) = rest.get(1) {
+                    self.key = Some(Keycode::from_name(key).ok_or_else(|| anyhow!("there is no key {}", key))?);
+                }
+            }
+            "panel" => {
+                let [x, y, width, height, color] = arguments else { bail!("panel x y width height #color") };
+                let rect = GameObject { active: true, x: number(x)?, y: number(y)?, width: number(width)?, height: number(height)? };
+                self.widgets.push(Widget::Panel { rect, color: parse_color(color)? });
+            }
+            "text" => {
+                let [x, y, text, rest @ ..] = arguments else { bail!("text x y \"text\" [#color]") };
+                let color = rest.first().map(|color| parse_color(color)).transpose()?.unwrap_or(Color::WHITE);
+                self.widgets.push(Widget::Text { x: number(x)?, y: number(y)?, text: text.clone(), color });
+            }
+            "button" => {
+                let [x, y, width, height, label, action @ ..] = arguments else { bail!("button x y width height \"label\" action") };
+                let rect = GameObject { active: true, x: number(x)?, y: number(y)?, width: number(width)?, height: number(height)? };
+                let button = Button::new(rect, Some(label.clone()), Color::RGB(40, 40, 60), Color::WHITE, Color::RGB(70, 70, 110), Color::RGB(20, 20, 30), None, TextAlign::Center)
+                    .with_accessible_label(label);
+                self.widgets.push(Widget::Button { button, action: parse_action(action)? });
+            }
+            other => bail!("there is no {} in a ui script", other),
+        }
+        Ok(())
+    }
+
+    // the action of the button pressed this frame, clicked or with the keyboard
+    fn update(&mut self, input: &InputState, accessibility: &mut UiAccessibility) -> Option<ScriptAction> {
+        let (mut buttons, actions): (Vec<&mut Button>, Vec<&ScriptAction>) = self
+            .widgets
+            .iter_mut()
+            .filter_map(|widget| match widget {
+                Widget::Button { button, action } => Some((button, &*action)),
+                _ => None,
+            })
+            .unzip();
+        let pressed = self.focus.update(input, &mut buttons, accessibility)?;
+        Some(actions[pressed].clone())
+    }
+
+    fn draw(&self, ui: &mut UiRenderer, text: &mut TextRenderer, font: &Font, cvars: &CvarRegistry) {
+        let settings = UiSettings::current();
+        for widget in &self.widgets {
+            match widget {
+                Widget::Panel { rect, color } => ui.draw_rect(settings.rect(rect), *color),
+                Widget::Text { x, y, text: line, color } => {
+                    text.draw_text(font, &fill_cvars(line, cvars), settings.px(*x), settings.px(*y), *color);
+                }
+                Widget::Button { button, .. } => button.draw(ui, text, font),
+            }
+        }
+    }
+}
+
+// every screen of the ui folder, the huds always on and the menus on a stack, the last opened on top
+pub struct ScriptScreens {
+    screens: Vec<ScriptScreen>,
+    open: Vec<String>,
+}
+
+impl ScriptScreens {
+    pub fn load() -> Self {
+        let mut screens = Self { screens: Vec::new(), open: Vec::new() };
+        screens.reload();
+        screens
+    }
+
+    // reads the folder again, called when a file of it changes
+    pub fn reload(&mut self) {
+        let files: Vec<String> = vfs::global().read().unwrap().list(SCRIPT_FOLDER).into_iter().filter(|file| file.ends_with(EXTENSION)).collect();
+        let mut screens = Vec::new();
+        for file in files {
+            let path = Path::new(vfs::DEFAULT_BASE).join(&file);
+            let parsed = vfs::global().read().unwrap().read(&path).and_then(|bytes| Ok(String::from_utf8(bytes)?)).and_then(|source| ScriptScreen::parse(&file, &source));
+            match parsed {
+                Ok(screen) => screens.push(screen),
+                Err(e) => {
+                    eprintln!("the ui script {} was not loaded: {:#}", file, e);
+                    // the screen it had keeps working until the file is fixed
+                    if let Some(index) = self.screens.iter().position(|screen| screen.file == file) {
+                        screens.push(self.screens.remove(index));
+                    }
+                }
+            }
+        }
+        self.screens = screens;
+        let screens = &self.screens;
+        self.open.retain(|name| screens.iter().any(|screen| &screen.name == name));
+    }
+
+    // the files on disk, for the file watcher (the ones of a mod are where the mod has them)
+    pub fn files(&self) -> Vec<PathBuf> {
+        self.screens.iter().map(|screen| vfs::resolve(Path::new(vfs::DEFAULT_BASE).join(&screen.file))).collect()
+    }
+
+    fn find(&self, name: &str) -> Option<&ScriptScreen> {
+        self.screens.iter().find(|screen| screen.name == name)
+    }
+
+    pub fn open(&mut self, name: &str) -> bool {
+        let is_menu = self.find(name).is_some_and(|screen| screen.kind == ScreenKind::Menu);
+        if is_menu && !self.is_open(name) {
+            self.open.push(name.to_string());
+        }
+        is_menu
+    }
+
+    // the menu on top
+    pub fn close(&mut self) {
+        self.open.pop();
+    }
+
+    pub fn is_open(&self, name: &str) -> bool {
+        self.open.iter().any(|open| open == name)
+    }
+
+    // a menu has the input while it's open, the game doesn't see it
+    pub fn has_input(&self) -> bool {
+        !self.open.is_empty()
+    }
+
+    // the keys of the menus and the buttons of the one on top, close and open are done here, the rest is for the
+    // game (it owns the cvars, the chat and the app state)
+    pub fn handle_input(&mut self, input: &InputState, accessibility: &mut UiAccessibility) -> Vec<ScriptAction> {
+        let toggled = self.screens.iter().find(|screen| screen.key.is_some_and(|key| input.just_pressed(InputButton::Key(key)))).map(|screen| screen.name.clone());
+        if let Some(name) = toggled {
+            if self.open.last() == Some(&name) {
+                self.close();
+            } else {
+                self.open(&name);
+            }
+            return Vec::new();
+        }
+        if !self.has_input() {
+            return Vec::new();
+        }
+        if input.just_pressed(InputButton::Key(Keycode::Escape)) {
+            self.close();
+            return Vec::new();
+        }
+        let top = self.open.last().cloned().unwrap_or_default();
+        let Some(screen) = self.screens.iter_mut().find(|screen| screen.name == top) else { return Vec::new() };
+        let mut actions: Vec<ScriptAction> = screen.update(input, accessibility).into_iter().collect();
+        actions.retain(|action| match action {
+            ScriptAction::Close => {
+                self.open.pop();
+                false
+            }
+            ScriptAction::Open(name) => {
+                if !self.open(name) {
+                    eprintln!("there is no ui menu {}", name);
+                }
+                false
+            }
+            _ => true,
+        });
+        actions
+    }
+
+    // the huds under the menus, the menus in the order they were opened
+    pub fn draw(&self, ui: &mut UiRenderer, text: &mut TextRenderer, font: &Font, cvars: &CvarRegistry) {
+        for screen in self.screens.iter().filter(|screen| screen.kind == ScreenKind::Hud) {
+            screen.draw(ui, text, font, cvars);
+        }
+        for name in &self.open {
+            if let Some(screen) = self.find(name) {
+                screen.draw(ui, text, font, cvars);
+            }
+        }
+    }
+}
+
+// the words of a line, "quoted text" is one word, a line that starts with # is a comment (after that a # is a color)
+fn split_words(line: &str) -> anyhow::Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut chars = line.trim().chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '#' && words.is_empty() {
+            break;
+        } else if c == '"' {
+            chars.next();
+            let mut word = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some(c) => word.push(c),
+                    None => bail!("a text without the closing \""),
+                }
+            }
+            words.push(word);
+        } else {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            words.push(word);
+        }
+    }
+    Ok(words)
+}
+
+fn number(word: &str) -> anyhow::Result<f32> {
+    word.parse().map_err(|_| anyhow!("{} is not a number", word))
+}
+
+// #rrggbb or #rrggbbaa
+fn parse_color(word: &str) -> anyhow::Result<Color> {
+    let hex = word.strip_prefix('#').filter(|hex| (hex.len() == 6 || hex.len() == 8) && hex.is_ascii()).ok_or_else(|| anyhow!("{} is not a #rrggbb color", word))?;
+    let channel = |index: usize| u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16).map_err(|_| anyhow!("{} is not a #rrggbb color", word));
+    let alpha = if hex.len() == 8 { channel(3)? } else { 255 };
+    Ok(Color::RGBA(channel(0)?, channel(1)?, channel(2)?, alpha))
+}
+
+fn parse_action(words: &[String]) -> anyhow::Result<ScriptAction> {
+    let Some((action, arguments)) = words.split_first() else { bail!("the button has no action") };
+    let rest = arguments.join(" ");
+    Ok(match action.as_str() {
+        "close" => ScriptAction::Close,
+        "quit" => ScriptAction::Quit,
+        "open" if !rest.is_empty() => ScriptAction::Open(rest),
+        "cvar" if !rest.is_empty() => ScriptAction::Cvar(rest),
+        "say" if !rest.is_empty() => ScriptAction::Say(rest),
+        "open" | "cvar" | "say" => bail!("{} needs something after it", action),
+        other => bail!("there is no action {}", other),
+    })
+}
+
+// {name} becomes the value of the cvar, the unknown ones stay as they are
+fn fill_cvars(text: &str, cvars: &CvarRegistry) -> String {
+    let mut filled = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}') else { break };
+        filled.push_str(&rest[..start]);
+        let name = &rest[start + 1..start + end];
+        match cvars.get(name).map(|cvar| cvar.value()) {
+            Some(CvarValue::Text(value)) => filled.push_str(value),
+            Some(value) => filled.push_str(&value.to_string()),
+            None => filled.push_str(&rest[start..=start + end]),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    filled.push_str(rest);
+    filled
+}
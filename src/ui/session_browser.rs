@@ -0,0 +1,164 @@
+// the list of the games on the local network (see net/discovery.rs), a panel over the game while the state is
+// GameState::Browsing. up and down pick one, enter joins it and escape goes back
+// the sessions that can't be joined (another protocol, full) are listed greyed out with the reason
+
+use std::net::SocketAddr;
+
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::ttf::Font;
+
+use crate::input::input_state::{InputButton, InputState};
+use crate::net::discovery::{Session, SessionBrowser};
+use crate::ui::scale::UiSettings;
+use crate::ui::text::TextRenderer;
+use crate::ui::ui_renderer::UiRenderer;
+
+pub enum BrowserAction {
+    None,
+    Join(SocketAddr),
+    Leave,
+}
+
+pub struct SessionBrowserMenu {
+    browser: Option<SessionBrowser>, // only listening while it's open
+    error: Option<String>,           // why it can't listen
+    selected: usize,
+    pub background: Color,
+    pub text_color: Color,
+    pub selected_color: Color,
+    pub disabled_color: Color,
+}
+
+impl SessionBrowserMenu {
+    // the layout is in design pixels like the rest of the ui
+    const WIDTH: f32 = 640.0;
+    const PADDING: f32 = 12.0;
+    const VISIBLE_SESSIONS: usize = 10;
+
+    pub fn new() -> Self {
+        Self {
+            browser: None,
+            error: None,
+            selected: 0,
+            background: Color::RGBA(10, 10, 20, 220),
+            text_color: Color::WHITE,
+            selected_color: Color::RGB(120, 200, 255),
+            disabled_color: Color::RGB(120, 120, 120),
+        }
+    }
+
+    pub fn open(&mut self) {
+        self.selected = 0;
+        match SessionBrowser::new() {
+            Ok(browser) => {
+                self.browser = Some(browser);
+                self.error = None;
+            }
+            Err(e) => {
+                self.browser = None;
+                self.error = Some(format!("the lan can't be searched: {}", e));
+            }
+        }
+    }
+
+    // the port is let go so another game on this machine can browse
+    pub fn close(&mut self) {
+        self.browser = None;
+    }
+
+    pub fn sessions(&self) -> &[Session] {
+        self.browser.as_ref().map_or(&[], |browser| browser.sessions())
+    }
+
+    pub fn update(&mut self) {
+        if let Some(browser) = &mut self.browser {
+            browser.poll();
+        }
+        self.selected = self.selected.min(self.sessions().len().saturating_sub(1));
+    }
+
+    // the keys and the pad walk the list the same way the menus do
+    pub fn handle_input(&mut self, input: &InputState) -> BrowserAction {
+        if input.just_pressed(InputButton::Key(Keycode::Escape)) || input.action_just_pressed("Pause") {
+            return BrowserAction::Leave;
+        }
+        let count = self.sessions().len();
+        if count == 0 {
+            return BrowserAction::None;
+        }
+        if input.action_just_pressed("UiNext") {
+            self.selected = (self.selected + 1) % count;
+        }
+        if input.action_just_pressed("UiPrevious") {
+            self.selected = (self.selected + count - 1) % count;
+        }
+        if input.action_just_pressed("UiAccept") {
+            let session = &self.sessions()[self.selected];
+            if session.can_join() {
+                return BrowserAction::Join(session.address);
+            }
+        }
+        BrowserAction::None
+    }
+
+    fn describe(session: &Session) -> String {
+        let advertisement = &session.advertisement;
+        let state = if session.version.is_none() {
+            format!(" - protocol {} to {}, can't join", advertisement.handshake.min_version, advertisement.handshake.max_version)
+        } else if advertisement.is_full() {
+            " - full".to_string()
+        } else {
+            String::new()
+        };
+        format!("{}  {}/{}  {}{}", advertisement.name, advertisement.players, advertisement.max_players, session.address, state)
+    }
+
+    pub fn draw(&self, ui: &mut UiRenderer, text: &mut TextRenderer, font: &Font, screen_width: u32, screen_height: u32) {
+        let settings = UiSettings::current();
+        let padding = settings.px(Self::PADDING);
+        let line_height = font.height() + padding;
+        let width = settings.px(Self::WIDTH).min(screen_width as i32);
+        let height = line_height * (Self::VISIBLE_SESSIONS as i32 + 2) + padding;
+        let x = (screen_width as i32 - width) / 2;
+        let y = (screen_height as i32 - height) / 2;
+        ui.draw_rect(Rect::new(x, y, width.max(0) as u32, height.max(0) as u32), self.background);
+
+        let mut line_y = y + padding;
+        text.draw_text(font, "games on the local network", x + padding, line_y, self.text_color);
+        line_y += line_height * 2;
+
+        let sessions = self.sessions();
+        if let Some(error) = &self.error {
+            text.draw_text(font, error, x + padding, line_y, self.disabled_color);
+            return;
+        }
+        if sessions.is_empty() {
+            text.draw_text(font, "searching...", x + padding, line_y, self.disabled_color);
+            return;
+        }
+        // the selected one stays on screen when the list is longer than the panel
+        let first = self.selected.saturating_sub(Self::VISIBLE_SESSIONS - 1);
+        for (index, session) in sessions.iter().enumerate().skip(first).take(Self::VISIBLE_SESSIONS) {
+            let color = if !session.can_join() {
+                self.disabled_color
+            } else if index == self.selected {
+                self.selected_color
+            } else {
+                self.text_color
+            };
+            if index == self.selected {
+                ui.draw_outline(Rect::new(x + padding / 2, line_y - padding / 4, (width - padding).max(0) as u32, line_height.max(0) as u32), 1, color);
+            }
+            text.draw_text(font, &Self::describe(session), x + padding, line_y, color);
+            line_y += line_height;
+        }
+    }
+}
+
+impl Default for SessionBrowserMenu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,8 +1,21 @@
-use sdl2::{render::{Canvas, TextureCreator, TextureQuery}, ttf::Font, video::{Window, WindowContext}};
+// the labels of the ui and the text renderer that puts them on the screen through wgpu
+// the glyphs are rasterized by sdl ttf once, packed in an atlas that only keeps their coverage, and every text is a
+// run of quads in screen pixels drawn over the final image (after the post process and the ui pass, see ui_renderer.rs)
+
+use std::collections::HashMap;
+use std::mem;
+
+use sdl2::{pixels::PixelFormatEnum, render::{Canvas, TextureCreator, TextureQuery}, ttf::Font, video::{Window, WindowContext}};
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
+use wgpu::{util::DeviceExt, Device, Queue};
 
+use crate::debug::frame_graph::{self, PassKind};
 use crate::game_object::GameObject;
+use crate::rendering::render_passes::FramePasses;
+use crate::rendering::shader_preprocessor::ShaderLibrary;
+use crate::ui::scale::UiSettings;
+use crate::util::color::Color as LinearColor;
 
 #[derive(Clone)]
 
@@ -26,7 +39,8 @@ impl Label {
     pub fn render(&self, canvas: &mut Canvas<Window>, texture_creator: &TextureCreator<WindowContext>, font: &Font) {
         if self.game_object.active == true {
             canvas.set_draw_color(self.color); // it must be a Color::RGB() or other
-            canvas.fill_rect(Rect::new(self.game_object.x as i32, self.game_object.y as i32, self.game_object.width as u32, self.game_object.height as u32)).unwrap();
+            let rect = UiSettings::current().rect(&self.game_object); // design pixels to the screen
+            canvas.fill_rect(rect).unwrap();
 
             // Render the button text
                     let surface = font.render(&self.text).solid(self.text_color).expect("Something went wrong while creating the surface");
@@ -34,11 +48,375 @@ impl Label {
         
                     // We center the text on the button
                     let TextureQuery { width: text_width, height: text_height, .. } = texture.query();
-                    let text_x = self.game_object.x as i32 + (self.game_object.width as i32 - text_width as i32) / 2;
-                    let text_y = self.game_object.y as i32 + (self.game_object.height as i32 - text_height as i32) / 2;
+                    let text_x = rect.x() + (rect.width() as i32 - text_width as i32) / 2;
+                    let text_y = rect.y() + (rect.height() as i32 - text_height as i32) / 2;
         
                     // render
                     canvas.copy(&texture, None, Rect::new(text_x, text_y, text_width, text_height)).unwrap();
         }
     }
-}
\ No newline at end of file
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct GlyphInstance {
+    position: [f32; 2],
+    size: [f32; 2],
+    uv_rect: [f32; 4],
+    color: [f32; 4],
+}
+
+impl GlyphInstance {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<GlyphInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[
+                wgpu::VertexAttribute { offset: 0, shader_location: 0, format: wgpu::VertexFormat::Float32x2 },
+                wgpu::VertexAttribute { offset: mem::size_of::<[f32; 2]>() as wgpu::BufferAddress, shader_location: 1, format: wgpu::VertexFormat::Float32x2 },
+                wgpu::VertexAttribute { offset: mem::size_of::<[f32; 4]>() as wgpu::BufferAddress, shader_location: 2, format: wgpu::VertexFormat::Float32x4 },
+                wgpu::VertexAttribute { offset: mem::size_of::<[f32; 8]>() as wgpu::BufferAddress, shader_location: 3, format: wgpu::VertexFormat::Float32x4 },
+            ],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct TextUniform {
+    screen_size: [f32; 2],
+    white_level: f32,
+    _padding: f32,
+}
+
+// where a glyph is in the atlas (x, y, width, height in pixels), none for the ones with nothing to draw (spaces)
+#[derive(Copy, Clone, Debug)]
+struct Glyph {
+    region: Option<[u32; 4]>,
+    advance: i32,
+}
+
+pub struct TextRenderer {
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    uniform_buffer: wgpu::Buffer,
+    atlas: wgpu::Texture,
+    pixels: Vec<u8>, // the coverage of the atlas on the cpu, uploaded whole when a glyph is added
+    dirty: bool,
+    cursor: (u32, u32), // where the next glyph goes, the atlas is filled in rows
+    row_height: u32,
+    glyphs: HashMap<(char, i32), Glyph>, // by the char and the height of the font, so a font of another size has its own
+    instance_buffer: wgpu::Buffer,
+    instance_capacity: usize,
+    instance_count: u32,
+    queued: Vec<GlyphInstance>,
+    screen_size: (u32, u32),
+    pub white_level: f32,
+}
+
+impl TextRenderer {
+    const ATLAS_SIZE: u32 = 1024;
+
+    pub fn new(device: &Device, config: &wgpu::SurfaceConfiguration) -> Self {
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Text Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[TextUniform { screen_size: [config.width as f32, config.height as f32], white_level: 1.0, _padding: 0.0 }]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let atlas = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Glyph Atlas"),
+            size: wgpu::Extent3d { width: Self::ATLAS_SIZE, height: Self::ATLAS_SIZE, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let view = atlas.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            // the glyphs are drawn at the size they were rasterized, on whole pixels
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("text_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("text_bind_group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: uniform_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&view) },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::Sampler(&sampler) },
+            ],
+        });
+
+        let shader = ShaderLibrary::builtin().create_module(device, "Text Shader", "text.wgsl", &[]);
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Text Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Text Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[GlyphInstance::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: None, // drawn in the order they were queued
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let instance_capacity = 256;
+        let mut text = Self {
+            pipeline,
+            bind_group,
+            uniform_buffer,
+            atlas,
+            pixels: vec![0; (Self::ATLAS_SIZE * Self::ATLAS_SIZE) as usize],
+            dirty: true,
+            cursor: (0, 0),
+            row_height: 0,
+            glyphs: HashMap::new(),
+            instance_buffer: Self::create_instance_buffer(device, instance_capacity),
+            instance_capacity,
+            instance_count: 0,
+            queued: Vec::new(),
+            screen_size: (config.width, config.height),
+            white_level: 1.0,
+        };
+        text.clear_atlas();
+        text
+    }
+
+    fn create_instance_buffer(device: &Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Glyph Instance Buffer"),
+            size: (capacity * mem::size_of::<GlyphInstance>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    // forgets every glyph
+    fn clear_atlas(&mut self) {
+        self.pixels.fill(0);
+        self.cursor = (0, 0);
+        self.row_height = 0;
+        self.glyphs.clear();
+        self.dirty = true;
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.screen_size = (width, height);
+    }
+
+    // a line of text with its top left corner at x y in screen pixels, it returns how wide it was
+    // there is no kerning, every glyph moves the pen by its advance
+    pub fn draw_text(&mut self, font: &Font, text: &str, x: i32, y: i32, color: Color) -> i32 {
+        let color = to_linear(color);
+        let mut pen = x;
+        for character in text.chars() {
+            let Some(glyph) = self.glyph(font, character) else { continue };
+            if let Some([glyph_x, glyph_y, width, height]) = glyph.region {
+                let atlas_size = Self::ATLAS_SIZE as f32;
+                self.queued.push(GlyphInstance {
+                    position: [pen as f32, y as f32],
+                    size: [width as f32, height as f32],
+                    uv_rect: [glyph_x as f32 / atlas_size, glyph_y as f32 / atlas_size, (glyph_x + width) as f32 / atlas_size, (glyph_y + height) as f32 / atlas_size],
+                    color,
+                });
+            }
+            pen += glyph.advance;
+        }
+        pen - x
+    }
+
+    // the glyph from the cache or rasterized now, none when the font doesn't have it
+    fn glyph(&mut self, font: &Font, character: char) -> Option<Glyph> {
+        let key = (character, font.height());
+        if let Some(glyph) = self.glyphs.get(&key) {
+            return Some(*glyph);
+        }
+        let metrics = font.find_glyph_metrics(character)?;
+        let surface = font.render_char(character).blended(Color::WHITE).ok().and_then(|surface| surface.convert_format(PixelFormatEnum::RGBA32).ok());
+        let mut glyph = Glyph { region: None, advance: metrics.advance };
+
+        if let Some(surface) = surface.filter(|surface| surface.width() > 0 && surface.height() > 0) {
+            let (width, height) = (surface.width(), surface.height());
+            if width + 1 > Self::ATLAS_SIZE || height + 1 > Self::ATLAS_SIZE {
+                eprintln!("the glyph {:?} is bigger than the text atlas", character);
+                return None;
+            }
+            if self.cursor.0 + width + 1 > Self::ATLAS_SIZE {
+                self.cursor = (0, self.cursor.1 + self.row_height);
+                self.row_height = 0;
+            }
+            if self.cursor.1 + height + 1 > Self::ATLAS_SIZE {
+                // the atlas is full, it starts again with the glyphs that are used from now on
+                // the text queued before this in the frame can show the wrong glyphs for one frame
+                self.clear_atlas();
+            }
+            let (atlas_x, atlas_y) = self.cursor;
+            let pitch = surface.pitch() as usize;
+            let pixels = &mut self.pixels;
+            surface.with_lock(|source| {
+                for row in 0..height as usize {
+                    for column in 0..width as usize {
+                        // rgba32 is r g b a in memory, the alpha is the coverage
+                        pixels[(atlas_y as usize + row) * Self::ATLAS_SIZE as usize + atlas_x as usize + column] = source[row * pitch + column * 4 + 3];
+                    }
+                }
+            });
+            glyph.region = Some([atlas_x, atlas_y, width, height]);
+            self.cursor.0 += width + 1; // one texel between glyphs
+            self.row_height = self.row_height.max(height + 1);
+            self.dirty = true;
+        }
+
+        self.glyphs.insert(key, glyph);
+        Some(glyph)
+    }
+
+    pub fn has_work(&self) -> bool {
+        self.instance_count > 0
+    }
+
+    // uploads the new glyphs and the text queued this frame, call once per frame before render
+    pub fn prepare(&mut self, device: &Device, queue: &Queue) {
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[TextUniform {
+            screen_size: [self.screen_size.0.max(1) as f32, self.screen_size.1.max(1) as f32],
+            white_level: self.white_level,
+            _padding: 0.0,
+        }]));
+
+        if self.dirty {
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &self.atlas,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &self.pixels,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(Self::ATLAS_SIZE),
+                    rows_per_image: Some(Self::ATLAS_SIZE),
+                },
+                wgpu::Extent3d { width: Self::ATLAS_SIZE, height: Self::ATLAS_SIZE, depth_or_array_layers: 1 },
+            );
+            self.dirty = false;
+        }
+
+        self.instance_count = self.queued.len() as u32;
+        if self.queued.is_empty() {
+            return;
+        }
+        if self.queued.len() > self.instance_capacity {
+            self.instance_capacity = self.queued.len().next_power_of_two();
+            self.instance_buffer = Self::create_instance_buffer(device, self.instance_capacity);
+        }
+        queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&self.queued));
+        self.queued.clear();
+    }
+
+    // draws over what the target already has, the target is the surface
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        if !self.has_work() {
+            return;
+        }
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Text Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+        render_pass.draw(0..6, 0..self.instance_count);
+    }
+
+    // the pass of the frame, after the one of the ui so the text goes over its backgrounds
+    pub fn add_pass<'a>(&'a self, frame: &mut FramePasses<'a>) {
+        if self.has_work() {
+            frame.add(
+                "ui text",
+                PassKind::Render,
+                &[frame_graph::buffer("glyph_instances"), frame_graph::texture("glyph_atlas")],
+                &[frame_graph::surface("surface")],
+                move |encoder, targets| self.render(encoder, targets.surface),
+            );
+        }
+    }
+}
+
+// the ui colors are srgb bytes, the surface wants linear values
+fn to_linear(color: Color) -> [f32; 4] {
+    LinearColor::from_srgb8(color.r, color.g, color.b, color.a).to_array()
+}
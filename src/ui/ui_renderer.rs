@@ -0,0 +1,288 @@
+// the 2D pass of the ui: flat rectangles in screen pixels (button backgrounds, panels, outlines) drawn on the surface
+// after the 3D scene and the post process, the text renderer goes after it so the text is always over its background
+// the shapes are queued during the frame and drawn in that order, one indexed draw for all of them
+
+use std::mem;
+
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use wgpu::{util::DeviceExt, Device, Queue};
+
+use crate::debug::frame_graph::{self, PassKind};
+use crate::rendering::render_passes::FramePasses;
+use crate::rendering::shader_preprocessor::ShaderLibrary;
+use crate::util::color::Color as LinearColor;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct UiVertex {
+    position: [f32; 2],
+    color: [f32; 4],
+}
+
+impl UiVertex {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<UiVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute { offset: 0, shader_location: 0, format: wgpu::VertexFormat::Float32x2 },
+                wgpu::VertexAttribute { offset: mem::size_of::<[f32; 2]>() as wgpu::BufferAddress, shader_location: 1, format: wgpu::VertexFormat::Float32x4 },
+            ],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct UiUniform {
+    screen_size: [f32; 2],
+    white_level: f32,
+    _padding: f32,
+}
+
+pub struct UiRenderer {
+    pipeline: wgpu::RenderPipeline,
+    counting_pipeline: Option<wgpu::RenderPipeline>, // the overdraw debug view, made the first time it is used
+    format: wgpu::TextureFormat,
+    bind_group_layout: wgpu::BindGroupLayout,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    capacity: usize, // in quads
+    index_count: u32,
+    vertices: Vec<UiVertex>,
+    indices: Vec<u32>,
+    screen_size: (u32, u32),
+    pub white_level: f32,
+}
+
+impl UiRenderer {
+    pub fn new(device: &Device, config: &wgpu::SurfaceConfiguration) -> Self {
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Ui Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[UiUniform { screen_size: [config.width as f32, config.height as f32], white_level: 1.0, _padding: 0.0 }]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("ui_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("ui_bind_group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: uniform_buffer.as_entire_binding() },
+            ],
+        });
+
+        let pipeline = Self::create_pipeline(device, &[&bind_group_layout], config.format, &[]);
+
+        let capacity = 64;
+        let (vertex_buffer, index_buffer) = Self::create_buffers(device, capacity);
+
+        Self {
+            pipeline,
+            counting_pipeline: None,
+            format: config.format,
+            bind_group_layout,
+            uniform_buffer,
+            bind_group,
+            vertex_buffer,
+            index_buffer,
+            capacity,
+            index_count: 0,
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            screen_size: (config.width, config.height),
+            white_level: 1.0,
+        }
+    }
+
+    // the counting one (COUNT_OVERDRAW) has the overdraw counter as a second group and writes no color
+    fn create_pipeline(device: &Device, layouts: &[&wgpu::BindGroupLayout], format: wgpu::TextureFormat, defines: &[&str]) -> wgpu::RenderPipeline {
+        let counting = defines.contains(&"COUNT_OVERDRAW");
+        let shader = ShaderLibrary::builtin().create_module(device, "Ui Shader", "ui.wgsl", defines);
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Ui Pipeline Layout"),
+            bind_group_layouts: layouts,
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Ui Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[UiVertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: if counting { wgpu::ColorWrites::empty() } else { wgpu::ColorWrites::ALL },
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: None, // drawn in the order they were queued
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    }
+
+    // makes the pipeline of render_counting_overdraw, once
+    pub fn enable_overdraw_count(&mut self, device: &Device, counter_layout: &wgpu::BindGroupLayout) {
+        if self.counting_pipeline.is_none() {
+            self.counting_pipeline = Some(Self::create_pipeline(device, &[&self.bind_group_layout, counter_layout], self.format, &["COUNT_OVERDRAW"]));
+        }
+    }
+
+    fn create_buffers(device: &Device, capacity: usize) -> (wgpu::Buffer, wgpu::Buffer) {
+        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Ui Vertex Buffer"),
+            size: (capacity * 4 * mem::size_of::<UiVertex>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let index_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Ui Index Buffer"),
+            size: (capacity * 6 * mem::size_of::<u32>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        (vertex_buffer, index_buffer)
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.screen_size = (width, height);
+    }
+
+    // a filled rectangle in screen pixels, the colors are the srgb ones of the sdl ui
+    pub fn draw_rect(&mut self, rect: Rect, color: Color) {
+        if color.a == 0 || rect.width() == 0 || rect.height() == 0 {
+            return;
+        }
+        let color = LinearColor::from_srgb8(color.r, color.g, color.b, color.a).to_array();
+        let (left, top) = (rect.x() as f32, rect.y() as f32);
+        let (right, bottom) = (left + rect.width() as f32, top + rect.height() as f32);
+
+        let first = self.vertices.len() as u32;
+        self.vertices.extend_from_slice(&[
+            UiVertex { position: [left, top], color },
+            UiVertex { position: [right, top], color },
+            UiVertex { position: [left, bottom], color },
+            UiVertex { position: [right, bottom], color },
+        ]);
+        self.indices.extend_from_slice(&[first, first + 1, first + 2, first + 2, first + 1, first + 3]);
+    }
+
+    // the border of a rectangle, inside it, for the focus and the selected items
+    pub fn draw_outline(&mut self, rect: Rect, thickness: u32, color: Color) {
+        let thickness = thickness.min(rect.width() / 2).min(rect.height() / 2);
+        if thickness == 0 {
+            return;
+        }
+        let inner_height = rect.height() - thickness * 2;
+        self.draw_rect(Rect::new(rect.x(), rect.y(), rect.width(), thickness), color);
+        self.draw_rect(Rect::new(rect.x(), rect.bottom() - thickness as i32, rect.width(), thickness), color);
+        self.draw_rect(Rect::new(rect.x(), rect.y() + thickness as i32, thickness, inner_height), color);
+        self.draw_rect(Rect::new(rect.right() - thickness as i32, rect.y() + thickness as i32, thickness, inner_height), color);
+    }
+
+    pub fn has_work(&self) -> bool {
+        self.index_count > 0
+    }
+
+    // uploads the shapes queued this frame, call once per frame before render
+    pub fn prepare(&mut self, device: &Device, queue: &Queue) {
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[UiUniform {
+            screen_size: [self.screen_size.0.max(1) as f32, self.screen_size.1.max(1) as f32],
+            white_level: self.white_level,
+            _padding: 0.0,
+        }]));
+
+        self.index_count = self.indices.len() as u32;
+        if self.indices.is_empty() {
+            return;
+        }
+        let quads = self.vertices.len() / 4;
+        if quads > self.capacity {
+            self.capacity = quads.next_power_of_two();
+            (self.vertex_buffer, self.index_buffer) = Self::create_buffers(device, self.capacity);
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&self.vertices));
+        queue.write_buffer(&self.index_buffer, 0, bytemuck::cast_slice(&self.indices));
+        self.vertices.clear();
+        self.indices.clear();
+    }
+
+    // draws over what the target already has, the target is the surface
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        self.render_with(encoder, target, &self.pipeline, None);
+    }
+
+    // the same shapes but they only add to the overdraw counter, the target is not touched
+    // enable_overdraw_count has to be called before, without it nothing is counted
+    pub fn render_counting_overdraw(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView, counter: &wgpu::BindGroup) {
+        if let Some(pipeline) = &self.counting_pipeline {
+            self.render_with(encoder, target, pipeline, Some(counter));
+        }
+    }
+
+    fn render_with(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView, pipeline: &wgpu::RenderPipeline, counter: Option<&wgpu::BindGroup>) {
+        if !self.has_work() {
+            return;
+        }
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Ui Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        if let Some(counter) = counter {
+            render_pass.set_bind_group(1, counter, &[]);
+        }
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(0..self.index_count, 0, 0..1);
+    }
+
+    // the pass of the frame, over everything on the surface
+    pub fn add_pass<'a>(&'a self, frame: &mut FramePasses<'a>) {
+        if self.has_work() {
+            frame.add("ui", PassKind::Render, &[frame_graph::buffer("ui_vertices")], &[frame_graph::surface("surface")], move |encoder, targets| self.render(encoder, targets.surface));
+        }
+    }
+}
@@ -0,0 +1,146 @@
+// text that belongs to a place of the 3D scene instead of the screen: the nameplates over the entities and the damage
+// numbers that float up from where the hit was. every frame the anchor goes through Camera::world_to_screen and the
+// text is queued on the text renderer like the rest of the ui, so they are all in its one draw
+// there is no depth test for the ui, a label behind a collider (see gameplay/collision.rs) fades out instead of
+// showing through the wall, and the far ones fade with the distance
+
+use cgmath::{EuclideanSpace, InnerSpace, Point3, Vector3};
+use sdl2::pixels::Color;
+use sdl2::ttf::Font;
+
+use crate::gameplay::collision::{Attachment, Collider, CollisionWorld};
+use crate::rendering::camera::Camera;
+use crate::scene::graph::{EntityId, SceneGraph};
+use crate::ui::scale::UiSettings;
+use crate::ui::text::TextRenderer;
+use crate::util::pool::{Pool, PoolHandle};
+
+// how much of the fade of the occlusion happens per second
+const FADE_SPEED: f32 = 6.0;
+// a collider this close to the anchor doesn't hide it, the hit was on the surface the number came from
+const OCCLUSION_MARGIN: f32 = 0.5;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LabelAnchor {
+    Point(Point3<f32>),
+    Entity { entity: EntityId, offset: Vector3<f32> }, // the offset goes over the head of the entity
+}
+
+#[derive(Clone, Debug)]
+pub struct WorldLabel {
+    pub text: String,
+    pub anchor: LabelAnchor,
+    pub color: Color,
+    pub lifetime: Option<f32>, // seconds, none stays until it's removed (the nameplates)
+    pub rise: f32, // design pixels per second it floats up
+    pub max_distance: f32, // from the camera, it fades out in the last quarter
+    age: f32,
+    visibility: f32, // 0 hidden behind something, 1 in sight
+}
+
+impl WorldLabel {
+    pub fn new(text: &str, anchor: LabelAnchor, color: Color) -> Self {
+        Self { text: text.to_string(), anchor, color, lifetime: None, rise: 0.0, max_distance: 40.0, age: 0.0, visibility: 1.0 }
+    }
+
+    fn position(&self, graph: &SceneGraph) -> Option<Point3<f32>> {
+        match self.anchor {
+            LabelAnchor::Point(point) => Some(point),
+            LabelAnchor::Entity { entity, offset } => graph.get(entity).map(|entity| Point3::from_vec(entity.world_position() + offset)),
+        }
+    }
+
+    // the end of its life, the last part of it fading
+    fn life_alpha(&self) -> f32 {
+        self.lifetime.map_or(1.0, |lifetime| ((lifetime - self.age) / (lifetime * 0.3).max(f32::EPSILON)).clamp(0.0, 1.0))
+    }
+}
+
+pub struct WorldLabels {
+    labels: Pool<WorldLabel>,
+    pub damage_color: Color,
+    pub nameplate_color: Color,
+}
+
+impl WorldLabels {
+    pub fn new() -> Self {
+        Self { labels: Pool::new(), damage_color: Color::RGB(255, 90, 60), nameplate_color: Color::WHITE }
+    }
+
+    pub fn add(&mut self, label: WorldLabel) -> PoolHandle {
+        self.labels.insert(label)
+    }
+
+    // the name over an entity while it lives
+    pub fn nameplate(&mut self, entity: EntityId, text: &str, height: f32) -> PoolHandle {
+        let anchor = LabelAnchor::Entity { entity, offset: Vector3::new(0.0, height, 0.0) };
+        self.add(WorldLabel::new(text, anchor, self.nameplate_color))
+    }
+
+    // the number that floats up from a hit for a second
+    pub fn damage_number(&mut self, position: Point3<f32>, amount: f32) -> PoolHandle {
+        let label = WorldLabel { lifetime: Some(1.0), rise: 40.0, ..WorldLabel::new(&format!("{}", amount.round() as i64), LabelAnchor::Point(position), self.damage_color) };
+        self.add(label)
+    }
+
+    // the labels get older and fade in and out of sight, the ones of despawned entities and the old numbers go
+    pub fn update(&mut self, delta_time: f32, camera: &Camera, graph: &SceneGraph, collision: &CollisionWorld) {
+        let frustum = camera.frustum();
+        self.labels.retain(|label| {
+            label.age += delta_time;
+            if label.lifetime.is_some_and(|lifetime| label.age >= lifetime) {
+                return false;
+            }
+            let Some(position) = label.position(graph) else { return false };
+            // a ray from the eye, the collider of the entity itself doesn't count
+            let ignore = match label.anchor {
+                LabelAnchor::Entity { entity, .. } => collision.find(Attachment::Entity(entity)),
+                LabelAnchor::Point(_) => None,
+            };
+            // out of the view there is nothing to hide it behind, it fades out without a ray
+            let to_label = position - camera.eye;
+            let distance = to_label.magnitude();
+            let hidden = !frustum.contains_point(position) || collision.sweep(&Collider::sphere(0.0), camera.eye, to_label, ignore).is_some_and(|hit| hit.time * distance < distance - OCCLUSION_MARGIN);
+            let target = if hidden { 0.0 } else { 1.0 };
+            let step = FADE_SPEED * delta_time;
+            label.visibility = if label.visibility < target { (label.visibility + step).min(target) } else { (label.visibility - step).max(target) };
+            true
+        });
+    }
+
+    // the far ones first so the near ones are drawn over them
+    pub fn draw(&self, text: &mut TextRenderer, font: &Font, camera: &Camera, graph: &SceneGraph, screen_width: u32, screen_height: u32) {
+        let settings = UiSettings::current();
+        let mut visible: Vec<(f32, &WorldLabel, Point3<f32>)> = self
+            .labels
+            .iter()
+            .filter(|(_, label)| label.visibility > 0.0)
+            .filter_map(|(_, label)| {
+                let position = label.position(graph)?;
+                let distance = (position - camera.eye).magnitude();
+                (distance <= label.max_distance).then_some((distance, label, position))
+            })
+            .collect();
+        visible.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        for (distance, label, position) in visible {
+            let Some(screen) = camera.world_to_screen(position, screen_width as f32, screen_height as f32) else { continue };
+            let distance_alpha = ((label.max_distance - distance) / (label.max_distance * 0.25)).clamp(0.0, 1.0);
+            let alpha = label.color.a as f32 / 255.0 * label.visibility * label.life_alpha() * distance_alpha;
+            if alpha <= 0.0 {
+                continue;
+            }
+            let width = font.size_of(&label.text).map_or(0, |(width, _)| width as i32);
+            let x = screen.x as i32 - width / 2;
+            let y = screen.y as i32 - font.height() / 2 - settings.px(label.rise * label.age);
+            let color = Color::RGBA(label.color.r, label.color.g, label.color.b, (alpha * 255.0) as u8);
+            text.draw_text(font, &label.text, x, y, color);
+        }
+    }
+}
+
+impl Default for WorldLabels {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,228 @@
+// the engine color, always linear rgba in f32
+// sdl2 wants srgb bytes, wgpu wants linear f64, and shaders want linear f32, so we convert only at the edges
+
+use std::ops::{Add, Mul};
+
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable, serde::Serialize, serde::Deserialize)]
+#[repr(C)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Color {
+    pub const WHITE: Color = Color::rgba(1.0, 1.0, 1.0, 1.0);
+    pub const BLACK: Color = Color::rgba(0.0, 0.0, 0.0, 1.0);
+    pub const TRANSPARENT: Color = Color::rgba(0.0, 0.0, 0.0, 0.0);
+    pub const RED: Color = Color::rgba(1.0, 0.0, 0.0, 1.0);
+    pub const GREEN: Color = Color::rgba(0.0, 1.0, 0.0, 1.0);
+    pub const BLUE: Color = Color::rgba(0.0, 0.0, 1.0, 1.0);
+
+    pub const fn rgba(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+
+    pub const fn rgb(r: f32, g: f32, b: f32) -> Self {
+        Self { r, g, b, a: 1.0 }
+    }
+
+    // from the usual 0-255 values of image editors (those are srgb)
+    pub fn from_srgb8(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self {
+            r: srgb_to_linear(r as f32 / 255.0),
+            g: srgb_to_linear(g as f32 / 255.0),
+            b: srgb_to_linear(b as f32 / 255.0),
+            a: a as f32 / 255.0, // alpha is never gamma encoded
+        }
+    }
+
+    pub fn to_srgb8(self) -> [u8; 4] {
+        let to_byte = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+        [
+            to_byte(linear_to_srgb(self.r)),
+            to_byte(linear_to_srgb(self.g)),
+            to_byte(linear_to_srgb(self.b)),
+            to_byte(self.a),
+        ]
+    }
+
+    // "#ff8800" or "#ff8800cc"
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let hex = hex.trim_start_matches('#');
+        let byte = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        match hex.len() {
+            6 => Some(Self::from_srgb8(byte(0)?, byte(2)?, byte(4)?, 255)),
+            8 => Some(Self::from_srgb8(byte(0)?, byte(2)?, byte(4)?, byte(6)?)),
+            _ => None,
+        }
+    }
+
+    pub fn to_array(self) -> [f32; 4] {
+        [self.r, self.g, self.b, self.a]
+    }
+
+    pub fn with_alpha(&self, a: f32) -> Self {
+        Self { a, ..*self }
+    }
+
+    // hue in degrees (0-360), saturation and value from 0 to 1, the hsv is done on the srgb values like in image editors
+    pub fn from_hsv(hue: f32, saturation: f32, value: f32, alpha: f32) -> Self {
+        let h = hue.rem_euclid(360.0) / 60.0;
+        let c = value * saturation;
+        let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+        let m = value - c;
+        let (r, g, b) = match h as u32 {
+            0 => (c, x, 0.0),
+            1 => (x, c, 0.0),
+            2 => (0.0, c, x),
+            3 => (0.0, x, c),
+            4 => (x, 0.0, c),
+            _ => (c, 0.0, x),
+        };
+        Self {
+            r: srgb_to_linear(r + m),
+            g: srgb_to_linear(g + m),
+            b: srgb_to_linear(b + m),
+            a: alpha,
+        }
+    }
+
+    // (hue in degrees, saturation, value)
+    pub fn to_hsv(self) -> (f32, f32, f32) {
+        let r = linear_to_srgb(self.r);
+        let g = linear_to_srgb(self.g);
+        let b = linear_to_srgb(self.b);
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let delta = max - min;
+
+        let hue = if delta == 0.0 {
+            0.0
+        } else if max == r {
+            60.0 * ((g - b) / delta).rem_euclid(6.0)
+        } else if max == g {
+            60.0 * ((b - r) / delta + 2.0)
+        } else {
+            60.0 * ((r - g) / delta + 4.0)
+        };
+        let saturation = if max == 0.0 { 0.0 } else { delta / max };
+
+        (hue, saturation, max)
+    }
+
+    pub fn shift_hue(&self, degrees: f32) -> Self {
+        let (h, s, v) = self.to_hsv();
+        Self::from_hsv(h + degrees, s, v, self.a)
+    }
+
+    pub fn saturate(&self, amount: f32) -> Self {
+        let (h, s, v) = self.to_hsv();
+        Self::from_hsv(h, (s * amount).clamp(0.0, 1.0), v, self.a)
+    }
+
+    pub fn brighten(&self, amount: f32) -> Self {
+        let (h, s, v) = self.to_hsv();
+        Self::from_hsv(h, s, (v * amount).clamp(0.0, 1.0), self.a)
+    }
+
+    // the mix is done in linear space, that is what makes gradients look right
+    pub fn lerp(&self, other: Color, t: f32) -> Self {
+        Self {
+            r: self.r + (other.r - self.r) * t,
+            g: self.g + (other.g - self.g) * t,
+            b: self.b + (other.b - self.b) * t,
+            a: self.a + (other.a - self.a) * t,
+        }
+    }
+}
+
+impl Default for Color {
+    fn default() -> Self {
+        Color::WHITE
+    }
+}
+
+impl Add for Color {
+    type Output = Color;
+
+    fn add(self, other: Color) -> Color {
+        Color::rgba(self.r + other.r, self.g + other.g, self.b + other.b, self.a + other.a)
+    }
+}
+
+impl Mul<f32> for Color {
+    type Output = Color;
+
+    fn mul(self, value: f32) -> Color {
+        Color::rgba(self.r * value, self.g * value, self.b * value, self.a)
+    }
+}
+
+impl Mul for Color {
+    type Output = Color;
+
+    fn mul(self, other: Color) -> Color {
+        Color::rgba(self.r * other.r, self.g * other.g, self.b * other.b, self.a * other.a)
+    }
+}
+
+// the conversions to the sdl2 and wgpu colors only exist in the client, the server has neither
+#[cfg(feature = "client")]
+impl From<sdl2::pixels::Color> for Color {
+    fn from(color: sdl2::pixels::Color) -> Self {
+        Color::from_srgb8(color.r, color.g, color.b, color.a)
+    }
+}
+
+#[cfg(feature = "client")]
+impl From<Color> for sdl2::pixels::Color {
+    fn from(color: Color) -> Self {
+        let [r, g, b, a] = color.to_srgb8();
+        sdl2::pixels::Color::RGBA(r, g, b, a)
+    }
+}
+
+// wgpu colors are linear already, so this is only a change of precision
+#[cfg(feature = "client")]
+impl From<wgpu::Color> for Color {
+    fn from(color: wgpu::Color) -> Self {
+        Color::rgba(color.r as f32, color.g as f32, color.b as f32, color.a as f32)
+    }
+}
+
+#[cfg(feature = "client")]
+impl From<Color> for wgpu::Color {
+    fn from(color: Color) -> Self {
+        wgpu::Color { r: color.r as f64, g: color.g as f64, b: color.b as f64, a: color.a as f64 }
+    }
+}
+
+impl From<[f32; 4]> for Color {
+    fn from(values: [f32; 4]) -> Self {
+        Color::rgba(values[0], values[1], values[2], values[3])
+    }
+}
+
+impl From<Color> for [f32; 4] {
+    fn from(color: Color) -> Self {
+        color.to_array()
+    }
+}
+
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
@@ -0,0 +1,210 @@
+// hashes of what the asset files have inside, not of when they were saved
+// the file watcher only reports a file when its hash changes (an editor that saves without changes, a checkout that
+// touches everything), the processed caches are named by the hash of what went in so they are stale exactly when
+// the source is different, and a shipped build checks what it loads against the manifest written when it was packed
+// the manifest is a text file at the root of the assets, a "hash  path" line per file like sha256sum
+// blake3, fast enough to hash every asset at startup and long enough that a different file never has the same hash
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use anyhow::{anyhow, bail, Context};
+
+pub const MANIFEST_NAME: &str = "assets.manifest";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ContentHash([u8; 32]);
+
+impl ContentHash {
+    pub fn of_bytes(bytes: &[u8]) -> Self {
+        Self(*blake3::hash(bytes).as_bytes())
+    }
+
+    // many inputs as one, each with its length so ("ab", "c") and ("a", "bc") are different
+    pub fn of_parts(parts: &[&[u8]]) -> Self {
+        let mut hasher = blake3::Hasher::new();
+        for part in parts {
+            hasher.update(&(part.len() as u64).to_le_bytes());
+            hasher.update(part);
+        }
+        Self(*hasher.finalize().as_bytes())
+    }
+
+    // without reading the whole file in memory
+    pub fn of_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut file = fs::File::open(path)?;
+        let mut hasher = blake3::Hasher::new();
+        io::copy(&mut file, &mut hasher)?;
+        Ok(Self(*hasher.finalize().as_bytes()))
+    }
+
+    pub fn to_hex(self) -> String {
+        self.0.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    pub fn from_hex(text: &str) -> Option<Self> {
+        if text.len() != 64 || !text.is_ascii() {
+            return None;
+        }
+        let mut bytes = [0u8; 32];
+        for (index, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&text[index * 2..index * 2 + 2], 16).ok()?;
+        }
+        Some(Self(bytes))
+    }
+}
+
+impl fmt::Display for ContentHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_hex())
+    }
+}
+
+// the hash of every file under a root, by the path from the root with / on every system
+pub struct AssetManifest {
+    root: PathBuf,
+    hashes: BTreeMap<String, ContentHash>,
+}
+
+impl AssetManifest {
+    // every file under the root except the manifest itself
+    pub fn build(root: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let mut manifest = Self { root: root.into(), hashes: BTreeMap::new() };
+        let mut folders = vec![manifest.root.clone()];
+        while let Some(folder) = folders.pop() {
+            for entry in fs::read_dir(&folder).with_context(|| format!("couldn't list {}", folder.display()))? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    folders.push(path);
+                    continue;
+                }
+                let Some(relative) = manifest.relative(&path) else { continue };
+                if relative == MANIFEST_NAME {
+                    continue;
+                }
+                let hash = ContentHash::of_file(&path).with_context(|| format!("couldn't hash {}", path.display()))?;
+                manifest.hashes.insert(relative, hash);
+            }
+        }
+        Ok(manifest)
+    }
+
+    pub fn load(root: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let root = root.into();
+        let path = root.join(MANIFEST_NAME);
+        let text = fs::read_to_string(&path).with_context(|| format!("couldn't read {}", path.display()))?;
+        let mut hashes = BTreeMap::new();
+        for (number, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let (hash, relative) = line.split_once("  ").ok_or_else(|| anyhow!("{} line {} is not \"hash  path\"", path.display(), number + 1))?;
+            let hash = ContentHash::from_hex(hash).ok_or_else(|| anyhow!("{} line {} has a bad hash", path.display(), number + 1))?;
+            hashes.insert(relative.to_string(), hash);
+        }
+        Ok(Self { root, hashes })
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        let text: String = self.hashes.iter().map(|(relative, hash)| format!("{}  {}\n", hash, relative)).collect();
+        fs::write(self.root.join(MANIFEST_NAME), text)?;
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    fn relative(&self, path: &Path) -> Option<String> {
+        let relative = path.strip_prefix(&self.root).ok()?;
+        Some(relative.to_string_lossy().replace('\\', "/"))
+    }
+
+    pub fn covers(&self, path: &Path) -> bool {
+        path.starts_with(&self.root)
+    }
+
+    // the bytes of a file under the root are what was packed, a file the manifest doesn't know fails too
+    pub fn verify(&self, path: &Path, bytes: &[u8]) -> anyhow::Result<()> {
+        let relative = self.relative(path).ok_or_else(|| anyhow!("{} is not under {}", path.display(), self.root.display()))?;
+        let Some(expected) = self.hashes.get(&relative) else {
+            bail!("{} is not in the asset manifest", relative);
+        };
+        if ContentHash::of_bytes(bytes) != *expected {
+            bail!("{} is not the file that was shipped (damaged or modified)", relative);
+        }
+        Ok(())
+    }
+}
+
+// the manifests the loads are checked against, none in development so the assets can be edited freely
+fn manifests() -> &'static Mutex<Vec<AssetManifest>> {
+    static MANIFESTS: OnceLock<Mutex<Vec<AssetManifest>>> = OnceLock::new();
+    MANIFESTS.get_or_init(|| Mutex::new(Vec::new()))
+}
+
+// from now on what is loaded from under the root is checked, if the root has a manifest
+pub fn verify_loads_under(root: impl Into<PathBuf>) -> anyhow::Result<()> {
+    let manifest = AssetManifest::load(root)?;
+    manifests().lock().unwrap().push(manifest);
+    Ok(())
+}
+
+// what the loaders call with the bytes they read, it passes when no manifest covers the path
+pub fn verify_load(path: impl AsRef<Path>, bytes: &[u8]) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let manifests = manifests().lock().unwrap();
+    match manifests.iter().find(|manifest| manifest.covers(path)) {
+        Some(manifest) => manifest.verify(path, bytes),
+        None => Ok(()),
+    }
+}
+
+// the same for the loaders that open the file themselves (tobj, gltf), it reads it once more only when it's checked
+pub fn verify_file(path: impl AsRef<Path>) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    if !manifests().lock().unwrap().iter().any(|manifest| manifest.covers(path)) {
+        return Ok(());
+    }
+    let bytes = fs::read(path).with_context(|| format!("couldn't read {}", path.display()))?;
+    verify_load(path, &bytes)
+}
+
+// outputs of slow processing (welding, tangents) on disk, named by the hash of everything that went in
+// a different input is a different name, so nothing is ever invalidated by hand and old entries are just unused
+pub struct ProcessedCache {
+    folder: PathBuf,
+}
+
+impl ProcessedCache {
+    pub fn new(folder: impl Into<PathBuf>) -> Self {
+        Self { folder: folder.into() }
+    }
+
+    fn path(&self, key: &ContentHash) -> PathBuf {
+        self.folder.join(format!("{}.bin", key))
+    }
+
+    // the entry starts with the hash of the rest, a torn write or a damaged disk is a miss and not garbage
+    pub fn get(&self, key: &ContentHash) -> Option<Vec<u8>> {
+        let bytes = fs::read(self.path(key)).ok()?;
+        if bytes.len() < 32 {
+            return None;
+        }
+        let (hash, payload) = bytes.split_at(32);
+        (ContentHash::of_bytes(payload).0[..] == *hash).then(|| payload.to_vec())
+    }
+
+    pub fn put(&self, key: &ContentHash, payload: &[u8]) -> anyhow::Result<()> {
+        fs::create_dir_all(&self.folder)?;
+        let mut bytes = Vec::with_capacity(32 + payload.len());
+        bytes.extend_from_slice(&ContentHash::of_bytes(payload).0);
+        bytes.extend_from_slice(payload);
+        // written next to it and renamed, a reader never sees half of it
+        let temporary = self.path(key).with_extension("tmp");
+        fs::write(&temporary, bytes)?;
+        fs::rename(&temporary, self.path(key))?;
+        Ok(())
+    }
+}
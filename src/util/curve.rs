@@ -0,0 +1,238 @@
+// curves (time -> float) and gradients (time -> color) that designers can edit and save
+// particles, animations and the day/night cycle evaluate them every frame
+// the keys of a file are sorted when it's read, and a key that isn't a number or two keys at the same time are errors
+
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+
+use super::color::Color;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Interpolation {
+    Step,   // keeps the value of the last key until the next one
+    Linear,
+    Smooth, // cubic with automatic tangents, no overshoot at the keys
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CurveKey {
+    pub time: f32,
+    pub value: f32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "CurveFile")]
+pub struct Curve {
+    keys: Vec<CurveKey>, // always sorted by time
+    pub interpolation: Interpolation,
+}
+
+// a curve as it is written, the keys in any order
+#[derive(Deserialize)]
+struct CurveFile {
+    keys: Vec<CurveKey>,
+    interpolation: Interpolation,
+}
+
+impl TryFrom<CurveFile> for Curve {
+    type Error = anyhow::Error;
+
+    fn try_from(file: CurveFile) -> anyhow::Result<Self> {
+        if let Some(key) = file.keys.iter().find(|key| !key.time.is_finite() || !key.value.is_finite()) {
+            bail!("the curve has a key that is not a number ({} at {})", key.value, key.time);
+        }
+        let keys = sorted_keys(file.keys, |key| key.time)?;
+        Ok(Self { keys, interpolation: file.interpolation })
+    }
+}
+
+// the keys by time, two at the same time can't both be right so they are an error
+fn sorted_keys<K>(mut keys: Vec<K>, time: impl Fn(&K) -> f32) -> anyhow::Result<Vec<K>> {
+    keys.sort_by(|a, b| time(a).total_cmp(&time(b)));
+    if let Some(pair) = keys.windows(2).find(|pair| time(&pair[0]) == time(&pair[1])) {
+        bail!("two keys at the time {}", time(&pair[0]));
+    }
+    Ok(keys)
+}
+
+impl Curve {
+    pub fn new(interpolation: Interpolation) -> Self {
+        Self { keys: Vec::new(), interpolation }
+    }
+
+    pub fn constant(value: f32) -> Self {
+        let mut curve = Self::new(Interpolation::Linear);
+        curve.add_key(0.0, value);
+        curve
+    }
+
+    // a line from (0, from) to (1, to)
+    pub fn linear(from: f32, to: f32) -> Self {
+        let mut curve = Self::new(Interpolation::Linear);
+        curve.add_key(0.0, from);
+        curve.add_key(1.0, to);
+        curve
+    }
+
+    pub fn keys(&self) -> &[CurveKey] {
+        &self.keys
+    }
+
+    // adds a key keeping the order, a key at the same time replaces the old one, returns its index
+    pub fn add_key(&mut self, time: f32, value: f32) -> usize {
+        match self.keys.binary_search_by(|k| k.time.total_cmp(&time)) {
+            Ok(index) => {
+                self.keys[index].value = value;
+                index
+            }
+            Err(index) => {
+                self.keys.insert(index, CurveKey { time, value });
+                index
+            }
+        }
+    }
+
+    pub fn remove_key(&mut self, index: usize) -> Option<CurveKey> {
+        if index < self.keys.len() {
+            Some(self.keys.remove(index))
+        } else {
+            None
+        }
+    }
+
+    // moving a key can change its place in the list, so the new index is returned
+    pub fn move_key(&mut self, index: usize, time: f32, value: f32) -> Option<usize> {
+        self.remove_key(index)?;
+        Some(self.add_key(time, value))
+    }
+
+    // before the first key and after the last one the value stays flat
+    pub fn evaluate(&self, time: f32) -> f32 {
+        let (Some(first), Some(last)) = (self.keys.first(), self.keys.last()) else {
+            return 0.0;
+        };
+        if time <= first.time {
+            return first.value;
+        }
+        if time >= last.time {
+            return last.value;
+        }
+
+        // the first key that is after the time, there is always one before it because of the checks above
+        let next = self.keys.partition_point(|k| k.time <= time);
+        let a = self.keys[next - 1];
+        let b = self.keys[next];
+        let t = (time - a.time) / (b.time - a.time);
+
+        match self.interpolation {
+            Interpolation::Step => a.value,
+            Interpolation::Linear => a.value + (b.value - a.value) * t,
+            Interpolation::Smooth => {
+                let m0 = self.tangent(next - 1) * (b.time - a.time);
+                let m1 = self.tangent(next) * (b.time - a.time);
+                hermite(a.value, m0, b.value, m1, t)
+            }
+        }
+    }
+
+    // catmull-rom like slope, flattened at the ends and at peaks so the curve never goes past a key
+    fn tangent(&self, index: usize) -> f32 {
+        if index == 0 || index + 1 >= self.keys.len() {
+            return 0.0;
+        }
+        let previous = self.keys[index - 1];
+        let current = self.keys[index];
+        let next = self.keys[index + 1];
+        if (current.value - previous.value) * (next.value - current.value) <= 0.0 {
+            return 0.0;
+        }
+        (next.value - previous.value) / (next.time - previous.time)
+    }
+}
+
+fn hermite(p0: f32, m0: f32, p1: f32, m1: f32, t: f32) -> f32 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    (2.0 * t3 - 3.0 * t2 + 1.0) * p0 + (t3 - 2.0 * t2 + t) * m0 + (-2.0 * t3 + 3.0 * t2) * p1 + (t3 - t2) * m1
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GradientKey {
+    pub time: f32,
+    pub color: Color,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "GradientFile")]
+pub struct Gradient {
+    keys: Vec<GradientKey>, // always sorted by time
+    pub interpolation: Interpolation,
+}
+
+#[derive(Deserialize)]
+struct GradientFile {
+    keys: Vec<GradientKey>,
+    interpolation: Interpolation,
+}
+
+impl TryFrom<GradientFile> for Gradient {
+    type Error = anyhow::Error;
+
+    fn try_from(file: GradientFile) -> anyhow::Result<Self> {
+        if let Some(key) = file.keys.iter().find(|key| !key.time.is_finite() || !key.color.to_array().iter().all(|channel| channel.is_finite())) {
+            bail!("the gradient has a key that is not a number at {}", key.time);
+        }
+        let keys = sorted_keys(file.keys, |key| key.time)?;
+        Ok(Self { keys, interpolation: file.interpolation })
+    }
+}
+
+impl Gradient {
+    pub fn new(interpolation: Interpolation) -> Self {
+        Self { keys: Vec::new(), interpolation }
+    }
+
+    pub fn two_colors(from: Color, to: Color) -> Self {
+        let mut gradient = Self::new(Interpolation::Linear);
+        gradient.add_key(0.0, from);
+        gradient.add_key(1.0, to);
+        gradient
+    }
+
+    pub fn add_key(&mut self, time: f32, color: Color) -> usize {
+        match self.keys.binary_search_by(|k| k.time.total_cmp(&time)) {
+            Ok(index) => {
+                self.keys[index].color = color;
+                index
+            }
+            Err(index) => {
+                self.keys.insert(index, GradientKey { time, color });
+                index
+            }
+        }
+    }
+
+    // the colors are mixed in linear space
+    pub fn evaluate(&self, time: f32) -> Color {
+        let (Some(first), Some(last)) = (self.keys.first(), self.keys.last()) else {
+            return Color::WHITE;
+        };
+        if time <= first.time {
+            return first.color;
+        }
+        if time >= last.time {
+            return last.color;
+        }
+
+        let next = self.keys.partition_point(|k| k.time <= time);
+        let a = self.keys[next - 1];
+        let b = self.keys[next];
+        let t = (time - a.time) / (b.time - a.time);
+
+        match self.interpolation {
+            Interpolation::Step => a.color,
+            Interpolation::Linear => a.color.lerp(b.color, t),
+            Interpolation::Smooth => a.color.lerp(b.color, t * t * (3.0 - 2.0 * t)),
+        }
+    }
+}
@@ -0,0 +1,276 @@
+// console variables: the runtime settings by name, with a type, a default and flags, so the console, the config
+// file and the debug ui all change them the same way. the app registers its own in App::new and applies the ones
+// that changed once per frame (see App::apply_cvars), the game can register more
+// the archived ones are saved in a key=value file like the other settings
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, bail};
+
+pub const CVARS_PATH: &str = "cvars.cfg";
+
+// the cvar that lets the cheat ones change
+pub const CHEATS_CVAR: &str = "sv_cheats";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CvarValue {
+    Bool(bool),
+    Int(i64),
+    Float(f32),
+    Text(String),
+}
+
+impl CvarValue {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            CvarValue::Bool(_) => "bool",
+            CvarValue::Int(_) => "int",
+            CvarValue::Float(_) => "float",
+            CvarValue::Text(_) => "text",
+        }
+    }
+
+    // a value of the same type from what was typed, bools take 1/0, on/off and true/false
+    pub fn parse_like(&self, text: &str) -> anyhow::Result<CvarValue> {
+        let text = text.trim();
+        Ok(match self {
+            CvarValue::Bool(_) => CvarValue::Bool(match text {
+                "1" | "true" | "on" => true,
+                "0" | "false" | "off" => false,
+                _ => bail!("{} is not a bool (1/0, true/false or on/off)", text),
+            }),
+            CvarValue::Int(_) => CvarValue::Int(text.parse().map_err(|_| anyhow!("{} is not an int", text))?),
+            CvarValue::Float(_) => CvarValue::Float(text.parse().map_err(|_| anyhow!("{} is not a float", text))?),
+            CvarValue::Text(_) => CvarValue::Text(text.trim_matches('"').to_string()),
+        })
+    }
+}
+
+impl fmt::Display for CvarValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CvarValue::Bool(value) => write!(f, "{}", if *value { 1 } else { 0 }),
+            CvarValue::Int(value) => write!(f, "{}", value),
+            CvarValue::Float(value) => write!(f, "{}", value),
+            CvarValue::Text(value) => write!(f, "\"{}\"", value),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CvarFlags {
+    pub archive: bool,   // saved in the config file
+    pub cheat: bool,     // only changes while sv_cheats is on
+    pub read_only: bool, // shown but never changed from outside (the version, what the gpu supports)
+}
+
+impl CvarFlags {
+    pub const NONE: CvarFlags = CvarFlags { archive: false, cheat: false, read_only: false };
+    pub const ARCHIVE: CvarFlags = CvarFlags { archive: true, cheat: false, read_only: false };
+    pub const CHEAT: CvarFlags = CvarFlags { archive: false, cheat: true, read_only: false };
+    pub const READ_ONLY: CvarFlags = CvarFlags { archive: false, cheat: false, read_only: true };
+}
+
+#[derive(Clone, Debug)]
+pub struct Cvar {
+    pub name: String,
+    pub description: String,
+    pub flags: CvarFlags,
+    value: CvarValue,
+    default: CvarValue,
+    range: Option<(f64, f64)>, // for the numbers, what is set outside is clamped
+}
+
+impl Cvar {
+    pub fn value(&self) -> &CvarValue {
+        &self.value
+    }
+
+    fn clamp(&self, value: CvarValue) -> CvarValue {
+        match (value, self.range) {
+            (CvarValue::Int(value), Some((min, max))) => CvarValue::Int(value.clamp(min as i64, max as i64)),
+            (CvarValue::Float(value), Some((min, max))) => CvarValue::Float(value.clamp(min as f32, max as f32)),
+            (value, _) => value,
+        }
+    }
+}
+
+pub struct CvarRegistry {
+    cvars: HashMap<String, Cvar>,
+    changed: Vec<String>, // since the last take_changed, in the order they changed
+}
+
+impl CvarRegistry {
+    pub fn new() -> Self {
+        let mut registry = Self { cvars: HashMap::new(), changed: Vec::new() };
+        registry.register(CHEATS_CVAR, CvarValue::Bool(false), CvarFlags::NONE, "lets the cheat cvars change");
+        registry
+    }
+
+    // registering a name again keeps the value it had and updates the rest, so reloading a module doesn't reset it
+    pub fn register(&mut self, name: &str, default: CvarValue, flags: CvarFlags, description: &str) {
+        let value = match self.cvars.get(name) {
+            Some(existing) if existing.value.type_name() == default.type_name() => existing.value.clone(),
+            _ => default.clone(),
+        };
+        self.cvars.insert(name.to_string(), Cvar { name: name.to_string(), description: description.to_string(), flags, value, default, range: None });
+    }
+
+    // a number that is always inside the range
+    pub fn register_ranged(&mut self, name: &str, default: CvarValue, range: (f64, f64), flags: CvarFlags, description: &str) {
+        self.register(name, default, flags, description);
+        if let Some(cvar) = self.cvars.get_mut(name) {
+            cvar.range = Some(range);
+            cvar.value = cvar.clamp(cvar.value.clone());
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Cvar> {
+        self.cvars.get(name)
+    }
+
+    pub fn bool(&self, name: &str) -> Option<bool> {
+        match self.cvars.get(name)?.value {
+            CvarValue::Bool(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn int(&self, name: &str) -> Option<i64> {
+        match self.cvars.get(name)?.value {
+            CvarValue::Int(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn float(&self, name: &str) -> Option<f32> {
+        match self.cvars.get(name)?.value {
+            CvarValue::Float(value) => Some(value),
+            CvarValue::Int(value) => Some(value as f32),
+            _ => None,
+        }
+    }
+
+    pub fn text(&self, name: &str) -> Option<&str> {
+        match &self.cvars.get(name)?.value {
+            CvarValue::Text(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    // from outside (console, config, debug ui), it follows the flags
+    pub fn set(&mut self, name: &str, value: CvarValue) -> anyhow::Result<()> {
+        let cheats = self.bool(CHEATS_CVAR).unwrap_or(false);
+        let cvar = self.cvars.get(name).ok_or_else(|| anyhow!("there is no cvar {}", name))?;
+        if cvar.flags.read_only {
+            bail!("{} is read only", name);
+        }
+        if cvar.flags.cheat && !cheats {
+            bail!("{} is a cheat, set {} 1 first", name, CHEATS_CVAR);
+        }
+        self.set_internal(name, value)
+    }
+
+    // the text is parsed with the type of the cvar
+    pub fn set_from_str(&mut self, name: &str, text: &str) -> anyhow::Result<()> {
+        let value = self.cvars.get(name).ok_or_else(|| anyhow!("there is no cvar {}", name))?.value.parse_like(text)?;
+        self.set(name, value)
+    }
+
+    // from the code that owns the setting, the flags don't stop it (the read only ones are set like this)
+    pub fn set_internal(&mut self, name: &str, value: CvarValue) -> anyhow::Result<()> {
+        let cvar = self.cvars.get_mut(name).ok_or_else(|| anyhow!("there is no cvar {}", name))?;
+        if cvar.value.type_name() != value.type_name() {
+            bail!("{} is a {}, not a {}", name, cvar.value.type_name(), value.type_name());
+        }
+        let value = cvar.clamp(value);
+        if cvar.value != value {
+            cvar.value = value;
+            if !self.changed.iter().any(|changed| changed == name) {
+                self.changed.push(name.to_string());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn reset(&mut self, name: &str) -> anyhow::Result<()> {
+        let default = self.cvars.get(name).ok_or_else(|| anyhow!("there is no cvar {}", name))?.default.clone();
+        self.set(name, default)
+    }
+
+    // the names that changed since the last call, whoever applies them calls this once per frame
+    pub fn take_changed(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.changed)
+    }
+
+    // by name, for the console completion and the debug ui
+    pub fn iter(&self) -> impl Iterator<Item = &Cvar> {
+        let mut cvars: Vec<&Cvar> = self.cvars.values().collect();
+        cvars.sort_by(|a, b| a.name.cmp(&b.name));
+        cvars.into_iter()
+    }
+
+    // a line of the console: "name" shows it, "name value" sets it, "reset name" goes back to the default
+    // the answer is what the console prints
+    pub fn execute(&mut self, line: &str) -> anyhow::Result<String> {
+        let line = line.trim();
+        let (command, argument) = match line.split_once(char::is_whitespace) {
+            Some((command, argument)) => (command, argument.trim()),
+            None => (line, ""),
+        };
+        if command == "reset" {
+            self.reset(argument)?;
+            return Ok(self.describe(argument));
+        }
+        if argument.is_empty() {
+            if !self.cvars.contains_key(command) {
+                bail!("there is no cvar {}", command);
+            }
+            return Ok(self.describe(command));
+        }
+        self.set_from_str(command, argument)?;
+        Ok(self.describe(command))
+    }
+
+    fn describe(&self, name: &str) -> String {
+        match self.cvars.get(name) {
+            Some(cvar) => format!("{} = {} (default {}) {}", cvar.name, cvar.value, cvar.default, cvar.description),
+            None => String::new(),
+        }
+    }
+
+    // the archived cvars of the file over the registered ones, unknown names and bad values are skipped
+    // it goes through set_internal so a cheat saved by hand doesn't stay locked
+    pub fn load(&mut self, path: impl AsRef<Path>) {
+        let Ok(text) = fs::read_to_string(path) else { return };
+        for line in text.lines() {
+            let Some((name, value)) = line.split_once('=') else { continue };
+            let name = name.trim();
+            let Some(cvar) = self.cvars.get(name) else { continue };
+            if !cvar.flags.archive {
+                continue;
+            }
+            if let Ok(value) = cvar.value.parse_like(value) {
+                let _ = self.set_internal(name, value);
+            }
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let mut text = String::new();
+        for cvar in self.iter().filter(|cvar| cvar.flags.archive) {
+            text.push_str(&format!("{}={}\n", cvar.name, cvar.value));
+        }
+        fs::write(path, text)?;
+        Ok(())
+    }
+}
+
+impl Default for CvarRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
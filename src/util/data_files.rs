@@ -0,0 +1,58 @@
+// the ron files of a folder of the assets (abilities, items), each a list of definitions, read through the mods (see
+// util/vfs.rs) and read again when one of them changes on disk
+// a file that doesn't parse keeps what it had before, a half saved file in the editor does no harm
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::de::DeserializeOwned;
+
+use crate::util::vfs;
+
+const EXTENSION: &str = ".ron";
+
+pub struct DataFiles<T> {
+    folder: &'static str,
+    files: Vec<(String, Vec<T>)>, // under the assets ("items/tools.ron") with what each one had, in name order
+}
+
+impl<T: DeserializeOwned + Clone> DataFiles<T> {
+    pub fn load(folder: &'static str) -> Self {
+        let mut files = Self { folder, files: Vec::new() };
+        files.reload();
+        files
+    }
+
+    pub fn reload(&mut self) {
+        let names: Vec<String> = vfs::global().read().unwrap().list(self.folder).into_iter().filter(|file| file.ends_with(EXTENSION)).collect();
+        let mut files = Vec::with_capacity(names.len());
+        for file in names {
+            match Self::read(&file) {
+                Ok(definitions) => files.push((file, definitions)),
+                Err(e) => {
+                    eprintln!("{} was not loaded: {:#}", file, e);
+                    let old = self.files.iter().find(|(known, _)| *known == file).map(|(_, definitions)| definitions.clone()).unwrap_or_default();
+                    files.push((file, old));
+                }
+            }
+        }
+        self.files = files;
+    }
+
+    fn read(file: &str) -> anyhow::Result<Vec<T>> {
+        let path = Path::new(vfs::DEFAULT_BASE).join(file);
+        let bytes = vfs::global().read().unwrap().read(&path)?;
+        let text = String::from_utf8(bytes).with_context(|| format!("{} is not text", file))?;
+        Ok(ron::from_str(&text)?)
+    }
+
+    // every definition, the ones of the later files after
+    pub fn definitions(&self) -> impl Iterator<Item = &T> {
+        self.files.iter().flat_map(|(_, definitions)| definitions.iter())
+    }
+
+    // the files on disk, for the file watcher
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.files.iter().map(|(file, _)| vfs::resolve(Path::new(vfs::DEFAULT_BASE).join(file))).collect()
+    }
+}
@@ -0,0 +1,106 @@
+// tells when files change on disk, for the hot reload of the assets
+// a thread looks at the modification time of every watched file a few times per second and sends the ones that
+// changed, the game takes them once per frame without waiting. no os notifications, polling is enough for a few
+// hundred assets and works the same everywhere
+// a new modification time only counts when the content hash changed too, saving without edits reloads nothing
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::util::content_hash::ContentHash;
+
+// none while the file doesn't exist (or can't be read)
+#[derive(Copy, Clone)]
+struct WatchedFile {
+    modified: Option<SystemTime>,
+    hash: Option<ContentHash>,
+}
+
+impl WatchedFile {
+    fn read(path: &Path) -> Self {
+        Self { modified: modified_time(path), hash: ContentHash::of_file(path).ok() }
+    }
+}
+
+pub struct FileWatcher {
+    watched: Arc<Mutex<HashMap<PathBuf, WatchedFile>>>,
+    changes: Receiver<PathBuf>,
+    running: Arc<AtomicBool>,
+}
+
+impl FileWatcher {
+    pub fn new(interval: Duration) -> Self {
+        let watched: Arc<Mutex<HashMap<PathBuf, WatchedFile>>> = Arc::new(Mutex::new(HashMap::new()));
+        let running = Arc::new(AtomicBool::new(true));
+        let (sender, changes) = mpsc::channel();
+
+        let thread_watched = watched.clone();
+        let thread_running = running.clone();
+        thread::Builder::new()
+            .name("file watcher".to_string())
+            .spawn(move || {
+                while thread_running.load(Ordering::Relaxed) {
+                    thread::sleep(interval);
+                    let mut watched = thread_watched.lock().unwrap();
+                    for (path, last) in watched.iter_mut() {
+                        let modified = modified_time(path);
+                        // an editor that saves by deleting and writing again shows up as missing for a moment
+                        if modified.is_none() || modified == last.modified {
+                            continue;
+                        }
+                        last.modified = modified;
+                        // the hash is only read for the ones that were saved, the rest only cost the metadata
+                        let hash = ContentHash::of_file(path).ok();
+                        if hash.is_none() || hash == last.hash {
+                            continue;
+                        }
+                        last.hash = hash;
+                        if sender.send(path.clone()).is_err() {
+                            return;
+                        }
+                    }
+                }
+            })
+            .expect("the file watcher thread couldn't start");
+
+        Self { watched, changes, running }
+    }
+
+    // a file that is already watched is not reported again until it changes
+    pub fn watch(&self, path: impl AsRef<Path>) {
+        let path = path.as_ref().to_path_buf();
+        let mut watched = self.watched.lock().unwrap();
+        if let Entry::Vacant(entry) = watched.entry(path) {
+            let file = WatchedFile::read(entry.key());
+            entry.insert(file);
+        }
+    }
+
+    // the files that changed since the last call, each once even if it was saved many times
+    pub fn changes(&self) -> Vec<PathBuf> {
+        let mut changed: Vec<PathBuf> = Vec::new();
+        for path in self.changes.try_iter() {
+            if !changed.contains(&path) {
+                changed.push(path);
+            }
+        }
+        changed
+    }
+}
+
+impl Drop for FileWatcher {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
@@ -0,0 +1,45 @@
+// keeps the frames from going faster than a target, with the surface on AutoNoVsync nothing else stops them
+// it sleeps most of the wait and spins the last bit, the sleep of the os is not precise enough for 144 hz
+
+use std::time::{Duration, Instant};
+
+// how much of the wait is spun instead of slept
+const SPIN_TIME: Duration = Duration::from_millis(1);
+
+pub struct FrameLimiter {
+    frame_time: Option<Duration>, // none is uncapped
+    next_frame: Instant,
+}
+
+impl FrameLimiter {
+    pub fn new(fps: Option<u32>) -> Self {
+        let mut limiter = Self { frame_time: None, next_frame: Instant::now() };
+        limiter.set_fps(fps);
+        limiter
+    }
+
+    pub fn set_fps(&mut self, fps: Option<u32>) {
+        self.frame_time = fps.filter(|fps| *fps > 0).map(|fps| Duration::from_secs_f64(1.0 / fps as f64));
+        self.next_frame = Instant::now();
+    }
+
+    // at the end of the frame, waits until the next one can start
+    pub fn wait(&mut self) {
+        let Some(frame_time) = self.frame_time else { return };
+        let now = Instant::now();
+        if now >= self.next_frame {
+            // we are late, the next frame is counted from now so we don't rush to catch up
+            self.next_frame = now + frame_time;
+            return;
+        }
+
+        let left = self.next_frame - now;
+        if left > SPIN_TIME {
+            std::thread::sleep(left - SPIN_TIME);
+        }
+        while Instant::now() < self.next_frame {
+            std::hint::spin_loop();
+        }
+        self.next_frame += frame_time;
+    }
+}
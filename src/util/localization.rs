@@ -0,0 +1,85 @@
+// the text the player reads, by key instead of written in the code or in the data files: a ron map per language in
+// the lang folder of the assets ("lang/en.ron"), a mod can bring a language or its own version of one (see util/vfs.rs)
+//
+//   {
+//       "npc.guard": "Guard",
+//       "dialogue.guard.hello": "Halt! Who goes there?",
+//   }
+//
+// a key the language doesn't have falls back to english and then to the key itself, so a missing line shows what is
+// missing instead of nothing. the language is the "language" cvar
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+use crate::util::vfs;
+
+pub const LANG_FOLDER: &str = "lang";
+pub const FALLBACK_LANGUAGE: &str = "en";
+
+pub struct Localization {
+    language: String,
+    strings: HashMap<String, String>,
+    fallback: HashMap<String, String>,
+}
+
+impl Localization {
+    pub fn load(language: &str) -> Self {
+        let mut localization = Self { language: language.to_string(), strings: HashMap::new(), fallback: HashMap::new() };
+        localization.reload();
+        localization
+    }
+
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+
+    pub fn set_language(&mut self, language: &str) {
+        if language != self.language {
+            self.language = language.to_string();
+            self.strings.clear();
+            self.reload();
+        }
+    }
+
+    // a file that doesn't parse keeps the lines it had
+    pub fn reload(&mut self) {
+        match read_table(FALLBACK_LANGUAGE) {
+            Ok(strings) => self.fallback = strings,
+            Err(e) => eprintln!("the language {} was not loaded: {:#}", FALLBACK_LANGUAGE, e),
+        }
+        if self.language == FALLBACK_LANGUAGE {
+            self.strings.clear();
+            return;
+        }
+        match read_table(&self.language) {
+            Ok(strings) => self.strings = strings,
+            Err(e) => eprintln!("the language {} was not loaded: {:#}", self.language, e),
+        }
+    }
+
+    pub fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        self.strings.get(key).or_else(|| self.fallback.get(key)).map_or(key, String::as_str)
+    }
+
+    // the files on disk, for the file watcher
+    pub fn files(&self) -> Vec<PathBuf> {
+        let mut files = vec![vfs::resolve(table_path(FALLBACK_LANGUAGE))];
+        if self.language != FALLBACK_LANGUAGE {
+            files.push(vfs::resolve(table_path(&self.language)));
+        }
+        files
+    }
+}
+
+fn table_path(language: &str) -> PathBuf {
+    Path::new(vfs::DEFAULT_BASE).join(LANG_FOLDER).join(format!("{}.ron", language))
+}
+
+fn read_table(language: &str) -> anyhow::Result<HashMap<String, String>> {
+    let bytes = vfs::global().read().unwrap().read(&table_path(language))?;
+    let text = String::from_utf8(bytes).with_context(|| format!("{}.ron is not text", language))?;
+    Ok(ron::from_str(&text)?)
+}
@@ -0,0 +1,85 @@
+// the monitors sdl knows about, so the game can list them in the settings and open (or move) the window on one
+// the refresh rate of the monitor is also what the frame limiter goes to by default
+
+use sdl2::rect::Rect;
+use sdl2::video::{DisplayMode, Window, WindowPos};
+use sdl2::VideoSubsystem;
+
+// what most monitors do, for the ones that don't say their refresh rate (sdl gives 0)
+pub const FALLBACK_REFRESH_RATE: u32 = 60;
+
+#[derive(Clone, Debug)]
+pub struct MonitorInfo {
+    pub index: i32,
+    pub name: String,
+    pub bounds: Rect, // where it is on the desktop, the first one usually starts at 0, 0
+    pub mode: DisplayMode, // the current resolution and refresh rate
+}
+
+impl MonitorInfo {
+    pub fn refresh_rate(&self) -> u32 {
+        refresh_rate_of(&self.mode)
+    }
+}
+
+pub fn refresh_rate_of(mode: &DisplayMode) -> u32 {
+    if mode.refresh_rate > 0 {
+        mode.refresh_rate as u32
+    } else {
+        FALLBACK_REFRESH_RATE
+    }
+}
+
+pub fn list_monitors(video: &VideoSubsystem) -> anyhow::Result<Vec<MonitorInfo>> {
+    let count = video.num_video_displays().map_err(anyhow::Error::msg)?;
+    (0..count).map(|index| monitor_info(video, index)).collect()
+}
+
+pub fn monitor_info(video: &VideoSubsystem, index: i32) -> anyhow::Result<MonitorInfo> {
+    let name = video.display_name(index).map_err(anyhow::Error::msg)?;
+    let bounds = video.display_bounds(index).map_err(anyhow::Error::msg)?;
+    let mode = video.current_display_mode(index).map_err(anyhow::Error::msg)?;
+    Ok(MonitorInfo { index, name, bounds, mode })
+}
+
+// the index if that monitor exists, the first one if not (it was unplugged since the settings were saved)
+pub fn valid_monitor(video: &VideoSubsystem, index: i32) -> i32 {
+    let count = video.num_video_displays().unwrap_or(1);
+    if (0..count).contains(&index) {
+        index
+    } else {
+        0
+    }
+}
+
+// the top left corner that centers a window of that size on the monitor
+pub fn centered_position(monitor: &MonitorInfo, width: u32, height: u32) -> (i32, i32) {
+    let x = monitor.bounds.x() + (monitor.bounds.width() as i32 - width as i32) / 2;
+    let y = monitor.bounds.y() + (monitor.bounds.height() as i32 - height as i32) / 2;
+    (x, y)
+}
+
+pub fn center_window_on(window: &mut Window, monitor: &MonitorInfo) {
+    let (width, height) = window.size();
+    let (x, y) = centered_position(monitor, width, height);
+    window.set_position(WindowPos::Positioned(x), WindowPos::Positioned(y));
+}
+
+// the rectangle that covers all the monitors, for a window spanning them (none if there are no monitors)
+pub fn spanning_bounds(monitors: &[MonitorInfo]) -> Option<Rect> {
+    monitors.iter().map(|monitor| monitor.bounds).reduce(|bounds, other| bounds.union(other))
+}
+
+// how the window covers its monitor, see App::set_fullscreen
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FullscreenMode {
+    Windowed,
+    Borderless, // a window at the size of the desktop, alt tab doesn't change the mode of the monitor
+    Exclusive(DisplayMode), // the monitor changes to that mode while the game has it
+}
+
+// the resolutions and refresh rates the monitor can go to, for the exclusive fullscreen
+pub fn display_modes(video: &VideoSubsystem, index: i32) -> anyhow::Result<Vec<DisplayMode>> {
+    let count = video.num_display_modes(index).map_err(anyhow::Error::msg)?;
+    (0..count).map(|mode| video.display_mode(index, mode).map_err(anyhow::Error::msg)).collect()
+}
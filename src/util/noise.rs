@@ -0,0 +1,129 @@
+// perlin and fbm noise with a seed, for terrain, procedural textures and camera shake
+// the lattice uses an integer hash instead of a permutation table so shaders/noise.wgsl gives the same values on the gpu
+
+#[derive(Copy, Clone, Debug)]
+pub struct Noise {
+    pub seed: u32,
+}
+
+impl Noise {
+    pub fn new(seed: u32) -> Self {
+        Self { seed }
+    }
+
+    // around -1 to 1
+    pub fn perlin2(&self, x: f32, y: f32) -> f32 {
+        let xi = x.floor();
+        let yi = y.floor();
+        let xf = x - xi;
+        let yf = y - yi;
+        let (xi, yi) = (xi as i32, yi as i32);
+
+        let g00 = grad2(hash3(xi, yi, 0, self.seed), xf, yf);
+        let g10 = grad2(hash3(xi + 1, yi, 0, self.seed), xf - 1.0, yf);
+        let g01 = grad2(hash3(xi, yi + 1, 0, self.seed), xf, yf - 1.0);
+        let g11 = grad2(hash3(xi + 1, yi + 1, 0, self.seed), xf - 1.0, yf - 1.0);
+
+        let u = fade(xf);
+        let v = fade(yf);
+        lerp(lerp(g00, g10, u), lerp(g01, g11, u), v)
+    }
+
+    // around -1 to 1
+    pub fn perlin3(&self, x: f32, y: f32, z: f32) -> f32 {
+        let xi = x.floor();
+        let yi = y.floor();
+        let zi = z.floor();
+        let xf = x - xi;
+        let yf = y - yi;
+        let zf = z - zi;
+        let (xi, yi, zi) = (xi as i32, yi as i32, zi as i32);
+
+        let corner = |dx: i32, dy: i32, dz: i32| {
+            grad3(hash3(xi + dx, yi + dy, zi + dz, self.seed), xf - dx as f32, yf - dy as f32, zf - dz as f32)
+        };
+
+        let u = fade(xf);
+        let v = fade(yf);
+        let w = fade(zf);
+
+        let x00 = lerp(corner(0, 0, 0), corner(1, 0, 0), u);
+        let x10 = lerp(corner(0, 1, 0), corner(1, 1, 0), u);
+        let x01 = lerp(corner(0, 0, 1), corner(1, 0, 1), u);
+        let x11 = lerp(corner(0, 1, 1), corner(1, 1, 1), u);
+        lerp(lerp(x00, x10, v), lerp(x01, x11, v), w)
+    }
+
+    // fractal brownian motion, layers of noise each one smaller and weaker than the last
+    // the result is normalized so it stays around -1 to 1 for any number of octaves
+    pub fn fbm3(&self, x: f32, y: f32, z: f32, octaves: u32, lacunarity: f32, gain: f32) -> f32 {
+        let mut sum = 0.0;
+        let mut amplitude = 1.0;
+        let mut frequency = 1.0;
+        let mut total_amplitude = 0.0;
+        for octave in 0..octaves {
+            // every octave with its own seed so the layers don't line up
+            let layer = Noise::new(self.seed.wrapping_add(octave));
+            sum += layer.perlin3(x * frequency, y * frequency, z * frequency) * amplitude;
+            total_amplitude += amplitude;
+            amplitude *= gain;
+            frequency *= lacunarity;
+        }
+        if total_amplitude > 0.0 { sum / total_amplitude } else { 0.0 }
+    }
+}
+
+// the wgsl version of these functions, to paste or include in shaders that need the same noise
+pub const NOISE_WGSL: &str = include_str!("../shaders/noise.wgsl");
+
+// pcg based hash of a lattice point, it must stay exactly the same as hash3 in noise.wgsl
+pub fn hash3(x: i32, y: i32, z: i32, seed: u32) -> u32 {
+    let mut h = seed
+        ^ (x as u32).wrapping_mul(0x8da6_b343)
+        ^ (y as u32).wrapping_mul(0xd816_3841)
+        ^ (z as u32).wrapping_mul(0xcb1a_b31f);
+    h = h.wrapping_mul(747_796_405).wrapping_add(2_891_336_453);
+    h = ((h >> ((h >> 28) + 4)) ^ h).wrapping_mul(277_803_737);
+    (h >> 22) ^ h
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+// one of 8 directions
+fn grad2(h: u32, x: f32, y: f32) -> f32 {
+    const D: f32 = 0.707_106_77;
+    match h & 7 {
+        0 => x,
+        1 => -x,
+        2 => y,
+        3 => -y,
+        4 => D * (x + y),
+        5 => D * (-x + y),
+        6 => D * (x - y),
+        _ => D * (-x - y),
+    }
+}
+
+// one of the 12 edges of a cube, like the original improved perlin
+fn grad3(h: u32, x: f32, y: f32, z: f32) -> f32 {
+    match h % 12 {
+        0 => x + y,
+        1 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x + z,
+        5 => -x + z,
+        6 => x - z,
+        7 => -x - z,
+        8 => y + z,
+        9 => -y + z,
+        10 => y - z,
+        _ => -y - z,
+    }
+}
@@ -0,0 +1,124 @@
+// a pool for things that are spawned and despawned all the time (projectiles, particles, audio voices)
+// the slots are reused so we don't allocate every time, and the handles have a generation so an old
+// handle can't touch the new object that took its slot
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PoolHandle {
+    index: u32,
+    generation: u32,
+}
+
+impl PoolHandle {
+    pub fn index(&self) -> usize {
+        self.index as usize
+    }
+}
+
+struct Slot<T> {
+    generation: u32,
+    value: Option<T>,
+}
+
+pub struct Pool<T> {
+    slots: Vec<Slot<T>>,
+    free: Vec<u32>, // free slots, the last freed is the first reused since it is probably still in cache
+    len: usize,
+}
+
+impl<T> Pool<T> {
+    pub fn new() -> Self {
+        Self { slots: Vec::new(), free: Vec::new(), len: 0 }
+    }
+
+    // reserves the memory up front so spawning never allocates until we go over the capacity
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self { slots: Vec::with_capacity(capacity), free: Vec::with_capacity(capacity), len: 0 }
+    }
+
+    // a freed slot when there is one, a new one at the end otherwise, so it always has room
+    pub fn insert(&mut self, value: T) -> PoolHandle {
+        if let Some(index) = self.free.pop() {
+            let slot = &mut self.slots[index as usize];
+            slot.value = Some(value);
+            self.len += 1;
+            return PoolHandle { index, generation: slot.generation };
+        }
+
+        let index = self.slots.len() as u32;
+        self.slots.push(Slot { generation: 0, value: Some(value) });
+        self.len += 1;
+        PoolHandle { index, generation: 0 }
+    }
+
+    // gives the value back and frees the slot, the generation goes up so old handles stop working
+    pub fn despawn(&mut self, handle: PoolHandle) -> Option<T> {
+        let slot = self.slots.get_mut(handle.index as usize)?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+        let value = slot.value.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(handle.index);
+        self.len -= 1;
+        Some(value)
+    }
+
+    pub fn contains(&self, handle: PoolHandle) -> bool {
+        self.get(handle).is_some()
+    }
+
+    pub fn get(&self, handle: PoolHandle) -> Option<&T> {
+        let slot = self.slots.get(handle.index as usize)?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+        slot.value.as_ref()
+    }
+
+    pub fn get_mut(&mut self, handle: PoolHandle) -> Option<&mut T> {
+        let slot = self.slots.get_mut(handle.index as usize)?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+        slot.value.as_mut()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (PoolHandle, &T)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            slot.value.as_ref().map(|value| (PoolHandle { index: index as u32, generation: slot.generation }, value))
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (PoolHandle, &mut T)> {
+        self.slots.iter_mut().enumerate().filter_map(|(index, slot)| {
+            let generation = slot.generation;
+            slot.value.as_mut().map(|value| (PoolHandle { index: index as u32, generation }, value))
+        })
+    }
+
+    // despawns everything that doesn't pass the check, useful for "remove the dead particles" every frame
+    pub fn retain(&mut self, mut keep: impl FnMut(&mut T) -> bool) {
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            let remove = match slot.value.as_mut() {
+                Some(value) => !keep(value),
+                None => false,
+            };
+            if remove {
+                slot.value = None;
+                slot.generation = slot.generation.wrapping_add(1);
+                self.free.push(index as u32);
+                self.len -= 1;
+            }
+        }
+    }
+}
+
+impl<T> Default for Pool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,107 @@
+// seeded random numbers, every system takes numbers from its own named stream (gameplay, vfx, ai...)
+// so the same seed gives the same game, and adding particles doesn't change what the ai decides
+
+use std::collections::HashMap;
+
+// xoshiro128++, small and fast, good enough for games (not for cryptography)
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: [u32; 4],
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // splitmix64 spreads the seed so close seeds don't give close sequences
+        let mut s = seed;
+        let mut next = || {
+            s = s.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            let mut z = s;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            z ^ (z >> 31)
+        };
+        let a = next();
+        let b = next();
+        let mut state = [a as u32, (a >> 32) as u32, b as u32, (b >> 32) as u32];
+        // the state can't be all zeros
+        if state == [0; 4] {
+            state[0] = 1;
+        }
+        Self { state }
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let result = self.state[0].wrapping_add(self.state[3]).rotate_left(7).wrapping_add(self.state[0]);
+        let t = self.state[1] << 9;
+
+        self.state[2] ^= self.state[0];
+        self.state[3] ^= self.state[1];
+        self.state[1] ^= self.state[2];
+        self.state[0] ^= self.state[3];
+        self.state[2] ^= t;
+        self.state[3] = self.state[3].rotate_left(11);
+
+        result
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        ((self.next_u32() as u64) << 32) | self.next_u32() as u64
+    }
+
+    // between 0 (included) and 1 (excluded)
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 * (1.0 / (1u32 << 24) as f32)
+    }
+
+    pub fn range_f32(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+
+    // uniform direction on the xz plane
+    pub fn unit_vector_2d(&mut self) -> cgmath::Vector2<f32> {
+        let angle = self.range_f32(0.0, std::f32::consts::TAU);
+        cgmath::Vector2::new(angle.cos(), angle.sin())
+    }
+}
+
+pub struct RngStreams {
+    seed: u64,
+    streams: HashMap<String, Rng>,
+}
+
+impl RngStreams {
+    pub fn new(seed: u64) -> Self {
+        Self { seed, streams: HashMap::new() }
+    }
+
+    // a seed from the clock, for when we don't care about repeating the game
+    pub fn from_time() -> Self {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        Self::new(nanos)
+    }
+
+    // every stream gets its own seed made from the main seed and its name, so streams don't depend on each other
+    pub fn stream(&mut self, name: &str) -> &mut Rng {
+        let seed = self.seed;
+        self.streams.entry(name.to_string()).or_insert_with(|| Rng::new(seed ^ hash_name(name)))
+    }
+
+    // starts all the streams again from the seed, for replays and tests
+    pub fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.streams.clear();
+    }
+}
+
+// fnv-1a, stable between runs and platforms (the std hasher is not)
+fn hash_name(name: &str) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in name.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
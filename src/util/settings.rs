@@ -0,0 +1,98 @@
+// the settings of the player that the engine reads before anything else: the size of the window, fullscreen, vsync,
+// the mouse sensitivity and the controls, in a ron file next to the game. App::new opens the window
+// with them and the options menu (see ui/options_menu.rs) changes them and saves the file again
+// the controls only keep the actions the file has, the rest keep the buttons the game gives them
+//
+//   (
+//       resolution: Some((1600, 900)),
+//       fullscreen: false,
+//       vsync: true,
+//       sensitivity: 1.0,
+//       bindings: {"Dash": ["key:Q", "pad:x"]},
+//   )
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::input::input_state::{InputButton, InputState};
+
+pub const SETTINGS_PATH: &str = "settings.ron";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EngineSettings {
+    pub resolution: Option<(u32, u32)>, // of the window, none is the size of the monitor
+    pub fullscreen: bool,                // borderless on the monitor, at its size
+    pub vsync: bool,
+    pub sensitivity: f32,
+    pub bindings: BTreeMap<String, Vec<String>>, // the buttons by name, see InputButton::name
+}
+
+impl Default for EngineSettings {
+    fn default() -> Self {
+        Self { resolution: Some((1280, 720)), fullscreen: false, vsync: false, sensitivity: 1.0, bindings: BTreeMap::new() }
+    }
+}
+
+impl EngineSettings {
+    pub const SENSITIVITY_RANGE: (f32, f32) = (0.1, 5.0);
+    // what the options menu goes through, the monitor size comes first
+    pub const RESOLUTIONS: [(u32, u32); 6] = [(1280, 720), (1366, 768), (1600, 900), (1920, 1080), (2560, 1440), (3840, 2160)];
+
+    // the defaults when the file doesn't exist yet, a file that doesn't parse is reported and the defaults are used
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        let Ok(text) = fs::read_to_string(path) else { return Self::default() };
+        match ron::from_str::<Self>(&text) {
+            Ok(settings) => settings.clamped(),
+            Err(e) => {
+                eprintln!("{} was not loaded: {}", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        fs::write(path, ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?)?;
+        Ok(())
+    }
+
+    pub fn clamped(mut self) -> Self {
+        self.resolution = self.resolution.map(|(width, height)| (width.max(320), height.max(240)));
+        self.sensitivity = if self.sensitivity.is_finite() { self.sensitivity.clamp(Self::SENSITIVITY_RANGE.0, Self::SENSITIVITY_RANGE.1) } else { 1.0 };
+        self
+    }
+
+    // with vsync the surface waits for the monitor, without it the mode of the display settings (F8) is kept unless
+    // that one waits too
+    pub fn present_mode(&self, display: wgpu::PresentMode) -> wgpu::PresentMode {
+        match (self.vsync, display) {
+            (true, _) => wgpu::PresentMode::AutoVsync,
+            (false, wgpu::PresentMode::AutoVsync | wgpu::PresentMode::Fifo | wgpu::PresentMode::FifoRelaxed) => wgpu::PresentMode::AutoNoVsync,
+            (false, mode) => mode,
+        }
+    }
+
+    // the controls of the file over the ones the input has, the names it doesn't know are skipped
+    pub fn apply_input(&self, input: &mut InputState) {
+        input.sensitivity = self.sensitivity;
+        for (action, names) in &self.bindings {
+            let buttons: Vec<InputButton> = names.iter().filter_map(|name| {
+                let button = InputButton::from_name(name);
+                if button.is_none() {
+                    eprintln!("the button {} of {} is not known", name, action);
+                }
+                button
+            }).collect();
+            input.rebind(action, &buttons);
+        }
+    }
+
+    // every action of the input goes to the file, what the player changed and what they didn't
+    pub fn store_bindings(&mut self, input: &InputState) {
+        self.bindings = input.actions().into_iter().map(|(action, buttons)| (action.to_string(), buttons.iter().map(InputButton::name).collect())).collect();
+    }
+}
@@ -0,0 +1,39 @@
+// turns the variable frame time into a number of fixed steps, physics needs the same dt every step to be stable
+// every simulation (2D physics, 3D physics later) can own one of these or share one to step together
+
+#[derive(Copy, Clone, Debug)]
+pub struct FixedTimestep {
+    pub step: f32,      // seconds per step
+    pub max_steps: u32, // after a hitch we drop time instead of simulating forever (the spiral of death)
+    accumulator: f32,
+}
+
+impl FixedTimestep {
+    pub fn new(steps_per_second: f32) -> Self {
+        Self { step: 1.0 / steps_per_second.max(1.0), max_steps: 8, accumulator: 0.0 }
+    }
+
+    // adds the frame time and says how many steps have to run now
+    pub fn advance(&mut self, delta_time: f32) -> u32 {
+        self.accumulator += delta_time.max(0.0);
+        let mut steps = (self.accumulator / self.step) as u32;
+        if steps > self.max_steps {
+            steps = self.max_steps;
+            self.accumulator = 0.0;
+        } else {
+            self.accumulator -= steps as f32 * self.step;
+        }
+        steps
+    }
+
+    // how far we are between the last step and the next one (0 to 1), to interpolate what is drawn
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.step).clamp(0.0, 1.0)
+    }
+}
+
+impl Default for FixedTimestep {
+    fn default() -> Self {
+        Self::new(60.0)
+    }
+}
@@ -0,0 +1,274 @@
+// the files of the game seen through mounts: the base assets folder at the bottom and folders or zip archives (.zip
+// or .pak) mounted over it with a priority, so a mod replaces a texture, a scene or a script by having a file with
+// the same path, and the originals are never touched. the highest priority that has the file wins, the base is
+// under every mount
+// the loaders keep asking for their paths under the base ("./assets/textures/x.png"), resolve gives the real file
+// of the mount that has it. what comes from an archive is extracted once to the cache, so the loaders that open
+// files themselves (tobj, gltf, sdl2 mixer) work with it too
+// a mod can have a mod.cfg at its root, key=value lines like the other settings: name, priority, description and
+// enabled. without it the mods of a folder go in alphabetical order, the last one on top
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{OnceLock, RwLock};
+use std::time::SystemTime;
+
+use anyhow::{anyhow, bail, Context};
+
+pub const DEFAULT_BASE: &str = "./assets";
+pub const MODS_FOLDER: &str = "./mods";
+const MOD_CONFIG: &str = "mod.cfg";
+const EXTRACT_FOLDER: &str = "./cache/paks";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MountSource {
+    Folder(PathBuf),
+    Archive(PathBuf), // a zip, the extension can be .pak
+}
+
+#[derive(Clone, Debug)]
+pub struct Mount {
+    pub name: String,
+    pub source: MountSource,
+    pub priority: i32,
+    pub enabled: bool,
+    pub description: String,
+    files: BTreeSet<String>, // of an archive, read once when it's mounted
+    modified: Option<SystemTime>,
+}
+
+impl Mount {
+    fn has(&self, relative: &str) -> bool {
+        match &self.source {
+            MountSource::Folder(folder) => folder.join(relative).is_file(),
+            MountSource::Archive(_) => self.files.contains(relative),
+        }
+    }
+
+    // the file on disk, an archive extracts it the first time (and again when the archive changes)
+    fn real_path(&self, relative: &str) -> anyhow::Result<PathBuf> {
+        let archive = match &self.source {
+            MountSource::Folder(folder) => return Ok(folder.join(relative)),
+            MountSource::Archive(archive) => archive,
+        };
+        // the name is checked when it's mounted, the path comes from the archive and could climb out of the cache
+        if !Path::new(relative).components().all(|component| matches!(component, Component::Normal(_))) {
+            bail!("{} of the mod {} goes outside of it", relative, self.name);
+        }
+        let extracted = Path::new(EXTRACT_FOLDER).join(&self.name).join(relative);
+        let fresh = fs::metadata(&extracted).and_then(|metadata| metadata.modified()).ok().zip(self.modified).is_some_and(|(extracted, archive)| extracted >= archive);
+        if !fresh {
+            let bytes = read_from_archive(archive, relative)?;
+            if let Some(parent) = extracted.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&extracted, bytes)?;
+        }
+        Ok(extracted)
+    }
+
+    fn read(&self, relative: &str) -> anyhow::Result<Vec<u8>> {
+        match &self.source {
+            MountSource::Folder(folder) => Ok(fs::read(folder.join(relative))?),
+            MountSource::Archive(archive) => read_from_archive(archive, relative),
+        }
+    }
+
+    fn list(&self, folder: &str) -> Vec<String> {
+        match &self.source {
+            MountSource::Folder(root) => list_folder(root, folder),
+            MountSource::Archive(_) => self.files.iter().filter(|file| is_in(file, folder)).cloned().collect(),
+        }
+    }
+}
+
+pub struct Vfs {
+    base: PathBuf,
+    mounts: Vec<Mount>, // the highest priority first, the last mounted first between equals
+}
+
+impl Vfs {
+    pub fn new(base: impl Into<PathBuf>) -> Self {
+        Self { base: base.into(), mounts: Vec::new() }
+    }
+
+    pub fn mount_folder(&mut self, name: &str, folder: impl Into<PathBuf>, priority: i32) -> anyhow::Result<()> {
+        let folder = folder.into();
+        if !folder.is_dir() {
+            bail!("{} is not a folder", folder.display());
+        }
+        self.add(Mount { name: name.to_string(), source: MountSource::Folder(folder), priority, enabled: true, description: String::new(), files: BTreeSet::new(), modified: None })
+    }
+
+    pub fn mount_archive(&mut self, name: &str, archive: impl Into<PathBuf>, priority: i32) -> anyhow::Result<()> {
+        let archive = archive.into();
+        let zip = zip::ZipArchive::new(fs::File::open(&archive).with_context(|| format!("couldn't open {}", archive.display()))?)
+            .with_context(|| format!("{} is not a zip archive", archive.display()))?;
+        // the folders of the archive are entries too, only the files count
+        let files = zip.file_names().filter(|file| !file.ends_with('/')).map(str::to_string).collect();
+        let modified = fs::metadata(&archive).and_then(|metadata| metadata.modified()).ok();
+        self.add(Mount { name: name.to_string(), source: MountSource::Archive(archive), priority, enabled: true, description: String::new(), files, modified })
+    }
+
+    fn add(&mut self, mount: Mount) -> anyhow::Result<()> {
+        // it's the folder of the extracted files too, so one plain folder name (mod.cfg can set anything)
+        let mut components = Path::new(&mount.name).components();
+        if !matches!((components.next(), components.next()), (Some(Component::Normal(_)), None)) || mount.name.contains(['/', '\\']) {
+            bail!("{:?} can't be the name of a mount, it has to be a plain folder name", mount.name);
+        }
+        if self.mounts.iter().any(|existing| existing.name == mount.name) {
+            bail!("there is already a mount called {}", mount.name);
+        }
+        let index = self.mounts.iter().position(|existing| existing.priority <= mount.priority).unwrap_or(self.mounts.len());
+        self.mounts.insert(index, mount);
+        Ok(())
+    }
+
+    pub fn unmount(&mut self, name: &str) -> bool {
+        let before = self.mounts.len();
+        self.mounts.retain(|mount| mount.name != name);
+        self.mounts.len() != before
+    }
+
+    // the mods whose files are being used
+    pub fn active_mods(&self) -> impl Iterator<Item = &Mount> {
+        self.mounts.iter().filter(|mount| mount.enabled)
+    }
+
+    // mounts every folder, .zip and .pak inside the folder, the ones that fail are reported and skipped
+    pub fn discover_mods(&mut self, folder: impl AsRef<Path>) -> usize {
+        let Ok(entries) = fs::read_dir(folder.as_ref()) else { return 0 };
+        let mut paths: Vec<PathBuf> = entries.filter_map(|entry| entry.ok().map(|entry| entry.path())).collect();
+        paths.sort();
+        let mut mounted = 0;
+        for (index, path) in paths.iter().enumerate() {
+            let extension = path.extension().and_then(|extension| extension.to_str()).map(str::to_lowercase);
+            let is_archive = matches!(extension.as_deref(), Some("zip") | Some("pak"));
+            if !path.is_dir() && !is_archive {
+                continue;
+            }
+            let file_name = path.file_stem().and_then(|name| name.to_str()).unwrap_or("mod").to_string();
+            let result = if is_archive { self.mount_archive(&file_name, path, index as i32 + 1) } else { self.mount_folder(&file_name, path, index as i32 + 1) };
+            match result.and_then(|_| self.apply_mod_config(&file_name)) {
+                Ok(()) => mounted += 1,
+                Err(e) => {
+                    eprintln!("the mod {} was not mounted: {:#}", path.display(), e);
+                    self.unmount(&file_name);
+                }
+            }
+        }
+        mounted
+    }
+
+    // the mod.cfg of the mount, if it has one
+    fn apply_mod_config(&mut self, name: &str) -> anyhow::Result<()> {
+        let index = self.mounts.iter().position(|mount| mount.name == name).ok_or_else(|| anyhow!("there is no mount {}", name))?;
+        if !self.mounts[index].has(MOD_CONFIG) {
+            return Ok(());
+        }
+        let text = String::from_utf8(self.mounts[index].read(MOD_CONFIG)?)?;
+        let mut mount = self.mounts.remove(index);
+        for line in text.lines() {
+            let Some((key, value)) = line.split_once('=') else { continue };
+            let value = value.trim();
+            match key.trim() {
+                "name" if !value.is_empty() => mount.name = value.to_string(),
+                "priority" => mount.priority = value.parse().with_context(|| format!("the priority of {} is not a number", name))?,
+                "description" => mount.description = value.to_string(),
+                "enabled" => mount.enabled = !matches!(value, "0" | "false" | "off"),
+                _ => {}
+            }
+        }
+        self.add(mount)
+    }
+
+    // the path under the base, with / on every system, none for the files that are not assets
+    pub fn virtual_path(&self, path: &Path) -> Option<String> {
+        let relative = path.strip_prefix(&self.base).ok()?;
+        Some(relative.to_string_lossy().replace('\\', "/"))
+    }
+
+    // the mount that has the file, none when it comes from the base
+    pub fn provider(&self, path: &Path) -> Option<&Mount> {
+        let relative = self.virtual_path(path)?;
+        self.active_mods().find(|mount| mount.has(&relative))
+    }
+
+    // the real file for a path under the base, the path itself when no mod has it (or it's not an asset)
+    pub fn resolve(&self, path: &Path) -> PathBuf {
+        let Some(relative) = self.virtual_path(path) else { return path.to_path_buf() };
+        for mount in self.active_mods().filter(|mount| mount.has(&relative)) {
+            match mount.real_path(&relative) {
+                Ok(real) => return real,
+                Err(e) => eprintln!("{} of the mod {} can't be used: {:#}", relative, mount.name, e),
+            }
+        }
+        path.to_path_buf()
+    }
+
+    pub fn read(&self, path: &Path) -> anyhow::Result<Vec<u8>> {
+        if let Some(relative) = self.virtual_path(path) {
+            if let Some(mount) = self.active_mods().find(|mount| mount.has(&relative)) {
+                return mount.read(&relative);
+            }
+        }
+        fs::read(path).with_context(|| format!("couldn't read {}", path.display()))
+    }
+
+    pub fn exists(&self, path: &Path) -> bool {
+        self.provider(path).is_some() || path.is_file()
+    }
+
+    // the files of a folder of the assets in every mount, each once, for the lists of levels or scripts
+    pub fn list(&self, folder: &str) -> Vec<String> {
+        let folder = folder.trim_matches('/');
+        let mut files: BTreeSet<String> = list_folder(&self.base, folder).into_iter().collect();
+        for mount in self.active_mods() {
+            files.extend(mount.list(folder));
+        }
+        files.remove(MOD_CONFIG);
+        files.into_iter().collect()
+    }
+}
+
+// the one the loaders use, the base is the assets folder of the game
+pub fn global() -> &'static RwLock<Vfs> {
+    static VFS: OnceLock<RwLock<Vfs>> = OnceLock::new();
+    VFS.get_or_init(|| RwLock::new(Vfs::new(DEFAULT_BASE)))
+}
+
+// what the loaders call before opening a file
+pub fn resolve(path: impl AsRef<Path>) -> PathBuf {
+    global().read().unwrap().resolve(path.as_ref())
+}
+
+fn read_from_archive(archive: &Path, relative: &str) -> anyhow::Result<Vec<u8>> {
+    let mut zip = zip::ZipArchive::new(fs::File::open(archive)?)?;
+    let mut file = zip.by_name(relative).with_context(|| format!("{} is not in {}", relative, archive.display()))?;
+    let mut bytes = Vec::with_capacity(file.size() as usize);
+    file.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+// the paths from the root of the files inside folder, at any depth
+fn list_folder(root: &Path, folder: &str) -> Vec<String> {
+    let mut files = Vec::new();
+    let mut folders = vec![root.join(folder)];
+    while let Some(current) = folders.pop() {
+        let Ok(entries) = fs::read_dir(&current) else { continue };
+        for path in entries.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+            if path.is_dir() {
+                folders.push(path);
+            } else if let Ok(relative) = path.strip_prefix(root) {
+                files.push(relative.to_string_lossy().replace('\\', "/"));
+            }
+        }
+    }
+    files
+}
+
+fn is_in(file: &str, folder: &str) -> bool {
+    folder.is_empty() || file.strip_prefix(folder).is_some_and(|rest| rest.starts_with('/'))
+}